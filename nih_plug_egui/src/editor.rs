@@ -1,11 +1,11 @@
 //! An [`Editor`] implementation for egui.
 
 use baseview::gl::GlConfig;
-use baseview::{Size, WindowHandle, WindowOpenOptions, WindowScalePolicy};
+use baseview::{PhySize, Size, WindowHandle, WindowOpenOptions, WindowScalePolicy};
 use crossbeam::atomic::AtomicCell;
 use egui_baseview::egui::Context;
 use egui_baseview::EguiWindow;
-use nih_plug::prelude::{Editor, GuiContext, ParamSetter, ParentWindowHandle};
+use nih_plug::prelude::{Editor, GuiContext, ParamSetter, ParentWindowHandle, ResizeHints};
 use parking_lot::RwLock;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use std::sync::atomic::Ordering;
@@ -67,6 +67,7 @@ where
         let build = self.build.clone();
         let update = self.update.clone();
         let state = self.user_state.clone();
+        let egui_state = self.egui_state.clone();
 
         let (unscaled_width, unscaled_height) = self.egui_state.size();
         let scaling_factor = self.scaling_factor.load();
@@ -100,9 +101,19 @@ where
             },
             state,
             move |egui_ctx, _queue, state| build(egui_ctx, &mut state.write()),
-            move |egui_ctx, _queue, state| {
+            move |egui_ctx, queue, state| {
                 let setter = ParamSetter::new(context.as_ref());
 
+                // The host may have resized the plugin window, in which case the embedded window
+                // needs to be resized to match
+                if let Some((new_width, new_height)) = egui_state.requested_size.swap(None) {
+                    let pixels_per_point = egui_ctx.pixels_per_point();
+                    queue.resize(PhySize::new(
+                        (new_width as f32 * pixels_per_point).round() as u32,
+                        (new_height as f32 * pixels_per_point).round() as u32,
+                    ));
+                }
+
                 // For now, just always redraw. Most plugin GUIs have meters, and those almost always
                 // need a redraw. Later we can try to be a bit more sophisticated about this. Without
                 // this we would also have a blank GUI when it gets first opened because most DAWs open
@@ -134,6 +145,24 @@ where
         true
     }
 
+    fn resize_hints(&self) -> Option<ResizeHints> {
+        self.egui_state.resize_hints.load()
+    }
+
+    fn set_size(&self, width: u32, height: u32) -> bool {
+        if self.egui_state.resize_hints.load().is_none() {
+            return false;
+        }
+
+        // The window will be resized during the next frame
+        self.egui_state.size.store((width, height));
+        if self.egui_state.is_open() {
+            self.egui_state.requested_size.store(Some((width, height)));
+        }
+
+        true
+    }
+
     fn param_value_changed(&self, _id: &str, _normalized_value: f32) {
         // As mentioned above, for now we'll always force a redraw to allow meter widgets to work
         // correctly. In the future we can use an `Arc<AtomicBool>` and only force a redraw when
@@ -8,7 +8,7 @@
 use crossbeam::atomic::AtomicCell;
 use egui::Context;
 use nih_plug::params::persist::PersistentField;
-use nih_plug::prelude::{Editor, ParamSetter};
+use nih_plug::prelude::{Editor, ParamSetter, ResizeHints};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -69,6 +69,15 @@ pub struct EguiState {
     /// Whether the editor's window is currently open.
     #[serde(skip)]
     open: AtomicBool,
+
+    /// The constraints for resizing the editor from the host, if the editor is resizable. Set
+    /// through [`EguiState::with_resize_hints()`].
+    #[serde(skip)]
+    resize_hints: AtomicCell<Option<ResizeHints>>,
+    /// A new size set by the host that the editor's window should be resized to during the next
+    /// frame.
+    #[serde(skip)]
+    requested_size: AtomicCell<Option<(u32, u32)>>,
 }
 
 impl<'a> PersistentField<'a, EguiState> for Arc<EguiState> {
@@ -91,9 +100,19 @@ impl EguiState {
         Arc::new(EguiState {
             size: AtomicCell::new((width, height)),
             open: AtomicBool::new(false),
+            resize_hints: AtomicCell::new(None),
+            requested_size: AtomicCell::new(None),
         })
     }
 
+    /// Allow the host to resize the editor within these constraints, for instance when the user
+    /// drags the edge of the plugin window. The editor's layout should adapt to the new size since
+    /// egui does not scale the GUI when the window is resized.
+    pub fn with_resize_hints(self: Arc<Self>, hints: ResizeHints) -> Arc<Self> {
+        self.resize_hints.store(Some(hints));
+        self
+    }
+
     /// Returns a `(width, height)` pair for the current size of the GUI in logical pixels.
     pub fn size(&self) -> (u32, u32) {
         self.size.load()
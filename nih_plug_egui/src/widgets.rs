@@ -1,4 +1,4 @@
-//! Custom egui widgets for displaying parameter values.
+//! Custom egui widgets for displaying parameter values and spectra.
 //!
 //! # Note
 //!
@@ -7,6 +7,8 @@
 
 pub mod generic_ui;
 mod param_slider;
+mod spectrum_analyzer;
 pub mod util;
 
 pub use param_slider::ParamSlider;
+pub use spectrum_analyzer::SpectrumAnalyzer;
@@ -22,7 +22,9 @@ lazy_static! {
 
 /// A slider widget similar to [`egui::widgets::Slider`] that knows about NIH-plug parameters ranges
 /// and can get values for it. The slider supports double click and control click to reset,
-/// shift+drag for granular dragging, text value entry by clicking on the value text.
+/// shift+drag for granular dragging, text value entry by clicking on the value text. Right clicking
+/// opens the host's context menu for the parameter, or resets the parameter if the host doesn't
+/// provide one.
 ///
 /// TODO: Vertical orientation
 /// TODO: Check below for more input methods that should be added
@@ -131,6 +133,20 @@ impl<'a, P: Param> ParamSlider<'a, P> {
             .set_parameter(self.param, self.param.default_plain_value());
     }
 
+    /// Ask the host to show its context menu for the parameter where the slider was clicked.
+    /// Returns `false` if the host cannot show a context menu.
+    fn show_context_menu(&self, ui: &Ui, response: &Response) -> bool {
+        // The host expects physical pixels, while egui works in points
+        let pos = response
+            .interact_pointer_pos()
+            .unwrap_or_else(|| response.rect.left_bottom())
+            .to_vec2()
+            * ui.ctx().pixels_per_point();
+
+        self.setter
+            .show_context_menu(self.param, pos.x.round() as i32, pos.y.round() as i32)
+    }
+
     fn granular_drag(&self, ui: &Ui, drag_delta: Vec2) {
         // Remember the intial position when we started with the granular drag. This value gets
         // reset whenever we have a normal itneraction with the slider.
@@ -185,7 +201,17 @@ impl<'a, P: Param> ParamSlider<'a, P> {
             self.begin_drag();
             Self::set_drag_amount_memory(ui, 0.0);
         }
-        if let Some(click_pos) = response.interact_pointer_pos() {
+        if response.secondary_clicked() {
+            if !self.show_context_menu(ui, response) {
+                self.begin_drag();
+                self.reset_param();
+                self.end_drag();
+                response.mark_changed();
+            }
+        } else if let Some(click_pos) = response
+            .interact_pointer_pos()
+            .filter(|_| !ui.input(|i| i.pointer.secondary_down()))
+        {
             if ui.input(|i| i.modifiers.command) {
                 // Like double clicking, Ctrl+Click should reset the parameter
                 self.reset_param();
@@ -0,0 +1,121 @@
+//! A spectrum analyzer widget for spectra computed using [`nih_plug::util::spectrum`].
+
+use egui_baseview::egui::{self, pos2, vec2, Rect, Response, Sense, Stroke, Ui, Vec2, Widget};
+use nih_plug::prelude::util;
+use nih_plug::util::spectrum::SpectrumOutput;
+
+/// The frequency corresponding to the left edge of the analyzer.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The frequency corresponding to the right edge of the analyzer.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The decibel value corresponding to the bottom of the analyzer.
+const MIN_DB: f32 = -80.0;
+/// The decibel value corresponding to the top of the analyzer.
+const MAX_DB: f32 = 6.0;
+
+/// Draws the most recent spectrum from a [`SpectrumOutput`] on a logarithmic frequency axis. If
+/// peak hold is enabled, the held peaks are drawn as a line on top of the spectrum. Since the
+/// spectrum keeps changing, this widget requests a repaint every time it's drawn.
+///
+/// TODO: There are currently no options for the frequency and decibel ranges
+#[must_use = "You should put this widget in an ui with `ui.add(widget);`"]
+pub struct SpectrumAnalyzer<'a> {
+    spectrum: &'a mut SpectrumOutput,
+
+    size: Option<Vec2>,
+}
+
+impl<'a> SpectrumAnalyzer<'a> {
+    /// Create a new spectrum analyzer for a spectrum output created using
+    /// [`SpectrumInput::new()`][nih_plug::util::spectrum::SpectrumInput::new()]. When the output is
+    /// stored in a mutex, the lock only needs to be held while adding the widget.
+    pub fn new(spectrum: &'a mut SpectrumOutput) -> Self {
+        Self {
+            spectrum,
+
+            size: None,
+        }
+    }
+
+    /// Set a custom size for the spectrum analyzer. By default it fills the available width.
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = Some(size);
+        self
+    }
+}
+
+impl Widget for SpectrumAnalyzer<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let size = self
+            .size
+            .unwrap_or_else(|| vec2(ui.available_width(), ui.spacing().interact_size.y * 8.0));
+        let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+        ui.ctx().request_repaint();
+
+        if !ui.is_rect_visible(rect) {
+            return response;
+        }
+
+        let painter = ui.painter_at(rect);
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, 0.0, visuals.widgets.inactive.bg_fill);
+
+        let spectrum = self.spectrum.read();
+
+        // Every pixel column gets the loudest bin within its frequency range. That way narrow peaks
+        // in the higher frequencies don't disappear in between columns.
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let num_columns = (rect.width() * pixels_per_point).round().max(1.0) as usize;
+        let column_width = rect.width() / num_columns as f32;
+        let column_frequency = |column_idx: usize| {
+            let t = column_idx as f32 / num_columns as f32;
+            MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(t)
+        };
+        let magnitude_to_y = |magnitude: f32| {
+            let height =
+                ((util::gain_to_db(magnitude) - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+            rect.bottom() - (rect.height() * height)
+        };
+
+        let fill_color = visuals.selection.bg_fill.gamma_multiply(0.5);
+        let mut fill = egui::Mesh::default();
+        let mut line = Vec::with_capacity(num_columns);
+        let mut peaks = Vec::with_capacity(if spectrum.peaks.is_empty() {
+            0
+        } else {
+            num_columns
+        });
+        for column_idx in 0..num_columns {
+            let low_frequency = column_frequency(column_idx);
+            let high_frequency = column_frequency(column_idx + 1);
+            let x = rect.left() + (column_idx as f32 * column_width);
+
+            let y = magnitude_to_y(spectrum.magnitude_between(low_frequency, high_frequency));
+            fill.add_colored_rect(
+                Rect::from_min_max(pos2(x, y), pos2(x + column_width, rect.bottom())),
+                fill_color,
+            );
+            line.push(pos2(x + (column_width / 2.0), y));
+
+            if let Some(peak) = spectrum.peak_between(low_frequency, high_frequency) {
+                peaks.push(pos2(x + (column_width / 2.0), magnitude_to_y(peak)));
+            }
+        }
+
+        painter.add(fill);
+        painter.add(egui::Shape::line(
+            line,
+            Stroke::new(1.5, visuals.selection.bg_fill),
+        ));
+        if !peaks.is_empty() {
+            painter.add(egui::Shape::line(
+                peaks,
+                Stroke::new(1.0, visuals.widgets.inactive.fg_stroke.color),
+            ));
+        }
+
+        painter.rect_stroke(rect, 0.0, Stroke::new(1.0, visuals.widgets.active.bg_fill));
+
+        response
+    }
+}
@@ -4,7 +4,7 @@ use baseview::{WindowOpenOptions, WindowScalePolicy};
 use crossbeam::atomic::AtomicCell;
 use crossbeam::channel;
 pub use iced_baseview::*;
-use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle};
+use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle, ResizeHints};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -106,6 +106,8 @@ impl<E: IcedEditor> Editor for IcedEditorWrapper<E> {
                 // We use this wrapper to be able to pass the GUI context to the editor
                 flags: (
                     context,
+                    self.iced_state.clone(),
+                    scaling_factor.unwrap_or(1.0),
                     self.parameter_updates_receiver.clone(),
                     self.initialization_flags.clone(),
                 ),
@@ -134,6 +136,26 @@ impl<E: IcedEditor> Editor for IcedEditorWrapper<E> {
         true
     }
 
+    fn resize_hints(&self) -> Option<ResizeHints> {
+        self.iced_state.resize_hints.load()
+    }
+
+    fn set_size(&self, width: u32, height: u32) -> bool {
+        if self.iced_state.resize_hints.load().is_none() {
+            return false;
+        }
+
+        self.iced_state.size.store((width, height));
+        if self.iced_state.is_open() {
+            // The parameter update message wakes up the application, which will then resize the
+            // window to the requested size
+            self.iced_state.requested_size.store(Some((width, height)));
+            let _ = self.parameter_updates_sender.try_send(ParameterUpdate);
+        }
+
+        true
+    }
+
     fn param_value_changed(&self, _id: &str, _normalized_value: f32) {
         // If there's already a paramter change notification in the channel then we don't need
         // to do anything else. This avoids queueing up redundant GUI redraws.
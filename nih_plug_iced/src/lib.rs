@@ -93,7 +93,7 @@ use baseview::WindowScalePolicy;
 use crossbeam::atomic::AtomicCell;
 use crossbeam::channel;
 use nih_plug::params::persist::PersistentField;
-use nih_plug::prelude::{Editor, GuiContext};
+use nih_plug::prelude::{Editor, GuiContext, ResizeHints};
 use serde::{Deserialize, Serialize};
 // This doesn't need to be re-export but otherwise the compiler complains about
 // `hidden_glob_reexports`
@@ -230,6 +230,17 @@ pub trait IcedEditor: 'static + Send + Sync + Sized {
                 context.raw_set_parameter_normalized(p, v)
             },
             ParamMessage::EndSetParameter(p) => unsafe { context.raw_end_set_parameter(p) },
+            ParamMessage::ShowContextMenu(p, x, y) => unsafe {
+                // The host expects physical pixels, while iced works in logical pixels
+                let scaling_factor = wrapper::scaling_factor();
+                let x = (x * scaling_factor).round() as i32;
+                let y = (y * scaling_factor).round() as i32;
+                if !context.raw_show_param_context_menu(p, x, y) {
+                    context.raw_begin_set_parameter(p);
+                    context.raw_set_parameter_normalized(p, p.default_normalized_value());
+                    context.raw_end_set_parameter(p);
+                }
+            },
         }
     }
 }
@@ -243,6 +254,15 @@ pub struct IcedState {
     /// Whether the editor's window is currently open.
     #[serde(skip)]
     open: AtomicBool,
+
+    /// The constraints for resizing the editor from the host, if the editor is resizable. Set
+    /// through [`IcedState::with_resize_hints()`].
+    #[serde(skip)]
+    resize_hints: AtomicCell<Option<ResizeHints>>,
+    /// A new size set by the host that the editor's window should be resized to during the next
+    /// update.
+    #[serde(skip)]
+    requested_size: AtomicCell<Option<(u32, u32)>>,
 }
 
 impl<'a> PersistentField<'a, IcedState> for Arc<IcedState> {
@@ -265,9 +285,18 @@ impl IcedState {
         Arc::new(IcedState {
             size: AtomicCell::new((width, height)),
             open: AtomicBool::new(false),
+            resize_hints: AtomicCell::new(None),
+            requested_size: AtomicCell::new(None),
         })
     }
 
+    /// Allow the host to resize the editor within these constraints, for instance when the user
+    /// drags the edge of the plugin window. The editor's layout should adapt to the new size.
+    pub fn with_resize_hints(self: Arc<Self>, hints: ResizeHints) -> Arc<Self> {
+        self.resize_hints.store(Some(hints));
+        self
+    }
+
     /// Returns a `(width, height)` pair for the current size of the GUI in logical pixels.
     pub fn size(&self) -> (u32, u32) {
         self.size.load()
@@ -10,10 +10,12 @@ use nih_plug::prelude::ParamPtr;
 pub mod generic_ui;
 pub mod param_slider;
 pub mod peak_meter;
+pub mod spectrum_analyzer;
 pub mod util;
 
 pub use param_slider::ParamSlider;
 pub use peak_meter::PeakMeter;
+pub use spectrum_analyzer::SpectrumAnalyzer;
 
 /// A message to update a parameter value. Since NIH-plug manages the parameters, interacting with
 /// parameter values with iced works a little different from updating any other state. This main
@@ -29,4 +31,8 @@ pub enum ParamMessage {
     SetParameterNormalized(ParamPtr, f32),
     /// End an automation gesture for a parameter.
     EndSetParameter(ParamPtr),
+    /// Ask the host to show its context menu for a parameter at the specified position within the
+    /// editor window, in logical pixels. If the host cannot show a context menu, then the parameter
+    /// is reset to its default value instead.
+    ShowContextMenu(ParamPtr, f32, f32),
 }
@@ -346,6 +346,18 @@ impl<'a, P: Param> Widget<ParamMessage, Renderer> for ParamSlider<'a, P> {
                     return event::Status::Captured;
                 }
             }
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                if bounds.contains(cursor_position) {
+                    // Right clicks bring up the host's context menu for the parameter
+                    shell.publish(ParamMessage::ShowContextMenu(
+                        self.param.as_ptr(),
+                        cursor_position.x,
+                        cursor_position.y,
+                    ));
+
+                    return event::Status::Captured;
+                }
+            }
             Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
             | Event::Touch(touch::Event::FingerLifted { .. } | touch::Event::FingerLost { .. }) => {
                 if self.state.drag_active {
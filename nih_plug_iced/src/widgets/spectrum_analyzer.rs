@@ -0,0 +1,194 @@
+//! A spectrum analyzer widget for spectra computed using [`nih_plug::util::spectrum`].
+
+use nih_plug::prelude::util;
+use nih_plug::util::spectrum::SpectrumOutput;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use crate::backend::Renderer;
+use crate::renderer::Renderer as GraphicsRenderer;
+use crate::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Size, Widget,
+};
+
+/// The thickness of this widget's borders.
+const BORDER_WIDTH: f32 = 1.0;
+/// The thickness of the held peaks line.
+const PEAK_HEIGHT: f32 = 1.0;
+
+/// The frequency corresponding to the left edge of the analyzer.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The frequency corresponding to the right edge of the analyzer.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The decibel value corresponding to the bottom of the analyzer.
+const MIN_DB: f32 = -80.0;
+/// The decibel value corresponding to the top of the analyzer.
+const MAX_DB: f32 = 6.0;
+
+/// Draws the most recent spectrum from a [`SpectrumOutput`] on a logarithmic frequency axis. If
+/// peak hold is enabled, the held peaks are drawn on top of the spectrum. The spectrum is read
+/// when the widget is drawn, so the editor should set
+/// [`WindowSubs::on_frame`][crate::WindowSubs::on_frame] in its subscriptions to redraw it
+/// regularly.
+///
+/// TODO: There are currently no styling options at all
+/// TODO: There are currently no options for the frequency and decibel ranges
+pub struct SpectrumAnalyzer<'a, Message> {
+    spectrum: &'a Mutex<SpectrumOutput>,
+
+    height: Length,
+    width: Length,
+
+    /// We don't emit any messages, but iced requires us to define some message type anyways.
+    _phantom: PhantomData<Message>,
+}
+
+impl<'a, Message> SpectrumAnalyzer<'a, Message> {
+    /// Creates a new [`SpectrumAnalyzer`] for a spectrum output created using
+    /// [`SpectrumInput::new()`][nih_plug::util::spectrum::SpectrumInput::new()].
+    pub fn new(spectrum: &'a Mutex<SpectrumOutput>) -> Self {
+        Self {
+            spectrum,
+
+            width: Length::Units(400),
+            height: Length::Units(200),
+
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets the width of the [`SpectrumAnalyzer`].
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets the height of the [`SpectrumAnalyzer`].
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl<'a, Message> Widget<Message, Renderer> for SpectrumAnalyzer<'a, Message>
+where
+    Message: Clone,
+{
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let plot_bounds = Rectangle {
+            x: bounds.x + BORDER_WIDTH,
+            y: bounds.y + BORDER_WIDTH,
+            width: bounds.width - (BORDER_WIDTH * 2.0),
+            height: bounds.height - (BORDER_WIDTH * 2.0),
+        };
+
+        let mut spectrum = self.spectrum.lock().unwrap();
+        let spectrum = spectrum.read();
+
+        // Every pixel column gets the loudest bin within its frequency range. That way narrow peaks
+        // in the higher frequencies don't disappear in between columns. The columns are drawn as
+        // quads, with one pixel per column.
+        let num_columns = plot_bounds.width.round().max(1.0) as usize;
+        let column_frequency = |column_idx: usize| {
+            let t = column_idx as f32 / num_columns as f32;
+            MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(t)
+        };
+        let magnitude_to_height = |magnitude: f32| {
+            let height =
+                ((util::gain_to_db(magnitude) - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+            (plot_bounds.height * height).round()
+        };
+
+        let spectrum_color = Color {
+            a: style.text_color.a * 0.5,
+            ..style.text_color
+        };
+        for column_idx in 0..num_columns {
+            let low_frequency = column_frequency(column_idx);
+            let high_frequency = column_frequency(column_idx + 1);
+            let x = plot_bounds.x + column_idx as f32;
+
+            let height =
+                magnitude_to_height(spectrum.magnitude_between(low_frequency, high_frequency));
+            if height > 0.0 {
+                renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x,
+                            y: plot_bounds.y + plot_bounds.height - height,
+                            width: 1.0,
+                            height,
+                        },
+                        border_radius: 0.0,
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT,
+                    },
+                    Background::Color(spectrum_color),
+                );
+            }
+
+            if let Some(peak) = spectrum.peak_between(low_frequency, high_frequency) {
+                let peak_height = magnitude_to_height(peak);
+                if peak_height > 0.0 {
+                    renderer.fill_quad(
+                        renderer::Quad {
+                            bounds: Rectangle {
+                                x,
+                                y: plot_bounds.y + plot_bounds.height - peak_height,
+                                width: 1.0,
+                                height: PEAK_HEIGHT,
+                            },
+                            border_radius: 0.0,
+                            border_width: 0.0,
+                            border_color: Color::TRANSPARENT,
+                        },
+                        Background::Color(style.text_color),
+                    );
+                }
+            }
+        }
+
+        // Draw the border last so the spectrum doesn't overlap with it
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border_radius: 0.0,
+                border_width: BORDER_WIDTH,
+                border_color: Color::BLACK,
+            },
+            Background::Color(Color::TRANSPARENT),
+        );
+    }
+}
+
+impl<'a, Message> From<SpectrumAnalyzer<'a, Message>> for Element<'a, Message>
+where
+    Message: 'a + Clone,
+{
+    fn from(widget: SpectrumAnalyzer<'a, Message>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -3,18 +3,37 @@
 
 use crossbeam::channel;
 use nih_plug::prelude::GuiContext;
+use std::cell::Cell;
 use std::sync::Arc;
 
 use crate::futures::FutureExt;
 use crate::{
-    futures, subscription, Application, Color, Command, Element, IcedEditor, ParameterUpdate,
-    Subscription, WindowQueue, WindowScalePolicy, WindowSubs,
+    futures, subscription, Application, Color, Command, Element, IcedEditor, IcedState,
+    ParameterUpdate, Subscription, WindowQueue, WindowScalePolicy, WindowSubs,
 };
 
+thread_local! {
+    /// The scaling factor of the editor whose [`IcedEditor::update()`] function is currently being
+    /// called on this thread. Multiple editor windows may share the same GUI thread, so this is set
+    /// for every call.
+    static SCALING_FACTOR: Cell<f32> = Cell::new(1.0);
+}
+
+/// The scaling factor for the editor that's currently being updated. Used to convert logical
+/// coordinates to physical pixels in [`IcedEditor::handle_param_message()`].
+pub(crate) fn scaling_factor() -> f32 {
+    SCALING_FACTOR.with(Cell::get)
+}
+
 /// Wraps an `iced_baseview` [`Application`] around [`IcedEditor`]. Needed to allow editors to
 /// always receive a copy of the GUI context.
 pub(crate) struct IcedEditorWrapperApplication<E: IcedEditor> {
     editor: E,
+    /// The editor's state. Used to resize the window when the host resizes the editor.
+    iced_state: Arc<IcedState>,
+    /// The scaling factor reported by the host, or 1.0 if the host did not set one. In that case
+    /// the host expects logical coordinates, as is the case on macOS.
+    scaling_factor: f32,
 
     /// We will receive notifications about parameters being changed on here. Whenever a parameter
     /// update gets sent, we will trigger a [`Message::parameterUpdate`] which causes the UI to be
@@ -53,18 +72,22 @@ impl<E: IcedEditor> Application for IcedEditorWrapperApplication<E> {
     type Message = Message<E>;
     type Flags = (
         Arc<dyn GuiContext>,
+        Arc<IcedState>,
+        f32,
         Arc<channel::Receiver<ParameterUpdate>>,
         E::InitializationFlags,
     );
 
     fn new(
-        (context, parameter_updates_receiver, flags): Self::Flags,
+        (context, iced_state, scaling_factor, parameter_updates_receiver, flags): Self::Flags,
     ) -> (Self, Command<Self::Message>) {
         let (editor, command) = E::new(flags, context);
 
         (
             Self {
                 editor,
+                iced_state,
+                scaling_factor,
                 parameter_updates_receiver,
             },
             command.map(Message::EditorMessage),
@@ -78,12 +101,22 @@ impl<E: IcedEditor> Application for IcedEditorWrapperApplication<E> {
         message: Self::Message,
     ) -> Command<Self::Message> {
         match message {
-            Message::EditorMessage(message) => self
-                .editor
-                .update(window, message)
-                .map(Message::EditorMessage),
-            // This message only exists to force a redraw
-            Message::ParameterUpdate => Command::none(),
+            Message::EditorMessage(message) => {
+                let previous_scaling_factor =
+                    SCALING_FACTOR.with(|factor| factor.replace(self.scaling_factor));
+                let command = self.editor.update(window, message);
+                SCALING_FACTOR.with(|factor| factor.set(previous_scaling_factor));
+
+                command.map(Message::EditorMessage)
+            }
+            // This message forces a redraw, and it's also sent when the host has resized the editor
+            Message::ParameterUpdate => {
+                if let Some((new_width, new_height)) = self.iced_state.requested_size.swap(None) {
+                    window.resize_window(baseview::Size::new(new_width as f64, new_height as f64));
+                }
+
+                Command::none()
+            }
         }
     }
 
@@ -3,13 +3,13 @@
 use baseview::{WindowHandle, WindowScalePolicy};
 use crossbeam::atomic::AtomicCell;
 use nih_plug::debug::*;
-use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle};
+use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle, ResizeHints};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use vizia::context::backend::TextConfig;
 use vizia::prelude::*;
 
-use crate::widgets::RawParamEvent;
+use crate::widgets::{HostResizeEvent, RawParamEvent};
 use crate::{assets, widgets, ViziaState, ViziaTheming};
 
 /// An [`Editor`] implementation that calls an vizia draw loop.
@@ -94,7 +94,15 @@ impl Editor for ViziaEditor {
         })
         .on_idle({
             let emit_parameters_changed_event = self.emit_parameters_changed_event.clone();
+            let vizia_state = self.vizia_state.clone();
             move |cx| {
+                if let Some(scale_factor) = vizia_state.requested_scale_factor.swap(None) {
+                    cx.emit_custom(
+                        Event::new(HostResizeEvent::SetUserScaleFactor(scale_factor))
+                            .propagate(Propagation::Subtree),
+                    );
+                }
+
                 if emit_parameters_changed_event
                     .compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed)
                     .is_ok()
@@ -140,6 +148,49 @@ impl Editor for ViziaEditor {
         true
     }
 
+    fn resize_hints(&self) -> Option<ResizeHints> {
+        let (min_scale_factor, max_scale_factor) =
+            self.vizia_state.host_resize_scale_factors.load()?;
+        let (inner_width, inner_height) = self.vizia_state.inner_logical_size();
+        let scale = |factor: f64| {
+            (
+                (inner_width as f64 * factor).round() as u32,
+                (inner_height as f64 * factor).round() as u32,
+            )
+        };
+
+        Some(ResizeHints {
+            min_size: scale(min_scale_factor),
+            max_size: scale(max_scale_factor),
+            aspect_ratio: Some((inner_width, inner_height)),
+            ..Default::default()
+        })
+    }
+
+    fn set_size(&self, width: u32, height: u32) -> bool {
+        let Some((min_scale_factor, max_scale_factor)) =
+            self.vizia_state.host_resize_scale_factors.load()
+        else {
+            return false;
+        };
+
+        // The GUI is scaled uniformly, so the smaller of the two factors is used to make sure the
+        // GUI fits within the window
+        let (inner_width, inner_height) = self.vizia_state.inner_logical_size();
+        let scale_factor = (width as f64 / inner_width.max(1) as f64)
+            .min(height as f64 / inner_height.max(1) as f64)
+            .clamp(min_scale_factor, max_scale_factor);
+
+        self.vizia_state.scale_factor.store(scale_factor);
+        if self.vizia_state.is_open() {
+            self.vizia_state
+                .requested_scale_factor
+                .store(Some(scale_factor));
+        }
+
+        true
+    }
+
     fn param_value_changed(&self, _id: &str, _normalized_value: f32) {
         // This will cause a future idle callback to send a parameters changed event.
         // NOTE: We could add an event containing the parameter's ID and the normalized value, but
@@ -98,6 +98,15 @@ pub struct ViziaState {
     /// Whether the editor's window is currently open.
     #[serde(skip)]
     open: AtomicBool,
+
+    /// The `(min, max)` user scale factors the host can resize the editor to, if the editor can be
+    /// resized by the host. Set through [`ViziaState::with_host_resizing()`].
+    #[serde(skip)]
+    host_resize_scale_factors: AtomicCell<Option<(f64, f64)>>,
+    /// A new user scale factor resulting from the host resizing the editor. This is applied to the
+    /// open window during the next idle callback.
+    #[serde(skip)]
+    requested_scale_factor: AtomicCell<Option<f64>>,
 }
 
 /// A default implementation for `size_fn` needed to be able to derive the `Deserialize` trait.
@@ -140,6 +149,8 @@ impl ViziaState {
             size_fn: Box::new(size_fn),
             scale_factor: AtomicCell::new(1.0),
             open: AtomicBool::new(false),
+            host_resize_scale_factors: AtomicCell::new(None),
+            requested_scale_factor: AtomicCell::new(None),
         })
     }
 
@@ -154,9 +165,28 @@ impl ViziaState {
             size_fn: Box::new(size_fn),
             scale_factor: AtomicCell::new(default_scale_factor),
             open: AtomicBool::new(false),
+            host_resize_scale_factors: AtomicCell::new(None),
+            requested_scale_factor: AtomicCell::new(None),
         })
     }
 
+    /// Allow the host to resize the editor, for instance when the user drags the edge of the plugin
+    /// window. Like with the [`ResizeHandle`][widgets::ResizeHandle], this uniformly scales the GUI
+    /// by changing the user scale factor, which is kept within `min_scale_factor` and
+    /// `max_scale_factor`. The host will preserve the aspect ratio of the size returned by the size
+    /// function.
+    pub fn with_host_resizing(
+        self: Arc<Self>,
+        min_scale_factor: f64,
+        max_scale_factor: f64,
+    ) -> Arc<Self> {
+        self.host_resize_scale_factors.store(Some((
+            min_scale_factor,
+            max_scale_factor.max(min_scale_factor),
+        )));
+        self
+    }
+
     /// Returns a `(width, height)` pair for the current size of the GUI in logical pixels, after
     /// applying the user scale factor.
     pub fn scaled_logical_size(&self) -> (u32, u32) {
@@ -19,6 +19,7 @@ mod param_button;
 mod param_slider;
 mod peak_meter;
 mod resize_handle;
+mod spectrum_analyzer;
 pub mod util;
 
 pub use generic_ui::GenericUi;
@@ -26,6 +27,7 @@ pub use param_button::{ParamButton, ParamButtonExt};
 pub use param_slider::{ParamSlider, ParamSliderExt, ParamSliderStyle};
 pub use peak_meter::PeakMeter;
 pub use resize_handle::ResizeHandle;
+pub use spectrum_analyzer::SpectrumAnalyzer;
 
 /// Register the default theme for the widgets exported by this module. This is automatically called
 /// for you when using [`create_vizia_editor()`][super::create_vizia_editor()].
@@ -53,6 +55,10 @@ pub enum ParamEvent<'a, P: Param> {
     SetParameterNormalized(&'a P, f32),
     /// End an automation gesture for a parameter.
     EndSetParameter(&'a P),
+    /// Ask the host to show its context menu for a parameter at the mouse cursor's position. If
+    /// the host cannot show a context menu, then the parameter is reset to its default value
+    /// instead.
+    ShowContextMenu(&'a P),
 }
 
 /// The same as [`ParamEvent`], but type erased. Use `ParamEvent` as an easier way to construct
@@ -66,6 +72,10 @@ pub enum RawParamEvent {
     SetParameterNormalized(ParamPtr, f32),
     /// End an automation gesture for a parameter.
     EndSetParameter(ParamPtr),
+    /// Ask the host to show its context menu for a parameter at the mouse cursor's position. If
+    /// the host cannot show a context menu, then the parameter is reset to its default value
+    /// instead.
+    ShowContextMenu(ParamPtr),
     /// Sent by the wrapper to indicate that one or more parameter values have changed. Useful when
     /// using properties based on a parameter's value that are computed inside of an event handler.
     ParametersChanged,
@@ -108,6 +118,14 @@ pub enum GuiContextEvent {
     Resize,
 }
 
+/// Sent from the editor's idle callback when the host has resized the editor. The window has
+/// already been resized at this point, so unlike [`GuiContextEvent::Resize`] this should not cause
+/// a new resize request to be sent to the host.
+pub(crate) enum HostResizeEvent {
+    /// Apply a new user scale factor. The [`ViziaState`] already contains this scale factor.
+    SetUserScaleFactor(f64),
+}
+
 /// Handles parameter updates for VIZIA GUIs. Registered in
 /// [`ViziaEditor::spawn()`][super::ViziaEditor::spawn()].
 pub(crate) struct ParamModel {
@@ -127,7 +145,7 @@ pub(crate) struct WindowModel {
 }
 
 impl Model for ParamModel {
-    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         // `ParamEvent` gets downcast into `NormalizedParamEvent` by the `Message`
         // implementation below
         event.map(|param_event, _| match *param_event {
@@ -138,6 +156,19 @@ impl Model for ParamModel {
                 self.context.raw_set_parameter_normalized(p, v)
             },
             RawParamEvent::EndSetParameter(p) => unsafe { self.context.raw_end_set_parameter(p) },
+            RawParamEvent::ShowContextMenu(p) => unsafe {
+                // VIZIA's mouse coordinates are already in physical pixels relative to the window
+                let (x, y) = (cx.mouse().cursorx, cx.mouse().cursory);
+                if !self
+                    .context
+                    .raw_show_param_context_menu(p, x.round() as i32, y.round() as i32)
+                {
+                    self.context.raw_begin_set_parameter(p);
+                    self.context
+                        .raw_set_parameter_normalized(p, p.default_normalized_value());
+                    self.context.raw_end_set_parameter(p);
+                }
+            },
             // This can be used by widgets to be notified when parameter values have changed
             RawParamEvent::ParametersChanged => (),
         });
@@ -157,6 +188,16 @@ impl Model for WindowModel {
             }
         });
 
+        event.map(|host_resize_event, meta| match *host_resize_event {
+            HostResizeEvent::SetUserScaleFactor(scale_factor) => {
+                // Since the `ViziaState`'s scale factor already matches, the resulting geometry
+                // change won't trigger another resize request in the handler below
+                cx.set_user_scale_factor(scale_factor);
+
+                meta.consume();
+            }
+        });
+
         // This gets fired whenever the inner window gets resized
         event.map(|window_event, _| {
             if let WindowEvent::GeometryChanged { .. } = window_event {
@@ -212,6 +253,7 @@ impl<P: Param> From<ParamEvent<'_, P>> for RawParamEvent {
                 RawParamEvent::SetParameterNormalized(p.as_ptr(), v)
             }
             ParamEvent::EndSetParameter(p) => RawParamEvent::EndSetParameter(p.as_ptr()),
+            ParamEvent::ShowContextMenu(p) => RawParamEvent::ShowContextMenu(p.as_ptr()),
         }
     }
 }
@@ -227,6 +227,13 @@ impl ParamWidgetBase {
         cx.emit(RawParamEvent::EndSetParameter(self.param_ptr));
     }
 
+    /// Ask the host to show its context menu for the parameter at the mouse cursor's position.
+    /// This resets the parameter to its default value if the host cannot show a context menu.
+    /// Usually this is done on right click.
+    pub fn show_context_menu(&self, cx: &mut EventContext) {
+        cx.emit(RawParamEvent::ShowContextMenu(self.param_ptr));
+    }
+
     param_ptr_forward!(pub fn name(&self) -> &str);
     param_ptr_forward!(pub fn unit(&self) -> &'static str);
     param_ptr_forward!(pub fn poly_modulation_id(&self) -> Option<u32>);
@@ -93,6 +93,10 @@ impl View for ParamButton {
                 self.toggle_value(cx);
                 meta.consume();
             }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.param_base.show_context_menu(cx);
+                meta.consume();
+            }
             WindowEvent::MouseScroll(_scroll_x, scroll_y) if self.use_scroll_wheel => {
                 // With a regular scroll wheel `scroll_y` will only ever be -1 or 1, but with smooth
                 // scrolling trackpads being a thing `scroll_y` could be anything.
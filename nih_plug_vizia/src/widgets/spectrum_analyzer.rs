@@ -0,0 +1,142 @@
+//! A spectrum analyzer widget for spectra computed using [`nih_plug::util::spectrum`].
+
+use nih_plug::prelude::util;
+use nih_plug::util::spectrum::SpectrumOutput;
+use std::sync::{Arc, Mutex};
+use vizia::prelude::*;
+use vizia::vg;
+
+/// The frequency corresponding to the left edge of the analyzer.
+const MIN_FREQUENCY: f32 = 20.0;
+/// The frequency corresponding to the right edge of the analyzer.
+const MAX_FREQUENCY: f32 = 20_000.0;
+/// The decibel value corresponding to the bottom of the analyzer.
+const MIN_DB: f32 = -80.0;
+/// The decibel value corresponding to the top of the analyzer.
+const MAX_DB: f32 = 6.0;
+
+/// Draws the most recent spectrum from a [`SpectrumOutput`] on a logarithmic frequency axis.
+/// The spectrum is drawn using the font color, and the held peaks are drawn as a fainter line if
+/// peak hold is enabled. The spectrum is read every time the widget is drawn.
+///
+/// TODO: There are currently no options for the frequency and decibel ranges
+pub struct SpectrumAnalyzer {
+    spectrum: Arc<Mutex<SpectrumOutput>>,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates a new [`SpectrumAnalyzer`] for a spectrum output created using
+    /// [`SpectrumInput::new()`][nih_plug::util::spectrum::SpectrumInput::new()].
+    pub fn new<L>(cx: &mut Context, spectrum: L) -> Handle<Self>
+    where
+        L: Lens<Target = Arc<Mutex<SpectrumOutput>>>,
+    {
+        Self {
+            spectrum: spectrum.get(cx),
+        }
+        .build(
+            cx,
+            // This is an otherwise empty element only used for custom drawing
+            |_cx| (),
+        )
+    }
+}
+
+impl View for SpectrumAnalyzer {
+    fn element(&self) -> Option<&'static str> {
+        Some("spectrum-analyzer")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let opacity = cx.opacity();
+        let mut background_color: vg::Color = cx.background_color().into();
+        background_color.set_alphaf(background_color.a * opacity);
+        let mut border_color: vg::Color = cx.border_color().into();
+        border_color.set_alphaf(border_color.a * opacity);
+        let border_width = cx.border_width();
+        let mut line_color: vg::Color = cx.font_color().into();
+        line_color.set_alphaf(line_color.a * opacity);
+
+        let mut outline = vg::Path::new();
+        outline.rect(
+            bounds.x + border_width / 2.0,
+            bounds.y + border_width / 2.0,
+            bounds.w - border_width,
+            bounds.h - border_width,
+        );
+        canvas.fill_path(&outline, &vg::Paint::color(background_color));
+
+        let mut spectrum = self.spectrum.lock().unwrap();
+        let spectrum = spectrum.read();
+
+        // Every pixel column gets the loudest bin within its frequency range. That way narrow peaks
+        // in the higher frequencies don't disappear in between columns.
+        let plot_bounds = bounds.shrink(border_width);
+        let num_columns = plot_bounds.w.round().max(1.0) as usize;
+        let column_frequency = |column_idx: usize| {
+            let t = column_idx as f32 / num_columns as f32;
+            MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(t)
+        };
+        let magnitude_to_y = |magnitude: f32| {
+            let height =
+                ((util::gain_to_db(magnitude) - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0);
+            plot_bounds.bottom() - (plot_bounds.h * height)
+        };
+
+        let mut line = vg::Path::new();
+        let mut fill = vg::Path::new();
+        let mut peaks = vg::Path::new();
+        fill.move_to(plot_bounds.left(), plot_bounds.bottom());
+        for column_idx in 0..num_columns {
+            let low_frequency = column_frequency(column_idx);
+            let high_frequency = column_frequency(column_idx + 1);
+            let x = plot_bounds.left() + column_idx as f32 + 0.5;
+
+            let y = magnitude_to_y(spectrum.magnitude_between(low_frequency, high_frequency));
+            if column_idx == 0 {
+                line.move_to(x, y);
+            } else {
+                line.line_to(x, y);
+            }
+            fill.line_to(x, y);
+
+            if let Some(peak) = spectrum.peak_between(low_frequency, high_frequency) {
+                let y = magnitude_to_y(peak);
+                if column_idx == 0 {
+                    peaks.move_to(x, y);
+                } else {
+                    peaks.line_to(x, y);
+                }
+            }
+        }
+        fill.line_to(plot_bounds.right(), plot_bounds.bottom());
+        fill.close();
+
+        let line_width = cx.scale_factor() * 1.5;
+        let mut fill_color = line_color;
+        fill_color.set_alphaf(line_color.a * 0.25);
+        canvas.fill_path(&fill, &vg::Paint::color(fill_color));
+        canvas.stroke_path(
+            &line,
+            &vg::Paint::color(line_color).with_line_width(line_width),
+        );
+        if !spectrum.peaks.is_empty() {
+            let mut peak_color = line_color;
+            peak_color.set_alphaf(line_color.a * 0.5);
+            canvas.stroke_path(
+                &peaks,
+                &vg::Paint::color(peak_color).with_line_width(line_width / 1.5),
+            );
+        }
+
+        // Draw border last
+        let mut paint = vg::Paint::color(border_color);
+        paint.set_line_width(border_width);
+        canvas.stroke_path(&outline, &paint);
+    }
+}
@@ -39,7 +39,7 @@ struct PackageConfig {
 /// The target we're generating a plugin for. This can be either the native target or a cross
 /// compilation target, so to reduce redundancy when determining the correct bundle paths we'll use
 /// an enum for this.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompilationTarget {
     Linux(Architecture),
     MacOS(Architecture),
@@ -48,7 +48,7 @@ pub enum CompilationTarget {
     Windows(Architecture),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Architecture {
     X86,
     X86_64,
@@ -426,7 +426,19 @@ fn bundle_plugin(
         .with_context(|| format!("Could not parse '{}'", first_lib_path.display()))?;
     let bundle_vst3 = symbols::exported(first_lib_path, "GetPluginFactory")
         .with_context(|| format!("Could not parse '{}'", first_lib_path.display()))?;
-    let bundled_plugin = bundle_clap || bundle_vst2 || bundle_vst3;
+    let exports_lv2 = symbols::exported(first_lib_path, "lv2_descriptor")
+        .with_context(|| format!("Could not parse '{}'", first_lib_path.display()))?;
+    // The Turtle files describing an LV2 plugin are generated by the plugin library itself, so an
+    // LV2 bundle can only be created when the library can be loaded on this machine. A bundle
+    // without those files would not be usable.
+    let bundle_lv2 = exports_lv2 && is_native_target(compilation_target);
+    if exports_lv2 && !bundle_lv2 {
+        eprintln!(
+            "WARNING: Not creating an LV2 bundle because its Turtle files cannot be generated \
+             when cross-compiling"
+        );
+    }
+    let bundled_plugin = bundle_clap || bundle_vst2 || bundle_vst3 || exports_lv2;
 
     if bundle_clap {
         let clap_bundle_library_name = clap_bundle_library_name(&bundle_name, compilation_target);
@@ -511,6 +523,23 @@ fn bundle_plugin(
 
         eprintln!("Created a VST3 bundle at '{}'", vst3_bundle_home.display());
     }
+    if bundle_lv2 {
+        let lv2_lib_path =
+            bundle_home_dir.join(lv2_bundle_library_name(&bundle_name, compilation_target));
+
+        fs::create_dir_all(lv2_lib_path.parent().unwrap())
+            .context("Could not create LV2 bundle directory")?;
+        util::reflink_or_combine(lib_paths, &lv2_lib_path, compilation_target)
+            .context("Could not create LV2 bundle")?;
+
+        let lv2_bundle_home = lv2_lib_path.parent().unwrap();
+        util::write_lv2_turtle_files(&lv2_lib_path)
+            .context("Could not write the LV2 bundle's Turtle files")?;
+        // LV2 bundles are not macOS bundles, so only the library itself is signed
+        maybe_codesign(&lv2_lib_path, compilation_target);
+
+        eprintln!("Created an LV2 bundle at '{}'", lv2_bundle_home.display());
+    }
     if !bundled_plugin {
         eprintln!("Not creating any plugin bundles because the package does not export any plugins")
     }
@@ -727,6 +756,27 @@ fn vst3_bundle_library_name(package: &str, target: CompilationTarget) -> String
     }
 }
 
+/// The full path to the library file inside of an LV2 bundle, including the leading `.lv2`
+/// directory. LV2 bundles have the same structure on every platform.
+fn lv2_bundle_library_name(package: &str, target: CompilationTarget) -> String {
+    match target {
+        CompilationTarget::Linux(_) => format!("{package}.lv2/{package}.so"),
+        CompilationTarget::MacOS(_) | CompilationTarget::MacOSUniversal => {
+            format!("{package}.lv2/{package}.dylib")
+        }
+        CompilationTarget::Windows(_) => format!("{package}.lv2/{package}.dll"),
+    }
+}
+
+/// Whether libraries built for `target` can be loaded on this machine. Universal macOS binaries
+/// also contain a native binary when bundling on macOS.
+fn is_native_target(target: CompilationTarget) -> bool {
+    match target {
+        CompilationTarget::MacOSUniversal => cfg!(target_os = "macos"),
+        target => compilation_target(None).map_or(false, |native| native == target),
+    }
+}
+
 /// If compiling for macOS, create all of the bundl-y stuff Steinberg and Apple require you to have.
 ///
 /// This still requires you to move the dylib file to `{bundle_home}/Contents/macOS/{package}`
@@ -1,5 +1,7 @@
 use anyhow::{Context, Result};
+use std::ffi::CString;
 use std::fs;
+use std::os::raw::c_char;
 use std::path::Path;
 use std::process::Command;
 
@@ -64,3 +66,42 @@ pub fn lipo(inputs: &[&Path], target: &Path) -> Result<()> {
         Ok(())
     }
 }
+
+/// Load an LV2 plugin library exported using `nih_export_lv2!()` and let it write the Turtle files
+/// for the bundle it's in. These files describe the plugin's ports, so they can only be generated
+/// by the plugin itself. The library must be loadable on this machine.
+pub fn write_lv2_turtle_files(lib_path: &Path) -> Result<()> {
+    let bundle_path = lib_path
+        .parent()
+        .context("The library is not inside of a bundle")?;
+    let bundle_path = CString::new(
+        bundle_path
+            .to_str()
+            .context("The bundle path is not valid UTF-8")?,
+    )?;
+    let binary_name = CString::new(
+        lib_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("The library's file name is not valid UTF-8")?,
+    )?;
+
+    // SAFETY: Loading the library runs its initializers, which is unavoidable here. The function's
+    //         signature matches the one generated by `nih_export_lv2!()`.
+    unsafe {
+        let library = libloading::Library::new(lib_path)
+            .with_context(|| format!("Could not load '{}'", lib_path.display()))?;
+        let write_ttl: libloading::Symbol<
+            unsafe extern "C" fn(*const c_char, *const c_char) -> bool,
+        > = library.get(b"nih_plug_lv2_write_ttl\0").context(
+            "The library does not export 'nih_plug_lv2_write_ttl', was it created using \
+             'nih_export_lv2!()'?",
+        )?;
+
+        if !write_ttl(bundle_path.as_ptr(), binary_name.as_ptr()) {
+            anyhow::bail!("The plugin library could not write the Turtle files");
+        }
+    }
+
+    Ok(())
+}
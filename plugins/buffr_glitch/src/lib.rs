@@ -249,22 +249,31 @@ impl Plugin for BuffrGlitch {
                                 let new_voice_id = self.new_voice_id();
                                 self.voices[new_voice_id].note_on(&self.params, note, velocity);
                             }
+                            // A missing note number means that the event applies to all notes
                             NoteEvent::NoteOff { note, .. } => {
                                 for voice in &mut self.voices {
-                                    if voice.midi_note_id == Some(note) {
+                                    if voice.midi_note_id.is_some()
+                                        && (note.is_none() || voice.midi_note_id == note)
+                                    {
                                         // Playback still continues until the release is done.
                                         voice.note_off();
-                                        break;
+                                        if note.is_some() {
+                                            break;
+                                        }
                                     }
                                 }
                             }
                             NoteEvent::PolyVolume { note, gain, .. } => {
                                 for voice in &mut self.voices {
-                                    if voice.midi_note_id == Some(note) {
+                                    if voice.midi_note_id.is_some()
+                                        && (note.is_none() || voice.midi_note_id == note)
+                                    {
                                         voice
                                             .gain_expression_gain
                                             .set_target(self.sample_rate, gain);
-                                        break;
+                                        if note.is_some() {
+                                            break;
+                                        }
                                     }
                                 }
                             }
@@ -15,12 +15,12 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use nih_plug::prelude::*;
+use nih_plug::util::filter;
 use nih_plug_vizia::ViziaState;
 use pcg::Pcg32iState;
 use std::sync::Arc;
 
 mod editor;
-mod filter;
 mod pcg;
 
 /// The number of channels we support. Hardcoded to allow for easier SIMD-ifying in the future.
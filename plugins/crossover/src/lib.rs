@@ -14,20 +14,12 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-#![cfg_attr(feature = "simd", feature(portable_simd))]
-
-#[cfg(not(feature = "simd"))]
-compile_error!("Compiling without SIMD support is currently not supported");
-
-use crossover::fir::{FirCrossover, FirCrossoverType};
-use crossover::iir::{IirCrossover, IirCrossoverType};
 use nih_plug::prelude::*;
+use nih_plug::util::crossover::{FirCrossover, FirCrossoverType, IirCrossover, IirCrossoverType};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-mod crossover;
-
-/// The number of channels this plugin supports. Hard capped at 2 for SIMD reasons.
+/// The number of channels this plugin supports.
 pub const NUM_CHANNELS: u32 = 2;
 
 /// The number of bands. Not used directly here, but this avoids hardcoding some constants in the
@@ -151,8 +143,16 @@ impl Default for Crossover {
                 process_mode: ProcessMode::Realtime,
             },
 
-            iir_crossover: IirCrossover::new(IirCrossoverType::LinkwitzRiley24),
-            fir_crossover: FirCrossover::new(FirCrossoverType::LinkwitzRiley24LinearPhase),
+            iir_crossover: IirCrossover::new(
+                IirCrossoverType::LinkwitzRiley24,
+                NUM_CHANNELS as usize,
+                NUM_BANDS,
+            ),
+            fir_crossover: FirCrossover::new(
+                FirCrossoverType::LinkwitzRiley24LinearPhase,
+                NUM_CHANNELS as usize,
+                NUM_BANDS,
+            ),
             should_update_filters,
         }
     }
@@ -183,6 +183,7 @@ impl Plugin for Crossover {
             aux_inputs: &[],
             aux_outputs: &["Band 1", "Band 2", "Band 3", "Band 4", "Band 5"],
         },
+        channel_layouts: ChannelLayouts::const_default(),
     }];
 
     type SysExMessage = ();
@@ -244,100 +245,53 @@ impl Plugin for Crossover {
 }
 
 impl Crossover {
-    /// Takes care of all of the boilerplate in zipping the outputs together to get a nice iterator
-    /// friendly and SIMD-able interface for the processing function. Prevents having to branch per
-    /// sample. The closure receives an input sample and it should write the output samples for each
-    /// band to the array.
+    /// Split the main input into bands using the IIR crossovers. This is done per sample so the
+    /// filters can be updated while the crossover frequencies are being smoothed.
     fn process_iir(&mut self, buffer: &mut Buffer, aux: &mut AuxiliaryBuffers) {
-        let aux_outputs = &mut aux.outputs;
-        let (band_1_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_2_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_3_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_4_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_5_buffer, _) = aux_outputs.split_first_mut().unwrap();
-
-        // Snoclists for days
-        for (
-            (
-                (
-                    ((main_channel_samples, band_1_channel_samples), band_2_channel_samples),
-                    band_3_channel_samples,
-                ),
-                band_4_channel_samples,
-            ),
-            band_5_channel_samples,
-        ) in buffer
-            .iter_samples()
-            .zip(band_1_buffer.iter_samples())
-            .zip(band_2_buffer.iter_samples())
-            .zip(band_3_buffer.iter_samples())
-            .zip(band_4_buffer.iter_samples())
-            .zip(band_5_buffer.iter_samples())
-        {
-            // We can avoid a lot of hardcoding and conditionals by restoring the original array structure
-            let bands = [
-                band_1_channel_samples,
-                band_2_channel_samples,
-                band_3_channel_samples,
-                band_4_channel_samples,
-                band_5_channel_samples,
-            ];
-
+        for sample_idx in 0..buffer.samples() {
             // Only update the filters when needed
             if self.should_update_filters() {
                 self.update_filters(1);
             }
 
-            self.iir_crossover.process(
-                self.params.num_bands.value() as usize,
-                &main_channel_samples,
-                bands,
-            );
-
-            // The main output should be silent as the signal is already evenly split over the other
-            // bands
-            for sample in main_channel_samples {
-                *sample = 0.0;
+            for (channel_idx, main_channel) in buffer.as_slice().iter_mut().enumerate() {
+                let mut band_samples = [0.0; NUM_BANDS];
+                self.iir_crossover.process_sample(
+                    channel_idx,
+                    main_channel[sample_idx],
+                    &mut band_samples,
+                );
+
+                for (band_buffer, band_sample) in aux
+                    .outputs
+                    .iter_mut()
+                    .zip(band_samples)
+                    .take(self.iir_crossover.num_bands())
+                {
+                    band_buffer.as_slice()[channel_idx][sample_idx] = band_sample;
+                }
+
+                // The main output should be silent as the signal is already evenly split over the
+                // other bands
+                main_channel[sample_idx] = 0.0;
             }
         }
     }
 
-    /// `process_iir()`, but for the linear-phase FIR crossovers. This processes an entire channel
+    /// `process_iir()`, but for the linear-phase FIR crossovers. This processes an entire buffer
     /// at once instead of processing per-sample since we use FFT convolution.
     fn process_fir(&mut self, buffer: &mut Buffer, aux: &mut AuxiliaryBuffers) {
-        // In theory we could do smoothing in between processed blocks, but this hsould be fine
+        // In theory we could do smoothing in between processed blocks, but this should be fine
         if self.should_update_filters() {
             self.update_filters(buffer.samples() as u32);
         }
 
-        let aux_outputs = &mut aux.outputs;
-        let (band_1_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_2_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_3_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_4_buffer, aux_outputs) = aux_outputs.split_first_mut().unwrap();
-        let (band_5_buffer, _) = aux_outputs.split_first_mut().unwrap();
-
-        // We can avoid a lot of hardcoding and conditionals by restoring the original array structure
-        for channel_idx in 0..buffer.channels() {
-            let main_io = &mut buffer.as_slice()[channel_idx];
-            let band_outputs = [
-                &mut band_1_buffer.as_slice()[channel_idx],
-                &mut band_2_buffer.as_slice()[channel_idx],
-                &mut band_3_buffer.as_slice()[channel_idx],
-                &mut band_4_buffer.as_slice()[channel_idx],
-                &mut band_5_buffer.as_slice()[channel_idx],
-            ];
-
-            self.fir_crossover.process(
-                self.params.num_bands.value() as usize,
-                main_io,
-                band_outputs,
-                channel_idx,
-            );
-
-            // The main output should be silent as the signal is already evenly split over the other
-            // bands
-            main_io.fill(0.0);
+        self.fir_crossover.process(buffer, aux.outputs);
+
+        // The main output should be silent as the signal is already evenly split over the other
+        // bands
+        for channel_samples in buffer.as_slice() {
+            channel_samples.fill(0.0);
         }
     }
 
@@ -370,12 +324,12 @@ impl Crossover {
             CrossoverType::LinkwitzRiley24 => self.iir_crossover.update(
                 self.buffer_config.sample_rate,
                 self.params.num_bands.value() as usize,
-                crossover_frequencies,
+                &crossover_frequencies,
             ),
             CrossoverType::LinkwitzRiley24LinearPhase => self.fir_crossover.update(
                 self.buffer_config.sample_rate,
                 self.params.num_bands.value() as usize,
-                crossover_frequencies,
+                &crossover_frequencies,
             ),
         }
     }
@@ -17,6 +17,7 @@
 use atomic_float::AtomicF32;
 use nih_plug::debug::*;
 use nih_plug::prelude::{Editor, Plugin};
+use nih_plug::util::spectrum::SpectrumOutput;
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{assets, create_vizia_editor, ViziaState, ViziaTheming};
@@ -25,7 +26,6 @@ use std::sync::{Arc, Mutex};
 use self::button::SafeModeButton;
 use self::slider::RestrictedParamSlider;
 use crate::params::DiopserParams;
-use crate::spectrum::SpectrumOutput;
 use crate::Diopser;
 
 mod analyzer;
@@ -17,13 +17,18 @@
 use atomic_float::AtomicF32;
 use nih_plug::nih_debug_assert;
 use nih_plug::prelude::FloatRange;
+use nih_plug::util::spectrum::SpectrumOutput;
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::vizia::vg;
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
 use crate::params;
-use crate::spectrum::SpectrumOutput;
+
+/// Keeps the analyzer at the same levels as before it used `nih_plug::util::spectrum`. Diopser's
+/// old spectrum was only divided by the window size, which put a full scale sine wave at a
+/// magnitude of 0.25.
+const SPECTRUM_OFFSET_DB: f32 = -12.041_2;
 
 /// A very abstract spectrum analyzer. This draws the magnitude spectrum's bins as vertical lines
 /// with the same distribution as the filter frequency parameter..
@@ -93,9 +98,9 @@ impl View for SpectrumAnalyzer {
         let line_width = cx.scale_factor() * 1.5;
         let paint = vg::Paint::color(cx.font_color().into()).with_line_width(line_width);
         let mut path = vg::Path::new();
-        for (bin_idx, magnitude) in spectrum.iter().enumerate() {
+        for (bin_idx, magnitude) in spectrum.magnitudes.iter().enumerate() {
             // We'll match up the bin's x-coordinate with the filter frequency parameter
-            let frequency = (bin_idx as f32 / spectrum.len() as f32) * nyquist;
+            let frequency = (bin_idx as f32 / spectrum.num_bins() as f32) * nyquist;
             // NOTE: This takes the safe-mode switch into acocunt. When it is enabled, the range is
             //       zoomed in to match the X-Y pad.
             let t = (self.x_renormalize_display)(self.frequency_range.normalize(frequency));
@@ -104,9 +109,11 @@ impl View for SpectrumAnalyzer {
             }
 
             // Scale this so that 1.0/0 dBFS magnitude is at 80% of the height, the bars begin at
-            // -80 dBFS, and that the scaling is linear
+            // -80 dBFS, and that the scaling is linear. The spectrum is normalized so a full scale
+            // sine wave has a magnitude of 1.0, which is 12 dB higher than what this was designed
+            // for.
             nih_debug_assert!(*magnitude >= 0.0);
-            let magnitude_db = nih_plug::util::gain_to_db(*magnitude);
+            let magnitude_db = nih_plug::util::gain_to_db(*magnitude) + SPECTRUM_OFFSET_DB;
             let height = ((magnitude_db + 80.0) / 100.0).clamp(0.0, 1.0);
 
             path.move_to(
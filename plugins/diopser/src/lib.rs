@@ -22,17 +22,16 @@ compile_error!("Compiling without SIMD support is currently not supported");
 use atomic_float::AtomicF32;
 use editor::SafeModeClamper;
 use nih_plug::prelude::*;
+use nih_plug::util::filter;
+use nih_plug::util::spectrum::{SpectrumConfig, SpectrumInput, SpectrumOutput};
 use std::simd::f32x2;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use crate::params::{DiopserParams, SpreadStyle};
-use crate::spectrum::{SpectrumInput, SpectrumOutput};
 
 mod editor;
-mod filter;
 mod params;
-mod spectrum;
 
 /// The number of channels we support. Hardcoded to simplify the SIMD version.
 const NUM_CHANNELS: u32 = 2;
@@ -79,7 +78,8 @@ impl Default for Diopser {
         let bypass_smoother = Arc::new(Smoother::new(SmoothingStyle::Linear(10.0)));
 
         // We only do stereo right now so this is simple
-        let (spectrum_input, spectrum_output) = SpectrumInput::new(NUM_CHANNELS as usize);
+        let (spectrum_input, spectrum_output) =
+            SpectrumInput::new(NUM_CHANNELS as usize, SpectrumConfig::default());
 
         Self {
             params: Arc::new(DiopserParams::new(
@@ -119,6 +119,17 @@ impl Plugin for Diopser {
 
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
+    const STATE_MIGRATIONS: &'static [StateMigration] = &[StateMigration {
+        versions: "<0.4.0",
+        // Safe-mode is enabled by default, so to avoid changing the behavior we'll keep it disabled
+        // for older presets
+        migrate: |state| {
+            state
+                .fields
+                .insert(String::from("safe-mode"), String::from("false"));
+        },
+    }];
+
     type SysExMessage = ();
     type BackgroundTask = ();
 
@@ -139,19 +150,6 @@ impl Plugin for Diopser {
         )
     }
 
-    fn filter_state(state: &mut PluginState) {
-        // Safe-mode is enabled by default, so to avoid changing the behavior we'll keep it disabled
-        // for older presets
-        if semver::Version::parse(&state.version)
-            .map(|version| version < semver::Version::parse("0.4.0").unwrap())
-            .unwrap_or(true)
-        {
-            state
-                .fields
-                .insert(String::from("safe-mode"), String::from("false"));
-        }
-    }
-
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
@@ -163,7 +161,7 @@ impl Plugin for Diopser {
 
         // The spectrum is smoothed so it decays gradually
         self.spectrum_input
-            .update_sample_rate(buffer_config.sample_rate);
+            .set_sample_rate(buffer_config.sample_rate);
 
         true
     }
@@ -247,7 +245,7 @@ impl Plugin for Diopser {
 
         // Compute a spectrum for the GUI if needed
         if self.params.editor_state.is_open() {
-            self.spectrum_input.compute(buffer);
+            self.spectrum_input.process(buffer);
         }
 
         ProcessStatus::Normal
@@ -360,3 +358,19 @@ impl Vst3Plugin for Diopser {
 
 nih_export_clap!(Diopser);
 nih_export_vst3!(Diopser);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_migration_versions() {
+        for migration in Diopser::STATE_MIGRATIONS {
+            assert!(
+                migration.version_req().is_ok(),
+                "Invalid version requirement \"{}\"",
+                migration.versions
+            );
+        }
+    }
+}
@@ -63,6 +63,7 @@ impl Plugin for Alien {
         aux_input_ports: &[],
         aux_output_ports: &[],
         names: PortNames::const_default(),
+        channel_layouts: ChannelLayouts::const_default(),
     }];
 
     const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
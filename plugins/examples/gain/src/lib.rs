@@ -134,6 +134,9 @@ impl Plugin for Gain {
             // are generated as needed. This layout will be called 'Stereo', while the other one is
             // given the name 'Mono' based no the number of input and output channels.
             names: PortNames::const_default(),
+            // Ports with one or two channels are mono or stereo ports by default. Surround and
+            // ambisonic ports need to have their channel layout set explicitly.
+            channel_layouts: ChannelLayouts::const_default(),
         },
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(1),
@@ -163,6 +166,16 @@ impl Plugin for Gain {
         self.params.clone()
     }
 
+    // The gain is applied to every channel in the same way, so this plugin can use any number of
+    // channels. CLAP and VST3 hosts that want something other than mono or stereo can propose a
+    // different channel count, and the plugin accepts it as long as the input and output match.
+    fn accept_audio_io_layout(&self, audio_io_layout: &AudioIOLayout) -> bool {
+        let num_output_channels = audio_io_layout.main_output_channels.map(NonZeroU32::get);
+
+        audio_io_layout.main_input_channels.map(NonZeroU32::get) == num_output_channels
+            && matches!(num_output_channels, Some(1..=64))
+    }
+
     // This plugin doesn't need any special initialization, but if you need to do anything expensive
     // then this would be the place. State is kept around when the host reconfigures the
     // plugin. If we do need special initialization, we could implement the `initialize()` and/or
@@ -210,5 +223,11 @@ impl Vst3Plugin for Gain {
         &[Vst3SubCategory::Fx, Vst3SubCategory::Tools];
 }
 
+impl Lv2Plugin for Gain {
+    const LV2_URI: &'static str = "https://moist-plugins-gmbh.com/plugins/gain";
+    const LV2_CLASSES: &'static [Lv2PluginClass] = &[Lv2PluginClass::Amplifier];
+}
+
 nih_export_clap!(Gain);
 nih_export_vst3!(Gain);
+nih_export_lv2!(Gain);
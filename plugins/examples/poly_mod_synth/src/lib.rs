@@ -1,3 +1,4 @@
+use nih_plug::midi::voices::{self, VoiceManager, VoiceUpdate};
 use nih_plug::prelude::*;
 use rand::Rng;
 use rand_pcg::Pcg32;
@@ -22,11 +23,10 @@ struct PolyModSynth {
     /// A pseudo-random number generator. This will always be reseeded with the same seed when the
     /// synth is reset. That way the output is deterministic when rendering multiple times.
     prng: Pcg32,
-    /// The synth's voices. Inactive voices will be set to `None` values.
-    voices: [Option<Voice>; NUM_VOICES as usize],
-    /// The next internal voice ID, used only to figure out the oldest voice for voice stealing.
-    /// This is incremented by one each time a voice is created.
-    next_internal_voice_id: u64,
+    /// The synth's voices. The voice manager takes care of voice allocation, voice stealing,
+    /// routing polyphonic modulation to the right voices, and informing the host about terminated
+    /// voices.
+    voices: VoiceManager<Voice>,
 }
 
 #[derive(Params)]
@@ -46,18 +46,6 @@ struct PolyModSynthParams {
 /// struct of arrays instead of having a struct for each voice.
 #[derive(Debug, Clone)]
 struct Voice {
-    /// The identifier for this voice. Polyphonic modulation events are linked to a voice based on
-    /// these IDs. If the host doesn't provide these IDs, then this is computed through
-    /// `compute_fallback_voice_id()`. In that case polyphonic modulation will not work, but the
-    /// basic note events will still have an effect.
-    voice_id: i32,
-    /// The note's channel, in `0..16`. Only used for the voice terminated event.
-    channel: u8,
-    /// The note's key/note, in `0..128`. Only used for the voice terminated event.
-    note: u8,
-    /// The voices internal ID. Each voice has an internal voice ID one higher than the previous
-    /// voice. This is used to steal the last voice in case all 16 voices are in use.
-    internal_voice_id: u64,
     /// The square root of the note's velocity. This is used as a gain multiplier.
     velocity_sqrt: f32,
 
@@ -78,15 +66,23 @@ struct Voice {
     voice_gain: Option<(f32, Smoother<f32>)>,
 }
 
+impl voices::Voice for Voice {
+    fn is_finished(&self) -> bool {
+        self.releasing && self.amp_envelope.previous_value() == 0.0
+    }
+
+    fn amplitude(&self) -> f32 {
+        self.velocity_sqrt * self.amp_envelope.previous_value()
+    }
+}
+
 impl Default for PolyModSynth {
     fn default() -> Self {
         Self {
             params: Arc::new(PolyModSynthParams::default()),
 
             prng: Pcg32::new(420, 1337),
-            // `[None; N]` requires the `Some(T)` to be `Copy`able
-            voices: [0; NUM_VOICES as usize].map(|_| None),
-            next_internal_voice_id: 0,
+            voices: VoiceManager::new(NUM_VOICES as usize),
         }
     }
 }
@@ -165,15 +161,25 @@ impl Plugin for PolyModSynth {
         self.params.clone()
     }
 
-    // If the synth as a variable number of voices, you will need to call
-    // `context.set_current_voice_capacity()` in `initialize()` and in `process()` (when the
-    // capacity changes) to inform the host about this.
+    // If the synth as a variable number of voices, the host needs to be informed about the
+    // current voice capacity. The voice manager does this in `initialize()`, and in
+    // `finish_block()` whenever the polyphony or voice mode changes.
+    fn initialize(
+        &mut self,
+        _audio_io_layout: &AudioIOLayout,
+        _buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.voices.initialize(context);
+
+        true
+    }
+
     fn reset(&mut self) {
         // This ensures the output is at least somewhat deterministic when rendering to audio
         self.prng = Pcg32::new(420, 1337);
 
-        self.voices.fill(None);
-        self.next_internal_voice_id = 0;
+        self.voices.reset();
     }
 
     fn process(
@@ -196,152 +202,114 @@ impl Plugin for PolyModSynth {
         let mut block_end: usize = MAX_BLOCK_SIZE.min(num_samples);
         while block_start < num_samples {
             // First of all, handle all note events that happen at the start of the block, and cut
-            // the block short if another event happens before the end of it. To handle polyphonic
-            // modulation for new notes properly, we'll keep track of the next internal note index
-            // at the block's start. If we receive polyphonic modulation that matches a voice that
-            // has an internal note ID that's great than or equal to this one, then we should start
-            // the note's smoother at the new value instead of fading in from the global value.
-            let this_sample_internal_voice_id_start = self.next_internal_voice_id;
+            // the block short if another event happens before the end of it. The voice manager
+            // creates voices for new notes using the first closure, and passes everything else
+            // that happens to an existing voice to the second closure.
             'events: loop {
                 match next_event {
                     // If the event happens now, then we'll keep processing events
                     Some(event) if (event.timing() as usize) <= block_start => {
-                        // This synth doesn't support any of the polyphonic expression events. A
-                        // real synth plugin however will want to support those.
-                        match event {
-                            NoteEvent::NoteOn {
-                                timing,
-                                voice_id,
-                                channel,
-                                note,
-                                velocity,
-                            } => {
-                                let initial_phase: f32 = self.prng.gen();
+                        let params = &self.params;
+                        let prng = &mut self.prng;
+                        self.voices.handle_event(
+                            context,
+                            &event,
+                            |note| {
                                 // This starts with the attack portion of the amplitude envelope
                                 let amp_envelope = Smoother::new(SmoothingStyle::Exponential(
-                                    self.params.amp_attack_ms.value(),
+                                    params.amp_attack_ms.value(),
                                 ));
                                 amp_envelope.reset(0.0);
                                 amp_envelope.set_target(sample_rate, 1.0);
 
-                                let voice =
-                                    self.start_voice(context, timing, voice_id, channel, note);
-                                voice.velocity_sqrt = velocity.sqrt();
-                                voice.phase = initial_phase;
-                                voice.phase_delta = util::midi_note_to_freq(note) / sample_rate;
-                                voice.amp_envelope = amp_envelope;
-                            }
-                            NoteEvent::NoteOff {
-                                timing: _,
-                                voice_id,
-                                channel,
-                                note,
-                                velocity: _,
-                            } => {
-                                self.start_release_for_voices(sample_rate, voice_id, channel, note)
-                            }
-                            NoteEvent::Choke {
-                                timing,
-                                voice_id,
-                                channel,
-                                note,
-                            } => {
-                                self.choke_voices(context, timing, voice_id, channel, note);
-                            }
-                            NoteEvent::PolyModulation {
-                                timing: _,
-                                voice_id,
-                                poly_modulation_id,
-                                normalized_offset,
-                            } => {
+                                Voice {
+                                    velocity_sqrt: note.velocity.sqrt(),
+
+                                    phase: prng.gen(),
+                                    phase_delta: util::midi_note_to_freq(note.note) / sample_rate,
+                                    releasing: false,
+                                    amp_envelope,
+
+                                    voice_gain: None,
+                                }
+                            },
+                            // This synth doesn't support any of the polyphonic expression events.
+                            // A real synth plugin however will want to support those.
+                            |voice, update| match update {
+                                VoiceUpdate::Release { .. } => {
+                                    voice.releasing = true;
+                                    voice.amp_envelope.style =
+                                        SmoothingStyle::Exponential(params.amp_release_ms.value());
+                                    voice.amp_envelope.set_target(sample_rate, 0.0);
+                                }
                                 // Polyphonic modulation events are matched to voices using the
-                                // voice ID, and to parameters using the poly modulation ID. The
-                                // host will probably send a modulation event every N samples. This
-                                // will happen before the voice is active, and of course also after
-                                // it has been terminated (because the host doesn't know that it
-                                // will be). Because of that, we won't print any assertion failures
-                                // when we can't find the voice index here.
-                                if let Some(voice_idx) = self.get_voice_idx(voice_id) {
-                                    let voice = self.voices[voice_idx].as_mut().unwrap();
-
-                                    match poly_modulation_id {
-                                        GAIN_POLY_MOD_ID => {
-                                            // This should either create a smoother for this
-                                            // modulated parameter or update the existing one.
-                                            // Notice how this uses the parameter's unmodulated
-                                            // normalized value in combination with the normalized
-                                            // offset to create the target plain value
-                                            let target_plain_value = self
-                                                .params
-                                                .gain
-                                                .preview_modulated(normalized_offset);
-                                            let (_, smoother) =
-                                                voice.voice_gain.get_or_insert_with(|| {
-                                                    (
-                                                        normalized_offset,
-                                                        self.params.gain.smoothed.clone(),
-                                                    )
-                                                });
-
-                                            // If this `PolyModulation` events happens on the
-                                            // same sample as a voice's `NoteOn` event, then it
-                                            // should immediately use the modulated value
-                                            // instead of slowly fading in
-                                            if voice.internal_voice_id
-                                                >= this_sample_internal_voice_id_start
-                                            {
-                                                smoother.reset(target_plain_value);
-                                            } else {
-                                                smoother
-                                                    .set_target(sample_rate, target_plain_value);
-                                            }
+                                // voice ID by the voice manager, and to parameters using the poly
+                                // modulation ID.
+                                VoiceUpdate::PolyModulation {
+                                    poly_modulation_id,
+                                    normalized_offset,
+                                    just_started,
+                                } => match poly_modulation_id {
+                                    GAIN_POLY_MOD_ID => {
+                                        // This should either create a smoother for this modulated
+                                        // parameter or update the existing one. Notice how this
+                                        // uses the parameter's unmodulated normalized value in
+                                        // combination with the normalized offset to create the
+                                        // target plain value
+                                        let target_plain_value =
+                                            params.gain.preview_modulated(normalized_offset);
+                                        let (offset, smoother) =
+                                            voice.voice_gain.get_or_insert_with(|| {
+                                                (normalized_offset, params.gain.smoothed.clone())
+                                            });
+                                        *offset = normalized_offset;
+
+                                        // If this `PolyModulation` events happens in the same
+                                        // block as a voice's `NoteOn` event, then it should
+                                        // immediately use the modulated value instead of slowly
+                                        // fading in
+                                        if just_started {
+                                            smoother.reset(target_plain_value);
+                                        } else {
+                                            smoother.set_target(sample_rate, target_plain_value);
                                         }
-                                        n => nih_debug_assert_failure!(
-                                            "Polyphonic modulation sent for unknown poly \
-                                             modulation ID {}",
-                                            n
-                                        ),
                                     }
-                                }
-                            }
-                            NoteEvent::MonoAutomation {
-                                timing: _,
-                                poly_modulation_id,
-                                normalized_value,
-                            } => {
+                                    n => nih_debug_assert_failure!(
+                                        "Polyphonic modulation sent for unknown poly modulation \
+                                         ID {}",
+                                        n
+                                    ),
+                                },
                                 // Modulation always acts as an offset to the parameter's current
                                 // automated value. So if the host sends a new automation value for
                                 // a modulated parameter, the modulated values/smoothing targets
                                 // need to be updated for all polyphonically modulated voices.
-                                for voice in self.voices.iter_mut().filter_map(|v| v.as_mut()) {
-                                    match poly_modulation_id {
-                                        GAIN_POLY_MOD_ID => {
-                                            let (normalized_offset, smoother) =
-                                                match voice.voice_gain.as_mut() {
-                                                    Some((o, s)) => (o, s),
-                                                    // If the voice does not have existing
-                                                    // polyphonic modulation, then there's nothing
-                                                    // to do here. The global automation/monophonic
-                                                    // modulation has already been taken care of by
-                                                    // the framework.
-                                                    None => continue,
-                                                };
-                                            let target_plain_value =
-                                                self.params.gain.preview_plain(
-                                                    normalized_value + *normalized_offset,
-                                                );
+                                VoiceUpdate::MonoAutomation {
+                                    poly_modulation_id,
+                                    normalized_value,
+                                } => match poly_modulation_id {
+                                    GAIN_POLY_MOD_ID => {
+                                        // If the voice does not have existing polyphonic
+                                        // modulation, then there's nothing to do here. The global
+                                        // automation/monophonic modulation has already been taken
+                                        // care of by the framework.
+                                        if let Some((normalized_offset, smoother)) =
+                                            voice.voice_gain.as_mut()
+                                        {
+                                            let target_plain_value = params.gain.preview_plain(
+                                                normalized_value + *normalized_offset,
+                                            );
                                             smoother.set_target(sample_rate, target_plain_value);
                                         }
-                                        n => nih_debug_assert_failure!(
-                                            "Automation event sent for unknown poly modulation ID \
-                                             {}",
-                                            n
-                                        ),
                                     }
-                                }
-                            }
-                            _ => (),
-                        };
+                                    n => nih_debug_assert_failure!(
+                                        "Automation event sent for unknown poly modulation ID {}",
+                                        n
+                                    ),
+                                },
+                                _ => (),
+                            },
+                        );
 
                         next_event = context.next_event();
                     }
@@ -372,7 +340,7 @@ impl Plugin for PolyModSynth {
 
             // TODO: Some form of band limiting
             // TODO: Filter
-            for voice in self.voices.iter_mut().filter_map(|v| v.as_mut()) {
+            for voice in self.voices.voices_mut() {
                 // Depending on whether the voice has polyphonic modulation applied to it,
                 // either the global parameter values are used, or the voice's smoother is used
                 // to generate unique modulated values for that voice
@@ -405,24 +373,10 @@ impl Plugin for PolyModSynth {
                 }
             }
 
-            // Terminate voices whose release period has fully ended. This could be done as part of
-            // the previous loop but this is simpler.
-            for voice in self.voices.iter_mut() {
-                match voice {
-                    Some(v) if v.releasing && v.amp_envelope.previous_value() == 0.0 => {
-                        // This event is very important, as it allows the host to manage its own modulation
-                        // voices
-                        context.send_event(NoteEvent::VoiceTerminated {
-                            timing: block_end as u32,
-                            voice_id: Some(v.voice_id),
-                            channel: v.channel,
-                            note: v.note,
-                        });
-                        *voice = None;
-                    }
-                    _ => (),
-                }
-            }
+            // Terminate voices whose release period has fully ended. This informs the host about
+            // the terminated voices, which is very important as it allows the host to manage its
+            // own modulation voices.
+            self.voices.finish_block(context, block_end as u32);
 
             // And then just keep processing blocks until we've run out of buffer to fill
             block_start = block_end;
@@ -433,161 +387,6 @@ impl Plugin for PolyModSynth {
     }
 }
 
-impl PolyModSynth {
-    /// Get the index of a voice by its voice ID, if the voice exists. This does not immediately
-    /// reutnr a reference to the voice to avoid lifetime issues.
-    fn get_voice_idx(&mut self, voice_id: i32) -> Option<usize> {
-        self.voices
-            .iter_mut()
-            .position(|voice| matches!(voice, Some(voice) if voice.voice_id == voice_id))
-    }
-
-    /// Start a new voice with the given voice ID. If all voices are currently in use, the oldest
-    /// voice will be stolen. Returns a reference to the new voice.
-    fn start_voice(
-        &mut self,
-        context: &mut impl ProcessContext<Self>,
-        sample_offset: u32,
-        voice_id: Option<i32>,
-        channel: u8,
-        note: u8,
-    ) -> &mut Voice {
-        let new_voice = Voice {
-            voice_id: voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)),
-            internal_voice_id: self.next_internal_voice_id,
-            channel,
-            note,
-            velocity_sqrt: 1.0,
-
-            phase: 0.0,
-            phase_delta: 0.0,
-            releasing: false,
-            amp_envelope: Smoother::none(),
-
-            voice_gain: None,
-        };
-        self.next_internal_voice_id = self.next_internal_voice_id.wrapping_add(1);
-
-        // Can't use `.iter_mut().find()` here because nonlexical lifetimes don't apply to return
-        // values
-        match self.voices.iter().position(|voice| voice.is_none()) {
-            Some(free_voice_idx) => {
-                self.voices[free_voice_idx] = Some(new_voice);
-                return self.voices[free_voice_idx].as_mut().unwrap();
-            }
-            None => {
-                // If there is no free voice, find and steal the oldest one
-                // SAFETY: We can skip a lot of checked unwraps here since we already know all voices are in
-                //         use
-                let oldest_voice = unsafe {
-                    self.voices
-                        .iter_mut()
-                        .min_by_key(|voice| voice.as_ref().unwrap_unchecked().internal_voice_id)
-                        .unwrap_unchecked()
-                };
-
-                // The stolen voice needs to be terminated so the host can reuse its modulation
-                // resources
-                {
-                    let oldest_voice = oldest_voice.as_ref().unwrap();
-                    context.send_event(NoteEvent::VoiceTerminated {
-                        timing: sample_offset,
-                        voice_id: Some(oldest_voice.voice_id),
-                        channel: oldest_voice.channel,
-                        note: oldest_voice.note,
-                    });
-                }
-
-                *oldest_voice = Some(new_voice);
-                return oldest_voice.as_mut().unwrap();
-            }
-        }
-    }
-
-    /// Start the release process for one or more voice by changing their amplitude envelope. If
-    /// `voice_id` is not provided, then this will terminate all matching voices.
-    fn start_release_for_voices(
-        &mut self,
-        sample_rate: f32,
-        voice_id: Option<i32>,
-        channel: u8,
-        note: u8,
-    ) {
-        for voice in self.voices.iter_mut() {
-            match voice {
-                Some(Voice {
-                    voice_id: candidate_voice_id,
-                    channel: candidate_channel,
-                    note: candidate_note,
-                    releasing,
-                    amp_envelope,
-                    ..
-                }) if voice_id == Some(*candidate_voice_id)
-                    || (channel == *candidate_channel && note == *candidate_note) =>
-                {
-                    *releasing = true;
-                    amp_envelope.style =
-                        SmoothingStyle::Exponential(self.params.amp_release_ms.value());
-                    amp_envelope.set_target(sample_rate, 0.0);
-
-                    // If this targetted a single voice ID, we're done here. Otherwise there may be
-                    // multiple overlapping voices as we enabled support for that in the
-                    // `PolyModulationConfig`.
-                    if voice_id.is_some() {
-                        return;
-                    }
-                }
-                _ => (),
-            }
-        }
-    }
-
-    /// Immediately terminate one or more voice, removing it from the pool and informing the host
-    /// that the voice has ended. If `voice_id` is not provided, then this will terminate all
-    /// matching voices.
-    fn choke_voices(
-        &mut self,
-        context: &mut impl ProcessContext<Self>,
-        sample_offset: u32,
-        voice_id: Option<i32>,
-        channel: u8,
-        note: u8,
-    ) {
-        for voice in self.voices.iter_mut() {
-            match voice {
-                Some(Voice {
-                    voice_id: candidate_voice_id,
-                    channel: candidate_channel,
-                    note: candidate_note,
-                    ..
-                }) if voice_id == Some(*candidate_voice_id)
-                    || (channel == *candidate_channel && note == *candidate_note) =>
-                {
-                    context.send_event(NoteEvent::VoiceTerminated {
-                        timing: sample_offset,
-                        // Notice how we always send the terminated voice ID here
-                        voice_id: Some(*candidate_voice_id),
-                        channel,
-                        note,
-                    });
-                    *voice = None;
-
-                    if voice_id.is_some() {
-                        return;
-                    }
-                }
-                _ => (),
-            }
-        }
-    }
-}
-
-/// Compute a voice ID in case the host doesn't provide them. Polyphonic modulation will not work in
-/// this case, but playing notes will.
-const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
-    note as i32 | ((channel as i32) << 16)
-}
-
 impl ClapPlugin for PolyModSynth {
     const CLAP_ID: &'static str = "com.moist-plugins-gmbh.poly-mod-synth";
     const CLAP_DESCRIPTION: Option<&'static str> =
@@ -608,6 +407,10 @@ impl ClapPlugin for PolyModSynth {
         max_voice_capacity: NUM_VOICES,
         // This enables voice stacking in Bitwig.
         supports_overlapping_voices: true,
+        // This synth only supports modulating individual voices. Hosts may also target all voices
+        // playing a specific key or channel if those scopes are enabled here.
+        modulation_scope: PolyModulationScope::NOTE_ID,
+        automation_scope: PolyModulationScope::empty(),
     });
 }
 
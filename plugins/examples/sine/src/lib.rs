@@ -172,11 +172,14 @@ impl Plugin for Sine {
                             self.midi_note_freq = util::midi_note_to_freq(note);
                             self.midi_note_gain.set_target(self.sample_rate, velocity);
                         }
-                        NoteEvent::NoteOff { note, .. } if note == self.midi_note_id => {
+                        // The host may also release or change the pressure of all notes at once
+                        NoteEvent::NoteOff { note, .. }
+                            if note.map_or(true, |note| note == self.midi_note_id) =>
+                        {
                             self.midi_note_gain.set_target(self.sample_rate, 0.0);
                         }
                         NoteEvent::PolyPressure { note, pressure, .. }
-                            if note == self.midi_note_id =>
+                            if note.map_or(true, |note| note == self.midi_note_id) =>
                         {
                             self.midi_note_gain.set_target(self.sample_rate, pressure);
                         }
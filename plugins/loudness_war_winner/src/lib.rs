@@ -15,10 +15,9 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use nih_plug::prelude::*;
+use nih_plug::util::filter;
 use std::sync::Arc;
 
-mod filter;
-
 /// The length of silence after which the signal should start fading out into silence. This is to
 /// avoid outputting a constant DC signal.
 const SILENCE_FADEOUT_START_MS: f32 = 1000.0;
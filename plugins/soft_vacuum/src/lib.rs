@@ -19,9 +19,12 @@ use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use nih_plug::prelude::*;
+use nih_plug::util::oversampling::{
+    oversampling_factor_to_times, oversampling_times_to_factor, MultichannelOversampler,
+    Oversampler, OversamplingFilter,
+};
 
 mod hard_vacuum;
-mod oversampling;
 
 /// The maximum number of samples to process at a time. Used to create scratch buffers for the
 /// oversampling.
@@ -42,12 +45,15 @@ struct SoftVacuum {
     /// Stores implementations of the Hard Vacuum algorithm for each channel, since each channel
     /// needs to maintain its own state.
     hard_vacuum_processors: Vec<hard_vacuum::HardVacuum>,
-    /// Oversampling for each channel.
-    oversamplers: Vec<oversampling::Lanczos3Oversampler>,
+    /// Oversampling for the main audio channels.
+    oversampler: MultichannelOversampler,
     /// Oversampling for each channel's slew control signal. This is upsampled separately to make
     /// the oversampled algorithm sound similar to the regular, non oversampled version as the slews
     /// will necessarily be lower in the oversampled version.
-    slew_oversamplers: Vec<oversampling::Lanczos3Oversampler>,
+    slew_oversamplers: Vec<Oversampler>,
+    /// The slew signal for each channel, computed for the entire buffer before oversampling it.
+    /// These contain `max_buffer_size` samples.
+    slews: Vec<Vec<f32>>,
 
     /// Scratch buffers that the smoothed parameters can be rendered to. Allocated on the heap
     /// because Windows uses tiny stack sizes which may eventually cause problems in some hosts.
@@ -210,8 +216,14 @@ impl Default for SoftVacuum {
             params: Arc::new(SoftVacuumParams::default()),
 
             hard_vacuum_processors: Vec::new(),
-            oversamplers: Vec::new(),
+            oversampler: MultichannelOversampler::new(
+                0,
+                MAX_BLOCK_SIZE,
+                MAX_OVERSAMPLING_FACTOR,
+                OversamplingFilter::Lanczos3,
+            ),
             slew_oversamplers: Vec::new(),
+            slews: Vec::new(),
 
             scratch_buffers: Box::default(),
         }
@@ -251,7 +263,7 @@ impl Plugin for SoftVacuum {
     fn initialize(
         &mut self,
         audio_io_layout: &AudioIOLayout,
-        _buffer_config: &BufferConfig,
+        buffer_config: &BufferConfig,
         context: &mut impl InitContext<Self>,
     ) -> bool {
         let num_channels = audio_io_layout
@@ -261,19 +273,29 @@ impl Plugin for SoftVacuum {
 
         self.hard_vacuum_processors
             .resize_with(num_channels, hard_vacuum::HardVacuum::default);
-        self.oversamplers.resize_with(num_channels, || {
-            oversampling::Lanczos3Oversampler::new(MAX_BLOCK_SIZE, MAX_OVERSAMPLING_FACTOR)
-        });
+        self.oversampler = MultichannelOversampler::new(
+            num_channels,
+            MAX_BLOCK_SIZE,
+            MAX_OVERSAMPLING_FACTOR,
+            OversamplingFilter::Lanczos3,
+        );
         self.slew_oversamplers.resize_with(num_channels, || {
-            oversampling::Lanczos3Oversampler::new(MAX_BLOCK_SIZE, MAX_OVERSAMPLING_FACTOR)
+            Oversampler::new(
+                MAX_BLOCK_SIZE,
+                MAX_OVERSAMPLING_FACTOR,
+                OversamplingFilter::Lanczos3,
+            )
         });
-
-        if let Some(oversampler) = self.oversamplers.first() {
-            context.set_latency_samples(
-                oversampler.latency(self.params.oversampling_factor.value() as usize),
-            );
+        self.slews.resize_with(num_channels, Vec::new);
+        for slews in &mut self.slews {
+            slews.resize(buffer_config.max_buffer_size as usize, 0.0);
         }
 
+        context.set_latency_samples(
+            self.oversampler
+                .latency(self.params.oversampling_factor.value() as usize),
+        );
+
         true
     }
 
@@ -282,9 +304,7 @@ impl Plugin for SoftVacuum {
             hard_vacuum.reset();
         }
 
-        for oversampler in &mut self.oversamplers {
-            oversampler.reset();
-        }
+        self.oversampler.reset();
         for oversampler in &mut self.slew_oversamplers {
             oversampler.reset();
         }
@@ -301,68 +321,75 @@ impl Plugin for SoftVacuum {
 
         // If the oversampling factor parameter is changed then the host needs to know about the new
         // latency
-        if let Some(oversampler) = self.oversamplers.first() {
-            context.set_latency_samples(oversampler.latency(oversampling_factor));
+        context.set_latency_samples(self.oversampler.latency(oversampling_factor));
+
+        // The slew signal is computed and oversampled first. This is then used as a control signal
+        // in the oversampled version of the algorithm so it sounds more similar to the
+        // non-oversampled version. Otherwise the slews are necessarily going to be much lower.
+        let num_samples = buffer.samples();
+        for ((channel, slews), hard_vacuum) in buffer
+            .as_slice_immutable()
+            .iter()
+            .zip(self.slews.iter_mut())
+            .zip(self.hard_vacuum_processors.iter_mut())
+        {
+            for (sample, slew) in channel.iter().zip(slews[..num_samples].iter_mut()) {
+                *slew = hard_vacuum.compute_slew(*sample);
+            }
         }
 
-        for (_, block) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
-            let block_len = block.samples();
-            let upsampled_block_len = block_len * oversampling_times;
-
-            // These are the parameters for the distortion algorithm
-            let drive = &mut self.scratch_buffers.drive;
-            self.params
-                .drive
-                .smoothed
-                .next_block(drive, upsampled_block_len);
-            let warmth = &mut self.scratch_buffers.warmth;
-            self.params
-                .warmth
-                .smoothed
-                .next_block(warmth, upsampled_block_len);
-            let aura = &mut self.scratch_buffers.aura;
-            self.params
-                .aura
-                .smoothed
-                .next_block(aura, upsampled_block_len);
-
-            // And the general output mixing
-            let output_gain = &mut self.scratch_buffers.output_gain;
-            self.params
-                .output_gain
-                .smoothed
-                .next_block(output_gain, upsampled_block_len);
-            let dry_wet_ratio = &mut self.scratch_buffers.dry_wet_ratio;
-            self.params
-                .dry_wet_ratio
-                .smoothed
-                .next_block(dry_wet_ratio, upsampled_block_len);
-
-            for (block_channel, ((oversampler, slew_oversampler), hard_vacuum)) in
-                block.into_iter().zip(
-                    self.oversamplers
-                        .iter_mut()
-                        .zip(self.slew_oversamplers.iter_mut())
-                        .zip(self.hard_vacuum_processors.iter_mut()),
-                )
-            {
-                // The slew signal is computed and oversampled first. This is then used as a control
-                // signal in the oversampled version of the algorithm so it sounds more similar to
-                // the non-oversampled version. Otherwise the slews are necessarily going to be much
-                // lower.
-                let mut slews = [0.0f32; MAX_BLOCK_SIZE];
-                for (sample, slew) in block_channel.iter().zip(slews.iter_mut()) {
-                    *slew = hard_vacuum.compute_slew(*sample);
-                }
-
-                let upsampled_slews =
-                    slew_oversampler.upsample_only(&mut slews, oversampling_factor);
-
-                oversampler.process(block_channel, oversampling_factor, |upsampled| {
-                    assert!(upsampled.len() == upsampled_block_len);
+        // The oversampler calls this closure once for every `MAX_BLOCK_SIZE` sample block
+        let mut block_start = 0;
+        self.oversampler
+            .process(buffer, oversampling_factor, |upsampled_block| {
+                let upsampled_block_len = upsampled_block.samples();
+                let block_len = upsampled_block_len / oversampling_times;
+
+                // These are the parameters for the distortion algorithm
+                let drive = &mut self.scratch_buffers.drive;
+                self.params
+                    .drive
+                    .smoothed
+                    .next_block(drive, upsampled_block_len);
+                let warmth = &mut self.scratch_buffers.warmth;
+                self.params
+                    .warmth
+                    .smoothed
+                    .next_block(warmth, upsampled_block_len);
+                let aura = &mut self.scratch_buffers.aura;
+                self.params
+                    .aura
+                    .smoothed
+                    .next_block(aura, upsampled_block_len);
+
+                // And the general output mixing
+                let output_gain = &mut self.scratch_buffers.output_gain;
+                self.params
+                    .output_gain
+                    .smoothed
+                    .next_block(output_gain, upsampled_block_len);
+                let dry_wet_ratio = &mut self.scratch_buffers.dry_wet_ratio;
+                self.params
+                    .dry_wet_ratio
+                    .smoothed
+                    .next_block(dry_wet_ratio, upsampled_block_len);
+
+                for (upsampled, ((slews, slew_oversampler), hard_vacuum)) in
+                    upsampled_block.as_slice().iter_mut().zip(
+                        self.slews
+                            .iter_mut()
+                            .zip(self.slew_oversamplers.iter_mut())
+                            .zip(self.hard_vacuum_processors.iter()),
+                    )
+                {
+                    let upsampled_slews = slew_oversampler.upsample_only(
+                        &mut slews[block_start..block_start + block_len],
+                        oversampling_factor,
+                    );
+                    assert!(upsampled_slews.len() == upsampled_block_len);
 
                     for (sample_idx, (sample, slew)) in
-                        upsampled.iter_mut().zip(upsampled_slews).enumerate()
+                        upsampled.iter_mut().zip(upsampled_slews.iter()).enumerate()
                     {
                         // SAFETY: We already made sure that the blocks are equal in size. We could
                         //         zip iterators instead but with six iterators that's already a bit
@@ -381,23 +408,15 @@ impl Plugin for SoftVacuum {
                         *sample = (distorted * output_gain * dry_wet_ratio)
                             + (*sample * (1.0 - dry_wet_ratio));
                     }
-                });
-            }
-        }
+                }
+
+                block_start += block_len;
+            });
 
         ProcessStatus::Normal
     }
 }
 
-// Used in the conversion for the oversampling amount parameter
-const fn oversampling_factor_to_times(factor: usize) -> usize {
-    2usize.pow(factor as u32)
-}
-
-const fn oversampling_times_to_factor(times: usize) -> usize {
-    times.ilog2() as usize
-}
-
 impl ClapPlugin for SoftVacuum {
     const CLAP_ID: &'static str = "nl.robbertvanderhelm.soft-vacuum";
     const CLAP_DESCRIPTION: Option<&'static str> =
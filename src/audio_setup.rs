@@ -1,8 +1,9 @@
 //! Types and definitions surrounding a plugin's audio IO setup.
 
+use std::marker::PhantomData;
 use std::num::NonZeroU32;
 
-use crate::prelude::Buffer;
+use crate::prelude::{Buffer, Plugin};
 
 /// A description of a plugin's audio IO configuration. The [`Plugin`][crate::prelude::Plugin]
 /// defines a list of supported audio IO configs, with the first one acting as the default layout.
@@ -30,6 +31,10 @@ pub struct AudioIOLayout {
     /// Optional names for the audio ports. Defining these can be useful for plugins with multiple
     /// output and input ports.
     pub names: PortNames,
+    /// Optional channel layouts for the audio ports. Ports with one or two channels default to mono
+    /// and stereo respectively. Surround and ambisonic ports need to have their channel layout set
+    /// here so the host knows how the channels are ordered.
+    pub channel_layouts: ChannelLayouts,
 }
 
 /// Construct a `NonZeroU32` value at compile time. Equivalent to `NonZeroU32::new(n).unwrap()`.
@@ -51,6 +56,23 @@ pub struct AuxiliaryBuffers<'a> {
     pub outputs: &'a mut [Buffer<'a>],
 }
 
+impl AuxiliaryBuffers<'_> {
+    /// Returns whether the host has activated the auxiliary input port with the specified index.
+    /// Hosts may deactivate sidechain inputs when nothing is connected to them. The buffers for
+    /// inactive inputs are still present, but they only contain silence. Returns `false` if the
+    /// port does not exist. See [`Buffer::is_active()`].
+    pub fn is_input_active(&self, port: usize) -> bool {
+        self.inputs.get(port).is_some_and(Buffer::is_active)
+    }
+
+    /// Returns whether the host has activated the auxiliary output port with the specified index.
+    /// Anything written to an inactive output's buffer is discarded. Returns `false` if the port
+    /// does not exist. See [`Buffer::is_active()`].
+    pub fn is_output_active(&self, port: usize) -> bool {
+        self.outputs.get(port).is_some_and(Buffer::is_active)
+    }
+}
+
 /// Contains names for the ports defined in an `AudioIOLayout`. Setting these is optional, but it
 /// makes working with multi-output plugins much more convenient.
 ///
@@ -74,6 +96,97 @@ pub struct PortNames {
     pub aux_outputs: &'static [&'static str],
 }
 
+/// Channel layouts for the ports defined in an `AudioIOLayout`. Setting these is optional. Ports
+/// without an explicit channel layout are treated as mono or stereo ports if they have one or two
+/// channels, and as a collection of discrete channels otherwise.
+///
+/// A port's channel layout needs to have the same number of channels as the port itself. This is
+/// checked at compile time when the plugin gets exported.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelLayouts {
+    /// The channel layout for the main input port.
+    pub main_input: Option<ChannelLayout>,
+    /// The channel layout for the main output port.
+    pub main_output: Option<ChannelLayout>,
+    /// Channel layouts for auxiliary (sidechain) input ports. This slice may contain fewer
+    /// elements than there are auxiliary input ports, but not more.
+    pub aux_inputs: &'static [ChannelLayout],
+    /// Channel layouts for auxiliary output ports. This slice may contain fewer elements than there
+    /// are auxiliary output ports, but not more.
+    pub aux_outputs: &'static [ChannelLayout],
+}
+
+/// Describes how the channels in an audio port are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelLayout {
+    /// A single channel.
+    Mono,
+    /// A left and a right channel, in that order.
+    Stereo,
+    /// A surround layout. Every channel is associated with one of these speakers, in order. The
+    /// speakers must be unique and ordered by their [`Speaker`] discriminants, since this is the
+    /// order VST3 and most hosts use for their channels. See [`ChannelLayout::SURROUND_5_1`] and
+    /// the other constants for predefined layouts.
+    Surround(&'static [Speaker]),
+    /// An ambisonic layout of the specified order. These layouts contain `(order + 1)^2` channels.
+    /// Only orders one through three are supported. VST3 hosts always use ACN channel ordering and
+    /// SN3D normalization (AmbiX), so other configurations are only supported by CLAP.
+    Ambisonic {
+        order: u32,
+        ordering: AmbisonicOrdering,
+        normalization: AmbisonicNormalization,
+    },
+}
+
+/// A speaker position for [`ChannelLayout::Surround`]. The discriminants match the bit positions
+/// used in WAVE, CLAP, and VST3 channel masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Speaker {
+    FrontLeft = 0,
+    FrontRight = 1,
+    FrontCenter = 2,
+    LowFrequency = 3,
+    BackLeft = 4,
+    BackRight = 5,
+    FrontLeftOfCenter = 6,
+    FrontRightOfCenter = 7,
+    BackCenter = 8,
+    SideLeft = 9,
+    SideRight = 10,
+    TopCenter = 11,
+    TopFrontLeft = 12,
+    TopFrontCenter = 13,
+    TopFrontRight = 14,
+    TopBackLeft = 15,
+    TopBackCenter = 16,
+    TopBackRight = 17,
+}
+
+/// The order of the channels in an ambisonic port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmbisonicOrdering {
+    /// Ambisonic Channel Number ordering, used by AmbiX.
+    Acn,
+    /// Furse-Malham ordering.
+    FuMa,
+}
+
+/// The normalization used for an ambisonic port's channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmbisonicNormalization {
+    /// Furse-Malham normalization.
+    MaxN,
+    /// Schmidt semi-normalization, used by AmbiX.
+    Sn3d,
+    /// Full three-dimensional normalization.
+    N3d,
+    /// Schmidt semi-normalization for horizontal-only ambisonics.
+    Sn2d,
+    /// Full normalization for horizontal-only ambisonics.
+    N2d,
+}
+
 /// Configuration for (the host's) audio buffers.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BufferConfig {
@@ -115,7 +228,61 @@ impl AudioIOLayout {
             aux_input_ports: &[],
             aux_output_ports: &[],
             names: PortNames::const_default(),
+            channel_layouts: ChannelLayouts::const_default(),
+        }
+    }
+
+    /// Check whether the channel layouts in [`channel_layouts`][Self::channel_layouts] match the
+    /// layout's ports. The plugin wrappers evaluate this for all of a plugin's audio IO layouts at
+    /// compile time, and compilation fails if this returns an error.
+    pub const fn validate(&self) -> Result<(), &'static str> {
+        if let Err(err) =
+            validate_port_channel_layout(self.main_input_channels, self.channel_layouts.main_input)
+        {
+            return Err(err);
+        }
+        if let Err(err) = validate_port_channel_layout(
+            self.main_output_channels,
+            self.channel_layouts.main_output,
+        ) {
+            return Err(err);
+        }
+
+        if self.channel_layouts.aux_inputs.len() > self.aux_input_ports.len() {
+            return Err(
+                "There are more auxiliary input channel layouts than auxiliary input ports",
+            );
+        }
+        let mut aux_input_idx = 0;
+        while aux_input_idx < self.channel_layouts.aux_inputs.len() {
+            if let Err(err) = validate_port_channel_layout(
+                Some(self.aux_input_ports[aux_input_idx]),
+                Some(self.channel_layouts.aux_inputs[aux_input_idx]),
+            ) {
+                return Err(err);
+            }
+
+            aux_input_idx += 1;
+        }
+
+        if self.channel_layouts.aux_outputs.len() > self.aux_output_ports.len() {
+            return Err(
+                "There are more auxiliary output channel layouts than auxiliary output ports",
+            );
         }
+        let mut aux_output_idx = 0;
+        while aux_output_idx < self.channel_layouts.aux_outputs.len() {
+            if let Err(err) = validate_port_channel_layout(
+                Some(self.aux_output_ports[aux_output_idx]),
+                Some(self.channel_layouts.aux_outputs[aux_output_idx]),
+            ) {
+                return Err(err);
+            }
+
+            aux_output_idx += 1;
+        }
+
+        Ok(())
     }
 
     /// A descriptive name for the layout. This is taken from `PortNames::layout` if set. Otherwise
@@ -150,6 +317,47 @@ impl AudioIOLayout {
         }
     }
 
+    /// The channel layout for the main input port, if it has one. Either taken from the
+    /// `channel_layouts` field or derived from the number of channels. Returns `None` if the port
+    /// does not exist or if its channels are not part of any particular layout.
+    pub fn main_input_channel_layout(&self) -> Option<ChannelLayout> {
+        self.channel_layouts
+            .main_input
+            .or_else(|| default_channel_layout(self.main_input_channels?))
+    }
+
+    /// The channel layout for the main output port, if it has one. See
+    /// [`main_input_channel_layout()`][Self::main_input_channel_layout()].
+    pub fn main_output_channel_layout(&self) -> Option<ChannelLayout> {
+        self.channel_layouts
+            .main_output
+            .or_else(|| default_channel_layout(self.main_output_channels?))
+    }
+
+    /// The channel layout for the auxiliary input port with the given index, if it has one. See
+    /// [`main_input_channel_layout()`][Self::main_input_channel_layout()].
+    pub fn aux_input_channel_layout(&self, idx: usize) -> Option<ChannelLayout> {
+        let num_channels = *self.aux_input_ports.get(idx)?;
+
+        self.channel_layouts
+            .aux_inputs
+            .get(idx)
+            .copied()
+            .or_else(|| default_channel_layout(num_channels))
+    }
+
+    /// The channel layout for the auxiliary output port with the given index, if it has one. See
+    /// [`main_input_channel_layout()`][Self::main_input_channel_layout()].
+    pub fn aux_output_channel_layout(&self, idx: usize) -> Option<ChannelLayout> {
+        let num_channels = *self.aux_output_ports.get(idx)?;
+
+        self.channel_layouts
+            .aux_outputs
+            .get(idx)
+            .copied()
+            .or_else(|| default_channel_layout(num_channels))
+    }
+
     /// The name for the main input port. Either generated or taken from the `names` field.
     pub fn main_input_name(&self) -> String {
         self.names.main_input.unwrap_or("Input").to_owned()
@@ -202,3 +410,266 @@ impl PortNames {
         }
     }
 }
+
+impl ChannelLayouts {
+    /// [`ChannelLayouts::default()`], but as a const function. Used when initializing
+    /// `Plugin::AUDIO_IO_LAYOUTS`. (<https://github.com/rust-lang/rust/issues/67792>)
+    pub const fn const_default() -> Self {
+        Self {
+            main_input: None,
+            main_output: None,
+            aux_inputs: &[],
+            aux_outputs: &[],
+        }
+    }
+}
+
+impl ChannelLayout {
+    /// A 5.1 surround layout.
+    pub const SURROUND_5_1: Self = Self::Surround(&[
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+    ]);
+    /// A 7.1 surround layout.
+    pub const SURROUND_7_1: Self = Self::Surround(&[
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+        Speaker::SideLeft,
+        Speaker::SideRight,
+    ]);
+    /// A 7.1.4 surround layout with four height channels, as used by Dolby Atmos.
+    pub const SURROUND_7_1_4: Self = Self::Surround(&[
+        Speaker::FrontLeft,
+        Speaker::FrontRight,
+        Speaker::FrontCenter,
+        Speaker::LowFrequency,
+        Speaker::BackLeft,
+        Speaker::BackRight,
+        Speaker::SideLeft,
+        Speaker::SideRight,
+        Speaker::TopFrontLeft,
+        Speaker::TopFrontRight,
+        Speaker::TopBackLeft,
+        Speaker::TopBackRight,
+    ]);
+    /// First order ambisonics using ACN ordering and SN3D normalization (AmbiX).
+    pub const AMBISONIC_FIRST_ORDER: Self = Self::ambix(1);
+    /// Second order ambisonics using ACN ordering and SN3D normalization (AmbiX).
+    pub const AMBISONIC_SECOND_ORDER: Self = Self::ambix(2);
+    /// Third order ambisonics using ACN ordering and SN3D normalization (AmbiX).
+    pub const AMBISONIC_THIRD_ORDER: Self = Self::ambix(3);
+
+    /// An ambisonic layout of the specified order using ACN ordering and SN3D normalization.
+    pub const fn ambix(order: u32) -> Self {
+        Self::Ambisonic {
+            order,
+            ordering: AmbisonicOrdering::Acn,
+            normalization: AmbisonicNormalization::Sn3d,
+        }
+    }
+
+    /// The number of channels in this layout.
+    pub const fn num_channels(&self) -> u32 {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround(speakers) => speakers.len() as u32,
+            ChannelLayout::Ambisonic { order, .. } => (*order + 1) * (*order + 1),
+        }
+    }
+
+    /// Check whether this is a valid layout. See [`AudioIOLayout::validate()`].
+    pub const fn validate(&self) -> Result<(), &'static str> {
+        match self {
+            ChannelLayout::Mono | ChannelLayout::Stereo => Ok(()),
+            ChannelLayout::Surround(speakers) => {
+                if speakers.is_empty() {
+                    return Err("Surround channel layouts need at least one speaker");
+                }
+
+                let mut speaker_idx = 1;
+                while speaker_idx < speakers.len() {
+                    if speakers[speaker_idx] as u8 <= speakers[speaker_idx - 1] as u8 {
+                        return Err(
+                            "The speakers in a surround channel layout need to be unique \
+                                    and ordered by their discriminants",
+                        );
+                    }
+
+                    speaker_idx += 1;
+                }
+
+                Ok(())
+            }
+            ChannelLayout::Ambisonic { order, .. } => {
+                if *order >= 1 && *order <= 3 {
+                    Ok(())
+                } else {
+                    Err("Only first, second, and third order ambisonics are supported")
+                }
+            }
+        }
+    }
+
+    /// A bit mask containing the speakers used in a [`ChannelLayout::Surround`] layout. The bit
+    /// positions correspond to the [`Speaker`] discriminants. Returns `None` for other layouts.
+    pub fn speaker_mask(&self) -> Option<u64> {
+        match self {
+            ChannelLayout::Surround(speakers) => Some(
+                speakers
+                    .iter()
+                    .fold(0, |mask, speaker| mask | (1 << *speaker as u8)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Compile time validation for a plugin's audio IO layouts. Referencing
+/// [`ASSERT_VALID`][Self::ASSERT_VALID] causes compilation to fail if any of the layouts are
+/// invalid.
+pub(crate) struct AudioIOLayoutsValidator<P>(PhantomData<P>);
+
+impl<P: Plugin> AudioIOLayoutsValidator<P> {
+    pub const ASSERT_VALID: () = {
+        let mut layout_idx = 0;
+        while layout_idx < P::AUDIO_IO_LAYOUTS.len() {
+            if let Err(err) = P::AUDIO_IO_LAYOUTS[layout_idx].validate() {
+                panic!("{}", err);
+            }
+
+            layout_idx += 1;
+        }
+    };
+}
+
+/// The channel layout implied by a port's channel count.
+fn default_channel_layout(num_channels: NonZeroU32) -> Option<ChannelLayout> {
+    match num_channels.get() {
+        1 => Some(ChannelLayout::Mono),
+        2 => Some(ChannelLayout::Stereo),
+        _ => None,
+    }
+}
+
+const fn validate_port_channel_layout(
+    num_channels: Option<NonZeroU32>,
+    channel_layout: Option<ChannelLayout>,
+) -> Result<(), &'static str> {
+    match (num_channels, channel_layout) {
+        (Some(num_channels), Some(channel_layout)) => {
+            if let Err(err) = channel_layout.validate() {
+                return Err(err);
+            }
+
+            if num_channels.get() == channel_layout.num_channels() {
+                Ok(())
+            } else {
+                Err("A port's channel layout does not match the port's channel count")
+            }
+        }
+        (None, Some(_)) => Err("A channel layout was set for a main port that does not exist"),
+        (_, None) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURROUND_LAYOUT: AudioIOLayout = AudioIOLayout {
+        main_input_channels: NonZeroU32::new(16),
+        main_output_channels: NonZeroU32::new(12),
+        aux_input_ports: &[new_nonzero_u32(2)],
+        channel_layouts: ChannelLayouts {
+            main_input: Some(ChannelLayout::AMBISONIC_THIRD_ORDER),
+            main_output: Some(ChannelLayout::SURROUND_7_1_4),
+            ..ChannelLayouts::const_default()
+        },
+        ..AudioIOLayout::const_default()
+    };
+
+    #[test]
+    fn test_valid_layouts() {
+        assert_eq!(SURROUND_LAYOUT.validate(), Ok(()));
+        assert_eq!(AudioIOLayout::const_default().validate(), Ok(()));
+
+        assert_eq!(
+            SURROUND_LAYOUT.main_input_channel_layout(),
+            Some(ChannelLayout::AMBISONIC_THIRD_ORDER)
+        );
+        assert_eq!(
+            SURROUND_LAYOUT.main_output_channel_layout(),
+            Some(ChannelLayout::SURROUND_7_1_4)
+        );
+        // Ports without an explicit layout fall back to mono and stereo
+        assert_eq!(
+            SURROUND_LAYOUT.aux_input_channel_layout(0),
+            Some(ChannelLayout::Stereo)
+        );
+        assert_eq!(SURROUND_LAYOUT.aux_input_channel_layout(1), None);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        let mismatched_channel_count = AudioIOLayout {
+            main_output_channels: NonZeroU32::new(8),
+            channel_layouts: ChannelLayouts {
+                main_output: Some(ChannelLayout::SURROUND_5_1),
+                ..ChannelLayouts::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        };
+        assert!(mismatched_channel_count.validate().is_err());
+
+        let missing_port = AudioIOLayout {
+            main_output_channels: NonZeroU32::new(2),
+            channel_layouts: ChannelLayouts {
+                main_input: Some(ChannelLayout::Stereo),
+                ..ChannelLayouts::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        };
+        assert!(missing_port.validate().is_err());
+
+        const TOO_MANY_AUX_LAYOUTS: AudioIOLayout = AudioIOLayout {
+            aux_output_ports: &[new_nonzero_u32(2)],
+            channel_layouts: ChannelLayouts {
+                aux_outputs: &[ChannelLayout::Stereo, ChannelLayout::Stereo],
+                ..ChannelLayouts::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        };
+        assert!(TOO_MANY_AUX_LAYOUTS.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_channel_layouts() {
+        assert!(ChannelLayout::Surround(&[]).validate().is_err());
+        assert!(
+            ChannelLayout::Surround(&[Speaker::FrontRight, Speaker::FrontLeft])
+                .validate()
+                .is_err()
+        );
+        assert!(ChannelLayout::ambix(4).validate().is_err());
+    }
+
+    #[test]
+    fn test_speaker_mask() {
+        assert_eq!(ChannelLayout::SURROUND_5_1.speaker_mask(), Some(0b11_1111));
+        assert_eq!(
+            ChannelLayout::SURROUND_7_1.speaker_mask(),
+            Some(0b110_0011_1111)
+        );
+        assert_eq!(ChannelLayout::Stereo.speaker_mask(), None);
+        assert_eq!(ChannelLayout::AMBISONIC_SECOND_ORDER.num_channels(), 9);
+    }
+}
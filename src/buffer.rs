@@ -15,7 +15,6 @@ pub use samples::{ChannelSamples, ChannelSamplesIter, SamplesIter};
 ///
 /// TODO: This lifetime makes zero sense because you're going to need unsafe lifetime casts to use
 ///       this either way. Maybe just get rid of it in favor for raw pointers.
-#[derive(Default)]
 pub struct Buffer<'a> {
     /// The number of samples contained within `output_slices`. This needs to be stored separately
     /// to be able to handle 0 channel IO for MIDI-only plugins.
@@ -29,6 +28,20 @@ pub struct Buffer<'a> {
     /// buffers, and it also cannot be stored in a field next to it because that would mean
     /// containing mutable references to data stored in a mutex.
     output_slices: Vec<&'a mut [f32]>,
+
+    /// Whether the host has activated the port this buffer belongs to. See
+    /// [`is_active()`][Self::is_active()].
+    active: bool,
+}
+
+impl Default for Buffer<'_> {
+    fn default() -> Self {
+        Self {
+            num_samples: 0,
+            output_slices: Vec::new(),
+            active: true,
+        }
+    }
 }
 
 impl<'a> Buffer<'a> {
@@ -50,6 +63,16 @@ impl<'a> Buffer<'a> {
         self.num_samples == 0
     }
 
+    /// Returns whether the host has activated the port this buffer belongs to. The main buffer is
+    /// always active. Auxiliary ports may be deactivated by the host when nothing is connected to
+    /// them, in which case an auxiliary input buffer contains only silence and anything written to
+    /// an auxiliary output buffer is discarded. Plugins can use this to skip unnecessary work, like
+    /// a compressor falling back to its internal detector when its sidechain input is inactive.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
     /// Obtain the raw audio buffers.
     #[inline]
     pub fn as_slice(&mut self) -> &mut [&'a mut [f32]] {
@@ -131,6 +154,13 @@ impl<'a> Buffer<'a> {
             nih_debug_assert_eq!(slice.len(), num_samples);
         }
     }
+
+    /// Mark the port this buffer belongs to as active or inactive. This is used by the plugin
+    /// wrappers to pass the host's port activation state on to the plugin. Buffers are active by
+    /// default.
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
 }
 
 #[cfg(any(miri, test))]
@@ -14,6 +14,7 @@ pub mod remote_controls;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PluginApi {
     Clap,
+    Lv2,
     Standalone,
     Vst3,
 }
@@ -22,8 +23,41 @@ impl Display for PluginApi {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PluginApi::Clap => write!(f, "CLAP"),
+            PluginApi::Lv2 => write!(f, "LV2"),
             PluginApi::Standalone => write!(f, "standalone"),
             PluginApi::Vst3 => write!(f, "VST3"),
         }
     }
 }
+
+/// Information about the track or mixer channel the plugin has been inserted on, as reported by the
+/// host. Hosts may only provide some of these fields, or none at all. See
+/// [`Plugin::track_info_changed()`][crate::prelude::Plugin::track_info_changed()].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrackInfo {
+    /// The track's name.
+    pub name: Option<String>,
+    /// The track's color. Editors can use this to tint themselves to match the track.
+    pub color: Option<TrackColor>,
+    /// The number of audio channels on the track. This is only provided by CLAP hosts.
+    pub channel_count: Option<u32>,
+    /// Whether the plugin has been inserted on a bus or a group track. This is only provided by
+    /// CLAP hosts.
+    pub is_bus: bool,
+    /// Whether the plugin has been inserted on a return or send track. This is only provided by
+    /// CLAP hosts.
+    pub is_return_track: bool,
+    /// Whether the plugin has been inserted on the master track. This is only provided by CLAP
+    /// hosts.
+    pub is_master: bool,
+}
+
+/// A track's color in 8-bit sRGB with an alpha channel. This is also used for the colors in
+/// [`ParamIndication`][crate::prelude::ParamIndication].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
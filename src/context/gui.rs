@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 
-use super::PluginApi;
+use super::{PluginApi, TrackInfo};
 use crate::prelude::{Param, ParamPtr, Plugin, PluginState};
 
 /// Callbacks the plugin can make when the user interacts with its GUI such as updating parameter
@@ -18,12 +18,19 @@ pub trait GuiContext: Send + Sync + 'static {
     /// about screen.
     fn plugin_api(&self) -> PluginApi;
 
+    /// Get information about the track the plugin has been inserted on, if the host provided any.
+    /// Editors can use this to display the track's name or to match the track's color. This may
+    /// change at any time while the editor is open, so this should be queried again when the
+    /// editor redraws. See
+    /// [`Plugin::track_info_changed()`][crate::prelude::Plugin::track_info_changed()].
+    fn track_info(&self) -> Option<TrackInfo>;
+
     /// Ask the host to resize the editor window to the size specified by
     /// [`Editor::size()`][crate::prelude::Editor::size()]. This will return false if the host
     /// somehow didn't like this and rejected the resize, in which case the window should revert to
     /// its old size. You should only actually resize your embedded window once this returns `true`.
-    ///
-    /// TODO: Host->Plugin resizing has not been implemented yet
+    /// See [`Editor::resize_hints()`][crate::prelude::Editor::resize_hints()] for the other
+    /// direction, where the host resizes the editor.
     fn request_resize(&self) -> bool;
 
     /// Inform the host a parameter will be automated. Create a [`ParamSetter`] and use
@@ -54,6 +61,37 @@ pub trait GuiContext: Send + Sync + 'static {
     /// mostly marked as unsafe for API reasons.
     unsafe fn raw_end_set_parameter(&self, param: ParamPtr);
 
+    /// Ask the host to show its context menu for a parameter at the specified position. Create a
+    /// [`ParamSetter`] and use [`ParamSetter::show_context_menu()`] instead for a safe, user
+    /// friendly API.
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_show_param_context_menu(&self, param: ParamPtr, x: i32, y: i32) -> bool;
+
+    /// Get the items the host would add to its context menu for a parameter. Create a
+    /// [`ParamSetter`] and use [`ParamSetter::context_menu_items()`] instead for a safe, user
+    /// friendly API.
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_param_context_menu_items(&self, param: ParamPtr) -> Vec<ContextMenuItem>;
+
+    /// Perform one of the actions from the host's context menu for a parameter. Create a
+    /// [`ParamSetter`] and use [`ParamSetter::perform_context_menu_action()`] instead for a safe,
+    /// user friendly API.
+    ///
+    /// # Safety
+    ///
+    /// The implementing function still needs to check if `param` actually exists. This function is
+    /// mostly marked as unsafe for API reasons.
+    unsafe fn raw_perform_param_context_menu_action(&self, param: ParamPtr, action_id: u32)
+        -> bool;
+
     /// Serialize the plugin's current state to a serde-serializable object. Useful for implementing
     /// preset handling within a plugin's GUI.
     fn get_state(&self) -> PluginState;
@@ -63,6 +101,35 @@ pub trait GuiContext: Send + Sync + 'static {
     /// host. If the plugin is currently processing audio, then the parameter values will be
     /// restored at the end of the current processing cycle.
     fn set_state(&self, state: PluginState);
+
+    /// Inform the host that the names returned by
+    /// [`Plugin::note_name()`][crate::prelude::Plugin::note_name()] have changed.
+    fn note_names_changed(&self);
+}
+
+/// An item from the host's context menu for a parameter. See
+/// [`ParamSetter::context_menu_items()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextMenuItem {
+    /// An entry that can be clicked on. Pass the action ID to
+    /// [`ParamSetter::perform_context_menu_action()`] when the user selects it.
+    Entry {
+        label: String,
+        action_id: u32,
+        is_enabled: bool,
+        /// Whether the entry should be displayed with a check mark.
+        is_checked: bool,
+    },
+    /// A submenu containing more items.
+    Submenu {
+        label: String,
+        is_enabled: bool,
+        items: Vec<ContextMenuItem>,
+    },
+    /// A non-interactive title.
+    Title { label: String },
+    /// A horizontal separator line.
+    Separator,
 }
 
 /// An way to run background tasks from the plugin's GUI, equivalent to the
@@ -180,4 +247,38 @@ impl<'a> ParamSetter<'a> {
     pub fn end_set_parameter<P: Param>(&self, param: &P) {
         unsafe { self.raw_context.raw_end_set_parameter(param.as_ptr()) };
     }
+
+    /// Ask the host to show its context menu for a parameter. Editors should call this when the
+    /// user right clicks on a parameter's widget. Depending on the host, this menu can contain
+    /// options for editing automation, MIDI learn, or modulation. `x` and `y` are the menu's
+    /// position in physical pixels, relative to the top left corner of the editor window. Returns
+    /// `false` if the host cannot show a context menu for the parameter. Use
+    /// [`context_menu_items()`][Self::context_menu_items()] to show the host's items in the
+    /// editor's own menu instead.
+    pub fn show_context_menu<P: Param>(&self, param: &P, x: i32, y: i32) -> bool {
+        unsafe {
+            self.raw_context
+                .raw_show_param_context_menu(param.as_ptr(), x, y)
+        }
+    }
+
+    /// Get the items the host would show in its context menu for a parameter, so they can be
+    /// added to the editor's own context menu. Returns an empty list if the host does not provide
+    /// any items for the parameter.
+    pub fn context_menu_items<P: Param>(&self, param: &P) -> Vec<ContextMenuItem> {
+        unsafe {
+            self.raw_context
+                .raw_param_context_menu_items(param.as_ptr())
+        }
+    }
+
+    /// Perform the action for one of the [`ContextMenuItem::Entry`]s returned by
+    /// [`context_menu_items()`][Self::context_menu_items()]. Returns `false` if the host did not
+    /// perform the action.
+    pub fn perform_context_menu_action<P: Param>(&self, param: &P, action_id: u32) -> bool {
+        unsafe {
+            self.raw_context
+                .raw_perform_param_context_menu_action(param.as_ptr(), action_id)
+        }
+    }
 }
@@ -1,6 +1,6 @@
 //! A context passed during plugin initialization.
 
-use super::PluginApi;
+use super::{PluginApi, TrackInfo};
 use crate::prelude::Plugin;
 
 /// Callbacks the plugin can make while it is being initialized. This is passed to the plugin during
@@ -14,6 +14,10 @@ pub trait InitContext<P: Plugin> {
     /// Get the current plugin API.
     fn plugin_api(&self) -> PluginApi;
 
+    /// Get information about the track the plugin has been inserted on, if the host provided any.
+    /// See [`Plugin::track_info_changed()`][crate::prelude::Plugin::track_info_changed()].
+    fn track_info(&self) -> Option<TrackInfo>;
+
     /// Run a task directly on this thread. This ensures that the task has finished executing before
     /// the plugin finishes initializing.
     ///
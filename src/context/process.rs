@@ -60,7 +60,7 @@ pub trait ProcessContext<P: Plugin> {
     ///
     ///         match event {
     ///             NoteEvent::NoteOn { note, velocity, .. } => { ... },
-    ///             NoteEvent::NoteOff { note, .. } if note == 69 => { ... },
+    ///             NoteEvent::NoteOff { note: Some(69), .. } => { ... },
     ///             NoteEvent::PolyPressure { note, pressure, .. } { ... },
     ///             _ => (),
     ///         }
@@ -92,6 +92,11 @@ pub trait ProcessContext<P: Plugin> {
     /// monophonic modulation when dropping the capacity down to 1.
     fn set_current_voice_capacity(&self, capacity: u32);
 
+    /// Inform the host that the names returned by
+    /// [`Plugin::note_name()`][crate::prelude::Plugin::note_name()] have changed. Like the other
+    /// methods on this context, this is realtime-safe.
+    fn note_names_changed(&self);
+
     // TODO: Add this, this works similar to [GuiContext::set_parameter] but it adds the parameter
     //       change to a queue (or directly to the VST3 plugin's parameter output queues) instead of
     //       using main thread host automation (and all the locks involved there).
@@ -5,9 +5,10 @@ use std::any::Any;
 use std::ffi::c_void;
 use std::sync::Arc;
 
-use crate::prelude::GuiContext;
+use crate::prelude::{GuiContext, TrackColor};
 
 /// An editor for a [`Plugin`][crate::prelude::Plugin].
+#[allow(unused_variables)]
 pub trait Editor: Send {
     /// Create an instance of the plugin's editor and embed it in the parent window. As explained in
     /// [`Plugin::editor()`][crate::prelude::Plugin::editor()], you can then read the parameter
@@ -69,11 +70,191 @@ pub trait Editor: Send {
     /// loaded.
     fn param_values_changed(&self);
 
+    /// Return the editor's resize constraints if the host is allowed to resize the editor, for
+    /// instance by dragging the edge of the plugin window. Returning `None`, the default, indicates
+    /// that the editor has a fixed size. The wrapper may call this at any point, including while
+    /// the editor is closed.
+    fn resize_hints(&self) -> Option<ResizeHints> {
+        None
+    }
+
+    /// Called by the host with a size it would like to resize the editor to, before actually
+    /// resizing the window. This should return the closest size the editor can be resized to, or
+    /// `None` if the editor cannot be resized. Sizes are in logical pixels, just like
+    /// [`size()`][Self::size()]. The default implementation constrains the size using the editor's
+    /// [`resize_hints()`][Self::resize_hints()].
+    fn adjust_size(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        self.resize_hints()
+            .map(|hints| hints.constrain_size(width, height))
+    }
+
+    /// Called after the host has resized the plugin's window to resize the editor to match. The
+    /// size is in logical pixels and has already been passed through
+    /// [`adjust_size()`][Self::adjust_size()]. If this returns `true`, then
+    /// [`size()`][Self::size()] should return the new size from this point onwards. The default
+    /// implementation does not allow resizing and returns `false`.
+    fn set_size(&self, width: u32, height: u32) -> bool {
+        false
+    }
+
+    /// Called when the host starts or stops indicating that a parameter is mapped to a hardware
+    /// controller, or when the parameter's automation state changes. Editors can use this to
+    /// highlight mapped and automated parameters, for instance using the host-provided colors.
+    /// `indication` always contains the parameter's complete current indication. This is
+    /// currently only supported by CLAP hosts. The default implementation does nothing.
+    fn param_indication_changed(&self, id: &str, indication: &ParamIndication) {}
+
     // TODO: Reconsider adding a tick function here for the Linux `IRunLoop`. To keep this platform
     //       and API agnostic, add a way to ask the GuiContext if the wrapper already provides a
     //       tick function. If it does not, then the Editor implementation must handle this by
     //       itself. This would also need an associated `PREFERRED_FRAME_RATE` constant.
-    // TODO: Host->Plugin resizing
+}
+
+/// How the host is currently using a parameter. Passed to [`Editor::param_indication_changed()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParamIndication {
+    /// Set when the host has mapped the parameter to a hardware controller.
+    pub mapping: Option<ParamMapping>,
+    /// The parameter's automation state.
+    pub automation: AutomationState,
+    /// The color the host uses to display the parameter's automation, if it has one.
+    pub automation_color: Option<TrackColor>,
+}
+
+/// A hardware controller mapping for a parameter. See [`ParamIndication::mapping`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParamMapping {
+    /// The color the host uses for the controller this parameter is mapped to, if any.
+    pub color: Option<TrackColor>,
+    /// A short label for the mapping, like `MIDI CC 7`.
+    pub label: Option<String>,
+    /// A longer description of the mapping that can be displayed in a tooltip.
+    pub description: Option<String>,
+}
+
+/// A parameter's automation state. See [`ParamIndication::automation`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AutomationState {
+    /// The host does not have any automation for this parameter.
+    #[default]
+    None,
+    /// The host has automation for this parameter, but it isn't being played back.
+    Present,
+    /// The host is playing back automation for this parameter.
+    Playing,
+    /// The host is recording automation for this parameter.
+    Recording,
+    /// The host is playing back automation for this parameter, but the automation is currently
+    /// being overridden by the user.
+    Overriding,
+}
+
+/// Constraints for editors that can be resized by the host. Returned from
+/// [`Editor::resize_hints()`]. All sizes are in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResizeHints {
+    /// The smallest allowed `(width, height)`.
+    pub min_size: (u32, u32),
+    /// The largest allowed `(width, height)`. Use `u32::MAX` for unbounded dimensions.
+    pub max_size: (u32, u32),
+    /// The editor's size will always be `min_size` plus an integer multiple of this step size.
+    /// Use `(1, 1)` to allow any size.
+    pub step_size: (u32, u32),
+    /// If set, the editor's size should always keep this `(width, height)` aspect ratio.
+    pub aspect_ratio: Option<(u32, u32)>,
+}
+
+impl Default for ResizeHints {
+    fn default() -> Self {
+        Self {
+            min_size: (1, 1),
+            max_size: (u32::MAX, u32::MAX),
+            step_size: (1, 1),
+            aspect_ratio: None,
+        }
+    }
+}
+
+impl ResizeHints {
+    /// Whether these constraints allow the editor's width to change.
+    pub fn can_resize_horizontally(&self) -> bool {
+        self.min_size.0 < self.max_size.0
+    }
+
+    /// Whether these constraints allow the editor's height to change.
+    pub fn can_resize_vertically(&self) -> bool {
+        self.min_size.1 < self.max_size.1
+    }
+
+    /// Constrain a proposed size so it fits these hints. The size is first clamped to the minimum
+    /// and maximum sizes. If an aspect ratio is set, then the largest size with that ratio that
+    /// fits within the clamped size and the minimum and maximum sizes is used. Finally the size is
+    /// snapped down to the step size without leaving those bounds. When both an aspect ratio and a
+    /// step size are set, only the width is snapped and the height is derived from the width so
+    /// the aspect ratio is always kept.
+    pub fn constrain_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let (min_width, min_height) = self.min_size;
+        let (max_width, max_height) = (
+            self.max_size.0.max(min_width),
+            self.max_size.1.max(min_height),
+        );
+        let width = width.clamp(min_width, max_width);
+        let height = height.clamp(min_height, max_height);
+
+        // Snaps `value` down to `min` plus a multiple of `step`, or up if that would go below `lower`.
+        // The value is left as is if neither fits within `lower..=upper`.
+        let snap = |value: u32, min: u32, step: u32, lower: u32, upper: u32| {
+            let step = step.max(1) as u64;
+            let (value, min, lower, upper) = (value as u64, min as u64, lower as u64, upper as u64);
+            let mut snapped = min + ((value - min) / step) * step;
+            if snapped < lower {
+                snapped += ((lower - snapped) + step - 1) / step * step;
+            }
+
+            if (lower..=upper).contains(&snapped) {
+                snapped as u32
+            } else {
+                value as u32
+            }
+        };
+
+        match self.aspect_ratio {
+            Some((ratio_width, ratio_height)) => {
+                let (ratio_width, ratio_height) =
+                    (ratio_width.max(1) as u64, ratio_height.max(1) as u64);
+                let height_for_width = |width: u64| width * ratio_height / ratio_width;
+
+                // These are the widths that have a height within the minimum and maximum heights
+                let lowest_width = (min_width as u64)
+                    .max((min_height as u64 * ratio_width + ratio_height - 1) / ratio_height);
+                let highest_width = (max_width as u64)
+                    .min(max_height as u64 * ratio_width / ratio_height)
+                    .max(lowest_width);
+
+                let fitting_width = (width as u64).min(height as u64 * ratio_width / ratio_height);
+                let width = fitting_width.clamp(lowest_width, highest_width);
+                let width = snap(
+                    width.min(u32::MAX as u64) as u32,
+                    min_width,
+                    self.step_size.0,
+                    lowest_width.min(u32::MAX as u64) as u32,
+                    highest_width.min(u32::MAX as u64) as u32,
+                );
+
+                // The height can only fall outside of the bounds if the hints themselves are
+                // contradictory
+                let height = height_for_width(width as u64)
+                    .clamp(min_height as u64, max_height as u64)
+                    as u32;
+
+                (width, height)
+            }
+            None => (
+                snap(width, min_width, self.step_size.0, min_width, max_width),
+                snap(height, min_height, self.step_size.1, min_height, max_height),
+            ),
+        }
+    }
 }
 
 /// A raw window handle for platform and GUI framework agnostic editors. This implements
@@ -111,3 +292,97 @@ unsafe impl HasRawWindowHandle for ParentWindowHandle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constrain_size_min_max() {
+        let hints = ResizeHints {
+            min_size: (200, 100),
+            max_size: (800, 600),
+            ..Default::default()
+        };
+
+        assert_eq!(hints.constrain_size(50, 50), (200, 100));
+        assert_eq!(hints.constrain_size(1000, 1000), (800, 600));
+        assert_eq!(hints.constrain_size(400, 300), (400, 300));
+    }
+
+    #[test]
+    fn test_constrain_size_step() {
+        let hints = ResizeHints {
+            min_size: (100, 100),
+            step_size: (50, 10),
+            ..Default::default()
+        };
+
+        assert_eq!(hints.constrain_size(249, 137), (200, 130));
+    }
+
+    #[test]
+    fn test_constrain_size_aspect_ratio() {
+        let hints = ResizeHints {
+            aspect_ratio: Some((4, 3)),
+            ..Default::default()
+        };
+
+        assert_eq!(hints.constrain_size(800, 800), (800, 600));
+        assert_eq!(hints.constrain_size(1600, 600), (800, 600));
+    }
+
+    #[test]
+    fn test_constrain_size_aspect_ratio_min_max() {
+        let hints = ResizeHints {
+            min_size: (200, 200),
+            max_size: (1000, 1000),
+            aspect_ratio: Some((1, 2)),
+            ..Default::default()
+        };
+
+        // Shrinking the width to match the ratio would go below the minimum width, so the height
+        // grows instead
+        assert_eq!(hints.constrain_size(1000, 300), (200, 400));
+        // And the height cannot exceed the maximum height
+        assert_eq!(hints.constrain_size(1000, 5000), (500, 1000));
+        assert_eq!(hints.constrain_size(10, 10), (200, 400));
+    }
+
+    #[test]
+    fn test_constrain_size_step_aspect_ratio() {
+        let hints = ResizeHints {
+            min_size: (100, 100),
+            max_size: (400, 400),
+            step_size: (50, 50),
+            aspect_ratio: Some((1, 1)),
+        };
+
+        assert_eq!(hints.constrain_size(249, 1000), (200, 200));
+        assert_eq!(hints.constrain_size(1000, 1000), (400, 400));
+        assert_eq!(hints.constrain_size(120, 90), (100, 100));
+
+        // The snapped width is rounded up when it would otherwise have a height below the minimum
+        // height
+        let hints = ResizeHints {
+            min_size: (100, 300),
+            step_size: (64, 1),
+            aspect_ratio: Some((1, 1)),
+            ..Default::default()
+        };
+        assert_eq!(hints.constrain_size(300, 300), (356, 356));
+    }
+
+    #[test]
+    fn test_constrain_size_step_min_max() {
+        let hints = ResizeHints {
+            min_size: (100, 100),
+            max_size: (330, 330),
+            step_size: (100, 100),
+            aspect_ratio: None,
+        };
+
+        assert_eq!(hints.constrain_size(1000, 50), (300, 100));
+        assert_eq!(hints.constrain_size(330, 299), (300, 200));
+    }
+}
@@ -111,6 +111,8 @@ mod event_loop;
 pub mod midi;
 pub mod params;
 pub mod plugin;
+pub mod presets;
+pub mod testing;
 pub mod wrapper;
 
 // This is also re-exported from the prelude but since the other export entry points are macros and
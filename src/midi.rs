@@ -1,11 +1,14 @@
 //! Constants and definitions surrounding MIDI support.
 
 use midi_consts::channel_event as midi;
+use std::ops::Range;
 
 use self::sysex::SysExMessage;
 use crate::prelude::Plugin;
 
+pub mod mpe;
 pub mod sysex;
+pub mod voices;
 
 pub use midi_consts::channel_event::control_change;
 
@@ -34,12 +37,16 @@ pub enum MidiConfig {
     /// involves adding 130*16 parameters to bind to the the 128 MIDI CCs, pitch bend, and channel
     /// pressure.
     MidiCCs,
+    /// Everything from [`MidiConfig::MidiCCs`], with MIDI Polyphonic Expression on top. Pitch
+    /// bend, channel pressure, and CC74 messages sent on an MPE zone's member channels are
+    /// translated to [`NoteEvent::PolyTuning`], [`NoteEvent::PolyPressure`], and
+    /// [`NoteEvent::PolyBrightness`] events for the notes playing on those channels, so MPE
+    /// controllers can be handled the same way as CLAP and VST3 note expressions. Messages on the
+    /// zones' master channels are passed through as is. See [`mpe::MpeTranslator`] for more
+    /// information.
+    Mpe,
 }
 
-// FIXME: Like the voice ID, channel and note number can also be omitted in CLAP. And instead of an
-//        Option, maybe this should use a dedicated type to more clearly indicate that missing
-//        values should be treated as wildcards.
-
 /// Event for (incoming) notes. The set of supported note events depends on the value of
 /// [`Plugin::MIDI_INPUT`][crate::prelude::Plugin::MIDI_INPUT]. Also check out the
 /// [`util`][crate::util] module for convenient conversion functions.
@@ -50,6 +57,12 @@ pub enum MidiConfig {
 ///
 /// All of the timings are sample offsets within the current buffer. Out of bound timings are
 /// clamped to the current buffer's length. All sample, channel and note numbers are zero-indexed.
+///
+/// Note off, choke, and polyphonic expression events may target more than a single note. CLAP
+/// hosts can for instance choke every note on a channel by omitting the key. In that case the
+/// event's `channel` and/or `note` field is set to `None`, which should be treated as a wildcard.
+/// [`NoteEvent::matches_voice()`] can be used to check whether one of these events applies to a
+/// voice.
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[non_exhaustive]
 pub enum NoteEvent<S> {
@@ -74,10 +87,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's velocity, in `[0, 1]`. Some plugin APIs may allow higher precision than the
         /// 128 levels available in MIDI.
         velocity: f32,
@@ -90,10 +103,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
     },
 
     /// Sent by the plugin to the host to indicate that a voice has ended. This **needs** to be sent
@@ -173,6 +186,58 @@ pub enum NoteEvent<S> {
         /// docstring for more information.
         normalized_value: f32,
     },
+    /// A polyphonic modulation event that targets all voices matching a combination of a note ID,
+    /// key, channel, and note port, with `None` acting as a wildcard for each of these fields. This
+    /// is only sent by CLAP hosts when the corresponding scopes have been enabled in
+    /// [`PolyModulationConfig::modulation_scope`][crate::prelude::PolyModulationConfig]. Modulation
+    /// targeted at a single note ID without any of the other fields is still sent as a regular
+    /// `PolyModulation` event.
+    ///
+    /// This works exactly like `PolyModulation`, except that the normalized offset should be
+    /// applied to every active voice matching the event's fields. A host may for instance use
+    /// `key: Some(60)` with all other fields set to `None` to modulate every voice playing middle C.
+    ScopedPolyModulation {
+        timing: u32,
+        /// The note ID of the voice this event targets, or `None` to match any voice.
+        note_id: Option<i32>,
+        /// The note port the targeted voices were started from, or `None` to match any port.
+        port_index: Option<u16>,
+        /// The targeted voices' MIDI channel, in `0..16`, or `None` to match any channel.
+        channel: Option<u8>,
+        /// The targeted voices' MIDI note number, in `0..128`, or `None` to match any key.
+        key: Option<u8>,
+        /// The ID that was set for the modulated parameter using the `.with_poly_modulation_id()`
+        /// method.
+        poly_modulation_id: u32,
+        /// The normalized offset value. See the `PolyModulation` event's docstring for more
+        /// information.
+        normalized_offset: f32,
+    },
+    /// Automation targeted at all voices matching a combination of a note ID, key, channel, and
+    /// note port, with `None` acting as a wildcard for each of these fields. This is only sent by
+    /// CLAP hosts when the corresponding scopes have been enabled in
+    /// [`PolyModulationConfig::automation_scope`][crate::prelude::PolyModulationConfig].
+    ///
+    /// Unlike `MonoAutomation`, the parameter's global value is not changed. Instead, the
+    /// normalized value replaces the parameter's global normalized value for every active voice
+    /// matching the event's fields. Any polyphonic modulation offsets for those voices should be
+    /// added to this value instead of to the global value.
+    PolyAutomation {
+        timing: u32,
+        /// The note ID of the voice this event targets, or `None` to match any voice.
+        note_id: Option<i32>,
+        /// The note port the targeted voices were started from, or `None` to match any port.
+        port_index: Option<u16>,
+        /// The targeted voices' MIDI channel, in `0..16`, or `None` to match any channel.
+        channel: Option<u8>,
+        /// The targeted voices' MIDI note number, in `0..128`, or `None` to match any key.
+        key: Option<u8>,
+        /// The ID that was set for the modulated parameter using the `.with_poly_modulation_id()`
+        /// method.
+        poly_modulation_id: u32,
+        /// The parameter's new normalized value for the matching voices.
+        normalized_value: f32,
+    },
 
     /// A polyphonic note pressure/aftertouch event, available on [`MidiConfig::Basic`] and up. Not
     /// all hosts may support polyphonic aftertouch.
@@ -187,10 +252,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's pressure, in `[0, 1]`.
         pressure: f32,
     },
@@ -201,10 +266,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's voltage gain ratio, where 1.0 is unity gain.
         gain: f32,
     },
@@ -215,10 +280,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's panning from, in `[-1, 1]`, with -1 being panned hard left, and 1
         /// being panned hard right.
         pan: f32,
@@ -230,11 +295,11 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
-        /// The note's tuning in semitones, in `[-128, 128]`.
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
+        /// The note's tuning in semitones, in `[-120, 120]`.
         tuning: f32,
     },
     /// A vibrato expression event, available on [`MidiConfig::Basic`] and up. Not all hosts may support
@@ -244,10 +309,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's vibrato amount, in `[0, 1]`.
         vibrato: f32,
     },
@@ -258,10 +323,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's expression amount, in `[0, 1]`.
         expression: f32,
     },
@@ -272,10 +337,10 @@ pub enum NoteEvent<S> {
         /// A unique identifier for this note, if available. Using this to refer to a note is
         /// required when allowing overlapping voices for CLAP plugins.
         voice_id: Option<i32>,
-        /// The note's channel, in `0..16`.
-        channel: u8,
-        /// The note's MIDI key number, in `0..128`.
-        note: u8,
+        /// The note's channel, in `0..16`, or `None` if the event applies to all channels.
+        channel: Option<u8>,
+        /// The note's MIDI key number, in `0..128`, or `None` if the event applies to all keys.
+        note: Option<u8>,
         /// The note's brightness amount, in `[0, 1]`.
         brightness: f32,
     },
@@ -349,6 +414,8 @@ impl<S> NoteEvent<S> {
             NoteEvent::VoiceTerminated { timing, .. } => *timing,
             NoteEvent::PolyModulation { timing, .. } => *timing,
             NoteEvent::MonoAutomation { timing, .. } => *timing,
+            NoteEvent::ScopedPolyModulation { timing, .. } => *timing,
+            NoteEvent::PolyAutomation { timing, .. } => *timing,
             NoteEvent::PolyPressure { timing, .. } => *timing,
             NoteEvent::PolyVolume { timing, .. } => *timing,
             NoteEvent::PolyPan { timing, .. } => *timing,
@@ -373,6 +440,8 @@ impl<S> NoteEvent<S> {
             NoteEvent::VoiceTerminated { voice_id, .. } => *voice_id,
             NoteEvent::PolyModulation { voice_id, .. } => Some(*voice_id),
             NoteEvent::MonoAutomation { .. } => None,
+            NoteEvent::ScopedPolyModulation { note_id, .. } => *note_id,
+            NoteEvent::PolyAutomation { note_id, .. } => *note_id,
             NoteEvent::PolyPressure { voice_id, .. } => *voice_id,
             NoteEvent::PolyVolume { voice_id, .. } => *voice_id,
             NoteEvent::PolyPan { voice_id, .. } => *voice_id,
@@ -392,18 +461,20 @@ impl<S> NoteEvent<S> {
     pub fn channel(&self) -> Option<u8> {
         match self {
             NoteEvent::NoteOn { channel, .. } => Some(*channel),
-            NoteEvent::NoteOff { channel, .. } => Some(*channel),
-            NoteEvent::Choke { channel, .. } => Some(*channel),
+            NoteEvent::NoteOff { channel, .. } => *channel,
+            NoteEvent::Choke { channel, .. } => *channel,
             NoteEvent::VoiceTerminated { channel, .. } => Some(*channel),
             NoteEvent::PolyModulation { .. } => None,
             NoteEvent::MonoAutomation { .. } => None,
-            NoteEvent::PolyPressure { channel, .. } => Some(*channel),
-            NoteEvent::PolyVolume { channel, .. } => Some(*channel),
-            NoteEvent::PolyPan { channel, .. } => Some(*channel),
-            NoteEvent::PolyTuning { channel, .. } => Some(*channel),
-            NoteEvent::PolyVibrato { channel, .. } => Some(*channel),
-            NoteEvent::PolyExpression { channel, .. } => Some(*channel),
-            NoteEvent::PolyBrightness { channel, .. } => Some(*channel),
+            NoteEvent::ScopedPolyModulation { channel, .. } => *channel,
+            NoteEvent::PolyAutomation { channel, .. } => *channel,
+            NoteEvent::PolyPressure { channel, .. } => *channel,
+            NoteEvent::PolyVolume { channel, .. } => *channel,
+            NoteEvent::PolyPan { channel, .. } => *channel,
+            NoteEvent::PolyTuning { channel, .. } => *channel,
+            NoteEvent::PolyVibrato { channel, .. } => *channel,
+            NoteEvent::PolyExpression { channel, .. } => *channel,
+            NoteEvent::PolyBrightness { channel, .. } => *channel,
             NoteEvent::MidiChannelPressure { channel, .. } => Some(*channel),
             NoteEvent::MidiPitchBend { channel, .. } => Some(*channel),
             NoteEvent::MidiCC { channel, .. } => Some(*channel),
@@ -411,6 +482,74 @@ impl<S> NoteEvent<S> {
             NoteEvent::MidiSysEx { .. } => None,
         }
     }
+
+    /// Returns the event's MIDI key number, if it has any.
+    pub fn note(&self) -> Option<u8> {
+        match self {
+            NoteEvent::NoteOn { note, .. } => Some(*note),
+            NoteEvent::NoteOff { note, .. } => *note,
+            NoteEvent::Choke { note, .. } => *note,
+            NoteEvent::VoiceTerminated { note, .. } => Some(*note),
+            NoteEvent::ScopedPolyModulation { key, .. } => *key,
+            NoteEvent::PolyAutomation { key, .. } => *key,
+            NoteEvent::PolyPressure { note, .. } => *note,
+            NoteEvent::PolyVolume { note, .. } => *note,
+            NoteEvent::PolyPan { note, .. } => *note,
+            NoteEvent::PolyTuning { note, .. } => *note,
+            NoteEvent::PolyVibrato { note, .. } => *note,
+            NoteEvent::PolyExpression { note, .. } => *note,
+            NoteEvent::PolyBrightness { note, .. } => *note,
+            NoteEvent::PolyModulation { .. }
+            | NoteEvent::MonoAutomation { .. }
+            | NoteEvent::MidiChannelPressure { .. }
+            | NoteEvent::MidiPitchBend { .. }
+            | NoteEvent::MidiCC { .. }
+            | NoteEvent::MidiProgramChange { .. }
+            | NoteEvent::MidiSysEx { .. } => None,
+        }
+    }
+
+    /// Check whether this event applies to a voice that was started with the given voice ID,
+    /// channel, and note number. If both the event and the voice have a voice ID, then only the
+    /// voice IDs are compared. Otherwise the event matches when its channel and note are either
+    /// equal to the voice's or are wildcards. Events that don't target notes, like MIDI CCs and
+    /// monophonic automation, never match. The note port in `ScopedPolyModulation` and
+    /// `PolyAutomation` events is ignored as NIH-plug plugins only have a single note input port.
+    ///
+    /// `PolyModulation` events only match voices with the same voice ID.
+    pub fn matches_voice(&self, voice_id: Option<i32>, channel: u8, note: u8) -> bool {
+        let (event_channel, event_note) = match self {
+            NoteEvent::PolyModulation {
+                voice_id: event_voice_id,
+                ..
+            } => return voice_id == Some(*event_voice_id),
+            NoteEvent::NoteOn { .. }
+            | NoteEvent::NoteOff { .. }
+            | NoteEvent::Choke { .. }
+            | NoteEvent::VoiceTerminated { .. }
+            | NoteEvent::ScopedPolyModulation { .. }
+            | NoteEvent::PolyAutomation { .. }
+            | NoteEvent::PolyPressure { .. }
+            | NoteEvent::PolyVolume { .. }
+            | NoteEvent::PolyPan { .. }
+            | NoteEvent::PolyTuning { .. }
+            | NoteEvent::PolyVibrato { .. }
+            | NoteEvent::PolyExpression { .. }
+            | NoteEvent::PolyBrightness { .. } => (self.channel(), self.note()),
+            NoteEvent::MonoAutomation { .. }
+            | NoteEvent::MidiChannelPressure { .. }
+            | NoteEvent::MidiPitchBend { .. }
+            | NoteEvent::MidiCC { .. }
+            | NoteEvent::MidiProgramChange { .. }
+            | NoteEvent::MidiSysEx { .. } => return false,
+        };
+
+        if let (Some(event_voice_id), Some(voice_id)) = (self.voice_id(), voice_id) {
+            return event_voice_id == voice_id;
+        }
+
+        event_channel.map_or(true, |c| c == channel) && event_note.map_or(true, |n| n == note)
+    }
 }
 
 impl<S: SysExMessage> NoteEvent<S> {
@@ -431,8 +570,8 @@ impl<S: SysExMessage> NoteEvent<S> {
                     return Ok(NoteEvent::NoteOff {
                         timing,
                         voice_id: None,
-                        channel,
-                        note: midi_data[1],
+                        channel: Some(channel),
+                        note: Some(midi_data[1]),
                         // Few things use release velocity. Just having this be zero here is fine, right?
                         velocity: 0.0,
                     });
@@ -450,8 +589,8 @@ impl<S: SysExMessage> NoteEvent<S> {
                     return Ok(NoteEvent::NoteOff {
                         timing,
                         voice_id: None,
-                        channel,
-                        note: midi_data[1],
+                        channel: Some(channel),
+                        note: Some(midi_data[1]),
                         velocity: midi_data[2] as f32 / 127.0,
                     });
                 }
@@ -459,8 +598,8 @@ impl<S: SysExMessage> NoteEvent<S> {
                     return Ok(NoteEvent::PolyPressure {
                         timing,
                         voice_id: None,
-                        channel,
-                        note: midi_data[1],
+                        channel: Some(channel),
+                        note: Some(midi_data[1]),
                         pressure: midi_data[2] as f32 / 127.0,
                     });
                 }
@@ -524,9 +663,104 @@ impl<S: SysExMessage> NoteEvent<S> {
         }
     }
 
+    /// Parse a MIDI 2.0 Universal MIDI Packet into a [`NoteEvent`]. Both MIDI 1.0 channel voice
+    /// messages (message type `0x2`) and MIDI 2.0 channel voice messages (message type `0x4`) are
+    /// supported. The higher resolution MIDI 2.0 values are converted to the normalized ranges used
+    /// by [`NoteEvent`], and per-note pitch bend is converted to [`NoteEvent::PolyTuning`] using
+    /// the default range of 48 semitones. The UMP group is ignored. Will return `Err(event_type)`
+    /// if the packet does not contain a supported message.
+    pub fn from_midi2(timing: u32, ump: &[u32; 4]) -> Result<Self, u8> {
+        let message_type = ump[0] >> 28;
+        let status_byte = ((ump[0] >> 16) & 0xff) as u8;
+        let event_type = status_byte & midi::EVENT_TYPE_MASK;
+        let channel = status_byte & midi::MIDI_CHANNEL_MASK;
+        let index = ((ump[0] >> 8) & 0x7f) as u8;
+        let data = ump[1];
+
+        match message_type {
+            // MIDI 1.0 channel voice messages, these can be parsed just like regular MIDI
+            0x2 => {
+                let midi_data = [status_byte, index, (ump[0] & 0x7f) as u8];
+                match event_type {
+                    midi::CHANNEL_KEY_PRESSURE | midi::PROGRAM_CHANGE => {
+                        Self::from_midi(timing, &midi_data[..2])
+                    }
+                    _ => Self::from_midi(timing, &midi_data),
+                }
+            }
+            0x4 => {
+                let data_32 = data as f32 / u32::MAX as f32;
+                match event_type {
+                    midi::NOTE_ON => Ok(NoteEvent::NoteOn {
+                        timing,
+                        voice_id: None,
+                        channel,
+                        note: index,
+                        velocity: (data >> 16) as f32 / u16::MAX as f32,
+                    }),
+                    midi::NOTE_OFF => Ok(NoteEvent::NoteOff {
+                        timing,
+                        voice_id: None,
+                        channel: Some(channel),
+                        note: Some(index),
+                        velocity: (data >> 16) as f32 / u16::MAX as f32,
+                    }),
+                    midi::POLYPHONIC_KEY_PRESSURE => Ok(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id: None,
+                        channel: Some(channel),
+                        note: Some(index),
+                        pressure: data_32,
+                    }),
+                    midi::CONTROL_CHANGE => Ok(NoteEvent::MidiCC {
+                        timing,
+                        channel,
+                        cc: index,
+                        value: data_32,
+                    }),
+                    midi::PROGRAM_CHANGE => Ok(NoteEvent::MidiProgramChange {
+                        timing,
+                        channel,
+                        program: ((data >> 24) & 0x7f) as u8,
+                    }),
+                    midi::CHANNEL_KEY_PRESSURE => Ok(NoteEvent::MidiChannelPressure {
+                        timing,
+                        channel,
+                        pressure: data_32,
+                    }),
+                    midi::PITCH_BEND_CHANGE => Ok(NoteEvent::MidiPitchBend {
+                        timing,
+                        channel,
+                        value: data_32,
+                    }),
+                    // Per-note pitch bend, this is centered around `0x80000000`
+                    0x60 => Ok(NoteEvent::PolyTuning {
+                        timing,
+                        voice_id: None,
+                        channel: Some(channel),
+                        note: Some(index),
+                        tuning: ((data as f64 - 2_147_483_648.0) / 2_147_483_648.0
+                            * mpe::DEFAULT_MEMBER_PITCH_BEND_RANGE as f64)
+                            as f32,
+                    }),
+                    _ => {
+                        nih_trace!("Unhandled MIDI 2.0 status byte {status_byte:#x}");
+                        Err(event_type)
+                    }
+                }
+            }
+            _ => {
+                nih_trace!("Unhandled UMP message type {message_type:#x}");
+                Err(event_type)
+            }
+        }
+    }
+
     /// Create a MIDI message from this note event. Returns `None` if this even does not have a
     /// direct MIDI equivalent. `PolyPressure` will be converted to polyphonic key pressure, but the
-    /// other polyphonic note expression types will not be converted to MIDI CC messages.
+    /// other polyphonic note expression types will not be converted to MIDI CC messages. A
+    /// `NoteOff` event for all keys on a channel is converted to an all notes off message, while
+    /// events for all channels cannot be converted.
     pub fn as_midi(self) -> Option<MidiResult<S>> {
         match self {
             NoteEvent::NoteOn {
@@ -544,19 +778,31 @@ impl<S: SysExMessage> NoteEvent<S> {
             NoteEvent::NoteOff {
                 timing: _,
                 voice_id: _,
-                channel,
-                note,
+                channel: Some(channel),
+                note: Some(note),
                 velocity,
             } => Some(MidiResult::Basic([
                 midi::NOTE_OFF | channel,
                 note,
                 (velocity * 127.0).round().clamp(0.0, 127.0) as u8,
             ])),
+            // A note off for every key on a channel is the same as MIDI's all notes off message
+            NoteEvent::NoteOff {
+                timing: _,
+                voice_id: _,
+                channel: Some(channel),
+                note: None,
+                velocity: _,
+            } => Some(MidiResult::Basic([
+                midi::CONTROL_CHANGE | channel,
+                control_change::ALL_NOTES_OFF,
+                0,
+            ])),
             NoteEvent::PolyPressure {
                 timing: _,
                 voice_id: _,
-                channel,
-                note,
+                channel: Some(channel),
+                note: Some(note),
                 pressure,
             } => Some(MidiResult::Basic([
                 midi::POLYPHONIC_KEY_PRESSURE | channel,
@@ -613,10 +859,14 @@ impl<S: SysExMessage> NoteEvent<S> {
                 let (padded_sysex_buffer, length) = message.to_buffer();
                 Some(MidiResult::SysEx(padded_sysex_buffer, length))
             }
-            NoteEvent::Choke { .. }
+            NoteEvent::NoteOff { channel: None, .. }
+            | NoteEvent::PolyPressure { .. }
+            | NoteEvent::Choke { .. }
             | NoteEvent::VoiceTerminated { .. }
             | NoteEvent::PolyModulation { .. }
             | NoteEvent::MonoAutomation { .. }
+            | NoteEvent::ScopedPolyModulation { .. }
+            | NoteEvent::PolyAutomation { .. }
             | NoteEvent::PolyVolume { .. }
             | NoteEvent::PolyPan { .. }
             | NoteEvent::PolyTuning { .. }
@@ -637,6 +887,8 @@ impl<S: SysExMessage> NoteEvent<S> {
             NoteEvent::VoiceTerminated { timing, .. } => *timing -= samples,
             NoteEvent::PolyModulation { timing, .. } => *timing -= samples,
             NoteEvent::MonoAutomation { timing, .. } => *timing -= samples,
+            NoteEvent::ScopedPolyModulation { timing, .. } => *timing -= samples,
+            NoteEvent::PolyAutomation { timing, .. } => *timing -= samples,
             NoteEvent::PolyPressure { timing, .. } => *timing -= samples,
             NoteEvent::PolyVolume { timing, .. } => *timing -= samples,
             NoteEvent::PolyPan { timing, .. } => *timing -= samples,
@@ -653,6 +905,227 @@ impl<S: SysExMessage> NoteEvent<S> {
     }
 }
 
+impl<S: Clone> NoteEvent<S> {
+    /// Expand a note off, choke, or polyphonic expression event that applies to all channels or
+    /// all keys into one event for every matching channel and key. This is needed for plugin APIs
+    /// that cannot target more than a single note at a time. Note offs and chokes for every key on
+    /// a channel are sent as MIDI's all notes off and all sound off messages instead of as 128
+    /// separate events, and polyphonic expressions for every key on every channel are dropped
+    /// since they would expand to 2048 events. Events with a voice ID can be matched using just
+    /// that ID, so those and all other events are yielded as is.
+    #[cfg_attr(not(feature = "vst3"), allow(dead_code))]
+    pub(crate) fn expand_wildcards(self) -> ExpandWildcards<S> {
+        let (channels, notes) = match self {
+            NoteEvent::NoteOff {
+                timing,
+                voice_id: None,
+                channel,
+                note: None,
+                ..
+            } => return ExpandWildcards::all_notes(timing, channel, control_change::ALL_NOTES_OFF),
+            NoteEvent::Choke {
+                timing,
+                voice_id: None,
+                channel,
+                note: None,
+            } => return ExpandWildcards::all_notes(timing, channel, control_change::ALL_SOUND_OFF),
+            NoteEvent::NoteOff {
+                voice_id: None,
+                channel: None,
+                note: Some(note),
+                ..
+            }
+            | NoteEvent::Choke {
+                voice_id: None,
+                channel: None,
+                note: Some(note),
+                ..
+            } => (0..16, note..note + 1),
+            NoteEvent::PolyPressure {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyVolume {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyPan {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyTuning {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyVibrato {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyExpression {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            }
+            | NoteEvent::PolyBrightness {
+                voice_id: None,
+                channel,
+                note,
+                ..
+            } if channel.is_none() || note.is_none() => match (channel, note) {
+                (None, None) => {
+                    nih_debug_assert_failure!(
+                        "Polyphonic expressions for every key on every channel require a voice ID, \
+                         dropping the event"
+                    );
+                    return ExpandWildcards {
+                        event: None,
+                        channels: 0..0,
+                        notes: 0..0,
+                        all_notes: 0..0,
+                    };
+                }
+                (channel, note) => (
+                    channel.map_or(0..16, |channel| channel..channel + 1),
+                    note.map_or(0..128, |note| note..note + 1),
+                ),
+            },
+            _ => (0..0, 0..0),
+        };
+
+        ExpandWildcards {
+            event: Some(self),
+            channels,
+            notes: notes.clone(),
+            all_notes: notes,
+        }
+    }
+}
+
+/// An iterator over the events produced by [`NoteEvent::expand_wildcards()`].
+#[cfg_attr(not(feature = "vst3"), allow(dead_code))]
+pub(crate) struct ExpandWildcards<S> {
+    /// The event being expanded. This is taken directly if the event does not contain any
+    /// wildcards.
+    event: Option<NoteEvent<S>>,
+    /// The channels that still need to be yielded. Empty if the event does not contain wildcards.
+    channels: Range<u8>,
+    /// The remaining notes for the current channel.
+    notes: Range<u8>,
+    /// All notes the event applies to, used to reset `notes` when moving on to the next channel.
+    all_notes: Range<u8>,
+}
+
+impl<S> ExpandWildcards<S> {
+    /// Send a channel mode message on `channel`, or on every channel if it's `None`.
+    fn all_notes(timing: u32, channel: Option<u8>, cc: u8) -> Self {
+        Self {
+            event: Some(NoteEvent::MidiCC {
+                timing,
+                channel: channel.unwrap_or_default(),
+                cc,
+                value: 0.0,
+            }),
+            channels: channel.map_or(0..16, |channel| channel..channel + 1),
+            notes: 0..1,
+            all_notes: 0..1,
+        }
+    }
+}
+
+impl<S: Clone> Iterator for ExpandWildcards<S> {
+    type Item = NoteEvent<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.channels.is_empty() {
+            return self.event.take();
+        }
+
+        let channel = self.channels.start;
+        let note = self.notes.next()?;
+        let mut event = if self.notes.is_empty() {
+            self.channels.next();
+            self.notes = self.all_notes.clone();
+
+            // The original event can be reused for the last expanded event
+            if self.channels.is_empty() {
+                self.event.take()?
+            } else {
+                self.event.clone()?
+            }
+        } else {
+            self.event.clone()?
+        };
+        match &mut event {
+            NoteEvent::NoteOff {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::Choke {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyPressure {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyVolume {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyPan {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyTuning {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyVibrato {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyExpression {
+                channel: event_channel,
+                note: event_note,
+                ..
+            }
+            | NoteEvent::PolyBrightness {
+                channel: event_channel,
+                note: event_note,
+                ..
+            } => {
+                *event_channel = Some(channel);
+                *event_note = Some(note);
+            }
+            NoteEvent::MidiCC {
+                channel: event_channel,
+                ..
+            } => *event_channel = channel,
+            _ => unreachable!(),
+        }
+
+        Some(event)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     pub use super::*;
@@ -688,8 +1161,8 @@ mod tests {
         let event = NoteEvent::<()>::NoteOff {
             timing: TIMING,
             voice_id: None,
-            channel: 1,
-            note: 2,
+            channel: Some(1),
+            note: Some(2),
             velocity: 0.6929134,
         };
 
@@ -701,8 +1174,8 @@ mod tests {
         let event = NoteEvent::<()>::PolyPressure {
             timing: TIMING,
             voice_id: None,
-            channel: 1,
-            note: 2,
+            channel: Some(1),
+            note: Some(2),
             pressure: 0.6929134,
         };
 
@@ -754,6 +1227,248 @@ mod tests {
         assert_eq!(roundtrip_basic_event(event), event);
     }
 
+    #[test]
+    fn test_midi1_ump_conversion() {
+        // A MIDI 1.0 note on on group 3, channel 2
+        let ump = [0x2392_4064, 0, 0, 0];
+
+        assert_eq!(
+            NoteEvent::<()>::from_midi2(TIMING, &ump).unwrap(),
+            NoteEvent::NoteOn {
+                timing: TIMING,
+                voice_id: None,
+                channel: 2,
+                note: 0x40,
+                velocity: 100.0 / 127.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_midi2_note_on_conversion() {
+        let ump = [0x4091_3c00, 0xffff_0000, 0, 0];
+
+        assert_eq!(
+            NoteEvent::<()>::from_midi2(TIMING, &ump).unwrap(),
+            NoteEvent::NoteOn {
+                timing: TIMING,
+                voice_id: None,
+                channel: 1,
+                note: 0x3c,
+                velocity: 1.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_midi2_per_note_pitch_bend_conversion() {
+        let ump = [0x4060_3c00, 0xc000_0000, 0, 0];
+
+        assert_eq!(
+            NoteEvent::<()>::from_midi2(TIMING, &ump).unwrap(),
+            NoteEvent::PolyTuning {
+                timing: TIMING,
+                voice_id: None,
+                channel: Some(0),
+                note: Some(0x3c),
+                tuning: 24.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_midi2_unsupported_message() {
+        // A MIDI 2.0 registered per-note controller message
+        let ump = [0x4000_3c01, 0, 0, 0];
+
+        assert!(NoteEvent::<()>::from_midi2(TIMING, &ump).is_err());
+    }
+
+    #[test]
+    fn test_scoped_poly_modulation_wildcards() {
+        let key_scoped = NoteEvent::<()>::ScopedPolyModulation {
+            timing: TIMING,
+            note_id: None,
+            port_index: None,
+            channel: None,
+            key: Some(60),
+            poly_modulation_id: 0,
+            normalized_offset: 0.25,
+        };
+        assert_eq!(key_scoped.voice_id(), None);
+        assert_eq!(key_scoped.channel(), None);
+        assert!(key_scoped.as_midi().is_none());
+
+        let note_scoped = NoteEvent::<()>::PolyAutomation {
+            timing: TIMING,
+            note_id: Some(42),
+            port_index: Some(0),
+            channel: Some(3),
+            key: Some(60),
+            poly_modulation_id: 0,
+            normalized_value: 0.5,
+        };
+        assert_eq!(note_scoped.voice_id(), Some(42));
+        assert_eq!(note_scoped.channel(), Some(3));
+    }
+
+    #[test]
+    fn test_wildcard_note_off_midi_conversion() {
+        let event = NoteEvent::<()>::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(3),
+            note: None,
+            velocity: 0.0,
+        };
+        match event.as_midi() {
+            Some(MidiResult::Basic(midi_data)) => {
+                assert_eq!(midi_data, [0xb3, control_change::ALL_NOTES_OFF, 0])
+            }
+            result => panic!("Unexpected result: {result:?}"),
+        }
+
+        let event = NoteEvent::<()>::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: None,
+            velocity: 0.0,
+        };
+        assert!(event.as_midi().is_none());
+    }
+
+    #[test]
+    fn test_expand_wildcards() {
+        let choke_key = NoteEvent::<()>::Choke {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: Some(60),
+        };
+        let expanded: Vec<_> = choke_key.expand_wildcards().collect();
+        assert_eq!(expanded.len(), 16);
+        assert_eq!(
+            expanded[2],
+            NoteEvent::Choke {
+                timing: TIMING,
+                voice_id: None,
+                channel: Some(2),
+                note: Some(60),
+            }
+        );
+
+        let tuning_channel = NoteEvent::<()>::PolyTuning {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(2),
+            note: None,
+            tuning: 1.0,
+        };
+        assert_eq!(tuning_channel.expand_wildcards().count(), 128);
+
+        // Releasing every key on a channel is sent as a single channel mode message per channel
+        let choke_channel = NoteEvent::<()>::Choke {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(2),
+            note: None,
+        };
+        assert_eq!(
+            choke_channel.expand_wildcards().collect::<Vec<_>>(),
+            [NoteEvent::MidiCC {
+                timing: TIMING,
+                channel: 2,
+                cc: control_change::ALL_SOUND_OFF,
+                value: 0.0,
+            }]
+        );
+        let note_off_all = NoteEvent::<()>::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: None,
+            velocity: 0.0,
+        };
+        let expanded: Vec<_> = note_off_all.expand_wildcards().collect();
+        assert_eq!(expanded.len(), 16);
+        assert_eq!(
+            expanded[15],
+            NoteEvent::MidiCC {
+                timing: TIMING,
+                channel: 15,
+                cc: control_change::ALL_NOTES_OFF,
+                value: 0.0,
+            }
+        );
+
+        // Events without wildcards and events with voice IDs are passed through as is
+        let note_off = NoteEvent::<()>::NoteOff {
+            timing: TIMING,
+            voice_id: Some(1),
+            channel: None,
+            note: None,
+            velocity: 0.0,
+        };
+        assert_eq!(note_off.expand_wildcards().collect::<Vec<_>>(), [note_off]);
+    }
+
+    #[test]
+    fn test_matches_voice() {
+        let choke_channel = NoteEvent::<()>::Choke {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(2),
+            note: None,
+        };
+        assert!(choke_channel.matches_voice(None, 2, 60));
+        assert!(choke_channel.matches_voice(Some(5), 2, 61));
+        assert!(!choke_channel.matches_voice(None, 3, 60));
+
+        let note_off_key = NoteEvent::<()>::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: Some(60),
+            velocity: 0.0,
+        };
+        assert!(note_off_key.matches_voice(None, 0, 60));
+        assert!(note_off_key.matches_voice(None, 15, 60));
+        assert!(!note_off_key.matches_voice(None, 0, 61));
+
+        // Voice IDs take precedence over the channel and note when both sides have one
+        let pressure = NoteEvent::<()>::PolyPressure {
+            timing: TIMING,
+            voice_id: Some(7),
+            channel: Some(0),
+            note: Some(60),
+            pressure: 0.5,
+        };
+        assert!(pressure.matches_voice(Some(7), 1, 61));
+        assert!(!pressure.matches_voice(Some(8), 0, 60));
+        assert!(pressure.matches_voice(None, 0, 60));
+
+        let key_modulation = NoteEvent::<()>::ScopedPolyModulation {
+            timing: TIMING,
+            note_id: None,
+            port_index: None,
+            channel: None,
+            key: Some(60),
+            poly_modulation_id: 0,
+            normalized_offset: 0.25,
+        };
+        assert!(key_modulation.matches_voice(Some(1), 4, 60));
+        assert!(!key_modulation.matches_voice(Some(1), 4, 62));
+
+        let cc = NoteEvent::<()>::MidiCC {
+            timing: TIMING,
+            channel: 0,
+            cc: 1,
+            value: 0.5,
+        };
+        assert!(!cc.matches_voice(None, 0, 60));
+    }
+
     mod sysex {
         use super::*;
 
@@ -0,0 +1,529 @@
+//! Translation from MIDI Polyphonic Expression (MPE) to polyphonic note expression events.
+//!
+//! MPE controllers assign every note its own MIDI channel so that channel-wide messages like pitch
+//! bend, channel pressure, and CC74 can be used as per-note expressions. The wrappers use
+//! [`MpeTranslator`] to turn those messages into [`NoteEvent::PolyTuning`],
+//! [`NoteEvent::PolyPressure`], and [`NoteEvent::PolyBrightness`] events when a plugin sets
+//! [`Plugin::MIDI_INPUT`][crate::prelude::Plugin::MIDI_INPUT] to [`MidiConfig::Mpe`][super::MidiConfig::Mpe].
+//! This way a plugin can handle CLAP note expressions, VST3 note expressions, and MPE controllers
+//! using the exact same code.
+//!
+//! See the [MPE specification](https://www.midi.org/specifications/midi1-specifications/mpe-midi-polyphonic-expression)
+//! for more information.
+
+use super::{control_change, NoteEvent};
+
+/// The pitch bend range used for an MPE zone's member channels when no pitch bend range has been
+/// set through RPN 0. This is the default defined by the MPE specification.
+pub const DEFAULT_MEMBER_PITCH_BEND_RANGE: f32 = 48.0;
+
+/// The registered parameter number for the pitch bend sensitivity.
+const RPN_PITCH_BEND_SENSITIVITY: u16 = 0x0000;
+/// The registered parameter number for the MPE Configuration Message.
+const RPN_MPE_CONFIGURATION: u16 = 0x0006;
+/// The RPN value used to indicate that no RPN is currently selected.
+const RPN_NULL: u16 = 0x3fff;
+
+/// The lower zone's master channel. The member channels are allocated upwards starting from
+/// channel 1.
+const LOWER_ZONE_MASTER_CHANNEL: u8 = 0;
+/// The upper zone's master channel. The member channels are allocated downwards starting from
+/// channel 14.
+const UPPER_ZONE_MASTER_CHANNEL: u8 = 15;
+
+/// Translates per-channel pitch bend, channel pressure, and CC74 messages sent on MPE member
+/// channels to polyphonic expression events for the notes playing on those channels.
+///
+/// The zone layout follows the MPE Configuration Messages (RPN 6) sent to the master channels.
+/// Until such a message is received, the translator assumes a lower zone using channel 0 as its
+/// master channel and channels 1 through 15 as member channels, since that's what most MPE
+/// controllers send out of the box. Pitch bend ranges set through RPN 0 on a zone's member
+/// channels are also respected.
+///
+/// Messages on member channels that are translated are not passed through as regular MIDI events.
+/// All other events, including everything sent on the master channels, are passed through
+/// unchanged.
+#[derive(Debug, Clone)]
+pub struct MpeTranslator {
+    /// The number of member channels in the lower zone, in `0..=15`.
+    lower_zone_members: u8,
+    /// The number of member channels in the upper zone, in `0..=15`. The two zones never overlap.
+    upper_zone_members: u8,
+    /// The pitch bend range for the lower zone's member channels, in semitones.
+    lower_zone_pitch_bend_range: f32,
+    /// The pitch bend range for the upper zone's member channels, in semitones.
+    upper_zone_pitch_bend_range: f32,
+    channels: [ChannelState; 16],
+}
+
+/// The expression and RPN state for a single MIDI channel.
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    /// A bitmask of the MIDI notes currently held on this channel.
+    held_notes: u128,
+    /// The last received pitch bend, in semitones.
+    tuning: f32,
+    /// The last received channel pressure, in `[0, 1]`.
+    pressure: f32,
+    /// The last received CC74 value, in `[0, 1]`. `None` until the first CC74 message has been
+    /// received so notes don't get an initial brightness that was never sent.
+    brightness: Option<f32>,
+    /// The registered parameter number selected through CC101 and CC100.
+    rpn: u16,
+}
+
+impl Default for MpeTranslator {
+    fn default() -> Self {
+        Self {
+            lower_zone_members: 15,
+            upper_zone_members: 0,
+            lower_zone_pitch_bend_range: DEFAULT_MEMBER_PITCH_BEND_RANGE,
+            upper_zone_pitch_bend_range: DEFAULT_MEMBER_PITCH_BEND_RANGE,
+            channels: [ChannelState::default(); 16],
+        }
+    }
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            held_notes: 0,
+            tuning: 0.0,
+            pressure: 0.0,
+            brightness: None,
+            rpn: RPN_NULL,
+        }
+    }
+}
+
+impl MpeTranslator {
+    /// Reset the translator to its default zone layout and clear all held notes and expression
+    /// values. Called by the wrappers when the plugin is reset.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Whether `channel` is a member channel in either the lower or the upper zone.
+    pub fn is_member_channel(&self, channel: u8) -> bool {
+        self.member_pitch_bend_range(channel).is_some()
+    }
+
+    /// Process an incoming event. `push` is called for every resulting event, in order. Events that
+    /// have been translated to polyphonic expressions are consumed, everything else is passed
+    /// through unchanged.
+    pub fn translate<S>(&mut self, event: NoteEvent<S>, mut push: impl FnMut(NoteEvent<S>)) {
+        match event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                ..
+            } if self.is_member_channel(channel) => {
+                let state = &mut self.channels[channel as usize];
+                state.held_notes |= 1u128 << (note & 127);
+                let state = *state;
+
+                // MPE controllers send the initial expression values before the note on, so those
+                // need to be sent again as polyphonic expressions for the new note
+                push(event);
+                if state.tuning != 0.0 {
+                    push(NoteEvent::PolyTuning {
+                        timing,
+                        voice_id,
+                        channel: Some(channel),
+                        note: Some(note),
+                        tuning: state.tuning,
+                    });
+                }
+                if state.pressure != 0.0 {
+                    push(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id,
+                        channel: Some(channel),
+                        note: Some(note),
+                        pressure: state.pressure,
+                    });
+                }
+                if let Some(brightness) = state.brightness {
+                    push(NoteEvent::PolyBrightness {
+                        timing,
+                        voice_id,
+                        channel: Some(channel),
+                        note: Some(note),
+                        brightness,
+                    });
+                }
+            }
+            NoteEvent::NoteOff { channel, note, .. } | NoteEvent::Choke { channel, note, .. } => {
+                // Wildcard events without a channel release the notes on every member channel
+                for member_channel in 0..16 {
+                    if channel.is_some_and(|channel| channel != member_channel)
+                        || !self.is_member_channel(member_channel)
+                    {
+                        continue;
+                    }
+
+                    let state = &mut self.channels[member_channel as usize];
+                    match note {
+                        Some(note) => state.held_notes &= !(1u128 << (note & 127)),
+                        None => state.held_notes = 0,
+                    }
+                }
+                push(event);
+            }
+            NoteEvent::MidiPitchBend {
+                timing,
+                channel,
+                value,
+            } => match self.member_pitch_bend_range(channel) {
+                Some(range) => {
+                    // The center position is at 8192/16383 and not at 0.5
+                    const PITCH_BEND_RANGE: f32 = ((1 << 14) - 1) as f32;
+                    let raw_value = (value * PITCH_BEND_RANGE)
+                        .round()
+                        .clamp(0.0, PITCH_BEND_RANGE);
+                    let tuning = (raw_value - 8192.0) / 8192.0 * range;
+
+                    let state = &mut self.channels[channel as usize];
+                    state.tuning = tuning;
+                    for note in held_notes(state.held_notes) {
+                        push(NoteEvent::PolyTuning {
+                            timing,
+                            voice_id: None,
+                            channel: Some(channel),
+                            note: Some(note),
+                            tuning,
+                        });
+                    }
+                }
+                None => push(event),
+            },
+            NoteEvent::MidiChannelPressure {
+                timing,
+                channel,
+                pressure,
+            } if self.is_member_channel(channel) => {
+                let state = &mut self.channels[channel as usize];
+                state.pressure = pressure;
+                for note in held_notes(state.held_notes) {
+                    push(NoteEvent::PolyPressure {
+                        timing,
+                        voice_id: None,
+                        channel: Some(channel),
+                        note: Some(note),
+                        pressure,
+                    });
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: control_change::SOUND_CONTROLLER_5,
+                value,
+            } if self.is_member_channel(channel) => {
+                let state = &mut self.channels[channel as usize];
+                state.brightness = Some(value);
+                for note in held_notes(state.held_notes) {
+                    push(NoteEvent::PolyBrightness {
+                        timing,
+                        voice_id: None,
+                        channel: Some(channel),
+                        note: Some(note),
+                        brightness: value,
+                    });
+                }
+            }
+            NoteEvent::MidiCC {
+                channel, cc, value, ..
+            } => {
+                self.handle_rpn_cc(channel, cc, (value * 127.0).round().clamp(0.0, 127.0) as u8);
+                push(event);
+            }
+            event => push(event),
+        }
+    }
+
+    /// The pitch bend range for `channel` if it's a member channel in one of the two zones.
+    fn member_pitch_bend_range(&self, channel: u8) -> Option<f32> {
+        if channel > LOWER_ZONE_MASTER_CHANNEL && channel <= self.lower_zone_members {
+            Some(self.lower_zone_pitch_bend_range)
+        } else if channel < UPPER_ZONE_MASTER_CHANNEL
+            && channel >= UPPER_ZONE_MASTER_CHANNEL - self.upper_zone_members
+        {
+            Some(self.upper_zone_pitch_bend_range)
+        } else {
+            None
+        }
+    }
+
+    /// Keep track of the RPN selection and handle the data entry messages for the RPNs that are
+    /// relevant for MPE.
+    fn handle_rpn_cc(&mut self, channel: u8, cc: u8, value: u8) {
+        let rpn = &mut self.channels[channel as usize].rpn;
+        match cc {
+            control_change::REGISTERED_PARAMETER_NUMBER_MSB => {
+                *rpn = (*rpn & 0x7f) | ((value as u16) << 7);
+            }
+            control_change::REGISTERED_PARAMETER_NUMBER_LSB => {
+                *rpn = (*rpn & !0x7f) | value as u16;
+            }
+            control_change::DATA_ENTRY_MSB => match *rpn {
+                RPN_MPE_CONFIGURATION => self.configure_zone(channel, value),
+                RPN_PITCH_BEND_SENSITIVITY => {
+                    self.set_member_pitch_bend_range(channel, |_| value as f32)
+                }
+                _ => (),
+            },
+            control_change::DATA_ENTRY_LSB if *rpn == RPN_PITCH_BEND_SENSITIVITY => {
+                // The LSB contains the range's cents
+                self.set_member_pitch_bend_range(channel, |range| {
+                    range.trunc() + (value.min(99) as f32 / 100.0)
+                })
+            }
+            _ => (),
+        }
+    }
+
+    /// Handle an MPE Configuration Message. These resize the zone, which also resets the zone's
+    /// pitch bend range to the default. If the other zone would overlap, then it shrinks.
+    fn configure_zone(&mut self, master_channel: u8, num_members: u8) {
+        let num_members = num_members.min(15);
+        match master_channel {
+            LOWER_ZONE_MASTER_CHANNEL => {
+                self.lower_zone_members = num_members;
+                self.lower_zone_pitch_bend_range = DEFAULT_MEMBER_PITCH_BEND_RANGE;
+                self.upper_zone_members = self.upper_zone_members.min(14 - num_members.min(14));
+            }
+            UPPER_ZONE_MASTER_CHANNEL => {
+                self.upper_zone_members = num_members;
+                self.upper_zone_pitch_bend_range = DEFAULT_MEMBER_PITCH_BEND_RANGE;
+                self.lower_zone_members = self.lower_zone_members.min(14 - num_members.min(14));
+            }
+            // MCMs are only valid on the master channels
+            _ => (),
+        }
+    }
+
+    /// Update the pitch bend range of the zone `channel` is a member of. Pitch bend sensitivity
+    /// messages sent to the master channels only apply to the master channel's own pitch bend
+    /// messages, which are passed through as is.
+    fn set_member_pitch_bend_range(&mut self, channel: u8, f: impl FnOnce(f32) -> f32) {
+        if channel > LOWER_ZONE_MASTER_CHANNEL && channel <= self.lower_zone_members {
+            self.lower_zone_pitch_bend_range = f(self.lower_zone_pitch_bend_range);
+        } else if self.is_member_channel(channel) {
+            self.upper_zone_pitch_bend_range = f(self.upper_zone_pitch_bend_range);
+        }
+    }
+}
+
+/// Iterate over the note numbers set in a held note bitmask.
+fn held_notes(mut mask: u128) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+
+        let note = mask.trailing_zeros() as u8;
+        mask &= mask - 1;
+        Some(note)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMING: u32 = 5;
+
+    fn translate(translator: &mut MpeTranslator, event: NoteEvent<()>) -> Vec<NoteEvent<()>> {
+        let mut events = Vec::new();
+        translator.translate(event, |event| events.push(event));
+        events
+    }
+
+    fn cc(channel: u8, cc: u8, value: u8) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing: TIMING,
+            channel,
+            cc,
+            value: value as f32 / 127.0,
+        }
+    }
+
+    fn note_on(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: TIMING,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn pitch_bend(channel: u8, raw_value: u16) -> NoteEvent<()> {
+        NoteEvent::MidiPitchBend {
+            timing: TIMING,
+            channel,
+            value: raw_value as f32 / ((1 << 14) - 1) as f32,
+        }
+    }
+
+    #[test]
+    fn test_member_pitch_bend_to_tuning() {
+        let mut translator = MpeTranslator::default();
+        translate(&mut translator, note_on(1, 60));
+
+        assert_eq!(
+            translate(&mut translator, pitch_bend(1, 8192 + 4096)),
+            [NoteEvent::PolyTuning {
+                timing: TIMING,
+                voice_id: None,
+                channel: Some(1),
+                note: Some(60),
+                tuning: 24.0,
+            }]
+        );
+        assert_eq!(
+            translate(&mut translator, pitch_bend(1, 8192)),
+            [NoteEvent::PolyTuning {
+                timing: TIMING,
+                voice_id: None,
+                channel: Some(1),
+                note: Some(60),
+                tuning: 0.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_note_off() {
+        let mut translator = MpeTranslator::default();
+        translate(&mut translator, note_on(1, 60));
+        translate(&mut translator, note_on(2, 62));
+        translate(&mut translator, note_on(3, 64));
+
+        let event = NoteEvent::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: None,
+            velocity: 0.0,
+        };
+        assert_eq!(translate(&mut translator, event), [event]);
+        for channel in 1..4 {
+            assert_eq!(translate(&mut translator, pitch_bend(channel, 0)), []);
+        }
+
+        // A wildcard note should only release that note on every member channel
+        translate(&mut translator, note_on(1, 60));
+        translate(&mut translator, note_on(2, 62));
+        let event = NoteEvent::Choke {
+            timing: TIMING,
+            voice_id: None,
+            channel: None,
+            note: Some(60),
+        };
+        assert_eq!(translate(&mut translator, event), [event]);
+        assert_eq!(translate(&mut translator, pitch_bend(1, 0)), []);
+        assert_eq!(translate(&mut translator, pitch_bend(2, 0)).len(), 1);
+    }
+
+    #[test]
+    fn test_master_channel_passthrough() {
+        let mut translator = MpeTranslator::default();
+        translate(&mut translator, note_on(0, 60));
+
+        let event = pitch_bend(0, 1234);
+        assert_eq!(translate(&mut translator, event), [event]);
+        let event = cc(0, control_change::SOUND_CONTROLLER_5, 100);
+        assert_eq!(translate(&mut translator, event), [event]);
+    }
+
+    #[test]
+    fn test_initial_expressions() {
+        let mut translator = MpeTranslator::default();
+        translate(
+            &mut translator,
+            cc(2, control_change::SOUND_CONTROLLER_5, 127),
+        );
+        translate(
+            &mut translator,
+            NoteEvent::MidiChannelPressure {
+                timing: TIMING,
+                channel: 2,
+                pressure: 0.5,
+            },
+        );
+
+        assert_eq!(
+            translate(&mut translator, note_on(2, 64)),
+            [
+                note_on(2, 64),
+                NoteEvent::PolyPressure {
+                    timing: TIMING,
+                    voice_id: None,
+                    channel: Some(2),
+                    note: Some(64),
+                    pressure: 0.5,
+                },
+                NoteEvent::PolyBrightness {
+                    timing: TIMING,
+                    voice_id: None,
+                    channel: Some(2),
+                    note: Some(64),
+                    brightness: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pitch_bend_sensitivity_rpn() {
+        let mut translator = MpeTranslator::default();
+        for event in [
+            cc(3, control_change::REGISTERED_PARAMETER_NUMBER_MSB, 0),
+            cc(3, control_change::REGISTERED_PARAMETER_NUMBER_LSB, 0),
+            cc(3, control_change::DATA_ENTRY_MSB, 12),
+        ] {
+            translate(&mut translator, event);
+        }
+        translate(&mut translator, note_on(4, 60));
+
+        assert_eq!(
+            translate(&mut translator, pitch_bend(4, 0)),
+            [NoteEvent::PolyTuning {
+                timing: TIMING,
+                voice_id: None,
+                channel: Some(4),
+                note: Some(60),
+                tuning: -12.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_mpe_configuration_message() {
+        let mut translator = MpeTranslator::default();
+        // This sets up an upper zone with 5 member channels, which shrinks the lower zone to the
+        // first nine member channels
+        for event in [
+            cc(15, control_change::REGISTERED_PARAMETER_NUMBER_MSB, 0),
+            cc(15, control_change::REGISTERED_PARAMETER_NUMBER_LSB, 6),
+            cc(15, control_change::DATA_ENTRY_MSB, 5),
+        ] {
+            translate(&mut translator, event);
+        }
+
+        assert!(!translator.is_member_channel(0));
+        assert!(translator.is_member_channel(9));
+        assert!(translator.is_member_channel(10));
+        assert!(translator.is_member_channel(14));
+        assert!(!translator.is_member_channel(15));
+
+        // Channels that are no longer member channels pass their pitch bend through
+        translator.configure_zone(LOWER_ZONE_MASTER_CHANNEL, 0);
+        let event = pitch_bend(5, 0);
+        assert_eq!(translate(&mut translator, event), [event]);
+    }
+}
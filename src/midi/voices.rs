@@ -0,0 +1,1120 @@
+//! Voice management for polyphonic instruments.
+//!
+//! [`VoiceManager`] takes care of the bookkeeping every synthesizer needs to do on top of actually
+//! rendering its voices: allocating voices for new notes, stealing voices when all of them are in
+//! use, monophonic and legato note priority, sustain pedal handling, routing polyphonic modulation
+//! and note expression events to the right voices, and informing the host about terminated voices
+//! and the current voice capacity. The plugin only needs to define what a voice is, how it's
+//! created, and how it reacts to [`VoiceUpdate`]s.
+
+use super::{control_change, NoteEvent, PluginNoteEvent};
+use crate::context::init::InitContext;
+use crate::context::process::ProcessContext;
+use crate::prelude::Plugin;
+
+/// The maximum number of held notes remembered for [`VoiceMode::Mono`] and [`VoiceMode::Legato`].
+/// When more notes than this are held at the same time, the oldest held note is forgotten.
+const MAX_HELD_NOTES: usize = 128;
+
+/// A single synthesizer voice managed by a [`VoiceManager`]. Everything else the voice manager
+/// needs to tell a voice is sent through [`VoiceUpdate`]s.
+pub trait Voice {
+    /// Whether the voice has finished playing. This is checked in
+    /// [`VoiceManager::finish_block()`], and voices that have finished are removed and reported to
+    /// the host as terminated. This will usually return `true` once the voice has been released
+    /// and its amplitude envelope has reached zero.
+    fn is_finished(&self) -> bool;
+
+    /// The voice's current amplitude. This is only used for [`VoiceStealing::Quietest`]. The
+    /// scale does not matter as long as it's the same for every voice.
+    fn amplitude(&self) -> f32 {
+        1.0
+    }
+}
+
+/// Determines which voice is stolen when a new note is played while all voices are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// Steal the voice that was started the longest time ago. Voices that have already been
+    /// released are stolen before voices that are still held.
+    Oldest,
+    /// Steal the voice with the lowest [`Voice::amplitude()`].
+    Quietest,
+    /// Playing a note that already has a voice on the same channel always steals that voice, even
+    /// if there are still free voices. The old voice is terminated and a new voice is started in
+    /// its place. Otherwise this behaves the same as [`VoiceStealing::Oldest`].
+    SameNote,
+}
+
+/// How new notes are assigned to voices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceMode {
+    /// Every note gets its own voice, up to the voice manager's polyphony.
+    Poly,
+    /// Only a single voice plays at a time, and every new note restarts it. Releasing the most
+    /// recent note while older notes are still held restarts the voice for the last of those
+    /// notes.
+    Mono,
+    /// Like [`VoiceMode::Mono`], but notes played or returned to while the voice is still held
+    /// are sent to the existing voice as a [`VoiceUpdate::Legato`] instead of restarting it.
+    Legato,
+}
+
+/// The note a voice is currently playing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteInfo {
+    /// The note's sample offset within the current buffer.
+    pub timing: u32,
+    /// The note's voice ID. If the host did not provide one, then this is computed from the
+    /// channel and note number. Polyphonic modulation will not work in that case, but everything
+    /// else will.
+    pub voice_id: i32,
+    /// The note's channel, in `0..16`.
+    pub channel: u8,
+    /// The note's MIDI key number, in `0..128`.
+    pub note: u8,
+    /// The note's velocity, in `[0, 1]`.
+    pub velocity: f32,
+}
+
+/// Something that happened to a voice. These are passed to the update callback of
+/// [`VoiceManager::handle_event()`] together with the voice they apply to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceUpdate {
+    /// The voice's note has been released, taking the sustain pedal into account. The voice
+    /// should start its release stage and return `true` from [`Voice::is_finished()`] once it's
+    /// done.
+    Release { velocity: f32 },
+    /// In [`VoiceMode::Legato`], the voice should continue playing the new note without
+    /// retriggering its envelopes.
+    Legato(NoteInfo),
+    /// Polyphonic modulation for this voice. See [`NoteEvent::PolyModulation`]. `just_started` is
+    /// set when the voice was started during the current block, in which case the voice should
+    /// immediately use the modulated value instead of smoothing towards it.
+    PolyModulation {
+        poly_modulation_id: u32,
+        normalized_offset: f32,
+        just_started: bool,
+    },
+    /// A new automation value for a polyphonically modulated parameter. See
+    /// [`NoteEvent::MonoAutomation`]. This is sent to every active voice.
+    MonoAutomation {
+        poly_modulation_id: u32,
+        normalized_value: f32,
+    },
+    /// A polyphonic automation value for this voice. See [`NoteEvent::PolyAutomation`].
+    PolyAutomation {
+        poly_modulation_id: u32,
+        normalized_value: f32,
+    },
+    /// See [`NoteEvent::PolyPressure`].
+    Pressure(f32),
+    /// See [`NoteEvent::PolyVolume`].
+    Volume(f32),
+    /// See [`NoteEvent::PolyPan`].
+    Pan(f32),
+    /// See [`NoteEvent::PolyTuning`].
+    Tuning(f32),
+    /// See [`NoteEvent::PolyVibrato`].
+    Vibrato(f32),
+    /// See [`NoteEvent::PolyExpression`].
+    Expression(f32),
+    /// See [`NoteEvent::PolyBrightness`].
+    Brightness(f32),
+}
+
+/// A fixed-capacity voice allocator. All memory is allocated up front in [`VoiceManager::new()`],
+/// so the voice manager can be used freely on the audio thread.
+///
+/// Events from [`ProcessContext::next_event()`] are passed to [`VoiceManager::handle_event()`],
+/// which starts, steals, releases, and updates voices as needed. Stolen and choked voices are
+/// reported to the host using [`NoteEvent::VoiceTerminated`] events right away. After rendering a
+/// block of audio using [`VoiceManager::voices_mut()`], the plugin must call
+/// [`VoiceManager::finish_block()`] to remove the voices that have finished playing. That also
+/// informs the host when the voice capacity changes. Call [`VoiceManager::initialize()`] from the
+/// plugin's `initialize()` function so the host knows the initial voice capacity.
+///
+/// Sustain pedal (CC64), all notes off (CC123), and all sound off (CC120) messages are only
+/// received when the plugin's [`Plugin::MIDI_INPUT`] is set to
+/// [`MidiConfig::MidiCCs`][super::MidiConfig::MidiCCs] or higher.
+#[derive(Debug)]
+pub struct VoiceManager<V> {
+    /// The voices, with `None` values for unused voices. This always contains `max_voices`
+    /// elements.
+    slots: Vec<Option<VoiceSlot<V>>>,
+    /// The number of voices that can be used in [`VoiceMode::Poly`], in `1..=slots.len()`.
+    polyphony: usize,
+    mode: VoiceMode,
+    stealing: VoiceStealing,
+
+    /// The notes that are currently held in [`VoiceMode::Mono`] and [`VoiceMode::Legato`], in the
+    /// order they were played in. The last note is the one that should be playing.
+    held_notes: Vec<HeldNote>,
+    /// Whether the sustain pedal is currently pressed, per MIDI channel.
+    sustain: [bool; 16],
+
+    /// The internal ID for the next voice. This is used to find the oldest voice.
+    next_internal_voice_id: u64,
+    /// The last voice capacity sent to the host, if any.
+    reported_capacity: Option<u32>,
+}
+
+/// An active voice and the bookkeeping that goes with it.
+#[derive(Debug)]
+struct VoiceSlot<V> {
+    voice: V,
+    info: NoteInfo,
+    /// Increases by one for every voice that's started. Used to find the oldest voice.
+    internal_voice_id: u64,
+    /// Whether [`VoiceUpdate::Release`] has already been sent to this voice.
+    released: bool,
+    /// Whether the note has been released while the sustain pedal was held down. The voice will
+    /// be released when the pedal is lifted.
+    sustained: bool,
+    /// Whether the voice was started during the current block. Cleared in
+    /// [`VoiceManager::finish_block()`].
+    just_started: bool,
+}
+
+/// A note held in [`VoiceMode::Mono`] or [`VoiceMode::Legato`].
+#[derive(Debug, Clone, Copy)]
+struct HeldNote {
+    info: NoteInfo,
+    /// Whether the note has been released while the sustain pedal was held down.
+    sustained: bool,
+}
+
+impl<V: Voice> VoiceManager<V> {
+    /// Create a voice manager that can play up to `max_voices` voices at the same time. This
+    /// should match the `max_voice_capacity` from the plugin's
+    /// [`PolyModulationConfig`][crate::prelude::PolyModulationConfig]. The polyphony starts out at
+    /// `max_voices`, the mode at [`VoiceMode::Poly`], and voice stealing at
+    /// [`VoiceStealing::Oldest`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_voices` is zero.
+    pub fn new(max_voices: usize) -> Self {
+        assert!(max_voices > 0, "A voice manager needs at least one voice");
+
+        let mut slots = Vec::with_capacity(max_voices);
+        slots.resize_with(max_voices, || None);
+
+        Self {
+            slots,
+            polyphony: max_voices,
+            mode: VoiceMode::Poly,
+            stealing: VoiceStealing::Oldest,
+
+            held_notes: Vec::with_capacity(MAX_HELD_NOTES),
+            sustain: [false; 16],
+
+            next_internal_voice_id: 0,
+            reported_capacity: None,
+        }
+    }
+
+    /// The maximum number of voices, as passed to [`VoiceManager::new()`].
+    pub fn max_voices(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The number of voices that can be used in [`VoiceMode::Poly`].
+    pub fn polyphony(&self) -> usize {
+        self.polyphony
+    }
+
+    /// Change the number of voices that can be used in [`VoiceMode::Poly`]. This is clamped to
+    /// `1..=max_voices`. Lowering the polyphony does not stop any active voices, but new notes
+    /// will steal voices until the number of active voices fits within the new limit.
+    pub fn set_polyphony(&mut self, polyphony: usize) {
+        self.polyphony = polyphony.clamp(1, self.max_voices());
+    }
+
+    /// How new notes are assigned to voices.
+    pub fn mode(&self) -> VoiceMode {
+        self.mode
+    }
+
+    /// Change how new notes are assigned to voices. Active voices keep playing. Notes held in
+    /// [`VoiceMode::Mono`] or [`VoiceMode::Legato`] that are not currently playing are forgotten
+    /// and reported to the host as terminated at `timing`, so this should be called from the
+    /// plugin's `process()` function.
+    pub fn set_mode<P: Plugin>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        timing: u32,
+        mode: VoiceMode,
+    ) {
+        self.set_mode_with(timing, mode, &mut |event| context.send_event(event));
+    }
+
+    /// Which voice is stolen when all voices are in use.
+    pub fn stealing(&self) -> VoiceStealing {
+        self.stealing
+    }
+
+    /// Change which voice is stolen when all voices are in use.
+    pub fn set_stealing(&mut self, stealing: VoiceStealing) {
+        self.stealing = stealing;
+    }
+
+    /// The number of voices that can currently play at the same time. This is what's reported to
+    /// the host as the current voice capacity.
+    pub fn voice_capacity(&self) -> u32 {
+        match self.mode {
+            VoiceMode::Poly => self.polyphony as u32,
+            VoiceMode::Mono | VoiceMode::Legato => 1,
+        }
+    }
+
+    /// The number of voices that are currently active.
+    pub fn num_active_voices(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    /// Iterate over all active voices.
+    pub fn voices(&self) -> impl Iterator<Item = &V> {
+        self.slots.iter().flatten().map(|slot| &slot.voice)
+    }
+
+    /// Iterate over all active voices. Use this to render the voices.
+    pub fn voices_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.slots.iter_mut().flatten().map(|slot| &mut slot.voice)
+    }
+
+    /// Inform the host about the current voice capacity. Call this from the plugin's
+    /// `initialize()` function.
+    pub fn initialize<P: Plugin>(&mut self, context: &impl InitContext<P>) {
+        let capacity = self.voice_capacity();
+        context.set_current_voice_capacity(capacity);
+        self.reported_capacity = Some(capacity);
+    }
+
+    /// Stop all voices without informing the host. Call this from the plugin's `reset()`
+    /// function.
+    pub fn reset(&mut self) {
+        self.slots.fill_with(|| None);
+        self.held_notes.clear();
+        self.sustain = [false; 16];
+        self.next_internal_voice_id = 0;
+    }
+
+    /// Handle a note event. `new_voice` is called to create a voice when a note starts a new
+    /// voice, and `update` is called for every [`VoiceUpdate`] sent to an existing voice. Returns
+    /// `false` if the voice manager has no use for the event, in which case the plugin may want to
+    /// handle it itself.
+    pub fn handle_event<P: Plugin>(
+        &mut self,
+        context: &mut impl ProcessContext<P>,
+        event: &PluginNoteEvent<P>,
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        update: impl FnMut(&mut V, VoiceUpdate),
+    ) -> bool {
+        self.handle_event_with(
+            event,
+            &mut |event| context.send_event(event),
+            new_voice,
+            update,
+        )
+    }
+
+    /// Remove all voices that have finished playing and inform the host that they have been
+    /// terminated. The voice capacity is also reported to the host if it has changed. This must
+    /// be called after rendering every block, where `timing` is the block's end.
+    pub fn finish_block<P: Plugin>(&mut self, context: &mut impl ProcessContext<P>, timing: u32) {
+        self.terminate_finished_voices(timing, &mut |event| context.send_event(event));
+
+        let capacity = self.voice_capacity();
+        if self.reported_capacity != Some(capacity) {
+            context.set_current_voice_capacity(capacity);
+            self.reported_capacity = Some(capacity);
+        }
+    }
+
+    /// [`VoiceManager::set_mode()`], with the terminated voice events sent to a closure.
+    fn set_mode_with<S>(
+        &mut self,
+        timing: u32,
+        mode: VoiceMode,
+        send: &mut impl FnMut(NoteEvent<S>),
+    ) {
+        if mode != self.mode {
+            for held in std::mem::take(&mut self.held_notes) {
+                if !self.is_playing(held.info.voice_id) {
+                    send(voice_terminated(timing, &held.info));
+                }
+            }
+        }
+
+        self.mode = mode;
+    }
+
+    /// [`VoiceManager::handle_event()`], but with terminated voice events sent to a closure so it
+    /// can be tested without a process context.
+    fn handle_event_with<S>(
+        &mut self,
+        event: &NoteEvent<S>,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        mut update: impl FnMut(&mut V, VoiceUpdate),
+    ) -> bool {
+        match *event {
+            NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity,
+            } => {
+                let info = NoteInfo {
+                    timing,
+                    voice_id: voice_id.unwrap_or_else(|| compute_fallback_voice_id(note, channel)),
+                    channel,
+                    note,
+                    velocity,
+                };
+
+                match self.mode {
+                    VoiceMode::Poly => self.note_on_poly(info, send, new_voice),
+                    VoiceMode::Mono | VoiceMode::Legato => {
+                        self.note_on_mono(info, send, new_voice, &mut update)
+                    }
+                }
+            }
+            NoteEvent::NoteOff {
+                timing, velocity, ..
+            } => {
+                self.note_off(event, timing, velocity, send, new_voice, &mut update);
+            }
+            NoteEvent::Choke { timing, .. } => self.choke(event, timing, send),
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: control_change::DAMPER_PEDAL,
+                value,
+            } => {
+                let sustain = value >= 0.5;
+                let was_sustained = std::mem::replace(&mut self.sustain[channel as usize], sustain);
+                if was_sustained && !sustain {
+                    self.release_sustained_notes(timing, channel, send, new_voice, &mut update);
+                }
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: control_change::ALL_NOTES_OFF,
+                ..
+            } => {
+                let note_off: NoteEvent<S> = NoteEvent::NoteOff {
+                    timing,
+                    voice_id: None,
+                    channel: Some(channel),
+                    note: None,
+                    velocity: 0.0,
+                };
+                self.note_off(&note_off, timing, 0.0, send, new_voice, &mut update);
+            }
+            NoteEvent::MidiCC {
+                timing,
+                channel,
+                cc: control_change::ALL_SOUND_OFF,
+                ..
+            } => {
+                let choke: NoteEvent<S> = NoteEvent::Choke {
+                    timing,
+                    voice_id: None,
+                    channel: Some(channel),
+                    note: None,
+                };
+                self.choke(&choke, timing, send);
+            }
+            NoteEvent::PolyModulation {
+                poly_modulation_id,
+                normalized_offset,
+                ..
+            }
+            | NoteEvent::ScopedPolyModulation {
+                poly_modulation_id,
+                normalized_offset,
+                ..
+            } => {
+                // Modulation may arrive before a voice has started or after it has been terminated
+                // (because the host doesn't know that yet), so not finding a voice is fine
+                for slot in self.matching_slots(event) {
+                    update(
+                        &mut slot.voice,
+                        VoiceUpdate::PolyModulation {
+                            poly_modulation_id,
+                            normalized_offset,
+                            just_started: slot.just_started,
+                        },
+                    );
+                }
+            }
+            NoteEvent::MonoAutomation {
+                poly_modulation_id,
+                normalized_value,
+                ..
+            } => {
+                for slot in self.slots.iter_mut().flatten() {
+                    update(
+                        &mut slot.voice,
+                        VoiceUpdate::MonoAutomation {
+                            poly_modulation_id,
+                            normalized_value,
+                        },
+                    );
+                }
+            }
+            NoteEvent::PolyAutomation {
+                poly_modulation_id,
+                normalized_value,
+                ..
+            } => {
+                for slot in self.matching_slots(event) {
+                    update(
+                        &mut slot.voice,
+                        VoiceUpdate::PolyAutomation {
+                            poly_modulation_id,
+                            normalized_value,
+                        },
+                    );
+                }
+            }
+            NoteEvent::PolyPressure { pressure, .. } => {
+                self.update_matching(event, VoiceUpdate::Pressure(pressure), &mut update)
+            }
+            NoteEvent::PolyVolume { gain, .. } => {
+                self.update_matching(event, VoiceUpdate::Volume(gain), &mut update)
+            }
+            NoteEvent::PolyPan { pan, .. } => {
+                self.update_matching(event, VoiceUpdate::Pan(pan), &mut update)
+            }
+            NoteEvent::PolyTuning { tuning, .. } => {
+                self.update_matching(event, VoiceUpdate::Tuning(tuning), &mut update)
+            }
+            NoteEvent::PolyVibrato { vibrato, .. } => {
+                self.update_matching(event, VoiceUpdate::Vibrato(vibrato), &mut update)
+            }
+            NoteEvent::PolyExpression { expression, .. } => {
+                self.update_matching(event, VoiceUpdate::Expression(expression), &mut update)
+            }
+            NoteEvent::PolyBrightness { brightness, .. } => {
+                self.update_matching(event, VoiceUpdate::Brightness(brightness), &mut update)
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// [`VoiceManager::finish_block()`]'s voice termination, with the events sent to a closure.
+    fn terminate_finished_voices<S>(&mut self, timing: u32, send: &mut impl FnMut(NoteEvent<S>)) {
+        for slot in self.slots.iter_mut() {
+            match slot {
+                Some(active) if active.voice.is_finished() => {
+                    let info = active.info;
+                    *slot = None;
+                    if !self
+                        .held_notes
+                        .iter()
+                        .any(|h| h.info.voice_id == info.voice_id)
+                    {
+                        send(voice_terminated(timing, &info));
+                    }
+                }
+                Some(active) => active.just_started = false,
+                None => (),
+            }
+        }
+    }
+
+    fn note_on_poly<S>(
+        &mut self,
+        info: NoteInfo,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+    ) {
+        let same_note_idx = if self.stealing == VoiceStealing::SameNote {
+            self.slots.iter().position(|slot| {
+                matches!(slot, Some(slot) if slot.info.channel == info.channel && slot.info.note == info.note)
+            })
+        } else {
+            None
+        };
+
+        let slot_idx = match same_note_idx {
+            Some(slot_idx) => slot_idx,
+            None if self.num_active_voices() < self.polyphony => {
+                // There's always a free slot here since `polyphony <= max_voices`
+                self.slots.iter().position(|slot| slot.is_none()).unwrap()
+            }
+            None => self.find_voice_to_steal(),
+        };
+
+        if let Some(stolen) = self.slots[slot_idx].take() {
+            send(voice_terminated(info.timing, &stolen.info));
+        }
+        self.start_voice(slot_idx, info, new_voice);
+    }
+
+    fn note_on_mono<S>(
+        &mut self,
+        info: NoteInfo,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        // Playing the same note again moves it to the top of the stack
+        if let Some(held_idx) = self
+            .held_notes
+            .iter()
+            .position(|held| held.info.voice_id == info.voice_id)
+        {
+            self.held_notes.remove(held_idx);
+        } else if self.held_notes.len() == MAX_HELD_NOTES {
+            let forgotten = self.held_notes.remove(0);
+            if !self.is_playing(forgotten.info.voice_id) {
+                send(voice_terminated(info.timing, &forgotten.info));
+            }
+        }
+        self.held_notes.push(HeldNote {
+            info,
+            sustained: false,
+        });
+
+        self.switch_mono_voice(info, send, new_voice, update);
+    }
+
+    fn note_off<S>(
+        &mut self,
+        event: &NoteEvent<S>,
+        timing: u32,
+        velocity: f32,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        let single_voice = event.voice_id().is_some();
+
+        let mut held_idx = 0;
+        while held_idx < self.held_notes.len() {
+            let held = &mut self.held_notes[held_idx];
+            if !event.matches_voice(Some(held.info.voice_id), held.info.channel, held.info.note) {
+                held_idx += 1;
+                continue;
+            }
+
+            if self.sustain[held.info.channel as usize] {
+                held.sustained = true;
+                held_idx += 1;
+            } else {
+                let released = self.held_notes.remove(held_idx);
+                if !self.is_playing(released.info.voice_id) {
+                    send(voice_terminated(timing, &released.info));
+                }
+            }
+
+            if single_voice {
+                break;
+            }
+        }
+
+        let mut new_voice = Some(new_voice);
+        for slot_idx in 0..self.slots.len() {
+            let slot = match &mut self.slots[slot_idx] {
+                Some(slot)
+                    if !slot.released
+                        && event.matches_voice(
+                            Some(slot.info.voice_id),
+                            slot.info.channel,
+                            slot.info.note,
+                        ) =>
+                {
+                    slot
+                }
+                _ => continue,
+            };
+
+            if self.sustain[slot.info.channel as usize] {
+                slot.sustained = true;
+            } else {
+                self.release_slot(slot_idx, timing, velocity, send, &mut new_voice, update);
+            }
+
+            // If this targetted a single voice ID, we're done here. Otherwise the host may release
+            // all notes on a channel, or there may be overlapping voices for the same note.
+            if single_voice {
+                break;
+            }
+        }
+    }
+
+    fn choke<S>(&mut self, event: &NoteEvent<S>, timing: u32, send: &mut impl FnMut(NoteEvent<S>)) {
+        let single_voice = event.voice_id().is_some();
+
+        for slot in self.slots.iter_mut() {
+            match slot {
+                Some(active)
+                    if event.matches_voice(
+                        Some(active.info.voice_id),
+                        active.info.channel,
+                        active.info.note,
+                    ) =>
+                {
+                    send(voice_terminated(timing, &active.info));
+                    *slot = None;
+
+                    if single_voice {
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Held notes that are not playing have not been terminated yet
+        let mut held_idx = 0;
+        while held_idx < self.held_notes.len() {
+            let held = self.held_notes[held_idx];
+            if event.matches_voice(Some(held.info.voice_id), held.info.channel, held.info.note) {
+                self.held_notes.remove(held_idx);
+                if !self.is_playing(held.info.voice_id) {
+                    send(voice_terminated(timing, &held.info));
+                }
+            } else {
+                held_idx += 1;
+            }
+        }
+    }
+
+    /// Release all notes on `channel` that were held by the sustain pedal.
+    fn release_sustained_notes<S>(
+        &mut self,
+        timing: u32,
+        channel: u8,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        let mut held_idx = 0;
+        while held_idx < self.held_notes.len() {
+            let held = self.held_notes[held_idx];
+            if held.sustained && held.info.channel == channel {
+                self.held_notes.remove(held_idx);
+                if !self.is_playing(held.info.voice_id) {
+                    send(voice_terminated(timing, &held.info));
+                }
+            } else {
+                held_idx += 1;
+            }
+        }
+
+        let mut new_voice = Some(new_voice);
+        for slot_idx in 0..self.slots.len() {
+            match &mut self.slots[slot_idx] {
+                Some(slot) if slot.sustained && slot.info.channel == channel => {
+                    slot.sustained = false;
+                    self.release_slot(slot_idx, timing, 0.0, send, &mut new_voice, update);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Release the voice in a slot. In the monophonic modes the voice that's currently playing
+    /// switches back to the last held note instead if there is one.
+    fn release_slot<S>(
+        &mut self,
+        slot_idx: usize,
+        timing: u32,
+        velocity: f32,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: &mut Option<impl FnOnce(&NoteInfo) -> V>,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        if self.mode != VoiceMode::Poly && Some(slot_idx) == self.newest_slot_idx() {
+            if let (Some(held), Some(_)) = (self.held_notes.last(), new_voice.as_ref()) {
+                let info = NoteInfo {
+                    timing,
+                    ..held.info
+                };
+                self.switch_mono_voice(info, send, new_voice.take().unwrap(), update);
+                return;
+            }
+        }
+
+        let slot = self.slots[slot_idx].as_mut().unwrap();
+        slot.released = true;
+        update(&mut slot.voice, VoiceUpdate::Release { velocity });
+    }
+
+    /// Make the monophonic voice play `info`, either by starting a new voice or, in legato mode,
+    /// by sending the note to the voice that's still held.
+    fn switch_mono_voice<S>(
+        &mut self,
+        info: NoteInfo,
+        send: &mut impl FnMut(NoteEvent<S>),
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        let slot_idx = match self.newest_slot_idx() {
+            Some(slot_idx) => slot_idx,
+            None => {
+                self.start_voice(0, info, new_voice);
+                return;
+            }
+        };
+
+        let slot = self.slots[slot_idx].as_mut().unwrap();
+        let previous_info = slot.info;
+        if self.mode == VoiceMode::Legato && !slot.released {
+            slot.info = info;
+            slot.sustained = false;
+            slot.just_started = true;
+            update(&mut slot.voice, VoiceUpdate::Legato(info));
+        } else {
+            self.slots[slot_idx] = None;
+            self.start_voice(slot_idx, info, new_voice);
+        }
+
+        if previous_info.voice_id != info.voice_id
+            && !self
+                .held_notes
+                .iter()
+                .any(|held| held.info.voice_id == previous_info.voice_id)
+        {
+            send(voice_terminated(info.timing, &previous_info));
+        }
+    }
+
+    fn start_voice(
+        &mut self,
+        slot_idx: usize,
+        info: NoteInfo,
+        new_voice: impl FnOnce(&NoteInfo) -> V,
+    ) {
+        self.slots[slot_idx] = Some(VoiceSlot {
+            voice: new_voice(&info),
+            info,
+            internal_voice_id: self.next_internal_voice_id,
+            released: false,
+            sustained: false,
+            just_started: true,
+        });
+        self.next_internal_voice_id = self.next_internal_voice_id.wrapping_add(1);
+    }
+
+    /// Find the voice to steal according to the stealing mode. Must only be called when there is
+    /// at least one active voice.
+    fn find_voice_to_steal(&self) -> usize {
+        let active_slots = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot_idx, slot)| slot.as_ref().map(|slot| (slot_idx, slot)));
+
+        let victim = match self.stealing {
+            VoiceStealing::Oldest | VoiceStealing::SameNote => {
+                active_slots.min_by_key(|(_, slot)| (!slot.released, slot.internal_voice_id))
+            }
+            VoiceStealing::Quietest => active_slots
+                .min_by(|(_, a), (_, b)| a.voice.amplitude().total_cmp(&b.voice.amplitude())),
+        };
+
+        victim.expect("No voices to steal").0
+    }
+
+    /// The index of the most recently started voice. In the monophonic modes this is the voice
+    /// that's playing the current note.
+    fn newest_slot_idx(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot_idx, slot)| slot.as_ref().map(|slot| (slot_idx, slot)))
+            .max_by_key(|(_, slot)| slot.internal_voice_id)
+            .map(|(slot_idx, _)| slot_idx)
+    }
+
+    /// Whether an active voice is currently playing the note with this voice ID.
+    fn is_playing(&self, voice_id: i32) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|slot| slot.info.voice_id == voice_id)
+    }
+
+    /// All active voices matching a polyphonic event, following the same rules as
+    /// [`NoteEvent::matches_voice()`].
+    fn matching_slots<'a, S>(
+        &'a mut self,
+        event: &'a NoteEvent<S>,
+    ) -> impl Iterator<Item = &'a mut VoiceSlot<V>> + 'a {
+        self.slots.iter_mut().flatten().filter(move |slot| {
+            event.matches_voice(Some(slot.info.voice_id), slot.info.channel, slot.info.note)
+        })
+    }
+
+    fn update_matching<S>(
+        &mut self,
+        event: &NoteEvent<S>,
+        voice_update: VoiceUpdate,
+        update: &mut impl FnMut(&mut V, VoiceUpdate),
+    ) {
+        for slot in self.matching_slots(event) {
+            update(&mut slot.voice, voice_update);
+        }
+    }
+}
+
+/// Compute a voice ID in case the host doesn't provide them.
+const fn compute_fallback_voice_id(note: u8, channel: u8) -> i32 {
+    note as i32 | ((channel as i32) << 16)
+}
+
+fn voice_terminated<S>(timing: u32, info: &NoteInfo) -> NoteEvent<S> {
+    NoteEvent::VoiceTerminated {
+        timing,
+        voice_id: Some(info.voice_id),
+        channel: info.channel,
+        note: info.note,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMING: u32 = 5;
+
+    #[derive(Debug)]
+    struct TestVoice {
+        note: u8,
+        amplitude: f32,
+        released: bool,
+        legato_notes: Vec<u8>,
+        poly_modulation: Option<(f32, bool)>,
+    }
+
+    impl Voice for TestVoice {
+        fn is_finished(&self) -> bool {
+            // The release stage is instant
+            self.released
+        }
+
+        fn amplitude(&self) -> f32 {
+            self.amplitude
+        }
+    }
+
+    fn handle(manager: &mut VoiceManager<TestVoice>, event: NoteEvent<()>) -> Vec<NoteEvent<()>> {
+        let mut events = Vec::new();
+        manager.handle_event_with(
+            &event,
+            &mut |event| events.push(event),
+            |info| TestVoice {
+                note: info.note,
+                amplitude: info.velocity,
+                released: false,
+                legato_notes: Vec::new(),
+                poly_modulation: None,
+            },
+            |voice, update| match update {
+                VoiceUpdate::Release { .. } => voice.released = true,
+                VoiceUpdate::Legato(info) => voice.legato_notes.push(info.note),
+                VoiceUpdate::PolyModulation {
+                    normalized_offset,
+                    just_started,
+                    ..
+                } => voice.poly_modulation = Some((normalized_offset, just_started)),
+                _ => (),
+            },
+        );
+        events
+    }
+
+    fn finish_block(manager: &mut VoiceManager<TestVoice>) -> Vec<NoteEvent<()>> {
+        let mut events = Vec::new();
+        manager.terminate_finished_voices(TIMING, &mut |event| events.push(event));
+        events
+    }
+
+    fn set_mode(manager: &mut VoiceManager<TestVoice>, mode: VoiceMode) -> Vec<NoteEvent<()>> {
+        let mut events = Vec::new();
+        manager.set_mode_with(TIMING, mode, &mut |event| events.push(event));
+        events
+    }
+
+    fn note_on(note: u8, velocity: f32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: TIMING,
+            voice_id: Some(note as i32),
+            channel: 0,
+            note,
+            velocity,
+        }
+    }
+
+    fn note_off(note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(0),
+            note: Some(note),
+            velocity: 0.0,
+        }
+    }
+
+    fn sustain(pressed: bool) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing: TIMING,
+            channel: 0,
+            cc: control_change::DAMPER_PEDAL,
+            value: if pressed { 1.0 } else { 0.0 },
+        }
+    }
+
+    fn terminated(note: u8) -> NoteEvent<()> {
+        NoteEvent::VoiceTerminated {
+            timing: TIMING,
+            voice_id: Some(note as i32),
+            channel: 0,
+            note,
+        }
+    }
+
+    fn playing_notes(manager: &VoiceManager<TestVoice>) -> Vec<u8> {
+        let mut notes: Vec<u8> = manager.voices().map(|voice| voice.note).collect();
+        notes.sort_unstable();
+        notes
+    }
+
+    #[test]
+    fn test_steal_oldest() {
+        let mut manager = VoiceManager::new(2);
+        assert!(handle(&mut manager, note_on(60, 1.0)).is_empty());
+        assert!(handle(&mut manager, note_on(62, 1.0)).is_empty());
+        assert_eq!(handle(&mut manager, note_on(64, 1.0)), [terminated(60)]);
+        assert_eq!(playing_notes(&manager), [62, 64]);
+
+        // Released voices are stolen first
+        handle(&mut manager, note_off(64));
+        assert_eq!(handle(&mut manager, note_on(65, 1.0)), [terminated(64)]);
+        assert_eq!(playing_notes(&manager), [62, 65]);
+    }
+
+    #[test]
+    fn test_steal_quietest() {
+        let mut manager = VoiceManager::new(2);
+        manager.set_stealing(VoiceStealing::Quietest);
+        handle(&mut manager, note_on(60, 0.8));
+        handle(&mut manager, note_on(62, 0.2));
+        assert_eq!(handle(&mut manager, note_on(64, 1.0)), [terminated(62)]);
+        assert_eq!(playing_notes(&manager), [60, 64]);
+    }
+
+    #[test]
+    fn test_steal_same_note() {
+        let mut manager = VoiceManager::new(4);
+        manager.set_stealing(VoiceStealing::SameNote);
+        handle(&mut manager, note_on(60, 1.0));
+        handle(&mut manager, note_on(62, 1.0));
+        assert_eq!(handle(&mut manager, note_on(60, 1.0)), [terminated(60)]);
+        assert_eq!(playing_notes(&manager), [60, 62]);
+    }
+
+    #[test]
+    fn test_sustain_pedal() {
+        let mut manager = VoiceManager::new(4);
+        handle(&mut manager, sustain(true));
+        handle(&mut manager, note_on(60, 1.0));
+        handle(&mut manager, note_off(60));
+        assert!(finish_block(&mut manager).is_empty());
+        assert_eq!(playing_notes(&manager), [60]);
+
+        handle(&mut manager, sustain(false));
+        assert_eq!(finish_block(&mut manager), [terminated(60)]);
+        assert_eq!(manager.num_active_voices(), 0);
+    }
+
+    #[test]
+    fn test_mono_returns_to_held_note() {
+        let mut manager = VoiceManager::new(4);
+        set_mode(&mut manager, VoiceMode::Mono);
+        assert_eq!(manager.voice_capacity(), 1);
+
+        handle(&mut manager, note_on(60, 1.0));
+        // The first note is still held, so it's not terminated yet
+        assert!(handle(&mut manager, note_on(64, 1.0)).is_empty());
+        assert_eq!(playing_notes(&manager), [64]);
+
+        // Releasing the second note restarts the voice for the first one
+        assert_eq!(handle(&mut manager, note_off(64)), [terminated(64)]);
+        assert_eq!(playing_notes(&manager), [60]);
+        assert!(!manager.voices().next().unwrap().released);
+
+        handle(&mut manager, note_off(60));
+        assert_eq!(finish_block(&mut manager), [terminated(60)]);
+    }
+
+    #[test]
+    fn test_legato() {
+        let mut manager = VoiceManager::new(4);
+        set_mode(&mut manager, VoiceMode::Legato);
+
+        handle(&mut manager, note_on(60, 1.0));
+        handle(&mut manager, note_on(64, 1.0));
+        handle(&mut manager, note_off(64));
+        assert_eq!(manager.num_active_voices(), 1);
+        assert_eq!(manager.voices().next().unwrap().legato_notes, [64, 60]);
+
+        // Releasing a note that isn't playing terminates it right away
+        handle(&mut manager, note_on(67, 1.0));
+        assert_eq!(handle(&mut manager, note_off(60)), [terminated(60)]);
+    }
+
+    #[test]
+    fn test_mode_change_terminates_held_notes() {
+        let mut manager = VoiceManager::new(4);
+        set_mode(&mut manager, VoiceMode::Mono);
+        handle(&mut manager, note_on(60, 1.0));
+        handle(&mut manager, note_on(64, 1.0));
+
+        // The held note without a voice is terminated, the playing voice keeps playing
+        assert_eq!(set_mode(&mut manager, VoiceMode::Poly), [terminated(60)]);
+        assert_eq!(playing_notes(&manager), [64]);
+        assert!(set_mode(&mut manager, VoiceMode::Poly).is_empty());
+
+        handle(&mut manager, note_off(64));
+        assert_eq!(finish_block(&mut manager), [terminated(64)]);
+    }
+
+    #[test]
+    fn test_poly_modulation_routing() {
+        let mut manager = VoiceManager::new(4);
+        handle(&mut manager, note_on(60, 1.0));
+        handle(&mut manager, note_on(62, 1.0));
+
+        let poly_modulation = |normalized_offset| NoteEvent::PolyModulation {
+            timing: TIMING,
+            voice_id: 62,
+            poly_modulation_id: 0,
+            normalized_offset,
+        };
+        handle(&mut manager, poly_modulation(0.5));
+        let modulation = |manager: &VoiceManager<TestVoice>, note| {
+            manager
+                .voices()
+                .find(|voice| voice.note == note)
+                .unwrap()
+                .poly_modulation
+        };
+        assert_eq!(modulation(&manager, 60), None);
+        assert_eq!(modulation(&manager, 62), Some((0.5, true)));
+
+        finish_block(&mut manager);
+        handle(&mut manager, poly_modulation(0.25));
+        assert_eq!(modulation(&manager, 62), Some((0.25, false)));
+    }
+}
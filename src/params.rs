@@ -87,8 +87,11 @@ pub trait Param: Display + Debug + sealed::Sealed {
     /// plugin, then polyphonic modulation will be enabled for that parameter. Polyphonic modulation
     /// is communicated to the plugin through
     /// [`NoteEvent::PolyModulation`][crate::prelude::NoteEvent::PolyModulation] and
-    /// [`NoteEvent::MonoAutomation`][crate::prelude::NoteEvent::MonoAutomation] events. See the
-    /// documentation on those events for more information.
+    /// [`NoteEvent::MonoAutomation`][crate::prelude::NoteEvent::MonoAutomation] events, as well as
+    /// [`NoteEvent::ScopedPolyModulation`][crate::prelude::NoteEvent::ScopedPolyModulation] and
+    /// [`NoteEvent::PolyAutomation`][crate::prelude::NoteEvent::PolyAutomation] events if the
+    /// plugin's [`PolyModulationConfig`][crate::prelude::PolyModulationConfig] enables those. See
+    /// the documentation on those events for more information.
     ///
     /// # Important
     ///
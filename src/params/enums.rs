@@ -435,15 +435,20 @@ impl EnumParamInner {
     /// Set the parameter based on a serialized stable string identifier. Return whether the ID was
     /// known and the parameter was set.
     pub fn set_from_id(&self, id: &str) -> bool {
-        match self
-            .ids
-            .and_then(|ids| ids.iter().position(|candidate| *candidate == id))
-        {
+        match self.index_from_id(id) {
             Some(index) => {
-                self.set_plain_value(index as i32);
+                self.set_plain_value(index);
                 true
             }
             None => false,
         }
     }
+
+    /// Get the variant index belonging to a serialized stable string identifier. Returns `None` if
+    /// the ID is not known or if this enum parameter doesn't have any stable IDs.
+    pub(crate) fn index_from_id(&self, id: &str) -> Option<i32> {
+        self.ids
+            .and_then(|ids| ids.iter().position(|candidate| *candidate == id))
+            .map(|index| index as i32)
+    }
 }
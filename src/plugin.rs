@@ -1,14 +1,17 @@
 //! Traits and structs describing plugins and editors. This includes extension structs for features
 //! that are specific to one or more plugin-APIs.
 
+use std::any::Any;
 use std::sync::Arc;
 
 use crate::prelude::{
     AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, Buffer, BufferConfig, Editor, InitContext,
-    MidiConfig, Params, PluginState, ProcessContext, SysExMessage,
+    MidiConfig, Params, PluginState, ProcessContext, StateMigration, SysExMessage, TrackInfo,
 };
 
 pub mod clap;
+#[cfg(feature = "lv2")]
+pub mod lv2;
 #[cfg(feature = "vst3")]
 pub mod vst3;
 
@@ -77,8 +80,9 @@ pub trait Plugin: Default + Send + 'static {
     /// auxiliary input and output ports, if the plugin has any. If the slice is empty, then the
     /// plugin will not have any audio IO.
     ///
-    /// Both [`AudioIOLayout`] and [`PortNames`][crate::prelude::PortNames] have `.const_default()`
-    /// functions for compile-time equivalents to `Default::default()`:
+    /// [`AudioIOLayout`], [`PortNames`][crate::prelude::PortNames], and
+    /// [`ChannelLayouts`][crate::prelude::ChannelLayouts] have `.const_default()` functions for
+    /// compile-time equivalents to `Default::default()`:
     ///
     /// ```
     /// # use nih_plug::prelude::*;
@@ -92,6 +96,25 @@ pub trait Plugin: Default + Send + 'static {
     /// }];
     /// ```
     ///
+    /// Surround and ambisonic ports need an explicit channel layout. The layouts are checked at
+    /// compile time when the plugin is exported:
+    ///
+    /// ```
+    /// # use nih_plug::prelude::*;
+    /// const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+    ///     main_input_channels: NonZeroU32::new(16),
+    ///     main_output_channels: NonZeroU32::new(12),
+    ///
+    ///     channel_layouts: ChannelLayouts {
+    ///         main_input: Some(ChannelLayout::AMBISONIC_THIRD_ORDER),
+    ///         main_output: Some(ChannelLayout::SURROUND_7_1_4),
+    ///         ..ChannelLayouts::const_default()
+    ///     },
+    ///
+    ///     ..AudioIOLayout::const_default()
+    /// }];
+    /// ```
+    ///
     /// # Note
     ///
     /// Some plugin hosts, like Ableton Live, don't support MIDI-only plugins and may refuse to load
@@ -117,6 +140,18 @@ pub trait Plugin: Default + Send + 'static {
     /// to do offline processing.
     const HARD_REALTIME_ONLY: bool = false;
 
+    /// Whether the plugin implements [`note_name()`][Self::note_name()]. The wrappers only query
+    /// the plugin's note names and expose them to the host when this is set. The names are
+    /// collected once and then cached until the plugin reports that they have changed.
+    const HAS_NOTE_NAMES: bool = false;
+
+    /// Migrations for state saved by older versions of the plugin. When loading state, every
+    /// migration whose version requirement matches the version the state was saved with is run in
+    /// order, just before [`filter_state()`][Self::filter_state()] is called. This makes it
+    /// possible to rename parameter IDs, rescale their values, change their types, and rewrite
+    /// `#[persist]` fields without losing old presets. See [`StateMigration`] for an example.
+    const STATE_MIGRATIONS: &'static [StateMigration] = &[];
+
     /// The plugin's SysEx message type if it supports sending or receiving MIDI SysEx messages, or
     /// `()` if it does not. This type can be a struct or enum wrapping around one or more message
     /// types, and the [`SysExMessage`] trait is then used to convert between this type and basic
@@ -166,18 +201,126 @@ pub trait Plugin: Default + Send + 'static {
         None
     }
 
-    /// This function is always called just before a [`PluginState`] is loaded. This lets you
-    /// directly modify old plugin state to perform migrations based on the [`PluginState::version`]
-    /// field. Some examples of use cases for this are renaming parameter indices, remapping
-    /// parameter values, and preserving old preset compatibility when introducing new parameters
-    /// with default values that would otherwise change the sound of a preset. Keep in mind that
-    /// automation may still be broken in the first two use cases.
+    /// Returns the name for a key on a specific MIDI channel, if it has one. Hosts may display these
+    /// names in their piano rolls, which is useful for labeling the sounds in a drum sampler.
+    /// `channel` is a zero-indexed MIDI channel and `note` is a MIDI note number. Returning `None`
+    /// lets the host display its own name for the key. This is only used when
+    /// [`MIDI_INPUT`][Self::MIDI_INPUT] and [`HAS_NOTE_NAMES`][Self::HAS_NOTE_NAMES] are set.
+    ///
+    /// The host caches these names, so when they change you need to call
+    /// [`GuiContext::note_names_changed()`][crate::prelude::GuiContext::note_names_changed()] or
+    /// [`ProcessContext::note_names_changed()`][crate::prelude::ProcessContext::note_names_changed()].
+    ///
+    /// # Note
+    ///
+    /// VST3 does not have per-channel note names, so VST3 hosts will only see the names for the
+    /// first channel.
+    fn note_name(&self, channel: u8, note: u8) -> Option<String> {
+        None
+    }
+
+    /// Whether the plugin can use an audio IO layout that's not listed in
+    /// [`AUDIO_IO_LAYOUTS`][Self::AUDIO_IO_LAYOUTS]. When the host asks for different channel
+    /// counts for one or more of the current layout's ports and none of the static layouts match
+    /// those channel counts, then the wrapper creates a layout with the requested channel counts
+    /// and asks the plugin whether it can use it. If this returns `true`, then the plugin will be
+    /// initialized with that layout. The number of ports never changes, and the port names are
+    /// taken from the layout the proposal is based on. This lets channel-agnostic plugins support
+    /// any number of channels without having to list every combination:
+    ///
+    /// ```
+    /// # use nih_plug::prelude::*;
+    /// # #[derive(Default)]
+    /// # struct Gain;
+    /// # impl Plugin for Gain {
+    /// # const NAME: &'static str = "";
+    /// # const VENDOR: &'static str = "";
+    /// # const URL: &'static str = "";
+    /// # const EMAIL: &'static str = "";
+    /// # const VERSION: &'static str = "";
+    /// # const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[];
+    /// # type SysExMessage = ();
+    /// # type BackgroundTask = ();
+    /// # fn params(&self) -> std::sync::Arc<dyn Params> { unimplemented!() }
+    /// # fn process(&mut self, _: &mut Buffer, _: &mut AuxiliaryBuffers, _: &mut impl ProcessContext<Self>) -> ProcessStatus { unimplemented!() }
+    /// fn accept_audio_io_layout(&self, audio_io_layout: &AudioIOLayout) -> bool {
+    ///     let num_channels = audio_io_layout.main_output_channels.map(NonZeroU32::get);
+    ///     audio_io_layout.main_input_channels.map(NonZeroU32::get) == num_channels
+    ///         && matches!(num_channels, Some(1..=64))
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// This is called from the main thread while the plugin is deactivated. The CLAP wrapper uses
+    /// this for the `configurable-audio-ports` extension, and the VST3 wrapper uses it when the
+    /// host calls `IAudioProcessor::setBusArrangements()`. The other wrappers only use the static
+    /// layouts.
+    fn accept_audio_io_layout(&self, audio_io_layout: &AudioIOLayout) -> bool {
+        false
+    }
+
+    /// Called when the host reports information about the track the plugin has been inserted on,
+    /// like the track's name and color. This is called once when the plugin is created if the host
+    /// already provides this information, and again every time it changes. The same information is
+    /// also available through [`InitContext::track_info()`] and
+    /// [`GuiContext::track_info()`][crate::prelude::GuiContext::track_info()]. If you need it in
+    /// the process function, then you can copy the relevant parts to your plugin's object here.
+    ///
+    /// This is called from the main thread. The CLAP wrapper uses the `track-info` extension, and
+    /// the VST3 wrapper uses `IInfoListener::setChannelContextInfos()`.
+    fn track_info_changed(&mut self, track_info: &TrackInfo) {}
+
+    /// This function is always called just before a [`PluginState`] is loaded, after the
+    /// [`STATE_MIGRATIONS`][Self::STATE_MIGRATIONS] have been applied. This lets you directly
+    /// modify old plugin state to perform migrations based on the [`PluginState::version`] field.
+    /// Some examples of use cases for this are renaming parameter indices, remapping parameter
+    /// values, and preserving old preset compatibility when introducing new parameters with default
+    /// values that would otherwise change the sound of a preset. Keep in mind that automation may
+    /// still be broken in the first two use cases.
     ///
     /// # Note
     ///
     /// This is an advanced feature that the vast majority of plugins won't need to implement.
     fn filter_state(state: &mut PluginState) {}
 
+    /// Prepare any heavyweight resources needed for a [`PluginState`] before it is loaded. When the
+    /// host or the editor loads state while the plugin is processing audio, the state is prepared on
+    /// the thread that's loading the state and then swapped in on the audio thread in between two
+    /// process calls. This function is called on that loading thread after
+    /// [`filter_state()`][Self::filter_state()], so this is the place to do things like loading
+    /// samples or building impulse responses. The returned data is passed to
+    /// [`apply_prepared_state()`][Self::apply_prepared_state()] on the audio thread. If this returns
+    /// `None`, then the state is loaded on the loading thread instead while the audio thread waits
+    /// for the plugin to be reinitialized.
+    ///
+    /// The plugin's persistent fields are only restored after the prepared state has been swapped
+    /// in, so any persisted data needed here should be read from `state.fields`.
+    ///
+    /// This does not take `self` as the plugin instance may be in use by the audio thread at the
+    /// same time. It is only called when the plugin has been initialized.
+    fn prepare_state(
+        state: &PluginState,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+    ) -> Option<Box<dyn Any + Send>> {
+        None
+    }
+
+    /// Swap in the resources prepared by [`prepare_state()`][Self::prepare_state()] after the
+    /// parameters have been set to the values from the loaded state. Return `true` if the plugin is
+    /// now ready to process audio with the new state, in which case [`reset()`][Self::reset()] is
+    /// called next. If this returns `false`, which is the default, then the plugin is reinitialized
+    /// using [`initialize()`][Self::initialize()] and [`reset()`][Self::reset()] instead.
+    ///
+    /// This is called from the audio thread when state is loaded during processing, so it must not
+    /// allocate or block. To avoid deallocating the old resources here, you can swap them into the
+    /// prepared data using [`std::mem::swap()`] so they are dropped on the loading thread instead.
+    /// When this returns `false` on the audio thread, the plugin is reinitialized on the loading
+    /// thread rather than on the audio thread.
+    fn apply_prepared_state(&mut self, prepared: Option<&mut (dyn Any + Send)>) -> bool {
+        false
+    }
+
     //
     // The following functions follow the lifetime of the plugin.
     //
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use super::Plugin;
 use crate::prelude::{ClapFeature, RemoteControlsContext};
 
@@ -20,6 +22,13 @@ pub trait ClapPlugin: Plugin {
     /// If set, this informs the host about the plugin's capabilities for polyphonic modulation.
     const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = None;
 
+    /// If set, the plugin's presets are exposed to the host's preset browser through CLAP's
+    /// [preset discovery
+    /// factory](https://github.com/free-audio/clap/blob/main/include/clap/factory/draft/preset-discovery.h),
+    /// and the host can load those presets using the preset-load extension. Presets are read using
+    /// the [`presets`][crate::presets] module, so they need to use NIH-plug's preset file format.
+    const CLAP_PRESET_DISCOVERY_CONFIG: Option<PresetDiscoveryConfig> = None;
+
     /// This function can be implemented to define plugin-specific [remote control
     /// pages](https://github.com/free-audio/clap/blob/main/include/clap/ext/draft/remote-controls.h)
     /// that the host can use to provide better hardware mapping for a plugin. See the linked
@@ -37,4 +46,53 @@ pub struct PolyModulationConfig {
     /// different voice IDs. Bitwig Studio, for instance, can use this to do voice stacking. After
     /// enabling this, you should always prioritize using voice IDs to map note events to voices.
     pub supports_overlapping_voices: bool,
+    /// The ways the host may target polyphonic modulation at notes. With only
+    /// [`PolyModulationScope::NOTE_ID`], modulation for individual voices is sent as
+    /// [`NoteEvent::PolyModulation`][crate::prelude::NoteEvent::PolyModulation] events. Enabling
+    /// the key, channel, or port scopes allows the host to send
+    /// [`NoteEvent::ScopedPolyModulation`][crate::prelude::NoteEvent::ScopedPolyModulation] events
+    /// that affect all voices matching a combination of those properties.
+    pub modulation_scope: PolyModulationScope,
+    /// The ways the host may target automation at notes. Per-note automation is sent as
+    /// [`NoteEvent::PolyAutomation`][crate::prelude::NoteEvent::PolyAutomation] events. Automation
+    /// that targets a combination of properties not included in this scope is treated as regular
+    /// monophonic automation. Set this to [`PolyModulationScope::empty()`] to only allow regular
+    /// monophonic automation.
+    pub automation_scope: PolyModulationScope,
+}
+
+bitflags::bitflags! {
+    /// The note properties the host can use to target polyphonic modulation and automation events
+    /// at a subset of the plugin's voices. These correspond to CLAP's per note ID, per key, per
+    /// channel, and per port parameter flags. Flags can be combined in a constant context using
+    /// `PolyModulationScope::NOTE_ID.union(PolyModulationScope::KEY)`.
+    #[repr(transparent)]
+    #[derive(Default)]
+    pub struct PolyModulationScope: u32 {
+        /// Events may target a single voice using its note ID.
+        const NOTE_ID = 1 << 0;
+        /// Events may target all voices playing a specific key.
+        const KEY = 1 << 1;
+        /// Events may target all voices on a specific MIDI channel.
+        const CHANNEL = 1 << 2;
+        /// Events may target all voices started from a specific note port.
+        const PORT = 1 << 3;
+    }
+}
+
+/// Configuration for the presets the plugin exposes to the host through CLAP's preset discovery
+/// factory. These options mirror those on [`PresetManager`][crate::presets::PresetManager].
+pub struct PresetDiscoveryConfig {
+    /// The file extensions used for the plugin's preset files, without a leading period. This
+    /// should usually be set to `&[PRESET_FILE_EXTENSION]`, see
+    /// [`PRESET_FILE_EXTENSION`][crate::presets::PRESET_FILE_EXTENSION].
+    pub file_extensions: &'static [&'static str],
+    /// Factory presets embedded in the plugin's binary, usually using `include_bytes!()`.
+    pub embedded_presets: &'static [&'static [u8]],
+    /// A function returning the directories containing the plugin's factory presets. This is a
+    /// function so the paths can be determined at runtime.
+    pub factory_directories: fn() -> Vec<PathBuf>,
+    /// Whether the host should also index the user's presets, stored in
+    /// [`default_user_directory()`][crate::presets::default_user_directory()].
+    pub include_user_directory: bool,
 }
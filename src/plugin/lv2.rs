@@ -0,0 +1,18 @@
+use super::Plugin;
+use crate::prelude::Lv2PluginClass;
+
+/// Provides auxiliary metadata needed for an LV2 plugin.
+///
+/// LV2 plugins describe their ports in Turtle files that are read by the host before the plugin
+/// is loaded. These files are generated from the plugin's parameters, its first
+/// [`AudioIOLayout`][crate::prelude::AudioIOLayout], and its MIDI configuration when the plugin is
+/// bundled with `cargo xtask bundle`. Parameters are exposed as control ports with normalized
+/// `[0, 1]` values. LV2 plugins currently do not support editors.
+pub trait Lv2Plugin: Plugin {
+    /// The URI that uniquely identifies this plugin, for instance
+    /// `https://example.com/plugins/plugin-name`. This should never change after the plugin has
+    /// been released.
+    const LV2_URI: &'static str;
+    /// The LV2 plugin classes this plugin belongs to. Hosts may use these to categorize the plugin.
+    const LV2_CLASSES: &'static [Lv2PluginClass];
+}
@@ -5,6 +5,8 @@ pub use std::num::NonZeroU32;
 pub use crate::debug::*;
 
 pub use crate::nih_export_clap;
+#[cfg(feature = "lv2")]
+pub use crate::nih_export_lv2;
 #[cfg(feature = "vst3")]
 pub use crate::nih_export_vst3;
 #[cfg(feature = "standalone")]
@@ -14,18 +16,21 @@ pub use crate::formatters;
 pub use crate::util;
 
 pub use crate::audio_setup::{
-    new_nonzero_u32, AudioIOLayout, AuxiliaryBuffers, BufferConfig, PortNames, ProcessMode,
+    new_nonzero_u32, AmbisonicNormalization, AmbisonicOrdering, AudioIOLayout, AuxiliaryBuffers,
+    BufferConfig, ChannelLayout, ChannelLayouts, PortNames, ProcessMode, Speaker,
 };
 pub use crate::buffer::Buffer;
-pub use crate::context::gui::{AsyncExecutor, GuiContext, ParamSetter};
+pub use crate::context::gui::{AsyncExecutor, ContextMenuItem, GuiContext, ParamSetter};
 pub use crate::context::init::InitContext;
 pub use crate::context::process::{ProcessContext, Transport};
 pub use crate::context::remote_controls::{
     RemoteControlsContext, RemoteControlsPage, RemoteControlsSection,
 };
-pub use crate::context::PluginApi;
+pub use crate::context::{PluginApi, TrackColor, TrackInfo};
 // This also includes the derive macro
-pub use crate::editor::{Editor, ParentWindowHandle};
+pub use crate::editor::{
+    AutomationState, Editor, ParamIndication, ParamMapping, ParentWindowHandle, ResizeHints,
+};
 pub use crate::midi::sysex::SysExMessage;
 pub use crate::midi::{control_change, MidiConfig, NoteEvent, PluginNoteEvent};
 pub use crate::params::enums::{Enum, EnumParam};
@@ -34,11 +39,17 @@ pub use crate::params::range::{FloatRange, IntRange};
 pub use crate::params::smoothing::{AtomicF32, Smoothable, Smoother, SmoothingStyle};
 pub use crate::params::Params;
 pub use crate::params::{BoolParam, FloatParam, IntParam, Param, ParamFlags};
-pub use crate::plugin::clap::{ClapPlugin, PolyModulationConfig};
+pub use crate::plugin::clap::{
+    ClapPlugin, PolyModulationConfig, PolyModulationScope, PresetDiscoveryConfig,
+};
+#[cfg(feature = "lv2")]
+pub use crate::plugin::lv2::Lv2Plugin;
 #[cfg(feature = "vst3")]
 pub use crate::plugin::vst3::Vst3Plugin;
 pub use crate::plugin::{Plugin, ProcessStatus, TaskExecutor};
 pub use crate::wrapper::clap::features::ClapFeature;
-pub use crate::wrapper::state::PluginState;
+#[cfg(feature = "lv2")]
+pub use crate::wrapper::lv2::classes::Lv2PluginClass;
+pub use crate::wrapper::state::{PluginState, StateMigration};
 #[cfg(feature = "vst3")]
 pub use crate::wrapper::vst3::subcategories::Vst3SubCategory;
@@ -0,0 +1,678 @@
+//! Preset management based on the plugin's [`PluginState`]. Presets are stored as JSON files with
+//! some additional metadata. If the `zstd` feature is enabled, then preset files are compressed
+//! using Zstandard, just like the plugin's state. Uncompressed preset files can still be loaded in
+//! that case.
+//!
+//! A [`PresetManager`] keeps track of the user's presets, factory presets installed to one or more
+//! directories, and factory presets embedded in the plugin's binary. It can be shared between the
+//! editor and the plugin's background tasks by wrapping it in an `Arc`. Loading a preset into the
+//! plugin is done through [`GuiContext::set_state()`]:
+//!
+//! ```ignore
+//! let preset_manager = Arc::new(
+//!     PresetManager::new::<MyPlugin>()
+//!         .with_embedded_presets(&[include_bytes!("../presets/init.nihpreset")]),
+//! );
+//!
+//! // Somewhere in the editor
+//! preset_manager.rescan();
+//! preset_manager.load_next(&*gui_context)?;
+//! ```
+
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::prelude::{GuiContext, Plugin, PluginState};
+
+/// The file extension used for preset files, without the leading period.
+pub const PRESET_FILE_EXTENSION: &str = "nihpreset";
+
+/// Used by [`sanitize_file_name()`] for names that don't contain any usable characters.
+const FALLBACK_FILE_NAME: &str = "Untitled";
+/// Device names that cannot be used as file names on Windows, regardless of their extension.
+const RESERVED_FILE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Metadata stored alongside a preset's state.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresetMetadata {
+    /// The preset's name as it should be shown to the user.
+    pub name: String,
+    /// The preset's author. Empty if not set.
+    #[serde(default)]
+    pub author: String,
+    /// Tags or categories for this preset, e.g. `"bass"` or `"pad"`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A free-form description of the preset. Empty if not set.
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// A preset, consisting of some metadata and the plugin's state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub metadata: PresetMetadata,
+    pub state: PluginState,
+}
+
+/// Where a preset in a [`PresetManager`] is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetLocation {
+    /// A factory preset embedded in the plugin's binary. Contains the index into the embedded
+    /// presets passed to [`PresetManager::with_embedded_presets()`].
+    Embedded(usize),
+    /// A factory preset stored in one of the factory preset directories.
+    Factory(PathBuf),
+    /// A preset stored in the user's preset directory.
+    User(PathBuf),
+}
+
+/// A preset found by [`PresetManager::rescan()`].
+#[derive(Debug, Clone)]
+pub struct PresetEntry {
+    pub metadata: PresetMetadata,
+    pub location: PresetLocation,
+}
+
+/// Keeps track of the available presets and the currently selected preset. All methods take `&self`
+/// so the manager can be shared between threads using an `Arc`. See the [module
+/// documentation][self] for an example.
+pub struct PresetManager {
+    /// The directory user presets are read from and written to. `None` if the platform's data
+    /// directory could not be determined.
+    user_directory: Option<PathBuf>,
+    /// Directories containing factory presets. These are never written to.
+    factory_directories: Vec<PathBuf>,
+    /// Factory presets embedded in the plugin's binary. These are always listed first.
+    embedded_presets: Vec<&'static [u8]>,
+
+    /// The presets found during the last scan, and the index of the currently selected preset.
+    list: RwLock<PresetList>,
+}
+
+#[derive(Default)]
+struct PresetList {
+    entries: Vec<PresetEntry>,
+    current_index: Option<usize>,
+}
+
+impl Preset {
+    /// Create a new preset from a state object.
+    pub fn new(metadata: PresetMetadata, state: PluginState) -> Self {
+        Self { metadata, state }
+    }
+
+    /// Deserialize a preset from the contents of a preset file. Both compressed and uncompressed
+    /// presets are supported if the `zstd` feature is enabled.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        #[cfg(feature = "zstd")]
+        if let Ok(decompressed) = zstd::decode_all(bytes) {
+            return serde_json::from_slice(&decompressed).context("Could not parse preset");
+        }
+
+        serde_json::from_slice(bytes).context("Could not parse preset")
+    }
+
+    /// Serialize the preset so it can be written to a preset file. This is compressed using
+    /// Zstandard if the `zstd` feature is enabled.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec_pretty(self).context("Could not format as JSON")?;
+
+        #[cfg(feature = "zstd")]
+        {
+            zstd::encode_all(json.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)
+                .context("Could not compress preset")
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            Ok(json)
+        }
+    }
+
+    /// Read a preset from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            fs::read(path).with_context(|| format!("Could not read '{}'", path.display()))?;
+
+        Self::from_bytes(&bytes).with_context(|| format!("Could not load '{}'", path.display()))
+    }
+
+    /// Write this preset to a file, creating any missing parent directories.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create '{}'", parent.display()))?;
+        }
+
+        fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("Could not write '{}'", path.display()))
+    }
+}
+
+impl PresetEntry {
+    /// Whether this is a factory preset, either embedded in the binary or stored in a factory
+    /// preset directory. Factory presets cannot be overwritten or deleted.
+    pub fn is_factory(&self) -> bool {
+        !matches!(self.location, PresetLocation::User(_))
+    }
+}
+
+impl PresetManager {
+    /// Create a preset manager for a plugin. User presets are stored in the platform's data
+    /// directory, see [`default_user_directory()`]. No presets are scanned until
+    /// [`rescan()`][Self::rescan()] is called.
+    pub fn new<P: Plugin>() -> Self {
+        Self {
+            user_directory: default_user_directory::<P>(),
+            factory_directories: Vec::new(),
+            embedded_presets: Vec::new(),
+
+            list: RwLock::new(PresetList::default()),
+        }
+    }
+
+    /// Use a different directory for the user's presets, or disable user presets altogether by
+    /// passing `None`.
+    pub fn with_user_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.user_directory = directory;
+        self
+    }
+
+    /// Add a directory containing factory presets. Factory presets are read-only.
+    pub fn with_factory_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.factory_directories.push(directory.into());
+        self
+    }
+
+    /// Add factory presets embedded in the plugin's binary. These are usually added using
+    /// `include_bytes!()`. Embedded presets are always listed before any other presets.
+    pub fn with_embedded_presets(mut self, presets: &[&'static [u8]]) -> Self {
+        self.embedded_presets.extend_from_slice(presets);
+        self
+    }
+
+    /// The directory user presets are stored in, if any.
+    pub fn user_directory(&self) -> Option<&Path> {
+        self.user_directory.as_deref()
+    }
+
+    /// The directories factory presets are read from.
+    pub fn factory_directories(&self) -> &[PathBuf] {
+        &self.factory_directories
+    }
+
+    /// Rescan the embedded presets and preset directories. Embedded presets are listed first,
+    /// followed by the presets from the factory directories and then the user presets, each sorted
+    /// by name. If the currently selected preset still exists, then it remains selected. Files that
+    /// cannot be loaded are skipped.
+    ///
+    /// This performs file IO and may thus be slow. Consider calling this from a background task.
+    pub fn rescan(&self) {
+        let mut entries = Vec::new();
+        for (idx, bytes) in self.embedded_presets.iter().enumerate() {
+            match Preset::from_bytes(bytes) {
+                Ok(preset) => entries.push(PresetEntry {
+                    metadata: preset.metadata,
+                    location: PresetLocation::Embedded(idx),
+                }),
+                Err(err) => nih_debug_assert_failure!("Invalid embedded preset {}: {:#}", idx, err),
+            }
+        }
+
+        let mut factory_entries = Vec::new();
+        for directory in &self.factory_directories {
+            scan_directory(directory, &mut factory_entries, PresetLocation::Factory);
+        }
+        sort_entries(&mut factory_entries);
+        entries.extend(factory_entries);
+
+        if let Some(directory) = &self.user_directory {
+            let mut user_entries = Vec::new();
+            scan_directory(directory, &mut user_entries, PresetLocation::User);
+            sort_entries(&mut user_entries);
+            entries.extend(user_entries);
+        }
+
+        let mut list = self.list.write();
+        let current_location = list
+            .current_index
+            .and_then(|idx| list.entries.get(idx))
+            .map(|entry| entry.location.clone());
+        list.current_index = current_location
+            .and_then(|location| entries.iter().position(|entry| entry.location == location));
+        list.entries = entries;
+    }
+
+    /// All presets found during the last [`rescan()`][Self::rescan()].
+    pub fn presets(&self) -> Vec<PresetEntry> {
+        self.list.read().entries.clone()
+    }
+
+    /// The index of the currently selected preset in [`presets()`][Self::presets()], if a preset
+    /// has been loaded through this manager.
+    pub fn current_index(&self) -> Option<usize> {
+        self.list.read().current_index
+    }
+
+    /// The currently selected preset's metadata, if any.
+    pub fn current_preset(&self) -> Option<PresetEntry> {
+        let list = self.list.read();
+        list.current_index
+            .and_then(|idx| list.entries.get(idx))
+            .cloned()
+    }
+
+    /// Read a preset from the list without loading it into the plugin.
+    pub fn read(&self, index: usize) -> Result<Preset> {
+        self.read_location(&self.location(index)?)
+    }
+
+    /// Read a preset from its location without loading it into the plugin.
+    pub fn read_location(&self, location: &PresetLocation) -> Result<Preset> {
+        match location {
+            PresetLocation::Embedded(idx) => Preset::from_bytes(
+                self.embedded_presets
+                    .get(*idx)
+                    .with_context(|| format!("Embedded preset {idx} does not exist"))?,
+            ),
+            PresetLocation::Factory(path) | PresetLocation::User(path) => Preset::load(path),
+        }
+    }
+
+    /// Load the preset at `index` into the plugin and select it. The preset is selected by its
+    /// location, so this still selects the right preset if the list is rescanned while the preset
+    /// is being loaded.
+    pub fn load(&self, index: usize, context: &dyn GuiContext) -> Result<()> {
+        self.load_location(&self.location(index)?, context)
+    }
+
+    /// Load the preset stored at `location` into the plugin and select it. The preset does not
+    /// need to be in the list, in which case no preset will be selected afterwards.
+    pub fn load_location(&self, location: &PresetLocation, context: &dyn GuiContext) -> Result<()> {
+        let preset = self.read_location(location)?;
+        context.set_state(preset.state);
+        self.select_location(location);
+
+        Ok(())
+    }
+
+    /// The index of the preset after the currently selected preset. This wraps around to the first
+    /// preset, and it returns the first preset if no preset is selected.
+    pub fn next_index(&self) -> Option<usize> {
+        let list = self.list.read();
+        match (list.current_index, list.entries.len()) {
+            (_, 0) => None,
+            (Some(idx), num_presets) => Some((idx + 1) % num_presets),
+            (None, _) => Some(0),
+        }
+    }
+
+    /// The index of the preset before the currently selected preset. This wraps around to the last
+    /// preset, and it returns the last preset if no preset is selected.
+    pub fn previous_index(&self) -> Option<usize> {
+        let list = self.list.read();
+        match (list.current_index, list.entries.len()) {
+            (_, 0) => None,
+            (Some(idx), num_presets) => Some((idx + num_presets - 1) % num_presets),
+            (None, num_presets) => Some(num_presets - 1),
+        }
+    }
+
+    /// Load the next preset. See [`next_index()`][Self::next_index()]. Returns an error if there
+    /// are no presets or if the preset could not be loaded.
+    pub fn load_next(&self, context: &dyn GuiContext) -> Result<()> {
+        let index = self.next_index().context("There are no presets")?;
+        self.load(index, context)
+    }
+
+    /// Load the previous preset. See [`previous_index()`][Self::previous_index()]. Returns an error
+    /// if there are no presets or if the preset could not be loaded.
+    pub fn load_previous(&self, context: &dyn GuiContext) -> Result<()> {
+        let index = self.previous_index().context("There are no presets")?;
+        self.load(index, context)
+    }
+
+    /// Save a state object as a user preset. The file name is derived from the preset's name, and
+    /// an existing preset with the same name will be overwritten. The preset list is rescanned
+    /// afterwards and the new preset is selected. Returns the new preset's path.
+    ///
+    /// This can be called from a background task with a state object obtained through
+    /// [`GuiContext::get_state()`]. Use [`save_current_state()`][Self::save_current_state()] to do
+    /// both at once from the editor.
+    pub fn save_user_preset(
+        &self,
+        metadata: PresetMetadata,
+        state: PluginState,
+    ) -> Result<PathBuf> {
+        let directory = self
+            .user_directory
+            .as_ref()
+            .context("There is no user preset directory")?;
+        let path = directory.join(format!(
+            "{}.{PRESET_FILE_EXTENSION}",
+            sanitize_file_name(&metadata.name)
+        ));
+
+        Preset::new(metadata, state).save(&path)?;
+
+        self.rescan();
+        self.select_location(&PresetLocation::User(path.clone()));
+
+        Ok(path)
+    }
+
+    /// Save the plugin's current state as a user preset. See
+    /// [`save_user_preset()`][Self::save_user_preset()].
+    pub fn save_current_state(
+        &self,
+        metadata: PresetMetadata,
+        context: &dyn GuiContext,
+    ) -> Result<PathBuf> {
+        self.save_user_preset(metadata, context.get_state())
+    }
+
+    /// Delete a user preset and rescan the preset list. Factory presets cannot be deleted.
+    pub fn delete_user_preset(&self, index: usize) -> Result<()> {
+        match self.location(index)? {
+            PresetLocation::User(path) => {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Could not delete '{}'", path.display()))?;
+                self.rescan();
+
+                Ok(())
+            }
+            PresetLocation::Embedded(_) | PresetLocation::Factory(_) => {
+                anyhow::bail!("Factory presets cannot be deleted")
+            }
+        }
+    }
+
+    /// The location of the preset at `index` in the current list.
+    fn location(&self, index: usize) -> Result<PresetLocation> {
+        self.list
+            .read()
+            .entries
+            .get(index)
+            .map(|entry| entry.location.clone())
+            .with_context(|| format!("Preset index {index} is out of range"))
+    }
+
+    /// Select the preset stored at `location`, or clear the selection if the list does not contain
+    /// that preset.
+    fn select_location(&self, location: &PresetLocation) {
+        let mut list = self.list.write();
+        list.current_index = list
+            .entries
+            .iter()
+            .position(|entry| &entry.location == location);
+    }
+}
+
+/// The default directory for a plugin's user presets. This is
+/// `$XDG_DATA_HOME/<vendor>/<plugin>/presets` (defaulting to `~/.local/share`) on Linux,
+/// `~/Library/Application Support/<vendor>/<plugin>/Presets` on macOS, and
+/// `%APPDATA%\<vendor>\<plugin>\Presets` on Windows. Returns `None` if the data directory could not
+/// be determined.
+pub fn default_user_directory<P: Plugin>() -> Option<PathBuf> {
+    let vendor = sanitize_file_name(P::VENDOR);
+    let name = sanitize_file_name(P::NAME);
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            let data_dir = std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library").join("Application Support"));
+            data_dir.map(|dir| dir.join(vendor).join(name).join("Presets"))
+        } else if #[cfg(target_os = "windows")] {
+            let data_dir = std::env::var_os("APPDATA").map(PathBuf::from);
+            data_dir.map(|dir| dir.join(vendor).join(name).join("Presets"))
+        } else {
+            let data_dir = std::env::var_os("XDG_DATA_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(".local").join("share"))
+                });
+            data_dir.map(|dir| dir.join(vendor).join(name).join("presets"))
+        }
+    }
+}
+
+/// Recursively scan a directory for preset files and add them to `entries`. Files that cannot be
+/// parsed are skipped. Symbolic links to preset files are followed, but symbolic links to
+/// directories are not so a link pointing to one of its parent directories cannot cause an infinite
+/// recursion.
+fn scan_directory(
+    directory: &Path,
+    entries: &mut Vec<PresetEntry>,
+    make_location: fn(PathBuf) -> PresetLocation,
+) {
+    let dir_entries = match fs::read_dir(directory) {
+        Ok(dir_entries) => dir_entries,
+        // It's fine for the preset directories to not exist yet
+        Err(_) => return,
+    };
+
+    for entry in dir_entries.filter_map(|entry| entry.ok()) {
+        // Unlike `Path::is_dir()`, this does not follow symbolic links
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            scan_directory(&path, entries, make_location);
+        } else if (file_type.is_file() || (file_type.is_symlink() && path.is_file()))
+            && path.extension().and_then(|extension| extension.to_str())
+                == Some(PRESET_FILE_EXTENSION)
+        {
+            match Preset::load(&path) {
+                Ok(preset) => entries.push(PresetEntry {
+                    metadata: preset.metadata,
+                    location: make_location(path),
+                }),
+                Err(err) => nih_warn!("Skipping invalid preset: {err:#}"),
+            }
+        }
+    }
+}
+
+fn sort_entries(entries: &mut [PresetEntry]) {
+    entries.sort_by(|a, b| {
+        a.metadata
+            .name
+            .to_lowercase()
+            .cmp(&b.metadata.name.to_lowercase())
+    });
+}
+
+/// Replace characters that are not allowed in file names on common file systems. Trailing periods
+/// and spaces are removed since Windows silently strips those, names that would be empty
+/// afterwards are replaced with [`FALLBACK_FILE_NAME`], and reserved Windows device names like
+/// `CON` or `NUL` get an underscore appended to the part before the extension.
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // This also covers `.` and `..`
+    let sanitized = sanitized.trim_end_matches(['.', ' ']);
+    if sanitized.is_empty() {
+        return String::from(FALLBACK_FILE_NAME);
+    }
+
+    // Windows also reserves these names when they're followed by an extension
+    let stem = sanitized.split('.').next().unwrap_or_default().trim_end();
+    let is_reserved = RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved));
+    if is_reserved {
+        let (stem, extension) = sanitized.split_at(stem.len());
+        format!("{stem}_{extension}")
+    } else {
+        String::from(sanitized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn make_preset(name: &str) -> Preset {
+        Preset::new(
+            PresetMetadata {
+                name: String::from(name),
+                author: String::from("Author"),
+                tags: vec![String::from("tag")],
+                comment: String::new(),
+            },
+            PluginState {
+                version: String::from("0.1.0"),
+                params: BTreeMap::new(),
+                fields: BTreeMap::new(),
+            },
+        )
+    }
+
+    fn make_manager(names: &[&str]) -> PresetManager {
+        let presets: Vec<&'static [u8]> = names
+            .iter()
+            .map(|name| &*make_preset(name).to_bytes().unwrap().leak())
+            .collect();
+
+        let manager = PresetManager {
+            user_directory: None,
+            factory_directories: Vec::new(),
+            embedded_presets: Vec::new(),
+
+            list: RwLock::new(PresetList::default()),
+        }
+        .with_embedded_presets(&presets);
+        manager.rescan();
+
+        manager
+    }
+
+    /// A temporary directory that's removed when this object is dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("nih-plug-presets-{}-{name}", std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn save_preset(directory: &Path, name: &str) -> PathBuf {
+        let path = directory.join(format!("{name}.{PRESET_FILE_EXTENSION}"));
+        make_preset(name).save(&path).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let preset = make_preset("Foo");
+        let parsed = Preset::from_bytes(&preset.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(parsed.metadata, preset.metadata);
+        assert_eq!(parsed.state.version, preset.state.version);
+    }
+
+    #[test]
+    fn test_navigation() {
+        let manager = make_manager(&["A", "B", "C"]);
+        assert_eq!(manager.presets().len(), 3);
+        assert_eq!(manager.next_index(), Some(0));
+        assert_eq!(manager.previous_index(), Some(2));
+
+        manager.list.write().current_index = Some(2);
+        assert_eq!(manager.next_index(), Some(0));
+        assert_eq!(manager.previous_index(), Some(1));
+        assert_eq!(manager.read(1).unwrap().metadata.name, "B");
+    }
+
+    #[test]
+    fn test_empty_navigation() {
+        let manager = make_manager(&[]);
+        assert_eq!(manager.next_index(), None);
+        assert_eq!(manager.previous_index(), None);
+    }
+
+    #[test]
+    fn test_select_by_location() {
+        let directory = TempDir::new("select");
+        save_preset(&directory.0, "B");
+        save_preset(&directory.0, "C");
+
+        let manager = make_manager(&[]).with_factory_directory(&directory.0);
+        manager.rescan();
+        let location = manager.location(1).unwrap();
+        assert_eq!(manager.read_location(&location).unwrap().metadata.name, "C");
+
+        // If the list is rescanned while a preset is being loaded, then the index the preset was
+        // loaded from may point to a different preset
+        save_preset(&directory.0, "A");
+        manager.rescan();
+        manager.select_location(&location);
+        assert_eq!(manager.current_index(), Some(2));
+        assert_eq!(manager.current_preset().unwrap().metadata.name, "C");
+
+        manager.select_location(&PresetLocation::Factory(directory.0.join("D")));
+        assert_eq!(manager.current_index(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlink_loop() {
+        let directory = TempDir::new("symlinks");
+        let preset_path = save_preset(&directory.0, "A");
+        std::os::unix::fs::symlink(&directory.0, directory.0.join("loop")).unwrap();
+        std::os::unix::fs::symlink(
+            &preset_path,
+            directory.0.join(format!("B.{PRESET_FILE_EXTENSION}")),
+        )
+        .unwrap();
+
+        let mut entries = Vec::new();
+        scan_directory(&directory.0, &mut entries, PresetLocation::Factory);
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("Bass: Wobble/2"), "Bass_ Wobble_2");
+        assert_eq!(sanitize_file_name(""), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_file_name("."), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_file_name(".."), FALLBACK_FILE_NAME);
+        assert_eq!(sanitize_file_name("Pad. "), "Pad");
+        assert_eq!(sanitize_file_name("con"), "con_");
+        assert_eq!(sanitize_file_name("NUL.tar"), "NUL_.tar");
+        assert_eq!(sanitize_file_name("Aux .txt"), "Aux_ .txt");
+        assert_eq!(sanitize_file_name("LPT1 "), "LPT1_");
+        assert_eq!(sanitize_file_name("Console"), "Console");
+    }
+}
@@ -0,0 +1,1403 @@
+//! A headless host for testing [`Plugin`] implementations without loading them in a DAW.
+//!
+//! [`PluginTester`] instantiates a plugin, initializes it with an audio IO layout and buffer
+//! configuration of your choosing, and then lets you process audio buffers, note events, and
+//! parameter changes using a scripted transport. Everything the plugin sends back to the host is
+//! recorded so it can be checked in your tests. This includes output note events, latency changes,
+//! voice capacity changes, and background tasks. The plugin's state can be saved and loaded in the
+//! same format the plugin wrappers use.
+//!
+//! ```ignore
+//! use nih_plug::prelude::*;
+//! use nih_plug::testing::PluginTester;
+//!
+//! #[test]
+//! fn silence_in_silence_out() {
+//!     let mut tester = PluginTester::<MyPlugin>::new();
+//!     assert!(tester.initialize(
+//!         MyPlugin::AUDIO_IO_LAYOUTS[0],
+//!         BufferConfig {
+//!             sample_rate: 48000.0,
+//!             min_buffer_size: None,
+//!             max_buffer_size: 512,
+//!             process_mode: ProcessMode::Realtime,
+//!         },
+//!     ));
+//!
+//!     assert!(tester.set_parameter_from_string("gain", "-6 dB"));
+//!
+//!     let mut buffers = tester.create_buffers(512);
+//!     let result = tester.process(&mut buffers, &[]);
+//!     assert_eq!(result.status, ProcessStatus::Normal);
+//!     assert!(buffers.main.iter().flatten().all(|sample| *sample == 0.0));
+//! }
+//! ```
+
+use anyhow::{Context, Result};
+use atomic_float::AtomicF32;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::audio_setup::AudioIOLayoutsValidator;
+use crate::prelude::{
+    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, Buffer, BufferConfig, ContextMenuItem,
+    GuiContext, InitContext, ParamPtr, Params, Plugin, PluginApi, PluginNoteEvent, PluginState,
+    ProcessContext, ProcessMode, ProcessStatus, TaskExecutor, TrackInfo, Transport,
+};
+use crate::util::permit_alloc;
+use crate::wrapper::state;
+use crate::wrapper::state::prepared::PreparedState;
+use crate::wrapper::util::process_wrapper;
+
+/// A headless host for a single plugin instance. See the [module level documentation][self] for
+/// an example.
+pub struct PluginTester<P: Plugin> {
+    plugin: P,
+    task_executor: TaskExecutor<P>,
+    params: Arc<dyn Params>,
+    param_id_to_ptr: HashMap<String, ParamPtr>,
+
+    audio_io_layout: AudioIOLayout,
+    buffer_config: BufferConfig,
+    is_initialized: bool,
+
+    /// The transport information passed to the plugin during the next process call.
+    transport: TestTransport,
+
+    /// Everything the plugin sent to the host through one of the contexts. This is shared with the
+    /// [`GuiContext`] and [`AsyncExecutor`] returned by this object.
+    host: Arc<TestHost<P>>,
+}
+
+/// Audio buffers for [`PluginTester::process()`]. Create these using
+/// [`PluginTester::create_buffers()`] to get the correct channel counts for the current audio IO
+/// layout. Since NIH-plug processes audio in place, the main buffer contains the plugin's main
+/// input before processing and the plugin's main output after processing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestBuffers {
+    /// The main input and output channels.
+    pub main: Vec<Vec<f32>>,
+    /// The channels for each auxiliary input port.
+    pub aux_inputs: Vec<Vec<Vec<f32>>>,
+    /// The channels for each auxiliary output port. These are cleared before processing.
+    pub aux_outputs: Vec<Vec<Vec<f32>>>,
+    /// Whether each auxiliary input port is active, see [`Buffer::is_active()`]. Ports without an
+    /// entry are active. Inactive inputs are cleared before processing, just like in the plugin
+    /// wrappers.
+    pub aux_inputs_active: Vec<bool>,
+    /// Whether each auxiliary output port is active. Ports without an entry are active.
+    pub aux_outputs_active: Vec<bool>,
+}
+
+/// The result of a single [`PluginTester::process()`] call.
+pub struct ProcessResult<P: Plugin> {
+    /// The status returned by [`Plugin::process()`].
+    pub status: ProcessStatus,
+    /// The note events the plugin sent to the host using [`ProcessContext::send_event()`].
+    pub output_events: Vec<PluginNoteEvent<P>>,
+}
+
+/// A scripted transport. The position is advanced by the buffer's length after every process call
+/// while the transport is playing, wrapping around at the end of the loop range if one is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestTransport {
+    /// Whether the transport is currently running.
+    pub playing: bool,
+    /// Whether recording is enabled in the project.
+    pub recording: bool,
+    /// The project's tempo in beats per minute.
+    pub tempo: Option<f64>,
+    /// The time signature as a `(numerator, denominator)` pair.
+    pub time_signature: Option<(i32, i32)>,
+    /// The position in the song in samples at the start of the next buffer.
+    pub pos_samples: i64,
+    /// The loop range in samples, with an exclusive end.
+    pub loop_range_samples: Option<(i64, i64)>,
+}
+
+/// The state shared between the tester and the contexts it hands out.
+struct TestHost<P: Plugin> {
+    plugin_api: Mutex<PluginApi>,
+    /// Set through [`PluginTester::set_track_info()`].
+    track_info: Mutex<Option<TrackInfo>>,
+    /// The current sample rate. Used to update the smoothers for parameter changes made through
+    /// the [`GuiContext`].
+    sample_rate: AtomicF32,
+
+    latency_changes: Mutex<Vec<u32>>,
+    current_voice_capacity: Mutex<Option<u32>>,
+    num_note_names_changed: Mutex<usize>,
+    num_resize_requests: Mutex<usize>,
+    /// Tasks sent to the background or GUI thread. These are not run until
+    /// [`PluginTester::run_tasks()`] is called.
+    tasks: Mutex<Vec<P::BackgroundTask>>,
+
+    /// The parameter IDs and normalized values for parameter changes made through the
+    /// [`GuiContext`]. These values have already been set on the parameters.
+    gui_parameter_changes: Mutex<Vec<(String, f32)>>,
+    /// The host's context menu items for every parameter. Set through
+    /// [`PluginTester::set_param_context_menu_items()`].
+    param_context_menu_items: Mutex<Vec<ContextMenuItem>>,
+    /// The parameter IDs and positions of the context menus the editor asked the host to show.
+    shown_param_context_menus: Mutex<Vec<(String, i32, i32)>>,
+    /// The parameter IDs and action IDs of the context menu actions the editor performed.
+    performed_param_context_menu_actions: Mutex<Vec<(String, u32)>>,
+    /// State loaded through [`GuiContext::set_state()`]. This is loaded at the start of the next
+    /// process call.
+    pending_gui_state: Mutex<Option<PluginState>>,
+
+    params: Arc<dyn Params>,
+    param_ptr_to_id: HashMap<ParamPtr, String>,
+}
+
+/// The [`InitContext`] passed to the plugin during [`PluginTester::initialize()`].
+struct TestInitContext<'a, P: Plugin> {
+    host: &'a TestHost<P>,
+    task_executor: &'a TaskExecutor<P>,
+}
+
+/// The [`ProcessContext`] passed to the plugin during [`PluginTester::process()`].
+struct TestProcessContext<'a, P: Plugin> {
+    host: &'a TestHost<P>,
+    transport: Transport,
+    input_events: &'a [PluginNoteEvent<P>],
+    input_events_idx: usize,
+    output_events: Vec<PluginNoteEvent<P>>,
+}
+
+/// The [`GuiContext`] returned by [`PluginTester::gui_context()`].
+struct TestGuiContext<P: Plugin> {
+    host: Arc<TestHost<P>>,
+}
+
+impl<P: Plugin> Default for PluginTester<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Plugin> PluginTester<P> {
+    /// Create a new tester for a default-constructed instance of the plugin. The plugin is not yet
+    /// initialized.
+    pub fn new() -> Self {
+        Self::with_plugin(P::default())
+    }
+
+    /// Create a new tester for an existing plugin instance. The plugin is not yet initialized.
+    pub fn with_plugin(mut plugin: P) -> Self {
+        let () = AudioIOLayoutsValidator::<P>::ASSERT_VALID;
+
+        let task_executor = plugin.task_executor();
+        let params = plugin.params();
+
+        let param_map = params.param_map();
+        let param_ptr_to_id = param_map
+            .iter()
+            .map(|(param_id, param_ptr, _)| (*param_ptr, param_id.clone()))
+            .collect();
+        let param_id_to_ptr = param_map
+            .into_iter()
+            .map(|(param_id, param_ptr, _)| (param_id, param_ptr))
+            .collect();
+
+        let audio_io_layout = P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default();
+        let buffer_config = BufferConfig {
+            sample_rate: 48000.0,
+            min_buffer_size: None,
+            max_buffer_size: 512,
+            process_mode: ProcessMode::Realtime,
+        };
+        Self {
+            plugin,
+            task_executor,
+            params: params.clone(),
+            param_id_to_ptr,
+
+            audio_io_layout,
+            buffer_config,
+            is_initialized: false,
+
+            transport: TestTransport::default(),
+
+            host: Arc::new(TestHost {
+                plugin_api: Mutex::new(PluginApi::Standalone),
+                track_info: Mutex::new(None),
+                sample_rate: AtomicF32::new(buffer_config.sample_rate),
+
+                latency_changes: Mutex::new(Vec::new()),
+                current_voice_capacity: Mutex::new(None),
+                num_note_names_changed: Mutex::new(0),
+                num_resize_requests: Mutex::new(0),
+                tasks: Mutex::new(Vec::new()),
+
+                gui_parameter_changes: Mutex::new(Vec::new()),
+                param_context_menu_items: Mutex::new(Vec::new()),
+                shown_param_context_menus: Mutex::new(Vec::new()),
+                performed_param_context_menu_actions: Mutex::new(Vec::new()),
+                pending_gui_state: Mutex::new(None),
+
+                params,
+                param_ptr_to_id,
+            }),
+        }
+    }
+
+    /// The plugin instance being tested.
+    pub fn plugin(&self) -> &P {
+        &self.plugin
+    }
+
+    /// The plugin instance being tested.
+    pub fn plugin_mut(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// The plugin's parameters.
+    pub fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    /// The plugin API reported to the plugin by the contexts. Defaults to
+    /// [`PluginApi::Standalone`]. This can be changed to test API specific behavior.
+    pub fn set_plugin_api(&mut self, plugin_api: PluginApi) {
+        *self.host.plugin_api.lock() = plugin_api;
+    }
+
+    /// Simulate the host reporting new track information. This is returned from the contexts'
+    /// `track_info()` functions, and it is passed to [`Plugin::track_info_changed()`].
+    pub fn set_track_info(&mut self, track_info: TrackInfo) {
+        *self.host.track_info.lock() = Some(track_info.clone());
+        self.plugin.track_info_changed(&track_info);
+    }
+
+    /// Initialize the plugin with an audio IO layout and buffer configuration, and then reset it.
+    /// The plugin is deactivated first if it was already initialized. Returns the value returned by
+    /// [`Plugin::initialize()`].
+    pub fn initialize(
+        &mut self,
+        audio_io_layout: AudioIOLayout,
+        buffer_config: BufferConfig,
+    ) -> bool {
+        if self.is_initialized {
+            self.deactivate();
+        }
+
+        self.audio_io_layout = audio_io_layout;
+        self.buffer_config = buffer_config;
+        self.host
+            .sample_rate
+            .store(buffer_config.sample_rate, Ordering::Relaxed);
+
+        // The wrappers also reset the smoothers when the sample rate changes
+        for param_ptr in self.param_id_to_ptr.values() {
+            unsafe { param_ptr.update_smoother(buffer_config.sample_rate, true) };
+        }
+
+        self.is_initialized = self.initialize_plugin();
+
+        self.is_initialized
+    }
+
+    /// Whether the plugin has been successfully initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    /// Call [`Plugin::reset()`], like the host would do when playback is restarted.
+    pub fn reset(&mut self) {
+        process_wrapper(|| self.plugin.reset());
+    }
+
+    /// Call [`Plugin::deactivate()`]. The plugin needs to be initialized again before it can
+    /// process audio.
+    pub fn deactivate(&mut self) {
+        if self.is_initialized {
+            self.plugin.deactivate();
+            self.is_initialized = false;
+        }
+    }
+
+    /// The audio IO layout the plugin was last initialized with.
+    pub fn audio_io_layout(&self) -> &AudioIOLayout {
+        &self.audio_io_layout
+    }
+
+    /// The buffer configuration the plugin was last initialized with.
+    pub fn buffer_config(&self) -> &BufferConfig {
+        &self.buffer_config
+    }
+
+    /// Create zeroed buffers with `num_samples` samples per channel matching the current audio IO
+    /// layout.
+    pub fn create_buffers(&self, num_samples: usize) -> TestBuffers {
+        let zeroed_channels =
+            |num_channels: u32| vec![vec![0.0; num_samples]; num_channels as usize];
+
+        TestBuffers {
+            main: zeroed_channels(
+                self.audio_io_layout
+                    .main_output_channels
+                    .map(|channels| channels.get())
+                    .unwrap_or(0),
+            ),
+            aux_inputs: self
+                .audio_io_layout
+                .aux_input_ports
+                .iter()
+                .map(|num_channels| zeroed_channels(num_channels.get()))
+                .collect(),
+            aux_outputs: self
+                .audio_io_layout
+                .aux_output_ports
+                .iter()
+                .map(|num_channels| zeroed_channels(num_channels.get()))
+                .collect(),
+            aux_inputs_active: vec![true; self.audio_io_layout.aux_input_ports.len()],
+            aux_outputs_active: vec![true; self.audio_io_layout.aux_output_ports.len()],
+        }
+    }
+
+    /// Process a single buffer. The main buffer's contents are replaced by the plugin's output.
+    /// `input_events` need to be sorted by timing, and their timings need to fall within the
+    /// buffer. Any state loaded through the [`GuiContext`] is restored before processing. After
+    /// processing the transport's position is advanced by the buffer's length if it is playing.
+    ///
+    /// Like in the plugin wrappers, the process call is run with denormals flushed to zero and the
+    /// `assert_process_allocs` feature will catch allocations made by the plugin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the plugin has not been initialized, or if the buffers' channels have different
+    /// lengths or exceed the maximum buffer size.
+    pub fn process(
+        &mut self,
+        buffers: &mut TestBuffers,
+        input_events: &[PluginNoteEvent<P>],
+    ) -> ProcessResult<P> {
+        assert!(
+            self.is_initialized,
+            "The plugin needs to be initialized before it can process audio"
+        );
+
+        let pending_gui_state = self.host.pending_gui_state.lock().take();
+        if let Some(mut state) = pending_gui_state {
+            self.set_state_inner(&mut state);
+        }
+
+        let num_samples = buffers
+            .main
+            .iter()
+            .chain(buffers.aux_inputs.iter().flatten())
+            .chain(buffers.aux_outputs.iter().flatten())
+            .map(|channel| channel.len())
+            .next()
+            .unwrap_or(0);
+        assert!(
+            buffers
+                .main
+                .iter()
+                .chain(buffers.aux_inputs.iter().flatten())
+                .chain(buffers.aux_outputs.iter().flatten())
+                .all(|channel| channel.len() == num_samples),
+            "All channels need to have the same length"
+        );
+        assert!(
+            num_samples <= self.buffer_config.max_buffer_size as usize,
+            "The buffer exceeds the maximum buffer size"
+        );
+        nih_debug_assert!(
+            input_events
+                .windows(2)
+                .all(|events| events[0].timing() <= events[1].timing()),
+            "The input events are not sorted by timing"
+        );
+        nih_debug_assert!(
+            input_events
+                .iter()
+                .all(|event| (event.timing() as usize) < num_samples.max(1)),
+            "An input event's timing falls outside of the buffer"
+        );
+
+        for channel in buffers.aux_outputs.iter_mut().flatten() {
+            channel.fill(0.0);
+        }
+        for (port_idx, port) in buffers.aux_inputs.iter_mut().enumerate() {
+            if !buffers.aux_inputs_active.get(port_idx).unwrap_or(&true) {
+                for channel in port {
+                    channel.fill(0.0);
+                }
+            }
+        }
+
+        let mut main_buffer = Buffer::default();
+        unsafe {
+            main_buffer.set_slices(num_samples, |output_slices| {
+                output_slices.extend(
+                    buffers
+                        .main
+                        .iter_mut()
+                        .map(|channel| channel.as_mut_slice()),
+                )
+            })
+        };
+        let mut aux_input_buffers: Vec<Buffer> = buffers
+            .aux_inputs
+            .iter_mut()
+            .enumerate()
+            .map(|(port_idx, port)| {
+                let mut buffer = Buffer::default();
+                unsafe {
+                    buffer.set_slices(num_samples, |slices| {
+                        slices.extend(port.iter_mut().map(|channel| channel.as_mut_slice()))
+                    })
+                };
+                buffer.set_active(*buffers.aux_inputs_active.get(port_idx).unwrap_or(&true));
+
+                buffer
+            })
+            .collect();
+        let mut aux_output_buffers: Vec<Buffer> = buffers
+            .aux_outputs
+            .iter_mut()
+            .enumerate()
+            .map(|(port_idx, port)| {
+                let mut buffer = Buffer::default();
+                unsafe {
+                    buffer.set_slices(num_samples, |slices| {
+                        slices.extend(port.iter_mut().map(|channel| channel.as_mut_slice()))
+                    })
+                };
+                buffer.set_active(*buffers.aux_outputs_active.get(port_idx).unwrap_or(&true));
+
+                buffer
+            })
+            .collect();
+        let mut aux = AuxiliaryBuffers {
+            inputs: &mut aux_input_buffers,
+            outputs: &mut aux_output_buffers,
+        };
+
+        let mut context = TestProcessContext {
+            host: &self.host,
+            transport: self.transport.to_transport(self.buffer_config.sample_rate),
+            input_events,
+            input_events_idx: 0,
+            output_events: Vec::with_capacity(input_events.len().max(512)),
+        };
+        let plugin = &mut self.plugin;
+        let status = process_wrapper(|| plugin.process(&mut main_buffer, &mut aux, &mut context));
+
+        self.transport.advance(num_samples);
+
+        ProcessResult {
+            status,
+            output_events: context.output_events,
+        }
+    }
+
+    /// Get a parameter's `ParamPtr` from its ID.
+    pub fn param_ptr(&self, param_id: &str) -> Option<ParamPtr> {
+        self.param_id_to_ptr.get(param_id).copied()
+    }
+
+    /// Get a parameter's current unmodulated normalized value.
+    pub fn parameter_normalized(&self, param_id: &str) -> Option<f32> {
+        self.param_ptr(param_id)
+            .map(|param_ptr| unsafe { param_ptr.unmodulated_normalized_value() })
+    }
+
+    /// Set a parameter to a normalized value like a host automating the parameter would. The
+    /// change takes effect immediately, and it will be smoothed during the next process call.
+    /// Returns `false` if the parameter does not exist.
+    pub fn set_parameter_normalized(&mut self, param_id: &str, normalized: f32) -> bool {
+        match self.param_ptr(param_id) {
+            Some(param_ptr) => {
+                unsafe {
+                    if param_ptr.set_normalized_value(normalized) {
+                        param_ptr.update_smoother(self.buffer_config.sample_rate, false);
+                    }
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set a parameter from a string, for instance `"-6 dB"`, using the parameter's string to value
+    /// conversion function. Returns `false` if the parameter does not exist or if the string could
+    /// not be parsed.
+    pub fn set_parameter_from_string(&mut self, param_id: &str, string: &str) -> bool {
+        let normalized = self
+            .param_ptr(param_id)
+            .and_then(|param_ptr| unsafe { param_ptr.string_to_normalized_value(string) });
+        match normalized {
+            Some(normalized) => self.set_parameter_normalized(param_id, normalized),
+            None => false,
+        }
+    }
+
+    /// The transport used for the next process call.
+    pub fn transport(&self) -> &TestTransport {
+        &self.transport
+    }
+
+    /// The transport used for the next process call. Changes made here are reflected in the next
+    /// [`process()`][Self::process()] call.
+    pub fn transport_mut(&mut self) -> &mut TestTransport {
+        &mut self.transport
+    }
+
+    /// The plugin's current latency in samples, as last set using `set_latency_samples()`.
+    pub fn latency(&self) -> u32 {
+        self.host
+            .latency_changes
+            .lock()
+            .last()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// All latency values the plugin has set using `set_latency_samples()`, in order.
+    pub fn latency_changes(&self) -> Vec<u32> {
+        self.host.latency_changes.lock().clone()
+    }
+
+    /// The voice capacity last set using `set_current_voice_capacity()`, if any.
+    pub fn current_voice_capacity(&self) -> Option<u32> {
+        *self.host.current_voice_capacity.lock()
+    }
+
+    /// The number of times the plugin called `note_names_changed()`.
+    pub fn num_note_names_changed(&self) -> usize {
+        *self.host.num_note_names_changed.lock()
+    }
+
+    /// The number of times the plugin's editor called [`GuiContext::request_resize()`].
+    pub fn num_resize_requests(&self) -> usize {
+        *self.host.num_resize_requests.lock()
+    }
+
+    /// The parameter changes made through the [`GuiContext`] so far, as parameter ID and
+    /// normalized value pairs.
+    pub fn gui_parameter_changes(&self) -> Vec<(String, f32)> {
+        self.host.gui_parameter_changes.lock().clone()
+    }
+
+    /// Set the items returned from [`GuiContext::raw_param_context_menu_items()`]. The same items
+    /// are used for every parameter. Only actions from these items can be performed.
+    pub fn set_param_context_menu_items(&mut self, items: Vec<ContextMenuItem>) {
+        *self.host.param_context_menu_items.lock() = items;
+    }
+
+    /// The host context menus the editor has shown so far, as parameter ID and position tuples.
+    pub fn shown_param_context_menus(&self) -> Vec<(String, i32, i32)> {
+        self.host.shown_param_context_menus.lock().clone()
+    }
+
+    /// The host context menu actions the editor has performed so far, as parameter ID and action
+    /// ID pairs.
+    pub fn performed_param_context_menu_actions(&self) -> Vec<(String, u32)> {
+        self.host
+            .performed_param_context_menu_actions
+            .lock()
+            .clone()
+    }
+
+    /// Take the background and GUI tasks the plugin has scheduled so far without running them.
+    pub fn take_tasks(&mut self) -> Vec<P::BackgroundTask> {
+        std::mem::take(&mut *self.host.tasks.lock())
+    }
+
+    /// Run the background and GUI tasks the plugin has scheduled so far using the plugin's task
+    /// executor. Tasks scheduled while running these tasks are also run. Returns the number of
+    /// tasks that were run.
+    pub fn run_tasks(&mut self) -> usize {
+        let mut num_tasks = 0;
+        loop {
+            let tasks = self.take_tasks();
+            if tasks.is_empty() {
+                break;
+            }
+
+            num_tasks += tasks.len();
+            for task in tasks {
+                (self.task_executor)(task);
+            }
+        }
+
+        num_tasks
+    }
+
+    /// A [`GuiContext`] for testing the plugin's editor or other code that uses a `GuiContext`.
+    /// Parameter changes are applied immediately and are recorded in
+    /// [`gui_parameter_changes()`][Self::gui_parameter_changes()]. State set through this context
+    /// is loaded at the start of the next process call.
+    pub fn gui_context(&self) -> Arc<dyn GuiContext> {
+        Arc::new(TestGuiContext {
+            host: self.host.clone(),
+        })
+    }
+
+    /// An [`AsyncExecutor`] that can be passed to [`Plugin::editor()`]. Tasks executed through it
+    /// are recorded like tasks sent from the process function.
+    pub fn async_executor(&self) -> AsyncExecutor<P> {
+        AsyncExecutor {
+            execute_background: Arc::new({
+                let host = self.host.clone();
+                move |task| host.tasks.lock().push(task)
+            }),
+            execute_gui: Arc::new({
+                let host = self.host.clone();
+                move |task| host.tasks.lock().push(task)
+            }),
+        }
+    }
+
+    /// Get the plugin's current state as a [`PluginState`] object.
+    pub fn state_object(&self) -> PluginState {
+        self.host.state_object()
+    }
+
+    /// Serialize the plugin's state to JSON the same way the plugin wrappers do. If the `zstd`
+    /// feature is enabled, then the state will be compressed.
+    pub fn save_state(&self) -> Result<Vec<u8>> {
+        unsafe {
+            state::serialize_json::<P>(
+                self.params.clone(),
+                self.param_id_to_ptr
+                    .iter()
+                    .map(|(param_id, param_ptr)| (param_id, *param_ptr)),
+            )
+        }
+    }
+
+    /// Load state previously saved with [`save_state()`][Self::save_state()] or by one of the plugin
+    /// wrappers. The plugin is reinitialized and reset afterwards if it was already initialized,
+    /// just like when a host loads state.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<()> {
+        let mut state = unsafe { state::deserialize_json(state) }
+            .context("Could not parse the plugin state")?;
+        anyhow::ensure!(
+            self.set_state_inner(&mut state),
+            "Could not restore the plugin state"
+        );
+
+        Ok(())
+    }
+
+    /// Restore the plugin's state from a [`PluginState`] object. Returns `false` if the state could
+    /// not be restored or if the plugin failed to reinitialize.
+    pub fn set_state_object(&mut self, mut state: PluginState) -> bool {
+        self.set_state_inner(&mut state)
+    }
+
+    fn set_state_inner(&mut self, state: &mut PluginState) -> bool {
+        let mut prepared = unsafe {
+            PreparedState::prepare::<P>(state, |param_id| {
+                self.param_id_to_ptr.get(param_id).copied()
+            })
+        };
+        if self.is_initialized {
+            prepared.set_plugin_data(P::prepare_state(
+                state,
+                &self.audio_io_layout,
+                &self.buffer_config,
+            ));
+        }
+
+        // The wrappers let the plugin swap in the prepared state on the audio thread when state is
+        // loaded during processing. There's no separate audio thread here, so that happens right
+        // away.
+        if self.is_initialized
+            && prepared.has_plugin_data()
+            && unsafe {
+                prepared.apply_on_audio_thread(&mut self.plugin, self.buffer_config.sample_rate)
+            }
+        {
+            prepared.apply_fields(&*self.params);
+            return true;
+        }
+
+        // Otherwise the plugin is reinitialized, just like in the wrappers
+        let mut init_context = TestInitContext {
+            host: &self.host,
+            task_executor: &self.task_executor,
+        };
+        let audio_io_layout = &self.audio_io_layout;
+        let buffer_config = &self.buffer_config;
+        let success = unsafe {
+            prepared.apply_with_plugin_lock(
+                &mut self.plugin,
+                &*self.params,
+                self.is_initialized.then_some(buffer_config.sample_rate),
+                |plugin| plugin.initialize(audio_io_layout, buffer_config, &mut init_context),
+            )
+        };
+        self.is_initialized &= success;
+
+        success
+    }
+
+    /// Call [`Plugin::initialize()`] followed by [`Plugin::reset()`] with the current audio IO
+    /// layout and buffer configuration.
+    fn initialize_plugin(&mut self) -> bool {
+        let mut init_context = TestInitContext {
+            host: &self.host,
+            task_executor: &self.task_executor,
+        };
+        let success = self.plugin.initialize(
+            &self.audio_io_layout,
+            &self.buffer_config,
+            &mut init_context,
+        );
+        if success {
+            self.reset();
+        }
+
+        success
+    }
+}
+
+impl TestBuffers {
+    /// The number of samples in each of the main buffer's channels.
+    pub fn samples(&self) -> usize {
+        self.main.first().map(|channel| channel.len()).unwrap_or(0)
+    }
+}
+
+impl Default for TestTransport {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            recording: false,
+            tempo: Some(120.0),
+            time_signature: Some((4, 4)),
+            pos_samples: 0,
+            loop_range_samples: None,
+        }
+    }
+}
+
+impl TestTransport {
+    /// Convert this to the [`Transport`] passed to the plugin. Positions in seconds and beats are
+    /// derived from the position in samples.
+    fn to_transport(&self, sample_rate: f32) -> Transport {
+        let mut transport = Transport::new(sample_rate);
+        transport.playing = self.playing;
+        transport.recording = self.recording;
+        transport.tempo = self.tempo;
+        transport.time_sig_numerator = self.time_signature.map(|(numerator, _)| numerator);
+        transport.time_sig_denominator = self.time_signature.map(|(_, denominator)| denominator);
+        transport.pos_samples = Some(self.pos_samples);
+        transport.loop_range_samples = self.loop_range_samples;
+
+        transport
+    }
+
+    /// Advance the position by `num_samples` samples if the transport is playing.
+    fn advance(&mut self, num_samples: usize) {
+        if !self.playing {
+            return;
+        }
+
+        self.pos_samples += num_samples as i64;
+        if let Some((loop_start, loop_end)) = self.loop_range_samples {
+            let loop_length = loop_end - loop_start;
+            if loop_length > 0 && self.pos_samples >= loop_end {
+                self.pos_samples = loop_start + (self.pos_samples - loop_start) % loop_length;
+            }
+        }
+    }
+}
+
+impl<P: Plugin> TestHost<P> {
+    fn state_object(&self) -> PluginState {
+        unsafe {
+            state::serialize_object::<P>(
+                self.params.clone(),
+                self.param_ptr_to_id
+                    .iter()
+                    .map(|(param_ptr, param_id)| (param_id, *param_ptr)),
+            )
+        }
+    }
+}
+
+impl<P: Plugin> InitContext<P> for TestInitContext<'_, P> {
+    fn plugin_api(&self) -> PluginApi {
+        *self.host.plugin_api.lock()
+    }
+
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.host.track_info.lock().clone()
+    }
+
+    fn execute(&self, task: P::BackgroundTask) {
+        (self.task_executor)(task);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        permit_alloc(|| self.host.latency_changes.lock().push(samples));
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        *self.host.current_voice_capacity.lock() = Some(capacity);
+    }
+}
+
+impl<P: Plugin> ProcessContext<P> for TestProcessContext<'_, P> {
+    fn plugin_api(&self) -> PluginApi {
+        *self.host.plugin_api.lock()
+    }
+
+    // The plugin wrappers don't allocate when scheduling tasks or changing the latency, so the
+    // vectors recording these calls may grow without triggering `assert_process_allocs`
+    fn execute_background(&self, task: P::BackgroundTask) {
+        permit_alloc(|| self.host.tasks.lock().push(task));
+    }
+
+    fn execute_gui(&self, task: P::BackgroundTask) {
+        permit_alloc(|| self.host.tasks.lock().push(task));
+    }
+
+    #[inline]
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<P>> {
+        let event = self.input_events.get(self.input_events_idx)?.clone();
+        self.input_events_idx += 1;
+
+        Some(event)
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<P>) {
+        // The events are stored in a preallocated vector, but the plugin may send more events than
+        // that. Growing the vector should not trigger `assert_process_allocs` since the plugin
+        // wrappers don't allocate here.
+        if self.output_events.len() == self.output_events.capacity() {
+            permit_alloc(|| {
+                self.output_events
+                    .reserve(self.output_events.capacity().max(1))
+            });
+        }
+
+        self.output_events.push(event);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        permit_alloc(|| self.host.latency_changes.lock().push(samples));
+    }
+
+    fn set_current_voice_capacity(&self, capacity: u32) {
+        *self.host.current_voice_capacity.lock() = Some(capacity);
+    }
+
+    fn note_names_changed(&self) {
+        *self.host.num_note_names_changed.lock() += 1;
+    }
+}
+
+impl<P: Plugin> GuiContext for TestGuiContext<P> {
+    fn plugin_api(&self) -> PluginApi {
+        *self.host.plugin_api.lock()
+    }
+
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.host.track_info.lock().clone()
+    }
+
+    fn request_resize(&self) -> bool {
+        *self.host.num_resize_requests.lock() += 1;
+        true
+    }
+
+    unsafe fn raw_begin_set_parameter(&self, _param: ParamPtr) {}
+
+    unsafe fn raw_set_parameter_normalized(&self, param: ParamPtr, normalized: f32) {
+        match self.host.param_ptr_to_id.get(&param) {
+            Some(param_id) => {
+                // This should behave the same as automation from the host
+                if param.set_normalized_value(normalized) {
+                    param.update_smoother(self.host.sample_rate.load(Ordering::Relaxed), false);
+                }
+
+                self.host
+                    .gui_parameter_changes
+                    .lock()
+                    .push((param_id.clone(), normalized));
+            }
+            None => {
+                nih_debug_assert_failure!("raw_set_parameter() called with an unknown ParamPtr")
+            }
+        }
+    }
+
+    unsafe fn raw_end_set_parameter(&self, _param: ParamPtr) {}
+
+    unsafe fn raw_show_param_context_menu(&self, param: ParamPtr, x: i32, y: i32) -> bool {
+        match self.host.param_ptr_to_id.get(&param) {
+            Some(param_id) => {
+                self.host
+                    .shown_param_context_menus
+                    .lock()
+                    .push((param_id.clone(), x, y));
+                true
+            }
+            None => {
+                nih_debug_assert_failure!(
+                    "raw_show_param_context_menu() called with an unknown ParamPtr"
+                );
+                false
+            }
+        }
+    }
+
+    unsafe fn raw_param_context_menu_items(&self, _param: ParamPtr) -> Vec<ContextMenuItem> {
+        self.host.param_context_menu_items.lock().clone()
+    }
+
+    unsafe fn raw_perform_param_context_menu_action(
+        &self,
+        param: ParamPtr,
+        action_id: u32,
+    ) -> bool {
+        fn has_action(items: &[ContextMenuItem], action_id: u32) -> bool {
+            items.iter().any(|item| match item {
+                ContextMenuItem::Entry {
+                    action_id: id,
+                    is_enabled,
+                    ..
+                } => *id == action_id && *is_enabled,
+                ContextMenuItem::Submenu { items, .. } => has_action(items, action_id),
+                ContextMenuItem::Title { .. } | ContextMenuItem::Separator => false,
+            })
+        }
+
+        match self.host.param_ptr_to_id.get(&param) {
+            Some(param_id) if has_action(&self.host.param_context_menu_items.lock(), action_id) => {
+                self.host
+                    .performed_param_context_menu_actions
+                    .lock()
+                    .push((param_id.clone(), action_id));
+                true
+            }
+            Some(_) => false,
+            None => {
+                nih_debug_assert_failure!(
+                    "raw_perform_param_context_menu_action() called with an unknown ParamPtr"
+                );
+                false
+            }
+        }
+    }
+
+    fn get_state(&self) -> PluginState {
+        self.host.state_object()
+    }
+
+    fn set_state(&self, state: PluginState) {
+        *self.host.pending_gui_state.lock() = Some(state);
+    }
+
+    fn note_names_changed(&self) {
+        *self.host.num_note_names_changed.lock() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::prelude::*;
+
+    struct TestPlugin {
+        params: Arc<TestParams>,
+        latency: u32,
+        /// If set, the plugin reports this latency from its process function.
+        process_latency: Option<u32>,
+        track_name: Option<String>,
+        accept_prepared_state: bool,
+        prepared_gain: Option<f32>,
+    }
+
+    struct TestParams {
+        gain: FloatParam,
+        mix: FloatParam,
+    }
+
+    unsafe impl Params for TestParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            vec![
+                (String::from("gain"), self.gain.as_ptr(), String::new()),
+                (String::from("mix"), self.mix.as_ptr(), String::new()),
+            ]
+        }
+    }
+
+    impl Default for TestPlugin {
+        fn default() -> Self {
+            Self {
+                params: Arc::new(TestParams {
+                    gain: FloatParam::new("Gain", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 }),
+                    mix: FloatParam::new("Mix", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                        .with_smoother(SmoothingStyle::Linear(10.0)),
+                }),
+                latency: 0,
+                process_latency: None,
+                track_name: None,
+                accept_prepared_state: false,
+                prepared_gain: None,
+            }
+        }
+    }
+
+    impl Plugin for TestPlugin {
+        const NAME: &'static str = "Test Plugin";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+            AudioIOLayout {
+                main_input_channels: NonZeroU32::new(2),
+                main_output_channels: NonZeroU32::new(2),
+                ..AudioIOLayout::const_default()
+            },
+            AudioIOLayout {
+                main_input_channels: NonZeroU32::new(2),
+                main_output_channels: NonZeroU32::new(2),
+                aux_input_ports: &[new_nonzero_u32(2)],
+                ..AudioIOLayout::const_default()
+            },
+        ];
+        const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+        const MIDI_OUTPUT: MidiConfig = MidiConfig::Basic;
+
+        type SysExMessage = ();
+        type BackgroundTask = u32;
+
+        fn params(&self) -> Arc<dyn Params> {
+            self.params.clone()
+        }
+
+        fn track_info_changed(&mut self, track_info: &TrackInfo) {
+            self.track_name = track_info.name.clone();
+        }
+
+        fn prepare_state(
+            state: &PluginState,
+            _audio_io_layout: &AudioIOLayout,
+            _buffer_config: &BufferConfig,
+        ) -> Option<Box<dyn std::any::Any + Send>> {
+            match state.params.get("gain") {
+                Some(state::ParamValue::F32(gain)) => Some(Box::new(*gain)),
+                _ => None,
+            }
+        }
+
+        fn apply_prepared_state(
+            &mut self,
+            prepared: Option<&mut (dyn std::any::Any + Send)>,
+        ) -> bool {
+            if !self.accept_prepared_state {
+                return false;
+            }
+
+            self.prepared_gain = prepared.and_then(|prepared| prepared.downcast_ref().copied());
+            true
+        }
+
+        fn initialize(
+            &mut self,
+            _audio_io_layout: &AudioIOLayout,
+            _buffer_config: &BufferConfig,
+            context: &mut impl InitContext<Self>,
+        ) -> bool {
+            context.set_latency_samples(self.latency);
+            true
+        }
+
+        fn process(
+            &mut self,
+            buffer: &mut Buffer,
+            aux: &mut AuxiliaryBuffers,
+            context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            // Echo all note events back to the host, and report the transport position as a task
+            while let Some(event) = context.next_event() {
+                context.send_event(event);
+            }
+            if let Some(pos_samples) = context.transport().pos_samples() {
+                context.execute_background(pos_samples as u32);
+            }
+            if let Some(latency) = self.process_latency {
+                context.set_latency_samples(latency);
+            }
+
+            for channel_samples in buffer.iter_samples() {
+                let gain = self.params.gain.value();
+                for sample in channel_samples {
+                    *sample *= gain;
+                }
+            }
+
+            // The sidechain input is mixed in when it's active
+            if aux.is_input_active(0) {
+                for (output, sidechain) in buffer
+                    .as_slice()
+                    .iter_mut()
+                    .zip(aux.inputs[0].as_slice_immutable())
+                {
+                    for (output_sample, sidechain_sample) in output.iter_mut().zip(sidechain.iter())
+                    {
+                        *output_sample += sidechain_sample;
+                    }
+                }
+            }
+
+            ProcessStatus::Normal
+        }
+    }
+
+    fn initialized_tester() -> PluginTester<TestPlugin> {
+        let mut tester = PluginTester::<TestPlugin>::new();
+        assert!(tester.initialize(
+            TestPlugin::AUDIO_IO_LAYOUTS[0],
+            BufferConfig {
+                sample_rate: 44100.0,
+                min_buffer_size: None,
+                max_buffer_size: 64,
+                process_mode: ProcessMode::Offline,
+            },
+        ));
+
+        tester
+    }
+
+    #[test]
+    fn process_audio_and_events() {
+        let mut tester = initialized_tester();
+        assert!(tester.set_parameter_normalized("gain", 0.25));
+        assert!(!tester.set_parameter_normalized("nonexistent", 0.25));
+
+        let mut buffers = tester.create_buffers(64);
+        assert_eq!(buffers.main.len(), 2);
+        buffers.main[0].fill(1.0);
+        buffers.main[1].fill(-1.0);
+
+        let events = [NoteEvent::NoteOn {
+            timing: 10,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        }];
+        let result = tester.process(&mut buffers, &events);
+        assert_eq!(result.status, ProcessStatus::Normal);
+        assert_eq!(result.output_events, events);
+        assert!(buffers.main[0].iter().all(|sample| *sample == 0.5));
+        assert!(buffers.main[1].iter().all(|sample| *sample == -0.5));
+    }
+
+    #[test]
+    fn many_output_events() {
+        let mut tester = initialized_tester();
+        let events: Vec<_> = (0..1000)
+            .map(|i| NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: (i % 128) as u8,
+                velocity: 1.0,
+            })
+            .collect();
+
+        let mut buffers = tester.create_buffers(64);
+        let result = tester.process(&mut buffers, &events);
+        assert_eq!(result.output_events, events);
+    }
+
+    #[test]
+    fn aux_port_activation() {
+        let mut tester = PluginTester::<TestPlugin>::new();
+        assert!(tester.initialize(
+            TestPlugin::AUDIO_IO_LAYOUTS[1],
+            BufferConfig {
+                sample_rate: 44100.0,
+                min_buffer_size: None,
+                max_buffer_size: 64,
+                process_mode: ProcessMode::Offline,
+            },
+        ));
+
+        let mut buffers = tester.create_buffers(64);
+        assert_eq!(buffers.aux_inputs_active, [true]);
+        buffers.aux_inputs[0][0].fill(0.5);
+        tester.process(&mut buffers, &[]);
+        assert!(buffers.main[0].iter().all(|sample| *sample == 0.5));
+        assert!(buffers.main[1].iter().all(|sample| *sample == 0.0));
+
+        // Inactive inputs are cleared, and the plugin skips the sidechain
+        let mut buffers = tester.create_buffers(64);
+        buffers.aux_inputs[0][0].fill(0.5);
+        buffers.aux_inputs_active[0] = false;
+        tester.process(&mut buffers, &[]);
+        assert!(buffers.main[0].iter().all(|sample| *sample == 0.0));
+        assert!(buffers.aux_inputs[0][0].iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn transport_and_tasks() {
+        let mut tester = initialized_tester();
+        tester.transport_mut().playing = true;
+        tester.transport_mut().loop_range_samples = Some((0, 96));
+
+        let mut buffers = tester.create_buffers(64);
+        tester.process(&mut buffers, &[]);
+        tester.process(&mut buffers, &[]);
+        tester.process(&mut buffers, &[]);
+
+        assert_eq!(tester.take_tasks(), [0, 64, 32]);
+        assert_eq!(tester.transport().pos_samples, 0);
+    }
+
+    #[test]
+    fn process_context_calls() {
+        // Recording these calls should not trigger `assert_process_allocs`, even after processing
+        // enough blocks for the vectors to grow
+        let mut tester = initialized_tester();
+        tester.plugin_mut().process_latency = Some(32);
+
+        let mut buffers = tester.create_buffers(16);
+        for _ in 0..100 {
+            tester.process(&mut buffers, &[]);
+        }
+
+        assert_eq!(tester.latency_changes().len(), 101);
+        assert_eq!(tester.take_tasks().len(), 100);
+    }
+
+    #[test]
+    fn latency_and_state() {
+        let mut tester = initialized_tester();
+        assert_eq!(tester.latency(), 0);
+
+        tester.plugin_mut().latency = 32;
+        assert!(tester.set_parameter_from_string("gain", "1.5"));
+        let state = tester.save_state().unwrap();
+
+        // Loading state reinitializes the plugin
+        assert!(tester.set_parameter_normalized("gain", 0.0));
+        tester.load_state(&state).unwrap();
+        assert_eq!(tester.parameter_normalized("gain"), Some(0.75));
+        assert_eq!(tester.latency_changes(), [0, 32]);
+    }
+
+    #[test]
+    fn prepared_state() {
+        let mut tester = initialized_tester();
+        tester.plugin_mut().accept_prepared_state = true;
+        assert!(tester.set_parameter_from_string("gain", "1.5"));
+        let state = tester.gui_context().get_state();
+
+        // The plugin accepts the prepared state, so it does not need to be reinitialized
+        assert!(tester.set_parameter_normalized("gain", 0.0));
+        tester.gui_context().set_state(state);
+        let mut buffers = tester.create_buffers(16);
+        tester.process(&mut buffers, &[]);
+        assert_eq!(tester.parameter_normalized("gain"), Some(0.75));
+        assert_eq!(tester.plugin().prepared_gain, Some(1.5));
+        assert_eq!(tester.latency_changes(), [0]);
+    }
+
+    #[test]
+    fn track_info() {
+        let mut tester = initialized_tester();
+        assert_eq!(tester.gui_context().track_info(), None);
+
+        let track_info = TrackInfo {
+            name: Some(String::from("Drums")),
+            color: Some(TrackColor {
+                red: 255,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            }),
+            ..TrackInfo::default()
+        };
+        tester.set_track_info(track_info.clone());
+        assert_eq!(tester.plugin().track_name.as_deref(), Some("Drums"));
+        assert_eq!(tester.gui_context().track_info(), Some(track_info));
+    }
+
+    #[test]
+    fn gui_context() {
+        let mut tester = initialized_tester();
+        let gui_context = tester.gui_context();
+
+        let param_ptr = tester.param_ptr("gain").unwrap();
+        let state = gui_context.get_state();
+        unsafe { gui_context.raw_set_parameter_normalized(param_ptr, 1.0) };
+        assert_eq!(tester.parameter_normalized("gain"), Some(1.0));
+        assert_eq!(
+            tester.gui_parameter_changes(),
+            [(String::from("gain"), 1.0)]
+        );
+
+        // Changes made through the GUI are smoothed like automation from the host
+        let params = tester.plugin().params.clone();
+        unsafe { gui_context.raw_set_parameter_normalized(params.mix.as_ptr(), 1.0) };
+        assert!(params.mix.smoothed.is_smoothing());
+        assert!(params.mix.smoothed.next() > 0.0);
+
+        // The state is restored at the start of the next process call
+        gui_context.set_state(state);
+        let mut buffers = tester.create_buffers(16);
+        tester.process(&mut buffers, &[]);
+        assert_eq!(tester.parameter_normalized("gain"), Some(0.5));
+    }
+
+    #[test]
+    fn param_context_menu() {
+        let mut tester = initialized_tester();
+        tester.set_param_context_menu_items(vec![
+            ContextMenuItem::Title {
+                label: String::from("Gain"),
+            },
+            ContextMenuItem::Submenu {
+                label: String::from("Automation"),
+                is_enabled: true,
+                items: vec![ContextMenuItem::Entry {
+                    label: String::from("Show Automation"),
+                    action_id: 3,
+                    is_enabled: true,
+                    is_checked: false,
+                }],
+            },
+        ]);
+
+        let gui_context = tester.gui_context();
+        let setter = ParamSetter::new(gui_context.as_ref());
+        let params = tester.plugin().params.clone();
+        assert!(setter.show_context_menu(&params.gain, 10, 20));
+        assert_eq!(setter.context_menu_items(&params.gain).len(), 2);
+        assert!(setter.perform_context_menu_action(&params.gain, 3));
+        assert!(!setter.perform_context_menu_action(&params.gain, 4));
+
+        assert_eq!(
+            tester.shown_param_context_menus(),
+            [(String::from("gain"), 10, 20)]
+        );
+        assert_eq!(
+            tester.performed_param_context_menu_actions(),
+            [(String::from("gain"), 3)]
+        );
+    }
+}
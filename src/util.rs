@@ -1,5 +1,10 @@
 //! General conversion functions and utilities.
 
+pub mod convolution;
+pub mod crossover;
+pub mod filter;
+pub mod oversampling;
+pub mod spectrum;
 mod stft;
 pub mod window;
 
@@ -0,0 +1,943 @@
+//! Partitioned FFT convolution for long impulse responses, like cabinet or reverb impulse
+//! responses.
+//!
+//! The impulse response is split into equally sized partitions that are convolved with the input
+//! in the frequency domain using a uniformly partitioned overlap-save algorithm with a frequency
+//! domain delay line. This results in a latency of one partition. In
+//! [`ConvolutionMode::ZeroLatency`] the first partition is instead convolved directly in the time
+//! domain, which removes the latency at the cost of some additional CPU usage.
+//!
+//! Impulse responses can be mono, have one channel per audio channel, or be true-stereo impulse
+//! responses with separate left-to-left, left-to-right, right-to-left, and right-to-right channels.
+//! See [`IrLayout`].
+//!
+//! Preparing an impulse response allocates memory and runs an FFT for every partition, so this
+//! should not be done on the audio thread. A [`Convolver`] hands out [`ConvolverLoader`]s that can
+//! be moved to a [`Plugin::BackgroundTask`][crate::prelude::Plugin::BackgroundTask] executor or to
+//! the editor. Loading an impulse response through the loader prepares it on the calling thread,
+//! after which the convolver picks it up at the start of the next process call and crossfades from
+//! the old impulse response to the new one. The old impulse response is then handed back to the
+//! loader so it can be deallocated off of the audio thread.
+
+use anyhow::{bail, Result};
+use crossbeam::queue::ArrayQueue;
+use parking_lot::Mutex;
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::buffer::Buffer;
+
+/// Determines how the first partition of the impulse response is convolved, and thus how much
+/// latency the [`Convolver`] introduces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvolutionMode {
+    /// Convolve the first partition directly in the time domain. This does not add any latency,
+    /// but the direct convolution costs one multiply-add per partition sample per output sample.
+    /// Use small partitions with this mode.
+    ZeroLatency,
+    /// Convolve all partitions in the frequency domain. This adds one partition of latency, which
+    /// should be reported to the host using [`Convolver::latency()`].
+    LowLatency,
+}
+
+/// How the channels of an impulse response map to the [`Convolver`]'s audio channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrLayout {
+    /// Every audio channel is convolved with the impulse response channel with the same index. A
+    /// single channel impulse response is applied to all audio channels.
+    PerChannel,
+    /// A true-stereo impulse response with four channels. These are the impulse responses from the
+    /// left input to the left output, the left input to the right output, the right input to the
+    /// left output, and the right input to the right output, in that order. This requires the
+    /// convolver to have exactly two channels.
+    TrueStereo,
+}
+
+/// A multichannel partitioned convolution processor. This is used on the audio thread, while
+/// impulse responses are loaded through a [`ConvolverLoader`]. No impulse response is loaded by
+/// default, in which case the convolver outputs silence.
+pub struct Convolver {
+    /// The state shared with the loaders.
+    shared: Arc<ConvolverShared>,
+
+    /// The engine convolving the audio with the current impulse response, if an impulse response
+    /// has been loaded.
+    engine: Option<Box<ConvolutionEngine>>,
+    /// An engine for a newly loaded impulse response the output is currently crossfading to. Once
+    /// the crossfade has finished, this replaces `engine`.
+    next_engine: Option<Box<ConvolutionEngine>>,
+    /// The length of the crossfade between two impulse responses, in samples. In
+    /// [`ConvolutionMode::LowLatency`] the new engine only starts producing output after
+    /// [`Convolver::latency()`] samples, so the crossfade is delayed by that amount. A new engine
+    /// also starts out with an empty frequency domain delay line. The impulse response's later
+    /// partitions are thus only applied to the input the new engine has seen so far, and for long
+    /// impulse responses the new tail builds up during and after the crossfade.
+    crossfade_length: usize,
+    /// The number of samples that have been processed since `next_engine` was picked up. This
+    /// includes the latency before the crossfade actually starts.
+    crossfade_pos: usize,
+    /// A copy of the input for `next_engine` during a crossfade, indexed by `[channel_idx]`. Each
+    /// channel contains `partition_size` samples, and longer buffers are crossfaded in chunks.
+    crossfade_scratch_buffers: Vec<Vec<f32>>,
+}
+
+/// Loads impulse responses for a [`Convolver`] from another thread. This can be cloned and moved
+/// to a background task executor or to the editor. See the module-level documentation for more
+/// information.
+#[derive(Clone)]
+pub struct ConvolverLoader {
+    shared: Arc<ConvolverShared>,
+}
+
+/// The state shared between a [`Convolver`] and its [`ConvolverLoader`]s.
+struct ConvolverShared {
+    num_channels: usize,
+    partition_size: usize,
+    mode: ConvolutionMode,
+
+    /// A prepared engine waiting to be picked up by the audio thread. This can only ever contain a
+    /// single item. Loading a new impulse response before the previous one has been picked up
+    /// replaces the pending engine.
+    incoming: ArrayQueue<Box<ConvolutionEngine>>,
+    /// Engines the audio thread has stopped using, waiting to be deallocated by the next load.
+    /// There can be at most two of these between loads: the engine that was faded out when the
+    /// load happened, and the engine that is being faded out because of the load. The audio thread
+    /// won't pick up new engines while this is full.
+    retired: ArrayQueue<Box<ConvolutionEngine>>,
+    /// Makes sure only one thread can load an impulse response at a time.
+    loading_lock: Mutex<()>,
+}
+
+/// The actual uniformly partitioned overlap-save convolution for a single impulse response.
+/// Creating this allocates, but processing audio and resetting it does not.
+struct ConvolutionEngine {
+    partition_size: usize,
+    mode: ConvolutionMode,
+
+    /// The input channel, output channel, and impulse response channel combinations that should be
+    /// convolved.
+    paths: Vec<ConvolutionPath>,
+    /// The DFTs of the impulse response's partitions, indexed by
+    /// `[ir_channel_idx][partition_idx][bin_idx]`. In [`ConvolutionMode::ZeroLatency`] the first
+    /// partition is stored in `ir_heads` instead. These have already been normalized. There's
+    /// always at least one partition to keep the bookkeeping simple.
+    ir_partitions: Vec<Vec<Vec<Complex32>>>,
+    /// The reversed first `partition_size` samples of every impulse response channel, used for the
+    /// direct convolution in [`ConvolutionMode::ZeroLatency`]. Empty otherwise.
+    ir_heads: Vec<Vec<f32>>,
+
+    /// The input for the next FFT for every input channel. This contains the previous
+    /// `partition_size` samples followed by the current partition's input samples.
+    input_buffers: Vec<Vec<f32>>,
+    /// The frequency domain delay line containing the DFTs of the most recent inputs for every
+    /// input channel, indexed by `[channel_idx][delay_line_idx][bin_idx]`.
+    input_spectra: Vec<Vec<Vec<Complex32>>>,
+    /// The position of the most recent input DFT in `input_spectra`.
+    input_spectra_pos: usize,
+    /// The output for the current partition for every output channel. This is produced at the end
+    /// of the previous partition.
+    output_buffers: Vec<Vec<f32>>,
+    /// The position in the current partition. When this reaches `partition_size`, the next
+    /// partition is processed.
+    partition_pos: usize,
+
+    /// A `2 * partition_size` ring buffer for the direct convolution with the input history for
+    /// every input channel. Every sample is written twice so the last `partition_size` samples can
+    /// always be read as a contiguous slice.
+    history_buffers: Vec<Vec<f32>>,
+    /// The position in `history_buffers` the next sample is written to.
+    history_pos: usize,
+    /// The output from the direct convolution for every output channel, for the current chunk.
+    head_output_buffers: Vec<Vec<f32>>,
+
+    r2c_plan: Arc<dyn RealToComplex<f32>>,
+    c2r_plan: Arc<dyn ComplexToReal<f32>>,
+    /// A `2 * partition_size` buffer for the FFT and IFFT operations.
+    real_scratch_buffer: Vec<f32>,
+    /// Accumulates an output channel's DFT before it's converted back to the time domain.
+    complex_scratch_buffer: Vec<Complex32>,
+    /// Scratch space for realfft.
+    fft_scratch_buffer: Vec<Complex32>,
+}
+
+/// A single input to output channel path through an impulse response channel.
+#[derive(Debug, Clone, Copy)]
+struct ConvolutionPath {
+    input_channel: usize,
+    output_channel: usize,
+    ir_channel: usize,
+}
+
+impl Convolver {
+    /// Create a convolver for `num_channels` channels. The impulse response is split into
+    /// partitions of `partition_size` samples. Larger partitions are more efficient for long
+    /// impulse responses, but in [`ConvolutionMode::LowLatency`] they also increase the latency.
+    /// When a new impulse response is loaded, the output crossfades to the new impulse response
+    /// over `crossfade_length` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `partition_size` is zero.
+    pub fn new(
+        num_channels: usize,
+        partition_size: usize,
+        mode: ConvolutionMode,
+        crossfade_length: usize,
+    ) -> Self {
+        assert!(partition_size > 0, "The partition size cannot be zero");
+
+        Self {
+            shared: Arc::new(ConvolverShared {
+                num_channels,
+                partition_size,
+                mode,
+
+                incoming: ArrayQueue::new(1),
+                retired: ArrayQueue::new(2),
+                loading_lock: Mutex::new(()),
+            }),
+
+            engine: None,
+            next_engine: None,
+            crossfade_length: crossfade_length.max(1),
+            crossfade_pos: 0,
+            crossfade_scratch_buffers: vec![vec![0.0; partition_size]; num_channels],
+        }
+    }
+
+    /// Get a loader that can be used to load impulse responses for this convolver from another
+    /// thread.
+    pub fn loader(&self) -> ConvolverLoader {
+        ConvolverLoader {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Get the latency in samples. This is zero for [`ConvolutionMode::ZeroLatency`], and equal to
+    /// the partition size for [`ConvolutionMode::LowLatency`].
+    pub fn latency(&self) -> u32 {
+        match self.shared.mode {
+            ConvolutionMode::ZeroLatency => 0,
+            ConvolutionMode::LowLatency => self.shared.partition_size as u32,
+        }
+    }
+
+    /// Convolve the buffer with the current impulse response in place. If a new impulse response
+    /// has been loaded, then this starts crossfading to it. The buffer needs to have the same number
+    /// of channels the convolver was created with.
+    pub fn process(&mut self, buffer: &mut Buffer) {
+        let num_samples = buffer.samples();
+        self.process_channels(buffer.as_slice(), 0..num_samples);
+    }
+
+    /// The same as [`process()`][Self::process()], but for any kind of channel slices. All
+    /// channels need to be at least `range.end` samples long.
+    pub fn process_channels<S: AsMut<[f32]>>(&mut self, channels: &mut [S], range: Range<usize>) {
+        nih_debug_assert_eq!(channels.len(), self.shared.num_channels);
+
+        // New impulse responses are only picked up once the previous crossfade has finished. The
+        // retired engines need to fit in the queue so they can be deallocated elsewhere.
+        if self.next_engine.is_none() && !self.shared.retired.is_full() {
+            if let Some(engine) = self.shared.incoming.pop() {
+                self.next_engine = Some(engine);
+                self.crossfade_pos = 0;
+            }
+        }
+
+        // The new engine's output is silent until it has processed a full partition in
+        // `ConvolutionMode::LowLatency`, so the old engine is kept at full volume until then
+        let crossfade_start = self.latency() as usize;
+        let mut current_sample_idx = range.start;
+        while self.next_engine.is_some() && current_sample_idx < range.end {
+            let chunk_len = (range.end - current_sample_idx).min(self.shared.partition_size);
+            let chunk = current_sample_idx..current_sample_idx + chunk_len;
+
+            for (channel, scratch_buffer) in channels
+                .iter_mut()
+                .zip(self.crossfade_scratch_buffers.iter_mut())
+            {
+                scratch_buffer[..chunk_len].copy_from_slice(&channel.as_mut()[chunk.clone()]);
+            }
+
+            Self::process_engine(&mut self.engine, channels, chunk.clone());
+            Self::process_engine(
+                &mut self.next_engine,
+                &mut self.crossfade_scratch_buffers,
+                0..chunk_len,
+            );
+
+            // This is a linear crossfade since both signals are strongly correlated
+            for (channel, scratch_buffer) in channels
+                .iter_mut()
+                .zip(self.crossfade_scratch_buffers.iter())
+            {
+                for (sample_idx, (sample, next_sample)) in channel.as_mut()[chunk.clone()]
+                    .iter_mut()
+                    .zip(scratch_buffer.iter())
+                    .enumerate()
+                {
+                    let t = ((self.crossfade_pos + sample_idx + 1).saturating_sub(crossfade_start)
+                        as f32
+                        / self.crossfade_length as f32)
+                        .min(1.0);
+                    *sample = (*sample * (1.0 - t)) + (*next_sample * t);
+                }
+            }
+
+            self.crossfade_pos += chunk_len;
+            current_sample_idx += chunk_len;
+            if self.crossfade_pos >= crossfade_start + self.crossfade_length {
+                self.finish_crossfade();
+            }
+        }
+
+        Self::process_engine(&mut self.engine, channels, current_sample_idx..range.end);
+    }
+
+    /// Clear the convolution's internal state. If the convolver was crossfading to a new impulse
+    /// response, then the new impulse response is used immediately.
+    pub fn reset(&mut self) {
+        if self.next_engine.is_some() {
+            self.finish_crossfade();
+        }
+
+        if let Some(engine) = &mut self.engine {
+            engine.reset();
+        }
+    }
+
+    /// Process a range of samples with an engine, or output silence if no impulse response has
+    /// been loaded.
+    fn process_engine<S: AsMut<[f32]>>(
+        engine: &mut Option<Box<ConvolutionEngine>>,
+        channels: &mut [S],
+        range: Range<usize>,
+    ) {
+        match engine {
+            Some(engine) => engine.process(channels, range),
+            None => {
+                for channel in channels {
+                    channel.as_mut()[range.clone()].fill(0.0);
+                }
+            }
+        }
+    }
+
+    /// Replace the current engine with the engine that was being faded in, and hand the old engine
+    /// back to the loaders.
+    fn finish_crossfade(&mut self) {
+        let old_engine = std::mem::replace(&mut self.engine, self.next_engine.take());
+        if let Some(old_engine) = old_engine {
+            // New engines are only picked up when there is room in this queue
+            let push_successful = self.shared.retired.push(old_engine).is_ok();
+            nih_debug_assert!(push_successful, "The retired engine queue is full");
+        }
+    }
+}
+
+impl ConvolverLoader {
+    /// Prepare an impulse response and send it to the audio thread, where the [`Convolver`] will
+    /// crossfade to it. `impulse_response` contains the impulse response's channels, which are
+    /// mapped to the convolver's channels according to `layout`. The impulse response should
+    /// already have been resampled to the plugin's sample rate.
+    ///
+    /// This allocates and performs a lot of FFT operations, so it must not be called from the
+    /// audio thread. Returns an error if the impulse response does not match the layout or the
+    /// convolver's channel count.
+    pub fn load<S: AsRef<[f32]>>(&self, impulse_response: &[S], layout: IrLayout) -> Result<()> {
+        let shared = &self.shared;
+        let paths = match layout {
+            IrLayout::PerChannel => {
+                if impulse_response.len() != 1 && impulse_response.len() != shared.num_channels {
+                    bail!(
+                        "Expected a mono impulse response or an impulse response with {} \
+                         channels, got {} channels",
+                        shared.num_channels,
+                        impulse_response.len()
+                    );
+                }
+
+                (0..shared.num_channels)
+                    .map(|channel_idx| ConvolutionPath {
+                        input_channel: channel_idx,
+                        output_channel: channel_idx,
+                        ir_channel: channel_idx.min(impulse_response.len() - 1),
+                    })
+                    .collect()
+            }
+            IrLayout::TrueStereo => {
+                if shared.num_channels != 2 {
+                    bail!(
+                        "True-stereo impulse responses require two audio channels, the convolver \
+                         has {}",
+                        shared.num_channels
+                    );
+                }
+                if impulse_response.len() != 4 {
+                    bail!(
+                        "True-stereo impulse responses need to have four channels, got {}",
+                        impulse_response.len()
+                    );
+                }
+
+                vec![
+                    ConvolutionPath {
+                        input_channel: 0,
+                        output_channel: 0,
+                        ir_channel: 0,
+                    },
+                    ConvolutionPath {
+                        input_channel: 0,
+                        output_channel: 1,
+                        ir_channel: 1,
+                    },
+                    ConvolutionPath {
+                        input_channel: 1,
+                        output_channel: 0,
+                        ir_channel: 2,
+                    },
+                    ConvolutionPath {
+                        input_channel: 1,
+                        output_channel: 1,
+                        ir_channel: 3,
+                    },
+                ]
+            }
+        };
+        if impulse_response
+            .iter()
+            .all(|channel| channel.as_ref().is_empty())
+        {
+            bail!("The impulse response is empty");
+        }
+
+        let engine = Box::new(ConvolutionEngine::new(
+            shared.num_channels,
+            shared.partition_size,
+            shared.mode,
+            impulse_response,
+            paths,
+        ));
+
+        let _loading_guard = shared.loading_lock.lock();
+        self.collect_garbage();
+
+        // If the previous impulse response hasn't been picked up yet, then it's replaced by the new
+        // one and deallocated here
+        let pending_engine = shared.incoming.pop();
+        let push_successful = shared.incoming.push(engine).is_ok();
+        nih_debug_assert!(push_successful, "The incoming engine queue is full");
+        drop(pending_engine);
+
+        Ok(())
+    }
+
+    /// Deallocate the impulse responses the audio thread is no longer using. This is also done
+    /// automatically when loading a new impulse response. Must not be called from the audio
+    /// thread.
+    pub fn collect_garbage(&self) {
+        while self.shared.retired.pop().is_some() {}
+    }
+}
+
+impl ConvolutionEngine {
+    /// Partition the impulse response and compute the DFTs for each partition, and allocate all of
+    /// the buffers needed for the convolution.
+    fn new<S: AsRef<[f32]>>(
+        num_channels: usize,
+        partition_size: usize,
+        mode: ConvolutionMode,
+        impulse_response: &[S],
+        paths: Vec<ConvolutionPath>,
+    ) -> Self {
+        let fft_size = partition_size * 2;
+        let num_bins = partition_size + 1;
+        let mut fft_planner = RealFftPlanner::new();
+        let r2c_plan = fft_planner.plan_fft_forward(fft_size);
+        let c2r_plan = fft_planner.plan_fft_inverse(fft_size);
+        let mut real_scratch_buffer = vec![0.0; fft_size];
+        let mut fft_scratch_buffer =
+            vec![Complex32::default(); r2c_plan.get_scratch_len().max(c2r_plan.get_scratch_len())];
+
+        // In zero-latency mode the first partition is convolved directly, and the rest of the
+        // impulse response is convolved in the frequency domain. Since the frequency domain
+        // convolution has exactly one partition of latency, the tail lines up with the head.
+        let (ir_heads, ir_tails): (Vec<Vec<f32>>, Vec<&[f32]>) = match mode {
+            ConvolutionMode::ZeroLatency => impulse_response
+                .iter()
+                .map(|channel| {
+                    let channel = channel.as_ref();
+                    let head_len = channel.len().min(partition_size);
+
+                    let mut head = vec![0.0; partition_size];
+                    head[..head_len].copy_from_slice(&channel[..head_len]);
+                    head.reverse();
+
+                    (head, &channel[head_len..])
+                })
+                .unzip(),
+            ConvolutionMode::LowLatency => (
+                Vec::new(),
+                impulse_response
+                    .iter()
+                    .map(|channel| channel.as_ref())
+                    .collect(),
+            ),
+        };
+
+        let num_partitions = ir_tails
+            .iter()
+            .map(|tail| (tail.len() + partition_size - 1) / partition_size)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        // The normalization for the IFFT is folded into the impulse response's DFTs
+        let normalization_factor = 1.0 / fft_size as f32;
+        let ir_partitions = ir_tails
+            .iter()
+            .map(|tail| {
+                (0..num_partitions)
+                    .map(|partition_idx| {
+                        let start = (partition_idx * partition_size).min(tail.len());
+                        let end = (start + partition_size).min(tail.len());
+                        real_scratch_buffer.fill(0.0);
+                        real_scratch_buffer[..end - start].copy_from_slice(&tail[start..end]);
+
+                        let mut spectrum = vec![Complex32::default(); num_bins];
+                        r2c_plan
+                            .process_with_scratch(
+                                &mut real_scratch_buffer,
+                                &mut spectrum,
+                                &mut fft_scratch_buffer,
+                            )
+                            .unwrap();
+                        for bin in &mut spectrum {
+                            *bin *= normalization_factor;
+                        }
+
+                        spectrum
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            partition_size,
+            mode,
+
+            paths,
+            ir_partitions,
+            ir_heads,
+
+            input_buffers: vec![vec![0.0; fft_size]; num_channels],
+            input_spectra: vec![
+                vec![vec![Complex32::default(); num_bins]; num_partitions];
+                num_channels
+            ],
+            input_spectra_pos: 0,
+            output_buffers: vec![vec![0.0; partition_size]; num_channels],
+            partition_pos: 0,
+
+            history_buffers: match mode {
+                ConvolutionMode::ZeroLatency => vec![vec![0.0; fft_size]; num_channels],
+                ConvolutionMode::LowLatency => Vec::new(),
+            },
+            history_pos: 0,
+            head_output_buffers: vec![vec![0.0; partition_size]; num_channels],
+
+            r2c_plan,
+            c2r_plan,
+            real_scratch_buffer,
+            complex_scratch_buffer: vec![Complex32::default(); num_bins],
+            fft_scratch_buffer,
+        }
+    }
+
+    /// Convolve a range of samples in place. All input samples for a chunk are read before any of
+    /// the outputs are written, so channels can be mixed together when using true-stereo impulse
+    /// responses.
+    fn process<S: AsMut<[f32]>>(&mut self, channels: &mut [S], range: Range<usize>) {
+        let partition_size = self.partition_size;
+
+        let mut current_sample_idx = range.start;
+        while current_sample_idx < range.end {
+            let chunk_len =
+                (partition_size - self.partition_pos).min(range.end - current_sample_idx);
+            let chunk = current_sample_idx..current_sample_idx + chunk_len;
+            let partition_range = self.partition_pos..self.partition_pos + chunk_len;
+
+            if self.mode == ConvolutionMode::ZeroLatency {
+                for head_output in &mut self.head_output_buffers {
+                    head_output[..chunk_len].fill(0.0);
+                }
+            }
+
+            for (channel_idx, channel) in channels.iter_mut().enumerate() {
+                let input = &channel.as_mut()[chunk.clone()];
+                self.input_buffers[channel_idx][partition_size..][partition_range.clone()]
+                    .copy_from_slice(input);
+
+                if self.mode == ConvolutionMode::ZeroLatency {
+                    let history = &mut self.history_buffers[channel_idx];
+                    let mut history_pos = self.history_pos;
+                    for (sample_idx, sample) in input.iter().enumerate() {
+                        history[history_pos] = *sample;
+                        history[history_pos + partition_size] = *sample;
+                        history_pos = (history_pos + 1) % partition_size;
+
+                        // This contains the last `partition_size` samples from old to new, and the
+                        // heads have been reversed
+                        let recent_samples = &history[history_pos..history_pos + partition_size];
+                        for path in self
+                            .paths
+                            .iter()
+                            .filter(|path| path.input_channel == channel_idx)
+                        {
+                            let head = &self.ir_heads[path.ir_channel];
+                            self.head_output_buffers[path.output_channel][sample_idx] +=
+                                recent_samples
+                                    .iter()
+                                    .zip(head)
+                                    .map(|(sample, coefficient)| sample * coefficient)
+                                    .sum::<f32>();
+                        }
+                    }
+                }
+            }
+            if self.mode == ConvolutionMode::ZeroLatency {
+                self.history_pos = (self.history_pos + chunk_len) % partition_size;
+            }
+
+            // All inputs have been read, so the outputs can now be written
+            for (channel_idx, channel) in channels.iter_mut().enumerate() {
+                let output = &mut channel.as_mut()[chunk.clone()];
+                output.copy_from_slice(&self.output_buffers[channel_idx][partition_range.clone()]);
+
+                if self.mode == ConvolutionMode::ZeroLatency {
+                    for (output_sample, head_sample) in output
+                        .iter_mut()
+                        .zip(&self.head_output_buffers[channel_idx])
+                    {
+                        *output_sample += head_sample;
+                    }
+                }
+            }
+
+            self.partition_pos += chunk_len;
+            current_sample_idx += chunk_len;
+            if self.partition_pos == partition_size {
+                self.process_partition();
+                self.partition_pos = 0;
+            }
+        }
+    }
+
+    /// Compute the output for the next partition once a full partition of input has been
+    /// gathered.
+    fn process_partition(&mut self) {
+        let partition_size = self.partition_size;
+        let num_partitions = self.input_spectra.first().map_or(1, Vec::len);
+
+        // The most recent input DFT is stored at `input_spectra_pos`, and the DFT from `n`
+        // partitions ago is stored `n` positions before that
+        self.input_spectra_pos = (self.input_spectra_pos + 1) % num_partitions;
+        for (input_buffer, input_spectra) in self
+            .input_buffers
+            .iter_mut()
+            .zip(self.input_spectra.iter_mut())
+        {
+            self.real_scratch_buffer.copy_from_slice(input_buffer);
+            self.r2c_plan
+                .process_with_scratch(
+                    &mut self.real_scratch_buffer,
+                    &mut input_spectra[self.input_spectra_pos],
+                    &mut self.fft_scratch_buffer,
+                )
+                .unwrap();
+
+            // This partition's input becomes the first half of the next FFT's input
+            input_buffer.copy_within(partition_size.., 0);
+        }
+
+        for (output_channel_idx, output_buffer) in self.output_buffers.iter_mut().enumerate() {
+            self.complex_scratch_buffer.fill(Complex32::default());
+            for path in self
+                .paths
+                .iter()
+                .filter(|path| path.output_channel == output_channel_idx)
+            {
+                let input_spectra = &self.input_spectra[path.input_channel];
+                for (partition_idx, ir_partition) in
+                    self.ir_partitions[path.ir_channel].iter().enumerate()
+                {
+                    let input_spectrum = &input_spectra[(self.input_spectra_pos + num_partitions
+                        - partition_idx)
+                        % num_partitions];
+                    for ((output_bin, input_bin), ir_bin) in self
+                        .complex_scratch_buffer
+                        .iter_mut()
+                        .zip(input_spectrum)
+                        .zip(ir_partition)
+                    {
+                        *output_bin += input_bin * ir_bin;
+                    }
+                }
+            }
+
+            // These should already be zero, but realfft is strict about this
+            self.complex_scratch_buffer[0].im = 0.0;
+            self.complex_scratch_buffer[partition_size].im = 0.0;
+            self.c2r_plan
+                .process_with_scratch(
+                    &mut self.complex_scratch_buffer,
+                    &mut self.real_scratch_buffer,
+                    &mut self.fft_scratch_buffer,
+                )
+                .unwrap();
+
+            // With overlap-save only the second half of the output is valid
+            output_buffer.copy_from_slice(&self.real_scratch_buffer[partition_size..]);
+        }
+    }
+
+    /// Clear all of the buffers containing past inputs and outputs.
+    fn reset(&mut self) {
+        for buffer in self
+            .input_buffers
+            .iter_mut()
+            .chain(self.output_buffers.iter_mut())
+            .chain(self.history_buffers.iter_mut())
+        {
+            buffer.fill(0.0);
+        }
+        for spectrum in self.input_spectra.iter_mut().flatten() {
+            spectrum.fill(Complex32::default());
+        }
+
+        self.input_spectra_pos = 0;
+        self.partition_pos = 0;
+        self.history_pos = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise in `[-1, 1]`.
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(747_796_405).wrapping_add(1);
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    fn direct_convolution(input: &[f32], impulse_response: &[f32]) -> Vec<f32> {
+        (0..input.len())
+            .map(|sample_idx| {
+                impulse_response
+                    .iter()
+                    .take(sample_idx + 1)
+                    .enumerate()
+                    .map(|(tap_idx, tap)| tap * input[sample_idx - tap_idx])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Load the impulse response, and then process the input in oddly sized blocks. The first
+    /// process call only picks up the impulse response and is not part of the output. The crossfade
+    /// length is 1, so the new impulse response is used immediately.
+    fn convolve(
+        mode: ConvolutionMode,
+        partition_size: usize,
+        inputs: &[Vec<f32>],
+        impulse_response: &[Vec<f32>],
+        layout: IrLayout,
+    ) -> Vec<Vec<f32>> {
+        let mut convolver = Convolver::new(inputs.len(), partition_size, mode, 1);
+        convolver.loader().load(impulse_response, layout).unwrap();
+        convolver.process_channels(&mut vec![vec![0.0; 0]; inputs.len()], 0..0);
+        convolver.reset();
+
+        let mut outputs = inputs.to_vec();
+        let num_samples = outputs[0].len();
+        let mut block_start = 0;
+        for block_size in [1, 7, 100, 3].into_iter().cycle() {
+            if block_start >= num_samples {
+                break;
+            }
+
+            let block_end = (block_start + block_size).min(num_samples);
+            convolver.process_channels(&mut outputs, block_start..block_end);
+            block_start = block_end;
+        }
+
+        outputs
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (sample_idx, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{actual} != {expected} at sample {sample_idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn zero_latency_matches_direct_convolution() {
+        let input = noise(1000, 1);
+        let impulse_response = noise(300, 2);
+        let expected = direct_convolution(&input, &impulse_response);
+
+        let outputs = convolve(
+            ConvolutionMode::ZeroLatency,
+            32,
+            &[input],
+            &[impulse_response],
+            IrLayout::PerChannel,
+        );
+        assert_close(&outputs[0], &expected);
+    }
+
+    #[test]
+    fn low_latency_matches_delayed_direct_convolution() {
+        const PARTITION_SIZE: usize = 32;
+
+        let input = noise(1000, 3);
+        let impulse_response = noise(300, 4);
+        let expected = direct_convolution(&input, &impulse_response);
+
+        let outputs = convolve(
+            ConvolutionMode::LowLatency,
+            PARTITION_SIZE,
+            &[input],
+            &[impulse_response],
+            IrLayout::PerChannel,
+        );
+        assert!(outputs[0][..PARTITION_SIZE]
+            .iter()
+            .all(|sample| *sample == 0.0));
+        assert_close(
+            &outputs[0][PARTITION_SIZE..],
+            &expected[..expected.len() - PARTITION_SIZE],
+        );
+    }
+
+    #[test]
+    fn true_stereo() {
+        let left = noise(500, 5);
+        let right = noise(500, 6);
+        let impulse_response = vec![noise(100, 7), noise(50, 8), noise(200, 9), noise(10, 10)];
+
+        let outputs = convolve(
+            ConvolutionMode::ZeroLatency,
+            16,
+            &[left.clone(), right.clone()],
+            &impulse_response,
+            IrLayout::TrueStereo,
+        );
+
+        let expected_left: Vec<f32> = direct_convolution(&left, &impulse_response[0])
+            .into_iter()
+            .zip(direct_convolution(&right, &impulse_response[2]))
+            .map(|(l, r)| l + r)
+            .collect();
+        let expected_right: Vec<f32> = direct_convolution(&left, &impulse_response[1])
+            .into_iter()
+            .zip(direct_convolution(&right, &impulse_response[3]))
+            .map(|(l, r)| l + r)
+            .collect();
+        assert_close(&outputs[0], &expected_left);
+        assert_close(&outputs[1], &expected_right);
+    }
+
+    #[test]
+    fn invalid_layouts() {
+        let convolver = Convolver::new(2, 16, ConvolutionMode::ZeroLatency, 1);
+        let loader = convolver.loader();
+
+        assert!(loader.load(&[[1.0]; 3], IrLayout::PerChannel).is_err());
+        assert!(loader.load(&[[1.0]; 2], IrLayout::TrueStereo).is_err());
+        assert!(loader
+            .load(&[Vec::<f32>::new()], IrLayout::PerChannel)
+            .is_err());
+        assert!(loader.load(&[vec![1.0]], IrLayout::PerChannel).is_ok());
+    }
+
+    #[test]
+    fn crossfade_and_retire() {
+        const CROSSFADE_LENGTH: usize = 100;
+
+        let mut convolver = Convolver::new(1, 16, ConvolutionMode::ZeroLatency, CROSSFADE_LENGTH);
+        let loader = convolver.loader();
+
+        // Without an impulse response the output is silent
+        let mut buffer = vec![vec![1.0; 200]];
+        convolver.process_channels(&mut buffer, 0..200);
+        assert!(buffer[0].iter().all(|sample| *sample == 0.0));
+
+        // A unit impulse should fade in from silence to the dry signal
+        loader.load(&[vec![1.0]], IrLayout::PerChannel).unwrap();
+        let mut buffer = vec![vec![1.0; 200]];
+        convolver.process_channels(&mut buffer, 0..200);
+        assert!(buffer[0][..CROSSFADE_LENGTH - 1]
+            .windows(2)
+            .all(|samples| samples[0] < samples[1]));
+        assert!(buffer[0][CROSSFADE_LENGTH - 1..]
+            .iter()
+            .all(|sample| *sample == 1.0));
+
+        // Halving the gain should crossfade down, after which the previous impulse response is
+        // handed back to the loader
+        loader.load(&[vec![0.5]], IrLayout::PerChannel).unwrap();
+        let mut buffer = vec![vec![1.0; 200]];
+        convolver.process_channels(&mut buffer, 0..200);
+        assert_close(&buffer[0][CROSSFADE_LENGTH..], &[0.5; 100]);
+        assert_eq!(convolver.shared.retired.len(), 1);
+
+        loader.collect_garbage();
+        assert!(convolver.shared.retired.is_empty());
+    }
+
+    #[test]
+    fn low_latency_crossfade() {
+        const PARTITION_SIZE: usize = 16;
+        const CROSSFADE_LENGTH: usize = 100;
+
+        let mut convolver = Convolver::new(
+            1,
+            PARTITION_SIZE,
+            ConvolutionMode::LowLatency,
+            CROSSFADE_LENGTH,
+        );
+        let loader = convolver.loader();
+
+        loader.load(&[vec![1.0]], IrLayout::PerChannel).unwrap();
+        let mut buffer = vec![vec![1.0; 200]];
+        convolver.process_channels(&mut buffer, 0..200);
+        assert_close(&buffer[0][PARTITION_SIZE + CROSSFADE_LENGTH..], &[1.0; 84]);
+
+        // The new engine does not output anything during the first partition, so the old impulse
+        // response should be used until then instead of fading towards silence
+        loader.load(&[vec![0.5]], IrLayout::PerChannel).unwrap();
+        let mut buffer = vec![vec![1.0; 200]];
+        convolver.process_channels(&mut buffer, 0..200);
+        assert_close(&buffer[0][..PARTITION_SIZE], &[1.0; PARTITION_SIZE]);
+        assert!(
+            buffer[0][PARTITION_SIZE - 1..PARTITION_SIZE + CROSSFADE_LENGTH]
+                .windows(2)
+                .all(|samples| samples[0] > samples[1])
+        );
+        assert_close(&buffer[0][PARTITION_SIZE + CROSSFADE_LENGTH..], &[0.5; 84]);
+        assert_eq!(convolver.shared.retired.len(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+//! Crossovers for splitting a signal into multiple frequency bands. [`IirCrossover`] uses
+//! minimum-phase Linkwitz-Riley filters and does not introduce any latency, while [`FirCrossover`]
+//! uses linear-phase FIR filters with the same magnitude response at the cost of some latency. In
+//! both cases the split bands add back up to a signal with a flat frequency response.
+//!
+//! Both crossovers can process entire [`Buffer`]s, where every band is written to its own buffer,
+//! or individual channels using any kind of slice as band outputs.
+
+use std::f32::consts;
+
+use crate::buffer::Buffer;
+
+mod fir;
+mod iir;
+
+pub use fir::{FirCrossover, FirCrossoverType};
+pub use iir::{IirCrossover, IirCrossoverType};
+
+/// The Q value for a Butterworth-style second order filter. Two of these in series form one half
+/// of an LR24 crossover.
+const NEUTRAL_Q: f32 = consts::FRAC_1_SQRT_2;
+
+/// Somewhere the split bands for a single channel can be written to. This lets the crossovers
+/// write to both [`Buffer`]s and plain slices without needing to collect the outputs first.
+trait BandOutputs {
+    /// Get the output for band `band_idx`. This is only called for bands that are in use.
+    fn band(&mut self, band_idx: usize) -> &mut [f32];
+}
+
+/// Writes the split bands for one channel to a list of [`Buffer`]s, with one buffer per band.
+struct BufferBandOutputs<'slice, 'buffer> {
+    buffers: &'slice mut [Buffer<'buffer>],
+    channel_idx: usize,
+}
+
+impl BandOutputs for BufferBandOutputs<'_, '_> {
+    fn band(&mut self, band_idx: usize) -> &mut [f32] {
+        &mut *self.buffers[band_idx].as_slice()[self.channel_idx]
+    }
+}
+
+impl<S: AsMut<[f32]>> BandOutputs for [S] {
+    fn band(&mut self, band_idx: usize) -> &mut [f32] {
+        self[band_idx].as_mut()
+    }
+}
@@ -0,0 +1,634 @@
+//! Linear-phase FIR crossovers using FFT convolution.
+
+use realfft::num_complex::Complex32;
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use std::f32;
+use std::sync::Arc;
+
+use super::{BandOutputs, BufferBandOutputs, NEUTRAL_Q};
+use crate::buffer::Buffer;
+use crate::util::filter::{Biquad, BiquadCoefficients};
+
+/// We're doing FFT convolution here since otherwise there's no way to get decent low-frequency
+/// accuracy while still having acceptable performance. The input going into the STFT will be
+/// smaller since it will be padding with zeroes to compensate for the otherwise overlapping tail
+/// caused by the convolution.
+const FFT_SIZE: usize = 4096;
+/// The input chunk size the FFT convolution is processing. This is also part of the latency, with
+/// the total latency being `FFT_INPUT_SIZE + (FILTER_SIZE / 2)` samples. By having this be exactly
+/// half of FFT_SIZE, we can make the overlap-add part of the FFT convolution a lot simpler for
+/// ourselves. (check [`StftHelper`][crate::util::StftHelper] for an example that can handle
+/// arbitrary padding)
+const FFT_INPUT_SIZE: usize = FFT_SIZE / 2;
+/// The size of the FIR filter window, or the number of taps. Convoling `FFT_INPUT_SIZE` samples
+/// with this filter should fit exactly in `FFT_SIZE`, and it should be an odd number.
+const FILTER_SIZE: usize = FFT_SIZE - FFT_INPUT_SIZE + 1;
+
+/// A multiband crossover using linear-phase FIR filters. The bands have the same magnitude
+/// responses as those produced by the equivalent [`IirCrossover`][super::IirCrossover], but
+/// without any phase shift. The bands sum back to the original signal delayed by
+/// [`latency()`][Self::latency()] samples. This latency should be reported to the host.
+pub struct FirCrossover {
+    /// The kind of crossover to use. `.update()` must be called after changing this.
+    mode: FirCrossoverType,
+    /// The number of bands the signal is currently split into. Set in `.update()`.
+    num_bands: usize,
+
+    /// Filters for each of the bands. Depending on the number of bands passed to `.update()`, not
+    /// all of these may be used. The first one always contains a low-pass filter, the last one
+    /// always contains a high-pass filter, while the other bands will contain band-pass filters.
+    ///
+    /// These filters will be fed the FFT from the main input to produce output samples for the
+    /// next period. Everything could be a bit nicer to read if the filter did the entire STFT
+    /// process, but that would mean duplicating the input ring buffer and forward DFT for every
+    /// band.
+    band_filters: Vec<FftFirFilter>,
+
+    /// A ring buffer for every channel that is used to store inputs for the next FFT. Until it is
+    /// time to take the next FFT, samples are copied from the inputs to this buffer, while
+    /// simultaneously copying the already processed output samples from the output buffers to the
+    /// output. Once `io_buffers_next_indices` wrap back around to 0, the next buffer should be
+    /// produced.
+    input_buffers: Vec<Vec<f32>>,
+    /// A ring buffer that contains the next period's outputs for each band, indexed by
+    /// `[band_idx][channel_idx]`. This is written to and read from in lockstep with
+    /// `input_buffers`.
+    band_output_buffers: Vec<Vec<Vec<f32>>>,
+    /// The index in the inner `input_buffers` the next sample should be read from. After a sample
+    /// is written to the band's output then this is incremented by one. Once
+    /// `self.io_buffers_next_indices[channel_idx] == FFT_INPUT_SIZE` then the next block should be
+    /// processed.
+    ///
+    /// This is stored per channel since each channel is processed individually. While this should
+    /// of course stay in sync, this makes it much simpler to process the channels in sequence.
+    io_buffers_next_indices: Vec<usize>,
+
+    /// The algorithm for the FFT operation.
+    r2c_plan: Arc<dyn RealToComplex<f32>>,
+    /// The algorithm for the IFFT operation.
+    c2r_plan: Arc<dyn ComplexToReal<f32>>,
+
+    /// A real buffer that may be written to in place during the FFT and IFFT operations.
+    real_scratch_buffer: Vec<f32>,
+    /// A complex buffer corresponding to `real_scratch_buffer` that may be written to in place
+    /// during the FFT and IFFT operations.
+    complex_scratch_buffer: Vec<Complex32>,
+    /// The FFT of the current input period. Since realfft expects to be able to modify its inputs,
+    /// this is copied to `complex_scratch_buffer` for every band.
+    input_fft_buffer: Vec<Complex32>,
+}
+
+/// The type of FIR crossover to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirCrossoverType {
+    /// Emulates the filter slope of [`IirCrossoverType::LinkwitzRiley24`][super::IirCrossoverType::LinkwitzRiley24],
+    /// but with linear-phase FIR filters instead of minimum-phase IIR filters. The exact same
+    /// filters are used to design the FIR filters.
+    LinkwitzRiley24LinearPhase,
+}
+
+/// A single FIR filter that may be configured in any way. In the crossover this will be a
+/// linear-phase low-pass, band-pass, or high-pass filter. Implemented using FFT convolution.
+///
+/// The input is processed in `FFT_INPUT_SIZE` chunks, and the size of the FFT window is
+/// `FFT_INPUT_SIZE * 2`. That makes handling the overlap easy, as each IDFT after multiplying the
+/// padded input and the padded impulse response FFTs will result one `FFT_INPUT_SIZE` period of
+/// output that can be taken as is, followed by one `FFT_INPUT_SIZE` period of samples that need to
+/// be added to the next period's outputs as part of the overlap-add process.
+#[derive(Debug, Clone)]
+struct FftFirFilter {
+    /// A `FILTER_SIZE` sized impulse response. Padded, ran through the DFT, and then normalized by
+    /// dividing by `FFT_SIZE`.
+    padded_ir_fft: Vec<Complex32>,
+
+    /// The padding from the previous IDFT operation that needs to be added to the next output
+    /// buffer for each channel. After the IDFT process there will be an `FFT_SIZE` real scratch
+    /// buffer containing the output. At that point the first `FFT_INPUT_SIZE` samples of those will
+    /// be copied to the crossover's output buffers, `unapplied_padding_buffers` will be added to
+    /// that output buffer, and then finally the last `FFT_INPUT_SIZE` samples of the scratch buffer
+    /// are copied to `unapplied_padding_buffers`. This thus makes sure the tail gets delayed by
+    /// another period so that everything matches up.
+    unapplied_padding_buffers: Vec<Vec<f32>>,
+}
+
+/// Coefficients for a (linear-phase) FIR filter. This struct includes ways to design the filter.
+/// `N` is the number of taps/coefficients and should be odd for linear-phase filters.
+#[repr(transparent)]
+#[derive(Debug, Clone)]
+struct FirCoefficients<const N: usize>([f32; N]);
+
+impl FirCrossover {
+    /// Create a new multiband crossover processor for `num_channels` channels that can split a
+    /// signal into up to `max_bands` bands. All filters will be configured to pass audio through as
+    /// is, albeit with a delay. `.update()` needs to be called first to set up the filters, and
+    /// `.reset()` can be called whenever the filter state must be cleared.
+    ///
+    /// Make sure to add the latency reported by [`latency()`][Self::latency()] to the plugin's
+    /// reported latency.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bands` is less than two.
+    pub fn new(mode: FirCrossoverType, num_channels: usize, max_bands: usize) -> Self {
+        assert!(max_bands >= 2, "A crossover needs at least two bands");
+
+        let mut fft_planner = RealFftPlanner::new();
+
+        Self {
+            mode,
+            num_bands: max_bands,
+
+            band_filters: vec![FftFirFilter::new(num_channels); max_bands],
+
+            input_buffers: vec![vec![0.0; FFT_INPUT_SIZE]; num_channels],
+            band_output_buffers: vec![vec![vec![0.0; FFT_INPUT_SIZE]; num_channels]; max_bands],
+            io_buffers_next_indices: vec![0; num_channels],
+
+            r2c_plan: fft_planner.plan_fft_forward(FFT_SIZE),
+            c2r_plan: fft_planner.plan_fft_inverse(FFT_SIZE),
+            real_scratch_buffer: vec![0.0; FFT_SIZE],
+            complex_scratch_buffer: vec![Complex32::default(); FFT_SIZE / 2 + 1],
+            input_fft_buffer: vec![Complex32::default(); FFT_SIZE / 2 + 1],
+        }
+    }
+
+    /// Get the current latency in samples. This depends on the selected mode.
+    pub fn latency(&self) -> u32 {
+        // Actually, that's a lie, since we currently only do linear-phase filters with a constant
+        // size
+        match self.mode {
+            FirCrossoverType::LinkwitzRiley24LinearPhase => {
+                (FFT_INPUT_SIZE + (FILTER_SIZE / 2)) as u32
+            }
+        }
+    }
+
+    /// The number of bands the signal is currently split into.
+    pub fn num_bands(&self) -> usize {
+        self.num_bands
+    }
+
+    /// Split every channel in `input` into bands using the crossovers previously configured through
+    /// `.update()`. Every band is written to the buffer with the same index in `band_outputs`, so
+    /// this needs to contain at least [`num_bands()`][Self::num_bands()] buffers with the same
+    /// number of channels and samples as `input`. Unused band buffers are not written to. The
+    /// outputs are delayed by [`latency()`][Self::latency()] samples.
+    pub fn process(&mut self, input: &Buffer, band_outputs: &mut [Buffer]) {
+        nih_debug_assert!(band_outputs.len() >= self.num_bands);
+
+        for (channel_idx, channel_samples) in input.as_slice_immutable().iter().enumerate() {
+            self.process_channel_inner(
+                channel_idx,
+                channel_samples,
+                &mut BufferBandOutputs {
+                    buffers: band_outputs,
+                    channel_idx,
+                },
+            );
+        }
+    }
+
+    /// The same as [`process()`][Self::process()], but for a single channel. `band_outputs` needs
+    /// to contain at least [`num_bands()`][Self::num_bands()] slices that are the same length as
+    /// `input`.
+    pub fn process_channel<S: AsMut<[f32]>>(
+        &mut self,
+        channel_idx: usize,
+        input: &[f32],
+        band_outputs: &mut [S],
+    ) {
+        nih_debug_assert!(band_outputs.len() >= self.num_bands);
+
+        self.process_channel_inner(channel_idx, input, band_outputs);
+    }
+
+    /// Update the crossover frequencies for all filters. This splits the signal into `num_bands`
+    /// bands using the first `num_bands - 1` frequencies from `frequencies`. `num_bands` needs to
+    /// be in `[2, max_bands]`.
+    pub fn update(&mut self, sample_rate: f32, num_bands: usize, frequencies: &[f32]) {
+        nih_debug_assert!(num_bands >= 2);
+        nih_debug_assert!(num_bands <= self.band_filters.len());
+        nih_debug_assert!(frequencies.len() >= num_bands - 1);
+        self.num_bands = num_bands;
+
+        match self.mode {
+            FirCrossoverType::LinkwitzRiley24LinearPhase => {
+                // The goal here is to design `num_bands` filters with the same frequency response
+                // magnitudes as the split bands in the IIR LR24 crossover version with the same
+                // center frequencies would have. The algorithm works in two stages. First, the IIR
+                // low-pass filters for the crossovers used in the equivalent IIR LR24 version are
+                // computed and converted to equivalent linear-phase FIR filters using the algorithm
+                // described below in `FirCoefficients`. Then these are used to build the
+                // coefficients for the bands:
+                //
+                // - The first band is always simply the first band's low-pass filter.
+                // - The middle bands are band-pass filters. These are created by taking the next
+                //   crossover's low-pass filter and subtracting the accumulated band impulse
+                //   response up to that point. The accumulated band impulse response is initialized
+                //   with the first band's low-pass filter, and the band-pass filter for every band
+                //   after that gets added to it.
+                // - The final band is a high-pass filter that's computed through spectral inversion
+                //   from the accumulated band impulse response.
+
+                // As explained above, we'll start with the low-pass band
+                let iir_coefs = BiquadCoefficients::lowpass(sample_rate, frequencies[0], NEUTRAL_Q);
+                let lp_fir_coefs =
+                    FirCoefficients::<FILTER_SIZE>::design_fourth_order_linear_phase_low_pass_from_biquad(
+                        iir_coefs,
+                    );
+                self.band_filters[0].recompute_coefficients(
+                    &lp_fir_coefs,
+                    &*self.r2c_plan,
+                    &mut self.real_scratch_buffer,
+                    &mut self.complex_scratch_buffer,
+                );
+
+                // For the band-pass filters and the final high-pass filter, we need to keep track
+                // of the accumulated impulse response
+                let mut accumulated_ir = lp_fir_coefs;
+                for (split_frequency, band_filter) in frequencies
+                    .iter()
+                    .zip(self.band_filters.iter_mut())
+                    // There are `num_bands` bands, so there are `num_bands - 1` crossovers. The
+                    // last band is formed from the accumulated impulse response.
+                    .take(num_bands - 1)
+                    // And the first band is already taken care of
+                    .skip(1)
+                {
+                    let iir_coefs =
+                        BiquadCoefficients::lowpass(sample_rate, *split_frequency, NEUTRAL_Q);
+                    let lp_fir_coefs =
+                        FirCoefficients::<FILTER_SIZE>::design_fourth_order_linear_phase_low_pass_from_biquad(
+                            iir_coefs,
+                        );
+
+                    // We want the band between the accumulated frequency response and the next
+                    // crossover's low-pass filter
+                    let mut fir_bp_coefs = lp_fir_coefs;
+                    for (bp_coef, accumulated_coef) in
+                        fir_bp_coefs.0.iter_mut().zip(accumulated_ir.0.iter_mut())
+                    {
+                        // At this point `bp_coef` is the low-pass filter
+                        *bp_coef -= *accumulated_coef;
+
+                        // And the accumulated coefficients for the next band/for the high-pass
+                        // filter should contain this band-pass filter. This becomes a bit weirder
+                        // to read when it's a single loop, but essentially this is what's going on
+                        // here:
+                        //
+                        //     fir_bp_coefs = fir_lp_coefs - accumulated_ir
+                        //     accumulated_ir += fir_bp_coefs
+
+                        *accumulated_coef += *bp_coef;
+                    }
+
+                    band_filter.recompute_coefficients(
+                        &fir_bp_coefs,
+                        &*self.r2c_plan,
+                        &mut self.real_scratch_buffer,
+                        &mut self.complex_scratch_buffer,
+                    );
+                }
+
+                // And finally we can do a spectral inversion of the accumulated IR to the the last
+                // band's high-pass filter
+                let mut fir_hp_coefs = accumulated_ir;
+                for coef in fir_hp_coefs.0.iter_mut() {
+                    *coef = -*coef;
+                }
+                fir_hp_coefs.0[FILTER_SIZE / 2] += 1.0;
+
+                self.band_filters[num_bands - 1].recompute_coefficients(
+                    &fir_hp_coefs,
+                    &*self.r2c_plan,
+                    &mut self.real_scratch_buffer,
+                    &mut self.complex_scratch_buffer,
+                );
+            }
+        }
+    }
+
+    /// Reset the internal filter state for all crossovers.
+    pub fn reset(&mut self) {
+        for filter in self.band_filters.iter_mut() {
+            filter.reset();
+        }
+
+        // The inputs don't need to be reset as they'll be overwritten immediately
+        for buffer in self.band_output_buffers.iter_mut().flatten() {
+            buffer.fill(0.0);
+        }
+
+        // This being 0 means that the very first period will simply output the silence from above
+        // and gather input for the next FFT
+        self.io_buffers_next_indices.fill(0);
+    }
+
+    fn process_channel_inner<O: BandOutputs + ?Sized>(
+        &mut self,
+        channel_idx: usize,
+        input: &[f32],
+        band_outputs: &mut O,
+    ) {
+        let num_bands = self.num_bands;
+
+        // We'll copy already processed output to `band_outputs` while storing input for the next
+        // FFT operation. This is a modified version of what's going on in `StftHelper`.
+        let mut current_sample_idx = 0;
+        while current_sample_idx < input.len() {
+            {
+                // When `self.io_buffers_next_indices == FFT_INPUT_SIZE`, the next block should be
+                // processed
+                let io_buffers_next_idx = self.io_buffers_next_indices[channel_idx];
+                let process_num_samples =
+                    (FFT_INPUT_SIZE - io_buffers_next_idx).min(input.len() - current_sample_idx);
+                let input_range = current_sample_idx..current_sample_idx + process_num_samples;
+                let io_buffers_range =
+                    io_buffers_next_idx..io_buffers_next_idx + process_num_samples;
+
+                // Since we can't do this in-place (without unnecessarily duplicating a ton of data),
+                // copying data from and to the ring buffers can be done with simple memcpys
+                self.input_buffers[channel_idx][io_buffers_range.clone()]
+                    .copy_from_slice(&input[input_range.clone()]);
+                for (band_idx, band_output_buffers) in
+                    self.band_output_buffers.iter().enumerate().take(num_bands)
+                {
+                    band_outputs.band(band_idx)[input_range.clone()].copy_from_slice(
+                        &band_output_buffers[channel_idx][io_buffers_range.clone()],
+                    );
+                }
+
+                // This is tracked per-channel because the channels are processed individually
+                self.io_buffers_next_indices[channel_idx] += process_num_samples;
+                current_sample_idx += process_num_samples;
+            }
+
+            // At this point we either reached the end of the buffer (`current_sample_idx ==
+            // input.len()`), or we filled up the `io_buffer` and we can process the next block
+            if self.io_buffers_next_indices[channel_idx] == FFT_INPUT_SIZE {
+                // Zero pad the input for the FFT
+                self.real_scratch_buffer[..FFT_INPUT_SIZE]
+                    .copy_from_slice(&self.input_buffers[channel_idx]);
+                self.real_scratch_buffer[FFT_INPUT_SIZE..].fill(0.0);
+
+                self.r2c_plan
+                    .process_with_scratch(
+                        &mut self.real_scratch_buffer,
+                        &mut self.input_fft_buffer,
+                        &mut [],
+                    )
+                    .unwrap();
+
+                // The input can then be used to produce each band's output
+                for (band_output_buffers, band_filter) in self
+                    .band_output_buffers
+                    .iter_mut()
+                    .zip(self.band_filters.iter_mut())
+                    .take(num_bands)
+                {
+                    band_filter.process(
+                        &self.input_fft_buffer,
+                        &mut band_output_buffers[channel_idx],
+                        channel_idx,
+                        &*self.c2r_plan,
+                        &mut self.real_scratch_buffer,
+                        &mut self.complex_scratch_buffer,
+                    )
+                }
+
+                self.io_buffers_next_indices[channel_idx] = 0;
+            }
+        }
+    }
+}
+
+impl FftFirFilter {
+    /// Create a filter for `num_channels` channels.
+    pub fn new(num_channels: usize) -> Self {
+        Self {
+            // Would be nicer to initialize this to an impulse response that actually had the
+            // correct position wrt the usual linear-phase latency, but this is fine since it should
+            // never be used anyways
+            padded_ir_fft: vec![Complex32::new(1.0 / FFT_SIZE as f32, 0.0); FFT_SIZE / 2 + 1],
+            unapplied_padding_buffers: vec![vec![0.0; FFT_INPUT_SIZE]; num_channels],
+        }
+    }
+
+    /// Filter `FFT_INPUT_SIZE` samples padded to `FFT_SIZE` through this filter, and write the
+    /// outputs to `output_samples` (belonging to channel `output_channel_idx`), at an
+    /// `FFT_INPUT_SIZE` delay. This is a bit weird and probably difficult to follow because as an
+    /// optimization the DFT is taken only once, and then the IDFT is taken once for every filtered
+    /// band. This function is thus called inside of the overlap-add loop to avoid duplicate work.
+    pub fn process(
+        &mut self,
+        input_fft: &[Complex32],
+        output_samples: &mut [f32],
+        output_channel_idx: usize,
+        c2r_plan: &dyn ComplexToReal<f32>,
+        real_scratch_buffer: &mut [f32],
+        complex_scratch_buffer: &mut [Complex32],
+    ) {
+        // The padded input FFT has already been taken, so we only need to copy it to the scratch
+        // buffer (the input cannot change as the next band might need it as well).
+        complex_scratch_buffer.copy_from_slice(input_fft);
+
+        // The FFT of the impulse response has already been normalized, so we just need to
+        // multiply the two buffers
+        for (output_bin, ir_bin) in complex_scratch_buffer
+            .iter_mut()
+            .zip(self.padded_ir_fft.iter())
+        {
+            *output_bin *= ir_bin;
+        }
+        c2r_plan
+            .process_with_scratch(complex_scratch_buffer, real_scratch_buffer, &mut [])
+            .unwrap();
+
+        // At this point the first `FFT_INPUT_SIZE` elements in `real_scratch_buffer`
+        // contain the output for the next period, while the last `FFT_INPUT_SIZE` elements
+        // contain output that needs to be added to the period after that. Since previous
+        // period also produced similar delayed output, we'll need to copy that to the
+        // results as well.
+        output_samples.copy_from_slice(&real_scratch_buffer[..FFT_INPUT_SIZE]);
+        for (output_sample, padding_sample) in output_samples
+            .iter_mut()
+            .zip(self.unapplied_padding_buffers[output_channel_idx].iter())
+        {
+            *output_sample += *padding_sample;
+        }
+        self.unapplied_padding_buffers[output_channel_idx]
+            .copy_from_slice(&real_scratch_buffer[FFT_INPUT_SIZE..]);
+    }
+
+    /// Set the filter's coefficients based on raw FIR filter coefficients. These will be padded,
+    /// ran through the DFT, and normalized.
+    pub fn recompute_coefficients(
+        &mut self,
+        coefficients: &FirCoefficients<FILTER_SIZE>,
+        r2c_plan: &dyn RealToComplex<f32>,
+        real_scratch_buffer: &mut [f32],
+        complex_scratch_buffer: &mut [Complex32],
+    ) {
+        // This needs to be padded with zeroes
+        real_scratch_buffer[..FILTER_SIZE].copy_from_slice(&coefficients.0);
+        real_scratch_buffer[FILTER_SIZE..].fill(0.0);
+
+        r2c_plan
+            .process_with_scratch(real_scratch_buffer, complex_scratch_buffer, &mut [])
+            .unwrap();
+
+        // The resulting buffer needs to be normalized and written to `self.padded_ir_fft`. That way
+        // we don't need to do anything but multiplying and writing the results back when
+        // processing.
+        let normalization_factor = 1.0 / FFT_SIZE as f32;
+        for (filter_bin, target_bin) in complex_scratch_buffer
+            .iter()
+            .zip(self.padded_ir_fft.iter_mut())
+        {
+            *target_bin = *filter_bin * normalization_factor;
+        }
+    }
+
+    /// Reset the internal filter state.
+    pub fn reset(&mut self) {
+        for buffer in &mut self.unapplied_padding_buffers {
+            buffer.fill(0.0);
+        }
+    }
+}
+
+impl<const N: usize> FirCoefficients<N> {
+    /// A somewhat crude but very functional and relatively fast way create linear phase FIR
+    /// **low-pass** filter that matches the frequency response of a fourth order biquad low-pass
+    /// filter. As in, this matches the frequency response magnitudes of applying those biquads to a
+    /// signal twice. This only works for low-pass filters, as the function normalizes the result to
+    /// have unity gain at the DC bin. The algorithm works as follows:
+    ///
+    /// - An impulse function (so all zeroes except for the first element) of length `FILTER_LEN / 2
+    ///   + 1` is filtered with the biquad.
+    /// - The biquad's state is reset, and the impulse response is filtered in the opposite
+    ///   direction.
+    /// - At this point the bidirectionally filtered impulse response contains the **right** half of
+    ///   a truncated linear phase FIR kernel.
+    ///
+    /// Since the FIR filter will be a symmetrical version of this impulse response, we can optimize
+    /// the post-processing work slightly by windowing and normalizing this bidirectionally filtered
+    /// impulse response instead.
+    ///
+    /// - A half Blackman window is applied to the impulse response. Since this is the right half,
+    ///   this starts at unity gain for the first sample and then tapers off towards the right.
+    /// - The impulse response is then normalized such that the final linear-phase FIR kernel has a
+    ///   sum of 1.0. Since it will be symmetrical around the IRs first sample, the would-be final
+    ///   sum can be computed as `ir.sum() * 2 - ir[0]`.
+    ///
+    /// Lastly the linear phase FIR filter simply needs to be constructed from this right half:
+    ///
+    /// - This bidirectionally filtered impulse response is then reversed, and placed at the start
+    ///   of the `FILTER_LEN` size FIR coefficient array.
+    /// - The non-reversed bidirectionally filtered impulse response is copied to the second half of
+    ///   the coefficients. (one of the copies doesn't need to include the centermost coefficient)
+    ///
+    /// The corresponding high-pass filter can be computed through spectral inversion.
+    pub fn design_fourth_order_linear_phase_low_pass_from_biquad(
+        biquad_coefs: BiquadCoefficients<f32>,
+    ) -> Self {
+        // Rust doesn't allow you to define this as a constant
+        let center_idx = N / 2;
+
+        // We'll start with an impulse (at exactly half of this odd sized buffer)...
+        let mut impulse_response = [0.0; N];
+        impulse_response[center_idx] = 1.0;
+
+        // ...and filter that in both directions
+        let mut biquad = Biquad::default();
+        biquad.coefficients = biquad_coefs;
+        for sample in impulse_response.iter_mut().skip(center_idx - 1) {
+            *sample = biquad.process(*sample);
+        }
+
+        biquad.reset();
+        for sample in impulse_response.iter_mut().skip(center_idx - 1).rev() {
+            *sample = biquad.process(*sample);
+        }
+
+        // Now the right half of `impulse_response` contains a truncated right half of the
+        // linear-phase FIR filter. We can apply the window function here, and then finally
+        // normalize it so that the the final FIR filter kernel sums to 1.
+
+        // Adopted from `nih_plug::util::window`. We only end up applying the right half of the
+        // window, starting at the top of the window.
+        let blackman_scale_1 = (2.0 * f32::consts::PI) / (N - 1) as f32;
+        let blackman_scale_2 = blackman_scale_1 * 2.0;
+        for (sample_idx, sample) in impulse_response.iter_mut().enumerate().skip(center_idx - 1) {
+            let cos_1 = (blackman_scale_1 * sample_idx as f32).cos();
+            let cos_2 = (blackman_scale_2 * sample_idx as f32).cos();
+            *sample *= 0.42 - (0.5 * cos_1) + (0.08 * cos_2);
+        }
+
+        // Since this final filter will be symmetrical around `impulse_response[CENTER_IDX]`, we
+        // can simply normalize based on that fact:
+        let would_be_impulse_response_sum = (impulse_response.iter().skip(center_idx).sum::<f32>()
+            * 2.0)
+            - impulse_response[center_idx];
+        let would_be_impulse_response_recip = would_be_impulse_response_sum.recip();
+        for sample in &mut impulse_response {
+            *sample *= would_be_impulse_response_recip;
+        }
+
+        // And finally we can simply copy the right half of the filter kernel to the left half
+        // around the `CENTER_IDX`.
+        for source_idx in center_idx + 1..N {
+            let target_idx = center_idx - (source_idx - center_idx);
+            impulse_response[target_idx] = impulse_response[source_idx];
+        }
+
+        Self(impulse_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_is_delayed_input() {
+        const NUM_SAMPLES: usize = FFT_SIZE * 3;
+        // This is deliberately not a divisor of `FFT_INPUT_SIZE` so the ring buffers wrap around
+        // in the middle of a block
+        const BLOCK_SIZE: usize = 1000;
+
+        let frequencies = [200.0, 1000.0, 5000.0];
+        let num_bands = frequencies.len() + 1;
+        let mut crossover =
+            FirCrossover::new(FirCrossoverType::LinkwitzRiley24LinearPhase, 1, num_bands);
+        crossover.update(44_100.0, num_bands, &frequencies);
+
+        let mut impulse = vec![0.0; NUM_SAMPLES];
+        impulse[0] = 1.0;
+        let mut bands = vec![vec![0.0; NUM_SAMPLES]; num_bands];
+        let mut block_start = 0;
+        while block_start < NUM_SAMPLES {
+            let block_end = (block_start + BLOCK_SIZE).min(NUM_SAMPLES);
+            let mut band_blocks: Vec<&mut [f32]> = bands
+                .iter_mut()
+                .map(|band| &mut band[block_start..block_end])
+                .collect();
+            crossover.process_channel(0, &impulse[block_start..block_end], &mut band_blocks);
+
+            block_start = block_end;
+        }
+
+        // The individual bands are not flat, but they should add back up to a delayed impulse
+        let latency = crossover.latency() as usize;
+        for idx in 0..NUM_SAMPLES {
+            let expected = if idx == latency { 1.0 } else { 0.0 };
+            let summed: f32 = bands.iter().map(|band| band[idx]).sum();
+
+            assert!(
+                (summed - expected).abs() < 1e-4,
+                "{summed} != {expected} at sample {idx}"
+            );
+        }
+        assert!(bands[0][latency] > 0.0 && bands[0][latency] < 1.0);
+    }
+}
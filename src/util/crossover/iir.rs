@@ -0,0 +1,414 @@
+//! Minimum-phase IIR crossovers.
+
+use super::{BandOutputs, BufferBandOutputs, NEUTRAL_Q};
+use crate::buffer::Buffer;
+use crate::util::filter::{Biquad, BiquadCoefficients};
+
+/// A multiband crossover built from cascaded biquad filters. This does not introduce any latency.
+/// The lower bands are run through all-pass filters to compensate for the phase shift introduced by
+/// the crossovers for the higher bands, so the bands sum back to a signal with a flat magnitude
+/// response.
+#[derive(Debug)]
+pub struct IirCrossover {
+    /// The kind of crossover to use. `.update()` must be called after changing this.
+    mode: IirCrossoverType,
+    /// The number of bands the signal is currently split into. Set in `.update()`.
+    num_bands: usize,
+
+    /// The crossovers for each channel, indexed by `[channel_idx][crossover_idx]`. Depending on
+    /// the number of bands passed to `.update()` only some of these may be used.
+    crossovers: Vec<Vec<Crossover>>,
+    /// Used to compensate the earlier bands for the phase shift introduced in the higher bands.
+    /// There is one of these for every channel.
+    all_passes: Vec<AllPassCascade>,
+}
+
+/// The type of IIR crossover to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IirCrossoverType {
+    /// Clean crossover with 24 dB/octave slopes and one period of delay in the power band. Stacks
+    /// two Butterworth-style (i.e. $q = \frac{\sqrt{2}}{2}$) filters per crossover.
+    LinkwitzRiley24,
+}
+
+/// A single crossover using multiple biquads in series to get steeper slopes. This can do both the
+/// low-pass and the high-pass parts of the crossover.
+#[derive(Debug, Clone, Default)]
+struct Crossover {
+    /// Filters for the low-pass section of the crossover. Not all filters may be used depending on
+    /// the crossover type.
+    lp_filters: [Biquad<f32>; 2],
+    /// Filters for the high-pass section of the crossover. Not all filters may be used depending on
+    /// the crossover type.
+    hp_filters: [Biquad<f32>; 2],
+}
+
+/// The crossover is super simple and feeds the low-passed result to the next band output while
+/// using the high-passed version as the input for the next band. Because the higher bands will thus
+/// have had more filters applied to them, the lower bands need to have their phase response
+/// adjusted to match the higher bands. So for the LR24 crossovers, low-passed band `n` will get a
+/// second order all-pass for the frequencies corresponding to crossovers `n + 1..num_crossovers`
+/// applied to it.
+#[derive(Debug)]
+struct AllPassCascade {
+    /// The aforementioned all-pass filters. This is indexed by `[crossover_idx][0..num_bands -
+    /// crossover_index - 2]`. Ergo, if there are three crossovers, then the low-pass section from
+    /// the first crossover needs to have `[0][0]` and `[0][1]` applied to it. The last band doesn't
+    /// need any compensation, hence the `max_bands - 2`. The outer vector contains an element for
+    /// every crossover. The last element will never contain any filters, but this avoids needing
+    /// an explicit check for that.
+    ap_filters: Vec<Vec<Biquad<f32>>>,
+}
+
+impl IirCrossover {
+    /// Create a new multiband crossover processor for `num_channels` channels that can split a
+    /// signal into up to `max_bands` bands. All filters will be configured to pass audio through as
+    /// is. `.update()` needs to be called first to set up the filters, and `.reset()` can be called
+    /// whenever the filter state must be cleared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bands` is less than two.
+    pub fn new(mode: IirCrossoverType, num_channels: usize, max_bands: usize) -> Self {
+        assert!(max_bands >= 2, "A crossover needs at least two bands");
+
+        Self {
+            mode,
+            num_bands: max_bands,
+
+            crossovers: vec![vec![Crossover::default(); max_bands - 1]; num_channels],
+            all_passes: (0..num_channels)
+                .map(|_| AllPassCascade::new(max_bands))
+                .collect(),
+        }
+    }
+
+    /// Get the latency in samples. IIR crossovers don't introduce any latency, so this always
+    /// returns zero. This exists so both crossover types can be used in the same way.
+    pub fn latency(&self) -> u32 {
+        0
+    }
+
+    /// The number of bands the signal is currently split into.
+    pub fn num_bands(&self) -> usize {
+        self.num_bands
+    }
+
+    /// Split every channel in `input` into bands using the crossovers previously configured through
+    /// `.update()`. Every band is written to the buffer with the same index in `band_outputs`, so
+    /// this needs to contain at least [`num_bands()`][Self::num_bands()] buffers with the same
+    /// number of channels and samples as `input`. Unused band buffers are not written to.
+    pub fn process(&mut self, input: &Buffer, band_outputs: &mut [Buffer]) {
+        nih_debug_assert!(band_outputs.len() >= self.num_bands);
+
+        for (channel_idx, channel_samples) in input.as_slice_immutable().iter().enumerate() {
+            self.process_channel_inner(
+                channel_idx,
+                channel_samples,
+                &mut BufferBandOutputs {
+                    buffers: band_outputs,
+                    channel_idx,
+                },
+            );
+        }
+    }
+
+    /// The same as [`process()`][Self::process()], but for a single channel. `band_outputs` needs
+    /// to contain at least [`num_bands()`][Self::num_bands()] slices that are the same length as
+    /// `input`.
+    pub fn process_channel<S: AsMut<[f32]>>(
+        &mut self,
+        channel_idx: usize,
+        input: &[f32],
+        band_outputs: &mut [S],
+    ) {
+        nih_debug_assert!(band_outputs.len() >= self.num_bands);
+
+        self.process_channel_inner(channel_idx, input, band_outputs);
+    }
+
+    /// Split a single sample for channel `channel_idx` into bands, and write the results to
+    /// `band_samples`. This needs to contain at least [`num_bands()`][Self::num_bands()] elements.
+    /// Useful when the crossover frequencies are smoothed every sample.
+    pub fn process_sample(&mut self, channel_idx: usize, sample: f32, band_samples: &mut [f32]) {
+        nih_debug_assert!(band_samples.len() >= self.num_bands);
+
+        self.split_sample(channel_idx, sample, |band_idx, band_sample| {
+            band_samples[band_idx] = band_sample
+        });
+    }
+
+    /// Update the crossover frequencies for all filters. This splits the signal into `num_bands`
+    /// bands using the first `num_bands - 1` frequencies from `frequencies`. `num_bands` needs to
+    /// be in `[2, max_bands]`.
+    pub fn update(&mut self, sample_rate: f32, num_bands: usize, frequencies: &[f32]) {
+        nih_debug_assert!(num_bands >= 2);
+        nih_debug_assert!(num_bands <= self.crossovers.first().map_or(0, Vec::len) + 1);
+        nih_debug_assert!(frequencies.len() >= num_bands - 1);
+        self.num_bands = num_bands;
+
+        // NOTE: Currently we don't actually need to make sure that the frequencies are monotonic
+
+        match self.mode {
+            IirCrossoverType::LinkwitzRiley24 => {
+                for (crossover_idx, frequency) in frequencies.iter().enumerate().take(num_bands - 1)
+                {
+                    let lp_coefs = BiquadCoefficients::lowpass(sample_rate, *frequency, NEUTRAL_Q);
+                    let hp_coefs = BiquadCoefficients::highpass(sample_rate, *frequency, NEUTRAL_Q);
+                    for channel_crossovers in &mut self.crossovers {
+                        channel_crossovers[crossover_idx].update_coefficients(lp_coefs, hp_coefs);
+                    }
+                }
+            }
+        }
+
+        for all_passes in &mut self.all_passes {
+            all_passes.update_coefficients(sample_rate, num_bands, frequencies);
+        }
+    }
+
+    /// Reset the internal filter state for all crossovers.
+    pub fn reset(&mut self) {
+        for crossover in self.crossovers.iter_mut().flatten() {
+            crossover.reset();
+        }
+
+        for all_passes in &mut self.all_passes {
+            all_passes.reset();
+        }
+    }
+
+    fn process_channel_inner<O: BandOutputs + ?Sized>(
+        &mut self,
+        channel_idx: usize,
+        input: &[f32],
+        band_outputs: &mut O,
+    ) {
+        for (sample_idx, sample) in input.iter().enumerate() {
+            self.split_sample(channel_idx, *sample, |band_idx, band_sample| {
+                band_outputs.band(band_idx)[sample_idx] = band_sample
+            });
+        }
+    }
+
+    /// Split a single sample into `self.num_bands` bands. `write_band` is called with the band
+    /// index and the sample for that band.
+    #[inline]
+    fn split_sample(
+        &mut self,
+        channel_idx: usize,
+        mut sample: f32,
+        mut write_band: impl FnMut(usize, f32),
+    ) {
+        let num_bands = self.num_bands;
+        let all_passes = &mut self.all_passes[channel_idx];
+        match self.mode {
+            IirCrossoverType::LinkwitzRiley24 => {
+                for (crossover_idx, crossover) in self.crossovers[channel_idx]
+                    .iter_mut()
+                    .take(num_bands - 1)
+                    .enumerate()
+                {
+                    let (lp_sample, hp_sample) = crossover.process_lr24(sample);
+
+                    // The low-pass result needs to have the same phase shift applied to it that
+                    // higher bands would get
+                    let lp_sample = all_passes.compensate_lr24(lp_sample, crossover_idx, num_bands);
+
+                    write_band(crossover_idx, lp_sample);
+                    sample = hp_sample;
+                }
+
+                // And the final high-passed result should be written to the last band
+                write_band(num_bands - 1, sample);
+            }
+        }
+    }
+}
+
+impl Crossover {
+    /// Process a sample through two low-pass and two high-pass filter stages. The resulting tuple
+    /// contains the low-passed and the high-passed samples. Used for the Linkwitz-Riley 24
+    /// dB/octave crossover.
+    pub fn process_lr24(&mut self, sample: f32) -> (f32, f32) {
+        let mut low_passed = sample;
+        for filter in &mut self.lp_filters[..2] {
+            low_passed = filter.process(low_passed)
+        }
+        let mut high_passed = sample;
+        for filter in &mut self.hp_filters[..2] {
+            high_passed = filter.process(high_passed)
+        }
+
+        (low_passed, high_passed)
+    }
+
+    /// Update the coefficients for all filters in the crossover.
+    pub fn update_coefficients(
+        &mut self,
+        lp_coefs: BiquadCoefficients<f32>,
+        hp_coefs: BiquadCoefficients<f32>,
+    ) {
+        for filter in &mut self.lp_filters {
+            filter.coefficients = lp_coefs;
+        }
+        for filter in &mut self.hp_filters {
+            filter.coefficients = hp_coefs;
+        }
+    }
+
+    /// Reset the internal filter state.
+    pub fn reset(&mut self) {
+        for filter in &mut self.lp_filters {
+            filter.reset();
+        }
+        for filter in &mut self.hp_filters {
+            filter.reset();
+        }
+    }
+}
+
+impl AllPassCascade {
+    /// Allocate the all-pass filters for a crossover with up to `max_bands` bands.
+    pub fn new(max_bands: usize) -> Self {
+        Self {
+            ap_filters: vec![vec![Biquad::default(); max_bands - 2]; max_bands - 1],
+        }
+    }
+
+    /// Compensate lower bands for the additional phase shift introduced in higher bands when using
+    /// LR24 filters to split those bands.
+    pub fn compensate_lr24(&mut self, lp_sample: f32, band_idx: usize, num_bands: usize) -> f32 {
+        // The all-pass filters are set up based on the crossover that produced the low-passed
+        // samples
+        let crossover_idx = band_idx;
+
+        // The idea here is that if `band_idx == 0`, and `num_bands == 3`, then there are two
+        // crossovers, and `lp_sample` only needs to be filtered by `self.ap_filters[0][0]`. If
+        // `num_bands` were 4 then it would additionally also be filtered by
+        // `self.ap_filters[0][1]`.
+        let mut compensated = lp_sample;
+        for filter in &mut self.ap_filters[crossover_idx][..num_bands - band_idx - 2] {
+            compensated = filter.process(compensated)
+        }
+
+        compensated
+    }
+
+    /// Update the coefficients for all filters in the cascade. For every active band, this adds up
+    /// to `num_bands - band_idx - 2` filters. The filter state of course cannot be shared between
+    /// bands, but the coefficients along the matrix's diagonals are identical.
+    pub fn update_coefficients(&mut self, sample_rate: f32, num_bands: usize, frequencies: &[f32]) {
+        // All output bands go through the first filter, so we don't compensate for that.
+        // `crossover_idx` starts at 1
+        for (crossover_idx, crossover_frequency) in
+            frequencies.iter().enumerate().take(num_bands - 1).skip(1)
+        {
+            let ap_coefs =
+                BiquadCoefficients::allpass(sample_rate, *crossover_frequency, NEUTRAL_Q);
+
+            // This sets the coefficients in a diagonal pattern. If `crossover_idx == 2`, then this
+            // will set the coefficients for these filters:
+            // ```
+            // [_, x, ...] // Crossover 1 filters
+            // [x, ...]    // Crossover 2 filters
+            // ...
+            // ```
+            for target_crossover_idx in 0..crossover_idx {
+                self.ap_filters[target_crossover_idx][crossover_idx - target_crossover_idx - 1]
+                    .coefficients = ap_coefs;
+            }
+        }
+    }
+
+    /// Reset the internal filter state.
+    pub fn reset(&mut self) {
+        for filter in self.ap_filters.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44_100.0;
+    const FREQUENCIES: [f32; 3] = [200.0, 1000.0, 5000.0];
+    const NUM_SAMPLES: usize = 4096;
+
+    /// Split an impulse into `FREQUENCIES.len() + 1` bands and return the bands.
+    fn split_impulse() -> Vec<Vec<f32>> {
+        let num_bands = FREQUENCIES.len() + 1;
+        let mut crossover = IirCrossover::new(IirCrossoverType::LinkwitzRiley24, 1, num_bands);
+        crossover.update(SAMPLE_RATE, num_bands, &FREQUENCIES);
+
+        let mut impulse = vec![0.0; NUM_SAMPLES];
+        impulse[0] = 1.0;
+        let mut bands = vec![vec![0.0; NUM_SAMPLES]; num_bands];
+        crossover.process_channel(0, &impulse, &mut bands);
+
+        bands
+    }
+
+    /// The magnitude of the DFT of `impulse_response` at `frequency`.
+    fn magnitude_at(impulse_response: &[f32], frequency: f32) -> f32 {
+        let omega = std::f32::consts::TAU * frequency / SAMPLE_RATE;
+        let (re, im) =
+            impulse_response
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (idx, sample)| {
+                    let phase = omega * idx as f32;
+                    (re + sample * phase.cos(), im - sample * phase.sin())
+                });
+
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn sum_is_flat() {
+        let bands = split_impulse();
+        let summed: Vec<f32> = (0..NUM_SAMPLES)
+            .map(|idx| bands.iter().map(|band| band[idx]).sum())
+            .collect();
+
+        for frequency in [20.0, 100.0, 200.0, 650.0, 1000.0, 3000.0, 5000.0, 12_000.0] {
+            let magnitude = magnitude_at(&summed, frequency);
+            assert!(
+                (magnitude - 1.0).abs() < 1e-3,
+                "{magnitude} at {frequency} Hz"
+            );
+        }
+    }
+
+    #[test]
+    fn phase_compensation() {
+        // With the phase compensation in place the bands should add up to the input filtered
+        // through an all-pass at every crossover frequency. Without it the bands would partially
+        // cancel each other out around the crossover frequencies.
+        let bands = split_impulse();
+
+        let mut all_passes: Vec<Biquad<f32>> = FREQUENCIES
+            .iter()
+            .map(|frequency| {
+                let mut filter = Biquad::default();
+                filter.coefficients =
+                    BiquadCoefficients::allpass(SAMPLE_RATE, *frequency, NEUTRAL_Q);
+                filter
+            })
+            .collect();
+        for idx in 0..NUM_SAMPLES {
+            let input = if idx == 0 { 1.0 } else { 0.0 };
+            let expected = all_passes
+                .iter_mut()
+                .fold(input, |sample, filter| filter.process(sample));
+            let summed: f32 = bands.iter().map(|band| band[idx]).sum();
+
+            assert!(
+                (summed - expected).abs() < 1e-5,
+                "{summed} != {expected} at sample {idx}"
+            );
+        }
+    }
+}
@@ -0,0 +1,730 @@
+//! Biquad and state variable filters that can process either single `f32` samples or, when the
+//! `simd` feature is enabled, `std::simd` vectors containing samples for multiple channels at once.
+//!
+//! [`Biquad`] implements all of the designs from Robert Bristow-Johnson's [Audio EQ
+//! Cookbook](http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html). [`Svf`] is a
+//! topology-preserving transform state variable filter with the same set of designs and the same
+//! frequency responses. Its state is not invalidated by coefficient changes, so it's the better
+//! choice when the filter's parameters are modulated every sample. Both filters' coefficients can
+//! also be evaluated at arbitrary frequencies using `magnitude_response()` and
+//! `phase_response()`, which can be used to draw EQ curves in an editor.
+
+use std::f32::consts;
+use std::ops::{Add, Mul, Sub};
+#[cfg(feature = "simd")]
+use std::simd::{LaneCount, Simd, SupportedLaneCount};
+
+/// Either an `f32` or some SIMD vector type of `f32`s that can be used with the filters in this
+/// module.
+pub trait SimdType:
+    Mul<Output = Self> + Sub<Output = Self> + Add<Output = Self> + Copy + Sized
+{
+    fn from_f32(value: f32) -> Self;
+}
+
+/// A simple biquad filter using the transposed direct form II structure. This works great for
+/// static or slowly changing filters. Use an [`Svf`] instead if the filter's parameters are
+/// modulated quickly.
+///
+/// Based on <https://en.wikipedia.org/wiki/Digital_biquad_filter#Transposed_direct_forms>.
+///
+/// The type parameter T should be either an `f32` or a SIMD type.
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad<T> {
+    pub coefficients: BiquadCoefficients<T>,
+    s1: T,
+    s2: T,
+}
+
+/// The coefficients `[b0, b1, b2, a1, a2]` for [`Biquad`]. These coefficients are all
+/// prenormalized, i.e. they have been divided by `a0`.
+///
+/// The type parameter T should be either an `f32` or a SIMD type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiquadCoefficients<T> {
+    b0: T,
+    b1: T,
+    b2: T,
+    a1: T,
+    a2: T,
+}
+
+/// A state variable filter based on the topology-preserving transform, as described in Andrew
+/// Simper's [Linear Trapezoidal Integrated State Variable
+/// Filter](https://cytomic.com/files/dsp/SvfLinearTrapOptimised2.pdf) paper. This filter stays
+/// stable and doesn't produce artifacts when its coefficients change every sample.
+///
+/// The type parameter T should be either an `f32` or a SIMD type.
+#[derive(Clone, Copy, Debug)]
+pub struct Svf<T> {
+    pub coefficients: SvfCoefficients<T>,
+    ic1eq: T,
+    ic2eq: T,
+}
+
+/// The coefficients for [`Svf`]. The `m` coefficients determine how the filter's low-pass,
+/// band-pass, and input signals are mixed to form the different filter types.
+///
+/// The type parameter T should be either an `f32` or a SIMD type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SvfCoefficients<T> {
+    g: T,
+    k: T,
+    a1: T,
+    a2: T,
+    a3: T,
+    m0: T,
+    m1: T,
+    m2: T,
+}
+
+impl<T: SimdType> Default for Biquad<T> {
+    /// Before setting constants the filter should just act as an identity function.
+    fn default() -> Self {
+        Self {
+            coefficients: BiquadCoefficients::identity(),
+            s1: T::from_f32(0.0),
+            s2: T::from_f32(0.0),
+        }
+    }
+}
+
+impl<T: SimdType> Biquad<T> {
+    /// Process a single sample.
+    pub fn process(&mut self, sample: T) -> T {
+        let result = self.coefficients.b0 * sample + self.s1;
+
+        self.s1 = self.coefficients.b1 * sample - self.coefficients.a1 * result + self.s2;
+        self.s2 = self.coefficients.b2 * sample - self.coefficients.a2 * result;
+
+        result
+    }
+
+    /// Reset the state to zero, useful after making making large, non-interpolatable changes to the
+    /// filter coefficients.
+    pub fn reset(&mut self) {
+        self.s1 = T::from_f32(0.0);
+        self.s2 = T::from_f32(0.0);
+    }
+}
+
+impl<T: SimdType> BiquadCoefficients<T> {
+    /// Convert scalar coefficients into the correct vector type.
+    pub fn from_f32s(scalar: BiquadCoefficients<f32>) -> Self {
+        Self {
+            b0: T::from_f32(scalar.b0),
+            b1: T::from_f32(scalar.b1),
+            b2: T::from_f32(scalar.b2),
+            a1: T::from_f32(scalar.a1),
+            a2: T::from_f32(scalar.a2),
+        }
+    }
+
+    /// Filter coefficients that would cause the sound to be passed through as is.
+    pub fn identity() -> Self {
+        Self::from_f32s(BiquadCoefficients {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        })
+    }
+
+    /// Compute the coefficients for a low-pass filter.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn lowpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+
+        Self::from_normalized(
+            (1.0 - cos_omega0) / 2.0,
+            1.0 - cos_omega0,
+            (1.0 - cos_omega0) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_omega0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Compute the coefficients for a high-pass filter.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn highpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+
+        Self::from_normalized(
+            (1.0 + cos_omega0) / 2.0,
+            -(1.0 + cos_omega0),
+            (1.0 + cos_omega0) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_omega0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Compute the coefficients for a band-pass filter with a constant 0 dB peak gain.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn bandpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+
+        Self::from_normalized(
+            alpha,
+            0.0,
+            -alpha,
+            1.0 + alpha,
+            -2.0 * cos_omega0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Compute the coefficients for a notch filter.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn notch(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+
+        Self::from_normalized(
+            1.0,
+            -2.0 * cos_omega0,
+            1.0,
+            1.0 + alpha,
+            -2.0 * cos_omega0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Compute the coefficients for an all-pass filter.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn allpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+
+        Self::from_normalized(
+            1.0 - alpha,
+            -2.0 * cos_omega0,
+            1.0 + alpha,
+            1.0 + alpha,
+            -2.0 * cos_omega0,
+            1.0 - alpha,
+        )
+    }
+
+    /// Compute the coefficients for a peaking or bell filter that boosts or cuts the signal around
+    /// `frequency` by `gain_db` decibels.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn peaking(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+        let a = shelf_amplitude(gain_db);
+
+        Self::from_normalized(
+            1.0 + alpha * a,
+            -2.0 * cos_omega0,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos_omega0,
+            1.0 - alpha / a,
+        )
+    }
+
+    /// Compute the coefficients for a low shelf filter that boosts or cuts the signal below
+    /// `frequency` by `gain_db` decibels. A Q value of `1 / sqrt(2)` results in the steepest slope
+    /// without any overshoot.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn lowshelf(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+        let a = shelf_amplitude(gain_db);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        Self::from_normalized(
+            a * ((a + 1.0) - (a - 1.0) * cos_omega0 + sqrt_a_alpha),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos_omega0),
+            a * ((a + 1.0) - (a - 1.0) * cos_omega0 - sqrt_a_alpha),
+            (a + 1.0) + (a - 1.0) * cos_omega0 + sqrt_a_alpha,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos_omega0),
+            (a + 1.0) + (a - 1.0) * cos_omega0 - sqrt_a_alpha,
+        )
+    }
+
+    /// Compute the coefficients for a high shelf filter that boosts or cuts the signal above
+    /// `frequency` by `gain_db` decibels. A Q value of `1 / sqrt(2)` results in the steepest slope
+    /// without any overshoot.
+    ///
+    /// Based on <http://shepazu.github.io/Audio-EQ-Cookbook/audio-eq-cookbook.html>.
+    pub fn highshelf(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let (cos_omega0, alpha) = cookbook_intermediates(sample_rate, frequency, q);
+        let a = shelf_amplitude(gain_db);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        Self::from_normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos_omega0 + sqrt_a_alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_omega0),
+            a * ((a + 1.0) + (a - 1.0) * cos_omega0 - sqrt_a_alpha),
+            (a + 1.0) - (a - 1.0) * cos_omega0 + sqrt_a_alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos_omega0),
+            (a + 1.0) - (a - 1.0) * cos_omega0 - sqrt_a_alpha,
+        )
+    }
+
+    /// Prenormalize the cookbook's coefficients with `a0` and convert them to the correct vector
+    /// type.
+    fn from_normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self::from_f32s(BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        })
+    }
+}
+
+impl BiquadCoefficients<f32> {
+    /// Compute the filter's gain at `frequency`. This is a linear gain value, use
+    /// [`gain_to_db()`][crate::util::gain_to_db()] to convert it to decibels.
+    pub fn magnitude_response(&self, sample_rate: f32, frequency: f32) -> f32 {
+        let (re, im) = self.response(sample_rate, frequency);
+
+        re.hypot(im)
+    }
+
+    /// Compute the filter's phase shift at `frequency` in radians, in the range `[-pi, pi]`.
+    pub fn phase_response(&self, sample_rate: f32, frequency: f32) -> f32 {
+        let (re, im) = self.response(sample_rate, frequency);
+
+        im.atan2(re)
+    }
+
+    /// Evaluate the filter's transfer function at `z = e^(j * omega)`, where omega is the
+    /// normalized angular frequency. Returns the real and imaginary parts of the result.
+    fn response(&self, sample_rate: f32, frequency: f32) -> (f32, f32) {
+        let omega = consts::TAU * (frequency / sample_rate);
+        let (sin_omega, cos_omega) = omega.sin_cos();
+        let (sin_2omega, cos_2omega) = (2.0 * omega).sin_cos();
+
+        // This is `b0 + b1 * z^-1 + b2 * z^-2` divided by `1 + a1 * z^-1 + a2 * z^-2`
+        let numerator = (
+            self.b0 + self.b1 * cos_omega + self.b2 * cos_2omega,
+            -(self.b1 * sin_omega + self.b2 * sin_2omega),
+        );
+        let denominator = (
+            1.0 + self.a1 * cos_omega + self.a2 * cos_2omega,
+            -(self.a1 * sin_omega + self.a2 * sin_2omega),
+        );
+
+        complex_div(numerator, denominator)
+    }
+}
+
+impl<T: SimdType> Default for Svf<T> {
+    /// Before setting constants the filter should just act as an identity function.
+    fn default() -> Self {
+        Self {
+            coefficients: SvfCoefficients::identity(),
+            ic1eq: T::from_f32(0.0),
+            ic2eq: T::from_f32(0.0),
+        }
+    }
+}
+
+impl<T: SimdType> Svf<T> {
+    /// Process a single sample. The coefficients can safely be changed in between calls to this
+    /// function.
+    pub fn process(&mut self, sample: T) -> T {
+        let SvfCoefficients {
+            a1,
+            a2,
+            a3,
+            m0,
+            m1,
+            m2,
+            ..
+        } = self.coefficients;
+        let two = T::from_f32(2.0);
+
+        let v3 = sample - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = two * v1 - self.ic1eq;
+        self.ic2eq = two * v2 - self.ic2eq;
+
+        m0 * sample + m1 * v1 + m2 * v2
+    }
+
+    /// Reset the state to zero. Unlike with a [`Biquad`], this is not needed after changing the
+    /// filter's coefficients.
+    pub fn reset(&mut self) {
+        self.ic1eq = T::from_f32(0.0);
+        self.ic2eq = T::from_f32(0.0);
+    }
+}
+
+impl<T: SimdType> SvfCoefficients<T> {
+    /// Convert scalar coefficients into the correct vector type.
+    pub fn from_f32s(scalar: SvfCoefficients<f32>) -> Self {
+        Self {
+            g: T::from_f32(scalar.g),
+            k: T::from_f32(scalar.k),
+            a1: T::from_f32(scalar.a1),
+            a2: T::from_f32(scalar.a2),
+            a3: T::from_f32(scalar.a3),
+            m0: T::from_f32(scalar.m0),
+            m1: T::from_f32(scalar.m1),
+            m2: T::from_f32(scalar.m2),
+        }
+    }
+
+    /// Filter coefficients that would cause the sound to be passed through as is.
+    pub fn identity() -> Self {
+        Self::from_f32s(SvfCoefficients::<f32>::new(0.0, 1.0, 1.0, 0.0, 0.0))
+    }
+
+    /// Compute the coefficients for a low-pass filter.
+    pub fn lowpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(g, k, 0.0, 0.0, 1.0))
+    }
+
+    /// Compute the coefficients for a high-pass filter.
+    pub fn highpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(g, k, 1.0, -k, -1.0))
+    }
+
+    /// Compute the coefficients for a band-pass filter with a constant 0 dB peak gain.
+    pub fn bandpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(g, k, 0.0, k, 0.0))
+    }
+
+    /// Compute the coefficients for a notch filter.
+    pub fn notch(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(g, k, 1.0, -k, 0.0))
+    }
+
+    /// Compute the coefficients for an all-pass filter.
+    pub fn allpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(g, k, 1.0, -2.0 * k, 0.0))
+    }
+
+    /// Compute the coefficients for a peaking or bell filter that boosts or cuts the signal around
+    /// `frequency` by `gain_db` decibels.
+    pub fn peaking(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let a = shelf_amplitude(gain_db);
+        let (g, _) = svf_intermediates(sample_rate, frequency, q);
+        let k = 1.0 / (q * a);
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(
+            g,
+            k,
+            1.0,
+            k * (a * a - 1.0),
+            0.0,
+        ))
+    }
+
+    /// Compute the coefficients for a low shelf filter that boosts or cuts the signal below
+    /// `frequency` by `gain_db` decibels. A Q value of `1 / sqrt(2)` results in the steepest slope
+    /// without any overshoot.
+    pub fn lowshelf(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let a = shelf_amplitude(gain_db);
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+        let g = g / a.sqrt();
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(
+            g,
+            k,
+            1.0,
+            k * (a - 1.0),
+            a * a - 1.0,
+        ))
+    }
+
+    /// Compute the coefficients for a high shelf filter that boosts or cuts the signal above
+    /// `frequency` by `gain_db` decibels. A Q value of `1 / sqrt(2)` results in the steepest slope
+    /// without any overshoot.
+    pub fn highshelf(sample_rate: f32, frequency: f32, q: f32, gain_db: f32) -> Self {
+        let a = shelf_amplitude(gain_db);
+        let (g, k) = svf_intermediates(sample_rate, frequency, q);
+        let g = g * a.sqrt();
+
+        Self::from_f32s(SvfCoefficients::<f32>::new(
+            g,
+            k,
+            a * a,
+            k * (1.0 - a) * a,
+            1.0 - a * a,
+        ))
+    }
+}
+
+impl SvfCoefficients<f32> {
+    /// Compute the filter's gain at `frequency`. This is a linear gain value, use
+    /// [`gain_to_db()`][crate::util::gain_to_db()] to convert it to decibels.
+    pub fn magnitude_response(&self, sample_rate: f32, frequency: f32) -> f32 {
+        let (re, im) = self.response(sample_rate, frequency);
+
+        re.hypot(im)
+    }
+
+    /// Compute the filter's phase shift at `frequency` in radians, in the range `[-pi, pi]`.
+    pub fn phase_response(&self, sample_rate: f32, frequency: f32) -> f32 {
+        let (re, im) = self.response(sample_rate, frequency);
+
+        im.atan2(re)
+    }
+
+    /// Compute the remaining coefficients from the prewarped cutoff frequency `g`, the damping `k`,
+    /// and the mixing coefficients.
+    fn new(g: f32, k: f32, m0: f32, m1: f32, m2: f32) -> Self {
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+
+        Self {
+            g,
+            k,
+            a1,
+            a2,
+            a3,
+            m0,
+            m1,
+            m2,
+        }
+    }
+
+    /// Evaluate the filter's transfer function at `frequency`. The filter is a bilinear transform
+    /// of an analog prototype with its cutoff frequency normalized to 1, so this evaluates that
+    /// prototype at the prewarped frequency. Returns the real and imaginary parts of the result.
+    fn response(&self, sample_rate: f32, frequency: f32) -> (f32, f32) {
+        // The identity filter has `g = 0`, and its response is independent of the frequency
+        if self.g == 0.0 {
+            return (self.m0, 0.0);
+        }
+
+        // The low-pass output is `1 / (s^2 + k * s + 1)`, and the band-pass output is `s` times
+        // that. `s` is purely imaginary here.
+        let s = (consts::PI * (frequency / sample_rate)).tan() / self.g;
+        let denominator = (1.0 - s * s, self.k * s);
+        let (re, im) = complex_div((self.m2, self.m1 * s), denominator);
+
+        (self.m0 + re, im)
+    }
+}
+
+impl SimdType for f32 {
+    #[inline(always)]
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<const LANES: usize> SimdType for Simd<f32, LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    #[inline(always)]
+    fn from_f32(value: f32) -> Self {
+        Simd::splat(value)
+    }
+}
+
+/// Compute `cos(omega0)` and `alpha` as defined in the Audio EQ Cookbook.
+fn cookbook_intermediates(sample_rate: f32, frequency: f32, q: f32) -> (f32, f32) {
+    nih_debug_assert!(sample_rate > 0.0);
+    nih_debug_assert!(frequency > 0.0);
+    nih_debug_assert!(frequency < sample_rate / 2.0);
+    nih_debug_assert!(q > 0.0);
+
+    let omega0 = consts::TAU * (frequency / sample_rate);
+    let cos_omega0 = omega0.cos();
+    let alpha = omega0.sin() / (2.0 * q);
+
+    (cos_omega0, alpha)
+}
+
+/// Compute the prewarped cutoff frequency `g` and the damping coefficient `k` for an [`Svf`].
+fn svf_intermediates(sample_rate: f32, frequency: f32, q: f32) -> (f32, f32) {
+    nih_debug_assert!(sample_rate > 0.0);
+    nih_debug_assert!(frequency > 0.0);
+    nih_debug_assert!(frequency < sample_rate / 2.0);
+    nih_debug_assert!(q > 0.0);
+
+    let g = (consts::PI * (frequency / sample_rate)).tan();
+    let k = 1.0 / q;
+
+    (g, k)
+}
+
+/// The cookbook's `A` value for peaking and shelving filters.
+fn shelf_amplitude(gain_db: f32) -> f32 {
+    10.0f32.powf(gain_db / 40.0)
+}
+
+/// Divide two complex numbers stored as `(re, im)` pairs.
+fn complex_div((a_re, a_im): (f32, f32), (b_re, b_im): (f32, f32)) -> (f32, f32) {
+    let norm = b_re * b_re + b_im * b_im;
+
+    (
+        (a_re * b_re + a_im * b_im) / norm,
+        (a_im * b_re - a_re * b_im) / norm,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::db_to_gain;
+
+    const SAMPLE_RATE: f32 = 44_100.0;
+
+    /// All designs as pairs of biquad and SVF coefficients.
+    fn designs(frequency: f32, q: f32) -> Vec<(BiquadCoefficients<f32>, SvfCoefficients<f32>)> {
+        vec![
+            (
+                BiquadCoefficients::lowpass(SAMPLE_RATE, frequency, q),
+                SvfCoefficients::lowpass(SAMPLE_RATE, frequency, q),
+            ),
+            (
+                BiquadCoefficients::highpass(SAMPLE_RATE, frequency, q),
+                SvfCoefficients::highpass(SAMPLE_RATE, frequency, q),
+            ),
+            (
+                BiquadCoefficients::bandpass(SAMPLE_RATE, frequency, q),
+                SvfCoefficients::bandpass(SAMPLE_RATE, frequency, q),
+            ),
+            (
+                BiquadCoefficients::notch(SAMPLE_RATE, frequency, q),
+                SvfCoefficients::notch(SAMPLE_RATE, frequency, q),
+            ),
+            (
+                BiquadCoefficients::allpass(SAMPLE_RATE, frequency, q),
+                SvfCoefficients::allpass(SAMPLE_RATE, frequency, q),
+            ),
+            (
+                BiquadCoefficients::peaking(SAMPLE_RATE, frequency, q, 6.0),
+                SvfCoefficients::peaking(SAMPLE_RATE, frequency, q, 6.0),
+            ),
+            (
+                BiquadCoefficients::lowshelf(SAMPLE_RATE, frequency, q, -9.0),
+                SvfCoefficients::lowshelf(SAMPLE_RATE, frequency, q, -9.0),
+            ),
+            (
+                BiquadCoefficients::highshelf(SAMPLE_RATE, frequency, q, 4.5),
+                SvfCoefficients::highshelf(SAMPLE_RATE, frequency, q, 4.5),
+            ),
+        ]
+    }
+
+    #[test]
+    fn identity() {
+        let mut biquad = Biquad::<f32>::default();
+        let mut svf = Svf::<f32>::default();
+        for sample in [1.0, -0.5, 0.25, 0.0] {
+            assert_eq!(biquad.process(sample), sample);
+            assert_eq!(svf.process(sample), sample);
+        }
+
+        assert_eq!(
+            BiquadCoefficients::<f32>::identity().magnitude_response(SAMPLE_RATE, 1000.0),
+            1.0
+        );
+        assert_eq!(
+            SvfCoefficients::<f32>::identity().magnitude_response(SAMPLE_RATE, 1000.0),
+            1.0
+        );
+    }
+
+    #[test]
+    fn magnitude_responses() {
+        let lowpass = BiquadCoefficients::lowpass(SAMPLE_RATE, 1000.0, 0.707);
+        approx::assert_relative_eq!(
+            lowpass.magnitude_response(SAMPLE_RATE, 1.0),
+            1.0,
+            epsilon = 1e-4
+        );
+        assert!(lowpass.magnitude_response(SAMPLE_RATE, 15_000.0) < db_to_gain(-40.0));
+
+        let peaking = BiquadCoefficients::peaking(SAMPLE_RATE, 1000.0, 2.0, 6.0);
+        approx::assert_relative_eq!(
+            peaking.magnitude_response(SAMPLE_RATE, 1000.0),
+            db_to_gain(6.0),
+            epsilon = 1e-4
+        );
+
+        let notch = BiquadCoefficients::notch(SAMPLE_RATE, 1000.0, 2.0);
+        assert!(notch.magnitude_response(SAMPLE_RATE, 1000.0) < 1e-3);
+
+        let allpass = BiquadCoefficients::allpass(SAMPLE_RATE, 1000.0, 2.0);
+        approx::assert_relative_eq!(
+            allpass.magnitude_response(SAMPLE_RATE, 300.0),
+            1.0,
+            epsilon = 1e-4
+        );
+        approx::assert_relative_eq!(
+            allpass.phase_response(SAMPLE_RATE, 1000.0).abs(),
+            consts::PI,
+            epsilon = 1e-3
+        );
+
+        let lowshelf = BiquadCoefficients::lowshelf(SAMPLE_RATE, 1000.0, 0.707, -9.0);
+        approx::assert_relative_eq!(
+            lowshelf.magnitude_response(SAMPLE_RATE, 10.0),
+            db_to_gain(-9.0),
+            epsilon = 1e-3
+        );
+        let highshelf = BiquadCoefficients::highshelf(SAMPLE_RATE, 1000.0, 0.707, 4.5);
+        approx::assert_relative_eq!(
+            highshelf.magnitude_response(SAMPLE_RATE, 20_000.0),
+            db_to_gain(4.5),
+            epsilon = 1e-2
+        );
+    }
+
+    /// The biquad and SVF designs should result in the same filters.
+    #[test]
+    fn svf_matches_biquad() {
+        for (biquad_coefficients, svf_coefficients) in designs(2500.0, 1.5) {
+            for frequency in [20.0, 500.0, 2000.0, 8000.0, 19_000.0] {
+                approx::assert_relative_eq!(
+                    biquad_coefficients.magnitude_response(SAMPLE_RATE, frequency),
+                    svf_coefficients.magnitude_response(SAMPLE_RATE, frequency),
+                    epsilon = 1e-3
+                );
+                approx::assert_relative_eq!(
+                    biquad_coefficients.phase_response(SAMPLE_RATE, frequency),
+                    svf_coefficients.phase_response(SAMPLE_RATE, frequency),
+                    epsilon = 1e-3
+                );
+            }
+
+            let mut biquad = Biquad {
+                coefficients: biquad_coefficients,
+                ..Default::default()
+            };
+            let mut svf = Svf {
+                coefficients: svf_coefficients,
+                ..Default::default()
+            };
+            for i in 0..64 {
+                let impulse = if i == 0 { 1.0 } else { 0.0 };
+                approx::assert_relative_eq!(
+                    biquad.process(impulse),
+                    svf.process(impulse),
+                    epsilon = 1e-4
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,1280 @@
+//! Multi-stage 2x oversampling for nonlinear processing like distortion and saturation.
+//!
+//! The oversampling factor used throughout this module is the 2-logarithm of the oversampling
+//! amount. A factor of 0 corresponds to 1x oversampling (so no oversampling at all), 1 corresponds
+//! to 2x oversampling, 2 to 4x oversampling, and so forth. The factor can be changed during
+//! processing without allocating, as long as it doesn't exceed the maximum factor the oversampler
+//! was created with. Use [`Oversampler::latency()`] together with
+//! [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()] to
+//! report the oversampling's latency to the host, and
+//! [`SmoothingStyle::OversamplingAware`][crate::prelude::SmoothingStyle::OversamplingAware] to
+//! smooth parameter values at the oversampled rate.
+
+use crate::buffer::Buffer;
+
+/// The filters used for the up- and downsampling. The IIR and linear-phase filters have more than
+/// 96 dB of stopband attenuation and a flat passband up to 42% of the base sample rate, or 20.2 kHz
+/// at 48 kHz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversamplingFilter {
+    /// Polyphase IIR allpass halfband filters. These are much cheaper and have less latency than
+    /// the linear-phase filters, but they are minimum phase and thus alter the signal's phase
+    /// response near the Nyquist frequency. The reported latency is the filters' group delay at
+    /// DC, rounded to the nearest sample.
+    Iir,
+    /// Linear-phase FIR halfband filters. These preserve the signal's phase response and their
+    /// latency is always an integer number of samples, at the cost of more latency and more
+    /// computations.
+    LinearPhase,
+    /// Short 11-tap linear-phase FIR filters using the Lanczos kernel with a = 3. These have very
+    /// little latency and are cheap to compute, but their passband starts rolling off well before
+    /// the Nyquist frequency and their stopband attenuation is much lower than that of the other
+    /// filters, so more aliasing will be audible. Like the other linear-phase filters, their
+    /// latency is always an integer number of samples.
+    Lanczos3,
+}
+
+/// The coefficients for the polyphase IIR halfband filter's allpass sections. The even
+/// coefficients belong to the first path, and the odd coefficients to the second path. Designed
+/// using the method from Laurent de Soras' HIIR library with 8 coefficients and a transition
+/// bandwidth of 0.04, resulting in 99 dB of stopband attenuation.
+const IIR_HALFBAND_COEFFICIENTS: [f32; 8] = [
+    0.04063346, 0.15050513, 0.30075705, 0.4607745, 0.6095243, 0.7385038, 0.8492238, 0.9497428,
+];
+
+/// The non-zero taps of a 95-tap Kaiser windowed (beta = 10) sinc halfband filter, excluding the
+/// 0.5 center tap. Every other tap in a halfband filter is zero, so the filter can be evaluated as
+/// a dot product between these taps and every other input sample, plus the center tap. This
+/// results in 100 dB of stopband attenuation. Since these taps are symmetrical, they don't need to
+/// be reversed for the convolution.
+///
+/// Computed using:
+///
+/// ```python
+/// n = np.arange(-47, 48)
+/// taps = 0.5 * np.sinc(n / 2) * np.kaiser(95, 10.0)
+/// taps = taps[::2] * 0.5 / np.sum(taps[::2])
+/// ```
+const FIR_HALFBAND_TAPS: [f32; 48] = [
+    -2.405267e-6,
+    1.1186279e-5,
+    -3.067704e-5,
+    6.80259e-5,
+    -1.3314094e-4,
+    2.3918429e-4,
+    -4.030396e-4,
+    6.4574886e-4,
+    -9.929377e-4,
+    1.4752877e-3,
+    -2.1291736e-3,
+    2.9976796e-3,
+    -4.132369e-3,
+    5.596458e-3,
+    -7.470574e-3,
+    9.863337e-3,
+    -1.2931323e-2,
+    1.691849e-2,
+    -2.223967e-2,
+    2.9676376e-2,
+    -4.0908966e-2,
+    6.0326114e-2,
+    -1.0407068e-1,
+    3.1762707e-1,
+    3.1762707e-1,
+    -1.0407068e-1,
+    6.0326114e-2,
+    -4.0908966e-2,
+    2.9676376e-2,
+    -2.223967e-2,
+    1.691849e-2,
+    -1.2931323e-2,
+    9.863337e-3,
+    -7.470574e-3,
+    5.596458e-3,
+    -4.132369e-3,
+    2.9976796e-3,
+    -2.1291736e-3,
+    1.4752877e-3,
+    -9.929377e-4,
+    6.4574886e-4,
+    -4.030396e-4,
+    2.3918429e-4,
+    -1.3314094e-4,
+    6.80259e-5,
+    -3.067704e-5,
+    1.1186279e-5,
+    -2.405267e-6,
+];
+
+/// The latency of the FIR halfband filter at the rate it's running at. This is the position of the
+/// center tap in the full 95-tap filter.
+const FIR_HALFBAND_LATENCY: usize = FIR_HALFBAND_TAPS.len() - 1;
+
+/// The kernel used for the Lanczos3 upsampling filter. Precomputed since compile-time floating
+/// point arithmetic is still unstable.
+///
+/// Computed using:
+///
+/// ```python
+/// LANCZOS_A = 3
+///
+/// x = np.arange(-LANCZOS_A * 2 + 1, LANCZOS_A * 2) / 2
+/// np.sinc(x) * np.sinc(x / LANCZOS_A)
+/// ```
+///
+/// Note the `+1` at the start of the range and the lack of `+1` at the (exclusive) end of the
+/// range. This is because we can ommit the first and last point because they are always zero.
+const LANCZOS3_UPSAMPLING_KERNEL: [f32; 11] = [
+    0.02431708,
+    -0.0,
+    -0.13509491,
+    0.0,
+    0.6079271,
+    1.0,
+    0.6079271,
+    0.0,
+    -0.13509491,
+    -0.0,
+    0.02431708,
+];
+
+/// `LANCZOS3_UPSAMPLING_KERNEL` divided by two, used for downsampling so that upsampling followed
+/// by downsampling results in unity gain.
+const LANCZOS3_DOWNSAMPLING_KERNEL: [f32; 11] = [
+    0.01215854,
+    -0.0,
+    -0.06754746,
+    0.0,
+    0.30396355,
+    0.5,
+    0.30396355,
+    0.0,
+    -0.06754746,
+    -0.0,
+    0.01215854,
+];
+
+/// The latency introduced by the two Lanczos3 kernels defined above, in samples.
+const LANCZOS3_KERNEL_LATENCY: usize = LANCZOS3_UPSAMPLING_KERNEL.len() / 2;
+
+/// Convert an oversampling factor to the oversampling amount. A factor of 0 corresponds to 1x
+/// oversampling, 1 corresponds to 2x oversampling, 2 corresponds to 4x oversampling, etc.
+pub const fn oversampling_factor_to_times(factor: usize) -> usize {
+    2usize.pow(factor as u32)
+}
+
+/// The inverse of [`oversampling_factor_to_times()`]. Rounds down if `times` is not a power of two,
+/// and returns 0 if `times` is 0.
+pub const fn oversampling_times_to_factor(times: usize) -> usize {
+    match times.checked_ilog2() {
+        Some(factor) => factor as usize,
+        None => 0,
+    }
+}
+
+/// A multi-stage 2x oversampler. Every stage oversamples the signal by another factor two, so 16x
+/// oversampling uses four stages. The filter used for these stages can be selected with
+/// [`OversamplingFilter`].
+///
+/// This only handles a single audio channel. Use [`MultichannelOversampler`] to oversample an
+/// entire [`Buffer`].
+#[derive(Debug)]
+pub struct Oversampler {
+    filter: OversamplingFilter,
+
+    /// The state used for each oversampling stage. Also contains stages that are not being used, so
+    /// the number of stages can change without allocating. The oversampling factor passed to
+    /// [`process()`][Self::process()] determines how many of these are actually used.
+    stages: Vec<Stage>,
+
+    /// The oversampler's latency when using the IIR filters. Precomputed for each possible number
+    /// of active stages.
+    iir_latencies: Vec<u32>,
+    /// The same as `iir_latencies`, but for the linear-phase FIR filters.
+    fir_latencies: Vec<u32>,
+    /// The same as `iir_latencies`, but for the Lanczos3 filters.
+    lanczos3_latencies: Vec<u32>,
+}
+
+/// A single oversampling stage. Contains the state for all filter types so the filter can be
+/// changed without allocating, and the scratch buffer containing this stage's upsampled signal.
+#[derive(Debug, Clone)]
+struct Stage {
+    /// The amount of oversampling that happens at this stage. Will be 2 for the first stage, 4 for
+    /// the second stage, 8 for the third stage, and so forth. Used to calculate the stage's effect
+    /// on the oversampling's latency.
+    oversampling_amount: usize,
+
+    iir: IirStage,
+    fir: FirStage,
+    lanczos3: Lanczos3Stage,
+
+    scratch_buffer: Vec<f32>,
+}
+
+/// The filter state for an IIR oversampling stage. Both halfband filters consist of two parallel
+/// chains of first order allpass filters running at the lower sample rate. Every allpass section
+/// stores its previous input and output sample.
+#[derive(Debug, Clone, Default)]
+struct IirStage {
+    upsampling_state: [(f32, f32); IIR_HALFBAND_COEFFICIENTS.len()],
+    downsampling_state: [(f32, f32); IIR_HALFBAND_COEFFICIENTS.len()],
+    /// The second path of the downsampling filter is delayed by one sample at the higher sample
+    /// rate. This is the previous block's last (odd) sample.
+    downsampling_previous_odd_sample: f32,
+}
+
+/// The filter state for a linear-phase FIR oversampling stage.
+#[derive(Debug, Clone)]
+struct FirStage {
+    /// The last `FIR_HALFBAND_TAPS.len()` input samples. These are stored twice so a contiguous
+    /// window containing the last samples can always be read starting at `upsampling_write_pos`.
+    upsampling_rb: [f32; FIR_HALFBAND_TAPS.len() * 2],
+    upsampling_write_pos: usize,
+
+    /// The signal needs to be delayed by this many samples at this stage's sample rate to make sure
+    /// the stage's _total_ (upsampling+downsampling) latency is divisible by the stage's
+    /// oversampling amount. That is needed to avoid fractional latency. This delay is applied to
+    /// the upsampled signal.
+    additional_delay_rb: Vec<f32>,
+    additional_delay_pos: usize,
+
+    /// The same as `upsampling_rb`, but for the even input samples of the downsampling filter.
+    downsampling_even_rb: [f32; FIR_HALFBAND_TAPS.len() * 2],
+    downsampling_even_write_pos: usize,
+    /// The odd input samples are only multiplied by the center tap, so they're simply delayed by
+    /// `FIR_HALFBAND_TAPS.len() / 2` samples.
+    downsampling_odd_rb: [f32; FIR_HALFBAND_TAPS.len() / 2],
+    downsampling_odd_pos: usize,
+}
+
+/// The filter state for a Lanczos3 oversampling stage. Contains the ring buffers and current
+/// position in those ring buffers used for convolving the kernels with the inputs in the
+/// upsampling and downsampling parts of the stage.
+#[derive(Debug, Clone)]
+struct Lanczos3Stage {
+    /// This ring buffer contains `LANCZOS3_UPSAMPLING_KERNEL.len()` samples, plus room to delay
+    /// the signal further to make sure the stage's _total_ (upsampling+downsampling) latency is
+    /// divisible by the stage's oversampling amount. That is needed to avoid fractional latency.
+    upsampling_rb: Vec<f32>,
+    upsampling_write_pos: usize,
+    /// The additional delay for the upsampling needed to make this stage impose an integer amount
+    /// of latency.
+    additional_upsampling_latency: usize,
+
+    /// No additional latency needs to be imposed for the downsampling, so to keep things simple
+    /// this doesn't add any additional delay.
+    downsampling_rb: [f32; LANCZOS3_DOWNSAMPLING_KERNEL.len()],
+    downsampling_write_pos: usize,
+}
+
+/// A [`Buffer`] adapter for [`Oversampler`]. This oversamples every channel in a buffer, and the
+/// oversampled channels are passed to the processing function as another [`Buffer`].
+#[derive(Default)]
+pub struct MultichannelOversampler {
+    oversamplers: Vec<Oversampler>,
+    maximum_block_size: usize,
+
+    /// The buffer that's passed to the processing function. The slices point to the last active
+    /// stage's scratch buffers while processing, and it's cleared again afterwards.
+    upsampled_buffer: Buffer<'static>,
+}
+
+impl Oversampler {
+    /// Create a new oversampler that can oversample up to the specified oversampling factor, or the
+    /// 2-logarithm of the oversampling amount. 1x oversampling (aka, do nothing) = 0, 2x
+    /// oversampling = 1, 4x oversampling = 2, etc. The actual amount of oversampling stages used is
+    /// passed to the `process()` function, and must be set to `max_factor` or lower.
+    ///
+    /// `maximum_block_size` is the largest block that can be passed to
+    /// [`process()`][Self::process()] at the base sample rate.
+    pub fn new(maximum_block_size: usize, max_factor: usize, filter: OversamplingFilter) -> Self {
+        let stages: Vec<Stage> = (0..max_factor)
+            .map(|stage_number| Stage::new(maximum_block_size, stage_number))
+            .collect();
+
+        // Since the number of active oversampling stages is passed to the process function, we also
+        // need to know the effective latencies of all possible oversampling settings in advance.
+        // The IIR filters' latency can be fractional, so that is rounded after summing the stages'
+        // latencies.
+        let iir_latencies = stages
+            .iter()
+            .scan(0.0, |total_latency, stage| {
+                *total_latency += stage.iir_latency();
+                Some(total_latency.round() as u32)
+            })
+            .collect();
+        let fir_latencies = stages
+            .iter()
+            .scan(0, |total_latency, stage| {
+                *total_latency += stage.fir_latency();
+                Some(*total_latency)
+            })
+            .collect();
+        let lanczos3_latencies = stages
+            .iter()
+            .scan(0, |total_latency, stage| {
+                *total_latency += stage.lanczos3_latency();
+                Some(*total_latency)
+            })
+            .collect();
+
+        Self {
+            filter,
+
+            stages,
+
+            iir_latencies,
+            fir_latencies,
+            lanczos3_latencies,
+        }
+    }
+
+    /// The filter type currently used for oversampling.
+    pub fn filter(&self) -> OversamplingFilter {
+        self.filter
+    }
+
+    /// Change the filter type used for oversampling. This resets the filters if the filter type
+    /// changed. Remember to report the new latency to the host.
+    pub fn set_filter(&mut self, filter: OversamplingFilter) {
+        if filter != self.filter {
+            self.filter = filter;
+            self.reset();
+        }
+    }
+
+    /// Reset the oversampling filters to their initial states.
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    /// Get the latency in samples for the given oversampling factor with the current filter type.
+    /// This value can be passed directly to
+    /// [`InitContext::set_latency_samples()`][crate::prelude::InitContext::set_latency_samples()].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor > max_factor`.
+    pub fn latency(&self, factor: usize) -> u32 {
+        if factor == 0 {
+            return 0;
+        }
+
+        match self.filter {
+            OversamplingFilter::Iir => self.iir_latencies[factor - 1],
+            OversamplingFilter::LinearPhase => self.fir_latencies[factor - 1],
+            OversamplingFilter::Lanczos3 => self.lanczos3_latencies[factor - 1],
+        }
+    }
+
+    /// Upsample `block` using the specified oversampling factor, process the upsampled version
+    /// using `f`, and then downsample it again and write the results back to `block` with a
+    /// [`latency()`][Self::latency()] sample delay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor > max_factor`, or if `block`'s length is longer than the maximum block
+    /// size.
+    pub fn process(&mut self, block: &mut [f32], factor: usize, f: impl FnOnce(&mut [f32])) {
+        assert!(factor <= self.stages.len());
+
+        // This is the 1x oversampling case, this should also modify the block to be consistent
+        if factor == 0 {
+            f(block);
+            return;
+        }
+
+        assert!(
+            block.len() <= self.stages[0].scratch_buffer.len() / 2,
+            "The block's size exceeds the maximum block size"
+        );
+
+        let upsampled = self.upsample_from(block, factor);
+        f(upsampled);
+        self.downsample_to(block, factor)
+    }
+
+    /// An upsample-only version of `process` that returns the upsampled version of the signal that
+    /// would normally be passed to `process`'s callback. Useful for upsampling control signals.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor > max_factor`, or if `block`'s length is longer than the maximum block
+    /// size.
+    pub fn upsample_only<'a>(&'a mut self, block: &'a mut [f32], factor: usize) -> &'a mut [f32] {
+        assert!(factor <= self.stages.len());
+
+        // This is the 1x oversampling case, this should also modify the block to be consistent
+        if factor == 0 {
+            return block;
+        }
+
+        assert!(
+            block.len() <= self.stages[0].scratch_buffer.len() / 2,
+            "The block's size exceeds the maximum block size"
+        );
+
+        self.upsample_from(block, factor)
+    }
+
+    /// Upsample `block` through `factor` oversampling stages. Returns a reference to the
+    /// oversampled output stored in the last stage's scratch buffer **with the correct length**.
+    /// This is a multiple of `block`'s length, which may be shorter than the entire scratch
+    /// buffer's length if `block` is shorter than the configured maximum block length.
+    fn upsample_from(&mut self, block: &[f32], factor: usize) -> &mut [f32] {
+        assert_ne!(factor, 0);
+        assert!(factor <= self.stages.len());
+
+        // The first stage is upsampled from `block`, and everything after that is upsampled from
+        // the stage preceeding it
+        let filter = self.filter;
+        self.stages[0].upsample_from(block, filter);
+
+        let mut previous_upsampled_block_len = block.len() * 2;
+        for to_stage_idx in 1..factor {
+            // This requires splitting the vector so we can borrow the from-stage immutably and the
+            // to-stage mutably at the same time
+            let ([.., from], [to, ..]) = self.stages.split_at_mut(to_stage_idx) else {
+                unreachable!()
+            };
+
+            to.upsample_from(&from.scratch_buffer[..previous_upsampled_block_len], filter);
+            previous_upsampled_block_len *= 2;
+        }
+
+        &mut self.stages[factor - 1].scratch_buffer[..previous_upsampled_block_len]
+    }
+
+    /// Downsample starting from the `factor`th oversampling stage, writing the results from
+    /// downsampling the first stage to `block`. `block`'s actual length is taken into account to
+    /// compute the length of the oversampled blocks.
+    fn downsample_to(&mut self, block: &mut [f32], factor: usize) {
+        assert_ne!(factor, 0);
+        assert!(factor <= self.stages.len());
+
+        // This is the reverse of `upsample_from`. Starting from the last stage, the oversampling
+        // stages are downsampled to the previous stage and then the first stage is downsampled to
+        // `block`.
+        let filter = self.filter;
+        let mut next_downsampled_block_len = block.len() * 2usize.pow(factor as u32 - 1);
+        for to_stage_idx in (1..factor).rev() {
+            let ([.., to], [from, ..]) = self.stages.split_at_mut(to_stage_idx) else {
+                unreachable!()
+            };
+
+            from.downsample_to(&mut to.scratch_buffer[..next_downsampled_block_len], filter);
+            next_downsampled_block_len /= 2;
+        }
+
+        // And then the first stage downsamples to `block`
+        assert_eq!(next_downsampled_block_len, block.len());
+        self.stages[0].downsample_to(block, filter);
+    }
+}
+
+impl MultichannelOversampler {
+    /// Create a new oversampler for `num_channels` channels. See [`Oversampler::new()`] for more
+    /// information on the other arguments. Unlike [`Oversampler::process()`], buffers longer than
+    /// `maximum_block_size` are processed in multiple blocks. `maximum_block_size` must be at
+    /// least 1, since [`process()`][Self::process()] cannot process any audio otherwise.
+    pub fn new(
+        num_channels: usize,
+        maximum_block_size: usize,
+        max_factor: usize,
+        filter: OversamplingFilter,
+    ) -> Self {
+        let mut upsampled_buffer = Buffer::default();
+        unsafe {
+            upsampled_buffer.set_slices(0, |slices| slices.reserve_exact(num_channels));
+        }
+
+        Self {
+            oversamplers: (0..num_channels)
+                .map(|_| Oversampler::new(maximum_block_size, max_factor, filter))
+                .collect(),
+            maximum_block_size,
+
+            upsampled_buffer,
+        }
+    }
+
+    /// The filter type currently used for oversampling.
+    pub fn filter(&self) -> OversamplingFilter {
+        self.oversamplers
+            .first()
+            .map(Oversampler::filter)
+            .unwrap_or(OversamplingFilter::LinearPhase)
+    }
+
+    /// Change the filter type used for oversampling. See [`Oversampler::set_filter()`].
+    pub fn set_filter(&mut self, filter: OversamplingFilter) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.set_filter(filter);
+        }
+    }
+
+    /// Reset the oversampling filters for all channels to their initial states.
+    pub fn reset(&mut self) {
+        for oversampler in &mut self.oversamplers {
+            oversampler.reset();
+        }
+    }
+
+    /// Get the latency in samples for the given oversampling factor. See
+    /// [`Oversampler::latency()`].
+    pub fn latency(&self, factor: usize) -> u32 {
+        self.oversamplers
+            .first()
+            .map(|oversampler| oversampler.latency(factor))
+            .unwrap_or(0)
+    }
+
+    /// Upsample every channel in `buffer` using the specified oversampling factor, process the
+    /// upsampled channels using `f`, and then downsample them again and write the results back to
+    /// `buffer` with a [`latency()`][Self::latency()] sample delay. If `buffer` contains more than
+    /// `maximum_block_size` samples, then `f` is called once for every block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor > max_factor`, or if `buffer` has more channels than this oversampler was
+    /// created for.
+    pub fn process(&mut self, buffer: &mut Buffer, factor: usize, mut f: impl FnMut(&mut Buffer)) {
+        let num_channels = buffer.channels();
+        let num_samples = buffer.samples();
+        assert!(
+            num_channels <= self.oversamplers.len(),
+            "The buffer contains more channels than the oversampler was created for"
+        );
+        if self.maximum_block_size == 0 {
+            nih_debug_assert!(
+                num_samples == 0,
+                "The oversampler was created with a maximum block size of 0, the buffer will not be \
+                 processed"
+            );
+            return;
+        }
+
+        let channels = buffer.as_slice();
+        let mut block_start = 0;
+        while block_start < num_samples {
+            let block_end = (block_start + self.maximum_block_size).min(num_samples);
+            let upsampled_block_len =
+                (block_end - block_start) * oversampling_factor_to_times(factor);
+
+            // SAFETY: The slices point to the oversamplers' scratch buffers, or to `buffer` itself
+            //         when `factor` is 0. These slices are removed from `upsampled_buffer` again
+            //         before the oversamplers and `buffer` are used again.
+            unsafe {
+                self.upsampled_buffer
+                    .set_slices(upsampled_block_len, |upsampled_slices| {
+                        upsampled_slices.clear();
+                        for (channel, oversampler) in
+                            channels.iter_mut().zip(self.oversamplers.iter_mut())
+                        {
+                            let upsampled = oversampler
+                                .upsample_only(&mut channel[block_start..block_end], factor);
+                            upsampled_slices.push(&mut *(upsampled as *mut [f32]));
+                        }
+                    });
+            }
+
+            f(&mut self.upsampled_buffer);
+
+            unsafe {
+                self.upsampled_buffer
+                    .set_slices(0, |upsampled_slices| upsampled_slices.clear());
+            }
+
+            if factor > 0 {
+                for (channel, oversampler) in channels.iter_mut().zip(self.oversamplers.iter_mut())
+                {
+                    oversampler.downsample_to(&mut channel[block_start..block_end], factor);
+                }
+            }
+
+            block_start = block_end;
+        }
+    }
+}
+
+impl Stage {
+    /// Create a `stage_number`th oversampling stage, where `stage_number` is this stage's
+    /// zero-based index in a list of stages. Stage 0 handles the 2x oversampling, stage 1 handles
+    /// the 4x oversampling, stage 2 handles the 8x oversampling, etc.. This is used to make sure
+    /// the stage's effect on the total latency is always an integer amount for the linear-phase
+    /// filters.
+    ///
+    /// The maximum block size is used to allocate enough scratch space for oversampling that many
+    /// samples *at the base sample rate*. The scratch buffer's size automatically takes the stage
+    /// number into account.
+    fn new(maximum_block_size: usize, stage_number: usize) -> Self {
+        let oversampling_amount = 2usize.pow(stage_number as u32 + 1);
+
+        Self {
+            oversampling_amount,
+
+            iir: IirStage::default(),
+            fir: FirStage::new(oversampling_amount),
+            lanczos3: Lanczos3Stage::new(oversampling_amount),
+
+            scratch_buffer: vec![0.0; maximum_block_size * oversampling_amount],
+        }
+    }
+
+    fn reset(&mut self) {
+        self.iir.reset();
+        self.fir.reset();
+        self.lanczos3.reset();
+    }
+
+    /// The stage's effect on the IIR oversampling's latency as a whole, in samples at the base
+    /// sample rate. This is the group delay at DC of the upsampling and downsampling filters, and
+    /// it is usually not an integer.
+    fn iir_latency(&self) -> f32 {
+        // The group delay of a first order allpass filter at DC is `(1 - a) / (1 + a)` samples,
+        // which is doubled because the allpass filters run at half this stage's sample rate. The
+        // second path is delayed by an additional sample. Both paths are in phase at DC, so the
+        // halfband filter's group delay at DC is the average of the two paths' group delays.
+        let allpass_delay = |a: f32| 2.0 * (1.0 - a) / (1.0 + a);
+        let first_path_delay: f32 = IIR_HALFBAND_COEFFICIENTS
+            .iter()
+            .step_by(2)
+            .copied()
+            .map(allpass_delay)
+            .sum();
+        let second_path_delay: f32 = 1.0
+            + IIR_HALFBAND_COEFFICIENTS
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .copied()
+                .map(allpass_delay)
+                .sum::<f32>();
+        let filter_delay = (first_path_delay + second_path_delay) / 2.0;
+
+        (filter_delay * 2.0) / self.oversampling_amount as f32
+    }
+
+    /// The stage's effect on the linear-phase oversampling's latency as a whole. This is already
+    /// divided by the stage's oversampling amount.
+    fn fir_latency(&self) -> u32 {
+        let total_stage_latency = FIR_HALFBAND_LATENCY * 2 + self.fir.additional_delay_rb.len();
+        assert!(total_stage_latency % self.oversampling_amount == 0);
+
+        (total_stage_latency / self.oversampling_amount) as u32
+    }
+
+    /// The stage's effect on the Lanczos3 oversampling's latency as a whole. This is already
+    /// divided by the stage's oversampling amount.
+    fn lanczos3_latency(&self) -> u32 {
+        let total_stage_latency =
+            LANCZOS3_KERNEL_LATENCY * 2 + self.lanczos3.additional_upsampling_latency;
+        assert!(total_stage_latency % self.oversampling_amount == 0);
+
+        (total_stage_latency / self.oversampling_amount) as u32
+    }
+
+    /// Upsample `block` 2x and write the results to this stage's scratch buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block`'s length times two exceeds the scratch buffer's size.
+    fn upsample_from(&mut self, block: &[f32], filter: OversamplingFilter) {
+        let output = &mut self.scratch_buffer[..block.len() * 2];
+        match filter {
+            OversamplingFilter::Iir => self.iir.upsample(block, output),
+            OversamplingFilter::LinearPhase => self.fir.upsample(block, output),
+            OversamplingFilter::Lanczos3 => self.lanczos3.upsample(block, output),
+        }
+    }
+
+    /// Downsample this stage's scratch buffer 2x and write the results to `block`. `block`'s
+    /// length determines how much of the scratch buffer is used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block`'s length times two exceeds the scratch buffer's size.
+    fn downsample_to(&mut self, block: &mut [f32], filter: OversamplingFilter) {
+        let input = &self.scratch_buffer[..block.len() * 2];
+        match filter {
+            OversamplingFilter::Iir => self.iir.downsample(input, block),
+            OversamplingFilter::LinearPhase => self.fir.downsample(input, block),
+            OversamplingFilter::Lanczos3 => self.lanczos3.downsample(input, block),
+        }
+    }
+}
+
+impl IirStage {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Upsample `input` 2x and write the results to `output`, which is twice as long as `input`.
+    /// Zero-stuffing the input and filtering it with the halfband filter is equivalent to running
+    /// every input sample through both allpass paths, which then produce the even and odd output
+    /// samples.
+    fn upsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len() * 2, output.len());
+
+        for (&sample, output) in input.iter().zip(output.chunks_exact_mut(2)) {
+            let mut even = sample;
+            let mut odd = sample;
+            for (coefficient_idx, (&coefficient, state)) in IIR_HALFBAND_COEFFICIENTS
+                .iter()
+                .zip(self.upsampling_state.iter_mut())
+                .enumerate()
+            {
+                if coefficient_idx % 2 == 0 {
+                    even = allpass(coefficient, state, even);
+                } else {
+                    odd = allpass(coefficient, state, odd);
+                }
+            }
+
+            output[0] = even;
+            output[1] = odd;
+        }
+    }
+
+    /// Downsample `input` 2x and write the results to `output`, which is half as long as `input`.
+    /// The first allpass path filters the even input samples, and the second path filters the odd
+    /// samples delayed by one sample.
+    fn downsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len(), output.len() * 2);
+
+        for (input, output) in input.chunks_exact(2).zip(output.iter_mut()) {
+            let mut even = input[0];
+            let mut odd = self.downsampling_previous_odd_sample;
+            self.downsampling_previous_odd_sample = input[1];
+            for (coefficient_idx, (&coefficient, state)) in IIR_HALFBAND_COEFFICIENTS
+                .iter()
+                .zip(self.downsampling_state.iter_mut())
+                .enumerate()
+            {
+                if coefficient_idx % 2 == 0 {
+                    even = allpass(coefficient, state, even);
+                } else {
+                    odd = allpass(coefficient, state, odd);
+                }
+            }
+
+            *output = (even + odd) * 0.5;
+        }
+    }
+}
+
+impl FirStage {
+    fn new(oversampling_amount: usize) -> Self {
+        // Say the oversampling amount is 4, then an uncompensated stage latency of 8 results in 0
+        // additional samples of delay, 9 in 3, 10 in 2, 11 in 1, 12 in 0, etc.
+        let uncompensated_stage_latency = FIR_HALFBAND_LATENCY * 2;
+        let additional_delay_required = (-(uncompensated_stage_latency as isize))
+            .rem_euclid(oversampling_amount as isize)
+            as usize;
+
+        Self {
+            upsampling_rb: [0.0; FIR_HALFBAND_TAPS.len() * 2],
+            upsampling_write_pos: 0,
+
+            additional_delay_rb: vec![0.0; additional_delay_required],
+            additional_delay_pos: 0,
+
+            downsampling_even_rb: [0.0; FIR_HALFBAND_TAPS.len() * 2],
+            downsampling_even_write_pos: 0,
+            downsampling_odd_rb: [0.0; FIR_HALFBAND_TAPS.len() / 2],
+            downsampling_odd_pos: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        // Resetting the positions is not needed, but it also doesn't hurt
+        self.upsampling_rb.fill(0.0);
+        self.upsampling_write_pos = 0;
+
+        self.additional_delay_rb.fill(0.0);
+        self.additional_delay_pos = 0;
+
+        self.downsampling_even_rb.fill(0.0);
+        self.downsampling_even_write_pos = 0;
+        self.downsampling_odd_rb.fill(0.0);
+        self.downsampling_odd_pos = 0;
+    }
+
+    /// Upsample `input` 2x and write the results to `output`, which is twice as long as `input`.
+    /// Because every other sample in the zero-stuffed input is zero, the even output samples are
+    /// the dot product between the last input samples and the non-zero taps, and because every
+    /// other tap is zero the odd output samples are simply delayed input samples.
+    fn upsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len() * 2, output.len());
+
+        const NUM_TAPS: usize = FIR_HALFBAND_TAPS.len();
+        for (&sample, output) in input.iter().zip(output.chunks_exact_mut(2)) {
+            let window = push_rb(
+                &mut self.upsampling_rb,
+                &mut self.upsampling_write_pos,
+                sample,
+            );
+
+            // The zero-stuffing halves the signal's energy, so the outputs are multiplied by two
+            // to compensate for that. The center tap is 0.5, so this becomes a unity gain delay.
+            output[0] = dot(&FIR_HALFBAND_TAPS, window) * 2.0;
+            output[1] = window[NUM_TAPS / 2];
+        }
+
+        for sample in output {
+            *sample = delay(
+                &mut self.additional_delay_rb,
+                &mut self.additional_delay_pos,
+                *sample,
+            );
+        }
+    }
+
+    /// Downsample `input` 2x and write the results to `output`, which is half as long as `input`.
+    /// Only the even samples need to be computed since the odd samples are discarded during
+    /// decimation.
+    fn downsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len(), output.len() * 2);
+
+        for (input, output) in input.chunks_exact(2).zip(output.iter_mut()) {
+            let window = push_rb(
+                &mut self.downsampling_even_rb,
+                &mut self.downsampling_even_write_pos,
+                input[0],
+            );
+            let even_sum = dot(&FIR_HALFBAND_TAPS, window);
+            let odd_sample = delay(
+                &mut self.downsampling_odd_rb,
+                &mut self.downsampling_odd_pos,
+                input[1],
+            );
+
+            *output = even_sum + odd_sample * 0.5;
+        }
+    }
+}
+
+impl Lanczos3Stage {
+    fn new(oversampling_amount: usize) -> Self {
+        // In theory we would only need to delay one of these, but we'll distribute the delay
+        // cleanly
+        assert!(LANCZOS3_UPSAMPLING_KERNEL.len() == LANCZOS3_DOWNSAMPLING_KERNEL.len());
+        assert!(LANCZOS3_UPSAMPLING_KERNEL.len() % 2 == 1);
+
+        // This works the same way as in `FirStage::new()`. The extra delay is only applied to the
+        // upsampling part to keep the downsampling simpler.
+        let uncompensated_stage_latency = LANCZOS3_KERNEL_LATENCY * 2;
+        let additional_delay_required = (-(uncompensated_stage_latency as isize))
+            .rem_euclid(oversampling_amount as isize)
+            as usize;
+
+        Self {
+            upsampling_rb: vec![0.0; LANCZOS3_UPSAMPLING_KERNEL.len() + additional_delay_required],
+            upsampling_write_pos: 0,
+            additional_upsampling_latency: additional_delay_required,
+
+            downsampling_rb: [0.0; LANCZOS3_DOWNSAMPLING_KERNEL.len()],
+            downsampling_write_pos: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        // Resetting the positions is not needed, but it also doesn't hurt
+        self.upsampling_rb.fill(0.0);
+        self.upsampling_write_pos = 0;
+
+        self.downsampling_rb.fill(0.0);
+        self.downsampling_write_pos = 0;
+    }
+
+    /// Upsample `input` 2x and write the results to `output`, which is twice as long as `input`.
+    fn upsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len() * 2, output.len());
+
+        // We'll first zero-stuff the input, and then run that through the lanczos halfband filter
+        for (&sample, output) in input.iter().zip(output.chunks_exact_mut(2)) {
+            output[0] = sample;
+            output[1] = 0.0;
+        }
+
+        // The zero-stuffed input is now run through the lanczos filter, which is a windowed sinc
+        // filter where every even tap has a value of zero. That means that if the filter is
+        // centered on a non-zero sample, the output must be equal to that sample and we can thus
+        // skip the convolution step entirely. Another important consideration is that we are
+        // imposing an additional `self.additional_upsampling_latency` samples of delay on the input
+        // to make sure the effective latency of the oversampling is always an integer amount.
+        let mut direct_read_pos =
+            (self.upsampling_write_pos + LANCZOS3_KERNEL_LATENCY) % self.upsampling_rb.len();
+        for (output_sample_idx, output_sample) in output.iter_mut().enumerate() {
+            // For a more intuitive description, imagine that `self.additional_upsampling_latency`
+            // is 2, and `self.upsampling_write_pos` is currently 0. For an 11-tap filter (like the
+            // lanczos3 kernel with the zero points removed from both ends), the situation after
+            // this statement would look like this:
+            //
+            // [n, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+            //  ^-- self.upsampling_write_pos
+            self.upsampling_rb[self.upsampling_write_pos] = *output_sample;
+
+            // The read/write head position needs to be incremented before filtering so that the
+            // just-added sample becomes the last sample in the ring buffer (if the additional
+            // latency/delay is 0)
+            self.upsampling_write_pos += 1;
+            if self.upsampling_write_pos == self.upsampling_rb.len() {
+                self.upsampling_write_pos = 0;
+            }
+
+            direct_read_pos += 1;
+            if direct_read_pos == self.upsampling_rb.len() {
+                direct_read_pos = 0;
+            }
+
+            // We can now read starting from the new `self.upsampling_write_pos`. This will cause
+            // the output to be delayed by `self.additional_upsampling_latency` samples. The range
+            // used for convolution is visualized below. It in this example it takes 2 additional
+            // iterations of this loop before sample `n` is considered again. Even output samples
+            // can directly be read from the ring buffer without convolution at the visualized
+            // offset.
+            //
+            // [n, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+            //     ^--------------^---------------^
+            //                    └- direct_read_position
+            //
+            // NOTE: 'Even samples' is considered from the perspective of a zero latency filter. In
+            //       this case the evenness of the filter's latency also needs to be considered. If
+            //       it's odd then the direct reading should also happen for odd indexed samples.
+            *output_sample = if output_sample_idx % 2 == (LANCZOS3_KERNEL_LATENCY % 2) {
+                nih_debug_assert_eq!(
+                    self.upsampling_rb[(direct_read_pos + self.upsampling_rb.len() - 1)
+                        % self.upsampling_rb.len()],
+                    0.0
+                );
+                nih_debug_assert_eq!(
+                    self.upsampling_rb[(direct_read_pos + 1) % self.upsampling_rb.len()],
+                    0.0
+                );
+
+                self.upsampling_rb[direct_read_pos]
+            } else {
+                convolve_rb(
+                    &self.upsampling_rb,
+                    &LANCZOS3_UPSAMPLING_KERNEL,
+                    self.upsampling_write_pos,
+                )
+            };
+        }
+    }
+
+    /// Downsample `input` 2x and write the results to `output`, which is half as long as `input`.
+    fn downsample(&mut self, input: &[f32], output: &mut [f32]) {
+        nih_debug_assert_eq!(input.len(), output.len() * 2);
+
+        // The additional delay to make the latency integer has already been taken into account in
+        // the upsampling part, so the downsampling is more straightforward
+        for (input_sample_idx, &input_sample) in input.iter().enumerate() {
+            self.downsampling_rb[self.downsampling_write_pos] = input_sample;
+
+            // The read/write head position needs to be incremented before filtering so that the
+            // just-added sample becomes the last sample in the ring buffer
+            self.downsampling_write_pos += 1;
+            if self.downsampling_write_pos == LANCZOS3_DOWNSAMPLING_KERNEL.len() {
+                self.downsampling_write_pos = 0;
+            }
+
+            // Because downsampling by a factor of two is filtering followed by decimation (where
+            // you take every even sample), we only need to compute the filtered output for the even
+            // samples. This is similar to how we only need to filter half the samples in the
+            // upsampling step.
+            if input_sample_idx % 2 == 0 {
+                output[input_sample_idx / 2] = convolve_rb(
+                    &self.downsampling_rb,
+                    &LANCZOS3_DOWNSAMPLING_KERNEL,
+                    self.downsampling_write_pos,
+                );
+            }
+        }
+    }
+}
+
+/// Process a single sample through a first order allpass filter with coefficient `a`. `state`
+/// contains the previous input and output samples.
+#[inline]
+fn allpass(a: f32, state: &mut (f32, f32), input: f32) -> f32 {
+    let (previous_input, previous_output) = *state;
+    let output = a * (input - previous_output) + previous_input;
+    *state = (input, output);
+
+    output
+}
+
+/// Write `sample` to a ring buffer where every sample is stored twice, and return a contiguous
+/// window containing the last `rb.len() / 2` samples, from oldest to newest.
+#[inline]
+fn push_rb<'a>(rb: &'a mut [f32], write_pos: &mut usize, sample: f32) -> &'a [f32] {
+    let len = rb.len() / 2;
+    rb[*write_pos] = sample;
+    rb[*write_pos + len] = sample;
+
+    *write_pos += 1;
+    if *write_pos == len {
+        *write_pos = 0;
+    }
+
+    &rb[*write_pos..*write_pos + len]
+}
+
+/// Delay `sample` by `rb.len()` samples. Returns the sample unchanged if the ring buffer is empty.
+#[inline]
+fn delay(rb: &mut [f32], pos: &mut usize, sample: f32) -> f32 {
+    if rb.is_empty() {
+        return sample;
+    }
+
+    let delayed = std::mem::replace(&mut rb[*pos], sample);
+    *pos += 1;
+    if *pos == rb.len() {
+        *pos = 0;
+    }
+
+    delayed
+}
+
+#[inline]
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Convolve `input_ring_buffer` with `kernel`, with `input_ring_buffer` rotated so that it starts
+/// at `ring_buffer_pos` and then wraps back around to the start.
+///
+/// # Panics
+///
+/// Assumes `input_ring_buffer` is at least as long as `kernel`. May panic if it isn't.
+fn convolve_rb(input_ring_buffer: &[f32], kernel: &[f32], ring_buffer_pos: usize) -> f32 {
+    let mut total = 0.0;
+
+    nih_debug_assert!(input_ring_buffer.len() >= kernel.len());
+
+    // This is straightforward convolution. Could be implemented much more efficiently, but for the
+    // 11-tap Lanczos3 kernel this works fine
+    let num_samples_until_wraparound =
+        (input_ring_buffer.len() - ring_buffer_pos).min(kernel.len());
+    for (read_pos_offset, kernel_sample) in kernel
+        .iter()
+        .rev()
+        .take(num_samples_until_wraparound)
+        .enumerate()
+    {
+        total += kernel_sample * input_ring_buffer[ring_buffer_pos + read_pos_offset];
+    }
+
+    for (read_pos, kernel_sample) in kernel
+        .iter()
+        .rev()
+        // Needs to happen before the `enumerate`
+        .skip(num_samples_until_wraparound)
+        .enumerate()
+    {
+        total += kernel_sample * input_ring_buffer[read_pos];
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argmax(iter: impl IntoIterator<Item = f32>) -> usize {
+        iter.into_iter()
+            .enumerate()
+            .max_by(|(_, value_a), (_, value_b)| value_a.total_cmp(value_b))
+            .unwrap()
+            .0
+    }
+
+    /// Makes sure that the reported latency for one of the linear-phase filters is correct and is
+    /// (more or less) an integer value
+    fn test_linear_phase_latency(oversampling_factor: usize, filter: OversamplingFilter) {
+        let mut delta_impulse = [0.0f32; 128];
+        delta_impulse[0] = 1.0;
+
+        let mut oversampler = Oversampler::new(delta_impulse.len(), oversampling_factor, filter);
+
+        let reported_latency = oversampler.latency(oversampling_factor) as usize;
+        assert!(
+            delta_impulse.len() > reported_latency,
+            "The delta impulse array is too small to test the latency at oversampling factor \
+             {oversampling_factor}, this is an error with the test case"
+        );
+
+        oversampler.process(&mut delta_impulse, oversampling_factor, |_| ());
+
+        let new_impulse_idx = argmax(delta_impulse);
+        assert_eq!(new_impulse_idx, reported_latency);
+
+        // The latency should also not be fractional
+        assert!(delta_impulse[new_impulse_idx] > delta_impulse[new_impulse_idx - 1]);
+        assert!(delta_impulse[new_impulse_idx] > delta_impulse[new_impulse_idx + 1]);
+    }
+
+    /// Checks whether the output matches the input when compensating for the latency. Also applies
+    /// a gain offset to make sure the process callback actually works.
+    fn test_sine_output(oversampling_factor: usize, filter: OversamplingFilter, epsilon: f32) {
+        // The gain applied to the oversampled version
+        const GAIN: f32 = 2.0;
+        // As a fraction of the sampling frequency
+        const FREQUENCY: f32 = 0.01;
+
+        let mut input = [0.0f32; 256];
+        for (i, sample) in input.iter_mut().enumerate() {
+            *sample = (i as f32 * (FREQUENCY * 2.0 * std::f32::consts::PI)).sin();
+        }
+
+        let mut output = input;
+        let mut oversampler = Oversampler::new(output.len(), oversampling_factor, filter);
+        oversampler.process(&mut output, oversampling_factor, |upsampled| {
+            for sample in upsampled {
+                *sample *= GAIN;
+            }
+        });
+
+        // The first couple samples will contain the filters' transient response
+        let reported_latency = oversampler.latency(oversampling_factor) as usize;
+        for (input_sample_idx, input_sample) in input
+            .into_iter()
+            .enumerate()
+            .take(input.len() - reported_latency)
+            .skip(32)
+        {
+            let output_sample_idx = input_sample_idx + reported_latency;
+            let output_sample = output[output_sample_idx];
+
+            approx::assert_relative_eq!(input_sample * GAIN, output_sample, epsilon = epsilon);
+        }
+    }
+
+    #[test]
+    fn times_to_factor() {
+        assert_eq!(oversampling_times_to_factor(0), 0);
+        assert_eq!(oversampling_times_to_factor(1), 0);
+        assert_eq!(oversampling_times_to_factor(6), 2);
+        assert_eq!(oversampling_times_to_factor(16), 4);
+    }
+
+    #[test]
+    fn fir_latency_2x() {
+        test_linear_phase_latency(1, OversamplingFilter::LinearPhase);
+    }
+
+    #[test]
+    fn fir_latency_4x() {
+        test_linear_phase_latency(2, OversamplingFilter::LinearPhase);
+    }
+
+    #[test]
+    fn fir_latency_8x() {
+        test_linear_phase_latency(3, OversamplingFilter::LinearPhase);
+    }
+
+    #[test]
+    fn fir_latency_16x() {
+        test_linear_phase_latency(4, OversamplingFilter::LinearPhase);
+    }
+
+    #[test]
+    fn fir_sine_output() {
+        for factor in 1..=4 {
+            test_sine_output(factor, OversamplingFilter::LinearPhase, 0.001);
+        }
+    }
+
+    #[test]
+    fn lanczos3_latency() {
+        for factor in 1..=4 {
+            test_linear_phase_latency(factor, OversamplingFilter::Lanczos3);
+        }
+    }
+
+    #[test]
+    fn lanczos3_sine_output() {
+        // There can be quite a big difference between the input and output thanks to the short
+        // filter's passband ripple
+        for factor in 1..=4 {
+            test_sine_output(factor, OversamplingFilter::Lanczos3, 0.1);
+        }
+    }
+
+    #[test]
+    fn convolve_rb_with_wrap() {
+        let input_rb = [1.0, 2.0, -3.0, 4.0];
+        let kernel = [1.0, 2.0, -0.0, -1.0];
+        let input_pos = 2;
+
+        // This should be `(-3.0 * -1.0) + (4.0 * 0.0) + (1.0 * 2.0) + (2.0 * 1.0) = 7.0`
+        let result = convolve_rb(&input_rb, &kernel, input_pos);
+        assert_eq!(result, 7.0);
+    }
+
+    #[test]
+    fn convolve_rb_no_wrap() {
+        let input_rb = [1.0, 2.0, -3.0, 4.0];
+        let kernel = [1.0, 2.0, 0.0, -1.0];
+        let input_pos = 0;
+
+        // This should be `(1.0 * -1.0) + (2.0 * 0.0) + (-3.0 * 2.0) + (4.0 * 1.0) = -3.0`
+        let result = convolve_rb(&input_rb, &kernel, input_pos);
+        assert_eq!(result, -3.0);
+    }
+
+    #[test]
+    fn iir_sine_output() {
+        // The IIR filters' group delay is not exactly an integer, so the output is only
+        // approximately the same as the delayed input
+        for factor in 1..=4 {
+            test_sine_output(factor, OversamplingFilter::Iir, 0.1);
+        }
+    }
+
+    #[test]
+    fn multichannel_blocks() {
+        const NUM_SAMPLES: usize = 100;
+        const MAX_BLOCK_SIZE: usize = 32;
+        const FACTOR: usize = 2;
+
+        let mut left = [0.0f32; NUM_SAMPLES];
+        let mut right = [0.0f32; NUM_SAMPLES];
+        left[0] = 1.0;
+        right[0] = -1.0;
+
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(NUM_SAMPLES, |slices| {
+                slices.push(&mut left);
+                slices.push(&mut right);
+            })
+        };
+
+        let mut oversampler = MultichannelOversampler::new(
+            2,
+            MAX_BLOCK_SIZE,
+            FACTOR,
+            OversamplingFilter::LinearPhase,
+        );
+        let mut num_upsampled_samples = 0;
+        oversampler.process(&mut buffer, FACTOR, |upsampled| {
+            assert_eq!(upsampled.channels(), 2);
+            num_upsampled_samples += upsampled.samples();
+        });
+        assert_eq!(
+            num_upsampled_samples,
+            NUM_SAMPLES * oversampling_factor_to_times(FACTOR)
+        );
+
+        let latency = oversampler.latency(FACTOR) as usize;
+        assert_eq!(argmax(left), latency);
+        assert_eq!(argmax(right.map(|sample| -sample)), latency);
+    }
+}
@@ -0,0 +1,718 @@
+//! A spectrum analyzer for showing the spectrum of a plugin's audio in its editor.
+//!
+//! [`SpectrumInput::new()`] creates a connected [`SpectrumInput`] and [`SpectrumOutput`] pair. The
+//! input is fed with audio from the plugin's `process()` function, and the output is moved to the
+//! editor. Depending on the [`AnalysisThread`], the windowing, FFT, averaging, and peak hold are
+//! either done on the audio thread, or the audio thread only sends the buffered audio to the
+//! output and the analysis happens when the editor reads the spectrum. In both cases the input and
+//! output communicate through lock-free triple buffers, and neither side allocates after creation.
+
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::sync::Arc;
+use triple_buffer::TripleBuffer;
+
+use super::stft::StftInput;
+use super::window;
+use super::StftHelper;
+
+/// The settings for a [`SpectrumInput`] and [`SpectrumOutput`] pair.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumConfig {
+    /// The FFT window size. The spectrum will contain `window_size / 2 + 1` bins.
+    pub window_size: usize,
+    /// The number of overlapping windows per `window_size` samples. A new spectrum is computed
+    /// every `window_size / overlap_times` samples.
+    pub overlap_times: usize,
+    /// How consecutive spectra are averaged.
+    pub averaging: SpectrumAveraging,
+    /// Peak hold settings. When this is `None`, [`Spectrum::peaks`] will be empty.
+    pub peak_hold: Option<PeakHold>,
+    /// Where the spectrum is computed.
+    pub analysis_thread: AnalysisThread,
+}
+
+/// How new spectra are combined with the previous spectrum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpectrumAveraging {
+    /// Every new spectrum replaces the previous one.
+    None,
+    /// An exponential moving average. `decay_ms` is the time it takes for a bin to fall by 12 dB
+    /// after the input goes silent, and for it to rise by the same relative amount.
+    Exponential { decay_ms: f32 },
+    /// Like a peak meter, bins that increase in magnitude jump up immediately while bins that
+    /// decrease in magnitude fall gradually. `decay_ms` is the time it takes for a bin to fall by
+    /// 12 dB after the input goes silent.
+    PeakDecay { decay_ms: f32 },
+}
+
+/// Keeps track of every bin's highest averaged magnitude for a while.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeakHold {
+    /// How long a peak is held before it starts falling. Use `f32::INFINITY` to hold peaks until
+    /// the spectrum is reset.
+    pub hold_ms: f32,
+    /// The time it takes for a peak to fall by 12 dB after the hold time has passed.
+    pub decay_ms: f32,
+}
+
+/// Where the windowing, FFT, averaging, and peak hold happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisThread {
+    /// Compute the spectrum in [`SpectrumInput::process()`] on the audio thread. The output only
+    /// needs to copy the result.
+    Audio,
+    /// Only buffer the audio in [`SpectrumInput::process()`], and compute the spectrum in
+    /// [`SpectrumOutput::read()`] on whichever thread reads the spectrum, usually the editor's GUI
+    /// thread. Windows that the output has not read before the next one is ready are skipped, but
+    /// the averaging and peak hold still follow the configured times.
+    Background,
+}
+
+impl Default for SpectrumConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 2048,
+            overlap_times: 2,
+            averaging: SpectrumAveraging::PeakDecay { decay_ms: 100.0 },
+            peak_hold: None,
+            analysis_thread: AnalysisThread::Audio,
+        }
+    }
+}
+
+/// The magnitude spectrum of a plugin's audio, as published by a [`SpectrumInput`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// The averaged magnitudes of all frequency bins, including the DC bin. The magnitudes are
+    /// scaled so that a full scale sine wave results in a magnitude of 1.0 in the sine's bin, and
+    /// they are averaged over all channels.
+    pub magnitudes: Vec<f32>,
+    /// The held peak magnitudes for every bin, or an empty vector if peak hold is disabled.
+    pub peaks: Vec<f32>,
+    /// The sample rate the spectrum was computed at. This is needed to compute a bin's frequency.
+    pub sample_rate: f32,
+}
+
+/// Feeds audio to a spectrum analyzer. This lives on the audio thread. See the module-level
+/// documentation for more information.
+pub struct SpectrumInput {
+    /// Takes care of the buffering and overlap.
+    stft: StftHelper,
+    num_channels: usize,
+    window_size: usize,
+    overlap_times: usize,
+    sample_rate: f32,
+
+    mode: InputMode,
+}
+
+/// Receives the spectrum computed from the audio sent to the connected [`SpectrumInput`]. This can
+/// be moved to the editor, for instance wrapped in an `Arc<Mutex<SpectrumOutput>>`.
+pub struct SpectrumOutput {
+    mode: OutputMode,
+}
+
+enum InputMode {
+    Audio {
+        analyzer: Box<Analyzer>,
+        spectrum: triple_buffer::Input<Spectrum>,
+    },
+    Background {
+        frames: triple_buffer::Input<Frame>,
+        /// The number of windows that have been sent to the output. The output uses this to know
+        /// how many windows it skipped.
+        num_frames: u64,
+    },
+}
+
+enum OutputMode {
+    Audio(triple_buffer::Output<Spectrum>),
+    Background {
+        frames: triple_buffer::Output<Frame>,
+        analyzer: Box<Analyzer>,
+        /// The frame number of the last analyzed frame.
+        last_frame: u64,
+        spectrum: Spectrum,
+    },
+}
+
+/// A window of audio sent from the input to the output for [`AnalysisThread::Background`].
+#[derive(Clone)]
+struct Frame {
+    /// The unwindowed audio, indexed by `[channel_idx][sample_idx]`.
+    samples: Vec<Vec<f32>>,
+    /// The number of windows sent by the input, including this one.
+    frame: u64,
+    sample_rate: f32,
+}
+
+/// Computes the averaged magnitude spectrum and the held peaks from windows of audio.
+struct Analyzer {
+    window_size: usize,
+    overlap_times: usize,
+    averaging: SpectrumAveraging,
+    peak_hold: Option<PeakHold>,
+    sample_rate: f32,
+
+    /// The weight applied to the old value for every window with the current averaging mode.
+    averaging_weight: f32,
+    /// The number of windows a peak is held for.
+    peak_hold_windows: f32,
+    /// The weight applied to a peak for every window after the hold time has passed.
+    peak_decay_weight: f32,
+
+    plan: Arc<dyn RealToComplex<f32>>,
+    /// A Hann window with the gain compensation baked in.
+    compensated_window_function: Vec<f32>,
+    complex_fft_buffer: Vec<Complex32>,
+    fft_scratch_buffer: Vec<Complex32>,
+    /// The magnitudes for the current window, summed over all channels.
+    summed_magnitudes: Vec<f32>,
+    /// The number of channels added to `summed_magnitudes`.
+    num_summed_channels: usize,
+
+    magnitudes: Vec<f32>,
+    peaks: Vec<f32>,
+    /// The number of windows each bin's peak is still held for.
+    peak_hold_remaining: Vec<f32>,
+}
+
+impl SpectrumInput {
+    /// Create a new spectrum input and output pair for `num_channels` channels of audio. The
+    /// output should be moved to the editor. [`set_sample_rate()`][Self::set_sample_rate()] should
+    /// be called in the plugin's `initialize()` function.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no channels, if the window size is zero, or if it is not divisible by
+    /// the overlap amount.
+    pub fn new(num_channels: usize, config: SpectrumConfig) -> (SpectrumInput, SpectrumOutput) {
+        assert!(num_channels > 0);
+        assert!(config.window_size > 0);
+        assert!(config.overlap_times > 0);
+        assert!(config.window_size % config.overlap_times == 0);
+
+        let num_bins = config.window_size / 2 + 1;
+        let empty_spectrum = Spectrum {
+            magnitudes: vec![0.0; num_bins],
+            peaks: if config.peak_hold.is_some() {
+                vec![0.0; num_bins]
+            } else {
+                Vec::new()
+            },
+            sample_rate: 0.0,
+        };
+
+        let (input_mode, output_mode) = match config.analysis_thread {
+            AnalysisThread::Audio => {
+                let (spectrum_input, spectrum_output) = TripleBuffer::new(&empty_spectrum).split();
+
+                (
+                    InputMode::Audio {
+                        analyzer: Box::new(Analyzer::new(&config)),
+                        spectrum: spectrum_input,
+                    },
+                    OutputMode::Audio(spectrum_output),
+                )
+            }
+            AnalysisThread::Background => {
+                let (frames_input, frames_output) = TripleBuffer::new(&Frame {
+                    samples: vec![vec![0.0; config.window_size]; num_channels],
+                    frame: 0,
+                    sample_rate: 0.0,
+                })
+                .split();
+
+                (
+                    InputMode::Background {
+                        frames: frames_input,
+                        num_frames: 0,
+                    },
+                    OutputMode::Background {
+                        frames: frames_output,
+                        analyzer: Box::new(Analyzer::new(&config)),
+                        last_frame: 0,
+                        spectrum: empty_spectrum,
+                    },
+                )
+            }
+        };
+
+        let input = Self {
+            stft: StftHelper::new(num_channels, config.window_size, 0),
+            num_channels,
+            window_size: config.window_size,
+            overlap_times: config.overlap_times,
+            // This is set in `initialize()`
+            sample_rate: 0.0,
+
+            mode: input_mode,
+        };
+
+        (input, SpectrumOutput { mode: output_mode })
+    }
+
+    /// Set the sample rate. The averaging and peak hold times depend on this. Called in
+    /// `initialize()`.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        if let InputMode::Audio { analyzer, .. } = &mut self.mode {
+            analyzer.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Clear the buffered audio, and the averaged spectrum and peaks if they are computed on the
+    /// audio thread.
+    pub fn reset(&mut self) {
+        self.stft.set_block_size(self.window_size);
+        if let InputMode::Audio { analyzer, .. } = &mut self.mode {
+            analyzer.reset();
+        }
+    }
+
+    /// Add a buffer of audio to the analysis. A new spectrum is published to the
+    /// [`SpectrumOutput`] every `window_size / overlap_times` samples. The buffer needs to have
+    /// the same number of channels as the one passed to [`SpectrumInput::new()`].
+    pub fn process<B: StftInput>(&mut self, buffer: &B) {
+        let last_channel_idx = self.num_channels - 1;
+        let sample_rate = self.sample_rate;
+        let mode = &mut self.mode;
+        self.stft
+            .process_analyze_only(
+                buffer,
+                self.overlap_times,
+                |channel_idx, real_buffer| match mode {
+                    InputMode::Audio { analyzer, spectrum } => {
+                        analyzer.add_channel(channel_idx, real_buffer);
+                        if channel_idx == last_channel_idx {
+                            analyzer.finish_window(1);
+                            analyzer.write_spectrum(spectrum.input_buffer());
+                            spectrum.publish();
+                        }
+                    }
+                    InputMode::Background { frames, num_frames } => {
+                        let frame = frames.input_buffer();
+                        frame.samples[channel_idx].copy_from_slice(real_buffer);
+                        if channel_idx == last_channel_idx {
+                            *num_frames += 1;
+                            frame.frame = *num_frames;
+                            frame.sample_rate = sample_rate;
+                            frames.publish();
+                        }
+                    }
+                },
+            );
+    }
+}
+
+impl SpectrumOutput {
+    /// Get the most recent spectrum. With [`AnalysisThread::Background`] this first analyzes the
+    /// most recent window of audio if a new one is available.
+    pub fn read(&mut self) -> &Spectrum {
+        match &mut self.mode {
+            OutputMode::Audio(spectrum) => spectrum.read(),
+            OutputMode::Background {
+                frames,
+                analyzer,
+                last_frame,
+                spectrum,
+            } => {
+                if frames.update() {
+                    let frame = frames.output_buffer();
+                    let elapsed_windows = frame.frame.saturating_sub(*last_frame).max(1);
+                    *last_frame = frame.frame;
+
+                    if frame.sample_rate != analyzer.sample_rate {
+                        analyzer.set_sample_rate(frame.sample_rate);
+                    }
+                    for (channel_idx, samples) in frame.samples.iter_mut().enumerate() {
+                        analyzer.add_channel(channel_idx, samples);
+                    }
+                    analyzer.finish_window(elapsed_windows);
+                    analyzer.write_spectrum(spectrum);
+                }
+
+                spectrum
+            }
+        }
+    }
+}
+
+impl Spectrum {
+    /// The number of frequency bins in the spectrum.
+    pub fn num_bins(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    /// The center frequency of a bin, in Hertz.
+    pub fn bin_frequency(&self, bin_idx: usize) -> f32 {
+        bin_idx as f32 * self.bin_width()
+    }
+
+    /// The magnitude for a range of frequencies, for instance a single pixel column in a spectrum
+    /// display. This is the highest magnitude of all bins within the range, or the magnitude
+    /// interpolated from the surrounding bins if the range doesn't contain any bins.
+    pub fn magnitude_between(&self, low_frequency: f32, high_frequency: f32) -> f32 {
+        bins_between(
+            &self.magnitudes,
+            self.bin_width(),
+            low_frequency,
+            high_frequency,
+        )
+    }
+
+    /// The same as [`magnitude_between()`][Self::magnitude_between()], but for the held peaks.
+    /// Returns `None` if peak hold is disabled.
+    pub fn peak_between(&self, low_frequency: f32, high_frequency: f32) -> Option<f32> {
+        if self.peaks.is_empty() {
+            None
+        } else {
+            Some(bins_between(
+                &self.peaks,
+                self.bin_width(),
+                low_frequency,
+                high_frequency,
+            ))
+        }
+    }
+
+    /// The distance between two bins, in Hertz.
+    fn bin_width(&self) -> f32 {
+        let window_size = (self.num_bins().max(2) - 1) * 2;
+        self.sample_rate / window_size as f32
+    }
+}
+
+impl Analyzer {
+    fn new(config: &SpectrumConfig) -> Self {
+        let num_bins = config.window_size / 2 + 1;
+        let plan = RealFftPlanner::new().plan_fft_forward(config.window_size);
+        let fft_scratch_buffer = plan.make_scratch_vec();
+
+        // The compensation makes a full scale sine wave result in a magnitude of one
+        let mut compensated_window_function = window::hann(config.window_size);
+        let compensation = 2.0 / compensated_window_function.iter().sum::<f32>();
+        for sample in &mut compensated_window_function {
+            *sample *= compensation;
+        }
+
+        Self {
+            window_size: config.window_size,
+            overlap_times: config.overlap_times,
+            averaging: config.averaging,
+            peak_hold: config.peak_hold,
+            sample_rate: 0.0,
+
+            // These are set in `set_sample_rate()`
+            averaging_weight: 0.0,
+            peak_hold_windows: 0.0,
+            peak_decay_weight: 0.0,
+
+            plan,
+            compensated_window_function,
+            complex_fft_buffer: vec![Complex32::default(); num_bins],
+            fft_scratch_buffer,
+            summed_magnitudes: vec![0.0; num_bins],
+            num_summed_channels: 0,
+
+            magnitudes: vec![0.0; num_bins],
+            peaks: vec![0.0; num_bins],
+            peak_hold_remaining: vec![0.0; num_bins],
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+
+        let windows_per_second = sample_rate / self.window_size as f32 * self.overlap_times as f32;
+        self.averaging_weight = match self.averaging {
+            SpectrumAveraging::None => 0.0,
+            SpectrumAveraging::Exponential { decay_ms }
+            | SpectrumAveraging::PeakDecay { decay_ms } => {
+                decay_weight(decay_ms, windows_per_second)
+            }
+        };
+        if let Some(peak_hold) = self.peak_hold {
+            self.peak_hold_windows = peak_hold.hold_ms / 1000.0 * windows_per_second;
+            self.peak_decay_weight = decay_weight(peak_hold.decay_ms, windows_per_second);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.magnitudes.fill(0.0);
+        self.peaks.fill(0.0);
+        self.peak_hold_remaining.fill(0.0);
+    }
+
+    /// Window and analyze a channel's audio. This overwrites `samples`.
+    fn add_channel(&mut self, channel_idx: usize, samples: &mut [f32]) {
+        window::multiply_with_window(samples, &self.compensated_window_function);
+        self.plan
+            .process_with_scratch(
+                samples,
+                &mut self.complex_fft_buffer,
+                &mut self.fft_scratch_buffer,
+            )
+            .unwrap();
+
+        if channel_idx == 0 {
+            self.num_summed_channels = 1;
+            for (summed, bin) in self
+                .summed_magnitudes
+                .iter_mut()
+                .zip(&self.complex_fft_buffer)
+            {
+                *summed = bin.norm();
+            }
+        } else {
+            self.num_summed_channels += 1;
+            for (summed, bin) in self
+                .summed_magnitudes
+                .iter_mut()
+                .zip(&self.complex_fft_buffer)
+            {
+                *summed += bin.norm();
+            }
+        }
+    }
+
+    /// Update the averaged magnitudes and peaks after all channels have been added with
+    /// [`add_channel()`][Self::add_channel()]. `num_elapsed_windows` is the number of windows since
+    /// the last call, which is more than one if windows were skipped.
+    fn finish_window(&mut self, num_elapsed_windows: u64) {
+        let num_elapsed_windows = num_elapsed_windows.min(i32::MAX as u64) as i32;
+        let averaging_weight = self.averaging_weight.powi(num_elapsed_windows);
+        let peak_decay_weight = self.peak_decay_weight.powi(num_elapsed_windows);
+        let num_channels_recip = (self.num_summed_channels as f32).recip();
+
+        for (bin_idx, (summed, averaged)) in self
+            .summed_magnitudes
+            .iter()
+            .zip(self.magnitudes.iter_mut())
+            .enumerate()
+        {
+            let magnitude = summed * num_channels_recip;
+            *averaged = match self.averaging {
+                SpectrumAveraging::None => magnitude,
+                SpectrumAveraging::PeakDecay { .. } if magnitude >= *averaged => magnitude,
+                SpectrumAveraging::Exponential { .. } | SpectrumAveraging::PeakDecay { .. } => {
+                    magnitude + ((*averaged - magnitude) * averaging_weight)
+                }
+            };
+
+            if self.peak_hold.is_some() {
+                let peak = &mut self.peaks[bin_idx];
+                let hold_remaining = &mut self.peak_hold_remaining[bin_idx];
+                if *averaged >= *peak {
+                    *peak = *averaged;
+                    *hold_remaining = self.peak_hold_windows;
+                } else if *hold_remaining > 0.0 {
+                    *hold_remaining -= num_elapsed_windows as f32;
+                } else {
+                    *peak = (*peak * peak_decay_weight).max(*averaged);
+                }
+            }
+        }
+    }
+
+    fn write_spectrum(&self, spectrum: &mut Spectrum) {
+        spectrum.magnitudes.copy_from_slice(&self.magnitudes);
+        if self.peak_hold.is_some() {
+            spectrum.peaks.copy_from_slice(&self.peaks);
+        }
+        spectrum.sample_rate = self.sample_rate;
+    }
+}
+
+/// Compute the weight for the old value in a moving average such that, after `decay_ms`
+/// milliseconds at `windows_per_second` updates per second, a value decays by 12 dB.
+fn decay_weight(decay_ms: f32, windows_per_second: f32) -> f32 {
+    let decay_windows = (decay_ms / 1000.0 * windows_per_second) as f64;
+    if decay_windows > 0.0 {
+        0.25f64.powf(decay_windows.recip()) as f32
+    } else {
+        0.0
+    }
+}
+
+/// The highest value in `bins` between two frequencies, or the interpolated value at the center
+/// of the range if it doesn't contain any bins.
+fn bins_between(bins: &[f32], bin_width: f32, low_frequency: f32, high_frequency: f32) -> f32 {
+    if bins.is_empty() || bin_width <= 0.0 {
+        return 0.0;
+    }
+
+    let last_bin_idx = bins.len() - 1;
+    let low_bin = (low_frequency / bin_width).max(0.0);
+    let high_bin = (high_frequency / bin_width).min(last_bin_idx as f32);
+    let first_included_bin_idx = low_bin.ceil() as usize;
+    let last_included_bin_idx = high_bin.floor() as usize;
+    if first_included_bin_idx <= last_included_bin_idx && high_bin >= low_bin {
+        return bins[first_included_bin_idx..=last_included_bin_idx]
+            .iter()
+            .copied()
+            .fold(0.0, f32::max);
+    }
+
+    let center_bin = ((low_bin + high_bin) / 2.0).clamp(0.0, last_bin_idx as f32);
+    let previous_bin_idx = (center_bin.floor() as usize).min(last_bin_idx);
+    let next_bin_idx = (previous_bin_idx + 1).min(last_bin_idx);
+    let t = center_bin - previous_bin_idx as f32;
+
+    bins[previous_bin_idx] + ((bins[next_bin_idx] - bins[previous_bin_idx]) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+    use std::f32::consts;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const WINDOW_SIZE: usize = 256;
+    /// The bin the test sine is centered on.
+    const SINE_BIN: usize = 16;
+
+    fn config(averaging: SpectrumAveraging, peak_hold: Option<PeakHold>) -> SpectrumConfig {
+        SpectrumConfig {
+            window_size: WINDOW_SIZE,
+            overlap_times: 2,
+            averaging,
+            peak_hold,
+            analysis_thread: AnalysisThread::Audio,
+        }
+    }
+
+    fn sine(num_samples: usize, amplitude: f32) -> Vec<f32> {
+        (0..num_samples)
+            .map(|i| {
+                (i as f32 * consts::TAU * SINE_BIN as f32 / WINDOW_SIZE as f32).sin() * amplitude
+            })
+            .collect()
+    }
+
+    fn process(input: &mut SpectrumInput, left: &[f32], right: &[f32]) {
+        let mut left = left.to_vec();
+        let mut right = right.to_vec();
+        let mut buffer = Buffer::default();
+        unsafe {
+            buffer.set_slices(left.len(), |slices| {
+                slices.push(&mut left);
+                slices.push(&mut right);
+            })
+        };
+
+        input.process(&buffer);
+    }
+
+    fn peak_bin(magnitudes: &[f32]) -> usize {
+        magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn full_scale_sine() {
+        let (mut input, mut output) = SpectrumInput::new(2, config(SpectrumAveraging::None, None));
+        input.set_sample_rate(SAMPLE_RATE);
+        let signal = sine(WINDOW_SIZE * 4, 1.0);
+        process(&mut input, &signal, &signal);
+
+        let spectrum = output.read();
+        assert_eq!(spectrum.num_bins(), WINDOW_SIZE / 2 + 1);
+        assert_eq!(spectrum.sample_rate, SAMPLE_RATE);
+        assert!(spectrum.peaks.is_empty());
+        assert_eq!(peak_bin(&spectrum.magnitudes), SINE_BIN);
+        assert!((spectrum.magnitudes[SINE_BIN] - 1.0).abs() < 1e-3);
+
+        let sine_frequency = spectrum.bin_frequency(SINE_BIN);
+        assert_eq!(
+            sine_frequency,
+            SINE_BIN as f32 * SAMPLE_RATE / WINDOW_SIZE as f32
+        );
+        assert!(
+            (spectrum.magnitude_between(sine_frequency - 500.0, sine_frequency + 500.0) - 1.0)
+                .abs()
+                < 1e-3
+        );
+    }
+
+    #[test]
+    fn channels_are_averaged() {
+        let (mut input, mut output) = SpectrumInput::new(2, config(SpectrumAveraging::None, None));
+        input.set_sample_rate(SAMPLE_RATE);
+        process(
+            &mut input,
+            &sine(WINDOW_SIZE * 4, 1.0),
+            &vec![0.0; WINDOW_SIZE * 4],
+        );
+
+        assert!((output.read().magnitudes[SINE_BIN] - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_channels() {
+        SpectrumInput::new(0, config(SpectrumAveraging::None, None));
+    }
+
+    #[test]
+    fn background_matches_audio_thread() {
+        let averaging = SpectrumAveraging::Exponential { decay_ms: 20.0 };
+        let (mut audio_input, mut audio_output) = SpectrumInput::new(2, config(averaging, None));
+        let (mut background_input, mut background_output) = SpectrumInput::new(
+            2,
+            SpectrumConfig {
+                analysis_thread: AnalysisThread::Background,
+                ..config(averaging, None)
+            },
+        );
+        audio_input.set_sample_rate(SAMPLE_RATE);
+        background_input.set_sample_rate(SAMPLE_RATE);
+
+        // Reading the background output after every window analyzes every window, so the results
+        // should be identical
+        let signal = sine(WINDOW_SIZE * 8, 0.5);
+        for chunk in signal.chunks(WINDOW_SIZE / 2) {
+            process(&mut audio_input, chunk, chunk);
+            process(&mut background_input, chunk, chunk);
+            background_output.read();
+        }
+
+        assert_eq!(audio_output.read(), background_output.read());
+    }
+
+    #[test]
+    fn peak_decay_and_hold() {
+        let (mut input, mut output) = SpectrumInput::new(
+            2,
+            config(
+                SpectrumAveraging::PeakDecay { decay_ms: 10.0 },
+                Some(PeakHold {
+                    hold_ms: f32::INFINITY,
+                    decay_ms: 10.0,
+                }),
+            ),
+        );
+        input.set_sample_rate(SAMPLE_RATE);
+        let signal = sine(WINDOW_SIZE * 4, 1.0);
+        process(&mut input, &signal, &signal);
+        let silence = vec![0.0; WINDOW_SIZE * 16];
+        process(&mut input, &silence, &silence);
+
+        // 16 windows of silence at 375 windows per second is roughly 40 ms, so the averaged value
+        // should have fallen by about 48 dB while the peak is held
+        let spectrum = output.read();
+        assert!(spectrum.magnitudes[SINE_BIN] < 0.01);
+        assert!((spectrum.peaks[SINE_BIN] - 1.0).abs() < 1e-3);
+        assert!(spectrum.peak_between(0.0, SAMPLE_RATE / 2.0).unwrap() >= spectrum.peaks[SINE_BIN]);
+
+        input.reset();
+        process(&mut input, &silence, &silence);
+        assert_eq!(output.read().peaks[SINE_BIN], 0.0);
+    }
+}
@@ -30,9 +30,10 @@ pub trait StftInputMut: StftInput {
 /// will not be written to, so they are purely used for analysis. These sidechain inputs will have
 /// the same number of channels as the main input.
 ///
+/// For showing a spectrum in a plugin's editor, see
+/// [`SpectrumInput`][crate::util::spectrum::SpectrumInput] instead.
+///
 /// TODO: Better name?
-/// TODO: We may need something like this purely for analysis, e.g. for showing spectrums in a GUI.
-///       Figure out the cleanest way to adapt this for the non-processing use case.
 pub struct StftHelper<const NUM_SIDECHAIN_INPUTS: usize = 0> {
     // These ring buffers store the input samples and the already processed output produced by
     // adding overlapping windows. Whenever we reach a new overlapping window, we'll write the
@@ -2,6 +2,8 @@
 //! name of a type that implements `Plugin` to. The macro will handle the rest.
 
 pub mod clap;
+#[cfg(feature = "lv2")]
+pub mod lv2;
 pub mod state;
 pub(crate) mod util;
 
@@ -4,12 +4,19 @@ mod util;
 mod context;
 mod descriptor;
 pub mod features;
+pub(crate) mod note_expressions;
+mod preset_discovery;
 mod wrapper;
 
 /// Re-export for the macro
 pub use self::descriptor::PluginDescriptor;
+pub use self::preset_discovery::{PresetDiscoveryProvider, PresetDiscoveryProviderDescriptor};
 pub use self::wrapper::Wrapper;
 pub use clap_sys::entry::clap_plugin_entry;
+pub use clap_sys::factory::draft::preset_discovery::{
+    clap_preset_discovery_factory, clap_preset_discovery_indexer, clap_preset_discovery_provider,
+    clap_preset_discovery_provider_descriptor, CLAP_PRESET_DISCOVERY_FACTORY_ID,
+};
 pub use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
 pub use clap_sys::host::clap_host;
 pub use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
@@ -27,8 +34,12 @@ macro_rules! nih_export_clap {
         mod clap {
             use $crate::prelude::nih_debug_assert_eq;
             use $crate::wrapper::setup_logger;
-            use $crate::wrapper::clap::{PluginDescriptor, Wrapper};
+            use $crate::wrapper::clap::{PluginDescriptor, PresetDiscoveryProvider, PresetDiscoveryProviderDescriptor, Wrapper};
             use $crate::wrapper::clap::{CLAP_PLUGIN_FACTORY_ID, clap_host, clap_plugin, clap_plugin_descriptor, clap_plugin_factory};
+            use $crate::wrapper::clap::{
+                CLAP_PRESET_DISCOVERY_FACTORY_ID, clap_preset_discovery_factory, clap_preset_discovery_indexer,
+                clap_preset_discovery_provider, clap_preset_discovery_provider_descriptor,
+            };
             use ::std::collections::HashSet;
             use ::std::ffi::{CStr, c_void};
             use ::std::os::raw::c_char;
@@ -114,6 +125,63 @@ macro_rules! nih_export_clap {
                 ::std::ptr::null()
             }
 
+            const CLAP_PRESET_DISCOVERY_FACTORY: clap_preset_discovery_factory = clap_preset_discovery_factory {
+                count: Some(get_preset_provider_count),
+                get_descriptor: Some(get_preset_provider_descriptor),
+                create: Some(create_preset_provider),
+            };
+
+            // Only plugins that set `ClapPlugin::CLAP_PRESET_DISCOVERY_CONFIG` have a preset
+            // discovery provider. These are stored in the same order as the plugin descriptors.
+            static PRESET_PROVIDER_DESCRIPTORS: OnceLock<[Option<PresetDiscoveryProviderDescriptor>; PLUGIN_COUNT]> =
+                OnceLock::new();
+
+            fn preset_provider_descriptors() -> &'static [Option<PresetDiscoveryProviderDescriptor>; PLUGIN_COUNT] {
+                PRESET_PROVIDER_DESCRIPTORS.get_or_init(|| {
+                    [$(PresetDiscoveryProviderDescriptor::for_plugin::<$plugin_ty>()),+]
+                })
+            }
+
+            unsafe extern "C" fn get_preset_provider_count(_factory: *const clap_preset_discovery_factory) -> u32 {
+                preset_provider_descriptors().iter().flatten().count() as u32
+            }
+
+            unsafe extern "C" fn get_preset_provider_descriptor(
+                _factory: *const clap_preset_discovery_factory,
+                index: u32,
+            ) -> *const clap_preset_discovery_provider_descriptor {
+                match preset_provider_descriptors().iter().flatten().nth(index as usize) {
+                    Some(descriptor) => descriptor.clap_provider_descriptor(),
+                    None => ::std::ptr::null()
+                }
+            }
+
+            unsafe extern "C" fn create_preset_provider(
+                _factory: *const clap_preset_discovery_factory,
+                indexer: *const clap_preset_discovery_indexer,
+                provider_id: *const c_char,
+            ) -> *const clap_preset_discovery_provider {
+                if indexer.is_null() || provider_id.is_null() {
+                    return ::std::ptr::null();
+                }
+                let provider_id_cstr = CStr::from_ptr(provider_id);
+
+                // This uses the same approach as `create_plugin()`
+                let descriptors = preset_provider_descriptors();
+                let mut descriptor_idx = 0;
+                $({
+                    if let Some(descriptor) = &descriptors[descriptor_idx] {
+                        if provider_id_cstr == descriptor.id() {
+                            return PresetDiscoveryProvider::<$plugin_ty>::create(descriptor, indexer);
+                        }
+                    }
+
+                    descriptor_idx += 1;
+                })+
+
+                ::std::ptr::null()
+            }
+
             pub extern "C" fn init(_plugin_path: *const c_char) -> bool {
                 setup_logger();
                 true
@@ -122,8 +190,17 @@ macro_rules! nih_export_clap {
             pub extern "C" fn deinit() {}
 
             pub extern "C" fn get_factory(factory_id: *const c_char) -> *const c_void {
-                if !factory_id.is_null() && unsafe { CStr::from_ptr(factory_id) } == CLAP_PLUGIN_FACTORY_ID {
+                if factory_id.is_null() {
+                    return ::std::ptr::null();
+                }
+
+                let factory_id = unsafe { CStr::from_ptr(factory_id) };
+                if factory_id == CLAP_PLUGIN_FACTORY_ID {
                     &CLAP_PLUGIN_FACTORY as *const _ as *const c_void
+                } else if factory_id == CLAP_PRESET_DISCOVERY_FACTORY_ID
+                    && preset_provider_descriptors().iter().any(Option::is_some)
+                {
+                    &CLAP_PRESET_DISCOVERY_FACTORY as *const _ as *const c_void
                 } else {
                     ::std::ptr::null()
                 }
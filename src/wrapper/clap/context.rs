@@ -11,8 +11,9 @@ use std::sync::Arc;
 use super::wrapper::{OutputParamEvent, Task, Wrapper};
 use crate::event_loop::EventLoop;
 use crate::prelude::{
-    ClapPlugin, GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent, ProcessContext,
-    RemoteControlsContext, RemoteControlsPage, RemoteControlsSection, Transport,
+    ClapPlugin, ContextMenuItem, GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent,
+    ProcessContext, RemoteControlsContext, RemoteControlsPage, RemoteControlsSection, TrackInfo,
+    Transport,
 };
 use crate::wrapper::util::strlcpy;
 
@@ -78,6 +79,10 @@ impl<P: ClapPlugin> InitContext<P> for WrapperInitContext<'_, P> {
         PluginApi::Clap
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.wrapper.track_info()
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.wrapper.task_executor.lock())(task);
     }
@@ -127,6 +132,10 @@ impl<P: ClapPlugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
     fn set_current_voice_capacity(&self, capacity: u32) {
         self.wrapper.set_current_voice_capacity(capacity)
     }
+
+    fn note_names_changed(&self) {
+        self.wrapper.note_names_changed()
+    }
 }
 
 impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
@@ -134,6 +143,10 @@ impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
         PluginApi::Clap
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.wrapper.track_info()
+    }
+
     fn request_resize(&self) -> bool {
         self.wrapper.request_resize()
     }
@@ -233,6 +246,42 @@ impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
         }
     }
 
+    unsafe fn raw_show_param_context_menu(&self, param: ParamPtr, x: i32, y: i32) -> bool {
+        match self.wrapper.param_ptr_to_hash.get(&param) {
+            Some(hash) => self.wrapper.show_param_context_menu(*hash, x, y),
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                false
+            }
+        }
+    }
+
+    unsafe fn raw_param_context_menu_items(&self, param: ParamPtr) -> Vec<ContextMenuItem> {
+        match self.wrapper.param_ptr_to_hash.get(&param) {
+            Some(hash) => self.wrapper.param_context_menu_items(*hash),
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                Vec::new()
+            }
+        }
+    }
+
+    unsafe fn raw_perform_param_context_menu_action(
+        &self,
+        param: ParamPtr,
+        action_id: u32,
+    ) -> bool {
+        match self.wrapper.param_ptr_to_hash.get(&param) {
+            Some(hash) => self
+                .wrapper
+                .perform_param_context_menu_action(*hash, action_id),
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                false
+            }
+        }
+    }
+
     fn get_state(&self) -> crate::wrapper::state::PluginState {
         self.wrapper.get_state_object()
     }
@@ -240,6 +289,10 @@ impl<P: ClapPlugin> GuiContext for WrapperGuiContext<P> {
     fn set_state(&self, state: crate::wrapper::state::PluginState) {
         self.wrapper.set_state_object_from_gui(state)
     }
+
+    fn note_names_changed(&self) {
+        self.wrapper.note_names_changed()
+    }
 }
 
 /// A remote control section. The plugin can fill this with information for one or more pages.
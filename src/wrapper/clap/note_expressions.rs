@@ -0,0 +1,359 @@
+//! Conversions between CLAP's predefined note expressions and NIH-plug's polyphonic expression
+//! events. These are kept in one place so the input and output directions stay symmetrical.
+
+use clap_sys::events::{
+    clap_event_header, clap_event_note_expression, CLAP_CORE_EVENT_SPACE_ID,
+    CLAP_EVENT_NOTE_EXPRESSION, CLAP_NOTE_EXPRESSION_BRIGHTNESS, CLAP_NOTE_EXPRESSION_EXPRESSION,
+    CLAP_NOTE_EXPRESSION_PAN, CLAP_NOTE_EXPRESSION_PRESSURE, CLAP_NOTE_EXPRESSION_TUNING,
+    CLAP_NOTE_EXPRESSION_VIBRATO, CLAP_NOTE_EXPRESSION_VOLUME,
+};
+use std::mem;
+
+use crate::prelude::NoteEvent;
+
+/// Translate a CLAP note expression event into a NIH-plug polyphonic expression event. Returns
+/// `None` if the expression ID is unknown. The timing is provided separately because the event's
+/// timing needs to be relative to the current block.
+pub fn translate_event<S>(timing: u32, event: &clap_event_note_expression) -> Option<NoteEvent<S>> {
+    let voice_id = if event.note_id != -1 {
+        Some(event.note_id)
+    } else {
+        None
+    };
+    // -1 means that the expression applies to all channels or keys
+    let channel = u8::try_from(event.channel).ok();
+    let note = u8::try_from(event.key).ok();
+
+    match event.expression_id {
+        CLAP_NOTE_EXPRESSION_PRESSURE => Some(NoteEvent::PolyPressure {
+            timing,
+            voice_id,
+            channel,
+            note,
+            pressure: event.value as f32,
+        }),
+        // CLAP also uses a voltage gain ratio here, with 4.0 being +12 dB
+        CLAP_NOTE_EXPRESSION_VOLUME => Some(NoteEvent::PolyVolume {
+            timing,
+            voice_id,
+            channel,
+            note,
+            gain: event.value as f32,
+        }),
+        // In CLAP this value goes from [0, 1] instead of [-1, 1]
+        CLAP_NOTE_EXPRESSION_PAN => Some(NoteEvent::PolyPan {
+            timing,
+            voice_id,
+            channel,
+            note,
+            pan: (event.value as f32 * 2.0) - 1.0,
+        }),
+        CLAP_NOTE_EXPRESSION_TUNING => Some(NoteEvent::PolyTuning {
+            timing,
+            voice_id,
+            channel,
+            note,
+            tuning: event.value as f32,
+        }),
+        CLAP_NOTE_EXPRESSION_VIBRATO => Some(NoteEvent::PolyVibrato {
+            timing,
+            voice_id,
+            channel,
+            note,
+            vibrato: event.value as f32,
+        }),
+        CLAP_NOTE_EXPRESSION_EXPRESSION => Some(NoteEvent::PolyExpression {
+            timing,
+            voice_id,
+            channel,
+            note,
+            expression: event.value as f32,
+        }),
+        CLAP_NOTE_EXPRESSION_BRIGHTNESS => Some(NoteEvent::PolyBrightness {
+            timing,
+            voice_id,
+            channel,
+            note,
+            brightness: event.value as f32,
+        }),
+        _ => None,
+    }
+}
+
+/// Translate a NIH-plug polyphonic expression event to a CLAP note expression event at sample
+/// `time`. Will return `None` if the event is not a polyphonic expression event, i.e. one of the
+/// events produced by `translate_event()`.
+pub fn translate_event_reverse<S>(
+    time: u32,
+    event: &NoteEvent<S>,
+) -> Option<clap_event_note_expression> {
+    let (expression_id, voice_id, channel, note, value) = match *event {
+        NoteEvent::PolyPressure {
+            voice_id,
+            channel,
+            note,
+            pressure,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_PRESSURE,
+            voice_id,
+            channel,
+            note,
+            pressure as f64,
+        ),
+        NoteEvent::PolyVolume {
+            voice_id,
+            channel,
+            note,
+            gain,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_VOLUME,
+            voice_id,
+            channel,
+            note,
+            gain as f64,
+        ),
+        NoteEvent::PolyPan {
+            voice_id,
+            channel,
+            note,
+            pan,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_PAN,
+            voice_id,
+            channel,
+            note,
+            (pan as f64 + 1.0) / 2.0,
+        ),
+        NoteEvent::PolyTuning {
+            voice_id,
+            channel,
+            note,
+            tuning,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_TUNING,
+            voice_id,
+            channel,
+            note,
+            tuning as f64,
+        ),
+        NoteEvent::PolyVibrato {
+            voice_id,
+            channel,
+            note,
+            vibrato,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_VIBRATO,
+            voice_id,
+            channel,
+            note,
+            vibrato as f64,
+        ),
+        NoteEvent::PolyExpression {
+            voice_id,
+            channel,
+            note,
+            expression,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_EXPRESSION,
+            voice_id,
+            channel,
+            note,
+            expression as f64,
+        ),
+        NoteEvent::PolyBrightness {
+            voice_id,
+            channel,
+            note,
+            brightness,
+            ..
+        } => (
+            CLAP_NOTE_EXPRESSION_BRIGHTNESS,
+            voice_id,
+            channel,
+            note,
+            brightness as f64,
+        ),
+        _ => return None,
+    };
+
+    Some(clap_event_note_expression {
+        header: clap_event_header {
+            size: mem::size_of::<clap_event_note_expression>() as u32,
+            time,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_: CLAP_EVENT_NOTE_EXPRESSION,
+            flags: 0,
+        },
+        expression_id,
+        note_id: voice_id.unwrap_or(-1),
+        port_index: 0,
+        channel: channel.map_or(-1, |channel| channel as i16),
+        key: note.map_or(-1, |note| note as i16),
+        value,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use clap_sys::events::clap_note_expression;
+
+    use super::*;
+
+    const TIMING: u32 = 5;
+
+    /// Polyphonic expression events paired with the CLAP expression ID and value they should be
+    /// converted to. The VST3 tests use the same events to make sure both formats agree.
+    pub(crate) fn test_cases() -> [(NoteEvent<()>, clap_note_expression, f64); 7] {
+        let voice_id = Some(1);
+        let channel = Some(2);
+        let note = Some(60);
+
+        [
+            (
+                NoteEvent::PolyPressure {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    pressure: 0.75,
+                },
+                CLAP_NOTE_EXPRESSION_PRESSURE,
+                0.75,
+            ),
+            (
+                NoteEvent::PolyVolume {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    gain: 2.0,
+                },
+                CLAP_NOTE_EXPRESSION_VOLUME,
+                2.0,
+            ),
+            (
+                NoteEvent::PolyPan {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    pan: -0.5,
+                },
+                CLAP_NOTE_EXPRESSION_PAN,
+                0.25,
+            ),
+            (
+                NoteEvent::PolyTuning {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    tuning: -30.0,
+                },
+                CLAP_NOTE_EXPRESSION_TUNING,
+                -30.0,
+            ),
+            (
+                NoteEvent::PolyVibrato {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    vibrato: 0.25,
+                },
+                CLAP_NOTE_EXPRESSION_VIBRATO,
+                0.25,
+            ),
+            (
+                NoteEvent::PolyExpression {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    expression: 0.5,
+                },
+                CLAP_NOTE_EXPRESSION_EXPRESSION,
+                0.5,
+            ),
+            (
+                NoteEvent::PolyBrightness {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    brightness: 0.125,
+                },
+                CLAP_NOTE_EXPRESSION_BRIGHTNESS,
+                0.125,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_translate_event() {
+        for (expected, expression_id, value) in test_cases() {
+            let event = clap_event_note_expression {
+                expression_id,
+                value,
+                ..translate_event_reverse(0, &expected).unwrap()
+            };
+
+            assert_eq!(translate_event::<()>(TIMING, &event), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_translate_event_reverse() {
+        for (event, expression_id, value) in test_cases() {
+            let clap_event = translate_event_reverse(TIMING, &event).unwrap();
+
+            assert_eq!(clap_event.header.time, TIMING);
+            assert_eq!(clap_event.expression_id, expression_id);
+            assert_eq!(clap_event.value, value);
+            assert_eq!(clap_event.note_id, 1);
+            assert_eq!(clap_event.channel, 2);
+            assert_eq!(clap_event.key, 60);
+        }
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let event = NoteEvent::<()>::PolyTuning {
+            timing: TIMING,
+            voice_id: None,
+            channel: Some(3),
+            note: None,
+            tuning: 1.0,
+        };
+        let clap_event = translate_event_reverse(TIMING, &event).unwrap();
+        assert_eq!(clap_event.note_id, -1);
+        assert_eq!(clap_event.channel, 3);
+        assert_eq!(clap_event.key, -1);
+
+        assert_eq!(translate_event(TIMING, &clap_event), Some(event));
+    }
+
+    #[test]
+    fn test_unknown_expression() {
+        let event = NoteEvent::<()>::NoteOn {
+            timing: TIMING,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        };
+        assert!(translate_event_reverse(TIMING, &event).is_none());
+
+        let clap_event = clap_event_note_expression {
+            expression_id: 1234,
+            ..translate_event_reverse(TIMING, &test_cases()[0].0).unwrap()
+        };
+        assert_eq!(translate_event::<()>(TIMING, &clap_event), None);
+    }
+}
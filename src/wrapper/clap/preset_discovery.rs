@@ -0,0 +1,824 @@
+//! An implementation of CLAP's preset discovery factory. This lets the host index the plugin's
+//! presets as configured through [`ClapPlugin::CLAP_PRESET_DISCOVERY_CONFIG`]. Loading those
+//! presets happens through the preset-load extension on the plugin instance.
+
+use anyhow::{Context, Result};
+use clap_sys::factory::draft::preset_discovery::clap_plugin_id;
+use clap_sys::factory::draft::preset_discovery::{
+    clap_preset_discovery_filetype, clap_preset_discovery_indexer, clap_preset_discovery_location,
+    clap_preset_discovery_location_kind, clap_preset_discovery_metadata_receiver,
+    clap_preset_discovery_provider, clap_preset_discovery_provider_descriptor,
+    CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT, CLAP_PRESET_DISCOVERY_IS_USER_CONTENT,
+    CLAP_PRESET_DISCOVERY_LOCATION_FILE, CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+};
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use super::util::ClapPtr;
+use crate::prelude::ClapPlugin;
+use crate::presets::{self, Preset};
+
+/// The descriptor for a plugin's preset discovery provider. Only plugins that set
+/// [`ClapPlugin::CLAP_PRESET_DISCOVERY_CONFIG`] have a provider.
+pub struct PresetDiscoveryProviderDescriptor {
+    id: CString,
+    name: CString,
+    vendor: CString,
+
+    /// Contains pointers to the strings above.
+    provider_descriptor: Option<clap_preset_discovery_provider_descriptor>,
+}
+
+unsafe impl Send for PresetDiscoveryProviderDescriptor {}
+unsafe impl Sync for PresetDiscoveryProviderDescriptor {}
+
+impl PresetDiscoveryProviderDescriptor {
+    /// Construct the provider descriptor for a plugin. Returns `None` if the plugin does not expose
+    /// its presets to the host.
+    pub fn for_plugin<P: ClapPlugin>() -> Option<Self> {
+        P::CLAP_PRESET_DISCOVERY_CONFIG.as_ref()?;
+
+        let mut descriptor = Self {
+            id: CString::new(format!("{}.presets", P::CLAP_ID))
+                .expect("`CLAP_ID` contained null bytes"),
+            name: CString::new(format!("{} presets", P::NAME))
+                .expect("`NAME` contained null bytes"),
+            vendor: CString::new(P::VENDOR).expect("`VENDOR` contained null bytes"),
+
+            provider_descriptor: None,
+        };
+
+        // NOTE: This is safe without pinning this struct because all of the data is already stored
+        //       on the heap
+        descriptor.provider_descriptor = Some(clap_preset_discovery_provider_descriptor {
+            clap_version: CLAP_VERSION,
+            id: descriptor.id.as_ptr(),
+            name: descriptor.name.as_ptr(),
+            vendor: descriptor.vendor.as_ptr(),
+        });
+
+        Some(descriptor)
+    }
+
+    pub fn clap_provider_descriptor(&self) -> &clap_preset_discovery_provider_descriptor {
+        self.provider_descriptor.as_ref().unwrap()
+    }
+
+    pub fn id(&self) -> &CStr {
+        self.id.as_c_str()
+    }
+}
+
+/// A preset discovery provider for a single plugin. The host creates these through the factory
+/// exported from `nih_export_clap!()`. The provider does not instantiate the plugin.
+pub struct PresetDiscoveryProvider<P: ClapPlugin> {
+    /// The vtable passed to the host. `provider_data` points to this object.
+    clap_preset_discovery_provider: clap_preset_discovery_provider,
+    indexer: ClapPtr<clap_preset_discovery_indexer>,
+
+    _phantom: PhantomData<P>,
+}
+
+impl<P: ClapPlugin> PresetDiscoveryProvider<P> {
+    /// Create a new provider. The returned pointer is freed again when the host calls
+    /// [`destroy()`][Self::destroy()].
+    ///
+    /// # Safety
+    ///
+    /// `descriptor` and `indexer` need to outlive the returned object.
+    pub unsafe fn create(
+        descriptor: &PresetDiscoveryProviderDescriptor,
+        indexer: *const clap_preset_discovery_indexer,
+    ) -> *const clap_preset_discovery_provider {
+        let provider = Box::leak(Box::new(Self {
+            clap_preset_discovery_provider: clap_preset_discovery_provider {
+                desc: descriptor.clap_provider_descriptor(),
+                // Set below, once the object has a stable address
+                provider_data: std::ptr::null_mut(),
+                init: Some(Self::init),
+                destroy: Some(Self::destroy),
+                get_metadata: Some(Self::get_metadata),
+                get_extension: Some(Self::get_extension),
+            },
+            indexer: ClapPtr::new(indexer),
+
+            _phantom: PhantomData,
+        }));
+        provider.clap_preset_discovery_provider.provider_data = provider as *mut Self as *mut _;
+
+        &provider.clap_preset_discovery_provider
+    }
+
+    unsafe extern "C" fn init(provider: *const clap_preset_discovery_provider) -> bool {
+        check_null_ptr!(false, provider, (*provider).provider_data);
+        let this = &*((*provider).provider_data as *const Self);
+        let config = match &P::CLAP_PRESET_DISCOVERY_CONFIG {
+            Some(config) => config,
+            None => return false,
+        };
+        let indexer = &this.indexer;
+
+        let filetype_name = CString::new(format!("{} preset", P::NAME)).unwrap();
+        let filetype_description = CString::default();
+        for extension in config.file_extensions {
+            let extension =
+                CString::new(*extension).expect("Preset file extension contained null bytes");
+            let filetype = clap_preset_discovery_filetype {
+                name: filetype_name.as_ptr(),
+                description: filetype_description.as_ptr(),
+                file_extension: extension.as_ptr(),
+            };
+            if !clap_call! { indexer=>declare_filetype(&**indexer, &filetype) } {
+                nih_debug_assert_failure!("The host rejected the {:?} preset file type", extension);
+                return false;
+            }
+        }
+
+        // Embedded presets don't have a location, and they're instead loaded using their index as
+        // the load key
+        if !config.embedded_presets.is_empty() {
+            let name = CString::new("Built-in presets").unwrap();
+            let location = clap_preset_discovery_location {
+                flags: CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+                name: name.as_ptr(),
+                kind: CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+                location: std::ptr::null(),
+            };
+            if !clap_call! { indexer=>declare_location(&**indexer, &location) } {
+                return false;
+            }
+        }
+
+        let factory_directories = (config.factory_directories)();
+        let user_directory = if config.include_user_directory {
+            presets::default_user_directory::<P>()
+        } else {
+            None
+        };
+        let directories = factory_directories
+            .iter()
+            .map(|directory| {
+                (
+                    directory,
+                    "Factory presets",
+                    CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+                )
+            })
+            .chain(user_directory.iter().map(|directory| {
+                (
+                    directory,
+                    "User presets",
+                    CLAP_PRESET_DISCOVERY_IS_USER_CONTENT,
+                )
+            }));
+        for (directory, name, flags) in directories {
+            let path = match directory.to_str().and_then(|path| CString::new(path).ok()) {
+                Some(path) => path,
+                None => {
+                    nih_debug_assert_failure!("Unsupported preset directory {:?}", directory);
+                    continue;
+                }
+            };
+            let name = CString::new(name).unwrap();
+            let location = clap_preset_discovery_location {
+                flags,
+                name: name.as_ptr(),
+                kind: CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                location: path.as_ptr(),
+            };
+            if !clap_call! { indexer=>declare_location(&**indexer, &location) } {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    unsafe extern "C" fn destroy(provider: *const clap_preset_discovery_provider) {
+        assert!(!provider.is_null() && !(*provider).provider_data.is_null());
+        drop(Box::from_raw((*provider).provider_data as *mut Self));
+    }
+
+    unsafe extern "C" fn get_metadata(
+        provider: *const clap_preset_discovery_provider,
+        location_kind: clap_preset_discovery_location_kind,
+        location: *const c_char,
+        metadata_receiver: *const clap_preset_discovery_metadata_receiver,
+    ) -> bool {
+        check_null_ptr!(
+            false,
+            provider,
+            (*provider).provider_data,
+            metadata_receiver
+        );
+        let config = match &P::CLAP_PRESET_DISCOVERY_CONFIG {
+            Some(config) => config,
+            None => return false,
+        };
+        let receiver = &*metadata_receiver;
+
+        if location_kind == CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+            for (preset_idx, bytes) in config.embedded_presets.iter().enumerate() {
+                match Preset::from_bytes(bytes) {
+                    Ok(preset) => {
+                        let load_key = CString::new(preset_idx.to_string()).unwrap();
+                        if !declare_preset::<P>(receiver, &preset, &load_key) {
+                            break;
+                        }
+                    }
+                    Err(err) => {
+                        nih_debug_assert_failure!(
+                            "Could not parse embedded preset {}: {:#}",
+                            preset_idx,
+                            err
+                        );
+                    }
+                }
+            }
+
+            true
+        } else {
+            check_null_ptr!(false, location);
+            let path = match CStr::from_ptr(location).to_str() {
+                Ok(path) => Path::new(path),
+                Err(_) => return false,
+            };
+
+            match Preset::load(path) {
+                Ok(preset) => {
+                    declare_preset::<P>(receiver, &preset, &CString::default());
+                    true
+                }
+                Err(err) => {
+                    let message = CString::new(format!("{err:#}")).unwrap_or_default();
+                    clap_call! { receiver=>on_error(receiver, 0, message.as_ptr()) };
+                    false
+                }
+            }
+        }
+    }
+
+    unsafe extern "C" fn get_extension(
+        provider: *const clap_preset_discovery_provider,
+        _extension_id: *const c_char,
+    ) -> *const c_void {
+        check_null_ptr!(std::ptr::null(), provider);
+
+        std::ptr::null()
+    }
+}
+
+/// Pass a preset's metadata to the host's metadata receiver. An empty load key is passed as a null
+/// pointer. Returns `false` if the host does not want to receive any more presets from this
+/// location.
+unsafe fn declare_preset<P: ClapPlugin>(
+    receiver: &clap_preset_discovery_metadata_receiver,
+    preset: &Preset,
+    load_key: &CStr,
+) -> bool {
+    let metadata = &preset.metadata;
+    let name = CString::new(metadata.name.as_str()).unwrap_or_default();
+    let load_key = if load_key.to_bytes().is_empty() {
+        std::ptr::null()
+    } else {
+        load_key.as_ptr()
+    };
+    if !clap_call! { receiver=>begin_preset(receiver, name.as_ptr(), load_key) } {
+        return false;
+    }
+
+    let plugin_id = CString::new(P::CLAP_ID).expect("`CLAP_ID` contained null bytes");
+    let clap_plugin_id = clap_plugin_id {
+        abi: b"clap\0".as_ptr() as *const c_char,
+        id: plugin_id.as_ptr(),
+    };
+    clap_call! { receiver=>add_plugin_id(receiver, &clap_plugin_id) };
+
+    if !metadata.author.is_empty() {
+        let author = CString::new(metadata.author.as_str()).unwrap_or_default();
+        clap_call! { receiver=>add_creator(receiver, author.as_ptr()) };
+    }
+    if !metadata.comment.is_empty() {
+        let comment = CString::new(metadata.comment.as_str()).unwrap_or_default();
+        clap_call! { receiver=>set_description(receiver, comment.as_ptr()) };
+    }
+    for tag in &metadata.tags {
+        let tag = CString::new(tag.as_str()).unwrap_or_default();
+        clap_call! { receiver=>add_feature(receiver, tag.as_ptr()) };
+    }
+
+    true
+}
+
+/// Read a preset from a location passed to the preset-load extension. Embedded presets use the
+/// `CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN` location kind with their index as the load key.
+///
+/// # Safety
+///
+/// `location` and `load_key` need to be either null pointers or valid C-strings.
+pub unsafe fn read_preset<P: ClapPlugin>(
+    location_kind: clap_preset_discovery_location_kind,
+    location: *const c_char,
+    load_key: *const c_char,
+) -> Result<Preset> {
+    let config = P::CLAP_PRESET_DISCOVERY_CONFIG
+        .as_ref()
+        .context("The plugin does not expose any presets")?;
+
+    if location_kind == CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+        anyhow::ensure!(
+            !load_key.is_null(),
+            "Missing load key for an embedded preset"
+        );
+        let preset_idx: usize = CStr::from_ptr(load_key)
+            .to_str()?
+            .parse()
+            .context("Invalid load key for an embedded preset")?;
+        let bytes = config
+            .embedded_presets
+            .get(preset_idx)
+            .with_context(|| format!("Embedded preset {preset_idx} does not exist"))?;
+
+        Preset::from_bytes(bytes)
+    } else if location_kind == CLAP_PRESET_DISCOVERY_LOCATION_FILE {
+        anyhow::ensure!(!location.is_null(), "Missing preset file location");
+        let path = CStr::from_ptr(location).to_str()?;
+
+        Preset::load(path)
+    } else {
+        anyhow::bail!("Unknown preset location kind {location_kind}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::num::NonZeroU32;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::presets::PRESET_FILE_EXTENSION;
+
+    /// A preset using NIH-plug's uncompressed preset format.
+    const EMBEDDED_PRESET: &[u8] = br#"{
+        "metadata": {"name": "Init", "author": "NIH-plug", "tags": ["Bass"], "comment": "The default sound"},
+        "state": {"version": "0.0.0", "params": {}, "fields": {}}
+    }"#;
+
+    struct EmptyParams;
+
+    unsafe impl Params for EmptyParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            Vec::new()
+        }
+    }
+
+    /// Exposes [`EMBEDDED_PRESET`] and the presets in [`factory_directory()`] to the host.
+    #[derive(Default)]
+    struct PresetPlugin;
+
+    impl Plugin for PresetPlugin {
+        const NAME: &'static str = "Presets";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        }];
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            Arc::new(EmptyParams)
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+    }
+
+    impl ClapPlugin for PresetPlugin {
+        const CLAP_ID: &'static str = "com.nih-plug.preset-test";
+        const CLAP_DESCRIPTION: Option<&'static str> = None;
+        const CLAP_MANUAL_URL: Option<&'static str> = None;
+        const CLAP_SUPPORT_URL: Option<&'static str> = None;
+        const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::AudioEffect];
+
+        const CLAP_PRESET_DISCOVERY_CONFIG: Option<PresetDiscoveryConfig> =
+            Some(PresetDiscoveryConfig {
+                file_extensions: &[PRESET_FILE_EXTENSION],
+                embedded_presets: &[EMBEDDED_PRESET],
+                factory_directories: || vec![factory_directory()],
+                include_user_directory: false,
+            });
+    }
+
+    /// The same as [`PresetPlugin`], but without any presets.
+    #[derive(Default)]
+    struct NoPresetsPlugin;
+
+    impl Plugin for NoPresetsPlugin {
+        const NAME: &'static str = "No Presets";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = PresetPlugin::AUDIO_IO_LAYOUTS;
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            Arc::new(EmptyParams)
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+    }
+
+    impl ClapPlugin for NoPresetsPlugin {
+        const CLAP_ID: &'static str = "com.nih-plug.no-presets-test";
+        const CLAP_DESCRIPTION: Option<&'static str> = None;
+        const CLAP_MANUAL_URL: Option<&'static str> = None;
+        const CLAP_SUPPORT_URL: Option<&'static str> = None;
+        const CLAP_FEATURES: &'static [ClapFeature] = &[ClapFeature::AudioEffect];
+    }
+
+    fn factory_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("nih-plug-preset-discovery-{}", std::process::id()))
+    }
+
+    /// Everything the provider passed to the indexer and metadata receiver from
+    /// [`indexer()`] and [`receiver()`].
+    #[derive(Debug, Default)]
+    struct Recorded {
+        file_extensions: Vec<String>,
+        /// The location's kind, name, path, and flags.
+        locations: Vec<(
+            clap_preset_discovery_location_kind,
+            String,
+            Option<String>,
+            u32,
+        )>,
+        presets: Vec<RecordedPreset>,
+        errors: Vec<String>,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct RecordedPreset {
+        name: String,
+        load_key: Option<String>,
+        plugin_ids: Vec<String>,
+        creators: Vec<String>,
+        description: Option<String>,
+        features: Vec<String>,
+    }
+
+    unsafe fn string(ptr: *const c_char) -> Option<String> {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(ptr).to_str().unwrap().to_owned())
+        }
+    }
+
+    /// An indexer that stores the declared file types and locations in `recorded`. The fields
+    /// differ between CLAP versions, so the unused callbacks are left as null pointers.
+    fn indexer(recorded: &RefCell<Recorded>) -> clap_preset_discovery_indexer {
+        unsafe extern "C" fn declare_filetype(
+            indexer: *const clap_preset_discovery_indexer,
+            filetype: *const clap_preset_discovery_filetype,
+        ) -> bool {
+            let recorded = &*((*indexer).indexer_data as *const RefCell<Recorded>);
+            recorded
+                .borrow_mut()
+                .file_extensions
+                .push(string((*filetype).file_extension).unwrap());
+            true
+        }
+
+        unsafe extern "C" fn declare_location(
+            indexer: *const clap_preset_discovery_indexer,
+            location: *const clap_preset_discovery_location,
+        ) -> bool {
+            let recorded = &*((*indexer).indexer_data as *const RefCell<Recorded>);
+            let location = &*location;
+            recorded.borrow_mut().locations.push((
+                location.kind,
+                string(location.name).unwrap(),
+                string(location.location),
+                location.flags,
+            ));
+            true
+        }
+
+        let mut indexer: clap_preset_discovery_indexer = unsafe { std::mem::zeroed() };
+        indexer.clap_version = CLAP_VERSION;
+        indexer.indexer_data = recorded as *const RefCell<Recorded> as *mut c_void;
+        indexer.declare_filetype = Some(declare_filetype);
+        indexer.declare_location = Some(declare_location);
+
+        indexer
+    }
+
+    /// A metadata receiver that stores the declared presets and errors in `recorded`.
+    fn receiver(recorded: &RefCell<Recorded>) -> clap_preset_discovery_metadata_receiver {
+        unsafe fn receiver_recorded<'a>(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+        ) -> &'a RefCell<Recorded> {
+            &*((*receiver).receiver_data as *const RefCell<Recorded>)
+        }
+
+        unsafe fn with_current_preset(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            f: impl FnOnce(&mut RecordedPreset),
+        ) {
+            f(receiver_recorded(receiver)
+                .borrow_mut()
+                .presets
+                .last_mut()
+                .expect("Metadata was declared outside of a preset"))
+        }
+
+        unsafe extern "C" fn on_error(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            _os_error: i32,
+            error_message: *const c_char,
+        ) {
+            receiver_recorded(receiver)
+                .borrow_mut()
+                .errors
+                .push(string(error_message).unwrap());
+        }
+
+        unsafe extern "C" fn begin_preset(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            name: *const c_char,
+            load_key: *const c_char,
+        ) -> bool {
+            receiver_recorded(receiver)
+                .borrow_mut()
+                .presets
+                .push(RecordedPreset {
+                    name: string(name).unwrap(),
+                    load_key: string(load_key),
+                    ..RecordedPreset::default()
+                });
+            true
+        }
+
+        unsafe extern "C" fn add_plugin_id(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            plugin_id: *const clap_plugin_id,
+        ) {
+            let plugin_id = &*plugin_id;
+            assert_eq!(string(plugin_id.abi).as_deref(), Some("clap"));
+            with_current_preset(receiver, |preset| {
+                preset.plugin_ids.push(string(plugin_id.id).unwrap())
+            });
+        }
+
+        unsafe extern "C" fn add_creator(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            creator: *const c_char,
+        ) {
+            with_current_preset(receiver, |preset| {
+                preset.creators.push(string(creator).unwrap())
+            });
+        }
+
+        unsafe extern "C" fn set_description(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            description: *const c_char,
+        ) {
+            with_current_preset(receiver, |preset| preset.description = string(description));
+        }
+
+        unsafe extern "C" fn add_feature(
+            receiver: *const clap_preset_discovery_metadata_receiver,
+            feature: *const c_char,
+        ) {
+            with_current_preset(receiver, |preset| {
+                preset.features.push(string(feature).unwrap())
+            });
+        }
+
+        let mut receiver: clap_preset_discovery_metadata_receiver = unsafe { std::mem::zeroed() };
+        receiver.receiver_data = recorded as *const RefCell<Recorded> as *mut c_void;
+        receiver.on_error = Some(on_error);
+        receiver.begin_preset = Some(begin_preset);
+        receiver.add_plugin_id = Some(add_plugin_id);
+        receiver.add_creator = Some(add_creator);
+        receiver.set_description = Some(set_description);
+        receiver.add_feature = Some(add_feature);
+
+        receiver
+    }
+
+    fn init_preset() -> RecordedPreset {
+        RecordedPreset {
+            name: String::from("Init"),
+            load_key: Some(String::from("0")),
+            plugin_ids: vec![String::from(PresetPlugin::CLAP_ID)],
+            creators: vec![String::from("NIH-plug")],
+            description: Some(String::from("The default sound")),
+            features: vec![String::from("Bass")],
+        }
+    }
+
+    #[test]
+    fn provider_descriptor() {
+        assert!(PresetDiscoveryProviderDescriptor::for_plugin::<NoPresetsPlugin>().is_none());
+
+        let descriptor = PresetDiscoveryProviderDescriptor::for_plugin::<PresetPlugin>().unwrap();
+        assert_eq!(
+            descriptor.id().to_str(),
+            Ok("com.nih-plug.preset-test.presets")
+        );
+
+        let clap_descriptor = descriptor.clap_provider_descriptor();
+        assert_eq!(clap_descriptor.id, descriptor.id().as_ptr());
+        assert_eq!(
+            unsafe { string(clap_descriptor.name) }.as_deref(),
+            Some("Presets presets")
+        );
+        assert_eq!(
+            unsafe { string(clap_descriptor.vendor) }.as_deref(),
+            Some("NIH-plug")
+        );
+    }
+
+    #[test]
+    fn index_presets() {
+        let recorded = RefCell::new(Recorded::default());
+        let descriptor = PresetDiscoveryProviderDescriptor::for_plugin::<PresetPlugin>().unwrap();
+        let indexer = indexer(&recorded);
+
+        unsafe {
+            let provider = PresetDiscoveryProvider::<PresetPlugin>::create(&descriptor, &indexer);
+            assert!(clap_call! { provider=>init(provider) });
+            assert_eq!(recorded.borrow().file_extensions, [PRESET_FILE_EXTENSION]);
+            assert_eq!(
+                recorded.borrow().locations,
+                [
+                    (
+                        CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+                        String::from("Built-in presets"),
+                        None,
+                        CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT
+                    ),
+                    (
+                        CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                        String::from("Factory presets"),
+                        factory_directory().to_str().map(String::from),
+                        CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT
+                    ),
+                ]
+            );
+
+            // Embedded presets use their index as the load key
+            let receiver = receiver(&recorded);
+            assert!(clap_call! { provider=>get_metadata(
+                provider,
+                CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+                std::ptr::null(),
+                &receiver,
+            ) });
+            assert_eq!(recorded.borrow().presets, [init_preset()]);
+
+            clap_call! { provider=>destroy(provider) };
+        }
+    }
+
+    #[test]
+    fn index_preset_files() {
+        let directory = factory_directory();
+        fs::create_dir_all(&directory).unwrap();
+        let preset_path = directory.join(format!("Init.{PRESET_FILE_EXTENSION}"));
+        let invalid_path = directory.join(format!("Invalid.{PRESET_FILE_EXTENSION}"));
+        Preset::from_bytes(EMBEDDED_PRESET)
+            .unwrap()
+            .save(&preset_path)
+            .unwrap();
+        fs::write(&invalid_path, b"not a preset").unwrap();
+
+        let recorded = RefCell::new(Recorded::default());
+        let descriptor = PresetDiscoveryProviderDescriptor::for_plugin::<PresetPlugin>().unwrap();
+        let indexer = indexer(&recorded);
+        let receiver = receiver(&recorded);
+
+        unsafe {
+            let provider = PresetDiscoveryProvider::<PresetPlugin>::create(&descriptor, &indexer);
+            assert!(clap_call! { provider=>init(provider) });
+
+            // Preset files don't need a load key
+            let location = CString::new(preset_path.to_str().unwrap()).unwrap();
+            assert!(clap_call! { provider=>get_metadata(
+                provider,
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                location.as_ptr(),
+                &receiver,
+            ) });
+            assert_eq!(
+                recorded.borrow().presets,
+                [RecordedPreset {
+                    load_key: None,
+                    ..init_preset()
+                }]
+            );
+
+            // Files that can't be parsed are reported to the host
+            let location = CString::new(invalid_path.to_str().unwrap()).unwrap();
+            assert!(!clap_call! { provider=>get_metadata(
+                provider,
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                location.as_ptr(),
+                &receiver,
+            ) });
+            assert_eq!(recorded.borrow().presets.len(), 1);
+            assert_eq!(recorded.borrow().errors.len(), 1);
+
+            clap_call! { provider=>destroy(provider) };
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn read_embedded_preset() {
+        let read = |load_key: Option<&str>| {
+            let load_key = load_key.map(|load_key| CString::new(load_key).unwrap());
+            unsafe {
+                read_preset::<PresetPlugin>(
+                    CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+                    std::ptr::null(),
+                    load_key
+                        .as_ref()
+                        .map_or(std::ptr::null(), |load_key| load_key.as_ptr()),
+                )
+            }
+        };
+
+        assert_eq!(read(Some("0")).unwrap().metadata.name, "Init");
+        assert!(read(Some("1")).is_err());
+        assert!(read(Some("Init")).is_err());
+        assert!(read(None).is_err());
+    }
+
+    #[test]
+    fn read_preset_errors() {
+        unsafe {
+            // Plugins without a preset discovery config can't load any presets
+            assert!(read_preset::<NoPresetsPlugin>(
+                CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+                std::ptr::null(),
+                b"0\0".as_ptr() as *const c_char,
+            )
+            .is_err());
+
+            let missing_path = CString::new(
+                factory_directory()
+                    .join(format!("Missing.{PRESET_FILE_EXTENSION}"))
+                    .to_str()
+                    .unwrap(),
+            )
+            .unwrap();
+            assert!(read_preset::<PresetPlugin>(
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                missing_path.as_ptr(),
+                std::ptr::null(),
+            )
+            .is_err());
+            assert!(read_preset::<PresetPlugin>(
+                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+            .is_err());
+            assert!(read_preset::<PresetPlugin>(1234, std::ptr::null(), std::ptr::null()).is_err());
+        }
+    }
+}
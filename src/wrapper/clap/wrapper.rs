@@ -1,19 +1,17 @@
 use atomic_float::AtomicF32;
 use atomic_refcell::{AtomicRefCell, AtomicRefMut};
+use clap_sys::color::clap_color;
 use clap_sys::events::{
-    clap_event_header, clap_event_midi, clap_event_midi_sysex, clap_event_note,
+    clap_event_header, clap_event_midi, clap_event_midi2, clap_event_midi_sysex, clap_event_note,
     clap_event_note_expression, clap_event_param_gesture, clap_event_param_mod,
     clap_event_param_value, clap_event_transport, clap_input_events, clap_output_events,
-    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_IS_LIVE, CLAP_EVENT_MIDI, CLAP_EVENT_MIDI_SYSEX,
-    CLAP_EVENT_NOTE_CHOKE, CLAP_EVENT_NOTE_END, CLAP_EVENT_NOTE_EXPRESSION, CLAP_EVENT_NOTE_OFF,
-    CLAP_EVENT_NOTE_ON, CLAP_EVENT_PARAM_GESTURE_BEGIN, CLAP_EVENT_PARAM_GESTURE_END,
-    CLAP_EVENT_PARAM_MOD, CLAP_EVENT_PARAM_VALUE, CLAP_EVENT_TRANSPORT,
-    CLAP_NOTE_EXPRESSION_BRIGHTNESS, CLAP_NOTE_EXPRESSION_EXPRESSION, CLAP_NOTE_EXPRESSION_PAN,
-    CLAP_NOTE_EXPRESSION_PRESSURE, CLAP_NOTE_EXPRESSION_TUNING, CLAP_NOTE_EXPRESSION_VIBRATO,
-    CLAP_NOTE_EXPRESSION_VOLUME, CLAP_TRANSPORT_HAS_BEATS_TIMELINE,
-    CLAP_TRANSPORT_HAS_SECONDS_TIMELINE, CLAP_TRANSPORT_HAS_TEMPO,
-    CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_LOOP_ACTIVE, CLAP_TRANSPORT_IS_PLAYING,
-    CLAP_TRANSPORT_IS_RECORDING, CLAP_TRANSPORT_IS_WITHIN_PRE_ROLL,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_IS_LIVE, CLAP_EVENT_MIDI, CLAP_EVENT_MIDI2,
+    CLAP_EVENT_MIDI_SYSEX, CLAP_EVENT_NOTE_CHOKE, CLAP_EVENT_NOTE_END, CLAP_EVENT_NOTE_EXPRESSION,
+    CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON, CLAP_EVENT_PARAM_GESTURE_BEGIN,
+    CLAP_EVENT_PARAM_GESTURE_END, CLAP_EVENT_PARAM_MOD, CLAP_EVENT_PARAM_VALUE,
+    CLAP_EVENT_TRANSPORT, CLAP_TRANSPORT_HAS_BEATS_TIMELINE, CLAP_TRANSPORT_HAS_SECONDS_TIMELINE,
+    CLAP_TRANSPORT_HAS_TEMPO, CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_LOOP_ACTIVE,
+    CLAP_TRANSPORT_IS_PLAYING, CLAP_TRANSPORT_IS_RECORDING, CLAP_TRANSPORT_IS_WITHIN_PRE_ROLL,
 };
 use clap_sys::ext::audio_ports::{
     clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS,
@@ -22,23 +20,67 @@ use clap_sys::ext::audio_ports::{
 use clap_sys::ext::audio_ports_config::{
     clap_audio_ports_config, clap_plugin_audio_ports_config, CLAP_EXT_AUDIO_PORTS_CONFIG,
 };
+use clap_sys::ext::draft::ambisonic::{
+    clap_ambisonic_config, clap_ambisonic_normalization, clap_ambisonic_ordering,
+    clap_plugin_ambisonic, CLAP_AMBISONIC_NORMALIZATION_MAXN, CLAP_AMBISONIC_NORMALIZATION_N2D,
+    CLAP_AMBISONIC_NORMALIZATION_N3D, CLAP_AMBISONIC_NORMALIZATION_SN2D,
+    CLAP_AMBISONIC_NORMALIZATION_SN3D, CLAP_AMBISONIC_ORDERING_ACN, CLAP_AMBISONIC_ORDERING_FUMA,
+    CLAP_EXT_AMBISONIC, CLAP_PORT_AMBISONIC,
+};
+use clap_sys::ext::draft::audio_ports_activation::{
+    clap_plugin_audio_ports_activation, CLAP_EXT_AUDIO_PORTS_ACTIVATION,
+};
+use clap_sys::ext::draft::configurable_audio_ports::{
+    clap_audio_port_configuration_request, clap_plugin_configurable_audio_ports,
+    CLAP_EXT_CONFIGURABLE_AUDIO_PORTS,
+};
+use clap_sys::ext::draft::context_menu::{
+    clap_context_menu_builder, clap_context_menu_check_entry, clap_context_menu_entry,
+    clap_context_menu_item_kind, clap_context_menu_item_title, clap_context_menu_submenu,
+    clap_context_menu_target, clap_host_context_menu, CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU,
+    CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY, CLAP_CONTEXT_MENU_ITEM_END_SUBMENU,
+    CLAP_CONTEXT_MENU_ITEM_ENTRY, CLAP_CONTEXT_MENU_ITEM_SEPARATOR, CLAP_CONTEXT_MENU_ITEM_TITLE,
+    CLAP_CONTEXT_MENU_TARGET_KIND_PARAM, CLAP_EXT_CONTEXT_MENU,
+};
+use clap_sys::ext::draft::param_indication::{
+    clap_plugin_param_indication, CLAP_EXT_PARAM_INDICATION,
+    CLAP_PARAM_INDICATION_AUTOMATION_OVERRIDING, CLAP_PARAM_INDICATION_AUTOMATION_PLAYING,
+    CLAP_PARAM_INDICATION_AUTOMATION_PRESENT, CLAP_PARAM_INDICATION_AUTOMATION_RECORDING,
+};
+use clap_sys::ext::draft::preset_load::{
+    clap_host_preset_load, clap_plugin_preset_load, CLAP_EXT_PRESET_LOAD,
+};
 use clap_sys::ext::draft::remote_controls::{
     clap_plugin_remote_controls, clap_remote_controls_page, CLAP_EXT_REMOTE_CONTROLS,
 };
+use clap_sys::ext::draft::surround::{clap_plugin_surround, CLAP_EXT_SURROUND, CLAP_PORT_SURROUND};
+use clap_sys::ext::draft::track_info::{
+    clap_host_track_info, clap_plugin_track_info, clap_track_info, CLAP_EXT_TRACK_INFO,
+    CLAP_TRACK_INFO_HAS_AUDIO_CHANNEL, CLAP_TRACK_INFO_HAS_TRACK_COLOR,
+    CLAP_TRACK_INFO_HAS_TRACK_NAME, CLAP_TRACK_INFO_IS_FOR_BUS, CLAP_TRACK_INFO_IS_FOR_MASTER,
+    CLAP_TRACK_INFO_IS_FOR_RETURN_TRACK,
+};
 use clap_sys::ext::gui::{
     clap_gui_resize_hints, clap_host_gui, clap_plugin_gui, clap_window, CLAP_EXT_GUI,
     CLAP_WINDOW_API_COCOA, CLAP_WINDOW_API_WIN32, CLAP_WINDOW_API_X11,
 };
 use clap_sys::ext::latency::{clap_host_latency, clap_plugin_latency, CLAP_EXT_LATENCY};
+use clap_sys::ext::note_name::{
+    clap_host_note_name, clap_note_name, clap_plugin_note_name, CLAP_EXT_NOTE_NAME,
+};
 use clap_sys::ext::note_ports::{
     clap_note_port_info, clap_plugin_note_ports, CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP,
-    CLAP_NOTE_DIALECT_MIDI,
+    CLAP_NOTE_DIALECT_MIDI, CLAP_NOTE_DIALECT_MIDI2, CLAP_NOTE_DIALECT_MIDI_MPE,
 };
 use clap_sys::ext::params::{
-    clap_host_params, clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS,
-    CLAP_PARAM_IS_AUTOMATABLE, CLAP_PARAM_IS_BYPASS, CLAP_PARAM_IS_HIDDEN,
-    CLAP_PARAM_IS_MODULATABLE, CLAP_PARAM_IS_MODULATABLE_PER_NOTE_ID, CLAP_PARAM_IS_READONLY,
-    CLAP_PARAM_IS_STEPPED, CLAP_PARAM_RESCAN_VALUES,
+    clap_host_params, clap_param_info, clap_param_info_flags, clap_plugin_params, CLAP_EXT_PARAMS,
+    CLAP_PARAM_IS_AUTOMATABLE, CLAP_PARAM_IS_AUTOMATABLE_PER_CHANNEL,
+    CLAP_PARAM_IS_AUTOMATABLE_PER_KEY, CLAP_PARAM_IS_AUTOMATABLE_PER_NOTE_ID,
+    CLAP_PARAM_IS_AUTOMATABLE_PER_PORT, CLAP_PARAM_IS_BYPASS, CLAP_PARAM_IS_HIDDEN,
+    CLAP_PARAM_IS_MODULATABLE, CLAP_PARAM_IS_MODULATABLE_PER_CHANNEL,
+    CLAP_PARAM_IS_MODULATABLE_PER_KEY, CLAP_PARAM_IS_MODULATABLE_PER_NOTE_ID,
+    CLAP_PARAM_IS_MODULATABLE_PER_PORT, CLAP_PARAM_IS_READONLY, CLAP_PARAM_IS_STEPPED,
+    CLAP_PARAM_RESCAN_VALUES,
 };
 use clap_sys::ext::render::{
     clap_plugin_render, clap_plugin_render_mode, CLAP_EXT_RENDER, CLAP_RENDER_OFFLINE,
@@ -51,6 +93,7 @@ use clap_sys::ext::voice_info::{
     clap_host_voice_info, clap_plugin_voice_info, clap_voice_info, CLAP_EXT_VOICE_INFO,
     CLAP_VOICE_INFO_SUPPORTS_OVERLAPPING_NOTES,
 };
+use clap_sys::factory::draft::preset_discovery::clap_preset_discovery_location_kind;
 use clap_sys::fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR};
 use clap_sys::host::clap_host;
 use clap_sys::id::{clap_id, CLAP_INVALID_ID};
@@ -60,14 +103,14 @@ use clap_sys::process::{
     CLAP_PROCESS_ERROR,
 };
 use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::string_sizes::CLAP_NAME_SIZE;
 use crossbeam::atomic::AtomicCell;
-use crossbeam::channel::{self, SendTimeoutError};
 use crossbeam::queue::ArrayQueue;
 use parking_lot::Mutex;
 use std::any::Any;
 use std::borrow::Borrow;
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::ffi::{c_void, CStr};
+use std::ffi::{c_void, CStr, CString};
 use std::mem;
 use std::num::NonZeroU32;
 use std::os::raw::c_char;
@@ -75,23 +118,33 @@ use std::ptr::NonNull;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Weak};
 use std::thread::{self, ThreadId};
-use std::time::Duration;
 
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
 use super::descriptor::PluginDescriptor;
+use super::note_expressions;
+use super::preset_discovery;
 use super::util::ClapPtr;
+use crate::audio_setup::AudioIOLayoutsValidator;
 use crate::event_loop::{BackgroundThread, EventLoop, MainThreadExecutor, TASK_QUEUE_CAPACITY};
+use crate::midi::mpe::MpeTranslator;
 use crate::midi::MidiResult;
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, AuxiliaryBuffers, BufferConfig, ClapPlugin, Editor, MidiConfig,
-    NoteEvent, ParamFlags, ParamPtr, Params, ParentWindowHandle, Plugin, PluginNoteEvent,
-    ProcessMode, ProcessStatus, SysExMessage, TaskExecutor, Transport,
+    AmbisonicNormalization, AmbisonicOrdering, AsyncExecutor, AudioIOLayout, AutomationState,
+    AuxiliaryBuffers, BufferConfig, ChannelLayout, ClapPlugin, ContextMenuItem, Editor, MidiConfig,
+    NoteEvent, ParamFlags, ParamIndication, ParamMapping, ParamPtr, Params, ParentWindowHandle,
+    Plugin, PluginNoteEvent, PolyModulationConfig, PolyModulationScope, ProcessMode, ProcessStatus,
+    SysExMessage, TaskExecutor, TrackColor, TrackInfo, Transport,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::clap::context::RemoteControlPages;
 use crate::wrapper::clap::util::{read_stream, write_stream};
+use crate::wrapper::state::prepared::{
+    handoff_timeout, HandoffResult, PreparedState, StateHandoff,
+};
 use crate::wrapper::state::{self, PluginState};
-use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+use crate::wrapper::util::audio_io_layouts::ProposedChannelCounts;
+use crate::wrapper::util::buffer_management::{AuxPortActivation, BufferManager, ChannelPointers};
+use crate::wrapper::util::note_names::NoteNameCache;
 use crate::wrapper::util::{
     clamp_input_event_timing, clamp_output_event_timing, hash_param_id, process_wrapper, strlcpy,
 };
@@ -129,6 +182,10 @@ pub struct Wrapper<P: ClapPlugin> {
     /// The current IO configuration, modified through the `clap_plugin_audio_ports_config`
     /// extension. Initialized to the plugin's first audio IO configuration.
     current_audio_io_layout: AtomicCell<AudioIOLayout>,
+    /// Which of the current audio IO layout's auxiliary ports the host has activated, modified
+    /// through the `clap_plugin_audio_ports_activation` extension. All ports are active by default,
+    /// and this is reset when the host selects a different audio IO layout.
+    aux_port_activation: AtomicRefCell<AuxPortActivation>,
     /// The current buffer configuration, containing the sample rate and the maximum block size.
     /// Will be set in `clap_plugin::activate()`.
     current_buffer_config: AtomicCell<Option<BufferConfig>>,
@@ -140,6 +197,9 @@ pub struct Wrapper<P: ClapPlugin> {
     /// TODO: Maybe load these lazily at some point instead of needing to spool them all to this
     ///       queue first
     input_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
+    /// Translates MIDI messages sent on MPE member channels to polyphonic expression events if
+    /// `P::MIDI_INPUT` is set to `MidiConfig::Mpe`.
+    mpe_translator: AtomicRefCell<MpeTranslator>,
     /// Stores any events the plugin has output during the current processing cycle, analogous to
     /// `input_events`.
     output_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
@@ -151,17 +211,12 @@ pub struct Wrapper<P: ClapPlugin> {
     /// A data structure that helps manage and create buffers for all of the plugin's inputs and
     /// outputs based on channel pointers provided by the host.
     buffer_manager: AtomicRefCell<BufferManager>,
-    /// The plugin is able to restore state through a method on the `GuiContext`. To avoid changing
-    /// parameters mid-processing and running into garbled data if the host also tries to load state
-    /// at the same time the restoring happens at the end of each processing call. If this zero
-    /// capacity channel contains state data at that point, then the audio thread will take the
-    /// state out of the channel, restore the state, and then send it back through the same channel.
-    /// In other words, the GUI thread acts as a sender and then as a receiver, while the audio
-    /// thread acts as a receiver and then as a sender. That way deallocation can happen on the GUI
-    /// thread. All of this happens without any blocking on the audio thread.
-    updated_state_sender: channel::Sender<PluginState>,
-    /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
-    updated_state_receiver: channel::Receiver<PluginState>,
+    /// State can be loaded by the host or through a method on the `GuiContext` while the plugin is
+    /// processing audio. To avoid changing parameters mid-processing and to avoid doing any
+    /// realtime-unsafe work on the audio thread, the state is prepared on the thread that's loading
+    /// it, and the prepared state is then applied at the end of the next processing call. See
+    /// [`set_state_inner()`][Self::set_state_inner()].
+    state_handoff: StateHandoff,
 
     // We'll query all of the host's extensions upfront
     host_callback: ClapPtr<clap_host>,
@@ -177,12 +232,41 @@ pub struct Wrapper<P: ClapPlugin> {
 
     clap_plugin_audio_ports: clap_plugin_audio_ports,
 
+    clap_plugin_audio_ports_activation: clap_plugin_audio_ports_activation,
+
+    clap_plugin_ambisonic: clap_plugin_ambisonic,
+
+    clap_plugin_configurable_audio_ports: clap_plugin_configurable_audio_ports,
+
+    clap_plugin_surround: clap_plugin_surround,
+
+    clap_plugin_track_info: clap_plugin_track_info,
+    host_track_info: AtomicRefCell<Option<ClapPtr<clap_host_track_info>>>,
+    /// The track information last reported by the host through the `track-info` extension.
+    track_info: Mutex<Option<TrackInfo>>,
+
     clap_plugin_gui: clap_plugin_gui,
     host_gui: AtomicRefCell<Option<ClapPtr<clap_host_gui>>>,
 
+    host_context_menu: AtomicRefCell<Option<ClapPtr<clap_host_context_menu>>>,
+
+    clap_plugin_param_indication: clap_plugin_param_indication,
+    /// The parameter indications set by the host, indexed by parameter hash. The host sets a
+    /// parameter's mapping and automation state separately, but the editor always receives the
+    /// complete indication.
+    param_indications: AtomicRefCell<HashMap<u32, ParamIndication>>,
+
     clap_plugin_latency: clap_plugin_latency,
     host_latency: AtomicRefCell<Option<ClapPtr<clap_host_latency>>>,
 
+    clap_plugin_note_name: clap_plugin_note_name,
+    host_note_name: AtomicRefCell<Option<ClapPtr<clap_host_note_name>>>,
+    /// The note names returned by `P::note_name()`. If the names have changed, then they're
+    /// collected again when the host calls
+    /// [`ext_note_name_count()`][Self::ext_note_name_count()] so the host can then query them by
+    /// index.
+    note_names: NoteNameCache,
+
     clap_plugin_note_ports: clap_plugin_note_ports,
 
     clap_plugin_params: clap_plugin_params,
@@ -225,6 +309,9 @@ pub struct Wrapper<P: ClapPlugin> {
 
     host_thread_check: AtomicRefCell<Option<ClapPtr<clap_host_thread_check>>>,
 
+    clap_plugin_preset_load: clap_plugin_preset_load,
+    host_preset_load: AtomicRefCell<Option<ClapPtr<clap_host_preset_load>>>,
+
     clap_plugin_remote_controls: clap_plugin_remote_controls,
     /// The plugin's remote control pages, if it defines any. Filled when initializing the plugin.
     remote_control_pages: Vec<clap_remote_controls_page>,
@@ -277,6 +364,8 @@ pub enum Task<P: Plugin> {
     LatencyChanged,
     /// Inform the host that the voice info has changed.
     VoiceInfoChanged,
+    /// Inform the host that the plugin's note names have changed.
+    NoteNamesChanged,
     /// Tell the host that it should rescan the current parameter values.
     RescanParamValues,
 }
@@ -412,6 +501,13 @@ impl<P: ClapPlugin> MainThreadExecutor<Task<P>> for Wrapper<P> {
                 }
                 None => nih_debug_assert_failure!("Host does not support the voice-info extension"),
             },
+            Task::NoteNamesChanged => match &*self.host_note_name.borrow() {
+                Some(host_note_name) => {
+                    nih_debug_assert!(is_gui_thread);
+                    unsafe_clap_call! { host_note_name=>changed(&*self.host_callback) };
+                }
+                None => nih_trace!("Host does not support the note-name extension"),
+            },
             Task::RescanParamValues => match &*self.host_params.borrow() {
                 Some(host_params) => {
                     nih_debug_assert!(is_gui_thread);
@@ -428,13 +524,11 @@ impl<P: ClapPlugin> Wrapper<P> {
     ///
     /// `host_callback` needs to outlive the returned object.
     pub unsafe fn new(host_callback: *const clap_host) -> Arc<Self> {
+        let () = AudioIOLayoutsValidator::<P>::ASSERT_VALID;
+
         let mut plugin = P::default();
         let task_executor = Mutex::new(plugin.task_executor());
 
-        // This is used to allow the plugin to restore preset data from its editor, see the comment
-        // on `Self::updated_state_sender`
-        let (updated_state_sender, updated_state_receiver) = channel::bounded(0);
-
         let plugin_descriptor: Box<PluginDescriptor> =
             Box::new(PluginDescriptor::for_plugin::<P>());
 
@@ -547,9 +641,13 @@ impl<P: ClapPlugin> Wrapper<P> {
             current_audio_io_layout: AtomicCell::new(
                 P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
             ),
+            aux_port_activation: AtomicRefCell::new(AuxPortActivation::for_audio_io_layout(
+                &P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
+            )),
             current_buffer_config: AtomicCell::new(None),
             current_process_mode: AtomicCell::new(ProcessMode::Realtime),
             input_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
+            mpe_translator: AtomicRefCell::new(MpeTranslator::default()),
             output_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
             last_process_status: AtomicCell::new(ProcessStatus::Normal),
             current_latency: AtomicU32::new(0),
@@ -559,8 +657,7 @@ impl<P: ClapPlugin> Wrapper<P> {
                 0,
                 AudioIOLayout::default(),
             )),
-            updated_state_sender,
-            updated_state_receiver,
+            state_handoff: StateHandoff::default(),
 
             host_callback,
 
@@ -596,6 +693,36 @@ impl<P: ClapPlugin> Wrapper<P> {
                 get: Some(Self::ext_audio_ports_get),
             },
 
+            clap_plugin_audio_ports_activation: clap_plugin_audio_ports_activation {
+                can_activate_while_processing: Some(
+                    Self::ext_audio_ports_activation_can_activate_while_processing,
+                ),
+                set_active: Some(Self::ext_audio_ports_activation_set_active),
+            },
+
+            clap_plugin_ambisonic: clap_plugin_ambisonic {
+                is_config_supported: Some(Self::ext_ambisonic_is_config_supported),
+                get_config: Some(Self::ext_ambisonic_get_config),
+            },
+
+            clap_plugin_configurable_audio_ports: clap_plugin_configurable_audio_ports {
+                can_apply_configuration: Some(
+                    Self::ext_configurable_audio_ports_can_apply_configuration,
+                ),
+                apply_configuration: Some(Self::ext_configurable_audio_ports_apply_configuration),
+            },
+
+            clap_plugin_surround: clap_plugin_surround {
+                is_channel_mask_supported: Some(Self::ext_surround_is_channel_mask_supported),
+                get_channel_map: Some(Self::ext_surround_get_channel_map),
+            },
+
+            clap_plugin_track_info: clap_plugin_track_info {
+                changed: Some(Self::ext_track_info_changed),
+            },
+            host_track_info: AtomicRefCell::new(None),
+            track_info: Mutex::new(None),
+
             clap_plugin_gui: clap_plugin_gui {
                 is_api_supported: Some(Self::ext_gui_is_api_supported),
                 get_preferred_api: Some(Self::ext_gui_get_preferred_api),
@@ -615,11 +742,26 @@ impl<P: ClapPlugin> Wrapper<P> {
             },
             host_gui: AtomicRefCell::new(None),
 
+            host_context_menu: AtomicRefCell::new(None),
+
+            clap_plugin_param_indication: clap_plugin_param_indication {
+                set_mapping: Some(Self::ext_param_indication_set_mapping),
+                set_automation: Some(Self::ext_param_indication_set_automation),
+            },
+            param_indications: AtomicRefCell::new(HashMap::new()),
+
             clap_plugin_latency: clap_plugin_latency {
                 get: Some(Self::ext_latency_get),
             },
             host_latency: AtomicRefCell::new(None),
 
+            clap_plugin_note_name: clap_plugin_note_name {
+                count: Some(Self::ext_note_name_count),
+                get: Some(Self::ext_note_name_get),
+            },
+            host_note_name: AtomicRefCell::new(None),
+            note_names: NoteNameCache::default(),
+
             clap_plugin_note_ports: clap_plugin_note_ports {
                 count: Some(Self::ext_note_ports_count),
                 get: Some(Self::ext_note_ports_get),
@@ -645,6 +787,11 @@ impl<P: ClapPlugin> Wrapper<P> {
 
             host_thread_check: AtomicRefCell::new(None),
 
+            clap_plugin_preset_load: clap_plugin_preset_load {
+                from_location: Some(Self::ext_preset_load_from_location),
+            },
+            host_preset_load: AtomicRefCell::new(None),
+
             clap_plugin_remote_controls: clap_plugin_remote_controls {
                 count: Some(Self::ext_remote_controls_count),
                 get: Some(Self::ext_remote_controls_get),
@@ -755,6 +902,72 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    /// Get the audio IO layout that results from applying a set of `configurable-audio-ports`
+    /// requests to the current layout. A matching layout from `P::AUDIO_IO_LAYOUTS` is preferred.
+    /// If there isn't one, then the plugin gets to decide whether it can use the requested channel
+    /// counts. Returns `None` if the requests cannot be applied.
+    ///
+    /// # Safety
+    ///
+    /// `requests` needs to point to `request_count` configuration requests.
+    unsafe fn audio_io_layout_for_requests(
+        &self,
+        requests: *const clap_audio_port_configuration_request,
+        request_count: u32,
+    ) -> Option<AudioIOLayout> {
+        let current_audio_io_layout = self.current_audio_io_layout.load();
+        if request_count == 0 {
+            return Some(current_audio_io_layout);
+        }
+        if requests.is_null() {
+            return None;
+        }
+        let requests = std::slice::from_raw_parts(requests, request_count as usize);
+
+        let has_main_input = current_audio_io_layout.main_input_channels.is_some();
+        let has_main_output = current_audio_io_layout.main_output_channels.is_some();
+        let mut proposed = ProposedChannelCounts::for_audio_io_layout(&current_audio_io_layout);
+        for request in requests {
+            // Ports cannot be removed, and the indices work the same as in `ext_audio_ports_get()`
+            let num_channels = NonZeroU32::new(request.channel_count)?;
+            let port_channels = match (request.is_input, request.port_index as usize) {
+                (true, 0) if has_main_input => proposed.main_input.as_mut()?,
+                (false, 0) if has_main_output => proposed.main_output.as_mut()?,
+                (true, n) if has_main_input => proposed.aux_inputs.get_mut(n - 1)?,
+                (false, n) if has_main_output => proposed.aux_outputs.get_mut(n - 1)?,
+                (true, n) => proposed.aux_inputs.get_mut(n)?,
+                (false, n) => proposed.aux_outputs.get_mut(n)?,
+            };
+            *port_channels = num_channels;
+        }
+
+        // The host can also request specific port types. Those only match if the port has the same
+        // channel layout.
+        let port_types_match = |audio_io_layout: &AudioIOLayout| {
+            requests.iter().all(|request| {
+                port_type_matches(
+                    port_channel_layout(audio_io_layout, request.is_input, request.port_index),
+                    request.port_type,
+                    request.port_details,
+                )
+            })
+        };
+
+        if let Some(audio_io_layout) = P::AUDIO_IO_LAYOUTS.iter().find(|audio_io_layout| {
+            proposed.matches(audio_io_layout) && port_types_match(audio_io_layout)
+        }) {
+            return Some(*audio_io_layout);
+        }
+
+        let audio_io_layout =
+            proposed.accept_runtime_layout(&*self.plugin.lock(), &current_audio_io_layout)?;
+        if port_types_match(&audio_io_layout) {
+            Some(audio_io_layout)
+        } else {
+            None
+        }
+    }
+
     /// Get a parameter's ID based on a `ParamPtr`. Used in the `GuiContext` implementation for the
     /// gesture checks.
     #[allow(unused)]
@@ -1107,8 +1320,8 @@ impl<P: ClapPlugin> Wrapper<P> {
                         },
                         note_id: voice_id.unwrap_or(-1),
                         port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
+                        channel: channel.map_or(-1, |channel| channel as i16),
+                        key: note.map_or(-1, |note| note as i16),
                         velocity: velocity as f64,
                     };
 
@@ -1139,177 +1352,18 @@ impl<P: ClapPlugin> Wrapper<P> {
 
                     clap_call! { out=>try_push(out, &event.header) }
                 }
-                NoteEvent::PolyPressure {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    pressure,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_PRESSURE,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: pressure as f64,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyVolume {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    gain,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_VOLUME,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: gain as f64,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyPan {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    pan,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_PAN,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: (pan as f64 + 1.0) / 2.0,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyTuning {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    tuning,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_TUNING,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: tuning as f64,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyVibrato {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    vibrato,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_VIBRATO,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: vibrato as f64,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyExpression {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    expression,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_EXPRESSION,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: expression as f64,
-                    };
-
-                    clap_call! { out=>try_push(out, &event.header) }
-                }
-                NoteEvent::PolyBrightness {
-                    timing: _,
-                    voice_id,
-                    channel,
-                    note,
-                    brightness,
-                } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
-                    let event = clap_event_note_expression {
-                        header: clap_event_header {
-                            size: mem::size_of::<clap_event_note_expression>() as u32,
-                            time,
-                            space_id: CLAP_CORE_EVENT_SPACE_ID,
-                            type_: CLAP_EVENT_NOTE_EXPRESSION,
-                            flags: 0,
-                        },
-                        expression_id: CLAP_NOTE_EXPRESSION_BRIGHTNESS,
-                        note_id: voice_id.unwrap_or(-1),
-                        port_index: 0,
-                        channel: channel as i16,
-                        key: note as i16,
-                        value: brightness as f64,
+                NoteEvent::PolyPressure { .. }
+                | NoteEvent::PolyVolume { .. }
+                | NoteEvent::PolyPan { .. }
+                | NoteEvent::PolyTuning { .. }
+                | NoteEvent::PolyVibrato { .. }
+                | NoteEvent::PolyExpression { .. }
+                | NoteEvent::PolyBrightness { .. }
+                    if P::MIDI_OUTPUT >= MidiConfig::Basic =>
+                {
+                    let event = match note_expressions::translate_event_reverse(time, &event) {
+                        Some(event) => event,
+                        None => unreachable!("Missing note expression conversion for note event"),
                     };
 
                     clap_call! { out=>try_push(out, &event.header) }
@@ -1414,6 +1468,44 @@ impl<P: ClapPlugin> Wrapper<P> {
         match (raw_event.space_id, raw_event.type_) {
             (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE) => {
                 let event = &*(event as *const clap_event_param_value);
+
+                // Automation targeted at specific notes, keys, channels, or ports only affects the
+                // matching voices, so the parameter's global value must not be changed
+                let event_scope = clap_event_poly_scope(
+                    event.note_id,
+                    event.port_index,
+                    event.channel,
+                    event.key,
+                );
+                if !event_scope.is_empty() && P::MIDI_INPUT >= MidiConfig::Basic {
+                    let automation_scope = P::CLAP_POLY_MODULATION_CONFIG
+                        .map(|config| config.automation_scope)
+                        .unwrap_or_default();
+                    match self.poly_mod_ids_by_hash.get(&event.param_id) {
+                        Some(poly_modulation_id) if automation_scope.contains(event_scope) => {
+                            let param_ptr = self.param_by_hash[&event.param_id];
+                            let normalized_value =
+                                event.value as f32 / param_ptr.step_count().unwrap_or(1) as f32;
+
+                            input_events.push_back(NoteEvent::PolyAutomation {
+                                timing,
+                                note_id: (event.note_id != -1).then_some(event.note_id),
+                                port_index: u16::try_from(event.port_index).ok(),
+                                channel: u8::try_from(event.channel).ok(),
+                                key: u8::try_from(event.key).ok(),
+                                poly_modulation_id: *poly_modulation_id,
+                                normalized_value,
+                            });
+
+                            return;
+                        }
+                        // Hosts may still send these events for other parameters, or use a
+                        // combination of properties the plugin did not opt into. Treating them as
+                        // regular automation is the closest we can get to the intended behavior.
+                        _ => (),
+                    }
+                }
+
                 self.update_plain_value_by_hash(
                     event.param_id,
                     ClapParamUpdate::PlainValueSet(event.value),
@@ -1441,7 +1533,14 @@ impl<P: ClapPlugin> Wrapper<P> {
             (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_MOD) => {
                 let event = &*(event as *const clap_event_param_mod);
 
-                if event.note_id != -1 && P::MIDI_INPUT >= MidiConfig::Basic {
+                let is_scoped = event.note_id != -1
+                    || event.port_index != -1
+                    || event.channel != -1
+                    || event.key != -1;
+                if is_scoped && P::MIDI_INPUT >= MidiConfig::Basic {
+                    let modulation_scope = P::CLAP_POLY_MODULATION_CONFIG
+                        .map(|config| config.modulation_scope)
+                        .unwrap_or(PolyModulationScope::NOTE_ID);
                     match self.poly_mod_ids_by_hash.get(&event.param_id) {
                         Some(poly_modulation_id) => {
                             // The modulation offset needs to be normalized to account for modulated
@@ -1450,22 +1549,45 @@ impl<P: ClapPlugin> Wrapper<P> {
                             let normalized_offset =
                                 event.amount as f32 / param_ptr.step_count().unwrap_or(1) as f32;
 
-                            // The host may also add key and channel information here, but it may
-                            // also pass -1. So not having that information here at all seems like
-                            // the safest choice.
-                            input_events.push_back(NoteEvent::PolyModulation {
-                                timing,
-                                voice_id: event.note_id,
-                                poly_modulation_id: *poly_modulation_id,
-                                normalized_offset,
-                            });
-
-                            return;
+                            // Modulation for a single voice is sent as a regular `PolyModulation`
+                            // event. The host may also add key and channel information there, but
+                            // it may also pass -1, so that information is not included. Plugins
+                            // that opt into key, channel, or port scoped modulation receive the
+                            // other events with all of the host's information intact.
+                            if event.note_id != -1
+                                && modulation_scope.contains(PolyModulationScope::NOTE_ID)
+                            {
+                                input_events.push_back(NoteEvent::PolyModulation {
+                                    timing,
+                                    voice_id: event.note_id,
+                                    poly_modulation_id: *poly_modulation_id,
+                                    normalized_offset,
+                                });
+
+                                return;
+                            } else if modulation_scope.intersects(
+                                PolyModulationScope::KEY
+                                    | PolyModulationScope::CHANNEL
+                                    | PolyModulationScope::PORT,
+                            ) {
+                                input_events.push_back(NoteEvent::ScopedPolyModulation {
+                                    timing,
+                                    note_id: (event.note_id != -1).then_some(event.note_id),
+                                    port_index: u16::try_from(event.port_index).ok(),
+                                    channel: u8::try_from(event.channel).ok(),
+                                    key: u8::try_from(event.key).ok(),
+                                    poly_modulation_id: *poly_modulation_id,
+                                    normalized_offset,
+                                });
+
+                                return;
+                            }
                         }
-                        None => nih_debug_assert_failure!(
+                        None if event.note_id != -1 => nih_debug_assert_failure!(
                             "Polyphonic modulation sent for a parameter without a poly modulation \
                              ID"
                         ),
+                        None => (),
                     }
                 }
 
@@ -1509,8 +1631,8 @@ impl<P: ClapPlugin> Wrapper<P> {
                         } else {
                             None
                         },
-                        channel: event.channel as u8,
-                        note: event.key as u8,
+                        channel: u8::try_from(event.channel).ok(),
+                        note: u8::try_from(event.key).ok(),
                         velocity: event.velocity as f32,
                     });
                 }
@@ -1525,134 +1647,40 @@ impl<P: ClapPlugin> Wrapper<P> {
                         } else {
                             None
                         },
-                        // FIXME: These values are also allowed to be -1, we need to support that
-                        channel: event.channel as u8,
-                        note: event.key as u8,
+                        channel: u8::try_from(event.channel).ok(),
+                        note: u8::try_from(event.key).ok(),
                     });
                 }
             }
             (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_NOTE_EXPRESSION) => {
                 if P::MIDI_INPUT >= MidiConfig::Basic {
-                    // TODO: Add support for the other expression types
                     let event = &*(event as *const clap_event_note_expression);
-                    match event.expression_id {
-                        CLAP_NOTE_EXPRESSION_PRESSURE => {
-                            input_events.push_back(NoteEvent::PolyPressure {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                pressure: event.value as f32,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_VOLUME => {
-                            input_events.push_back(NoteEvent::PolyVolume {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                gain: event.value as f32,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_PAN => {
-                            input_events.push_back(NoteEvent::PolyPan {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                // In CLAP this value goes from [0, 1] instead of [-1, 1]
-                                pan: (event.value as f32 * 2.0) - 1.0,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_TUNING => {
-                            input_events.push_back(NoteEvent::PolyTuning {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                tuning: event.value as f32,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_VIBRATO => {
-                            input_events.push_back(NoteEvent::PolyVibrato {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                vibrato: event.value as f32,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_EXPRESSION => {
-                            input_events.push_back(NoteEvent::PolyExpression {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                expression: event.value as f32,
-                            });
-                        }
-                        CLAP_NOTE_EXPRESSION_BRIGHTNESS => {
-                            input_events.push_back(NoteEvent::PolyBrightness {
-                                timing,
-                                voice_id: if event.note_id != -1 {
-                                    Some(event.note_id)
-                                } else {
-                                    None
-                                },
-                                channel: event.channel as u8,
-                                note: event.key as u8,
-                                brightness: event.value as f32,
-                            });
-                        }
-                        n => nih_debug_assert_failure!("Unhandled note expression ID {}", n),
+                    match note_expressions::translate_event(timing, event) {
+                        Some(event) => input_events.push_back(event),
+                        None => nih_debug_assert_failure!(
+                            "Unhandled note expression ID {}",
+                            event.expression_id
+                        ),
                     }
                 }
             }
             (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI) => {
-                // In the Basic note port type, we'll still handle note on, note off, and polyphonic
-                // pressure events if the host sents us those. But we'll throw away any other MIDI
-                // messages to stay consistent with the VST3 wrapper.
                 let event = &*(event as *const clap_event_midi);
 
                 match NoteEvent::from_midi(timing, &event.data) {
-                    Ok(
-                        note_event @ (NoteEvent::NoteOn { .. }
-                        | NoteEvent::NoteOff { .. }
-                        | NoteEvent::PolyPressure { .. }),
-                    ) if P::MIDI_INPUT >= MidiConfig::Basic => {
-                        input_events.push_back(note_event);
-                    }
-                    Ok(note_event) if P::MIDI_INPUT >= MidiConfig::MidiCCs => {
-                        input_events.push_back(note_event);
-                    }
-                    Ok(_) => (),
+                    Ok(note_event) => self.handle_in_midi_event(note_event, input_events),
                     Err(n) => nih_debug_assert_failure!("Unhandled MIDI message type {}", n),
                 };
             }
+            (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI2) => {
+                let event = &*(event as *const clap_event_midi2);
+
+                // Unsupported MIDI 2.0 messages are not necessarily an error, so these are only
+                // traced by `NoteEvent::from_midi2`
+                if let Ok(note_event) = NoteEvent::from_midi2(timing, &event.data) {
+                    self.handle_in_midi_event(note_event, input_events);
+                }
+            }
             (CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI_SYSEX)
                 if P::MIDI_INPUT >= MidiConfig::Basic =>
             {
@@ -1676,6 +1704,39 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    /// Add a note event parsed from a MIDI or MIDI 2.0 message to the input events queue. Like
+    /// [`handle_in_event()`][Self::handle_in_event()] this needs to be passed the lock guard for
+    /// the input events.
+    fn handle_in_midi_event(
+        &self,
+        note_event: PluginNoteEvent<P>,
+        input_events: &mut AtomicRefMut<VecDeque<PluginNoteEvent<P>>>,
+    ) {
+        match note_event {
+            // With MPE the notes also need to pass through the translator so it knows which notes
+            // are playing on which member channels
+            note_event if P::MIDI_INPUT >= MidiConfig::Mpe => self
+                .mpe_translator
+                .borrow_mut()
+                .translate(note_event, |event| input_events.push_back(event)),
+            // In the Basic note port type, we'll still handle note on, note off, and polyphonic
+            // pressure events if the host sents us those. But we'll throw away any other MIDI
+            // messages to stay consistent with the VST3 wrapper.
+            note_event @ (NoteEvent::NoteOn { .. }
+            | NoteEvent::NoteOff { .. }
+            | NoteEvent::PolyPressure { .. }
+            | NoteEvent::PolyTuning { .. })
+                if P::MIDI_INPUT >= MidiConfig::Basic =>
+            {
+                input_events.push_back(note_event);
+            }
+            note_event if P::MIDI_INPUT >= MidiConfig::MidiCCs => {
+                input_events.push_back(note_event);
+            }
+            _ => (),
+        }
+    }
+
     /// Get the plugin's state object, may be called by the plugin's GUI as part of its own preset
     /// management. The wrapper doesn't use these functions and serializes and deserializes directly
     /// the JSON in the relevant plugin API methods instead.
@@ -1690,44 +1751,10 @@ impl<P: ClapPlugin> Wrapper<P> {
 
     /// Update the plugin's internal state, called by the plugin itself from the GUI thread. To
     /// prevent corrupting data and changing parameters during processing the actual state is only
-    /// updated at the end of the audio processing cycle.
+    /// updated at the end of the audio processing cycle. See
+    /// [`set_state_inner()`][Self::set_state_inner()].
     pub fn set_state_object_from_gui(&self, mut state: PluginState) {
-        // Use a loop and timeouts to handle the super rare edge case when this function gets called
-        // between a process call and the host disabling the plugin
-        loop {
-            if self.is_processing.load(Ordering::SeqCst) {
-                // If the plugin is currently processing audio, then we'll perform the restore
-                // operation at the end of the audio call. This involves sending the state to the
-                // audio thread, having the audio thread handle the state restore at the very end of
-                // the process function, and then sending the state back to this thread so it can be
-                // deallocated without blocking the audio thread.
-                match self
-                    .updated_state_sender
-                    .send_timeout(state, Duration::from_secs(1))
-                {
-                    Ok(_) => {
-                        // As mentioned above, the state object will be passed back to this thread
-                        // so we can deallocate it without blocking.
-                        let state = self.updated_state_receiver.recv();
-                        drop(state);
-                        break;
-                    }
-                    Err(SendTimeoutError::Timeout(value)) => {
-                        state = value;
-                        continue;
-                    }
-                    Err(SendTimeoutError::Disconnected(_)) => {
-                        nih_debug_assert_failure!("State update channel got disconnected");
-                        return;
-                    }
-                }
-            } else {
-                // Otherwise we'll set the state right here and now, since this function should be
-                // called from a GUI thread
-                self.set_state_inner(&mut state);
-                break;
-            }
-        }
+        self.set_state_inner(&mut state);
 
         // After the state has been updated, notify the host about the new parameter values
         let task_posted = self.schedule_gui(Task::RescanParamValues);
@@ -1745,6 +1772,118 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    pub fn note_names_changed(&self) {
+        self.note_names.invalidate();
+        let task_posted = self.schedule_gui(Task::NoteNamesChanged);
+        nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
+    }
+
+    pub fn track_info(&self) -> Option<TrackInfo> {
+        self.track_info.lock().clone()
+    }
+
+    /// Ask the host to show its context menu for a parameter. See
+    /// [`ParamSetter::show_context_menu()`][crate::prelude::ParamSetter::show_context_menu()].
+    pub fn show_param_context_menu(&self, param_hash: u32, x: i32, y: i32) -> bool {
+        match &*self.host_context_menu.borrow() {
+            Some(host_context_menu) => {
+                if !unsafe_clap_call! { host_context_menu=>can_popup(&*self.host_callback) } {
+                    return false;
+                }
+
+                // The coordinates are relative to the plugin's window since the editor is embedded
+                let target = param_context_menu_target(param_hash);
+                unsafe_clap_call! {
+                    host_context_menu=>popup(&*self.host_callback, &target, 0, x, y)
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// Get the items from the host's context menu for a parameter. See
+    /// [`ParamSetter::context_menu_items()`][crate::prelude::ParamSetter::context_menu_items()].
+    pub fn param_context_menu_items(&self, param_hash: u32) -> Vec<ContextMenuItem> {
+        match &*self.host_context_menu.borrow() {
+            Some(host_context_menu) => {
+                let target = param_context_menu_target(param_hash);
+                let mut collector = ContextMenuItemCollector::default();
+                let builder = clap_context_menu_builder {
+                    ctx: &mut collector as *mut ContextMenuItemCollector as *mut c_void,
+                    add_item: Some(ContextMenuItemCollector::add_item),
+                    supports: Some(ContextMenuItemCollector::supports),
+                };
+
+                if unsafe_clap_call! {
+                    host_context_menu=>populate(&*self.host_callback, &target, &builder)
+                } {
+                    collector.finish()
+                } else {
+                    Vec::new()
+                }
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Perform an action from the host's context menu for a parameter. See
+    /// [`ParamSetter::perform_context_menu_action()`][crate::prelude::ParamSetter::perform_context_menu_action()].
+    pub fn perform_param_context_menu_action(&self, param_hash: u32, action_id: u32) -> bool {
+        match &*self.host_context_menu.borrow() {
+            Some(host_context_menu) => {
+                let target = param_context_menu_target(param_hash);
+
+                unsafe_clap_call! {
+                    host_context_menu=>perform(&*self.host_callback, &target, action_id)
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// Update the stored indication for a parameter and pass the result on to the editor. Must be
+    /// called from the main thread.
+    fn update_param_indication(&self, param_hash: u32, update: impl FnOnce(&mut ParamIndication)) {
+        let param_id = match self.param_id_by_hash.get(&param_hash) {
+            Some(param_id) => param_id,
+            None => {
+                nih_debug_assert_failure!(
+                    "The host set a parameter indication for an unknown parameter: {}",
+                    param_hash
+                );
+                return;
+            }
+        };
+
+        let mut param_indications = self.param_indications.borrow_mut();
+        let indication = param_indications.entry(param_hash).or_default();
+        update(indication);
+
+        if let Some(editor) = self.editor.borrow().as_ref() {
+            editor.lock().param_indication_changed(param_id, indication);
+        }
+    }
+
+    /// Fetch the current track information from the host, store it, and pass it on to the plugin.
+    /// This is called when the plugin is initialized and whenever the host reports that the track
+    /// information has changed. Must be called from the main thread.
+    fn update_track_info(&self) {
+        let track_info = match &*self.host_track_info.borrow() {
+            Some(host_track_info) => {
+                let mut info: clap_track_info = unsafe { std::mem::zeroed() };
+                if !unsafe_clap_call! { host_track_info=>get(&*self.host_callback, &mut info) } {
+                    return;
+                }
+
+                track_info_from_clap(&info)
+            }
+            None => return,
+        };
+
+        *self.track_info.lock() = Some(track_info.clone());
+        self.plugin.lock().track_info_changed(&track_info);
+    }
+
     pub fn set_current_voice_capacity(&self, capacity: u32) {
         match P::CLAP_POLY_MODULATION_CONFIG {
             Some(config) => {
@@ -1769,54 +1908,49 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
-    /// Immediately set the plugin state. Returns `false` if the deserialization failed. The plugin
-    /// state is set from a couple places, so this function aims to deduplicate that. Includes
-    /// `permit_alloc()`s around the deserialization and initialization for the use case where
-    /// `set_state_object_from_gui()` was called while the plugin is process audio.
+    /// Set the plugin state. Returns `false` if the plugin failed to load the state. The plugin
+    /// state is set from a couple places, so this function aims to deduplicate that. The state is
+    /// migrated, resolved, and prepared on the calling thread. If the plugin is currently processing
+    /// audio and it prepared data for the state, then the prepared state is swapped in at the end of
+    /// the next processing call and this function blocks until that has happened. Otherwise the
+    /// state is applied immediately while holding the lock on the plugin.
     ///
     /// Implicitly emits `Task::ParameterValuesChanged`.
     ///
     /// # Notes
     ///
-    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
     pub fn set_state_inner(&self, state: &mut PluginState) -> bool {
         let audio_io_layout = self.current_audio_io_layout.load();
         let buffer_config = self.current_buffer_config.load();
 
-        // FIXME: This is obviously not realtime-safe, but loading presets without doing this could
-        //        lead to inconsistencies. It's the plugin's responsibility to not perform any
-        //        realtime-unsafe work when the initialize function is called a second time if it
-        //        supports runtime preset loading.  `state::deserialize_object()` normally never
-        //        allocates, but if the plugin has persistent non-parameter data then its
-        //        `deserialize_fields()` implementation may still allocate.
-        let mut success = permit_alloc(|| unsafe {
-            state::deserialize_object::<P>(
+        let mut prepared = Box::new(unsafe {
+            PreparedState::prepare::<P>(
                 state,
-                self.params.clone(),
                 state::make_params_getter(&self.param_by_hash, &self.param_id_to_hash),
-                self.current_buffer_config.load().as_ref(),
             )
         });
-        if !success {
-            nih_debug_assert_failure!("Deserializing plugin state from a state object failed");
-            return false;
+        if let Some(buffer_config) = &buffer_config {
+            prepared.set_plugin_data(P::prepare_state(state, &audio_io_layout, buffer_config));
         }
 
-        // If the plugin was already initialized then it needs to be reinitialized
-        if let Some(buffer_config) = buffer_config {
-            // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
-            let mut init_context = self.make_init_context();
-            let mut plugin = self.plugin.lock();
-
-            // See above
-            success = permit_alloc(|| {
-                plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
-            });
-            if success {
-                process_wrapper(|| plugin.reset());
+        let timeout = buffer_config
+            .as_ref()
+            .map(handoff_timeout)
+            .unwrap_or_default();
+        let success = match self
+            .state_handoff
+            .send_and_wait(prepared, &self.is_processing, timeout)
+        {
+            HandoffResult::Applied(prepared) => {
+                prepared.apply_fields(&*self.params);
+                true
             }
-        }
-
+            // The plugin is not processing audio, it didn't prepare anything, or it needs to be
+            // reinitialized, so the state is applied right here
+            HandoffResult::NotApplied(mut prepared) => self.apply_prepared_state(&mut prepared),
+        };
         nih_debug_assert!(
             success,
             "Plugin returned false when reinitializing after loading state"
@@ -1836,6 +1970,53 @@ impl<P: ClapPlugin> Wrapper<P> {
         success
     }
 
+    /// Apply state prepared in [`set_state_inner()`][Self::set_state_inner()] on the calling
+    /// thread. The plugin is locked while doing so, and it's reinitialized if it doesn't swap in the
+    /// prepared state.
+    ///
+    /// # Notes
+    ///
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
+    fn apply_prepared_state(&self, prepared: &mut PreparedState) -> bool {
+        let audio_io_layout = self.current_audio_io_layout.load();
+        let buffer_config = self.current_buffer_config.load();
+
+        // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
+        let mut init_context = self.make_init_context();
+        let mut plugin = self.plugin.lock();
+        unsafe {
+            prepared.apply_with_plugin_lock(
+                &mut *plugin,
+                &*self.params,
+                buffer_config.map(|c| c.sample_rate),
+                |plugin| {
+                    buffer_config.map_or(true, |buffer_config| {
+                        plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
+                    })
+                },
+            )
+        }
+    }
+
+    /// Swap in state prepared in [`set_state_inner()`][Self::set_state_inner()]. This is called at
+    /// the end of a processing call when state is loaded while the plugin is processing audio, and
+    /// it does not allocate. Returns `false` if the plugin needs to be reinitialized on the loading
+    /// thread instead.
+    ///
+    /// # Notes
+    ///
+    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    fn apply_prepared_state_on_audio_thread(&self, prepared: &mut PreparedState) -> bool {
+        let buffer_config = match self.current_buffer_config.load() {
+            Some(buffer_config) => buffer_config,
+            None => return false,
+        };
+
+        let mut plugin = self.plugin.lock();
+        unsafe { prepared.apply_on_audio_thread(&mut *plugin, buffer_config.sample_rate) }
+    }
+
     unsafe extern "C" fn init(plugin: *const clap_plugin) -> bool {
         check_null_ptr!(false, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
@@ -1843,8 +2024,14 @@ impl<P: ClapPlugin> Wrapper<P> {
         // We weren't allowed to query these in the constructor, so we need to do it now instead.
         *wrapper.host_gui.borrow_mut() =
             query_host_extension::<clap_host_gui>(&wrapper.host_callback, CLAP_EXT_GUI);
+        *wrapper.host_context_menu.borrow_mut() = query_host_extension::<clap_host_context_menu>(
+            &wrapper.host_callback,
+            CLAP_EXT_CONTEXT_MENU,
+        );
         *wrapper.host_latency.borrow_mut() =
             query_host_extension::<clap_host_latency>(&wrapper.host_callback, CLAP_EXT_LATENCY);
+        *wrapper.host_note_name.borrow_mut() =
+            query_host_extension::<clap_host_note_name>(&wrapper.host_callback, CLAP_EXT_NOTE_NAME);
         *wrapper.host_params.borrow_mut() =
             query_host_extension::<clap_host_params>(&wrapper.host_callback, CLAP_EXT_PARAMS);
         *wrapper.host_voice_info.borrow_mut() = query_host_extension::<clap_host_voice_info>(
@@ -1855,6 +2042,19 @@ impl<P: ClapPlugin> Wrapper<P> {
             &wrapper.host_callback,
             CLAP_EXT_THREAD_CHECK,
         );
+        if P::CLAP_PRESET_DISCOVERY_CONFIG.is_some() {
+            *wrapper.host_preset_load.borrow_mut() = query_host_extension::<clap_host_preset_load>(
+                &wrapper.host_callback,
+                CLAP_EXT_PRESET_LOAD,
+            );
+        }
+        *wrapper.host_track_info.borrow_mut() = query_host_extension::<clap_host_track_info>(
+            &wrapper.host_callback,
+            CLAP_EXT_TRACK_INFO,
+        );
+
+        // The host may already know which track the plugin has been inserted on
+        wrapper.update_track_info();
 
         true
     }
@@ -1929,6 +2129,7 @@ impl<P: ClapPlugin> Wrapper<P> {
 
         // To be consistent with the VST3 wrapper, we'll also reset the buffers here in addition to
         // the dedicated `reset()` function.
+        wrapper.mpe_translator.borrow_mut().reset();
         process_wrapper(|| wrapper.plugin.lock().reset());
 
         true
@@ -1945,6 +2146,7 @@ impl<P: ClapPlugin> Wrapper<P> {
         check_null_ptr!((), plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
 
+        wrapper.mpe_translator.borrow_mut().reset();
         process_wrapper(|| wrapper.plugin.lock().reset());
     }
 
@@ -2044,6 +2246,10 @@ impl<P: ClapPlugin> Wrapper<P> {
                 let mut buffer_manager = wrapper.buffer_manager.borrow_mut();
                 let buffers =
                     buffer_manager.create_buffers(block_start, block_len, |buffer_source| {
+                        // Ports the host has deactivated through the audio ports activation
+                        // extension are passed to the plugin as silent, inactive buffers
+                        wrapper.aux_port_activation.borrow().apply(buffer_source);
+
                         // Explicitly take plugins with no main output that does have auxiliary
                         // outputs into account. Shouldn't happen, but if we just start copying
                         // audio here then that would result in unsoundness.
@@ -2279,25 +2485,13 @@ impl<P: ClapPlugin> Wrapper<P> {
                 }
             };
 
-            // After processing audio, we'll check if the editor has sent us updated plugin state.
-            // We'll restore that here on the audio thread to prevent changing the values during the
-            // process call and also to prevent inconsistent state when the host also wants to load
-            // plugin state.
-            // FIXME: Zero capacity channels allocate on receiving, find a better alternative that
-            //        doesn't do that
-            let updated_state = permit_alloc(|| wrapper.updated_state_receiver.try_recv());
-            if let Ok(mut state) = updated_state {
-                wrapper.set_state_inner(&mut state);
-
-                // We'll pass the state object back to the GUI thread so deallocation can happen
-                // there without potentially blocking the audio thread
-                if let Err(err) = wrapper.updated_state_sender.send(state) {
-                    nih_debug_assert_failure!(
-                        "Failed to send state object back to GUI thread: {}",
-                        err
-                    );
-                };
-            }
+            // After processing audio, we'll check if the host or the editor has loaded new plugin
+            // state. The state has already been prepared on the thread that loaded it, so all
+            // that's left to do here is to swap it in. Doing this at the end of the process call
+            // prevents changing the values in the middle of processing.
+            wrapper
+                .state_handoff
+                .apply_pending(|prepared| wrapper.apply_prepared_state_on_audio_thread(prepared));
 
             result
         })
@@ -2316,23 +2510,50 @@ impl<P: ClapPlugin> Wrapper<P> {
             &wrapper.clap_plugin_audio_ports_config as *const _ as *const c_void
         } else if id == CLAP_EXT_AUDIO_PORTS {
             &wrapper.clap_plugin_audio_ports as *const _ as *const c_void
+        } else if id == CLAP_EXT_AUDIO_PORTS_ACTIVATION {
+            &wrapper.clap_plugin_audio_ports_activation as *const _ as *const c_void
+        } else if id == CLAP_EXT_AMBISONIC
+            && all_channel_layouts::<P>()
+                .any(|channel_layout| matches!(channel_layout, ChannelLayout::Ambisonic { .. }))
+        {
+            // Only report that we support this extension if the plugin has ambisonic ports
+            &wrapper.clap_plugin_ambisonic as *const _ as *const c_void
+        } else if id == CLAP_EXT_CONFIGURABLE_AUDIO_PORTS {
+            &wrapper.clap_plugin_configurable_audio_ports as *const _ as *const c_void
         } else if id == CLAP_EXT_GUI && wrapper.editor.borrow().is_some() {
             // Only report that we support this extension if the plugin has an editor
             &wrapper.clap_plugin_gui as *const _ as *const c_void
+        } else if id == CLAP_EXT_PARAM_INDICATION && wrapper.editor.borrow().is_some() {
+            // The indications are only used by the editor
+            &wrapper.clap_plugin_param_indication as *const _ as *const c_void
         } else if id == CLAP_EXT_LATENCY {
             &wrapper.clap_plugin_latency as *const _ as *const c_void
+        } else if id == CLAP_EXT_NOTE_NAME
+            && P::HAS_NOTE_NAMES
+            && P::MIDI_INPUT >= MidiConfig::Basic
+        {
+            &wrapper.clap_plugin_note_name as *const _ as *const c_void
         } else if id == CLAP_EXT_NOTE_PORTS
             && (P::MIDI_INPUT >= MidiConfig::Basic || P::MIDI_OUTPUT >= MidiConfig::Basic)
         {
             &wrapper.clap_plugin_note_ports as *const _ as *const c_void
         } else if id == CLAP_EXT_PARAMS {
             &wrapper.clap_plugin_params as *const _ as *const c_void
+        } else if id == CLAP_EXT_PRESET_LOAD && P::CLAP_PRESET_DISCOVERY_CONFIG.is_some() {
+            &wrapper.clap_plugin_preset_load as *const _ as *const c_void
         } else if id == CLAP_EXT_REMOTE_CONTROLS {
             &wrapper.clap_plugin_remote_controls as *const _ as *const c_void
         } else if id == CLAP_EXT_RENDER {
             &wrapper.clap_plugin_render as *const _ as *const c_void
         } else if id == CLAP_EXT_STATE {
             &wrapper.clap_plugin_state as *const _ as *const c_void
+        } else if id == CLAP_EXT_SURROUND
+            && all_channel_layouts::<P>()
+                .any(|channel_layout| matches!(channel_layout, ChannelLayout::Surround(_)))
+        {
+            &wrapper.clap_plugin_surround as *const _ as *const c_void
+        } else if id == CLAP_EXT_TRACK_INFO {
+            &wrapper.clap_plugin_track_info as *const _ as *const c_void
         } else if id == CLAP_EXT_TAIL {
             &wrapper.clap_plugin_tail as *const _ as *const c_void
         } else if id == CLAP_EXT_VOICE_INFO && P::CLAP_POLY_MODULATION_CONFIG.is_some() {
@@ -2376,16 +2597,8 @@ impl<P: ClapPlugin> Wrapper<P> {
                 let main_input_channels = audio_io_layout.main_input_channels.map(NonZeroU32::get);
                 let main_output_channels =
                     audio_io_layout.main_output_channels.map(NonZeroU32::get);
-                let input_port_type = match main_input_channels {
-                    Some(1) => CLAP_PORT_MONO.as_ptr(),
-                    Some(2) => CLAP_PORT_STEREO.as_ptr(),
-                    _ => std::ptr::null(),
-                };
-                let output_port_type = match main_output_channels {
-                    Some(1) => CLAP_PORT_MONO.as_ptr(),
-                    Some(2) => CLAP_PORT_STEREO.as_ptr(),
-                    _ => std::ptr::null(),
-                };
+                let input_port_type = clap_port_type(audio_io_layout.main_input_channel_layout());
+                let output_port_type = clap_port_type(audio_io_layout.main_output_channel_layout());
 
                 *config = std::mem::zeroed();
 
@@ -2429,6 +2642,8 @@ impl<P: ClapPlugin> Wrapper<P> {
         match P::AUDIO_IO_LAYOUTS.get(config_id as usize) {
             Some(audio_io_layout) => {
                 wrapper.current_audio_io_layout.store(*audio_io_layout);
+                *wrapper.aux_port_activation.borrow_mut() =
+                    AuxPortActivation::for_audio_io_layout(audio_io_layout);
 
                 true
             }
@@ -2531,11 +2746,11 @@ impl<P: ClapPlugin> Wrapper<P> {
             (n, false) => current_audio_io_layout.aux_output_ports[n as usize].get(),
         };
 
-        let port_type = match channel_count {
-            1 => CLAP_PORT_MONO.as_ptr(),
-            2 => CLAP_PORT_STEREO.as_ptr(),
-            _ => std::ptr::null(),
-        };
+        let port_type = clap_port_type(port_channel_layout(
+            &current_audio_io_layout,
+            is_input,
+            index,
+        ));
 
         *info = std::mem::zeroed();
 
@@ -2575,6 +2790,202 @@ impl<P: ClapPlugin> Wrapper<P> {
         true
     }
 
+    unsafe extern "C" fn ext_audio_ports_activation_can_activate_while_processing(
+        _plugin: *const clap_plugin,
+    ) -> bool {
+        // The activation flags are atomics, so the host can change them from the audio thread
+        // while the plugin is processing audio
+        true
+    }
+
+    unsafe extern "C" fn ext_audio_ports_activation_set_active(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        is_active: bool,
+        sample_size: u32,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // NIH-plug only supports 32-bit floating point audio. A zero sample size means that the
+        // host doesn't specify it.
+        if is_active && sample_size != 0 && sample_size != 32 {
+            return false;
+        }
+
+        let current_audio_io_layout = wrapper.current_audio_io_layout.load();
+        let has_main_port = if is_input {
+            current_audio_io_layout.main_input_channels.is_some()
+        } else {
+            current_audio_io_layout.main_output_channels.is_some()
+        };
+
+        // The main ports are always active. Like with `ext_audio_ports_get()`, `port_index` is off
+        // by one for the auxiliary ports if the plugin has a main port.
+        if has_main_port && port_index == 0 {
+            return is_active;
+        }
+        let aux_port_idx = if has_main_port {
+            port_index - 1
+        } else {
+            port_index
+        } as usize;
+
+        // This may be called while the audio thread is processing audio, so this only takes a
+        // shared borrow and stores the new state atomically
+        let aux_port_activation = wrapper.aux_port_activation.borrow();
+        let updated = if is_input {
+            aux_port_activation.set_input_active(aux_port_idx, is_active)
+        } else {
+            aux_port_activation.set_output_active(aux_port_idx, is_active)
+        };
+        if !updated {
+            nih_debug_assert_failure!(
+                "Host tried to set the activation state for out of bounds audio port {} (input: \
+                 {})",
+                port_index,
+                is_input
+            );
+        }
+
+        updated
+    }
+
+    unsafe extern "C" fn ext_ambisonic_is_config_supported(
+        plugin: *const clap_plugin,
+        config: *const clap_ambisonic_config,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, config);
+
+        // The audio IO layouts are fixed, so only the configurations used by one of the plugin's
+        // ambisonic ports are supported
+        let config = &*config;
+        all_channel_layouts::<P>().any(|channel_layout| match channel_layout {
+            ChannelLayout::Ambisonic {
+                ordering,
+                normalization,
+                ..
+            } => {
+                clap_ambisonic_ordering(ordering) == config.ordering
+                    && clap_ambisonic_normalization(normalization) == config.normalization
+            }
+            _ => false,
+        })
+    }
+
+    unsafe extern "C" fn ext_ambisonic_get_config(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        config: *mut clap_ambisonic_config,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, config);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let current_audio_io_layout = wrapper.current_audio_io_layout.load();
+        match port_channel_layout(&current_audio_io_layout, is_input, port_index) {
+            Some(ChannelLayout::Ambisonic {
+                ordering,
+                normalization,
+                ..
+            }) => {
+                *config = clap_ambisonic_config {
+                    ordering: clap_ambisonic_ordering(ordering),
+                    normalization: clap_ambisonic_normalization(normalization),
+                };
+
+                true
+            }
+            _ => false,
+        }
+    }
+
+    unsafe extern "C" fn ext_configurable_audio_ports_can_apply_configuration(
+        plugin: *const clap_plugin,
+        requests: *const clap_audio_port_configuration_request,
+        request_count: u32,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        wrapper
+            .audio_io_layout_for_requests(requests, request_count)
+            .is_some()
+    }
+
+    unsafe extern "C" fn ext_configurable_audio_ports_apply_configuration(
+        plugin: *const clap_plugin,
+        requests: *const clap_audio_port_configuration_request,
+        request_count: u32,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        match wrapper.audio_io_layout_for_requests(requests, request_count) {
+            Some(audio_io_layout) => {
+                wrapper.current_audio_io_layout.store(audio_io_layout);
+                *wrapper.aux_port_activation.borrow_mut() =
+                    AuxPortActivation::for_audio_io_layout(&audio_io_layout);
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    unsafe extern "C" fn ext_surround_is_channel_mask_supported(
+        plugin: *const clap_plugin,
+        channel_mask: u64,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+
+        // Like with the ambisonic extension, the speaker layouts are fixed
+        all_channel_layouts::<P>()
+            .any(|channel_layout| channel_layout.speaker_mask() == Some(channel_mask))
+    }
+
+    unsafe extern "C" fn ext_surround_get_channel_map(
+        plugin: *const clap_plugin,
+        is_input: bool,
+        port_index: u32,
+        channel_map: *mut u8,
+        channel_map_capacity: u32,
+    ) -> u32 {
+        check_null_ptr!(0, plugin, (*plugin).plugin_data, channel_map);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let current_audio_io_layout = wrapper.current_audio_io_layout.load();
+        match port_channel_layout(&current_audio_io_layout, is_input, port_index) {
+            Some(ChannelLayout::Surround(speakers)) => {
+                let num_speakers = speakers.len().min(channel_map_capacity as usize);
+                for (speaker_idx, speaker) in speakers.iter().take(num_speakers).enumerate() {
+                    // The discriminants match CLAP's speaker positions
+                    *channel_map.add(speaker_idx) = *speaker as u8;
+                }
+
+                num_speakers as u32
+            }
+            _ => {
+                nih_debug_assert_failure!(
+                    "Host tried to query the channel map for non-surround audio port {} (input: \
+                     {})",
+                    port_index,
+                    is_input
+                );
+
+                0
+            }
+        }
+    }
+
+    unsafe extern "C" fn ext_track_info_changed(plugin: *const clap_plugin) {
+        check_null_ptr!((), plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        wrapper.update_track_info();
+    }
+
     unsafe extern "C" fn ext_gui_is_api_supported(
         _plugin: *const clap_plugin,
         api: *const c_char,
@@ -2711,26 +3122,87 @@ impl<P: ClapPlugin> Wrapper<P> {
         true
     }
 
-    unsafe extern "C" fn ext_gui_can_resize(_plugin: *const clap_plugin) -> bool {
-        // TODO: Implement Host->Plugin GUI resizing
-        false
+    unsafe extern "C" fn ext_gui_can_resize(plugin: *const clap_plugin) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        wrapper
+            .editor
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .lock()
+            .resize_hints()
+            .is_some()
     }
 
     unsafe extern "C" fn ext_gui_get_resize_hints(
-        _plugin: *const clap_plugin,
-        _hints: *mut clap_gui_resize_hints,
+        plugin: *const clap_plugin,
+        hints: *mut clap_gui_resize_hints,
     ) -> bool {
-        // TODO: Implement Host->Plugin GUI resizing
-        false
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, hints);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        match wrapper
+            .editor
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .lock()
+            .resize_hints()
+        {
+            Some(resize_hints) => {
+                *hints = std::mem::zeroed();
+
+                let hints = &mut *hints;
+                hints.can_resize_horizontally = resize_hints.can_resize_horizontally();
+                hints.can_resize_vertically = resize_hints.can_resize_vertically();
+                if let Some((aspect_ratio_width, aspect_ratio_height)) = resize_hints.aspect_ratio {
+                    hints.preserve_aspect_ratio = true;
+                    hints.aspect_ratio_width = aspect_ratio_width;
+                    hints.aspect_ratio_height = aspect_ratio_height;
+                }
+
+                true
+            }
+            None => false,
+        }
     }
 
     unsafe extern "C" fn ext_gui_adjust_size(
-        _plugin: *const clap_plugin,
-        _width: *mut u32,
-        _height: *mut u32,
+        plugin: *const clap_plugin,
+        width: *mut u32,
+        height: *mut u32,
     ) -> bool {
-        // TODO: Implement Host->Plugin GUI resizing
-        false
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, width, height);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // The host's sizes are in physical pixels, while the editor works with logical pixels. For
+        // macOS the scaling factor is always 1.
+        let scaling_factor = wrapper.editor_scaling_factor.load(Ordering::Relaxed);
+        let (unscaled_width, unscaled_height) = (
+            (*width as f32 / scaling_factor).round() as u32,
+            (*height as f32 / scaling_factor).round() as u32,
+        );
+
+        match wrapper
+            .editor
+            .borrow()
+            .as_ref()
+            .unwrap()
+            .lock()
+            .adjust_size(unscaled_width, unscaled_height)
+        {
+            Some((adjusted_width, adjusted_height)) => {
+                (*width, *height) = (
+                    (adjusted_width as f32 * scaling_factor).round() as u32,
+                    (adjusted_height as f32 * scaling_factor).round() as u32,
+                );
+
+                true
+            }
+            None => false,
+        }
     }
 
     unsafe extern "C" fn ext_gui_set_size(
@@ -2738,20 +3210,29 @@ impl<P: ClapPlugin> Wrapper<P> {
         width: u32,
         height: u32,
     ) -> bool {
-        // TODO: Implement Host->Plugin GUI resizing
-        // TODO: The host will also call this if an asynchronous (on Linux) resize request fails
         check_null_ptr!(false, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
 
-        let (unscaled_width, unscaled_height) =
-            wrapper.editor.borrow().as_ref().unwrap().lock().size();
+        let editor = wrapper.editor.borrow();
+        let editor = editor.as_ref().unwrap().lock();
+        let (unscaled_width, unscaled_height) = editor.size();
         let scaling_factor = wrapper.editor_scaling_factor.load(Ordering::Relaxed);
         let (editor_width, editor_height) = (
             (unscaled_width as f32 * scaling_factor).round() as u32,
             (unscaled_height as f32 * scaling_factor).round() as u32,
         );
 
-        width == editor_width && height == editor_height
+        // The host also calls this after a plugin initiated resize, in which case the editor already
+        // has the correct size. If an asynchronous (on Linux) resize request fails, then this
+        // resizes the editor back to the window's size if the editor supports that.
+        if width == editor_width && height == editor_height {
+            return true;
+        }
+
+        editor.set_size(
+            (width as f32 / scaling_factor).round() as u32,
+            (height as f32 / scaling_factor).round() as u32,
+        )
     }
 
     unsafe extern "C" fn ext_gui_set_parent(
@@ -2829,6 +3310,54 @@ impl<P: ClapPlugin> Wrapper<P> {
         false
     }
 
+    unsafe extern "C" fn ext_param_indication_set_mapping(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        has_mapping: bool,
+        color: *const clap_color,
+        label: *const c_char,
+        description: *const c_char,
+    ) {
+        check_null_ptr!((), plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let mapping = if has_mapping {
+            Some(ParamMapping {
+                color: color.as_ref().map(track_color_from_clap),
+                label: string_from_clap(label),
+                description: string_from_clap(description),
+            })
+        } else {
+            None
+        };
+
+        wrapper.update_param_indication(param_id, |indication| indication.mapping = mapping);
+    }
+
+    unsafe extern "C" fn ext_param_indication_set_automation(
+        plugin: *const clap_plugin,
+        param_id: clap_id,
+        automation_state: u32,
+        color: *const clap_color,
+    ) {
+        check_null_ptr!((), plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        let automation = match automation_state {
+            CLAP_PARAM_INDICATION_AUTOMATION_PRESENT => AutomationState::Present,
+            CLAP_PARAM_INDICATION_AUTOMATION_PLAYING => AutomationState::Playing,
+            CLAP_PARAM_INDICATION_AUTOMATION_RECORDING => AutomationState::Recording,
+            CLAP_PARAM_INDICATION_AUTOMATION_OVERRIDING => AutomationState::Overriding,
+            _ => AutomationState::None,
+        };
+        let automation_color = color.as_ref().map(track_color_from_clap);
+
+        wrapper.update_param_indication(param_id, |indication| {
+            indication.automation = automation;
+            indication.automation_color = automation_color;
+        });
+    }
+
     unsafe extern "C" fn ext_latency_get(plugin: *const clap_plugin) -> u32 {
         check_null_ptr!(0, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
@@ -2836,6 +3365,37 @@ impl<P: ClapPlugin> Wrapper<P> {
         wrapper.current_latency.load(Ordering::SeqCst)
     }
 
+    unsafe extern "C" fn ext_note_name_count(plugin: *const clap_plugin) -> u32 {
+        check_null_ptr!(0, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        // The names are collected again here if they have changed since the host will always call
+        // this function before querying the individual names. Keys that have the same name on
+        // every channel are reported once using the channel wildcard.
+        wrapper.note_names.names(&wrapper.plugin).len() as u32
+    }
+
+    unsafe extern "C" fn ext_note_name_get(
+        plugin: *const clap_plugin,
+        index: u32,
+        note_name: *mut clap_note_name,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data, note_name);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        match wrapper.note_names.cached_names().get(index as usize) {
+            Some(name) => {
+                *note_name =
+                    make_clap_note_name(name.channel.map_or(-1, i16::from), name.note, &name.name);
+                true
+            }
+            None => {
+                nih_debug_assert_failure!("Host tried to query out of bounds note name {}", index);
+                false
+            }
+        }
+    }
+
     unsafe extern "C" fn ext_note_ports_count(_plugin: *const clap_plugin, is_input: bool) -> u32 {
         match is_input {
             true if P::MIDI_INPUT >= MidiConfig::Basic => 1,
@@ -2857,8 +3417,11 @@ impl<P: ClapPlugin> Wrapper<P> {
                 let info = &mut *info;
                 info.id = 0;
                 // NOTE: REAPER won't send us SysEx if we don't support the MIDI dialect
-                // TODO: Implement MPE (would just be a toggle for the plugin to expose it) and MIDI2
-                info.supported_dialects = CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI;
+                info.supported_dialects =
+                    CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI | CLAP_NOTE_DIALECT_MIDI2;
+                if P::MIDI_INPUT >= MidiConfig::Mpe {
+                    info.supported_dialects |= CLAP_NOTE_DIALECT_MIDI_MPE;
+                }
                 info.preferred_dialect = CLAP_NOTE_DIALECT_CLAP;
                 strlcpy(&mut info.name, "Note Input");
 
@@ -2917,12 +3480,11 @@ impl<P: ClapPlugin> Wrapper<P> {
         //       hashmap lookup, but for now we'll stay consistent with the VST3 implementation.
         let param_info = &mut *param_info;
         param_info.id = *param_hash;
-        // TODO: Somehow expose per note/channel/port modulation
         param_info.flags = 0;
         if automatable && !hidden {
             param_info.flags |= CLAP_PARAM_IS_AUTOMATABLE | CLAP_PARAM_IS_MODULATABLE;
             if wrapper.poly_mod_ids_by_hash.contains_key(param_hash) {
-                param_info.flags |= CLAP_PARAM_IS_MODULATABLE_PER_NOTE_ID;
+                param_info.flags |= poly_modulation_param_flags(P::CLAP_POLY_MODULATION_CONFIG);
             }
         }
         if hidden {
@@ -3043,6 +3605,39 @@ impl<P: ClapPlugin> Wrapper<P> {
         }
     }
 
+    unsafe extern "C" fn ext_preset_load_from_location(
+        plugin: *const clap_plugin,
+        location_kind: clap_preset_discovery_location_kind,
+        location: *const c_char,
+        load_key: *const c_char,
+    ) -> bool {
+        check_null_ptr!(false, plugin, (*plugin).plugin_data);
+        let wrapper = &*((*plugin).plugin_data as *const Self);
+
+        match preset_discovery::read_preset::<P>(location_kind, location, load_key) {
+            Ok(preset) => {
+                // This goes through the same path as state restored from the plugin's editor, so
+                // it's also safe to load presets while the plugin is processing audio
+                wrapper.set_state_object_from_gui(preset.state);
+
+                if let Some(host_preset_load) = &*wrapper.host_preset_load.borrow() {
+                    clap_call! { host_preset_load=>loaded(&*wrapper.host_callback, location_kind, location, load_key) };
+                }
+
+                true
+            }
+            Err(err) => {
+                nih_log!("Could not load preset: {:#}", err);
+                if let Some(host_preset_load) = &*wrapper.host_preset_load.borrow() {
+                    let message = CString::new(format!("{err:#}")).unwrap_or_default();
+                    clap_call! { host_preset_load=>on_error(&*wrapper.host_callback, location_kind, location, load_key, 0, message.as_ptr()) };
+                }
+
+                false
+            }
+        }
+    }
+
     unsafe extern "C" fn ext_remote_controls_count(plugin: *const clap_plugin) -> u32 {
         check_null_ptr!(0, plugin, (*plugin).plugin_data);
         let wrapper = &*((*plugin).plugin_data as *const Self);
@@ -3212,6 +3807,374 @@ impl<P: ClapPlugin> Wrapper<P> {
     }
 }
 
+/// Determine which note properties a CLAP parameter event targets. A value of -1 means that the
+/// event applies to all notes, keys, channels, or ports. An empty scope means that the event is
+/// not targeted at specific voices.
+fn clap_event_poly_scope(
+    note_id: i32,
+    port_index: i16,
+    channel: i16,
+    key: i16,
+) -> PolyModulationScope {
+    let mut scope = PolyModulationScope::empty();
+    scope.set(PolyModulationScope::NOTE_ID, note_id != -1);
+    scope.set(PolyModulationScope::PORT, port_index != -1);
+    scope.set(PolyModulationScope::CHANNEL, channel != -1);
+    scope.set(PolyModulationScope::KEY, key != -1);
+
+    scope
+}
+
+/// Compute the per note, key, channel, and port parameter flags for a polyphonically modulated
+/// parameter. Without a polyphonic modulation config, only per note ID modulation is advertised.
+fn poly_modulation_param_flags(config: Option<PolyModulationConfig>) -> clap_param_info_flags {
+    let (modulation_scope, automation_scope) = match config {
+        Some(config) => (config.modulation_scope, config.automation_scope),
+        None => (PolyModulationScope::NOTE_ID, PolyModulationScope::empty()),
+    };
+
+    let mut flags = 0;
+    for (scope, modulation_flag, automation_flag) in [
+        (
+            PolyModulationScope::NOTE_ID,
+            CLAP_PARAM_IS_MODULATABLE_PER_NOTE_ID,
+            CLAP_PARAM_IS_AUTOMATABLE_PER_NOTE_ID,
+        ),
+        (
+            PolyModulationScope::KEY,
+            CLAP_PARAM_IS_MODULATABLE_PER_KEY,
+            CLAP_PARAM_IS_AUTOMATABLE_PER_KEY,
+        ),
+        (
+            PolyModulationScope::CHANNEL,
+            CLAP_PARAM_IS_MODULATABLE_PER_CHANNEL,
+            CLAP_PARAM_IS_AUTOMATABLE_PER_CHANNEL,
+        ),
+        (
+            PolyModulationScope::PORT,
+            CLAP_PARAM_IS_MODULATABLE_PER_PORT,
+            CLAP_PARAM_IS_AUTOMATABLE_PER_PORT,
+        ),
+    ] {
+        if modulation_scope.contains(scope) {
+            flags |= modulation_flag;
+        }
+        if automation_scope.contains(scope) {
+            flags |= automation_flag;
+        }
+    }
+
+    flags
+}
+
+/// Get the channel layout for a CLAP audio port in an audio IO layout. Like in
+/// `ext_audio_ports_get()`, the main port comes before the auxiliary ports if the layout has one.
+fn port_channel_layout(
+    audio_io_layout: &AudioIOLayout,
+    is_input: bool,
+    port_index: u32,
+) -> Option<ChannelLayout> {
+    let port_index = port_index as usize;
+    match (is_input, port_index) {
+        (true, 0) if audio_io_layout.main_input_channels.is_some() => {
+            audio_io_layout.main_input_channel_layout()
+        }
+        (false, 0) if audio_io_layout.main_output_channels.is_some() => {
+            audio_io_layout.main_output_channel_layout()
+        }
+        (true, n) if audio_io_layout.main_input_channels.is_some() => {
+            audio_io_layout.aux_input_channel_layout(n - 1)
+        }
+        (false, n) if audio_io_layout.main_output_channels.is_some() => {
+            audio_io_layout.aux_output_channel_layout(n - 1)
+        }
+        (true, n) => audio_io_layout.aux_input_channel_layout(n),
+        (false, n) => audio_io_layout.aux_output_channel_layout(n),
+    }
+}
+
+/// All channel layouts used by the ports in any of the plugin's audio IO layouts.
+fn all_channel_layouts<P: Plugin>() -> impl Iterator<Item = ChannelLayout> {
+    P::AUDIO_IO_LAYOUTS.iter().flat_map(|audio_io_layout| {
+        let aux_input_layouts = (0..audio_io_layout.aux_input_ports.len())
+            .filter_map(|idx| audio_io_layout.aux_input_channel_layout(idx));
+        let aux_output_layouts = (0..audio_io_layout.aux_output_ports.len())
+            .filter_map(|idx| audio_io_layout.aux_output_channel_layout(idx));
+
+        audio_io_layout
+            .main_input_channel_layout()
+            .into_iter()
+            .chain(audio_io_layout.main_output_channel_layout())
+            .chain(aux_input_layouts)
+            .chain(aux_output_layouts)
+    })
+}
+
+/// The CLAP port type for a channel layout. Ports without a channel layout don't have a type.
+fn clap_port_type(channel_layout: Option<ChannelLayout>) -> *const c_char {
+    match channel_layout {
+        Some(ChannelLayout::Mono) => CLAP_PORT_MONO.as_ptr(),
+        Some(ChannelLayout::Stereo) => CLAP_PORT_STEREO.as_ptr(),
+        Some(ChannelLayout::Surround(_)) => CLAP_PORT_SURROUND.as_ptr(),
+        Some(ChannelLayout::Ambisonic { .. }) => CLAP_PORT_AMBISONIC.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Whether a port with this channel layout has the port type and port details from a
+/// `configurable-audio-ports` request. A request without a port type matches any port.
+///
+/// # Safety
+///
+/// `port_details` needs to be null or point to the details for the requested port type.
+unsafe fn port_type_matches(
+    channel_layout: Option<ChannelLayout>,
+    port_type: *const c_char,
+    port_details: *const c_void,
+) -> bool {
+    if port_type.is_null() {
+        return true;
+    }
+
+    let port_type = CStr::from_ptr(port_type);
+    match channel_layout {
+        Some(ChannelLayout::Mono) => port_type == CLAP_PORT_MONO,
+        Some(ChannelLayout::Stereo) => port_type == CLAP_PORT_STEREO,
+        Some(ChannelLayout::Surround(speakers)) => {
+            // The details contain the port's channel map
+            port_type == CLAP_PORT_SURROUND
+                && (port_details.is_null()
+                    || std::slice::from_raw_parts(port_details as *const u8, speakers.len())
+                        .iter()
+                        .copied()
+                        .eq(speakers.iter().map(|speaker| *speaker as u8)))
+        }
+        Some(ChannelLayout::Ambisonic {
+            ordering,
+            normalization,
+            ..
+        }) => {
+            port_type == CLAP_PORT_AMBISONIC
+                && (port_details.is_null() || {
+                    let config = &*(port_details as *const clap_ambisonic_config);
+                    config.ordering == clap_ambisonic_ordering(ordering)
+                        && config.normalization == clap_ambisonic_normalization(normalization)
+                })
+        }
+        None => false,
+    }
+}
+
+fn clap_ambisonic_ordering(ordering: AmbisonicOrdering) -> clap_ambisonic_ordering {
+    match ordering {
+        AmbisonicOrdering::Acn => CLAP_AMBISONIC_ORDERING_ACN,
+        AmbisonicOrdering::FuMa => CLAP_AMBISONIC_ORDERING_FUMA,
+    }
+}
+
+fn clap_ambisonic_normalization(
+    normalization: AmbisonicNormalization,
+) -> clap_ambisonic_normalization {
+    match normalization {
+        AmbisonicNormalization::MaxN => CLAP_AMBISONIC_NORMALIZATION_MAXN,
+        AmbisonicNormalization::Sn3d => CLAP_AMBISONIC_NORMALIZATION_SN3D,
+        AmbisonicNormalization::N3d => CLAP_AMBISONIC_NORMALIZATION_N3D,
+        AmbisonicNormalization::Sn2d => CLAP_AMBISONIC_NORMALIZATION_SN2D,
+        AmbisonicNormalization::N2d => CLAP_AMBISONIC_NORMALIZATION_N2D,
+    }
+}
+
+/// Convert the track information from the `track-info` extension to NIH-plug's [`TrackInfo`].
+fn track_info_from_clap(info: &clap_track_info) -> TrackInfo {
+    let has_flag = |flag| info.flags & flag != 0;
+
+    TrackInfo {
+        name: has_flag(CLAP_TRACK_INFO_HAS_TRACK_NAME).then(|| {
+            // The host is supposed to null terminate this, but we can't rely on that
+            let name: Vec<u8> = info
+                .name
+                .iter()
+                .take_while(|c| **c != 0)
+                .map(|c| *c as u8)
+                .collect();
+
+            String::from_utf8_lossy(&name).into_owned()
+        }),
+        color: has_flag(CLAP_TRACK_INFO_HAS_TRACK_COLOR)
+            .then(|| track_color_from_clap(&info.color)),
+        channel_count: if has_flag(CLAP_TRACK_INFO_HAS_AUDIO_CHANNEL) {
+            u32::try_from(info.audio_channel_count).ok()
+        } else {
+            None
+        },
+        is_bus: has_flag(CLAP_TRACK_INFO_IS_FOR_BUS),
+        is_return_track: has_flag(CLAP_TRACK_INFO_IS_FOR_RETURN_TRACK),
+        is_master: has_flag(CLAP_TRACK_INFO_IS_FOR_MASTER),
+    }
+}
+
+fn track_color_from_clap(color: &clap_color) -> TrackColor {
+    TrackColor {
+        red: color.red,
+        green: color.green,
+        blue: color.blue,
+        alpha: color.alpha,
+    }
+}
+
+/// Copy a string from the host. Returns `None` for null pointers.
+unsafe fn string_from_clap(string: *const c_char) -> Option<String> {
+    if string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+/// The context menu target for the parameter with the specified hash, which is also its CLAP
+/// parameter ID.
+fn param_context_menu_target(param_hash: u32) -> clap_context_menu_target {
+    clap_context_menu_target {
+        kind: CLAP_CONTEXT_MENU_TARGET_KIND_PARAM,
+        id: param_hash,
+    }
+}
+
+/// Collects the items the host adds to a `clap_context_menu_builder` while populating a parameter's
+/// context menu. Submenus are added as a flat list of items between a begin and an end marker, so
+/// this keeps track of the submenus that haven't been closed yet.
+#[derive(Default)]
+struct ContextMenuItemCollector {
+    /// The top level menu's items.
+    items: Vec<ContextMenuItem>,
+    /// The label, enabled state, and items of every submenu that's currently being populated, from
+    /// the outermost to the innermost submenu.
+    open_submenus: Vec<(String, bool, Vec<ContextMenuItem>)>,
+}
+
+impl ContextMenuItemCollector {
+    fn push(&mut self, item: ContextMenuItem) {
+        match self.open_submenus.last_mut() {
+            Some((_, _, items)) => items.push(item),
+            None => self.items.push(item),
+        }
+    }
+
+    fn end_submenu(&mut self) -> bool {
+        match self.open_submenus.pop() {
+            Some((label, is_enabled, items)) => {
+                self.push(ContextMenuItem::Submenu {
+                    label,
+                    is_enabled,
+                    items,
+                });
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the collected items, closing any submenus the host forgot to end.
+    fn finish(mut self) -> Vec<ContextMenuItem> {
+        while self.end_submenu() {}
+
+        self.items
+    }
+
+    unsafe extern "C" fn add_item(
+        builder: *const clap_context_menu_builder,
+        item_kind: clap_context_menu_item_kind,
+        item_data: *const c_void,
+    ) -> bool {
+        check_null_ptr!(false, builder, (*builder).ctx);
+        let collector = &mut *((*builder).ctx as *mut Self);
+
+        match item_kind {
+            CLAP_CONTEXT_MENU_ITEM_ENTRY => {
+                check_null_ptr!(false, item_data);
+                let entry = &*(item_data as *const clap_context_menu_entry);
+                collector.push(ContextMenuItem::Entry {
+                    label: string_from_clap(entry.label).unwrap_or_default(),
+                    action_id: entry.action_id,
+                    is_enabled: entry.is_enabled,
+                    is_checked: false,
+                });
+            }
+            CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY => {
+                check_null_ptr!(false, item_data);
+                let entry = &*(item_data as *const clap_context_menu_check_entry);
+                collector.push(ContextMenuItem::Entry {
+                    label: string_from_clap(entry.label).unwrap_or_default(),
+                    action_id: entry.action_id,
+                    is_enabled: entry.is_enabled,
+                    is_checked: entry.is_checked,
+                });
+            }
+            CLAP_CONTEXT_MENU_ITEM_SEPARATOR => collector.push(ContextMenuItem::Separator),
+            CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU => {
+                check_null_ptr!(false, item_data);
+                let submenu = &*(item_data as *const clap_context_menu_submenu);
+                collector.open_submenus.push((
+                    string_from_clap(submenu.label).unwrap_or_default(),
+                    submenu.is_enabled,
+                    Vec::new(),
+                ));
+            }
+            CLAP_CONTEXT_MENU_ITEM_END_SUBMENU => {
+                if !collector.end_submenu() {
+                    nih_debug_assert_failure!(
+                        "The host ended a context menu submenu it never began"
+                    );
+                    return false;
+                }
+            }
+            CLAP_CONTEXT_MENU_ITEM_TITLE => {
+                check_null_ptr!(false, item_data);
+                let title = &*(item_data as *const clap_context_menu_item_title);
+                collector.push(ContextMenuItem::Title {
+                    label: string_from_clap(title.title).unwrap_or_default(),
+                });
+            }
+            n => {
+                nih_debug_assert_failure!("Unknown context menu item kind: {}", n);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    unsafe extern "C" fn supports(
+        _builder: *const clap_context_menu_builder,
+        item_kind: clap_context_menu_item_kind,
+    ) -> bool {
+        matches!(
+            item_kind,
+            CLAP_CONTEXT_MENU_ITEM_ENTRY
+                | CLAP_CONTEXT_MENU_ITEM_CHECK_ENTRY
+                | CLAP_CONTEXT_MENU_ITEM_SEPARATOR
+                | CLAP_CONTEXT_MENU_ITEM_BEGIN_SUBMENU
+                | CLAP_CONTEXT_MENU_ITEM_END_SUBMENU
+                | CLAP_CONTEXT_MENU_ITEM_TITLE
+        )
+    }
+}
+
+/// Create a note name for the note-name extension. A channel of -1 means that the name applies to
+/// all channels.
+fn make_clap_note_name(channel: i16, note: u8, name: &str) -> clap_note_name {
+    let mut note_name = clap_note_name {
+        name: [0; CLAP_NAME_SIZE],
+        port: 0,
+        key: note as i16,
+        channel,
+    };
+    strlcpy(&mut note_name.name, name);
+
+    note_name
+}
+
 /// Convenience function to query an extension from the host.
 ///
 /// # Safety
@@ -3229,3 +4192,36 @@ unsafe fn query_host_extension<T>(
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_poly_scope() {
+        assert!(clap_event_poly_scope(-1, -1, -1, -1).is_empty());
+        assert_eq!(
+            clap_event_poly_scope(3, -1, -1, -1),
+            PolyModulationScope::NOTE_ID
+        );
+        assert_eq!(
+            clap_event_poly_scope(-1, 0, 1, 60),
+            PolyModulationScope::PORT | PolyModulationScope::CHANNEL | PolyModulationScope::KEY
+        );
+    }
+
+    #[test]
+    fn mismatched_automation_scope() {
+        // A plugin that only accepts per-key automation should not receive per-channel or
+        // per-channel and key automation as polyphonic automation
+        let automation_scope = PolyModulationScope::KEY;
+        assert!(automation_scope.contains(clap_event_poly_scope(-1, -1, -1, 60)));
+        assert!(!automation_scope.contains(clap_event_poly_scope(-1, -1, 1, -1)));
+        assert!(!automation_scope.contains(clap_event_poly_scope(-1, -1, 1, 60)));
+        assert!(!automation_scope.contains(clap_event_poly_scope(7, -1, -1, 60)));
+
+        let automation_scope = PolyModulationScope::KEY | PolyModulationScope::CHANNEL;
+        assert!(automation_scope.contains(clap_event_poly_scope(-1, -1, 1, 60)));
+        assert!(!automation_scope.contains(clap_event_poly_scope(-1, 0, 1, 60)));
+    }
+}
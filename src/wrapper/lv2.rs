@@ -0,0 +1,122 @@
+#[macro_use]
+mod util;
+
+mod atom;
+pub mod classes;
+mod context;
+mod ports;
+mod sys;
+mod transport;
+mod turtle;
+mod wrapper;
+
+/// Re-export for the macro
+pub use self::sys::LV2_Descriptor;
+pub use self::turtle::{write_bundle, PluginTurtle};
+pub use self::wrapper::{PluginDescriptor, Wrapper};
+
+/// Export one or more LV2 plugins from this library using the provided plugin types. Next to the
+/// `lv2_descriptor()` entry point, this also exports a function `cargo xtask bundle` uses to
+/// generate the bundle's Turtle files.
+#[macro_export]
+macro_rules! nih_export_lv2 {
+    ($($plugin_ty:ty),+) => {
+        #[doc(hidden)]
+        mod lv2 {
+            use $crate::prelude::{nih_debug_assert_eq, nih_error};
+            use $crate::wrapper::setup_logger;
+            use $crate::wrapper::lv2::{write_bundle, LV2_Descriptor, PluginDescriptor, PluginTurtle};
+            use ::std::collections::HashSet;
+            use ::std::ffi::CStr;
+            use ::std::os::raw::c_char;
+            use ::std::path::Path;
+            use ::std::sync::{Once, OnceLock};
+
+            // Because the `$plugin_ty`s are likely defined in the enclosing scope. This works even
+            // if the types are not public because this is a child module.
+            use super::*;
+
+            // Sneaky way to get the number of expanded elements
+            const PLUGIN_COUNT: usize = [$(stringify!($plugin_ty)),+].len();
+
+            // This is a type erased version of the information stored on the plugin types
+            static PLUGIN_DESCRIPTORS: OnceLock<[PluginDescriptor; PLUGIN_COUNT]> = OnceLock::new();
+
+            // LV2 doesn't have a separate initialization function, and hosts call
+            // `lv2_descriptor()` until it returns a null pointer
+            static SETUP_LOGGER: Once = Once::new();
+
+            fn plugin_descriptors() -> &'static [PluginDescriptor; PLUGIN_COUNT] {
+                PLUGIN_DESCRIPTORS.get_or_init(|| {
+                    let descriptors = [$(PluginDescriptor::for_plugin::<$plugin_ty>()),+];
+
+                    if cfg!(debug_assertions) {
+                        let unique_plugin_uris: HashSet<_> = descriptors.iter().map(|d| d.uri()).collect();
+                        nih_debug_assert_eq!(
+                            unique_plugin_uris.len(),
+                            descriptors.len(),
+                            "Duplicate plugin URIs found in `nih_export_lv2!()` call"
+                        );
+                    }
+
+                    descriptors
+                })
+            }
+
+            pub fn lv2_descriptor(index: u32) -> *const LV2_Descriptor {
+                SETUP_LOGGER.call_once(setup_logger);
+
+                match plugin_descriptors().get(index as usize) {
+                    Some(descriptor) => descriptor.lv2_descriptor(),
+                    None => ::std::ptr::null(),
+                }
+            }
+
+            pub unsafe fn write_ttl(bundle_path: *const c_char, binary_name: *const c_char) -> bool {
+                SETUP_LOGGER.call_once(setup_logger);
+
+                if bundle_path.is_null() || binary_name.is_null() {
+                    return false;
+                }
+
+                let (bundle_path, binary_name) = match (
+                    CStr::from_ptr(bundle_path).to_str(),
+                    CStr::from_ptr(binary_name).to_str(),
+                ) {
+                    (Ok(bundle_path), Ok(binary_name)) => (bundle_path, binary_name),
+                    _ => return false,
+                };
+
+                let plugins = [$(PluginTurtle::for_plugin::<$plugin_ty>()),+];
+                match write_bundle(Path::new(bundle_path), binary_name, &plugins) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        nih_error!("Could not write the LV2 bundle's Turtle files: {err:#}");
+                        false
+                    }
+                }
+            }
+        }
+
+        /// The LV2 plugin's entry point.
+        #[no_mangle]
+        pub extern "C" fn lv2_descriptor(index: u32) -> *const $crate::wrapper::lv2::LV2_Descriptor {
+            self::lv2::lv2_descriptor(index)
+        }
+
+        /// Writes the bundle's Turtle files. Called by `cargo xtask bundle` after copying the
+        /// library to the `.lv2` bundle, since the Turtle files are generated from the plugin's
+        /// parameters.
+        ///
+        /// # Safety
+        ///
+        /// Both arguments must be null terminated UTF-8 strings.
+        #[no_mangle]
+        pub unsafe extern "C" fn nih_plug_lv2_write_ttl(
+            bundle_path: *const ::std::os::raw::c_char,
+            binary_name: *const ::std::os::raw::c_char,
+        ) -> bool {
+            self::lv2::write_ttl(bundle_path, binary_name)
+        }
+    };
+}
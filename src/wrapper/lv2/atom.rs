@@ -0,0 +1,356 @@
+//! Helpers for reading and writing LV2 atoms. The atoms are parsed from byte slices so the parsing
+//! itself does not need to care about alignment or about reading past the end of the host's
+//! buffers.
+
+use std::mem;
+
+use super::sys::{
+    LV2_Atom, LV2_Atom_Event, LV2_Atom_Object_Body, LV2_Atom_Property_Body, LV2_Atom_Sequence,
+};
+
+/// The size of an atom's header, containing its body size and type.
+const ATOM_HEADER_SIZE: usize = mem::size_of::<LV2_Atom>();
+/// The size of a sequence's header, including the atom header.
+const SEQUENCE_HEADER_SIZE: usize = mem::size_of::<LV2_Atom_Sequence>();
+/// The size of an event's header, including the event body's atom header.
+const EVENT_HEADER_SIZE: usize = mem::size_of::<LV2_Atom_Event>();
+/// The size of an object body's header, containing its ID and type.
+const OBJECT_HEADER_SIZE: usize = mem::size_of::<LV2_Atom_Object_Body>();
+/// The size of a property's header, including the property value's atom header.
+const PROPERTY_HEADER_SIZE: usize = mem::size_of::<LV2_Atom_Property_Body>();
+
+/// The URIDs for the atom types needed to read numbers.
+#[derive(Debug, Clone, Copy)]
+pub struct NumberUrids {
+    pub int: u32,
+    pub long: u32,
+    pub float: u32,
+    pub double: u32,
+}
+
+/// An event in an atom sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceEvent<'a> {
+    /// The event's time in frames, relative to the start of the buffer.
+    pub frames: i64,
+    /// The URID for the event body's atom type.
+    pub type_: u32,
+    /// The event body's contents.
+    pub body: &'a [u8],
+}
+
+/// An iterator over the events in an atom sequence. See [`read_sequence()`].
+pub struct SequenceIter<'a> {
+    /// The sequence's remaining events.
+    data: &'a [u8],
+}
+
+/// An iterator over the properties in an atom object. See [`read_object()`].
+pub struct ObjectIter<'a> {
+    /// The object's remaining properties.
+    data: &'a [u8],
+}
+
+/// Writes events to an atom sequence in a buffer provided by the host.
+pub struct SequenceWriter<'a> {
+    /// The host's buffer, including the sequence header.
+    buffer: &'a mut [u8],
+    /// The number of bytes written to the buffer so far, including the sequence header.
+    len: usize,
+}
+
+/// Get the bytes for the atom sequence pointed to by `sequence`, including the sequence header.
+///
+/// # Safety
+///
+/// `sequence` must point to a valid atom, and the atom's body must be readable for the atom's
+/// entire size.
+pub unsafe fn sequence_bytes<'a>(sequence: *const LV2_Atom_Sequence) -> &'a [u8] {
+    let size = ATOM_HEADER_SIZE + (*sequence).atom.size as usize;
+
+    std::slice::from_raw_parts(sequence as *const u8, size)
+}
+
+/// Iterate over the events in an atom sequence. `sequence` contains the entire atom sequence,
+/// including the sequence header. Returns `None` if the atom is not a sequence or if the sequence
+/// uses beat times.
+pub fn read_sequence(
+    sequence: &[u8],
+    sequence_urid: u32,
+    beat_time_urid: u32,
+) -> Option<SequenceIter<'_>> {
+    if sequence.len() < SEQUENCE_HEADER_SIZE || read_u32(sequence, 4)? != sequence_urid {
+        return None;
+    }
+
+    // Sequences with beat times are not supported. The unit is either 0 or the `atom:frameTime`
+    // URID for frame times, but hosts may also leave it set to some other value.
+    let unit = read_u32(sequence, 8)?;
+    let size = ATOM_HEADER_SIZE + read_u32(sequence, 0)? as usize;
+    if unit == beat_time_urid || size > sequence.len() {
+        return None;
+    }
+
+    Some(SequenceIter {
+        data: &sequence[SEQUENCE_HEADER_SIZE..size],
+    })
+}
+
+/// Iterate over the properties in an atom object's body. Returns the object's type and an iterator
+/// over the object's `(key, value_type, value_body)` properties, or `None` if the body is too small
+/// to be an object.
+pub fn read_object(body: &[u8]) -> Option<(u32, ObjectIter<'_>)> {
+    let otype = read_u32(body, 4)?;
+
+    Some((
+        otype,
+        ObjectIter {
+            data: body.get(OBJECT_HEADER_SIZE..)?,
+        },
+    ))
+}
+
+/// Read a numeric atom as an `f64`. Returns `None` if the atom is not an int, long, float, or
+/// double.
+pub fn read_number(urids: &NumberUrids, type_: u32, body: &[u8]) -> Option<f64> {
+    if type_ == urids.int {
+        Some(i32::from_ne_bytes(body.get(..4)?.try_into().ok()?) as f64)
+    } else if type_ == urids.long {
+        Some(i64::from_ne_bytes(body.get(..8)?.try_into().ok()?) as f64)
+    } else if type_ == urids.float {
+        Some(f32::from_ne_bytes(body.get(..4)?.try_into().ok()?) as f64)
+    } else if type_ == urids.double {
+        Some(f64::from_ne_bytes(body.get(..8)?.try_into().ok()?))
+    } else {
+        None
+    }
+}
+
+impl<'a> Iterator for SequenceIter<'a> {
+    type Item = SequenceEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frames = i64::from_ne_bytes(self.data.get(..8)?.try_into().ok()?);
+        let size = read_u32(self.data, 8)? as usize;
+        let type_ = read_u32(self.data, 12)?;
+        let body = self.data.get(EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + size)?;
+
+        let next_event_start = (EVENT_HEADER_SIZE + padded_size(size)).min(self.data.len());
+        self.data = &self.data[next_event_start..];
+
+        Some(SequenceEvent {
+            frames,
+            type_,
+            body,
+        })
+    }
+}
+
+impl<'a> Iterator for ObjectIter<'a> {
+    type Item = (u32, u32, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = read_u32(self.data, 0)?;
+        let size = read_u32(self.data, 8)? as usize;
+        let type_ = read_u32(self.data, 12)?;
+        let body = self
+            .data
+            .get(PROPERTY_HEADER_SIZE..PROPERTY_HEADER_SIZE + size)?;
+
+        let next_property_start = (PROPERTY_HEADER_SIZE + padded_size(size)).min(self.data.len());
+        self.data = &self.data[next_property_start..];
+
+        Some((key, type_, body))
+    }
+}
+
+impl<'a> SequenceWriter<'a> {
+    /// Start writing an empty atom sequence to `buffer`. Returns `None` if the buffer is too small
+    /// to contain an empty sequence.
+    pub fn new(buffer: &'a mut [u8], sequence_urid: u32) -> Option<Self> {
+        if buffer.len() < SEQUENCE_HEADER_SIZE {
+            return None;
+        }
+
+        // The size, type, unit, and padding fields. The sequence's size will be updated when
+        // writing events.
+        buffer[0..4]
+            .copy_from_slice(&((SEQUENCE_HEADER_SIZE - ATOM_HEADER_SIZE) as u32).to_ne_bytes());
+        buffer[4..8].copy_from_slice(&sequence_urid.to_ne_bytes());
+        buffer[8..16].fill(0);
+
+        Some(Self {
+            buffer,
+            len: SEQUENCE_HEADER_SIZE,
+        })
+    }
+
+    /// Append an event to the sequence. Events need to be written in order. Returns `false` if
+    /// there was not enough space left in the buffer.
+    pub fn write_event(&mut self, frames: i64, type_: u32, body: &[u8]) -> bool {
+        let event_size = EVENT_HEADER_SIZE + padded_size(body.len());
+        if self.len + event_size > self.buffer.len() {
+            return false;
+        }
+
+        let event = &mut self.buffer[self.len..self.len + event_size];
+        event[0..8].copy_from_slice(&frames.to_ne_bytes());
+        event[8..12].copy_from_slice(&(body.len() as u32).to_ne_bytes());
+        event[12..16].copy_from_slice(&type_.to_ne_bytes());
+        event[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + body.len()].copy_from_slice(body);
+        event[EVENT_HEADER_SIZE + body.len()..].fill(0);
+
+        self.len += event_size;
+        self.buffer[0..4].copy_from_slice(&((self.len - ATOM_HEADER_SIZE) as u32).to_ne_bytes());
+
+        true
+    }
+}
+
+/// Atoms in sequences and objects are padded to 64-bit boundaries.
+fn padded_size(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQUENCE: u32 = 1;
+    const MIDI_EVENT: u32 = 2;
+    const OBJECT: u32 = 3;
+    const FLOAT: u32 = 4;
+    const LONG: u32 = 5;
+    const FRAME_TIME: u32 = 8;
+    const BEAT_TIME: u32 = 9;
+
+    const URIDS: NumberUrids = NumberUrids {
+        int: 6,
+        long: LONG,
+        float: FLOAT,
+        double: 7,
+    };
+
+    #[test]
+    fn test_write_read_sequence() {
+        let mut buffer = vec![0u8; 256];
+        let mut writer = SequenceWriter::new(&mut buffer, SEQUENCE).unwrap();
+        assert!(writer.write_event(0, MIDI_EVENT, &[0x90, 60, 127]));
+        assert!(writer.write_event(10, MIDI_EVENT, &[0xf0, 1, 2, 3, 4, 5, 6, 7, 0xf7]));
+        assert!(writer.write_event(20, MIDI_EVENT, &[0x80, 60, 0]));
+
+        let events: Vec<_> = read_sequence(&buffer, SEQUENCE, BEAT_TIME)
+            .unwrap()
+            .collect();
+        assert_eq!(
+            events,
+            [
+                SequenceEvent {
+                    frames: 0,
+                    type_: MIDI_EVENT,
+                    body: &[0x90, 60, 127]
+                },
+                SequenceEvent {
+                    frames: 10,
+                    type_: MIDI_EVENT,
+                    body: &[0xf0, 1, 2, 3, 4, 5, 6, 7, 0xf7]
+                },
+                SequenceEvent {
+                    frames: 20,
+                    type_: MIDI_EVENT,
+                    body: &[0x80, 60, 0]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_sequence_overflow() {
+        // Room for the header and exactly one three byte MIDI event
+        let mut buffer = vec![0u8; SEQUENCE_HEADER_SIZE + EVENT_HEADER_SIZE + 8];
+        let mut writer = SequenceWriter::new(&mut buffer, SEQUENCE).unwrap();
+        assert!(writer.write_event(0, MIDI_EVENT, &[0x90, 60, 127]));
+        assert!(!writer.write_event(1, MIDI_EVENT, &[0x80, 60, 0]));
+
+        assert_eq!(
+            read_sequence(&buffer, SEQUENCE, BEAT_TIME).unwrap().count(),
+            1
+        );
+        assert!(SequenceWriter::new(&mut [0u8; 8], SEQUENCE).is_none());
+    }
+
+    #[test]
+    fn test_read_invalid_sequence() {
+        let mut buffer = vec![0u8; 64];
+        SequenceWriter::new(&mut buffer, SEQUENCE).unwrap();
+        assert!(read_sequence(&buffer, MIDI_EVENT, BEAT_TIME).is_none());
+        assert_eq!(
+            read_sequence(&buffer, SEQUENCE, BEAT_TIME).unwrap().count(),
+            0
+        );
+
+        // An event that claims to be larger than the sequence is ignored
+        let mut writer = SequenceWriter::new(&mut buffer, SEQUENCE).unwrap();
+        assert!(writer.write_event(0, MIDI_EVENT, &[0x90, 60, 127]));
+        buffer[SEQUENCE_HEADER_SIZE + 8..SEQUENCE_HEADER_SIZE + 12]
+            .copy_from_slice(&1000u32.to_ne_bytes());
+        assert_eq!(
+            read_sequence(&buffer, SEQUENCE, BEAT_TIME).unwrap().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_read_sequence_units() {
+        let mut buffer = vec![0u8; 64];
+        let mut writer = SequenceWriter::new(&mut buffer, SEQUENCE).unwrap();
+        assert!(writer.write_event(0, MIDI_EVENT, &[0x90, 60, 127]));
+
+        // The sequence writer uses a unit of 0, and hosts may also use the `atom:frameTime` URID
+        assert_eq!(
+            read_sequence(&buffer, SEQUENCE, BEAT_TIME).unwrap().count(),
+            1
+        );
+        buffer[8..12].copy_from_slice(&FRAME_TIME.to_ne_bytes());
+        assert_eq!(
+            read_sequence(&buffer, SEQUENCE, BEAT_TIME).unwrap().count(),
+            1
+        );
+        buffer[8..12].copy_from_slice(&BEAT_TIME.to_ne_bytes());
+        assert!(read_sequence(&buffer, SEQUENCE, BEAT_TIME).is_none());
+    }
+
+    #[test]
+    fn test_read_object() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        body.extend_from_slice(&OBJECT.to_ne_bytes());
+        // A float property, padded to eight bytes
+        body.extend_from_slice(&10u32.to_ne_bytes());
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        body.extend_from_slice(&4u32.to_ne_bytes());
+        body.extend_from_slice(&FLOAT.to_ne_bytes());
+        body.extend_from_slice(&120.0f32.to_ne_bytes());
+        body.extend_from_slice(&[0; 4]);
+        // And a long property
+        body.extend_from_slice(&11u32.to_ne_bytes());
+        body.extend_from_slice(&0u32.to_ne_bytes());
+        body.extend_from_slice(&8u32.to_ne_bytes());
+        body.extend_from_slice(&LONG.to_ne_bytes());
+        body.extend_from_slice(&48000i64.to_ne_bytes());
+
+        let (otype, properties) = read_object(&body).unwrap();
+        assert_eq!(otype, OBJECT);
+
+        let properties: Vec<_> = properties
+            .map(|(key, type_, body)| (key, read_number(&URIDS, type_, body)))
+            .collect();
+        assert_eq!(properties, [(10, Some(120.0)), (11, Some(48000.0))]);
+    }
+}
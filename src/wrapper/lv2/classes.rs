@@ -0,0 +1,106 @@
+//! Plugin classes for LV2 plugins. Hosts use these to organize plugins in their plugin browsers.
+
+/// An LV2 plugin class. See <https://lv2plug.in/ns/lv2core#Plugin> for the class hierarchy. A
+/// plugin is always an `lv2:Plugin`, so that class does not need to be added explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lv2PluginClass {
+    Allpass,
+    Amplifier,
+    Analyser,
+    Bandpass,
+    Chorus,
+    Comb,
+    Compressor,
+    Constant,
+    Converter,
+    Delay,
+    Distortion,
+    Dynamics,
+    Envelope,
+    Eq,
+    Expander,
+    Filter,
+    Flanger,
+    Function,
+    Gate,
+    Generator,
+    Highpass,
+    Instrument,
+    Limiter,
+    Lowpass,
+    Midi,
+    Mixer,
+    Modulator,
+    MultiEq,
+    Oscillator,
+    ParaEq,
+    Phaser,
+    Pitch,
+    Reverb,
+    Simulator,
+    Spatial,
+    Spectral,
+    Utility,
+    Waveshaper,
+    /// A class that is not part of the LV2 core specification. This must be a full URI, and it is
+    /// written to the plugin's Turtle file as is.
+    Custom(&'static str),
+}
+
+impl Lv2PluginClass {
+    /// The class as it should appear in a Turtle file. Predefined classes use the `lv2:` prefix,
+    /// and custom classes are written as full URIs.
+    pub fn as_turtle(&self) -> String {
+        let name = match self {
+            Lv2PluginClass::Allpass => "AllpassPlugin",
+            Lv2PluginClass::Amplifier => "AmplifierPlugin",
+            Lv2PluginClass::Analyser => "AnalyserPlugin",
+            Lv2PluginClass::Bandpass => "BandpassPlugin",
+            Lv2PluginClass::Chorus => "ChorusPlugin",
+            Lv2PluginClass::Comb => "CombPlugin",
+            Lv2PluginClass::Compressor => "CompressorPlugin",
+            Lv2PluginClass::Constant => "ConstantPlugin",
+            Lv2PluginClass::Converter => "ConverterPlugin",
+            Lv2PluginClass::Delay => "DelayPlugin",
+            Lv2PluginClass::Distortion => "DistortionPlugin",
+            Lv2PluginClass::Dynamics => "DynamicsPlugin",
+            Lv2PluginClass::Envelope => "EnvelopePlugin",
+            Lv2PluginClass::Eq => "EQPlugin",
+            Lv2PluginClass::Expander => "ExpanderPlugin",
+            Lv2PluginClass::Filter => "FilterPlugin",
+            Lv2PluginClass::Flanger => "FlangerPlugin",
+            Lv2PluginClass::Function => "FunctionPlugin",
+            Lv2PluginClass::Gate => "GatePlugin",
+            Lv2PluginClass::Generator => "GeneratorPlugin",
+            Lv2PluginClass::Highpass => "HighpassPlugin",
+            Lv2PluginClass::Instrument => "InstrumentPlugin",
+            Lv2PluginClass::Limiter => "LimiterPlugin",
+            Lv2PluginClass::Lowpass => "LowpassPlugin",
+            Lv2PluginClass::Midi => "MIDIPlugin",
+            Lv2PluginClass::Mixer => "MixerPlugin",
+            Lv2PluginClass::Modulator => "ModulatorPlugin",
+            Lv2PluginClass::MultiEq => "MultiEQPlugin",
+            Lv2PluginClass::Oscillator => "OscillatorPlugin",
+            Lv2PluginClass::ParaEq => "ParaEQPlugin",
+            Lv2PluginClass::Phaser => "PhaserPlugin",
+            Lv2PluginClass::Pitch => "PitchPlugin",
+            Lv2PluginClass::Reverb => "ReverbPlugin",
+            Lv2PluginClass::Simulator => "SimulatorPlugin",
+            Lv2PluginClass::Spatial => "SpatialPlugin",
+            Lv2PluginClass::Spectral => "SpectralPlugin",
+            Lv2PluginClass::Utility => "UtilityPlugin",
+            Lv2PluginClass::Waveshaper => "WaveshaperPlugin",
+            Lv2PluginClass::Custom(uri) => {
+                nih_debug_assert!(
+                    !uri.contains(['<', '>', '"', ' ']),
+                    "'{}' is not a valid URI",
+                    uri
+                );
+
+                return format!("<{uri}>");
+            }
+        };
+
+        format!("lv2:{name}")
+    }
+}
@@ -0,0 +1,102 @@
+use atomic_refcell::AtomicRefMut;
+use std::collections::VecDeque;
+
+use super::wrapper::Wrapper;
+use crate::prelude::{
+    InitContext, Lv2Plugin, PluginApi, PluginNoteEvent, ProcessContext, TrackInfo, Transport,
+};
+
+/// An [`InitContext`] implementation for the wrapper.
+pub(crate) struct WrapperInitContext<'a, P: Lv2Plugin> {
+    pub(super) wrapper: &'a Wrapper<P>,
+}
+
+/// A [`ProcessContext`] implementation for the wrapper. Since LV2 hosts may process blocks that are
+/// larger than the maximum block size the plugin was initialized with, the wrapper may split the
+/// host's block into smaller blocks. The input events for the entire host block are stored in the
+/// same queue, and this context only returns the events that fall within the current block.
+pub(crate) struct WrapperProcessContext<'a, P: Lv2Plugin> {
+    pub(super) wrapper: &'a Wrapper<P>,
+    pub(super) input_events_guard: AtomicRefMut<'a, VecDeque<PluginNoteEvent<P>>>,
+    pub(super) output_events_guard: AtomicRefMut<'a, VecDeque<PluginNoteEvent<P>>>,
+    pub(super) transport: Transport,
+    /// The start of the current block relative to the start of the host's block.
+    pub(super) block_start: usize,
+    /// The end of the current block relative to the start of the host's block, exclusive.
+    pub(super) block_end: usize,
+}
+
+impl<P: Lv2Plugin> InitContext<P> for WrapperInitContext<'_, P> {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Lv2
+    }
+
+    fn track_info(&self) -> Option<TrackInfo> {
+        // LV2 hosts don't provide any track information
+        None
+    }
+
+    fn execute(&self, task: P::BackgroundTask) {
+        (self.wrapper.task_executor.lock())(task);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.wrapper.set_latency_samples(samples)
+    }
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {
+        // This is only supported by CLAP
+    }
+}
+
+impl<P: Lv2Plugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Lv2
+    }
+
+    fn execute_background(&self, task: P::BackgroundTask) {
+        let task_posted = self.wrapper.schedule_background(task);
+        nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
+    }
+
+    fn execute_gui(&self, task: P::BackgroundTask) {
+        // There is no GUI thread, so these tasks are also run on the background thread
+        let task_posted = self.wrapper.schedule_background(task);
+        nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
+    }
+
+    #[inline]
+    fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    fn next_event(&mut self) -> Option<PluginNoteEvent<P>> {
+        match self.input_events_guard.front() {
+            Some(event) if (event.timing() as usize) < self.block_end => {
+                let mut event = self.input_events_guard.pop_front()?;
+                event.subtract_timing(self.block_start as u32);
+
+                Some(event)
+            }
+            _ => None,
+        }
+    }
+
+    fn send_event(&mut self, event: PluginNoteEvent<P>) {
+        // These are written to the output port with the block's offset applied after the block has
+        // been processed
+        self.output_events_guard.push_back(event);
+    }
+
+    fn set_latency_samples(&self, samples: u32) {
+        self.wrapper.set_latency_samples(samples)
+    }
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {
+        // This is only supported by CLAP
+    }
+
+    fn note_names_changed(&self) {
+        // LV2 does not have a way to notify the host about this
+    }
+}
@@ -0,0 +1,249 @@
+//! The mapping between the plugin's audio IO, events, and parameters and LV2's flat list of ports.
+//! This is shared between the Turtle generation and the wrapper itself so the port indices always
+//! agree.
+
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+
+use crate::prelude::AudioIOLayout;
+
+/// A single LV2 port. The port's index is its position in [`PortLayout::ports`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Port {
+    pub kind: PortKind,
+    /// The port's symbol. This needs to be a valid C identifier and it must be unique within the
+    /// plugin. Hosts use this to identify ports in saved state and presets.
+    pub symbol: String,
+    /// The port's human readable name.
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    /// A channel on the main input bus (`bus: None`) or on one of the auxiliary input buses.
+    AudioInput { bus: Option<usize>, channel: usize },
+    /// A channel on the main output bus (`bus: None`) or on one of the auxiliary output buses.
+    AudioOutput { bus: Option<usize>, channel: usize },
+    /// An atom sequence containing MIDI and transport events from the host.
+    EventsInput,
+    /// An atom sequence for the plugin's MIDI output.
+    EventsOutput,
+    /// A control output port reporting the plugin's latency in samples.
+    Latency,
+    /// A control input port for the parameter at this index in the plugin's parameter map.
+    Param(usize),
+}
+
+/// All of a plugin's ports in order. Parameters are added last so adding a parameter does not
+/// change the other ports' indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortLayout {
+    pub ports: Vec<Port>,
+}
+
+impl PortLayout {
+    /// Create the port layout for an audio IO layout and a list of parameter IDs. The events
+    /// input port is always present since it's also used for transport information. The events
+    /// output port is only added if the plugin outputs MIDI.
+    pub fn new<'a>(
+        audio_io_layout: &AudioIOLayout,
+        has_events_output: bool,
+        param_ids: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let mut ports = Vec::new();
+        let mut symbols = HashSet::new();
+        let mut add_port = |kind: PortKind, symbol: String, name: String| {
+            // Port symbols need to be unique, so in the very unlikely case that a sanitized
+            // parameter ID clashes with another port's symbol we'll add a suffix
+            let mut unique_symbol = symbol.clone();
+            let mut suffix = 2;
+            while !symbols.insert(unique_symbol.clone()) {
+                unique_symbol = format!("{symbol}_{suffix}");
+                suffix += 1;
+            }
+
+            ports.push(Port {
+                kind,
+                symbol: unique_symbol,
+                name,
+            });
+        };
+
+        let num_channels = |channels: Option<NonZeroU32>| channels.map_or(0, NonZeroU32::get);
+        let main_input_name = audio_io_layout.main_input_name();
+        for channel in 0..num_channels(audio_io_layout.main_input_channels) {
+            add_port(
+                PortKind::AudioInput {
+                    bus: None,
+                    channel: channel as usize,
+                },
+                format!("in_{}", channel + 1),
+                format!("{main_input_name} {}", channel + 1),
+            );
+        }
+        for (bus, channels) in audio_io_layout.aux_input_ports.iter().enumerate() {
+            let bus_name = audio_io_layout
+                .aux_input_name(bus)
+                .unwrap_or_else(|| format!("Sidechain Input {}", bus + 1));
+            for channel in 0..channels.get() {
+                add_port(
+                    PortKind::AudioInput {
+                        bus: Some(bus),
+                        channel: channel as usize,
+                    },
+                    format!("aux_in_{}_{}", bus + 1, channel + 1),
+                    format!("{bus_name} {}", channel + 1),
+                );
+            }
+        }
+
+        let main_output_name = audio_io_layout.main_output_name();
+        for channel in 0..num_channels(audio_io_layout.main_output_channels) {
+            add_port(
+                PortKind::AudioOutput {
+                    bus: None,
+                    channel: channel as usize,
+                },
+                format!("out_{}", channel + 1),
+                format!("{main_output_name} {}", channel + 1),
+            );
+        }
+        for (bus, channels) in audio_io_layout.aux_output_ports.iter().enumerate() {
+            let bus_name = audio_io_layout
+                .aux_output_name(bus)
+                .unwrap_or_else(|| format!("Auxiliary Output {}", bus + 1));
+            for channel in 0..channels.get() {
+                add_port(
+                    PortKind::AudioOutput {
+                        bus: Some(bus),
+                        channel: channel as usize,
+                    },
+                    format!("aux_out_{}_{}", bus + 1, channel + 1),
+                    format!("{bus_name} {}", channel + 1),
+                );
+            }
+        }
+
+        add_port(
+            PortKind::EventsInput,
+            String::from("events_in"),
+            String::from("Events Input"),
+        );
+        if has_events_output {
+            add_port(
+                PortKind::EventsOutput,
+                String::from("events_out"),
+                String::from("Events Output"),
+            );
+        }
+        add_port(
+            PortKind::Latency,
+            String::from("latency"),
+            String::from("Latency"),
+        );
+
+        for (param_idx, param_id) in param_ids.into_iter().enumerate() {
+            // The name is filled in by the Turtle generator since it's not needed by the wrapper
+            add_port(
+                PortKind::Param(param_idx),
+                port_symbol(param_id),
+                String::new(),
+            );
+        }
+
+        Self { ports }
+    }
+}
+
+/// Convert a parameter ID to a valid port symbol. Symbols may only contain ASCII letters, digits,
+/// and underscores, and they cannot start with a digit.
+fn port_symbol(id: &str) -> String {
+    let mut symbol: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !symbol.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        symbol.insert(0, '_');
+    }
+
+    symbol
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{new_nonzero_u32, PortNames};
+
+    const AUX_INPUT_PORTS: &[NonZeroU32] = &[new_nonzero_u32(1)];
+
+    #[test]
+    fn test_port_order() {
+        let layout = PortLayout::new(
+            &AudioIOLayout {
+                main_input_channels: NonZeroU32::new(2),
+                main_output_channels: NonZeroU32::new(2),
+                aux_input_ports: AUX_INPUT_PORTS,
+                names: PortNames {
+                    aux_inputs: &["Sidechain"],
+                    ..PortNames::const_default()
+                },
+                ..AudioIOLayout::const_default()
+            },
+            true,
+            ["gain"],
+        );
+
+        let kinds: Vec<_> = layout.ports.iter().map(|port| port.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                PortKind::AudioInput {
+                    bus: None,
+                    channel: 0
+                },
+                PortKind::AudioInput {
+                    bus: None,
+                    channel: 1
+                },
+                PortKind::AudioInput {
+                    bus: Some(0),
+                    channel: 0
+                },
+                PortKind::AudioOutput {
+                    bus: None,
+                    channel: 0
+                },
+                PortKind::AudioOutput {
+                    bus: None,
+                    channel: 1
+                },
+                PortKind::EventsInput,
+                PortKind::EventsOutput,
+                PortKind::Latency,
+                PortKind::Param(0),
+            ]
+        );
+        assert_eq!(layout.ports[2].symbol, "aux_in_1_1");
+        assert_eq!(layout.ports[2].name, "Sidechain 1");
+    }
+
+    #[test]
+    fn test_port_symbols() {
+        let layout = PortLayout::new(
+            &AudioIOLayout::const_default(),
+            false,
+            ["gain", "2nd-gain", "latency", "in_1", "in_1"],
+        );
+
+        let symbols: Vec<_> = layout
+            .ports
+            .iter()
+            .filter(|port| matches!(port.kind, PortKind::Param(_)))
+            .map(|port| port.symbol.as_str())
+            .collect();
+        assert_eq!(
+            symbols,
+            ["gain", "_2nd_gain", "latency_2", "in_1", "in_1_2"]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Transport information for LV2 plugins. LV2 hosts send `time:Position` objects through the
+//! plugin's events input port whenever the transport changes, so the wrapper needs to keep track of
+//! the position in between those updates.
+
+use crate::prelude::Transport;
+
+/// The last known transport state, advanced after every processing cycle. Fields are `None` until
+/// the host has sent them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransportState {
+    /// The transport's speed. 0.0 means stopped and 1.0 means playing at normal speed.
+    pub speed: f64,
+    /// The position in the song in samples.
+    pub frame: Option<i64>,
+    /// The tempo in beats per minute, where a beat is `1 / beat_unit` notes.
+    pub beats_per_minute: Option<f64>,
+    /// The time signature's numerator.
+    pub beats_per_bar: Option<f64>,
+    /// The time signature's denominator.
+    pub beat_unit: Option<i32>,
+    /// The current bar, starting at 0.
+    pub bar: Option<i64>,
+    /// The position within the current bar in beats.
+    pub bar_beat: Option<f64>,
+}
+
+impl TransportState {
+    /// Convert this state to NIH-plug's transport information for a block starting
+    /// `sample_offset` samples after the start of the current processing cycle.
+    pub fn to_transport(&self, sample_rate: f32, sample_offset: usize) -> Transport {
+        let mut transport = Transport::new(sample_rate);
+        transport.playing = self.speed != 0.0;
+        transport.tempo = self.beats_per_minute;
+        transport.time_sig_numerator = self
+            .beats_per_bar
+            .map(|beats_per_bar| beats_per_bar.round() as i32);
+        transport.time_sig_denominator = self.beat_unit;
+        transport.pos_samples = self
+            .frame
+            .map(|frame| frame + (sample_offset as f64 * self.speed) as i64);
+
+        if let (Some(bar), Some(bar_beat), Some(beats_per_bar), Some(beat_unit)) =
+            (self.bar, self.bar_beat, self.beats_per_bar, self.beat_unit)
+        {
+            // NIH-plug's beat positions are in quarter notes, while LV2 uses the time signature's
+            // beat unit. This assumes the time signature has not changed since the start of the
+            // song.
+            let quarter_notes_per_beat = 4.0 / beat_unit as f64;
+            let beats = (bar as f64 * beats_per_bar)
+                + bar_beat
+                + self
+                    .beats_elapsed(sample_rate, sample_offset)
+                    .unwrap_or(0.0);
+            let bar_number = (beats / beats_per_bar).floor();
+
+            transport.pos_beats = Some(beats * quarter_notes_per_beat);
+            transport.bar_start_pos_beats =
+                Some(bar_number * beats_per_bar * quarter_notes_per_beat);
+            transport.bar_number = Some(bar_number as i32);
+        }
+
+        transport
+    }
+
+    /// Advance the position by `num_samples` samples at the current speed.
+    pub fn advance(&mut self, sample_rate: f32, num_samples: usize) {
+        if self.speed == 0.0 {
+            return;
+        }
+
+        if let Some(frame) = &mut self.frame {
+            *frame += (num_samples as f64 * self.speed) as i64;
+        }
+
+        if let (Some(elapsed), Some(bar), Some(bar_beat), Some(beats_per_bar)) = (
+            self.beats_elapsed(sample_rate, num_samples),
+            &mut self.bar,
+            &mut self.bar_beat,
+            self.beats_per_bar,
+        ) {
+            *bar_beat += elapsed;
+            if beats_per_bar > 0.0 && *bar_beat >= beats_per_bar {
+                let elapsed_bars = (*bar_beat / beats_per_bar).floor();
+                *bar += elapsed_bars as i64;
+                *bar_beat -= elapsed_bars * beats_per_bar;
+            }
+        }
+    }
+
+    /// The number of beats in the time signature's beat unit that pass in `num_samples` samples,
+    /// if the tempo is known.
+    fn beats_elapsed(&self, sample_rate: f32, num_samples: usize) -> Option<f64> {
+        self.beats_per_minute.map(|beats_per_minute| {
+            num_samples as f64 * self.speed / sample_rate as f64 / 60.0 * beats_per_minute
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing_state() -> TransportState {
+        TransportState {
+            speed: 1.0,
+            frame: Some(48000),
+            beats_per_minute: Some(120.0),
+            beats_per_bar: Some(6.0),
+            beat_unit: Some(8),
+            bar: Some(2),
+            bar_beat: Some(1.5),
+        }
+    }
+
+    #[test]
+    fn test_to_transport() {
+        let transport = playing_state().to_transport(48000.0, 0);
+        assert!(transport.playing);
+        assert_eq!(transport.tempo, Some(120.0));
+        assert_eq!(transport.time_sig_numerator, Some(6));
+        assert_eq!(transport.time_sig_denominator, Some(8));
+        assert_eq!(transport.pos_samples(), Some(48000));
+        // Two bars of 6/8 and 1.5 eighth notes
+        assert_eq!(transport.pos_beats(), Some(6.75));
+        assert_eq!(transport.bar_start_pos_beats(), Some(6.0));
+        assert_eq!(transport.bar_number(), Some(2));
+
+        // Half a second later at 120 BPM is one more eighth note
+        let transport = playing_state().to_transport(48000.0, 24000);
+        assert_eq!(transport.pos_samples(), Some(72000));
+        assert_eq!(transport.pos_beats(), Some(7.25));
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut state = playing_state();
+        // Four seconds at 120 BPM is eight eighth notes, which crosses into the next bar
+        state.advance(48000.0, 4 * 48000);
+        assert_eq!(state.frame, Some(5 * 48000));
+        assert_eq!(state.bar, Some(3));
+        assert_eq!(state.bar_beat, Some(3.5));
+
+        let mut stopped_state = TransportState {
+            speed: 0.0,
+            ..playing_state()
+        };
+        stopped_state.advance(48000.0, 48000);
+        assert_eq!(stopped_state.frame, Some(48000));
+        assert!(!stopped_state.to_transport(48000.0, 0).playing);
+    }
+}
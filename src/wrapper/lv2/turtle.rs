@@ -0,0 +1,445 @@
+//! Generates the Turtle files that describe an LV2 bundle. LV2 hosts read these files to discover
+//! plugins and their ports without loading the plugin library. These files are written by `cargo
+//! xtask bundle` through the function exported by `nih_export_lv2!()`.
+
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::ports::{PortKind, PortLayout};
+use crate::prelude::{Lv2Plugin, MidiConfig, ParamFlags, ParamPtr, Params};
+
+/// Stepped parameters with at most this many steps get a scale point with a label for every value.
+/// Parameters with more steps only get a step count.
+const MAX_SCALE_POINTS: usize = 32;
+
+const PREFIXES: &str = "\
+@prefix atom:   <http://lv2plug.in/ns/ext/atom#> .
+@prefix bufsz:  <http://lv2plug.in/ns/ext/buf-size#> .
+@prefix doap:   <http://usefulinc.com/ns/doap#> .
+@prefix foaf:   <http://xmlns.com/foaf/0.1/> .
+@prefix lv2:    <http://lv2plug.in/ns/lv2core#> .
+@prefix midi:   <http://lv2plug.in/ns/ext/midi#> .
+@prefix opts:   <http://lv2plug.in/ns/ext/options#> .
+@prefix pprops: <http://lv2plug.in/ns/ext/port-props#> .
+@prefix rdf:    <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .
+@prefix rdfs:   <http://www.w3.org/2000/01/rdf-schema#> .
+@prefix state:  <http://lv2plug.in/ns/ext/state#> .
+@prefix time:   <http://lv2plug.in/ns/ext/time#> .
+@prefix urid:   <http://lv2plug.in/ns/ext/urid#> .
+";
+
+/// The Turtle description for a single plugin in a bundle.
+pub struct PluginTurtle {
+    uri: &'static str,
+    /// The name of the file the plugin's description is written to, relative to the bundle.
+    file_name: String,
+    contents: String,
+}
+
+impl PluginTurtle {
+    /// Generate the Turtle description for a plugin. This creates a default instance of the plugin
+    /// to query its parameters.
+    pub fn for_plugin<P: Lv2Plugin>() -> Self {
+        let plugin = P::default();
+        let params = plugin.params();
+
+        Self {
+            uri: P::LV2_URI,
+            file_name: format!("{}.ttl", file_stem(P::NAME)),
+            contents: plugin_turtle::<P>(params),
+        }
+    }
+}
+
+/// Write the bundle's `manifest.ttl` file and the Turtle files for every plugin to `bundle_path`.
+/// `binary_name` is the plugin library's file name within the bundle.
+pub fn write_bundle(bundle_path: &Path, binary_name: &str, plugins: &[PluginTurtle]) -> Result<()> {
+    fs::write(
+        bundle_path.join("manifest.ttl"),
+        manifest_turtle(binary_name, plugins),
+    )
+    .context("Could not write 'manifest.ttl'")?;
+    for plugin in plugins {
+        fs::write(bundle_path.join(&plugin.file_name), &plugin.contents)
+            .with_context(|| format!("Could not write '{}'", plugin.file_name))?;
+    }
+
+    Ok(())
+}
+
+/// The bundle's manifest. This only lists the plugins and where to find their descriptions so the
+/// host can scan the bundle quickly.
+fn manifest_turtle(binary_name: &str, plugins: &[PluginTurtle]) -> String {
+    let mut turtle = String::from(
+        "@prefix lv2:  <http://lv2plug.in/ns/lv2core#> .\n\
+         @prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .\n",
+    );
+    for plugin in plugins {
+        write!(
+            turtle,
+            "\n<{}>\n    a lv2:Plugin ;\n    lv2:binary <{}> ;\n    rdfs:seeAlso <{}> .\n",
+            plugin.uri,
+            relative_iri(binary_name),
+            plugin.file_name
+        )
+        .unwrap();
+    }
+
+    turtle
+}
+
+/// The full description for a plugin, including its ports.
+fn plugin_turtle<P: Lv2Plugin>(params: std::sync::Arc<dyn Params>) -> String {
+    let param_map = params.param_map();
+    let audio_io_layout = P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default();
+    let port_layout = PortLayout::new(
+        &audio_io_layout,
+        P::MIDI_OUTPUT >= MidiConfig::Basic,
+        param_map.iter().map(|(param_id, _, _)| param_id.as_str()),
+    );
+
+    let mut turtle = String::from(PREFIXES);
+    writeln!(turtle, "\n<{}>", P::LV2_URI).unwrap();
+
+    let classes: Vec<String> = std::iter::once(String::from("lv2:Plugin"))
+        .chain(P::LV2_CLASSES.iter().map(|class| class.as_turtle()))
+        .collect();
+    writeln!(turtle, "    a {} ;", classes.join(", ")).unwrap();
+    writeln!(turtle, "    doap:name {} ;", string_literal(P::NAME)).unwrap();
+    writeln!(turtle, "    doap:maintainer [").unwrap();
+    writeln!(turtle, "        foaf:name {} ;", string_literal(P::VENDOR)).unwrap();
+    if !P::URL.is_empty() {
+        writeln!(turtle, "        foaf:homepage <{}> ;", P::URL).unwrap();
+    }
+    if !P::EMAIL.is_empty() {
+        writeln!(turtle, "        foaf:mbox <mailto:{}> ;", P::EMAIL).unwrap();
+    }
+    writeln!(turtle, "    ] ;").unwrap();
+    // Hosts use these to pick the most recent version if multiple versions are installed
+    if let Ok(version) = semver::Version::parse(P::VERSION) {
+        writeln!(turtle, "    lv2:minorVersion {} ;", version.minor).unwrap();
+        writeln!(turtle, "    lv2:microVersion {} ;", version.patch).unwrap();
+    }
+    writeln!(turtle, "    lv2:requiredFeature urid:map ;").unwrap();
+    writeln!(
+        turtle,
+        "    lv2:optionalFeature lv2:hardRTCapable, opts:options, bufsz:boundedBlockLength ;"
+    )
+    .unwrap();
+    writeln!(
+        turtle,
+        "    opts:supportedOption bufsz:maxBlockLength, bufsz:nominalBlockLength ;"
+    )
+    .unwrap();
+    writeln!(turtle, "    lv2:extensionData state:interface ;").unwrap();
+
+    let ports: Vec<String> = port_layout
+        .ports
+        .iter()
+        .enumerate()
+        .map(|(port_idx, port)| {
+            let mut properties = Vec::new();
+            match port.kind {
+                PortKind::AudioInput { bus, .. } => {
+                    properties.push(String::from("a lv2:InputPort, lv2:AudioPort"));
+                    // Auxiliary ports that are not connected are passed to the plugin as
+                    // inactive buffers
+                    if bus.is_some() {
+                        properties.push(String::from(
+                            "lv2:portProperty lv2:isSideChain, lv2:connectionOptional",
+                        ));
+                    }
+                }
+                PortKind::AudioOutput { bus, .. } => {
+                    properties.push(String::from("a lv2:OutputPort, lv2:AudioPort"));
+                    if bus.is_some() {
+                        properties.push(String::from("lv2:portProperty lv2:connectionOptional"));
+                    }
+                }
+                PortKind::EventsInput => {
+                    properties.push(String::from("a lv2:InputPort, atom:AtomPort"));
+                    properties.push(String::from("atom:bufferType atom:Sequence"));
+                    if P::MIDI_INPUT >= MidiConfig::Basic {
+                        properties
+                            .push(String::from("atom:supports midi:MidiEvent, time:Position"));
+                    } else {
+                        properties.push(String::from("atom:supports time:Position"));
+                    }
+                    properties.push(String::from("lv2:designation lv2:control"));
+                }
+                PortKind::EventsOutput => {
+                    properties.push(String::from("a lv2:OutputPort, atom:AtomPort"));
+                    properties.push(String::from("atom:bufferType atom:Sequence"));
+                    properties.push(String::from("atom:supports midi:MidiEvent"));
+                }
+                PortKind::Latency => {
+                    properties.push(String::from("a lv2:OutputPort, lv2:ControlPort"));
+                    properties.push(String::from("lv2:designation lv2:latency"));
+                    properties.push(String::from(
+                        "lv2:portProperty lv2:reportsLatency, lv2:integer, pprops:notOnGUI",
+                    ));
+                }
+                PortKind::Param(_) => {
+                    properties.push(String::from("a lv2:InputPort, lv2:ControlPort"));
+                }
+            }
+
+            properties.push(format!("lv2:index {port_idx}"));
+            properties.push(format!("lv2:symbol {}", string_literal(&port.symbol)));
+            match port.kind {
+                PortKind::Param(param_idx) => {
+                    let (_, param_ptr, _) = &param_map[param_idx];
+                    properties.extend(unsafe { param_properties(*param_ptr) });
+                }
+                _ => properties.push(format!("lv2:name {}", string_literal(&port.name))),
+            }
+
+            format!("[\n        {} ;\n    ]", properties.join(" ;\n        "))
+        })
+        .collect();
+    writeln!(turtle, "    lv2:port {} .", ports.join(" , ")).unwrap();
+
+    turtle
+}
+
+/// The name, range, and port properties for a parameter's control port. Parameters are exposed
+/// using their normalized values.
+unsafe fn param_properties(param_ptr: ParamPtr) -> Vec<String> {
+    let mut properties = vec![
+        format!("lv2:name {}", string_literal(param_ptr.name())),
+        format!("lv2:default {:?}", param_ptr.default_normalized_value()),
+        String::from("lv2:minimum 0.0"),
+        String::from("lv2:maximum 1.0"),
+    ];
+
+    let mut port_properties = Vec::new();
+    match param_ptr.step_count() {
+        Some(1) => port_properties.push("lv2:toggled"),
+        Some(step_count) if step_count <= MAX_SCALE_POINTS => {
+            port_properties.push("lv2:enumeration");
+
+            let scale_points: Vec<String> = (0..=step_count)
+                .map(|step| {
+                    let normalized = step as f32 / step_count as f32;
+                    format!(
+                        "[\n            rdfs:label {} ;\n            rdf:value {:?} ;\n        ]",
+                        string_literal(&param_ptr.normalized_value_to_string(normalized, true)),
+                        normalized
+                    )
+                })
+                .collect();
+            properties.push(format!("lv2:scalePoint {}", scale_points.join(" , ")));
+        }
+        Some(step_count) => properties.push(format!("pprops:rangeSteps {}", step_count + 1)),
+        None => (),
+    }
+
+    let flags = param_ptr.flags();
+    if flags.intersects(ParamFlags::HIDDEN | ParamFlags::HIDE_IN_GENERIC_UI) {
+        port_properties.push("pprops:notOnGUI");
+    }
+    if flags.contains(ParamFlags::NON_AUTOMATABLE) {
+        port_properties.push("pprops:notAutomatic");
+    }
+    if !port_properties.is_empty() {
+        properties.push(format!("lv2:portProperty {}", port_properties.join(", ")));
+    }
+
+    properties
+}
+
+/// Percent-encode a file name so it can be used as a relative IRI. Bundle names may contain spaces.
+fn relative_iri(file_name: &str) -> String {
+    let mut iri = String::with_capacity(file_name.len());
+    for byte in file_name.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            iri.push(byte as char);
+        } else {
+            write!(iri, "%{byte:02X}").unwrap();
+        }
+    }
+
+    iri
+}
+
+/// Format a string as a Turtle string literal.
+fn string_literal(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+
+    literal
+}
+
+/// A file name for a plugin's Turtle file based on the plugin's name.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[derive(Default)]
+    struct TestPlugin {
+        params: Arc<TestParams>,
+    }
+
+    struct TestParams {
+        gain: FloatParam,
+        mode: IntParam,
+        bypass: BoolParam,
+    }
+
+    unsafe impl Params for TestParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            vec![
+                (String::from("gain"), self.gain.as_ptr(), String::new()),
+                (String::from("mode"), self.mode.as_ptr(), String::new()),
+                (String::from("bypass"), self.bypass.as_ptr(), String::new()),
+            ]
+        }
+    }
+
+    impl Default for TestParams {
+        fn default() -> Self {
+            Self {
+                gain: FloatParam::new(
+                    "Gain \"dB\"",
+                    0.5,
+                    FloatRange::Linear { min: 0.0, max: 1.0 },
+                ),
+                mode: IntParam::new("Mode", 1, IntRange::Linear { min: 0, max: 2 })
+                    .hide_in_generic_ui(),
+                bypass: BoolParam::new("Bypass", false).non_automatable(),
+            }
+        }
+    }
+
+    impl Plugin for TestPlugin {
+        const NAME: &'static str = "Test Plugin";
+        const VENDOR: &'static str = "Moist Plugins GmbH";
+        const URL: &'static str = "https://example.com";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "1.2.3";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        }];
+        const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            self.params.clone()
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+    }
+
+    impl Lv2Plugin for TestPlugin {
+        const LV2_URI: &'static str = "https://example.com/plugins/test";
+        const LV2_CLASSES: &'static [Lv2PluginClass] = &[
+            Lv2PluginClass::Utility,
+            Lv2PluginClass::Custom("https://example.com/classes#Test"),
+        ];
+    }
+
+    #[test]
+    fn test_manifest() {
+        let plugin = PluginTurtle::for_plugin::<TestPlugin>();
+        assert_eq!(plugin.file_name, "test_plugin.ttl");
+
+        let manifest = manifest_turtle("libtest.so", &[plugin]);
+        assert!(manifest.contains(
+            "<https://example.com/plugins/test>\n    a lv2:Plugin ;\n    lv2:binary <libtest.so> \
+             ;\n    rdfs:seeAlso <test_plugin.ttl> .\n"
+        ));
+
+        // Bundle names may contain spaces
+        let manifest = manifest_turtle(
+            "Test Plugin.so",
+            &[PluginTurtle::for_plugin::<TestPlugin>()],
+        );
+        assert!(manifest.contains("    lv2:binary <Test%20Plugin.so> ;\n"));
+    }
+
+    #[test]
+    fn test_plugin_description() {
+        let turtle = PluginTurtle::for_plugin::<TestPlugin>().contents;
+
+        assert!(turtle.contains(
+            "    a lv2:Plugin, lv2:UtilityPlugin, <https://example.com/classes#Test> ;\n"
+        ));
+        assert!(turtle.contains("    doap:name \"Test Plugin\" ;\n"));
+        assert!(turtle.contains("        foaf:homepage <https://example.com> ;\n"));
+        assert!(!turtle.contains("foaf:mbox"));
+        assert!(turtle.contains("    lv2:minorVersion 2 ;\n    lv2:microVersion 3 ;\n"));
+
+        // Two inputs, two outputs, the events input, the latency output, and three parameters
+        for port_idx in 0..9 {
+            assert!(turtle.contains(&format!("lv2:index {port_idx} ;")));
+        }
+        assert!(!turtle.contains("lv2:index 9 ;"));
+        assert!(!turtle.contains("events_out"));
+        assert!(turtle.contains("atom:supports midi:MidiEvent, time:Position"));
+        assert!(turtle.ends_with("    ] .\n"));
+    }
+
+    #[test]
+    fn test_param_ports() {
+        let turtle = PluginTurtle::for_plugin::<TestPlugin>().contents;
+
+        assert!(turtle.contains(
+            "lv2:index 6 ;\n        lv2:symbol \"gain\" ;\n        lv2:name \"Gain \\\"dB\\\"\" \
+             ;\n        lv2:default 0.5 ;\n        lv2:minimum 0.0 ;\n        lv2:maximum 1.0 ;\n"
+        ));
+        assert!(turtle.contains(
+            "lv2:scalePoint [\n            rdfs:label \"0\" ;\n            rdf:value 0.0 ;\n        \
+             ]"
+        ));
+        assert!(turtle.contains("rdf:value 0.5 ;"));
+        assert!(turtle.contains("lv2:portProperty lv2:enumeration, pprops:notOnGUI ;"));
+        assert!(turtle.contains("lv2:portProperty lv2:toggled, pprops:notAutomatic ;"));
+    }
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(
+            string_literal("a \"b\" \\ c\n"),
+            "\"a \\\"b\\\" \\\\ c\\n\""
+        );
+    }
+}
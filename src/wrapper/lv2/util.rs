@@ -0,0 +1,57 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use super::sys::{LV2_Feature, LV2_URID_Map, LV2_URID};
+
+/// Early exit out of a function with the specified return value when one of the passed pointers is
+/// null.
+macro_rules! check_null_ptr {
+    ($ret:expr, $ptr:expr $(, $ptrs:expr)* $(, )?) => {
+        // Clippy doesn't understand it when we use a unit in our `check_null_ptr!()` macro, even
+        // if we explicitly pattern match on that unit
+        #[allow(clippy::unused_unit)]
+        if $ptr.is_null() $(|| $ptrs.is_null())* {
+            nih_debug_assert_failure!("Null pointer passed to function");
+            return $ret;
+        }
+    };
+}
+
+/// Find a host feature by its URI. `uri` must be a null terminated URI from
+/// [`sys`][super::sys].
+///
+/// # Safety
+///
+/// `features` must be a null terminated array of valid feature pointers as passed to the plugin's
+/// `instantiate()` function.
+pub unsafe fn find_feature(features: *const *const LV2_Feature, uri: &[u8]) -> Option<*mut ()> {
+    let uri = CStr::from_bytes_with_nul(uri).expect("Feature URIs must be null terminated");
+    if features.is_null() {
+        return None;
+    }
+
+    let mut feature_ptr = features;
+    while !(*feature_ptr).is_null() {
+        let feature = &**feature_ptr;
+        if !feature.URI.is_null() && CStr::from_ptr(feature.URI) == uri {
+            return Some(feature.data as *mut ());
+        }
+
+        feature_ptr = feature_ptr.add(1);
+    }
+
+    None
+}
+
+/// Map a null terminated URI to a URID using the host's `urid:map` feature. Returns `None` if the
+/// host's map function is a null pointer.
+///
+/// # Safety
+///
+/// `map` must be the valid `LV2_URID_Map` provided by the host.
+pub unsafe fn map_uri(map: &LV2_URID_Map, uri: &[u8]) -> Option<LV2_URID> {
+    nih_debug_assert_eq!(uri.last(), Some(&0), "URIs must be null terminated");
+
+    map.map
+        .map(|map_fn| map_fn(map.handle, uri.as_ptr() as *const c_char))
+}
@@ -0,0 +1,1001 @@
+use atomic_refcell::{AtomicRefCell, AtomicRefMut};
+use parking_lot::Mutex;
+use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use super::atom::{self, NumberUrids, SequenceWriter};
+use super::context::{WrapperInitContext, WrapperProcessContext};
+use super::ports::{PortKind, PortLayout};
+use super::sys::*;
+use super::transport::TransportState;
+use super::util::{find_feature, map_uri};
+use crate::audio_setup::AudioIOLayoutsValidator;
+use crate::event_loop::{BackgroundThread, MainThreadExecutor};
+use crate::midi::mpe::MpeTranslator;
+use crate::midi::MidiResult;
+use crate::prelude::{
+    AudioIOLayout, AuxiliaryBuffers, BufferConfig, Lv2Plugin, MidiConfig, NoteEvent, ParamPtr,
+    Params, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor,
+};
+use crate::util::permit_alloc;
+use crate::wrapper::state::prepared::PreparedState;
+use crate::wrapper::state::{self, PluginState};
+use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+use crate::wrapper::util::{
+    clamp_input_event_timing, clamp_output_event_timing, hash_param_id, process_wrapper,
+};
+
+/// The maximum block size used when the host does not tell the plugin about its maximum block size
+/// through the options interface. Larger blocks are split up into smaller blocks.
+const DEFAULT_MAX_BUFFER_SIZE: u32 = 8192;
+
+/// The LV2 plugin instance. The host's `LV2_Handle` is a pointer to this object created using
+/// [`Arc::into_raw()`].
+pub struct Wrapper<P: Lv2Plugin> {
+    /// The wrapped plugin instance.
+    plugin: Mutex<P>,
+    /// The plugin's background task executor closure.
+    pub(super) task_executor: Mutex<TaskExecutor<P>>,
+    /// The plugin's parameters. These are fetched once during initialization. That way the
+    /// `ParamPtr`s are guaranteed to live at least as long as this object and we can interact with
+    /// the `Params` object without having to acquire a lock on `plugin`.
+    params: Arc<dyn Params>,
+    /// The plugin's parameters, in the same order as their control ports.
+    param_ptrs: Vec<ParamPtr>,
+    /// Mappings from parameter hashes to pointers and from string IDs to hashes. These are only
+    /// used for saving and restoring state.
+    param_by_hash: HashMap<u32, ParamPtr>,
+    param_id_to_hash: HashMap<String, u32>,
+
+    /// A background thread for running the plugin's background and GUI tasks. LV2 does not have a
+    /// way to run code on the host's GUI thread, so both kinds of tasks run here.
+    background_thread: BackgroundThread<P::BackgroundTask, Self>,
+
+    /// The URIDs for the URIs the wrapper needs to interact with, mapped using the host's
+    /// `urid:map` feature when the plugin was instantiated.
+    urids: Urids,
+    /// The mapping from the plugin's audio IO, events, and parameters to LV2 ports.
+    port_layout: PortLayout,
+    /// The audio IO layout used by the plugin. LV2 plugins have a fixed set of ports, so this is
+    /// always the plugin's first audio IO layout.
+    audio_io_layout: AudioIOLayout,
+    /// The buffer configuration passed to the plugin. The maximum buffer size comes from the host's
+    /// options, if it provides those.
+    buffer_config: BufferConfig,
+    /// Whether the plugin was successfully initialized in the last `activate()` call.
+    is_initialized: AtomicBool,
+
+    /// The host's port buffers, set through `connect_port()`.
+    ports: AtomicRefCell<PortConnections>,
+    /// Contains slices for the plugin's outputs. You can't directly create a nested slice from a
+    /// pointer to pointers, so this needs to be preallocated in `instantiate()`.
+    buffer_manager: AtomicRefCell<BufferManager>,
+    /// The last value read from each parameter port. A parameter is only updated when its port's
+    /// value changes, so a restored state is not immediately overwritten by the host's old port
+    /// values. These are initialized to NaN so the first `run()` call applies all port values.
+    last_param_port_values: AtomicRefCell<Vec<f32>>,
+
+    /// The incoming events for the plugin, if `P::MIDI_INPUT` is set to `MidiConfig::Basic` or
+    /// higher. These are read from the events input port at the start of `run()`.
+    input_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
+    /// Stores any events the plugin has output during the current processing cycle.
+    output_events: AtomicRefCell<VecDeque<PluginNoteEvent<P>>>,
+    /// Used to keep track of note IDs and MPE member channels when `P::MIDI_INPUT` is set to
+    /// `MidiConfig::Mpe`.
+    mpe_translator: AtomicRefCell<MpeTranslator>,
+    /// The last transport information sent by the host, advanced after every `run()` call.
+    transport: AtomicRefCell<TransportState>,
+    /// The current latency in samples, reported to the host through the latency port.
+    current_latency: AtomicU32,
+}
+
+/// The URIDs used by the wrapper.
+struct Urids {
+    atom_blank: LV2_URID,
+    atom_chunk: LV2_URID,
+    atom_object: LV2_URID,
+    atom_sequence: LV2_URID,
+    atom_beat_time: LV2_URID,
+    midi_event: LV2_URID,
+    numbers: NumberUrids,
+    time_position: LV2_URID,
+    time_bar: LV2_URID,
+    time_bar_beat: LV2_URID,
+    time_beat_unit: LV2_URID,
+    time_beats_per_bar: LV2_URID,
+    time_beats_per_minute: LV2_URID,
+    time_frame: LV2_URID,
+    time_speed: LV2_URID,
+    /// The key the plugin's state is stored under. This is `<LV2_URI>#state`.
+    state_key: LV2_URID,
+}
+
+/// The buffers connected to the plugin's ports. The audio ports are grouped per bus so they can be
+/// passed to the [`BufferManager`] directly. Unconnected ports are null pointers.
+struct PortConnections {
+    main_input: Vec<*mut f32>,
+    main_output: Vec<*mut f32>,
+    aux_inputs: Vec<Vec<*mut f32>>,
+    aux_outputs: Vec<Vec<*mut f32>>,
+    events_input: *const LV2_Atom_Sequence,
+    events_output: *mut LV2_Atom_Sequence,
+    latency: *mut f32,
+    params: Vec<*const f32>,
+}
+
+// The pointers are only dereferenced from the host's audio threading class
+unsafe impl Send for PortConnections {}
+unsafe impl Sync for PortConnections {}
+
+impl<P: Lv2Plugin> MainThreadExecutor<P::BackgroundTask> for Wrapper<P> {
+    fn execute(&self, task: P::BackgroundTask, _is_gui_thread: bool) {
+        (self.task_executor.lock())(task);
+    }
+}
+
+impl<P: Lv2Plugin> Wrapper<P> {
+    const STATE_INTERFACE: LV2_State_Interface = LV2_State_Interface {
+        save: Some(Self::state_save),
+        restore: Some(Self::state_restore),
+    };
+
+    /// Create a new plugin instance. Returns `None` if the host does not support the required
+    /// features.
+    ///
+    /// # Safety
+    ///
+    /// `features` must be the null terminated feature array passed to `instantiate()`.
+    unsafe fn new(sample_rate: f32, features: *const *const LV2_Feature) -> Option<Arc<Self>> {
+        let () = AudioIOLayoutsValidator::<P>::ASSERT_VALID;
+
+        let map = match find_feature(features, LV2_URID__map) {
+            Some(map) if !map.is_null() => &*(map as *const LV2_URID_Map),
+            _ => {
+                nih_log!("The host does not support the required urid:map feature");
+                return None;
+            }
+        };
+        if map.map.is_none() {
+            nih_log!("The host's urid:map feature does not contain a map function");
+            return None;
+        }
+
+        let state_key =
+            CString::new(format!("{}#state", P::LV2_URI)).expect("`LV2_URI` contained null bytes");
+        let urids = Urids {
+            atom_blank: map_uri(map, LV2_ATOM__Blank)?,
+            atom_chunk: map_uri(map, LV2_ATOM__Chunk)?,
+            atom_object: map_uri(map, LV2_ATOM__Object)?,
+            atom_sequence: map_uri(map, LV2_ATOM__Sequence)?,
+            atom_beat_time: map_uri(map, LV2_ATOM__beatTime)?,
+            midi_event: map_uri(map, LV2_MIDI__MidiEvent)?,
+            numbers: NumberUrids {
+                int: map_uri(map, LV2_ATOM__Int)?,
+                long: map_uri(map, LV2_ATOM__Long)?,
+                float: map_uri(map, LV2_ATOM__Float)?,
+                double: map_uri(map, LV2_ATOM__Double)?,
+            },
+            time_position: map_uri(map, LV2_TIME__Position)?,
+            time_bar: map_uri(map, LV2_TIME__bar)?,
+            time_bar_beat: map_uri(map, LV2_TIME__barBeat)?,
+            time_beat_unit: map_uri(map, LV2_TIME__beatUnit)?,
+            time_beats_per_bar: map_uri(map, LV2_TIME__beatsPerBar)?,
+            time_beats_per_minute: map_uri(map, LV2_TIME__beatsPerMinute)?,
+            time_frame: map_uri(map, LV2_TIME__frame)?,
+            time_speed: map_uri(map, LV2_TIME__speed)?,
+            state_key: map_uri(map, state_key.as_bytes_with_nul())?,
+        };
+
+        // The maximum block size is optional in LV2. If the host doesn't provide one then we'll
+        // split larger blocks up instead.
+        let max_buffer_size = find_feature(features, LV2_OPTIONS__options)
+            .and_then(|options| {
+                Self::max_buffer_size_from_options(
+                    map,
+                    &urids.numbers,
+                    options as *const LV2_Options_Option,
+                )
+            })
+            .unwrap_or(DEFAULT_MAX_BUFFER_SIZE);
+
+        let mut plugin = P::default();
+        let task_executor = Mutex::new(plugin.task_executor());
+
+        // The parameters are exposed as control ports in the same order as the parameter map
+        let params = plugin.params();
+        let param_map = params.param_map();
+        let param_ptrs: Vec<ParamPtr> = param_map.iter().map(|(_, ptr, _)| *ptr).collect();
+        let param_by_hash = param_map
+            .iter()
+            .map(|(id, ptr, _)| (hash_param_id(id), *ptr))
+            .collect();
+        let param_id_to_hash = param_map
+            .iter()
+            .map(|(id, _, _)| (id.clone(), hash_param_id(id)))
+            .collect();
+
+        let audio_io_layout = P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default();
+        let port_layout = PortLayout::new(
+            &audio_io_layout,
+            P::MIDI_OUTPUT >= MidiConfig::Basic,
+            param_map.iter().map(|(id, _, _)| id.as_str()),
+        );
+        let ports = PortConnections {
+            main_input: vec![
+                std::ptr::null_mut();
+                audio_io_layout.main_input_channels.map_or(0, |c| c.get()) as usize
+            ],
+            main_output: vec![
+                std::ptr::null_mut();
+                audio_io_layout.main_output_channels.map_or(0, |c| c.get()) as usize
+            ],
+            aux_inputs: audio_io_layout
+                .aux_input_ports
+                .iter()
+                .map(|channels| vec![std::ptr::null_mut(); channels.get() as usize])
+                .collect(),
+            aux_outputs: audio_io_layout
+                .aux_output_ports
+                .iter()
+                .map(|channels| vec![std::ptr::null_mut(); channels.get() as usize])
+                .collect(),
+            events_input: std::ptr::null(),
+            events_output: std::ptr::null_mut(),
+            latency: std::ptr::null_mut(),
+            params: vec![std::ptr::null(); param_ptrs.len()],
+        };
+
+        let buffer_config = BufferConfig {
+            sample_rate,
+            min_buffer_size: None,
+            max_buffer_size,
+            process_mode: ProcessMode::Realtime,
+        };
+        let last_param_port_values = vec![f32::NAN; param_ptrs.len()];
+
+        let wrapper = Arc::new_cyclic(|weak_self| Self {
+            plugin: Mutex::new(plugin),
+            task_executor,
+            params,
+            param_ptrs,
+            param_by_hash,
+            param_id_to_hash,
+
+            background_thread: BackgroundThread::get_or_create(weak_self.clone()),
+
+            urids,
+            port_layout,
+            audio_io_layout,
+            buffer_config,
+            is_initialized: AtomicBool::new(false),
+
+            ports: AtomicRefCell::new(ports),
+            buffer_manager: AtomicRefCell::new(BufferManager::for_audio_io_layout(
+                max_buffer_size as usize,
+                audio_io_layout,
+            )),
+            last_param_port_values: AtomicRefCell::new(last_param_port_values),
+
+            // Some hosts send more than 512 events per block, but this should be plenty to avoid
+            // allocations in most cases
+            input_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
+            output_events: AtomicRefCell::new(VecDeque::with_capacity(512)),
+            mpe_translator: AtomicRefCell::new(MpeTranslator::default()),
+            transport: AtomicRefCell::new(TransportState::default()),
+            current_latency: AtomicU32::new(0),
+        });
+
+        Some(wrapper)
+    }
+
+    /// Read the maximum block size from the host's options, falling back to the nominal block
+    /// size.
+    unsafe fn max_buffer_size_from_options(
+        map: &LV2_URID_Map,
+        numbers: &NumberUrids,
+        options: *const LV2_Options_Option,
+    ) -> Option<u32> {
+        if options.is_null() {
+            return None;
+        }
+
+        let max_block_length = map_uri(map, LV2_BUF_SIZE__maxBlockLength)?;
+        let nominal_block_length = map_uri(map, LV2_BUF_SIZE__nominalBlockLength)?;
+        let mut max_buffer_size = None;
+        let mut nominal_buffer_size = None;
+
+        // The options array is terminated by an option with a zero key and a null value
+        let mut option_ptr = options;
+        while (*option_ptr).key != 0 || !(*option_ptr).value.is_null() {
+            let option = &*option_ptr;
+            if !option.value.is_null() {
+                let value =
+                    std::slice::from_raw_parts(option.value as *const u8, option.size as usize);
+                let size = atom::read_number(numbers, option.type_, value)
+                    .filter(|size| *size >= 1.0)
+                    .map(|size| size as u32);
+                if option.key == max_block_length {
+                    max_buffer_size = size;
+                } else if option.key == nominal_block_length {
+                    nominal_buffer_size = size;
+                }
+            }
+
+            option_ptr = option_ptr.add(1);
+        }
+
+        max_buffer_size.or(nominal_buffer_size)
+    }
+
+    /// # Note
+    ///
+    /// The lock on the plugin must be dropped before this object is dropped to avoid deadlocks
+    /// caused by reentrant function calls.
+    fn make_init_context(&self) -> WrapperInitContext<'_, P> {
+        WrapperInitContext { wrapper: self }
+    }
+
+    fn make_process_context<'a>(
+        &'a self,
+        input_events_guard: AtomicRefMut<'a, VecDeque<PluginNoteEvent<P>>>,
+        block_start: usize,
+        block_end: usize,
+    ) -> WrapperProcessContext<'a, P> {
+        WrapperProcessContext {
+            wrapper: self,
+            input_events_guard,
+            output_events_guard: self.output_events.borrow_mut(),
+            transport: self
+                .transport
+                .borrow()
+                .to_transport(self.buffer_config.sample_rate, block_start),
+            block_start,
+            block_end,
+        }
+    }
+
+    pub fn schedule_background(&self, task: P::BackgroundTask) -> bool {
+        self.background_thread.schedule(task)
+    }
+
+    pub fn set_latency_samples(&self, samples: u32) {
+        // The host reads this from the latency port after the next `run()` call
+        self.current_latency.store(samples, Ordering::SeqCst);
+    }
+
+    /// Immediately set the plugin state. If the plugin was already initialized, then it can swap in
+    /// the resources it prepared in [`Plugin::prepare_state()`], and it's reinitialized otherwise.
+    /// LV2 hosts never restore state while the plugin is processing audio, so unlike in the other
+    /// wrappers the state is always applied right here. Returns `false` if the plugin failed to
+    /// load the state.
+    ///
+    /// # Notes
+    ///
+    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    fn set_state_inner(&self, state: &mut PluginState) -> bool {
+        let is_initialized = self.is_initialized.load(Ordering::SeqCst);
+
+        // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
+        let mut init_context = self.make_init_context();
+        let mut plugin = self.plugin.lock();
+        let success = permit_alloc(|| unsafe {
+            let mut prepared = PreparedState::prepare::<P>(
+                state,
+                state::make_params_getter(&self.param_by_hash, &self.param_id_to_hash),
+            );
+            if is_initialized {
+                prepared.set_plugin_data(P::prepare_state(
+                    state,
+                    &self.audio_io_layout,
+                    &self.buffer_config,
+                ));
+            }
+
+            prepared.apply_with_plugin_lock(
+                &mut *plugin,
+                &*self.params,
+                is_initialized.then_some(self.buffer_config.sample_rate),
+                |plugin| {
+                    plugin.initialize(
+                        &self.audio_io_layout,
+                        &self.buffer_config,
+                        &mut init_context,
+                    )
+                },
+            )
+        });
+
+        // The parameter ports still contain the old values. Those should only be applied again
+        // when the host changes them.
+        self.sync_last_param_port_values();
+
+        nih_debug_assert!(
+            success,
+            "Plugin returned false when reinitializing after loading state"
+        );
+
+        success
+    }
+
+    /// Store the current values of the connected parameter ports as the last seen values.
+    fn sync_last_param_port_values(&self) {
+        let ports = self.ports.borrow();
+        let mut last_param_port_values = self.last_param_port_values.borrow_mut();
+        for (last_value, port) in last_param_port_values.iter_mut().zip(ports.params.iter()) {
+            if !port.is_null() {
+                *last_value = unsafe { **port };
+            }
+        }
+    }
+
+    /// Apply the values from any parameter ports that have changed since the last `run()` call.
+    unsafe fn update_params_from_ports(&self, ports: &PortConnections) {
+        let mut last_param_port_values = self.last_param_port_values.borrow_mut();
+        for ((param_ptr, port), last_value) in self
+            .param_ptrs
+            .iter()
+            .zip(ports.params.iter())
+            .zip(last_param_port_values.iter_mut())
+        {
+            if port.is_null() {
+                continue;
+            }
+
+            let value = **port;
+            if value.to_bits() != last_value.to_bits() {
+                *last_value = value;
+                if value.is_finite() {
+                    param_ptr.set_normalized_value(value.clamp(0.0, 1.0));
+                    param_ptr.update_smoother(self.buffer_config.sample_rate, false);
+                }
+            }
+        }
+    }
+
+    /// Read the MIDI and transport events from the events input port. Transport updates are
+    /// applied at the start of the cycle regardless of their timing.
+    unsafe fn handle_in_events(
+        &self,
+        ports: &PortConnections,
+        input_events: &mut AtomicRefMut<VecDeque<PluginNoteEvent<P>>>,
+        total_buffer_len: usize,
+    ) {
+        input_events.clear();
+        if ports.events_input.is_null() {
+            return;
+        }
+
+        let sequence = atom::sequence_bytes(ports.events_input);
+        let events = match atom::read_sequence(
+            sequence,
+            self.urids.atom_sequence,
+            self.urids.atom_beat_time,
+        ) {
+            Some(events) => events,
+            None => {
+                nih_debug_assert_failure!("The events input port does not contain a sequence");
+                return;
+            }
+        };
+
+        for event in events {
+            if event.type_ == self.urids.midi_event {
+                let timing =
+                    clamp_input_event_timing(event.frames.max(0) as u32, total_buffer_len as u32);
+                match NoteEvent::from_midi(timing, event.body) {
+                    Ok(note_event) => self.handle_in_midi_event(note_event, input_events),
+                    Err(n) => nih_debug_assert_failure!("Unhandled MIDI message type {}", n),
+                };
+            } else if event.type_ == self.urids.atom_object || event.type_ == self.urids.atom_blank
+            {
+                self.handle_in_object(event.body);
+            }
+        }
+    }
+
+    /// Handle an incoming MIDI event. This filters the event based on `P::MIDI_INPUT` and passes
+    /// it through the MPE translator if needed.
+    fn handle_in_midi_event(
+        &self,
+        note_event: PluginNoteEvent<P>,
+        input_events: &mut AtomicRefMut<VecDeque<PluginNoteEvent<P>>>,
+    ) {
+        match note_event {
+            // With MPE the notes also need to pass through the translator so it knows which notes
+            // are playing on which member channels
+            note_event if P::MIDI_INPUT >= MidiConfig::Mpe => self
+                .mpe_translator
+                .borrow_mut()
+                .translate(note_event, |event| input_events.push_back(event)),
+            // Like with the other wrappers, we'll only pass through note and polyphonic events when
+            // the plugin doesn't want MIDI CCs
+            note_event @ (NoteEvent::NoteOn { .. }
+            | NoteEvent::NoteOff { .. }
+            | NoteEvent::PolyPressure { .. }
+            | NoteEvent::PolyTuning { .. }
+            | NoteEvent::MidiSysEx { .. })
+                if P::MIDI_INPUT >= MidiConfig::Basic =>
+            {
+                input_events.push_back(note_event);
+            }
+            note_event if P::MIDI_INPUT >= MidiConfig::MidiCCs => {
+                input_events.push_back(note_event);
+            }
+            _ => (),
+        }
+    }
+
+    /// Handle an atom object from the events input port. Only `time:Position` objects are used.
+    fn handle_in_object(&self, body: &[u8]) {
+        let properties = match atom::read_object(body) {
+            Some((otype, properties)) if otype == self.urids.time_position => properties,
+            _ => return,
+        };
+
+        let urids = &self.urids;
+        let mut transport = self.transport.borrow_mut();
+        for (key, type_, value) in properties {
+            let value = match atom::read_number(&urids.numbers, type_, value) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if key == urids.time_speed {
+                transport.speed = value;
+            } else if key == urids.time_frame {
+                transport.frame = Some(value as i64);
+            } else if key == urids.time_beats_per_minute {
+                transport.beats_per_minute = Some(value);
+            } else if key == urids.time_beats_per_bar {
+                transport.beats_per_bar = Some(value);
+            } else if key == urids.time_beat_unit {
+                transport.beat_unit = Some(value as i32);
+            } else if key == urids.time_bar {
+                transport.bar = Some(value as i64);
+            } else if key == urids.time_bar_beat {
+                transport.bar_beat = Some(value);
+            }
+        }
+    }
+
+    /// Write the events the plugin output during the last block to the events output port.
+    /// `block_start` is the block's offset relative to the start of the host's buffer.
+    fn handle_out_events(
+        &self,
+        writer: &mut Option<SequenceWriter>,
+        block_start: usize,
+        total_buffer_len: usize,
+    ) {
+        let mut output_events = self.output_events.borrow_mut();
+        let writer = match writer {
+            Some(writer) => writer,
+            None => {
+                output_events.clear();
+                return;
+            }
+        };
+
+        while let Some(event) = output_events.pop_front() {
+            // Out of bounds events are clamped to the buffer's size
+            let time = clamp_output_event_timing(
+                event.timing() + block_start as u32,
+                total_buffer_len as u32,
+            ) as i64;
+
+            let push_successful = match event {
+                NoteEvent::MidiChannelPressure { .. }
+                | NoteEvent::MidiPitchBend { .. }
+                | NoteEvent::MidiCC { .. }
+                | NoteEvent::MidiProgramChange { .. }
+                    if P::MIDI_OUTPUT < MidiConfig::MidiCCs =>
+                {
+                    nih_debug_assert_failure!(
+                        "Invalid output event for the current MIDI_OUTPUT setting"
+                    );
+                    continue;
+                }
+                // Events without a MIDI equivalent, like polyphonic modulation, are dropped
+                _ if P::MIDI_OUTPUT >= MidiConfig::Basic => {
+                    self.write_midi_event(writer, time, event)
+                }
+                _ => {
+                    nih_debug_assert_failure!(
+                        "Invalid output event for the current MIDI_OUTPUT setting"
+                    );
+                    continue;
+                }
+            };
+
+            nih_debug_assert!(push_successful, "Could not send note event");
+        }
+    }
+
+    /// Convert an event to MIDI and append it to the output sequence. Returns `false` if the
+    /// event did not fit in the host's buffer.
+    fn write_midi_event(
+        &self,
+        writer: &mut SequenceWriter,
+        time: i64,
+        event: PluginNoteEvent<P>,
+    ) -> bool {
+        match event.as_midi() {
+            Some(MidiResult::Basic(midi_data)) => {
+                // Program change and channel pressure messages only have a single data byte
+                let length = match midi_data[0] & 0xf0 {
+                    0xc0 | 0xd0 => 2,
+                    _ => 3,
+                };
+
+                writer.write_event(time, self.urids.midi_event, &midi_data[..length])
+            }
+            Some(MidiResult::SysEx(padded_sysex_buffer, length)) => {
+                let padded_sysex_buffer = padded_sysex_buffer.borrow();
+                nih_debug_assert!(padded_sysex_buffer.len() >= length);
+
+                writer.write_event(time, self.urids.midi_event, &padded_sysex_buffer[..length])
+            }
+            None => true,
+        }
+    }
+
+    /// Process a single cycle, splitting the host's buffer into blocks of at most the maximum
+    /// buffer size.
+    unsafe fn process_cycle(&self, total_buffer_len: usize) {
+        if !self.is_initialized.load(Ordering::SeqCst) {
+            nih_debug_assert_failure!("run() called without a successful activate() call");
+            return;
+        }
+
+        let ports = self.ports.borrow();
+        self.update_params_from_ports(&ports);
+
+        let mut input_events = self.input_events.borrow_mut();
+        self.handle_in_events(&ports, &mut input_events, total_buffer_len);
+
+        // The host sets the output sequence's size to the buffer's capacity before calling `run()`
+        let mut output_writer = NonNull::new(ports.events_output).and_then(|sequence| {
+            let capacity =
+                std::mem::size_of::<LV2_Atom>() + (*sequence.as_ptr()).atom.size as usize;
+            let buffer = std::slice::from_raw_parts_mut(sequence.as_ptr() as *mut u8, capacity);
+
+            SequenceWriter::new(buffer, self.urids.atom_sequence)
+        });
+
+        let max_buffer_size = self.buffer_config.max_buffer_size as usize;
+        let mut block_start = 0;
+        while block_start < total_buffer_len {
+            let block_end = (block_start + max_buffer_size).min(total_buffer_len);
+            let block_len = block_end - block_start;
+
+            let mut buffer_manager = self.buffer_manager.borrow_mut();
+            let buffers = buffer_manager.create_buffers(block_start, block_len, |buffer_source| {
+                *buffer_source.main_input_channel_pointers = channel_pointers(&ports.main_input);
+                *buffer_source.main_output_channel_pointers = channel_pointers(&ports.main_output);
+                // The auxiliary ports are optional, and ports the host has not connected are passed
+                // to the plugin as inactive buffers
+                for ((aux_input_channel_pointers, aux_input_active), aux_input) in buffer_source
+                    .aux_input_channel_pointers
+                    .iter_mut()
+                    .zip(buffer_source.aux_input_active.iter_mut())
+                    .zip(ports.aux_inputs.iter())
+                {
+                    *aux_input_channel_pointers = channel_pointers(aux_input);
+                    *aux_input_active = aux_input_channel_pointers.is_some();
+                }
+                for ((aux_output_channel_pointers, aux_output_active), aux_output) in buffer_source
+                    .aux_output_channel_pointers
+                    .iter_mut()
+                    .zip(buffer_source.aux_output_active.iter_mut())
+                    .zip(ports.aux_outputs.iter())
+                {
+                    *aux_output_channel_pointers = channel_pointers(aux_output);
+                    *aux_output_active = aux_output_channel_pointers.is_some();
+                }
+            });
+
+            // If the host has not connected all outputs then the buffer manager will have replaced
+            // those outputs with empty slices and we can't process audio
+            let buffer_is_valid = buffers
+                .main_buffer
+                .as_slice_immutable()
+                .iter()
+                .chain(
+                    buffers
+                        .aux_outputs
+                        .iter()
+                        .flat_map(|buffer| buffer.as_slice_immutable().iter()),
+                )
+                .all(|slice| !slice.is_empty());
+            nih_debug_assert!(buffer_is_valid);
+
+            if buffer_is_valid {
+                let mut plugin = self.plugin.lock();
+                let mut aux = AuxiliaryBuffers {
+                    inputs: buffers.aux_inputs,
+                    outputs: buffers.aux_outputs,
+                };
+                let mut context = self.make_process_context(input_events, block_start, block_end);
+                let result = plugin.process(buffers.main_buffer, &mut aux, &mut context);
+                input_events = context.input_events_guard;
+
+                if let ProcessStatus::Error(err) = result {
+                    nih_debug_assert_failure!("Process error: {}", err);
+                }
+            }
+
+            self.handle_out_events(&mut output_writer, block_start, total_buffer_len);
+            block_start = block_end;
+        }
+
+        // Events the plugin didn't read should not end up in the next cycle
+        input_events.clear();
+        self.transport
+            .borrow_mut()
+            .advance(self.buffer_config.sample_rate, total_buffer_len);
+
+        if !ports.latency.is_null() {
+            *ports.latency = self.current_latency.load(Ordering::SeqCst) as f32;
+        }
+    }
+
+    unsafe extern "C" fn instantiate(
+        _descriptor: *const LV2_Descriptor,
+        sample_rate: f64,
+        _bundle_path: *const c_char,
+        features: *const *const LV2_Feature,
+    ) -> LV2_Handle {
+        match Self::new(sample_rate as f32, features) {
+            // This pointer gets turned into an Arc again in `cleanup()`
+            Some(wrapper) => Arc::into_raw(wrapper) as LV2_Handle,
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    unsafe extern "C" fn connect_port(instance: LV2_Handle, port: u32, data_location: *mut c_void) {
+        check_null_ptr!((), instance);
+        let wrapper = &*(instance as *const Self);
+
+        let kind = match wrapper.port_layout.ports.get(port as usize) {
+            Some(port) => port.kind,
+            None => {
+                nih_debug_assert_failure!("Unknown port index {}", port);
+                return;
+            }
+        };
+
+        let mut ports = wrapper.ports.borrow_mut();
+        match kind {
+            PortKind::AudioInput { bus: None, channel } => {
+                ports.main_input[channel] = data_location as *mut f32
+            }
+            PortKind::AudioInput {
+                bus: Some(bus),
+                channel,
+            } => ports.aux_inputs[bus][channel] = data_location as *mut f32,
+            PortKind::AudioOutput { bus: None, channel } => {
+                ports.main_output[channel] = data_location as *mut f32
+            }
+            PortKind::AudioOutput {
+                bus: Some(bus),
+                channel,
+            } => ports.aux_outputs[bus][channel] = data_location as *mut f32,
+            PortKind::EventsInput => ports.events_input = data_location as *const LV2_Atom_Sequence,
+            PortKind::EventsOutput => ports.events_output = data_location as *mut LV2_Atom_Sequence,
+            PortKind::Latency => ports.latency = data_location as *mut f32,
+            PortKind::Param(param_idx) => ports.params[param_idx] = data_location as *const f32,
+        }
+    }
+
+    unsafe extern "C" fn activate(instance: LV2_Handle) {
+        check_null_ptr!((), instance);
+        let wrapper = &*(instance as *const Self);
+
+        // Before initializing the plugin, make sure all smoothers are set the the default values
+        for param in &wrapper.param_ptrs {
+            param.update_smoother(wrapper.buffer_config.sample_rate, true);
+        }
+
+        // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
+        let mut init_context = wrapper.make_init_context();
+        let mut plugin = wrapper.plugin.lock();
+        let success = plugin.initialize(
+            &wrapper.audio_io_layout,
+            &wrapper.buffer_config,
+            &mut init_context,
+        );
+        if success {
+            wrapper.mpe_translator.borrow_mut().reset();
+            process_wrapper(|| plugin.reset());
+        } else {
+            nih_log!("The plugin failed to initialize, it will not process audio");
+        }
+
+        wrapper.is_initialized.store(success, Ordering::SeqCst);
+    }
+
+    unsafe extern "C" fn run(instance: LV2_Handle, sample_count: u32) {
+        check_null_ptr!((), instance);
+        let wrapper = &*(instance as *const Self);
+
+        // Panic on allocations if the `assert_process_allocs` feature has been enabled, and make
+        // sure that FTZ is set up correctly
+        process_wrapper(|| wrapper.process_cycle(sample_count as usize));
+    }
+
+    unsafe extern "C" fn deactivate(instance: LV2_Handle) {
+        check_null_ptr!((), instance);
+        let wrapper = &*(instance as *const Self);
+
+        if wrapper.is_initialized.swap(false, Ordering::SeqCst) {
+            wrapper.plugin.lock().deactivate();
+        }
+    }
+
+    unsafe extern "C" fn cleanup(instance: LV2_Handle) {
+        check_null_ptr!((), instance);
+        let this = Arc::from_raw(instance as *const Self);
+        nih_debug_assert_eq!(Arc::strong_count(&this), 1);
+
+        drop(this);
+    }
+
+    unsafe extern "C" fn extension_data(uri: *const c_char) -> *const c_void {
+        check_null_ptr!(std::ptr::null(), uri);
+
+        let uri = CStr::from_ptr(uri);
+        if uri.to_bytes_with_nul() == LV2_STATE__interface {
+            &Self::STATE_INTERFACE as *const _ as *const c_void
+        } else {
+            nih_trace!("Host tried to query unknown extension {:?}", uri);
+            std::ptr::null()
+        }
+    }
+
+    unsafe extern "C" fn state_save(
+        instance: LV2_Handle,
+        store: LV2_State_Store_Function,
+        handle: LV2_State_Handle,
+        _flags: u32,
+        _features: *const *const LV2_Feature,
+    ) -> LV2_State_Status {
+        check_null_ptr!(LV2_STATE_ERR_UNKNOWN, instance);
+        let wrapper = &*(instance as *const Self);
+        let store = match store {
+            Some(store) => store,
+            None => {
+                nih_debug_assert_failure!("Null pointer passed to function");
+                return LV2_STATE_ERR_UNKNOWN;
+            }
+        };
+
+        let serialized = state::serialize_json::<P>(
+            wrapper.params.clone(),
+            state::make_params_iter(&wrapper.param_by_hash, &wrapper.param_id_to_hash),
+        );
+        match serialized {
+            Ok(serialized) => {
+                nih_trace!("Saved state ({} bytes)", serialized.len());
+
+                // The host copies the data, so the buffer can be dropped afterwards
+                store(
+                    handle,
+                    wrapper.urids.state_key,
+                    serialized.as_ptr() as *const c_void,
+                    serialized.len(),
+                    wrapper.urids.atom_chunk,
+                    LV2_STATE_IS_POD | LV2_STATE_IS_PORTABLE,
+                )
+            }
+            Err(err) => {
+                nih_debug_assert_failure!("Could not save state: {:#}", err);
+                LV2_STATE_ERR_UNKNOWN
+            }
+        }
+    }
+
+    unsafe extern "C" fn state_restore(
+        instance: LV2_Handle,
+        retrieve: LV2_State_Retrieve_Function,
+        handle: LV2_State_Handle,
+        _flags: u32,
+        _features: *const *const LV2_Feature,
+    ) -> LV2_State_Status {
+        check_null_ptr!(LV2_STATE_ERR_UNKNOWN, instance);
+        let wrapper = &*(instance as *const Self);
+        let retrieve = match retrieve {
+            Some(retrieve) => retrieve,
+            None => {
+                nih_debug_assert_failure!("Null pointer passed to function");
+                return LV2_STATE_ERR_UNKNOWN;
+            }
+        };
+
+        let mut size = 0;
+        let mut type_ = 0;
+        let mut flags = 0;
+        let data = retrieve(
+            handle,
+            wrapper.urids.state_key,
+            &mut size,
+            &mut type_,
+            &mut flags,
+        );
+        if data.is_null() {
+            return LV2_STATE_ERR_NO_PROPERTY;
+        }
+        if type_ != wrapper.urids.atom_chunk {
+            nih_debug_assert_failure!("Unexpected state type {}", type_);
+            return LV2_STATE_ERR_BAD_TYPE;
+        }
+
+        let data = std::slice::from_raw_parts(data as *const u8, size);
+        match state::deserialize_json(data) {
+            Some(mut state) => {
+                if wrapper.set_state_inner(&mut state) {
+                    nih_trace!("Loaded state ({} bytes)", data.len());
+                    LV2_STATE_SUCCESS
+                } else {
+                    LV2_STATE_ERR_UNKNOWN
+                }
+            }
+            None => LV2_STATE_ERR_UNKNOWN,
+        }
+    }
+}
+
+/// Convert a bus's channel pointers to a [`ChannelPointers`] object. Returns `None` if the bus has
+/// no channels or if any of its ports are not connected.
+fn channel_pointers(channels: &[*mut f32]) -> Option<ChannelPointers> {
+    if channels.is_empty() || channels.iter().any(|channel| channel.is_null()) {
+        return None;
+    }
+
+    Some(ChannelPointers {
+        ptrs: NonNull::new(channels.as_ptr() as *mut *mut f32).unwrap(),
+        num_channels: channels.len(),
+    })
+}
+
+/// A static descriptor for a plugin. The descriptor's URI points to [`Self::uri`], so this cannot
+/// be cloned.
+pub struct PluginDescriptor {
+    uri: CString,
+    descriptor: LV2_Descriptor,
+}
+
+unsafe impl Send for PluginDescriptor {}
+unsafe impl Sync for PluginDescriptor {}
+
+impl PluginDescriptor {
+    /// Construct the plugin descriptor for a specific LV2 plugin.
+    pub fn for_plugin<P: Lv2Plugin>() -> Self {
+        let uri = CString::new(P::LV2_URI).expect("`LV2_URI` contained null bytes");
+
+        // The `CString`'s heap allocation doesn't move when the descriptor is moved
+        let descriptor = LV2_Descriptor {
+            URI: uri.as_ptr(),
+            instantiate: Some(Wrapper::<P>::instantiate),
+            connect_port: Some(Wrapper::<P>::connect_port),
+            activate: Some(Wrapper::<P>::activate),
+            run: Some(Wrapper::<P>::run),
+            deactivate: Some(Wrapper::<P>::deactivate),
+            cleanup: Some(Wrapper::<P>::cleanup),
+            extension_data: Some(Wrapper::<P>::extension_data),
+        };
+
+        Self { uri, descriptor }
+    }
+
+    pub fn uri(&self) -> &CStr {
+        self.uri.as_c_str()
+    }
+
+    pub fn lv2_descriptor(&self) -> *const LV2_Descriptor {
+        &self.descriptor
+    }
+}
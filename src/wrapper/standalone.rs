@@ -12,6 +12,7 @@ use crate::prelude::Plugin;
 mod backend;
 mod config;
 mod context;
+mod repl;
 mod wrapper;
 
 /// Open an NIH-plug plugin as a standalone application. If the plugin has an editor, this will open
@@ -45,6 +46,24 @@ mod wrapper;
 /// By default this will connect to the 'default' audio and MIDI ports. Use the command line options
 /// to change this. `--help` lists all available options.
 ///
+/// The `--backend file` option can be used to render audio and MIDI files through the plugin
+/// without any audio hardware. This processes the plugin in offline mode as fast as possible, and
+/// the application will exit once the input files have been processed:
+///
+/// ```shell
+/// plugin_name --backend file --input-file in.wav --midi-input-file in.mid --output-file out.wav
+/// ```
+///
+/// The `--repl` option reads commands from STDIN while the plugin is running. This can be used to
+/// list and change parameters, to save and load the plugin's state as JSON, and to send notes to
+/// the plugin without needing a GUI or a MIDI device. On Unix-like platforms the same commands can
+/// also be sent over a local socket using `--repl-socket <path>`, for instance with `socat`:
+///
+/// ```shell
+/// plugin_name --repl-socket /tmp/plugin_name.sock &
+/// socat - UNIX-CONNECT:/tmp/plugin_name.sock
+/// ```
+///
 /// If the wrapped plugin fails to initialize or throws an error during audio processing, then this
 /// function will return `false`.
 pub fn nih_export_standalone<P: Plugin>() -> bool {
@@ -172,10 +191,21 @@ pub fn nih_export_standalone_with_args<P: Plugin, Args: IntoIterator<Item = Stri
         config::BackendType::Dummy => {
             run_wrapper::<P, _>(backend::Dummy::new::<P>(config.clone()), config)
         }
+        config::BackendType::File => match backend::File::new::<P>(config.clone()) {
+            Ok(backend) => run_wrapper::<P, _>(backend, config),
+            Err(err) => {
+                nih_error!("Could not initialize the file backend: {:#}", err);
+                false
+            }
+        },
     }
 }
 
 fn run_wrapper<P: Plugin, B: Backend<P>>(backend: B, config: WrapperConfig) -> bool {
+    let repl = config.repl;
+    #[cfg(unix)]
+    let repl_socket = config.repl_socket.clone();
+
     let wrapper = match Wrapper::<P, _>::new(backend, config) {
         Ok(wrapper) => wrapper,
         Err(err) => {
@@ -184,7 +214,16 @@ fn run_wrapper<P: Plugin, B: Backend<P>>(backend: B, config: WrapperConfig) -> b
         }
     };
 
-    // TODO: Add a repl while the application is running to interact with parameters
+    if repl {
+        repl::spawn_stdin(wrapper.clone());
+    }
+    #[cfg(unix)]
+    if let Some(path) = repl_socket {
+        if let Err(err) = repl::spawn_socket(wrapper.clone(), &path) {
+            nih_error!("Could not start the REPL socket: {err:#}");
+        }
+    }
+
     match wrapper.run() {
         Ok(()) => true,
         Err(err) => {
@@ -199,5 +238,11 @@ fn print_error(error: WrapperError) {
         WrapperError::InitializationFailed => {
             nih_error!("The plugin failed to initialize");
         }
+        WrapperError::ProcessingError => {
+            nih_error!("The plugin stopped processing audio because of an error");
+        }
+        WrapperError::BackendError(err) => {
+            nih_error!("The audio backend stopped processing audio: {err:#}");
+        }
     }
 }
@@ -1,11 +1,15 @@
+use anyhow::Result;
+
 use crate::prelude::{AuxiliaryBuffers, PluginNoteEvent, Transport};
 
 mod cpal;
 mod dummy;
+mod file;
 mod jack;
 
 pub use self::cpal::CpalMidir;
 pub use self::dummy::Dummy;
+pub use self::file::File;
 pub use self::jack::Jack;
 pub use crate::buffer::Buffer;
 pub use crate::plugin::Plugin;
@@ -15,7 +19,8 @@ pub trait Backend<P: Plugin>: 'static + Send + Sync {
     /// Start processing audio and MIDI on this thread. The process callback will be called whenever
     /// there's a new block of audio to be processed. The process callback receives the audio
     /// buffers for the wrapped plugin's outputs. Any inputs will have already been copied to this
-    /// buffer. This will block until the process callback returns `false`.
+    /// buffer. This will block until the process callback returns `false`. Returns an error if the
+    /// backend had to stop processing because of an IO error.
     fn run(
         &mut self,
         cb: impl FnMut(
@@ -27,5 +32,5 @@ pub trait Backend<P: Plugin>: 'static + Send + Sync {
             ) -> bool
             + 'static
             + Send,
-    );
+    ) -> Result<()>;
 }
@@ -103,7 +103,7 @@ impl<P: Plugin> Backend<P> for CpalMidir {
             ) -> bool
             + 'static
             + Send,
-    ) {
+    ) -> Result<()> {
         // So this is a lot of fun. There are up to four separate streams here, all using their own
         // callbacks. The audio output stream acts as the primary stream, and everything else either
         // sends data to it or (in the case of the MIDI output stream) receives data from it using
@@ -357,6 +357,8 @@ impl<P: Plugin> Backend<P> for CpalMidir {
                     }
                 });
         });
+
+        Ok(())
     }
 }
 
@@ -873,6 +875,11 @@ impl CpalMidir {
                                 num_channels: output_channel_pointers.get().len(),
                             });
                         }
+
+                        // CPAL only handles the main IO, so nothing is connected to the auxiliary
+                        // ports
+                        buffer_sources.aux_input_active.fill(false);
+                        buffer_sources.aux_output_active.fill(false);
                     })
                 };
 
@@ -1,3 +1,4 @@
+use anyhow::Result;
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
 use std::time::{Duration, Instant};
@@ -27,7 +28,7 @@ impl<P: Plugin> Backend<P> for Dummy {
             ) -> bool
             + 'static
             + Send,
-    ) {
+    ) -> Result<()> {
         // We can't really do anything meaningful here, so we'll simply periodically call the
         // callback with empty buffers
         let interval =
@@ -152,6 +153,10 @@ impl<P: Plugin> Backend<P> for Dummy {
                             num_channels: output_channel_pointers.len(),
                         });
                     }
+
+                    // Nothing is ever connected to the auxiliary ports
+                    buffer_sources.aux_input_active.fill(false);
+                    buffer_sources.aux_output_active.fill(false);
                 })
             };
 
@@ -175,6 +180,8 @@ impl<P: Plugin> Backend<P> for Dummy {
             let period_end = Instant::now();
             std::thread::sleep((period_start + interval).saturating_duration_since(period_end));
         }
+
+        Ok(())
     }
 }
 
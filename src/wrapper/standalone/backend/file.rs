@@ -0,0 +1,737 @@
+use anyhow::{Context, Result};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use midly::{MetaMessage, Smf, Timing, TrackEventKind};
+use std::fs;
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+
+use super::super::config::WrapperConfig;
+use super::Backend;
+use crate::prelude::{
+    AudioIOLayout, AuxiliaryBuffers, Buffer, NoteEvent, Plugin, PluginNoteEvent, Transport,
+};
+use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+
+type FileWavWriter = WavWriter<BufWriter<fs::File>>;
+
+/// Reads audio from WAV files and MIDI from a Standard MIDI File, and writes the plugin's output to
+/// WAV files. Unlike the other backends this processes audio as fast as possible and stops once
+/// the input has been fully processed. The plugin is run with [`ProcessMode::Offline`]. This is
+/// useful for rendering test files and for regression testing a plugin's DSP without needing any
+/// audio hardware.
+///
+/// [`ProcessMode::Offline`]: crate::prelude::ProcessMode::Offline
+pub struct File {
+    config: WrapperConfig,
+    audio_io_layout: AudioIOLayout,
+
+    /// The main input's audio data, with one vector per channel. Empty if the plugin doesn't have
+    /// a main input or if no input file was specified.
+    main_input: Vec<Vec<f32>>,
+    /// The same as `main_input`, but for each of the auxiliary input ports.
+    aux_inputs: Vec<Vec<Vec<f32>>>,
+    /// Raw MIDI messages from the MIDI input file, along with their sample position, sorted by
+    /// their position. These are only parsed into `NoteEvent`s while processing since this struct
+    /// is not generic over the plugin type.
+    midi_events: Vec<(u64, Vec<u8>)>,
+    /// The total number of samples that will be processed, including the tail.
+    num_samples: usize,
+
+    /// The writer for the main output. Wrapped in an option so it can be finalized at the end of
+    /// `run()`. This is `None` if the plugin does not have a main output.
+    main_output_writer: Option<FileWavWriter>,
+    /// The writers for the auxiliary output ports. These are written next to the main output file.
+    aux_output_writers: Vec<Option<FileWavWriter>>,
+}
+
+impl<P: Plugin> Backend<P> for File {
+    fn run(
+        &mut self,
+        mut cb: impl FnMut(
+                &mut Buffer,
+                &mut AuxiliaryBuffers,
+                Transport,
+                &[PluginNoteEvent<P>],
+                &mut Vec<PluginNoteEvent<P>>,
+            ) -> bool
+            + 'static
+            + Send,
+    ) -> Result<()> {
+        let period_size = self.config.period_size as usize;
+        let num_output_channels = self
+            .audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or_default() as usize;
+        let num_input_channels = self
+            .audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or_default() as usize;
+
+        // Just like the dummy backend we'll allocate our own storage, and then copy the input
+        // files' contents into that storage before every processing cycle
+        let mut main_input_storage = vec![vec![0.0f32; period_size]; num_input_channels];
+        let mut main_output_storage = vec![vec![0.0f32; period_size]; num_output_channels];
+        let mut aux_input_storage: Vec<Vec<Vec<f32>>> = Vec::new();
+        for channel_count in self.audio_io_layout.aux_input_ports {
+            aux_input_storage.push(vec![
+                vec![0.0f32; period_size];
+                channel_count.get() as usize
+            ]);
+        }
+
+        let mut aux_output_storage: Vec<Vec<Vec<f32>>> = Vec::new();
+        for channel_count in self.audio_io_layout.aux_output_ports {
+            aux_output_storage.push(vec![
+                vec![0.0f32; period_size];
+                channel_count.get() as usize
+            ]);
+        }
+
+        let mut main_input_channel_pointers: Vec<*mut f32> = main_input_storage
+            .iter_mut()
+            .map(|channel_slice| channel_slice.as_mut_ptr())
+            .collect();
+        let mut main_output_channel_pointers: Vec<*mut f32> = main_output_storage
+            .iter_mut()
+            .map(|channel_slice| channel_slice.as_mut_ptr())
+            .collect();
+        let mut aux_input_channel_pointers: Vec<Vec<*mut f32>> = aux_input_storage
+            .iter_mut()
+            .map(|aux_input_storage| {
+                aux_input_storage
+                    .iter_mut()
+                    .map(|channel_slice| channel_slice.as_mut_ptr())
+                    .collect()
+            })
+            .collect();
+        let mut aux_output_channel_pointers: Vec<Vec<*mut f32>> = aux_output_storage
+            .iter_mut()
+            .map(|aux_output_storage| {
+                aux_output_storage
+                    .iter_mut()
+                    .map(|channel_slice| channel_slice.as_mut_ptr())
+                    .collect()
+            })
+            .collect();
+
+        let mut buffer_manager =
+            BufferManager::for_audio_io_layout(period_size, self.audio_io_layout);
+
+        let mut input_events: Vec<PluginNoteEvent<P>> = Vec::with_capacity(2048);
+        // Output events are currently discarded
+        let mut output_events: Vec<PluginNoteEvent<P>> = Vec::with_capacity(2048);
+        let mut midi_events_idx = 0;
+        let mut num_processed_samples = 0usize;
+        let mut result = Ok(());
+        while num_processed_samples < self.num_samples {
+            let num_samples = (self.num_samples - num_processed_samples).min(period_size);
+            let block_range = num_processed_samples..num_processed_samples + num_samples;
+
+            let mut transport = Transport::new(self.config.sample_rate);
+            transport.pos_samples = Some(num_processed_samples as i64);
+            transport.tempo = Some(self.config.tempo as f64);
+            transport.time_sig_numerator = Some(self.config.timesig_num as i32);
+            transport.time_sig_denominator = Some(self.config.timesig_denom as i32);
+            transport.playing = true;
+
+            copy_input_block(&self.main_input, &mut main_input_storage, &block_range);
+            for (input, storage) in self.aux_inputs.iter().zip(aux_input_storage.iter_mut()) {
+                copy_input_block(input, storage, &block_range);
+            }
+            for channel in &mut main_output_storage {
+                channel.fill(0.0);
+            }
+            for aux_buffer in &mut aux_output_storage {
+                for channel in aux_buffer {
+                    channel.fill(0.0);
+                }
+            }
+
+            let buffers = unsafe {
+                buffer_manager.create_buffers(0, num_samples, |buffer_sources| {
+                    *buffer_sources.main_output_channel_pointers = Some(ChannelPointers {
+                        ptrs: NonNull::new(main_output_channel_pointers.as_mut_ptr()).unwrap(),
+                        num_channels: main_output_channel_pointers.len(),
+                    });
+                    *buffer_sources.main_input_channel_pointers = Some(ChannelPointers {
+                        ptrs: NonNull::new(main_input_channel_pointers.as_mut_ptr()).unwrap(),
+                        num_channels: main_input_channel_pointers.len(),
+                    });
+
+                    for (input_source_channel_pointers, input_channel_pointers) in buffer_sources
+                        .aux_input_channel_pointers
+                        .iter_mut()
+                        .zip(aux_input_channel_pointers.iter_mut())
+                    {
+                        *input_source_channel_pointers = Some(ChannelPointers {
+                            ptrs: NonNull::new(input_channel_pointers.as_mut_ptr()).unwrap(),
+                            num_channels: input_channel_pointers.len(),
+                        });
+                    }
+
+                    for (output_source_channel_pointers, output_channel_pointers) in buffer_sources
+                        .aux_output_channel_pointers
+                        .iter_mut()
+                        .zip(aux_output_channel_pointers.iter_mut())
+                    {
+                        *output_source_channel_pointers = Some(ChannelPointers {
+                            ptrs: NonNull::new(output_channel_pointers.as_mut_ptr()).unwrap(),
+                            num_channels: output_channel_pointers.len(),
+                        });
+                    }
+
+                    // Auxiliary inputs are only active if an input file was specified for them,
+                    // and auxiliary outputs are only active if they're being written to a file
+                    for (aux_input_idx, input_active) in
+                        buffer_sources.aux_input_active.iter_mut().enumerate()
+                    {
+                        *input_active = aux_input_idx < self.aux_inputs.len();
+                    }
+                    for (output_active, writer) in buffer_sources
+                        .aux_output_active
+                        .iter_mut()
+                        .zip(self.aux_output_writers.iter())
+                    {
+                        *output_active = writer.is_some();
+                    }
+                })
+            };
+
+            // The MIDI events are sorted, so we can just pop events off the front until we reach
+            // the next block
+            input_events.clear();
+            while let Some((position, midi_data)) = self.midi_events.get(midi_events_idx) {
+                if *position >= block_range.end as u64 {
+                    break;
+                }
+
+                let timing = (*position - block_range.start as u64) as u32;
+                if let Ok(event) = NoteEvent::from_midi(timing, midi_data) {
+                    input_events.push(event);
+                }
+
+                midi_events_idx += 1;
+            }
+
+            output_events.clear();
+            let mut aux = AuxiliaryBuffers {
+                inputs: buffers.aux_inputs,
+                outputs: buffers.aux_outputs,
+            };
+            if !cb(
+                buffers.main_buffer,
+                &mut aux,
+                transport,
+                &input_events,
+                &mut output_events,
+            ) {
+                break;
+            }
+
+            let write_result = self
+                .main_output_writer
+                .iter_mut()
+                .zip(std::iter::once(&main_output_storage))
+                .chain(
+                    self.aux_output_writers
+                        .iter_mut()
+                        .zip(aux_output_storage.iter())
+                        .filter_map(|(writer, storage)| Some((writer.as_mut()?, storage))),
+                )
+                .try_for_each(|(writer, storage)| write_output_block(writer, storage, num_samples));
+            if let Err(err) = write_result {
+                result = Err(err.context("Could not write to the output file"));
+                break;
+            }
+
+            num_processed_samples += num_samples;
+        }
+
+        for writer in self
+            .main_output_writer
+            .take()
+            .into_iter()
+            .chain(self.aux_output_writers.iter_mut().filter_map(Option::take))
+        {
+            let finalize_result = writer
+                .finalize()
+                .context("Could not finalize an output file");
+            if result.is_ok() {
+                result = finalize_result;
+            }
+        }
+
+        nih_log!(
+            "Rendered {num_processed_samples} samples ({:.2} seconds)",
+            num_processed_samples as f32 / self.config.sample_rate
+        );
+
+        result
+    }
+}
+
+impl File {
+    /// Initialize the file backend. This reads the input files into memory and creates the output
+    /// files. Returns an error if any of the files could not be read or created.
+    pub fn new<P: Plugin>(config: WrapperConfig) -> Result<Self> {
+        let audio_io_layout = config.audio_io_layout_or_exit::<P>();
+        let sample_rate = config.sample_rate;
+
+        let num_input_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or_default() as usize;
+        let main_input = match &config.input_file {
+            Some(path) if num_input_channels > 0 => {
+                read_wav_file(path, sample_rate, num_input_channels)?
+            }
+            Some(_) => {
+                nih_warn!("The plugin does not have a main input, ignoring '--input-file'");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        if config.aux_input_files.len() > audio_io_layout.aux_input_ports.len() {
+            nih_warn!(
+                "The plugin has {} auxiliary inputs, ignoring the excess '--aux-input-files'",
+                audio_io_layout.aux_input_ports.len()
+            );
+        }
+        let mut aux_inputs = Vec::with_capacity(audio_io_layout.aux_input_ports.len());
+        for (path, channel_count) in config
+            .aux_input_files
+            .iter()
+            .zip(audio_io_layout.aux_input_ports)
+        {
+            aux_inputs.push(read_wav_file(
+                path,
+                sample_rate,
+                channel_count.get() as usize,
+            )?);
+        }
+
+        let midi_events = match &config.midi_input_file {
+            Some(path) => read_midi_file(path, sample_rate, config.tempo)?,
+            None => Vec::new(),
+        };
+
+        // Rendering stops when all inputs have been processed, plus the optional tail
+        let input_length = main_input
+            .iter()
+            .chain(aux_inputs.iter().flatten())
+            .map(Vec::len)
+            .chain(
+                midi_events
+                    .last()
+                    .map(|(position, _)| *position as usize + 1),
+            )
+            .max()
+            .unwrap_or(0);
+        let tail_length = (config.tail_length.max(0.0) * sample_rate).round() as usize;
+        if input_length == 0 && tail_length == 0 {
+            anyhow::bail!(
+                "Nothing to render. Use '--input-file', '--aux-input-files', '--midi-input-file', \
+                 or '--tail-length' to specify what the plugin should process."
+            );
+        }
+
+        let output_path = config
+            .output_file
+            .as_ref()
+            .context("The file backend requires an output file, use '--output-file' to set one")?;
+        let main_output_writer = match audio_io_layout.main_output_channels {
+            Some(num_channels) => Some(create_wav_writer(
+                output_path,
+                sample_rate,
+                num_channels.get(),
+            )?),
+            None => None,
+        };
+
+        let mut aux_output_writers = Vec::with_capacity(audio_io_layout.aux_output_ports.len());
+        for (aux_output_idx, channel_count) in audio_io_layout.aux_output_ports.iter().enumerate() {
+            let aux_output_name = audio_io_layout
+                .aux_output_name(aux_output_idx)
+                .expect("Out of range aux output port")
+                .to_lowercase()
+                .replace(' ', "_");
+
+            aux_output_writers.push(Some(create_wav_writer(
+                &aux_output_path(output_path, &aux_output_name),
+                sample_rate,
+                channel_count.get(),
+            )?));
+        }
+
+        Ok(Self {
+            config,
+            audio_io_layout,
+
+            main_input,
+            aux_inputs,
+            midi_events,
+            num_samples: input_length + tail_length,
+
+            main_output_writer,
+            aux_output_writers,
+        })
+    }
+}
+
+/// Copy a block of an input file's data to the storage for a port. Any samples past the end of the
+/// input file are filled with zeroes.
+fn copy_input_block(
+    input: &[Vec<f32>],
+    storage: &mut [Vec<f32>],
+    block_range: &std::ops::Range<usize>,
+) {
+    for (channel_idx, channel) in storage.iter_mut().enumerate() {
+        channel.fill(0.0);
+
+        if let Some(input_channel) = input.get(channel_idx) {
+            let start = block_range.start.min(input_channel.len());
+            let end = block_range.end.min(input_channel.len());
+            channel[..end - start].copy_from_slice(&input_channel[start..end]);
+        }
+    }
+}
+
+/// Write the first `num_samples` samples from `storage` to a WAV file.
+fn write_output_block(
+    writer: &mut FileWavWriter,
+    storage: &[Vec<f32>],
+    num_samples: usize,
+) -> Result<()> {
+    for sample_idx in 0..num_samples {
+        for channel in storage {
+            writer.write_sample(channel[sample_idx])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a WAV file into a vector containing one vector per channel. Mono files are upmixed to all
+/// channels, and excess channels are filled with silence. Returns an error if the file's sample rate
+/// does not match the configured sample rate.
+fn read_wav_file(path: &Path, sample_rate: f32, num_channels: usize) -> Result<Vec<Vec<f32>>> {
+    let mut reader =
+        WavReader::open(path).with_context(|| format!("Could not open '{}'", path.display()))?;
+    let spec = reader.spec();
+    if spec.sample_rate as f32 != sample_rate {
+        anyhow::bail!(
+            "'{}' has a sample rate of {} Hz, but the plugin is running at {} Hz. Use the \
+             '--sample-rate' option to change the sample rate.",
+            path.display(),
+            spec.sample_rate,
+            sample_rate
+        );
+    }
+
+    let file_channels = spec.channels as usize;
+    if file_channels == 0 {
+        anyhow::bail!("'{}' does not contain any audio channels", path.display());
+    }
+    if file_channels != 1 && file_channels != num_channels {
+        nih_warn!(
+            "'{}' contains {} channels, but the port has {} channels",
+            path.display(),
+            file_channels,
+            num_channels
+        );
+    }
+
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Could not read '{}'", path.display()))?,
+        SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 * scale))
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("Could not read '{}'", path.display()))?
+        }
+    };
+
+    let mut channels = vec![Vec::with_capacity(samples.len() / file_channels); num_channels];
+    for frame in samples.chunks_exact(file_channels) {
+        for (channel_idx, channel) in channels.iter_mut().enumerate() {
+            channel.push(if file_channels == 1 {
+                frame[0]
+            } else {
+                frame.get(channel_idx).copied().unwrap_or(0.0)
+            });
+        }
+    }
+
+    Ok(channels)
+}
+
+/// Read a Standard MIDI File and convert it to a list of raw MIDI messages with their sample
+/// positions, sorted by position. Tempo changes in the file are taken into account, and
+/// `initial_tempo` is used until the file sets a tempo. Meta events are not included in the result.
+fn read_midi_file(
+    path: &Path,
+    sample_rate: f32,
+    initial_tempo: f32,
+) -> Result<Vec<(u64, Vec<u8>)>> {
+    let data = fs::read(path).with_context(|| format!("Could not open '{}'", path.display()))?;
+    let smf = Smf::parse(&data).with_context(|| format!("Could not parse '{}'", path.display()))?;
+
+    // All tracks are merged into a single list of events with absolute tick positions. The sort
+    // is stable so events on the same tick keep their original order.
+    let mut events: Vec<(u64, TrackEventKind)> = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            events.push((tick, event.kind));
+        }
+    }
+    events.sort_by_key(|(tick, _)| *tick);
+
+    // For metrical timing the length of a tick depends on the tempo, which may change throughout
+    // the file. Timecode based timing has a fixed tick length.
+    let (mut seconds_per_tick, ticks_per_beat) = match smf.header.timing {
+        Timing::Metrical(ticks_per_beat) => {
+            let ticks_per_beat = ticks_per_beat.as_int() as f64;
+            (
+                60.0 / initial_tempo as f64 / ticks_per_beat,
+                Some(ticks_per_beat),
+            )
+        }
+        Timing::Timecode(fps, subframes) => (1.0 / (fps.as_f32() as f64 * subframes as f64), None),
+    };
+
+    let mut result = Vec::new();
+    let mut last_tick = 0u64;
+    let mut last_seconds = 0.0f64;
+    for (tick, kind) in events {
+        let seconds = last_seconds + (tick - last_tick) as f64 * seconds_per_tick;
+        last_tick = tick;
+        last_seconds = seconds;
+
+        match (kind, ticks_per_beat) {
+            (TrackEventKind::Meta(MetaMessage::Tempo(micros_per_beat)), Some(ticks_per_beat)) => {
+                seconds_per_tick = micros_per_beat.as_int() as f64 / 1_000_000.0 / ticks_per_beat;
+            }
+            (kind, _) => {
+                if let Some(live_event) = kind.as_live_event() {
+                    let mut midi_data = Vec::with_capacity(3);
+                    live_event
+                        .write_std(&mut midi_data)
+                        .context("Could not convert MIDI event")?;
+
+                    result.push(((seconds * sample_rate as f64).round() as u64, midi_data));
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Create a 32-bit floating point WAV file for writing.
+fn create_wav_writer(path: &Path, sample_rate: f32, num_channels: u32) -> Result<FileWavWriter> {
+    let spec = WavSpec {
+        channels: num_channels as u16,
+        sample_rate: sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    WavWriter::create(path, spec).with_context(|| format!("Could not create '{}'", path.display()))
+}
+
+/// The path for an auxiliary output file. This is the main output file's path with the port's name
+/// appended to the file name, so `out.wav` becomes `out_sidechain_output.wav`.
+fn aux_output_path(output_path: &Path, aux_output_name: &str) -> PathBuf {
+    let file_stem = output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = output_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("wav"));
+
+    output_path.with_file_name(format!("{file_stem}_{aux_output_name}.{extension}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::{Params, ProcessStatus};
+
+    struct PassthroughPlugin {
+        params: Arc<PassthroughParams>,
+    }
+
+    struct PassthroughParams;
+
+    unsafe impl Params for PassthroughParams {
+        fn param_map(&self) -> Vec<(String, crate::prelude::ParamPtr, String)> {
+            Vec::new()
+        }
+    }
+
+    impl Default for PassthroughPlugin {
+        fn default() -> Self {
+            Self {
+                params: Arc::new(PassthroughParams),
+            }
+        }
+    }
+
+    impl Plugin for PassthroughPlugin {
+        const NAME: &'static str = "Passthrough";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        }];
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            self.params.clone()
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl crate::prelude::ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+    }
+
+    /// A unique path in the temporary directory. The file is removed when this gets dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("nih-plug-file-{}-{name}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn file_config(input_file: &Path, output_file: &Path) -> WrapperConfig {
+        WrapperConfig::parse_from([
+            "test",
+            "--backend",
+            "file",
+            "--sample-rate",
+            "48000",
+            // This is not a divisor of the file's length, so the last block is shorter
+            "--period-size",
+            "64",
+            "--input-file",
+            input_file.to_str().unwrap(),
+            "--output-file",
+            output_file.to_str().unwrap(),
+        ])
+    }
+
+    #[test]
+    fn round_trip_passthrough() {
+        let input_file = TempPath::new("input.wav");
+        let output_file = TempPath::new("output.wav");
+
+        let input: Vec<Vec<f32>> = (0..2)
+            .map(|channel_idx| {
+                (0..1000)
+                    .map(|sample_idx| (sample_idx as f32 * 0.01 + channel_idx as f32).sin() * 0.5)
+                    .collect()
+            })
+            .collect();
+        let mut writer = create_wav_writer(&input_file.0, 48000.0, 2).unwrap();
+        for sample_idx in 0..1000 {
+            for channel in &input {
+                writer.write_sample(channel[sample_idx]).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+
+        let mut backend =
+            File::new::<PassthroughPlugin>(file_config(&input_file.0, &output_file.0)).unwrap();
+        // The input has already been copied to the output buffer, so not touching the buffer
+        // makes this a passthrough
+        let mut num_calls = 0;
+        Backend::<PassthroughPlugin>::run(&mut backend, move |buffer, _, transport, _, _| {
+            assert_eq!(transport.pos_samples, Some(num_calls * 64));
+            assert_eq!(buffer.channels(), 2);
+            num_calls += 1;
+            true
+        })
+        .unwrap();
+
+        let output = read_wav_file(&output_file.0, 48000.0, 2).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn reject_zero_channel_files() {
+        let input_file = TempPath::new("no-channels.wav");
+
+        // A 32-bit floating point WAV file with a `fmt ` chunk that declares zero channels
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&36u32.to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&3u16.to_le_bytes());
+        wav.extend_from_slice(&0u16.to_le_bytes());
+        wav.extend_from_slice(&48000u32.to_le_bytes());
+        wav.extend_from_slice(&0u32.to_le_bytes());
+        wav.extend_from_slice(&0u16.to_le_bytes());
+        wav.extend_from_slice(&32u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&0u32.to_le_bytes());
+        fs::write(&input_file.0, wav).unwrap();
+
+        assert!(read_wav_file(&input_file.0, 48000.0, 2).is_err());
+    }
+
+    /// Writing to `/dev/full` always fails, so rendering should result in an error instead of
+    /// silently succeeding.
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn write_errors_are_returned() {
+        let input_file = TempPath::new("write-error-input.wav");
+
+        let mut writer = create_wav_writer(&input_file.0, 48000.0, 2).unwrap();
+        for _ in 0..2000 {
+            writer.write_sample(0.0f32).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut backend =
+            File::new::<PassthroughPlugin>(file_config(&input_file.0, Path::new("/dev/full")))
+                .unwrap();
+        assert!(Backend::<PassthroughPlugin>::run(&mut backend, |_, _, _, _, _| true).is_err());
+    }
+}
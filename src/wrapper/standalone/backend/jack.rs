@@ -64,7 +64,7 @@ impl<P: Plugin> Backend<P> for Jack {
             ) -> bool
             + 'static
             + Send,
-    ) {
+    ) -> Result<()> {
         let client = self.client.take().unwrap();
         let buffer_size = client.buffer_size();
 
@@ -236,6 +236,26 @@ impl<P: Plugin> Backend<P> for Jack {
                             num_channels: output_channel_pointers.get().len(),
                         });
                     }
+
+                    // An auxiliary port is active as long as any of its channels are connected
+                    for (input_active, input_ports) in buffer_sources
+                        .aux_input_active
+                        .iter_mut()
+                        .zip(aux_input_ports.iter())
+                    {
+                        *input_active = input_ports
+                            .iter()
+                            .any(|port| matches!(port.connected_count(), Ok(count) if count > 0));
+                    }
+                    for (output_active, output_ports) in buffer_sources
+                        .aux_output_active
+                        .iter_mut()
+                        .zip(aux_output_ports.iter())
+                    {
+                        *output_active = output_ports
+                            .iter()
+                            .any(|port| matches!(port.connected_count(), Ok(count) if count > 0));
+                    }
                 })
             };
 
@@ -314,6 +334,8 @@ impl<P: Plugin> Backend<P> for Jack {
         // And put the client back where it belongs in case this function is called a second time
         let (client, _, _) = async_client.deactivate().unwrap();
         self.client = Some(client);
+
+        Ok(())
     }
 }
 
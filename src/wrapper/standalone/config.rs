@@ -1,5 +1,6 @@
 use clap::{Parser, ValueEnum};
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 
 use crate::prelude::{AudioIOLayout, Plugin};
 
@@ -23,7 +24,8 @@ pub struct WrapperConfig {
     pub audio_layout: Option<String>,
     /// The audio backend's sample rate.
     ///
-    /// This setting is ignored when using the JACK backend.
+    /// This setting is ignored when using the JACK backend. When using the file backend this needs
+    /// to match the input files' sample rate.
     #[clap(value_parser, short = 'r', long, default_value = "48000")]
     pub sample_rate: f32,
     /// The audio backend's period size.
@@ -75,6 +77,38 @@ pub struct WrapperConfig {
     #[clap(value_parser, long)]
     pub connect_jack_midi_output: Option<String>,
 
+    /// The WAV file to read the main audio input from.
+    ///
+    /// This option is only used with the file backend. Mono files are played back on all input
+    /// channels.
+    #[clap(value_parser, long)]
+    pub input_file: Option<PathBuf>,
+    /// A comma separated list of WAV files to read the auxiliary (sidechain) inputs from, in the
+    /// same order as the plugin's auxiliary input ports.
+    ///
+    /// This option is only used with the file backend.
+    #[clap(value_parser, long, value_delimiter = ',')]
+    pub aux_input_files: Vec<PathBuf>,
+    /// A Standard MIDI File containing the note events that should be sent to the plugin.
+    ///
+    /// This option is only used with the file backend.
+    #[clap(value_parser, long)]
+    pub midi_input_file: Option<PathBuf>,
+    /// The WAV file the main audio output will be written to as 32-bit floating point audio.
+    /// Auxiliary outputs are written to the same location with the port's name appended to the
+    /// file name.
+    ///
+    /// This option is required for the file backend, and it is not used with any of the other
+    /// backends.
+    #[clap(value_parser, long)]
+    pub output_file: Option<PathBuf>,
+    /// The number of seconds of audio to keep rendering after the end of the input files. Useful
+    /// for plugins that produce a tail, like reverbs and delays.
+    ///
+    /// This option is only used with the file backend.
+    #[clap(value_parser, long, default_value = "0")]
+    pub tail_length: f32,
+
     /// The editor's DPI scaling factor.
     ///
     /// This option is ignored on macOS.
@@ -84,6 +118,16 @@ pub struct WrapperConfig {
     #[clap(value_parser, long, default_value = "1.0")]
     pub dpi_scale: f32,
 
+    /// Read commands from STDIN to list and change parameters, save and load the plugin's state,
+    /// and send notes to the plugin while it's running. Type 'help' for a list of commands.
+    #[clap(value_parser, long)]
+    pub repl: bool,
+    /// Listen for the same commands as `--repl` on a Unix domain socket at this path. Any existing
+    /// socket at that location will be replaced.
+    #[cfg(unix)]
+    #[clap(value_parser, long)]
+    pub repl_socket: Option<PathBuf>,
+
     /// The transport's tempo.
     #[clap(value_parser, long, default_value = "120")]
     pub tempo: f32,
@@ -115,6 +159,9 @@ pub enum BackendType {
     Wasapi,
     /// Does not playback or receive any audio or MIDI.
     Dummy,
+    /// Read audio and MIDI from files, and render the plugin's output to WAV files as fast as
+    /// possible. The plugin is processed in offline mode and its editor is not opened.
+    File,
 }
 
 impl WrapperConfig {
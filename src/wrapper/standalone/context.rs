@@ -3,8 +3,8 @@ use std::sync::Arc;
 use super::backend::Backend;
 use super::wrapper::{Task, Wrapper};
 use crate::prelude::{
-    GuiContext, InitContext, ParamPtr, Plugin, PluginApi, PluginNoteEvent, ProcessContext,
-    Transport,
+    ContextMenuItem, GuiContext, InitContext, ParamPtr, Plugin, PluginApi, PluginNoteEvent,
+    ProcessContext, TrackInfo, Transport,
 };
 
 /// An [`InitContext`] implementation for the standalone wrapper.
@@ -41,6 +41,11 @@ impl<P: Plugin, B: Backend<P>> InitContext<P> for WrapperInitContext<'_, P, B> {
         PluginApi::Standalone
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        // There's no host to get the track information from
+        None
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.wrapper.task_executor.lock())(task);
     }
@@ -97,6 +102,10 @@ impl<P: Plugin, B: Backend<P>> ProcessContext<P> for WrapperProcessContext<'_, P
     fn set_current_voice_capacity(&self, _capacity: u32) {
         // This is only supported by CLAP
     }
+
+    fn note_names_changed(&self) {
+        // There's no host to notify
+    }
 }
 
 impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
@@ -104,6 +113,10 @@ impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
         PluginApi::Standalone
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        None
+    }
+
     fn request_resize(&self) -> bool {
         self.wrapper.request_resize();
         true
@@ -152,6 +165,23 @@ impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
         }
     }
 
+    unsafe fn raw_show_param_context_menu(&self, _param: ParamPtr, _x: i32, _y: i32) -> bool {
+        // There's no host to provide a context menu
+        false
+    }
+
+    unsafe fn raw_param_context_menu_items(&self, _param: ParamPtr) -> Vec<ContextMenuItem> {
+        Vec::new()
+    }
+
+    unsafe fn raw_perform_param_context_menu_action(
+        &self,
+        _param: ParamPtr,
+        _action_id: u32,
+    ) -> bool {
+        false
+    }
+
     fn get_state(&self) -> crate::wrapper::state::PluginState {
         self.wrapper.get_state_object()
     }
@@ -159,4 +189,8 @@ impl<P: Plugin, B: Backend<P>> GuiContext for WrapperGuiContext<P, B> {
     fn set_state(&self, state: crate::wrapper::state::PluginState) {
         self.wrapper.set_state_object_from_gui(state)
     }
+
+    fn note_names_changed(&self) {
+        // There's no host to notify
+    }
 }
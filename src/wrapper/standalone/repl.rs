@@ -0,0 +1,616 @@
+//! A small command shell for interacting with a running standalone application. Commands can be
+//! read from STDIN and from a Unix domain socket. This is mostly useful for headless standalone
+//! builds, where there's no editor to change the parameters with.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use super::backend::Backend;
+use super::wrapper::Wrapper;
+use crate::prelude::{MidiConfig, NoteEvent, ParamFlags, ParamPtr, Plugin};
+use crate::wrapper::state::PluginState;
+
+const HELP: &str = "\
+Available commands:
+  params                          List all parameters with their IDs and current values
+  get <param_id>                  Show a parameter's current value
+  set <param_id> <value>          Set a parameter from a string, for instance '-6 dB' or '440 Hz'
+  reset <param_id>                Reset a parameter to its default value
+  save <path>                     Save the plugin's state to a JSON file
+  load <path>                     Load the plugin's state from a JSON file
+  note <note> [velocity] [chan]   Send a note on event, velocity is in [0, 1]
+  off <note> [velocity] [chan]    Send a note off event
+  help                            Show this message
+  quit                            End this session";
+
+/// The result of executing a command.
+#[derive(Debug, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// A parsed command. See [`HELP`] for a description of these commands.
+#[derive(Debug, PartialEq)]
+enum Command<'a> {
+    Empty,
+    Help,
+    Quit,
+    Params,
+    Get(&'a str),
+    Set(&'a str, &'a str),
+    Reset(&'a str),
+    Save(&'a Path),
+    Load(&'a Path),
+    Note {
+        is_note_on: bool,
+        note: u8,
+        velocity: f32,
+        channel: u8,
+    },
+}
+
+/// The parts of the standalone wrapper the REPL interacts with. This is implemented for
+/// [`Wrapper`], and it allows the command handling to be tested without running a plugin.
+trait ReplTarget {
+    /// The plugin's SysEx message type, needed for sending note events.
+    type SysExMessage;
+
+    const NAME: &'static str;
+    const VERSION: &'static str;
+    const MIDI_INPUT: MidiConfig;
+
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)>;
+    fn param_ptr_by_id(&self, param_id: &str) -> Option<ParamPtr>;
+    fn set_parameter(&self, param: ParamPtr, normalized: f32) -> bool;
+    fn get_state_object(&self) -> PluginState;
+    fn set_state_object(&self, state: PluginState);
+    fn queue_note_event(&self, event: NoteEvent<Self::SysExMessage>) -> bool;
+}
+
+impl<P: Plugin, B: Backend<P>> ReplTarget for Wrapper<P, B> {
+    type SysExMessage = P::SysExMessage;
+
+    const NAME: &'static str = P::NAME;
+    const VERSION: &'static str = P::VERSION;
+    const MIDI_INPUT: MidiConfig = P::MIDI_INPUT;
+
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.param_map()
+    }
+
+    fn param_ptr_by_id(&self, param_id: &str) -> Option<ParamPtr> {
+        self.param_ptr_by_id(param_id)
+    }
+
+    fn set_parameter(&self, param: ParamPtr, normalized: f32) -> bool {
+        self.set_parameter(param, normalized)
+    }
+
+    fn get_state_object(&self) -> PluginState {
+        self.get_state_object()
+    }
+
+    fn set_state_object(&self, state: PluginState) {
+        self.set_state_object_from_gui(state)
+    }
+
+    fn queue_note_event(&self, event: NoteEvent<Self::SysExMessage>) -> bool {
+        self.queue_note_event(event)
+    }
+}
+
+/// Spawn a thread that reads commands from STDIN until STDIN is closed or the `quit` command is
+/// used.
+pub fn spawn_stdin<P: Plugin, B: Backend<P>>(wrapper: Arc<Wrapper<P, B>>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        run_session(&*wrapper, stdin.lock(), io::stdout())
+    });
+}
+
+/// Spawn a thread that listens for connections on a Unix domain socket at `path`. Every connection
+/// gets its own session. An existing socket at the path is replaced, but any other kind of file is
+/// left alone and results in an error. The socket can only be accessed by the current user since
+/// the `save` and `load` commands can read and write arbitrary files.
+#[cfg(unix)]
+pub fn spawn_socket<P: Plugin, B: Backend<P>>(
+    wrapper: Arc<Wrapper<P, B>>,
+    path: &Path,
+) -> Result<()> {
+    spawn_socket_for_target(wrapper, path)
+}
+
+#[cfg(unix)]
+fn spawn_socket_for_target<T: ReplTarget + Send + Sync + 'static>(
+    target: Arc<T>,
+    path: &Path,
+) -> Result<()> {
+    use std::io::BufReader;
+
+    let listener = bind_socket(path)?;
+    nih_log!("Listening for REPL connections on {path:?}");
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let target = target.clone();
+                    thread::spawn(move || match stream.try_clone() {
+                        Ok(reader) => run_session(&*target, BufReader::new(reader), stream),
+                        Err(err) => nih_error!("Could not set up the REPL connection: {err}"),
+                    });
+                }
+                Err(err) => nih_error!("Error while accepting a REPL connection: {err}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Create the REPL's Unix domain socket at `path`, replacing a socket left behind by a previous
+/// instance. The socket's permissions are restricted to the current user.
+#[cfg(unix)]
+fn bind_socket(path: &Path) -> Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by a previous instance would otherwise prevent binding the socket. This
+    // doesn't follow symlinks, and anything that's not a socket is never removed.
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)
+            .with_context(|| format!("Could not remove the existing socket at {path:?}"))?,
+        Ok(_) => anyhow::bail!("{path:?} already exists and it is not a socket"),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Could not access {path:?}"));
+        }
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Could not create a socket at {path:?}"))?;
+    if let Err(err) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        // Leaving a socket other users can connect to around would not be a good idea
+        let _ = fs::remove_file(path);
+        return Err(err)
+            .with_context(|| format!("Could not set the permissions for the socket at {path:?}"));
+    }
+
+    Ok(listener)
+}
+
+/// Read and execute commands line by line until the input is closed or the user quits.
+fn run_session<T: ReplTarget>(target: &T, input: impl BufRead, mut output: impl Write) {
+    let _ = writeln!(
+        output,
+        "{} {}. Type 'help' for a list of commands.",
+        T::NAME,
+        T::VERSION
+    );
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        let result =
+            parse_command(&line).and_then(|command| execute_command(target, command, &mut output));
+        match result {
+            Ok(Flow::Continue) => (),
+            Ok(Flow::Quit) => break,
+            Err(err) => {
+                if writeln!(output, "error: {err:#}").is_err() {
+                    break;
+                }
+            }
+        }
+        if output.flush().is_err() {
+            break;
+        }
+    }
+}
+
+/// Parse a single line of input. This only checks the syntax and the ranges of the arguments.
+fn parse_command(line: &str) -> Result<Command<'_>> {
+    let line = line.trim();
+    let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let args = args.trim();
+
+    let command = match command {
+        "" => Command::Empty,
+        "help" | "?" => Command::Help,
+        "quit" | "exit" => Command::Quit,
+        "params" | "list" => Command::Params,
+        "get" => {
+            anyhow::ensure!(!args.is_empty(), "Usage: get <param_id>");
+            Command::Get(args)
+        }
+        "set" => {
+            let (param_id, value) = args
+                .split_once(char::is_whitespace)
+                .context("Usage: set <param_id> <value>")?;
+            Command::Set(param_id, value.trim())
+        }
+        "reset" => {
+            anyhow::ensure!(!args.is_empty(), "Usage: reset <param_id>");
+            Command::Reset(args)
+        }
+        "save" => {
+            anyhow::ensure!(!args.is_empty(), "Usage: save <path>");
+            Command::Save(Path::new(args))
+        }
+        "load" => {
+            anyhow::ensure!(!args.is_empty(), "Usage: load <path>");
+            Command::Load(Path::new(args))
+        }
+        "note" | "off" => {
+            let is_note_on = command == "note";
+
+            let mut args = args.split_whitespace();
+            let note: u8 = args
+                .next()
+                .with_context(|| format!("Usage: {command} <note> [velocity] [channel]"))?
+                .parse()
+                .context("The note number should be an integer")?;
+            let velocity: f32 = match args.next() {
+                Some(velocity) => velocity.parse().context("Invalid velocity")?,
+                None if is_note_on => 1.0,
+                None => 0.0,
+            };
+            let channel: u8 = match args.next() {
+                Some(channel) => channel.parse().context("Invalid channel")?,
+                None => 0,
+            };
+            anyhow::ensure!(note < 128, "The note number should be in 0..128");
+            anyhow::ensure!(channel < 16, "The channel should be in 0..16");
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&velocity),
+                "The velocity should be in [0, 1]"
+            );
+
+            Command::Note {
+                is_note_on,
+                note,
+                velocity,
+                channel,
+            }
+        }
+        command => anyhow::bail!("Unknown command '{command}', type 'help' for a list of commands"),
+    };
+
+    Ok(command)
+}
+
+/// Execute a parsed command, writing the results to `output`.
+fn execute_command<T: ReplTarget>(
+    target: &T,
+    command: Command,
+    output: &mut impl Write,
+) -> Result<Flow> {
+    match command {
+        Command::Empty => (),
+        Command::Help => writeln!(output, "{HELP}")?,
+        Command::Quit => return Ok(Flow::Quit),
+        Command::Params => {
+            for (param_id, param_ptr, group) in target.param_map() {
+                if unsafe { param_ptr.flags() }.contains(ParamFlags::HIDDEN) {
+                    continue;
+                }
+
+                let name = unsafe { param_ptr.name() };
+                let normalized_value = unsafe { param_ptr.unmodulated_normalized_value() };
+                let value = unsafe { param_ptr.normalized_value_to_string(normalized_value, true) };
+                if group.is_empty() {
+                    writeln!(output, "{param_id}: {name} = {value}")?;
+                } else {
+                    writeln!(output, "{param_id}: {group}/{name} = {value}")?;
+                }
+            }
+        }
+        Command::Get(param_id) => {
+            let param_ptr = target
+                .param_ptr_by_id(param_id)
+                .with_context(|| format!("Unknown parameter '{param_id}'"))?;
+            let normalized_value = unsafe { param_ptr.unmodulated_normalized_value() };
+            writeln!(
+                output,
+                "{} = {} (normalized: {normalized_value})",
+                unsafe { param_ptr.name() },
+                unsafe { param_ptr.normalized_value_to_string(normalized_value, true) }
+            )?;
+        }
+        Command::Set(param_id, value) => {
+            let param_ptr = target
+                .param_ptr_by_id(param_id)
+                .with_context(|| format!("Unknown parameter '{param_id}'"))?;
+            let normalized_value = unsafe { param_ptr.string_to_normalized_value(value) }
+                .with_context(|| format!("Could not parse '{value}'"))?;
+
+            anyhow::ensure!(
+                target.set_parameter(param_ptr, normalized_value),
+                "The parameter change queue is full"
+            );
+            writeln!(output, "{} = {}", unsafe { param_ptr.name() }, unsafe {
+                param_ptr.normalized_value_to_string(normalized_value, true)
+            })?;
+        }
+        Command::Reset(param_id) => {
+            let param_ptr = target
+                .param_ptr_by_id(param_id)
+                .with_context(|| format!("Unknown parameter '{param_id}'"))?;
+            let normalized_value = unsafe { param_ptr.default_normalized_value() };
+
+            anyhow::ensure!(
+                target.set_parameter(param_ptr, normalized_value),
+                "The parameter change queue is full"
+            );
+            writeln!(output, "{} = {}", unsafe { param_ptr.name() }, unsafe {
+                param_ptr.normalized_value_to_string(normalized_value, true)
+            })?;
+        }
+        Command::Save(path) => {
+            let state = target.get_state_object();
+            let json = serde_json::to_string_pretty(&state).context("Could not format as JSON")?;
+            fs::write(path, json).with_context(|| format!("Could not write to {path:?}"))?;
+            writeln!(output, "Saved the plugin's state to {path:?}")?;
+        }
+        Command::Load(path) => {
+            let json = fs::read(path).with_context(|| format!("Could not read {path:?}"))?;
+            let state: PluginState =
+                serde_json::from_slice(&json).context("Could not parse the plugin state")?;
+            target.set_state_object(state);
+            writeln!(output, "Loaded the plugin's state from {path:?}")?;
+        }
+        Command::Note {
+            is_note_on,
+            note,
+            velocity,
+            channel,
+        } => {
+            anyhow::ensure!(
+                T::MIDI_INPUT >= MidiConfig::Basic,
+                "The plugin does not accept note events"
+            );
+
+            let event = if is_note_on {
+                NoteEvent::NoteOn {
+                    timing: 0,
+                    voice_id: None,
+                    channel,
+                    note,
+                    velocity,
+                }
+            } else {
+                NoteEvent::NoteOff {
+                    timing: 0,
+                    voice_id: None,
+                    channel: Some(channel),
+                    note: Some(note),
+                    velocity,
+                }
+            };
+            anyhow::ensure!(
+                target.queue_note_event(event),
+                "The note event queue is full"
+            );
+        }
+    }
+
+    Ok(Flow::Continue)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::prelude::{FloatParam, FloatRange, Param};
+
+    /// A stand-in for the wrapper with a single parameter that records everything the REPL does.
+    struct TestTarget {
+        gain: FloatParam,
+        parameter_changes: Mutex<Vec<(ParamPtr, f32)>>,
+        loaded_states: Mutex<Vec<PluginState>>,
+        note_events: Mutex<Vec<NoteEvent<()>>>,
+    }
+
+    impl TestTarget {
+        fn new() -> Self {
+            Self {
+                gain: FloatParam::new("Gain", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+                parameter_changes: Mutex::new(Vec::new()),
+                loaded_states: Mutex::new(Vec::new()),
+                note_events: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl ReplTarget for TestTarget {
+        type SysExMessage = ();
+
+        const NAME: &'static str = "Test Plugin";
+        const VERSION: &'static str = "1.2.3";
+        const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            vec![(String::from("gain"), self.gain.as_ptr(), String::new())]
+        }
+
+        fn param_ptr_by_id(&self, param_id: &str) -> Option<ParamPtr> {
+            (param_id == "gain").then(|| self.gain.as_ptr())
+        }
+
+        fn set_parameter(&self, param: ParamPtr, normalized: f32) -> bool {
+            self.parameter_changes
+                .lock()
+                .unwrap()
+                .push((param, normalized));
+            true
+        }
+
+        fn get_state_object(&self) -> PluginState {
+            PluginState {
+                version: String::from(Self::VERSION),
+                params: BTreeMap::new(),
+                fields: BTreeMap::new(),
+            }
+        }
+
+        fn set_state_object(&self, state: PluginState) {
+            self.loaded_states.lock().unwrap().push(state);
+        }
+
+        fn queue_note_event(&self, event: NoteEvent<()>) -> bool {
+            self.note_events.lock().unwrap().push(event);
+            true
+        }
+    }
+
+    /// A unique path in the temporary directory. The file is removed when this gets dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("nih-plug-repl-{}-{name}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn run_lines(target: &TestTarget, input: &str) -> String {
+        let mut output = Vec::new();
+        run_session(target, Cursor::new(input), &mut output);
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse_command("  ").unwrap(), Command::Empty);
+        assert_eq!(parse_command("?").unwrap(), Command::Help);
+        assert_eq!(parse_command("exit").unwrap(), Command::Quit);
+        assert_eq!(parse_command("list").unwrap(), Command::Params);
+        assert_eq!(parse_command("get gain").unwrap(), Command::Get("gain"));
+        assert_eq!(
+            parse_command("set gain   -6 dB ").unwrap(),
+            Command::Set("gain", "-6 dB")
+        );
+        assert_eq!(parse_command("reset gain").unwrap(), Command::Reset("gain"));
+        assert_eq!(
+            parse_command("save /tmp/some state.json").unwrap(),
+            Command::Save(Path::new("/tmp/some state.json"))
+        );
+        assert_eq!(
+            parse_command("note 60").unwrap(),
+            Command::Note {
+                is_note_on: true,
+                note: 60,
+                velocity: 1.0,
+                channel: 0
+            }
+        );
+        assert_eq!(
+            parse_command("off 60 0.5 3").unwrap(),
+            Command::Note {
+                is_note_on: false,
+                note: 60,
+                velocity: 0.5,
+                channel: 3
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_commands() {
+        assert!(parse_command("frobnicate").is_err());
+        assert!(parse_command("get").is_err());
+        assert!(parse_command("set gain").is_err());
+        assert!(parse_command("load").is_err());
+        assert!(parse_command("note").is_err());
+        assert!(parse_command("note 128").is_err());
+        assert!(parse_command("note 60 1.5").is_err());
+        assert!(parse_command("note 60 1.0 16").is_err());
+    }
+
+    #[test]
+    fn session_sets_parameters_and_sends_notes() {
+        let target = TestTarget::new();
+        let output = run_lines(
+            &target,
+            "params\nset gain 0.25\nbogus\nnote 64 0.5\nquit\nreset gain\n",
+        );
+
+        assert!(output.starts_with("Test Plugin 1.2.3."));
+        assert!(output.contains("gain: Gain = 0.5"));
+        assert!(output.contains("error: Unknown command 'bogus'"));
+
+        // Everything after `quit` should be ignored
+        let parameter_changes = target.parameter_changes.lock().unwrap();
+        assert_eq!(parameter_changes.len(), 1);
+        assert_eq!(parameter_changes[0].0, target.gain.as_ptr());
+        assert_eq!(parameter_changes[0].1, 0.25);
+
+        let note_events = target.note_events.lock().unwrap();
+        assert_eq!(
+            note_events.as_slice(),
+            &[NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 64,
+                velocity: 0.5
+            }]
+        );
+    }
+
+    #[test]
+    fn session_saves_and_loads_state() {
+        let target = TestTarget::new();
+        let path = TempPath::new("state.json");
+        let output = run_lines(
+            &target,
+            &format!("save {0}\nload {0}\n", path.0.to_str().unwrap()),
+        );
+
+        assert!(!output.contains("error:"), "{output}");
+        let loaded_states = target.loaded_states.lock().unwrap();
+        assert_eq!(loaded_states.len(), 1);
+        assert_eq!(loaded_states[0].version, "1.2.3");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = TempPath::new("socket");
+        let listener = bind_socket(&path.0).unwrap();
+        let mode = fs::metadata(&path.0).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A stale socket from a previous instance can be replaced
+        drop(listener);
+        bind_socket(&path.0).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_does_not_replace_other_files() {
+        let path = TempPath::new("not-a-socket");
+        fs::write(&path.0, "important").unwrap();
+
+        assert!(bind_socket(&path.0).is_err());
+        assert_eq!(fs::read_to_string(&path.0).unwrap(), "important");
+    }
+}
@@ -11,15 +11,19 @@ use std::sync::Arc;
 use std::thread;
 
 use super::backend::Backend;
-use super::config::WrapperConfig;
+use super::config::{BackendType, WrapperConfig};
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
+use crate::audio_setup::AudioIOLayoutsValidator;
 use crate::event_loop::{EventLoop, MainThreadExecutor, OsEventLoop};
+use crate::midi::mpe::MpeTranslator;
 use crate::prelude::{
-    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, ParamFlags, ParamPtr, Params,
+    AsyncExecutor, AudioIOLayout, BufferConfig, Editor, MidiConfig, ParamFlags, ParamPtr, Params,
     ParentWindowHandle, Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor,
     Transport,
 };
-use crate::util::permit_alloc;
+use crate::wrapper::state::prepared::{
+    handoff_timeout, HandoffResult, PreparedState, StateHandoff,
+};
 use crate::wrapper::state::{self, PluginState};
 use crate::wrapper::util::process_wrapper;
 
@@ -51,8 +55,8 @@ pub struct Wrapper<P: Plugin, B: Backend<P>> {
     /// the way it does.
     event_loop: AtomicRefCell<Option<OsEventLoop<Task<P>, Self>>>,
 
-    /// This is used to grab the DPI scaling config. Not used on macOS.
-    #[allow(unused)]
+    /// This is used to grab the DPI scaling config and to check whether the plugin is rendering to
+    /// files in offline mode.
     config: WrapperConfig,
 
     /// A mapping from parameter pointers to string parameter IDs. This is used as part of
@@ -72,17 +76,22 @@ pub struct Wrapper<P: Plugin, B: Backend<P>> {
     /// This queue will be flushed at the end of every processing cycle, just like in the plugin
     /// versions.
     unprocessed_param_changes: ArrayQueue<(ParamPtr, f32)>,
+    /// Note events sent through the REPL that have not yet been sent to the plugin. These are
+    /// sent at the start of the next processing cycle.
+    unprocessed_note_events: ArrayQueue<PluginNoteEvent<P>>,
+    /// Translates the backend's MIDI input for MPE member channels to polyphonic expression events
+    /// if `P::MIDI_INPUT` is set to `MidiConfig::Mpe`. The translated events and the events from
+    /// `unprocessed_note_events` are written to `merged_input_events`, which is then passed to the
+    /// plugin instead of the backend's events.
+    mpe_translator: AtomicRefCell<MpeTranslator>,
+    merged_input_events: AtomicRefCell<Vec<PluginNoteEvent<P>>>,
     /// The plugin is able to restore state through a method on the `GuiContext`. To avoid changing
-    /// parameters mid-processing and running into garbled data if the host also tries to load state
-    /// at the same time the restoring happens at the end of each processing call. If this zero
-    /// capacity channel contains state data at that point, then the audio thread will take the
-    /// state out of the channel, restore the state, and then send it back through the same channel.
-    /// In other words, the GUI thread acts as a sender and then as a receiver, while the audio
-    /// thread acts as a receiver and then as a sender. That way deallocation can happen on the GUI
-    /// thread. All of this happens without any blocking on the audio thread.
-    updated_state_sender: channel::Sender<PluginState>,
-    /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
-    updated_state_receiver: channel::Receiver<PluginState>,
+    /// parameters mid-processing and to avoid doing any realtime-unsafe work on the audio thread,
+    /// the state is prepared on the GUI thread, and the prepared state is then applied at the end of
+    /// the next processing call. See [`set_state_inner()`][Self::set_state_inner()].
+    state_handoff: StateHandoff,
+    /// Whether the audio thread is currently running. State is applied directly when it isn't.
+    is_processing: AtomicBool,
     /// The current latency in samples, as set by the plugin through the [`InitContext`] and the
     /// [`ProcessContext`]. This value may not be used depending on the audio backend, but it's
     /// still kept track of to avoid firing debug assertions multiple times for the same latency
@@ -106,17 +115,23 @@ pub enum Task<P: Plugin> {
     ParameterValueChanged(ParamPtr, f32),
 }
 
-/// Errors that may arise while initializing the wrapped plugins.
-#[derive(Debug, Clone, Copy)]
+/// Errors that may arise while initializing or running the wrapped plugins.
+#[derive(Debug)]
 pub enum WrapperError {
     /// The plugin returned `false` during initialization.
     InitializationFailed,
+    /// The plugin returned [`ProcessStatus::Error`] while processing audio.
+    ProcessingError,
+    /// The audio backend stopped processing because of an error.
+    BackendError(anyhow::Error),
 }
 
 struct WrapperWindowHandler {
     /// The editor handle for the plugin's open editor. The editor should clean itself up when it
     /// gets dropped.
     _editor_handle: Box<dyn Any>,
+    /// The plugin's editor. Used to resize the editor when the window gets resized.
+    editor: Arc<Mutex<Box<dyn Editor>>>,
 
     /// This is used to communicate with the wrapper from the audio thread and from within the
     /// baseview window handler on the GUI thread.
@@ -146,7 +161,46 @@ impl WindowHandler for WrapperWindowHandler {
         }
     }
 
-    fn on_event(&mut self, _window: &mut Window, _event: baseview::Event) -> EventStatus {
+    fn on_event(&mut self, window: &mut Window, event: baseview::Event) -> EventStatus {
+        // When the user resizes the window, the editor needs to be resized to match. This works the
+        // same way as host initiated resizes in the plugin wrappers.
+        if let baseview::Event::Window(baseview::WindowEvent::Resized(window_info)) = event {
+            let logical_size = window_info.logical_size();
+            let (width, height) = (
+                logical_size.width.round() as u32,
+                logical_size.height.round() as u32,
+            );
+
+            // The editor must not stay locked while resizing the window, since some platforms
+            // handle the resulting resize event before `window.resize()` returns
+            let (editor_width, editor_height) = {
+                let editor = self.editor.lock();
+                if editor.size() == (width, height) {
+                    return EventStatus::Captured;
+                }
+
+                match editor.adjust_size(width, height) {
+                    Some((adjusted_width, adjusted_height))
+                        if editor.set_size(adjusted_width, adjusted_height) =>
+                    {
+                        (adjusted_width, adjusted_height)
+                    }
+                    // Editors that can't be resized will just keep their current size
+                    _ => editor.size(),
+                }
+            };
+
+            // The window may need to snap to the editor's constraints
+            if (editor_width, editor_height) != (width, height) {
+                window.resize(baseview::Size {
+                    width: editor_width as f64,
+                    height: editor_height as f64,
+                });
+            }
+
+            return EventStatus::Captured;
+        }
+
         EventStatus::Ignored
     }
 }
@@ -176,6 +230,8 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
     /// Instantiate a new instance of the standalone wrapper. Returns an error if the plugin does
     /// not accept the IO configuration from the wrapper config.
     pub fn new(backend: B, config: WrapperConfig) -> Result<Arc<Self>, WrapperError> {
+        let () = AudioIOLayoutsValidator::<P>::ASSERT_VALID;
+
         // The backend has already queried this, so this will never cause the program to exit
         // TODO: Do the validation and parsing in the argument parser so this value can be stored on
         //       the config itself. Right now clap doesn't support this.
@@ -185,10 +241,6 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         let task_executor = Mutex::new(plugin.task_executor());
         let params = plugin.params();
 
-        // This is used to allow the plugin to restore preset data from its editor, see the comment
-        // on `Self::updated_state_sender`
-        let (updated_state_sender, updated_state_receiver) = channel::bounded(0);
-
         // For consistency's sake we'll include the same assertions as the other backends
         // TODO: Move these common checks to a function instead of repeating them in every wrapper
         let param_map = params.param_map();
@@ -245,13 +297,19 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                 min_buffer_size: None,
                 max_buffer_size: config.period_size,
                 // TODO: Detect JACK freewheeling and report it here
-                process_mode: ProcessMode::Realtime,
+                process_mode: match config.backend {
+                    BackendType::File => ProcessMode::Offline,
+                    _ => ProcessMode::Realtime,
+                },
             },
             config,
 
             unprocessed_param_changes: ArrayQueue::new(EVENT_QUEUE_CAPACITY),
-            updated_state_sender,
-            updated_state_receiver,
+            unprocessed_note_events: ArrayQueue::new(EVENT_QUEUE_CAPACITY),
+            mpe_translator: AtomicRefCell::new(MpeTranslator::default()),
+            merged_input_events: AtomicRefCell::new(Vec::with_capacity(EVENT_QUEUE_CAPACITY * 2)),
+            state_handoff: StateHandoff::default(),
+            is_processing: AtomicBool::new(false),
             current_latency: AtomicU32::new(0),
         });
 
@@ -320,6 +378,15 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
             thread::spawn(move || this.run_audio_thread(terminate_audio_thread, gui_task_sender))
         };
 
+        // When rendering to files the editor is not opened, and the application terminates as
+        // soon as the backend has processed all of its input
+        if matches!(self.config.backend, BackendType::File) {
+            let result = audio_thread.join().unwrap();
+            self.plugin.lock().deactivate();
+
+            return result;
+        }
+
         match self.editor.borrow().clone() {
             Some(editor) => {
                 let context = self.clone().make_gui_context();
@@ -369,6 +436,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
 
                         WrapperWindowHandler {
                             _editor_handle: editor_handle,
+                            editor,
                             gui_task_receiver,
                         }
                     },
@@ -383,13 +451,13 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         }
 
         terminate_audio_thread.store(true, Ordering::SeqCst);
-        audio_thread.join().unwrap();
+        let result = audio_thread.join().unwrap();
 
         // Some plugins may use this to clean up resources. Should not be needed for the standalone
         // application, but it seems like a good idea to stay consistent.
         self.plugin.lock().deactivate();
 
-        Ok(())
+        result
     }
 
     /// Get a parameter's ID based on a `ParamPtr`. Used in the `GuiContext` implementation for the
@@ -399,6 +467,25 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         self.param_ptr_to_id.get(&param).map(|s| s.as_str())
     }
 
+    /// The plugin's parameters as returned by [`Params::param_map()`], in declaration order. Used by
+    /// the REPL.
+    pub fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.params.param_map()
+    }
+
+    /// Get a `ParamPtr` from a parameter's ID. Used by the REPL.
+    pub fn param_ptr_by_id(&self, param_id: &str) -> Option<ParamPtr> {
+        self.param_id_to_ptr.get(param_id).copied()
+    }
+
+    /// Send a note event to the plugin at the start of the next processing cycle. The event's
+    /// timing should be 0. Returns false if the queue is full.
+    pub fn queue_note_event(&self, event: PluginNoteEvent<P>) -> bool {
+        nih_debug_assert_eq!(event.timing(), 0);
+
+        self.unprocessed_note_events.push(event).is_ok()
+    }
+
     /// Set a parameter based on a `ParamPtr`. The value will be updated at the end of the next
     /// processing cycle, and this won't do anything if the parameter has not been registered by the
     /// plugin.
@@ -435,22 +522,10 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
 
     /// Update the plugin's internal state, called by the plugin itself from the GUI thread. To
     /// prevent corrupting data and changing parameters during processing the actual state is only
-    /// updated at the end of the audio processing cycle.
-    pub fn set_state_object_from_gui(&self, state: PluginState) {
-        match self.updated_state_sender.send(state) {
-            Ok(_) => {
-                // As mentioned above, the state object will be passed back to this thread
-                // so we can deallocate it without blocking.
-                let state = self.updated_state_receiver.recv();
-                drop(state);
-            }
-            Err(err) => {
-                nih_debug_assert_failure!(
-                    "Could not send new state to the audio thread: {:?}",
-                    err
-                );
-            }
-        }
+    /// updated at the end of the audio processing cycle. See
+    /// [`set_state_inner()`][Self::set_state_inner()].
+    pub fn set_state_object_from_gui(&self, mut state: PluginState) {
+        self.set_state_inner(&mut state);
     }
 
     /// Posts the task to the background task queue using [`EventLoop::schedule_background()`] so it
@@ -499,13 +574,21 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
     }
 
     /// The audio thread. This should be called from another thread, and it will run until
-    /// `should_terminate` is `true`.
+    /// `should_terminate` is `true`. Returns an error if the plugin or the backend ran into an
+    /// error while processing audio.
     fn run_audio_thread(
         self: Arc<Self>,
         should_terminate: Arc<AtomicBool>,
         gui_task_sender: channel::Sender<GuiTask>,
-    ) {
-        self.clone().backend.borrow_mut().run(
+    ) -> Result<(), WrapperError> {
+        // `self` is moved into the audio callback, and `set_state_inner()` needs to know when the
+        // audio thread has stopped
+        let this = self.clone();
+        let processing_failed = Arc::new(AtomicBool::new(false));
+        this.is_processing.store(true, Ordering::SeqCst);
+        let backend_result = self.clone().backend.borrow_mut().run({
+            let processing_failed = processing_failed.clone();
+
             move |buffer, aux, transport, input_events, output_events| {
                 // TODO: This process wrapper should actually be in the backends (since the backends
                 //       should also not allocate in their audio callbacks), but that's a bit more
@@ -517,6 +600,33 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
 
                     let sample_rate = self.buffer_config.sample_rate;
                     {
+                        // Events sent through the REPL are prepended to the backend's events. Since
+                        // these all use timing 0 the events stay sorted.
+                        let mut merged_input_events = self.merged_input_events.borrow_mut();
+                        let input_events = if P::MIDI_INPUT >= MidiConfig::Mpe
+                            || !self.unprocessed_note_events.is_empty()
+                        {
+                            merged_input_events.clear();
+                            while let Some(event) = self.unprocessed_note_events.pop() {
+                                merged_input_events.push(event);
+                            }
+
+                            if P::MIDI_INPUT >= MidiConfig::Mpe {
+                                let mut mpe_translator = self.mpe_translator.borrow_mut();
+                                for event in input_events {
+                                    mpe_translator.translate(event.clone(), |event| {
+                                        merged_input_events.push(event)
+                                    });
+                                }
+                            } else {
+                                merged_input_events.extend(input_events.iter().cloned());
+                            }
+
+                            &merged_input_events[..]
+                        } else {
+                            input_events
+                        };
+
                         let mut plugin = self.plugin.lock();
                         if let ProcessStatus::Error(err) = plugin.process(
                             buffer,
@@ -525,6 +635,7 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                         ) {
                             nih_error!("The plugin returned an error while processing:");
                             nih_error!("{}", err);
+                            processing_failed.store(true, Ordering::SeqCst);
 
                             let push_successful = gui_task_sender.send(GuiTask::Close).is_ok();
                             nih_debug_assert!(
@@ -557,30 +668,26 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
                         }
                     }
 
-                    // After processing audio, we'll check if the editor has sent us updated plugin
-                    // state.  We'll restore that here on the audio thread to prevent changing the
-                    // values during the process call and also to prevent inconsistent state when
-                    // the host also wants to load plugin state.
-                    // FIXME: Zero capacity channels allocate on receiving, find a better
-                    //        alternative that doesn't do that
-                    let updated_state = permit_alloc(|| self.updated_state_receiver.try_recv());
-                    if let Ok(mut state) = updated_state {
-                        self.set_state_inner(&mut state);
-
-                        // We'll pass the state object back to the GUI thread so deallocation can
-                        // happen there without potentially blocking the audio thread
-                        if let Err(err) = self.updated_state_sender.send(state) {
-                            nih_debug_assert_failure!(
-                                "Failed to send state object back to GUI thread: {}",
-                                err
-                            );
-                        };
-                    }
+                    // After processing audio, we'll check if the editor has loaded new plugin
+                    // state. The state has already been prepared on the GUI thread, so all that's
+                    // left to do here is to swap it in. Doing this at the end of the process call
+                    // prevents changing the values in the middle of processing.
+                    self.state_handoff.apply_pending(|prepared| {
+                        self.apply_prepared_state_on_audio_thread(prepared)
+                    });
 
                     true
                 })
-            },
-        );
+            }
+        });
+        this.is_processing.store(false, Ordering::SeqCst);
+
+        backend_result.map_err(WrapperError::BackendError)?;
+        if processing_failed.load(Ordering::SeqCst) {
+            return Err(WrapperError::ProcessingError);
+        }
+
+        Ok(())
     }
 
     fn make_gui_context(self: Arc<Self>) -> Arc<WrapperGuiContext<P, B>> {
@@ -610,56 +717,45 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
         }
     }
 
-    /// Immediately set the plugin state. Returns `false` if the deserialization failed. In other
+    /// Set the plugin state. Returns `false` if the plugin failed to load the state. In other
     /// wrappers state is set from a couple places, so this function is here to be consistent and to
-    /// centralize all of this behavior. Includes `permit_alloc()`s around the deserialization and
-    /// initialization for the use case where `set_state_object_from_gui()` was called while the
-    /// plugin is process audio.
+    /// centralize all of this behavior. The state is migrated, resolved, and prepared on the calling
+    /// thread. If the audio thread is running and the plugin prepared data for the state, then the
+    /// prepared state is swapped in at the end of the next processing call and this function blocks
+    /// until that has happened. Otherwise the state is applied immediately while holding the lock
+    /// on the plugin.
     ///
     /// Implicitly emits `Task::ParameterValuesChanged`.
     ///
     /// # Notes
     ///
-    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
     fn set_state_inner(&self, state: &mut PluginState) -> bool {
-        // FIXME: This is obviously not realtime-safe, but loading presets without doing this could
-        //        lead to inconsistencies. It's the plugin's responsibility to not perform any
-        //        realtime-unsafe work when the initialize function is called a second time if it
-        //        supports runtime preset loading. `state::deserialize_object()` normally never
-        //        allocates, but if the plugin has persistent non-parameter data then its
-        //        `deserialize_fields()` implementation may still allocate.
-        let mut success = permit_alloc(|| unsafe {
-            state::deserialize_object::<P>(
-                state,
-                self.params.clone(),
-                |param_id| self.param_id_to_ptr.get(param_id).copied(),
-                Some(&self.buffer_config),
-            )
+        let mut prepared = Box::new(unsafe {
+            PreparedState::prepare::<P>(state, |param_id| {
+                self.param_id_to_ptr.get(param_id).copied()
+            })
         });
-        if !success {
-            nih_debug_assert_failure!("Deserializing plugin state from a state object failed");
-            return false;
-        }
-
-        // If the plugin was already initialized then it needs to be reinitialized
-        {
-            // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
-            let mut init_context = self.make_init_context();
-            let mut plugin = self.plugin.lock();
-
-            // See above
-            success = permit_alloc(|| {
-                plugin.initialize(
-                    &self.audio_io_layout,
-                    &self.buffer_config,
-                    &mut init_context,
-                )
-            });
-            if success {
-                process_wrapper(|| plugin.reset());
+        prepared.set_plugin_data(P::prepare_state(
+            state,
+            &self.audio_io_layout,
+            &self.buffer_config,
+        ));
+
+        let success = match self.state_handoff.send_and_wait(
+            prepared,
+            &self.is_processing,
+            handoff_timeout(&self.buffer_config),
+        ) {
+            HandoffResult::Applied(prepared) => {
+                prepared.apply_fields(&*self.params);
+                true
             }
-        }
-
+            // The audio thread is not running, the plugin didn't prepare anything, or it needs to
+            // be reinitialized, so the state is applied right here
+            HandoffResult::NotApplied(mut prepared) => self.apply_prepared_state(&mut prepared),
+        };
         nih_debug_assert!(
             success,
             "Plugin returned false when reinitializing after loading state"
@@ -676,4 +772,45 @@ impl<P: Plugin, B: Backend<P>> Wrapper<P, B> {
 
         success
     }
+
+    /// Apply state prepared in [`set_state_inner()`][Self::set_state_inner()] on the calling
+    /// thread. The plugin is locked while doing so, and it's reinitialized if it doesn't swap in the
+    /// prepared state.
+    ///
+    /// # Notes
+    ///
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
+    fn apply_prepared_state(&self, prepared: &mut PreparedState) -> bool {
+        // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
+        let mut init_context = self.make_init_context();
+        let mut plugin = self.plugin.lock();
+        unsafe {
+            prepared.apply_with_plugin_lock(
+                &mut *plugin,
+                &*self.params,
+                Some(self.buffer_config.sample_rate),
+                |plugin| {
+                    plugin.initialize(
+                        &self.audio_io_layout,
+                        &self.buffer_config,
+                        &mut init_context,
+                    )
+                },
+            )
+        }
+    }
+
+    /// Swap in state prepared in [`set_state_inner()`][Self::set_state_inner()]. This is called at
+    /// the end of a processing call when state is loaded while the audio thread is running, and it
+    /// does not allocate. Returns `false` if the plugin needs to be reinitialized on the loading
+    /// thread instead.
+    ///
+    /// # Notes
+    ///
+    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    fn apply_prepared_state_on_audio_thread(&self, prepared: &mut PreparedState) -> bool {
+        let mut plugin = self.plugin.lock();
+        unsafe { prepared.apply_on_audio_thread(&mut *plugin, self.buffer_config.sample_rate) }
+    }
 }
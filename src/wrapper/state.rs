@@ -6,8 +6,12 @@ use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
-use crate::params::ParamMut;
-use crate::prelude::{BufferConfig, Param, ParamPtr, Params, Plugin};
+use crate::prelude::{Param, ParamPtr, Params, Plugin};
+
+pub mod migration;
+pub(crate) mod prepared;
+
+pub use migration::StateMigration;
 
 // These state objects are also exposed directly to the plugin so it can do its own internal preset
 // management
@@ -29,9 +33,9 @@ pub enum ParamValue {
 /// The fields are stored as `BTreeMap`s so the order in the serialized file is consistent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginState {
-    /// The plugin version this state was saved with. Right now this is not used, but later versions
-    /// of NIH-plug may allow you to modify the plugin state object directly before it is loaded to
-    /// allow migrating plugin states between breaking parameter changes.
+    /// The plugin version this state was saved with. This is used to run the plugin's
+    /// [`STATE_MIGRATIONS`][Plugin::STATE_MIGRATIONS] before the state is loaded, and it can also
+    /// be inspected in [`Plugin::filter_state()`].
     ///
     /// # Notes
     ///
@@ -159,89 +163,11 @@ pub(crate) unsafe fn serialize_json<'a, P: Plugin>(
     }
 }
 
-/// Deserialize a plugin's state from a [`PluginState`] object. This is used to allow the plugin to
-/// do its own internal preset management. Returns `false` and logs an error if the state could not
-/// be deserialized.
-///
-/// This uses a parameter getter function to avoid having to rebuild the parameter map, which may
-/// include expensive user written code. See [`make_params_getter()`].
-///
-/// Make sure to reinitialize plugin after deserializing the state so it can react to the new
-/// parameter values. The smoothers have already been reset by this function.
-///
-/// The [`Plugin`] argument is used to call [`Plugin::filter_state()`] just before loading the
-/// state.
-pub(crate) unsafe fn deserialize_object<P: Plugin>(
-    state: &mut PluginState,
-    plugin_params: Arc<dyn Params>,
-    params_getter: impl Fn(&str) -> Option<ParamPtr>,
-    current_buffer_config: Option<&BufferConfig>,
-) -> bool {
-    // This lets the plugin perform migrations on old state if needed
-    P::filter_state(state);
-
-    let sample_rate = current_buffer_config.map(|c| c.sample_rate);
-    for (param_id_str, param_value) in &state.params {
-        let param_ptr = match params_getter(param_id_str.as_str()) {
-            Some(ptr) => ptr,
-            None => {
-                nih_debug_assert_failure!("Unknown parameter: {}", param_id_str);
-                continue;
-            }
-        };
-
-        match (param_ptr, param_value) {
-            (ParamPtr::FloatParam(p), ParamValue::F32(v)) => {
-                (*p).set_plain_value(*v);
-            }
-            (ParamPtr::IntParam(p), ParamValue::I32(v)) => {
-                (*p).set_plain_value(*v);
-            }
-            (ParamPtr::BoolParam(p), ParamValue::Bool(v)) => {
-                (*p).set_plain_value(*v);
-            }
-            // Enums are either serialized based on the active variant's index (which may not be the
-            // same as the discriminator), or a custom set stable string ID. The latter allows the
-            // variants to be reordered.
-            (ParamPtr::EnumParam(p), ParamValue::I32(variant_idx)) => {
-                (*p).set_plain_value(*variant_idx);
-            }
-            (ParamPtr::EnumParam(p), ParamValue::String(id)) => {
-                let deserialized_enum = (*p).set_from_id(id);
-                nih_debug_assert!(
-                    deserialized_enum,
-                    "Unknown ID {:?} for enum parameter \"{}\"",
-                    id,
-                    param_id_str,
-                );
-            }
-            (param_ptr, param_value) => {
-                nih_debug_assert_failure!(
-                    "Invalid serialized value {:?} for parameter \"{}\" ({:?})",
-                    param_value,
-                    param_id_str,
-                    param_ptr,
-                );
-            }
-        }
-
-        // Make sure everything starts out in sync
-        if let Some(sample_rate) = sample_rate {
-            param_ptr.update_smoother(sample_rate, true);
-        }
-    }
-
-    // The plugin can also persist arbitrary fields alongside its parameters. This is useful for
-    // storing things like sample data.
-    plugin_params.deserialize_fields(&state.fields);
-
-    true
-}
-
 /// Deserialize a plugin's state from a vector containing (compressed) JSON data. Doesn't load the
 /// plugin state since doing so should be accompanied by calls to `Plugin::init()` and
 /// `Plugin::reset()`, and this way all of that behavior can be encapsulated so it can be reused in
-/// multiple places. The returned state object can be passed to [`deserialize_object()`].
+/// multiple places. The returned state object can be loaded using
+/// [`prepared::PreparedState`].
 pub(crate) unsafe fn deserialize_json(state: &[u8]) -> Option<PluginState> {
     #[cfg(feature = "zstd")]
     let result: Option<PluginState> = match zstd::decode_all(state) {
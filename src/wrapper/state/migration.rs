@@ -0,0 +1,328 @@
+//! Declarative migrations for state saved by older versions of a plugin.
+
+use semver::{Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::{ParamValue, PluginState};
+
+/// A single migration step for old plugin state. These are defined in
+/// [`Plugin::STATE_MIGRATIONS`][crate::prelude::Plugin::STATE_MIGRATIONS]. When loading state, all
+/// steps whose version requirement matches the version the state was saved with are run in order
+/// before the state is applied to the plugin. The helper methods on [`PluginState`] can be used to
+/// rename parameters, rescale or convert their values, and to rewrite `#[persist]` fields.
+///
+/// ```
+/// # use nih_plug::prelude::*;
+/// const STATE_MIGRATIONS: &'static [StateMigration] = &[
+///     // Version 0.2.0 renamed the `gain` parameter to `input_gain`
+///     StateMigration {
+///         versions: "<0.2.0",
+///         migrate: |state| {
+///             state.rename_param("gain", "input_gain");
+///         },
+///     },
+///     // And version 0.3.0 changed its unit from linear gain to decibels
+///     StateMigration {
+///         versions: "<0.3.0",
+///         migrate: |state| {
+///             state.map_param_f32("input_gain", util::gain_to_db);
+///         },
+///     },
+/// ];
+/// ```
+#[derive(Clone, Copy)]
+pub struct StateMigration {
+    /// A semver version requirement matched against the plugin version the state was saved with,
+    /// e.g. `"<0.3.0"` or `">=1.0.0, <1.2.0"`. State saved without a version, which may happen for
+    /// very old state, is treated as version `0.0.0`.
+    pub versions: &'static str,
+    /// The function that migrates the state. This is called just before the state is loaded, and
+    /// before [`Plugin::filter_state()`][crate::prelude::Plugin::filter_state()] is called.
+    pub migrate: fn(&mut PluginState),
+}
+
+impl StateMigration {
+    /// Parse [`versions`][Self::versions]. Plugins can use this in a unit test to make sure all of
+    /// their migrations have valid version requirements, since invalid requirements are otherwise
+    /// only reported as debug assertion failures when loading state.
+    pub fn version_req(&self) -> Result<VersionReq, semver::Error> {
+        VersionReq::parse(self.versions)
+    }
+}
+
+impl ParamValue {
+    /// Get this value as a floating point number. Integer and boolean values are converted to
+    /// floats, and `None` is returned for string values.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            ParamValue::F32(value) => Some(*value),
+            ParamValue::I32(value) => Some(*value as f32),
+            ParamValue::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            ParamValue::String(_) => None,
+        }
+    }
+
+    /// Get this value as an integer. Floating point values are rounded, boolean values are
+    /// converted to `0` or `1`, and `None` is returned for string values.
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            ParamValue::F32(value) => Some(value.round() as i32),
+            ParamValue::I32(value) => Some(*value),
+            ParamValue::Bool(value) => Some(*value as i32),
+            ParamValue::String(_) => None,
+        }
+    }
+
+    /// Get this value as a boolean. Numeric values are considered to be `true` when they're at
+    /// least 0.5, and `None` is returned for string values.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ParamValue::F32(value) => Some(*value >= 0.5),
+            ParamValue::I32(value) => Some(*value != 0),
+            ParamValue::Bool(value) => Some(*value),
+            ParamValue::String(_) => None,
+        }
+    }
+}
+
+impl PluginState {
+    /// Rename a parameter. Useful when a parameter's ID has been changed. Any existing value for
+    /// `new_id` is overwritten. Returns `false` if the state does not contain a value for `old_id`.
+    pub fn rename_param(&mut self, old_id: &str, new_id: &str) -> bool {
+        match self.params.remove(old_id) {
+            Some(value) => {
+                self.params.insert(String::from(new_id), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a parameter from the state, returning its old value if it existed. The parameter will
+    /// keep its current value when the state is loaded.
+    pub fn remove_param(&mut self, id: &str) -> Option<ParamValue> {
+        self.params.remove(id)
+    }
+
+    /// Replace a parameter's value using a function. The function can also be used to convert
+    /// between [`ParamValue`] types, for instance when a `FloatParam` has been changed to an
+    /// `IntParam`. Returns `false` if the state does not contain a value for this parameter.
+    pub fn map_param(&mut self, id: &str, f: impl FnOnce(ParamValue) -> ParamValue) -> bool {
+        match self.params.remove(id) {
+            Some(value) => {
+                self.params.insert(String::from(id), f(value));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rescale a parameter's plain value. Integer and boolean values are first converted to floats
+    /// using [`ParamValue::as_f32()`], and the parameter will be stored as a floating point value
+    /// afterwards. Returns `false` if the state does not contain a numeric value for this
+    /// parameter.
+    pub fn map_param_f32(&mut self, id: &str, f: impl FnOnce(f32) -> f32) -> bool {
+        match self.params.get_mut(id) {
+            Some(value) => match value.as_f32() {
+                Some(old_value) => {
+                    *value = ParamValue::F32(f(old_value));
+                    true
+                }
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Rename a `#[persist]` field. Returns `false` if the state does not contain a value for
+    /// `old_key`.
+    pub fn rename_field(&mut self, old_key: &str, new_key: &str) -> bool {
+        match self.fields.remove(old_key) {
+            Some(value) => {
+                self.fields.insert(String::from(new_key), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rewrite a `#[persist]` field's value. The field is deserialized from JSON as a `T`, passed
+    /// to the function, and the result is then serialized back to JSON. This can also be used to
+    /// change the field's type. Returns `false` if the state does not contain this field or if the
+    /// field could not be deserialized as a `T`.
+    pub fn map_field<T: DeserializeOwned, U: Serialize>(
+        &mut self,
+        key: &str,
+        f: impl FnOnce(T) -> U,
+    ) -> bool {
+        let json = match self.fields.get_mut(key) {
+            Some(json) => json,
+            None => return false,
+        };
+
+        let old_value: T = match serde_json::from_str(json) {
+            Ok(value) => value,
+            Err(err) => {
+                nih_debug_assert_failure!("Could not deserialize field \"{}\": {}", key, err);
+                return false;
+            }
+        };
+        match serde_json::to_string(&f(old_value)) {
+            Ok(new_json) => {
+                *json = new_json;
+                true
+            }
+            Err(err) => {
+                nih_debug_assert_failure!("Could not serialize field \"{}\": {}", key, err);
+                false
+            }
+        }
+    }
+}
+
+/// Run all migrations that apply to the state's version, in order. The state's `version` field is
+/// left unchanged so [`Plugin::filter_state()`][crate::prelude::Plugin::filter_state()] can still
+/// see which version the state was saved with. If the version cannot be parsed then the state is
+/// assumed to be older than every migration, so all of them are run.
+pub(crate) fn apply_migrations(state: &mut PluginState, migrations: &[StateMigration]) {
+    if migrations.is_empty() {
+        return;
+    }
+
+    let saved_version = if state.version.is_empty() {
+        Some(Version::new(0, 0, 0))
+    } else {
+        match Version::parse(&state.version) {
+            Ok(version) => Some(version),
+            Err(err) => {
+                nih_warn!(
+                    "Could not parse the state's version \"{}\", running all migrations: {}",
+                    state.version,
+                    err
+                );
+                None
+            }
+        }
+    };
+
+    for migration in migrations {
+        match migration.version_req() {
+            Ok(version_req) => {
+                let matches = saved_version
+                    .as_ref()
+                    .map_or(true, |version| version_req.matches(version));
+                if matches {
+                    nih_trace!(
+                        "Migrating state from version {} ({})",
+                        state.version,
+                        migration.versions
+                    );
+                    (migration.migrate)(state);
+                }
+            }
+            Err(err) => {
+                nih_debug_assert_failure!(
+                    "Invalid version requirement \"{}\" for state migration: {}",
+                    migration.versions,
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn make_state(version: &str) -> PluginState {
+        PluginState {
+            version: String::from(version),
+            params: BTreeMap::from([
+                (String::from("gain"), ParamValue::F32(0.5)),
+                (String::from("mode"), ParamValue::I32(2)),
+            ]),
+            fields: BTreeMap::from([(String::from("count"), String::from("3"))]),
+        }
+    }
+
+    const MIGRATIONS: &[StateMigration] = &[
+        StateMigration {
+            versions: "<0.2.0",
+            migrate: |state| {
+                state.rename_param("gain", "input_gain");
+            },
+        },
+        StateMigration {
+            versions: "<0.3.0",
+            migrate: |state| {
+                state.map_param_f32("input_gain", |value| value * 2.0);
+                state.map_param("mode", |value| ParamValue::Bool(value.as_bool().unwrap()));
+                state.map_field("count", |count: u32| count.to_string());
+            },
+        },
+    ];
+
+    #[test]
+    fn test_all_migrations() {
+        let mut state = make_state("0.1.0");
+        apply_migrations(&mut state, MIGRATIONS);
+
+        assert!(!state.params.contains_key("gain"));
+        assert!(matches!(state.params["input_gain"], ParamValue::F32(value) if value == 1.0));
+        assert!(matches!(state.params["mode"], ParamValue::Bool(true)));
+        assert_eq!(state.fields["count"], "\"3\"");
+        assert_eq!(state.version, "0.1.0");
+    }
+
+    #[test]
+    fn test_partial_migrations() {
+        let mut state = make_state("0.2.5");
+        state.rename_param("gain", "input_gain");
+        apply_migrations(&mut state, MIGRATIONS);
+
+        assert!(matches!(state.params["input_gain"], ParamValue::F32(value) if value == 1.0));
+    }
+
+    #[test]
+    fn test_no_migrations() {
+        let mut state = make_state("0.3.0");
+        apply_migrations(&mut state, MIGRATIONS);
+
+        assert!(matches!(state.params["gain"], ParamValue::F32(value) if value == 0.5));
+        assert!(matches!(state.params["mode"], ParamValue::I32(2)));
+        assert_eq!(state.fields["count"], "3");
+    }
+
+    #[test]
+    fn test_missing_version() {
+        let mut state = make_state("");
+        apply_migrations(&mut state, MIGRATIONS);
+
+        assert!(state.params.contains_key("input_gain"));
+    }
+
+    #[test]
+    fn test_invalid_version() {
+        let mut state = make_state("1.0-beta");
+        apply_migrations(&mut state, MIGRATIONS);
+
+        assert!(matches!(state.params["input_gain"], ParamValue::F32(value) if value == 1.0));
+        assert_eq!(state.version, "1.0-beta");
+    }
+
+    #[test]
+    fn test_version_reqs() {
+        for migration in MIGRATIONS {
+            assert!(migration.version_req().is_ok(), "{}", migration.versions);
+        }
+
+        let invalid = StateMigration {
+            versions: "< 0.2.0 or so",
+            migrate: |_| (),
+        };
+        assert!(invalid.version_req().is_err());
+    }
+}
@@ -0,0 +1,512 @@
+//! Loading state without doing any realtime-unsafe work on the audio thread. Incoming state is
+//! migrated, filtered, and resolved to concrete parameter values on the thread that loads the state.
+//! If the plugin prepared resources for the state through [`Plugin::prepare_state()`], then the
+//! result is handed to the audio thread through a [`StateHandoff`], and the audio thread only needs
+//! to write those values to the parameters and let the plugin swap in the prepared resources.
+//! Otherwise the state is applied on the loading thread while holding the lock on the plugin, which
+//! means the audio thread never needs to reinitialize the plugin.
+
+use crossbeam::queue::ArrayQueue;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{migration, ParamValue, PluginState};
+use crate::params::ParamMut;
+use crate::prelude::{BufferConfig, ParamPtr, Params, Plugin};
+use crate::wrapper::util::process_wrapper;
+
+/// The amount of time to sleep between checks while waiting for the audio thread to apply the
+/// prepared state.
+const HANDOFF_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// The number of buffers the loading thread waits for the audio thread to pick up the prepared
+/// state before applying it itself. Hosts may stop calling the process function without telling the
+/// plugin they stopped processing audio.
+const HANDOFF_TIMEOUT_BUFFERS: f32 = 4.0;
+/// The minimum amount of time to wait for the audio thread, so tiny buffer sizes and scheduling
+/// jitter don't cause the state to be applied on the loading thread.
+const MIN_HANDOFF_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// A [`PluginState`] that has been resolved to concrete values for the plugin's parameters. Applying
+/// this does not allocate or deallocate, so it can safely be done on the audio thread.
+pub(crate) struct PreparedState {
+    /// The parameters and the unnormalized values they should be set to. Enum parameters with
+    /// stable string IDs have already been resolved to their variant indices.
+    param_values: Vec<(ParamPtr, PreparedParamValue)>,
+    /// The plugin's persistent fields. These are restored on the loading thread after the
+    /// parameters have been set, since deserializing them allocates.
+    fields: BTreeMap<String, String>,
+    /// The data returned from [`Plugin::prepare_state()`], if the plugin prepared anything.
+    plugin_data: Option<Box<dyn Any + Send>>,
+    /// Set when the plugin's [`Plugin::apply_prepared_state()`] returned `false` on the audio
+    /// thread. The plugin then needs to be reinitialized on the loading thread instead.
+    plugin_data_rejected: bool,
+}
+
+/// A parameter value from a [`ParamValue`] that has been checked against the parameter's type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PreparedParamValue {
+    F32(f32),
+    I32(i32),
+    Bool(bool),
+}
+
+/// Hands [`PreparedState`]s from the thread loading state to the audio thread. The audio thread
+/// picks up the state at a block boundary using [`apply_pending()`][Self::apply_pending()], and it
+/// then sends the state back so it can be deallocated on the loading thread. Neither side of the
+/// exchange allocates or blocks on the audio thread.
+pub(crate) struct StateHandoff {
+    /// State waiting to be applied by the audio thread. This can only ever contain a single item.
+    pending: ArrayQueue<Box<PreparedState>>,
+    /// State that has been applied by the audio thread, along with whether the plugin swapped in
+    /// the prepared data.
+    applied: ArrayQueue<(Box<PreparedState>, bool)>,
+    /// Makes sure only one thread can load state at a time. Hosts are not supposed to load state
+    /// from multiple threads at once, but the plugin's editor may load state at the same time as the
+    /// host.
+    loading_lock: Mutex<()>,
+}
+
+/// The result of [`StateHandoff::send_and_wait()`].
+pub(crate) enum HandoffResult {
+    /// The audio thread set the parameters and the plugin swapped in the prepared data. The
+    /// persistent fields still need to be restored using
+    /// [`apply_fields()`][PreparedState::apply_fields()].
+    Applied(Box<PreparedState>),
+    /// The state has not been applied, or the plugin rejected the prepared data and it needs to be
+    /// reinitialized. The state should be applied on the calling thread using
+    /// [`apply_with_plugin_lock()`][PreparedState::apply_with_plugin_lock()].
+    NotApplied(Box<PreparedState>),
+}
+
+impl PreparedState {
+    /// Run the plugin's [`STATE_MIGRATIONS`][Plugin::STATE_MIGRATIONS] and
+    /// [`Plugin::filter_state()`] on the state, and resolve the parameter values stored in it.
+    ///
+    /// This allocates and thus should not be called from the audio thread. See
+    /// [`make_params_getter()`][super::make_params_getter()] for the `params_getter` argument.
+    pub unsafe fn prepare<P: Plugin>(
+        state: &mut PluginState,
+        params_getter: impl Fn(&str) -> Option<ParamPtr>,
+    ) -> Self {
+        // This lets the plugin perform migrations on old state if needed. The declarative
+        // migrations run first so `filter_state()` always sees the migrated state.
+        migration::apply_migrations(state, P::STATE_MIGRATIONS);
+        P::filter_state(state);
+
+        let mut param_values = Vec::with_capacity(state.params.len());
+        for (param_id_str, param_value) in &state.params {
+            let param_ptr = match params_getter(param_id_str.as_str()) {
+                Some(ptr) => ptr,
+                None => {
+                    nih_debug_assert_failure!("Unknown parameter: {}", param_id_str);
+                    continue;
+                }
+            };
+
+            let prepared_value = match (param_ptr, param_value) {
+                (ParamPtr::FloatParam(_), ParamValue::F32(v)) => PreparedParamValue::F32(*v),
+                (ParamPtr::IntParam(_), ParamValue::I32(v)) => PreparedParamValue::I32(*v),
+                (ParamPtr::BoolParam(_), ParamValue::Bool(v)) => PreparedParamValue::Bool(*v),
+                // Enums are either serialized based on the active variant's index (which may not be
+                // the same as the discriminator), or a custom set stable string ID. The latter
+                // allows the variants to be reordered.
+                (ParamPtr::EnumParam(_), ParamValue::I32(variant_idx)) => {
+                    PreparedParamValue::I32(*variant_idx)
+                }
+                (ParamPtr::EnumParam(p), ParamValue::String(id)) => match (*p).index_from_id(id) {
+                    Some(variant_idx) => PreparedParamValue::I32(variant_idx),
+                    None => {
+                        nih_debug_assert_failure!(
+                            "Unknown ID {:?} for enum parameter \"{}\"",
+                            id,
+                            param_id_str,
+                        );
+                        continue;
+                    }
+                },
+                (param_ptr, param_value) => {
+                    nih_debug_assert_failure!(
+                        "Invalid serialized value {:?} for parameter \"{}\" ({:?})",
+                        param_value,
+                        param_id_str,
+                        param_ptr,
+                    );
+                    continue;
+                }
+            };
+
+            param_values.push((param_ptr, prepared_value));
+        }
+
+        Self {
+            param_values,
+            fields: state.fields.clone(),
+            plugin_data: None,
+            plugin_data_rejected: false,
+        }
+    }
+
+    /// Store the data returned from [`Plugin::prepare_state()`] so it can be passed to
+    /// [`Plugin::apply_prepared_state()`] later.
+    pub fn set_plugin_data(&mut self, plugin_data: Option<Box<dyn Any + Send>>) {
+        self.plugin_data = plugin_data;
+    }
+
+    /// Whether [`Plugin::prepare_state()`] prepared anything. Only then can the state be swapped in
+    /// on the audio thread.
+    pub fn has_plugin_data(&self) -> bool {
+        self.plugin_data.is_some()
+    }
+
+    /// Restore the plugin's persistent fields. This allocates, so it must not be called from the
+    /// audio thread.
+    pub fn apply_fields(&self, plugin_params: &dyn Params) {
+        // The plugin can also persist arbitrary fields alongside its parameters. This is useful for
+        // storing things like sample data.
+        plugin_params.deserialize_fields(&self.fields);
+    }
+
+    /// Apply the state on the audio thread. This sets the parameters and lets the plugin swap in
+    /// the prepared data. Returns `false` if the plugin rejected the prepared data, in which case
+    /// it needs to be reinitialized on the loading thread. This is realtime-safe as long as the
+    /// plugin's [`Plugin::apply_prepared_state()`] is.
+    pub unsafe fn apply_on_audio_thread<P: Plugin>(
+        &mut self,
+        plugin: &mut P,
+        sample_rate: f32,
+    ) -> bool {
+        self.apply_params(Some(sample_rate));
+
+        if plugin.apply_prepared_state(self.plugin_data.as_deref_mut()) {
+            process_wrapper(|| plugin.reset());
+            true
+        } else {
+            self.plugin_data_rejected = true;
+            false
+        }
+    }
+
+    /// Apply the state on the thread that loaded it. `plugin` should be the locked plugin instance,
+    /// so the audio thread cannot process audio until the state has been applied. `sample_rate` is
+    /// `None` if the plugin has not yet been initialized, in which case only the parameters and the
+    /// persistent fields are restored. Otherwise the plugin gets to swap in the prepared data, and
+    /// if it doesn't then `reinitialize` is called to reinitialize the plugin. If that fails, then
+    /// the persistent fields are restored to their previous values. Returns whether the state was
+    /// applied successfully.
+    ///
+    /// This allocates, so it must not be called from the audio thread.
+    pub unsafe fn apply_with_plugin_lock<P: Plugin>(
+        &mut self,
+        plugin: &mut P,
+        plugin_params: &dyn Params,
+        sample_rate: Option<f32>,
+        reinitialize: impl FnOnce(&mut P) -> bool,
+    ) -> bool {
+        self.apply_params(sample_rate);
+
+        let previous_fields = plugin_params.serialize_fields();
+        self.apply_fields(plugin_params);
+        if sample_rate.is_none() {
+            return true;
+        }
+
+        let swapped_in = !self.plugin_data_rejected
+            && plugin.apply_prepared_state(self.plugin_data.as_deref_mut());
+        let success = swapped_in || reinitialize(plugin);
+        if success {
+            process_wrapper(|| plugin.reset());
+        } else {
+            plugin_params.deserialize_fields(&previous_fields);
+        }
+
+        success
+    }
+
+    /// Set the parameters to the prepared values. If the sample rate is known, then the smoothers
+    /// are also reset to the new values so everything starts out in sync. This is realtime-safe.
+    pub unsafe fn apply_params(&self, sample_rate: Option<f32>) {
+        for (param_ptr, value) in &self.param_values {
+            match (*param_ptr, *value) {
+                (ParamPtr::FloatParam(p), PreparedParamValue::F32(v)) => {
+                    (*p).set_plain_value(v);
+                }
+                (ParamPtr::IntParam(p), PreparedParamValue::I32(v)) => {
+                    (*p).set_plain_value(v);
+                }
+                (ParamPtr::BoolParam(p), PreparedParamValue::Bool(v)) => {
+                    (*p).set_plain_value(v);
+                }
+                (ParamPtr::EnumParam(p), PreparedParamValue::I32(v)) => {
+                    (*p).set_plain_value(v);
+                }
+                // `prepare()` only ever creates matching pairs
+                _ => unreachable!(),
+            }
+
+            if let Some(sample_rate) = sample_rate {
+                param_ptr.update_smoother(sample_rate, true);
+            }
+        }
+    }
+}
+
+impl Default for StateHandoff {
+    fn default() -> Self {
+        Self {
+            pending: ArrayQueue::new(1),
+            applied: ArrayQueue::new(1),
+            loading_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl StateHandoff {
+    /// Send the prepared state to the audio thread and block until it has been applied. The audio
+    /// thread only applies state when the plugin prepared data using [`Plugin::prepare_state()`].
+    /// If the plugin did not prepare anything, if the audio thread is not processing audio, or if
+    /// the audio thread has not picked up the state within `timeout`, then the state is returned
+    /// as [`HandoffResult::NotApplied`] so it can be applied on the calling thread instead. The
+    /// state is always deallocated on the calling thread.
+    ///
+    /// This must not be called from the audio thread.
+    pub fn send_and_wait(
+        &self,
+        prepared: Box<PreparedState>,
+        is_processing: &AtomicBool,
+        timeout: Duration,
+    ) -> HandoffResult {
+        let _loading_guard = self.loading_lock.lock();
+        if !prepared.has_plugin_data() || !is_processing.load(Ordering::SeqCst) {
+            return HandoffResult::NotApplied(prepared);
+        }
+
+        // Because of the lock above, there can't already be any state in the queue
+        if let Err(prepared) = self.pending.push(prepared) {
+            return HandoffResult::NotApplied(prepared);
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some((prepared, swapped_in)) = self.applied.pop() {
+                return if swapped_in {
+                    HandoffResult::Applied(prepared)
+                } else {
+                    HandoffResult::NotApplied(prepared)
+                };
+            }
+
+            // If the host stopped processing audio before the audio thread could pick up the state,
+            // or if the host stopped calling the process function without telling us, then we need
+            // to take it back. If this fails, then the audio thread is applying the state right
+            // now and we'll need to wait for it to finish.
+            if !is_processing.load(Ordering::SeqCst) || Instant::now() >= deadline {
+                if let Some(prepared) = self.pending.pop() {
+                    return HandoffResult::NotApplied(prepared);
+                }
+            }
+
+            thread::sleep(HANDOFF_POLL_INTERVAL);
+        }
+    }
+
+    /// Apply pending state, if there is any, using the supplied function. The function returns
+    /// whether the plugin swapped in the prepared state. This is called by the audio thread at the
+    /// end of a process call and it does not allocate or block.
+    pub fn apply_pending(&self, apply: impl FnOnce(&mut PreparedState) -> bool) {
+        if let Some(mut prepared) = self.pending.pop() {
+            let swapped_in = apply(&mut prepared);
+
+            // Only a single state object can be in flight at a time, so this can't fail
+            let push_successful = self.applied.push((prepared, swapped_in)).is_ok();
+            nih_debug_assert!(push_successful, "The applied state queue is full");
+        }
+    }
+}
+
+/// How long [`StateHandoff::send_and_wait()`] should wait for the audio thread to pick up the state
+/// for a buffer configuration.
+pub(crate) fn handoff_timeout(buffer_config: &BufferConfig) -> Duration {
+    let buffer_duration = buffer_config.max_buffer_size as f32 / buffer_config.sample_rate;
+
+    // This would fail for invalid sample rates
+    Duration::try_from_secs_f32(buffer_duration * HANDOFF_TIMEOUT_BUFFERS)
+        .map_or(MIN_HANDOFF_TIMEOUT, |timeout| {
+            timeout.max(MIN_HANDOFF_TIMEOUT)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::prelude::FloatParam;
+    use crate::prelude::FloatRange;
+
+    /// Prepared state for a single parameter. If `with_plugin_data` is set, then this pretends
+    /// [`Plugin::prepare_state()`] returned something so the state is handed to the audio thread.
+    fn prepared_state(param: &FloatParam, value: f32, with_plugin_data: bool) -> PreparedState {
+        PreparedState {
+            param_values: vec![(
+                ParamPtr::FloatParam(param as *const _),
+                PreparedParamValue::F32(value),
+            )],
+            fields: BTreeMap::new(),
+            plugin_data: if with_plugin_data {
+                Some(Box::new(()))
+            } else {
+                None
+            },
+            plugin_data_rejected: false,
+        }
+    }
+
+    #[test]
+    fn apply_params() {
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let prepared = prepared_state(&param, 0.5, false);
+        assert_eq!(param.value(), 0.0);
+
+        unsafe { prepared.apply_params(Some(44_100.0)) };
+        assert_eq!(param.value(), 0.5);
+        assert_eq!(param.smoothed.next(), 0.5);
+    }
+
+    #[test]
+    fn handoff_applies_on_audio_thread() {
+        let handoff = Arc::new(StateHandoff::default());
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let audio_thread = {
+            let handoff = handoff.clone();
+            let is_processing = is_processing.clone();
+            thread::spawn(move || {
+                let mut num_applied = 0;
+                while num_applied == 0 {
+                    handoff.apply_pending(|prepared| {
+                        assert_eq!(prepared.param_values.len(), 1);
+                        num_applied += 1;
+                        true
+                    });
+                    thread::sleep(Duration::from_micros(100));
+                }
+                is_processing.store(false, Ordering::SeqCst);
+            })
+        };
+
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let result = handoff.send_and_wait(
+            Box::new(prepared_state(&param, 0.5, true)),
+            &is_processing,
+            Duration::from_secs(10),
+        );
+        assert!(matches!(result, HandoffResult::Applied(_)));
+
+        audio_thread.join().unwrap();
+    }
+
+    #[test]
+    fn handoff_returns_rejected_state() {
+        let handoff = Arc::new(StateHandoff::default());
+        let is_processing = Arc::new(AtomicBool::new(true));
+
+        let audio_thread = {
+            let handoff = handoff.clone();
+            thread::spawn(move || {
+                let mut num_applied = 0;
+                while num_applied == 0 {
+                    handoff.apply_pending(|_| {
+                        num_applied += 1;
+                        false
+                    });
+                    thread::sleep(Duration::from_micros(100));
+                }
+            })
+        };
+
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let result = handoff.send_and_wait(
+            Box::new(prepared_state(&param, 0.5, true)),
+            &is_processing,
+            Duration::from_secs(10),
+        );
+        assert!(matches!(result, HandoffResult::NotApplied(_)));
+
+        audio_thread.join().unwrap();
+    }
+
+    #[test]
+    fn handoff_returns_state_when_not_processing() {
+        let handoff = StateHandoff::default();
+        let is_processing = AtomicBool::new(false);
+
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let result = handoff.send_and_wait(
+            Box::new(prepared_state(&param, 0.5, true)),
+            &is_processing,
+            Duration::from_secs(10),
+        );
+        assert!(matches!(result, HandoffResult::NotApplied(_)));
+        assert!(handoff.pending.is_empty());
+    }
+
+    #[test]
+    fn handoff_skipped_without_plugin_data() {
+        let handoff = StateHandoff::default();
+        let is_processing = AtomicBool::new(true);
+
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let result = handoff.send_and_wait(
+            Box::new(prepared_state(&param, 0.5, false)),
+            &is_processing,
+            Duration::from_secs(10),
+        );
+        assert!(matches!(result, HandoffResult::NotApplied(_)));
+        assert!(handoff.pending.is_empty());
+    }
+
+    #[test]
+    fn handoff_times_out() {
+        // This simulates a host that claims to be processing audio but that doesn't call the
+        // process function
+        let handoff = StateHandoff::default();
+        let is_processing = AtomicBool::new(true);
+
+        let param = FloatParam::new("Gain", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 });
+        let result = handoff.send_and_wait(
+            Box::new(prepared_state(&param, 0.5, true)),
+            &is_processing,
+            Duration::from_millis(5),
+        );
+        assert!(matches!(result, HandoffResult::NotApplied(_)));
+        assert!(handoff.pending.is_empty());
+    }
+
+    #[test]
+    fn handoff_timeout_duration() {
+        let buffer_config = |sample_rate, max_buffer_size| BufferConfig {
+            sample_rate,
+            min_buffer_size: None,
+            max_buffer_size,
+            process_mode: crate::prelude::ProcessMode::Realtime,
+        };
+
+        assert_eq!(
+            handoff_timeout(&buffer_config(48_000.0, 48_000)),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            handoff_timeout(&buffer_config(48_000.0, 64)),
+            MIN_HANDOFF_TIMEOUT
+        );
+        assert_eq!(
+            handoff_timeout(&buffer_config(0.0, 64)),
+            MIN_HANDOFF_TIMEOUT
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! Negotiating audio IO layouts that aren't part of `Plugin::AUDIO_IO_LAYOUTS` with the host.
+
+use parking_lot::Mutex;
+use std::num::NonZeroU32;
+
+use crate::prelude::{AudioIOLayout, ChannelLayouts, Plugin};
+
+/// Auxiliary port channel counts that were proposed by the host. `AudioIOLayout` only stores
+/// `'static` slices, so these are leaked and reused for every layout with the same channel counts.
+/// This set only grows when the host proposes a combination of channel counts it hasn't proposed
+/// before, so in practice it stays tiny.
+static INTERNED_PORT_CHANNELS: Mutex<Vec<&'static [NonZeroU32]>> = Mutex::new(Vec::new());
+
+/// The channel counts a host proposes for every port of an audio IO layout. The number of ports
+/// always matches the layout the proposal is based on, since neither CLAP nor VST3 allows the host
+/// to add or remove ports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProposedChannelCounts {
+    pub main_input: Option<NonZeroU32>,
+    pub main_output: Option<NonZeroU32>,
+    pub aux_inputs: Vec<NonZeroU32>,
+    pub aux_outputs: Vec<NonZeroU32>,
+}
+
+impl ProposedChannelCounts {
+    /// The channel counts of an existing audio IO layout. These can then be modified to match the
+    /// host's proposal.
+    pub fn for_audio_io_layout(audio_io_layout: &AudioIOLayout) -> Self {
+        Self {
+            main_input: audio_io_layout.main_input_channels,
+            main_output: audio_io_layout.main_output_channels,
+            aux_inputs: audio_io_layout.aux_input_ports.to_vec(),
+            aux_outputs: audio_io_layout.aux_output_ports.to_vec(),
+        }
+    }
+
+    /// Whether the audio IO layout has exactly these channel counts.
+    pub fn matches(&self, audio_io_layout: &AudioIOLayout) -> bool {
+        self.main_input == audio_io_layout.main_input_channels
+            && self.main_output == audio_io_layout.main_output_channels
+            && self.aux_inputs == audio_io_layout.aux_input_ports
+            && self.aux_outputs == audio_io_layout.aux_output_ports
+    }
+
+    /// Create an audio IO layout with these channel counts based on `base_layout`, and ask the
+    /// plugin whether it accepts that layout through
+    /// [`Plugin::accept_audio_io_layout()`]. The port names from the base layout are kept, but
+    /// explicit channel layouts are dropped when they no longer match a port's channel count.
+    /// Returns `None` if the plugin rejects the layout.
+    pub fn accept_runtime_layout<P: Plugin>(
+        &self,
+        plugin: &P,
+        base_layout: &AudioIOLayout,
+    ) -> Option<AudioIOLayout> {
+        let main_input_layout = base_layout
+            .channel_layouts
+            .main_input
+            .filter(|layout| Some(layout.num_channels()) == self.main_input.map(NonZeroU32::get));
+        let main_output_layout = base_layout
+            .channel_layouts
+            .main_output
+            .filter(|layout| Some(layout.num_channels()) == self.main_output.map(NonZeroU32::get));
+        let aux_input_layouts = if base_layout
+            .channel_layouts
+            .aux_inputs
+            .iter()
+            .zip(&self.aux_inputs)
+            .all(|(layout, num_channels)| layout.num_channels() == num_channels.get())
+        {
+            base_layout.channel_layouts.aux_inputs
+        } else {
+            &[]
+        };
+        let aux_output_layouts = if base_layout
+            .channel_layouts
+            .aux_outputs
+            .iter()
+            .zip(&self.aux_outputs)
+            .all(|(layout, num_channels)| layout.num_channels() == num_channels.get())
+        {
+            base_layout.channel_layouts.aux_outputs
+        } else {
+            &[]
+        };
+
+        let mut names = base_layout.names;
+        // The base layout's name will likely no longer describe the new channel counts, so
+        // `AudioIOLayout::name()` will generate a new one instead
+        names.layout = None;
+
+        let audio_io_layout = AudioIOLayout {
+            main_input_channels: self.main_input,
+            main_output_channels: self.main_output,
+            aux_input_ports: intern_port_channels(&self.aux_inputs),
+            aux_output_ports: intern_port_channels(&self.aux_outputs),
+            names,
+            channel_layouts: ChannelLayouts {
+                main_input: main_input_layout,
+                main_output: main_output_layout,
+                aux_inputs: aux_input_layouts,
+                aux_outputs: aux_output_layouts,
+            },
+        };
+        nih_debug_assert!(audio_io_layout.validate().is_ok());
+
+        if plugin.accept_audio_io_layout(&audio_io_layout) {
+            Some(audio_io_layout)
+        } else {
+            None
+        }
+    }
+}
+
+/// Get a `'static` slice containing the same channel counts as `port_channels`. See
+/// [`INTERNED_PORT_CHANNELS`].
+fn intern_port_channels(port_channels: &[NonZeroU32]) -> &'static [NonZeroU32] {
+    if port_channels.is_empty() {
+        return &[];
+    }
+
+    let mut interned_port_channels = INTERNED_PORT_CHANNELS.lock();
+    match interned_port_channels
+        .iter()
+        .find(|interned| **interned == port_channels)
+    {
+        Some(interned) => interned,
+        None => {
+            let interned: &'static [NonZeroU32] = Box::leak(port_channels.into());
+            interned_port_channels.push(interned);
+
+            interned
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use std::sync::Arc;
+
+    /// Accepts any layout with the same number of input and output channels.
+    #[derive(Default)]
+    struct ChannelAgnosticPlugin {
+        params: Arc<EmptyParams>,
+    }
+
+    #[derive(Default)]
+    struct EmptyParams {}
+
+    unsafe impl Params for EmptyParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            Vec::new()
+        }
+    }
+
+    impl Plugin for ChannelAgnosticPlugin {
+        const NAME: &'static str = "Channel Agnostic Plugin";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            aux_input_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                layout: Some("Stereo"),
+                ..PortNames::const_default()
+            },
+            channel_layouts: ChannelLayouts {
+                main_input: Some(ChannelLayout::Stereo),
+                main_output: Some(ChannelLayout::Stereo),
+                aux_inputs: &[ChannelLayout::Stereo],
+                aux_outputs: &[],
+            },
+            ..AudioIOLayout::const_default()
+        }];
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            self.params.clone()
+        }
+
+        fn accept_audio_io_layout(&self, audio_io_layout: &AudioIOLayout) -> bool {
+            audio_io_layout.main_input_channels == audio_io_layout.main_output_channels
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+    }
+
+    #[test]
+    fn matching_channel_counts() {
+        let base_layout = ChannelAgnosticPlugin::AUDIO_IO_LAYOUTS[0];
+        let proposed = ProposedChannelCounts::for_audio_io_layout(&base_layout);
+        assert!(proposed.matches(&base_layout));
+
+        let mut proposed = proposed;
+        proposed.aux_inputs[0] = new_nonzero_u32(1);
+        assert!(!proposed.matches(&base_layout));
+    }
+
+    #[test]
+    fn runtime_layout() {
+        let plugin = ChannelAgnosticPlugin::default();
+        let base_layout = ChannelAgnosticPlugin::AUDIO_IO_LAYOUTS[0];
+
+        let mut proposed = ProposedChannelCounts::for_audio_io_layout(&base_layout);
+        proposed.main_input = NonZeroU32::new(6);
+        assert_eq!(proposed.accept_runtime_layout(&plugin, &base_layout), None);
+
+        proposed.main_output = NonZeroU32::new(6);
+        proposed.aux_inputs[0] = new_nonzero_u32(1);
+        let audio_io_layout = proposed
+            .accept_runtime_layout(&plugin, &base_layout)
+            .unwrap();
+        assert!(proposed.matches(&audio_io_layout));
+        assert_eq!(audio_io_layout.names.layout, None);
+        // The stereo channel layouts no longer apply, so the ports now use their default layouts
+        assert_eq!(
+            audio_io_layout.channel_layouts,
+            ChannelLayouts::const_default()
+        );
+        assert_eq!(
+            audio_io_layout.aux_input_channel_layout(0),
+            Some(ChannelLayout::Mono)
+        );
+
+        // Proposing the same channel counts again should reuse the interned slices
+        let audio_io_layout_again = proposed
+            .accept_runtime_layout(&plugin, &base_layout)
+            .unwrap();
+        assert!(std::ptr::eq(
+            audio_io_layout.aux_input_ports,
+            audio_io_layout_again.aux_input_ports
+        ));
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::prelude::{AudioIOLayout, Buffer};
 
@@ -29,6 +30,8 @@ pub struct BufferManager {
     main_output_channel_pointers: Option<ChannelPointers>,
     aux_input_channel_pointers: Vec<Option<ChannelPointers>>,
     aux_output_channel_pointers: Vec<Option<ChannelPointers>>,
+    aux_input_active: Vec<bool>,
+    aux_output_active: Vec<bool>,
 
     /// The backing buffers that will be filled during `create_buffers`. This `'static` lifetime
     /// will be shortened when returning a reference to these buffers in `create_buffers` to match
@@ -42,6 +45,10 @@ pub struct BufferManager {
     aux_input_storage: Vec<Vec<Vec<f32>>>,
 
     aux_output_buffers: Vec<Buffer<'static>>,
+    /// Scratch storage for inactive auxiliary outputs. The host may not provide any buffers for
+    /// these, but the plugin should still be able to write to them without having to check whether
+    /// the port is active.
+    aux_output_storage: Vec<Vec<Vec<f32>>>,
 }
 
 // SAFETY: The raw pointers in the `ChannelPointers` fields/vectors are only used as scratch storage
@@ -57,6 +64,25 @@ pub struct BufferSource<'a> {
     pub main_output_channel_pointers: &'a mut Option<ChannelPointers>,
     pub aux_input_channel_pointers: &'a mut [Option<ChannelPointers>],
     pub aux_output_channel_pointers: &'a mut [Option<ChannelPointers>],
+    /// Whether the host has activated the auxiliary input ports. These default to `true`. Inactive
+    /// inputs are filled with zeroes, even if channel pointers have been provided for them.
+    pub aux_input_active: &'a mut [bool],
+    /// Whether the host has activated the auxiliary output ports. These default to `true`. Inactive
+    /// outputs are backed by scratch buffers, and any channel pointers provided for them are
+    /// ignored.
+    pub aux_output_active: &'a mut [bool],
+}
+
+/// The host's activation state for a plugin's auxiliary ports. Wrappers store this separately from
+/// the [`BufferManager`] since the host may change the activation state while the plugin is
+/// deactivated, and the buffer manager is recreated when the plugin gets activated again. Hosts
+/// may also change the activation state while the audio thread is processing audio, so the flags
+/// are stored as atomics. The number of ports can only change when the audio IO layout changes,
+/// and that only happens while the plugin is not processing audio.
+#[derive(Debug)]
+pub struct AuxPortActivation {
+    inputs: Vec<AtomicBool>,
+    outputs: Vec<AtomicBool>,
 }
 
 /// Pointers to raw multichannel audio data for this port.
@@ -70,6 +96,75 @@ pub struct ChannelPointers {
     pub num_channels: usize,
 }
 
+impl AuxPortActivation {
+    /// Create the activation state for an audio IO layout. All ports start out as active.
+    pub fn for_audio_io_layout(audio_io_layout: &AudioIOLayout) -> Self {
+        Self {
+            inputs: (0..audio_io_layout.aux_input_ports.len())
+                .map(|_| AtomicBool::new(true))
+                .collect(),
+            outputs: (0..audio_io_layout.aux_output_ports.len())
+                .map(|_| AtomicBool::new(true))
+                .collect(),
+        }
+    }
+
+    /// Whether this activation state has the same number of ports as the audio IO layout. If it
+    /// doesn't, then the wrapper should recreate it using
+    /// [`for_audio_io_layout()`][Self::for_audio_io_layout()]. Only the VST3 wrapper needs this.
+    #[cfg_attr(not(feature = "vst3"), allow(unused))]
+    pub fn matches_audio_io_layout(&self, audio_io_layout: &AudioIOLayout) -> bool {
+        self.inputs.len() == audio_io_layout.aux_input_ports.len()
+            && self.outputs.len() == audio_io_layout.aux_output_ports.len()
+    }
+
+    /// Set the activation state for an auxiliary input port. Returns `false` if the index is out of
+    /// bounds. This can safely be called while the audio thread is processing audio.
+    pub fn set_input_active(&self, aux_port_idx: usize, is_active: bool) -> bool {
+        match self.inputs.get(aux_port_idx) {
+            Some(active) => {
+                active.store(is_active, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the activation state for an auxiliary output port. Returns `false` if the index is out
+    /// of bounds. This can safely be called while the audio thread is processing audio.
+    pub fn set_output_active(&self, aux_port_idx: usize, is_active: bool) -> bool {
+        match self.outputs.get(aux_port_idx) {
+            Some(active) => {
+                active.store(is_active, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy this activation state to a [`BufferSource`] in
+    /// [`BufferManager::create_buffers()`]'s callback.
+    pub fn apply(&self, buffer_source: &mut BufferSource) {
+        nih_debug_assert_eq!(self.inputs.len(), buffer_source.aux_input_active.len());
+        nih_debug_assert_eq!(self.outputs.len(), buffer_source.aux_output_active.len());
+
+        for (source_active, is_active) in buffer_source
+            .aux_input_active
+            .iter_mut()
+            .zip(self.inputs.iter())
+        {
+            *source_active = is_active.load(Ordering::Relaxed);
+        }
+        for (source_active, is_active) in buffer_source
+            .aux_output_active
+            .iter_mut()
+            .zip(self.outputs.iter())
+        {
+            *source_active = is_active.load(Ordering::Relaxed);
+        }
+    }
+}
+
 impl BufferManager {
     /// Initialize managed buffers for a specific audio IO layout. The actual buffers can be set up
     /// using channel pointer data using [`create_buffers()`][Self::create_buffers()].
@@ -120,6 +215,7 @@ impl BufferManager {
         }
 
         let mut aux_output_buffers = Vec::with_capacity(audio_io_layout.aux_output_ports.len());
+        let mut aux_output_storage = Vec::with_capacity(audio_io_layout.aux_output_ports.len());
         for num_channels in audio_io_layout.aux_output_ports {
             let mut buffer = Buffer::default();
             unsafe {
@@ -129,6 +225,10 @@ impl BufferManager {
             };
 
             aux_output_buffers.push(buffer);
+            aux_output_storage.push(vec![
+                vec![0.0; max_buffer_size];
+                num_channels.get() as usize
+            ]);
         }
 
         Self {
@@ -136,6 +236,8 @@ impl BufferManager {
             main_output_channel_pointers: None,
             aux_input_channel_pointers: vec![None; audio_io_layout.aux_input_ports.len()],
             aux_output_channel_pointers: vec![None; audio_io_layout.aux_output_ports.len()],
+            aux_input_active: vec![true; audio_io_layout.aux_input_ports.len()],
+            aux_output_active: vec![true; audio_io_layout.aux_output_ports.len()],
 
             main_buffer,
 
@@ -143,6 +245,7 @@ impl BufferManager {
             aux_input_storage,
 
             aux_output_buffers,
+            aux_output_storage,
         }
     }
 
@@ -154,7 +257,9 @@ impl BufferManager {
     ///
     /// `sample_offset` and `num_samples` can be used to slice a set of host channel pointers for
     /// sample accurate automation. If any of the outputs are missing because the host hasn't
-    /// provided enough channels or outputs, then they will be replaced by empty slices.
+    /// provided enough channels or outputs, then they will be replaced by empty slices. Auxiliary
+    /// ports the host has deactivated are backed by zeroed buffers instead, and the created
+    /// [`Buffer`]s are marked as inactive.
     ///
     /// # Panics
     ///
@@ -175,11 +280,15 @@ impl BufferManager {
         self.main_output_channel_pointers = None;
         self.aux_input_channel_pointers.fill(None);
         self.aux_output_channel_pointers.fill(None);
+        self.aux_input_active.fill(true);
+        self.aux_output_active.fill(true);
         set_buffer_sources(&mut BufferSource {
             main_input_channel_pointers: &mut self.main_input_channel_pointers,
             main_output_channel_pointers: &mut self.main_output_channel_pointers,
             aux_input_channel_pointers: &mut self.aux_input_channel_pointers,
             aux_output_channel_pointers: &mut self.aux_output_channel_pointers,
+            aux_input_active: &mut self.aux_input_active,
+            aux_output_active: &mut self.aux_output_active,
         });
 
         // The main buffer points directly to the main output pointers
@@ -257,18 +366,22 @@ impl BufferManager {
         // Because NIH-plug's `Buffer` type is geared around in-place processing, auxiliary inputs
         // need to be copied to our own buffers first (backed by the 'storage' vectors on this
         // object). That way the plugin can modify those buffers like any other buffers.
-        for (input_channel_pointers, (input_storage, input_buffer)) in
-            self.aux_input_channel_pointers.iter().zip(
+        for ((input_channel_pointers, is_active), (input_storage, input_buffer)) in self
+            .aux_input_channel_pointers
+            .iter()
+            .zip(self.aux_input_active.iter())
+            .zip(
                 self.aux_input_storage
                     .iter_mut()
                     .zip(self.aux_input_buffers.iter_mut()),
             )
         {
             // Since these buffers are backed by our own storage, we can fill them with zeroes if
-            // the pointers are missing for whatever reason that might be
-            nih_debug_assert!(input_channel_pointers.is_some());
+            // the pointers are missing for whatever reason that might be, or if the host has
+            // deactivated the port
+            nih_debug_assert!(input_channel_pointers.is_some() || !is_active);
             match input_channel_pointers {
-                Some(input_channel_pointers) => {
+                Some(input_channel_pointers) if *is_active => {
                     nih_debug_assert_eq!(input_channel_pointers.num_channels, input_storage.len());
                     for (channel_idx, channel) in input_storage
                         .iter_mut()
@@ -295,13 +408,16 @@ impl BufferManager {
                         channel.fill(0.0);
                     }
                 }
-                None => {
+                _ => {
                     for channel in input_storage.iter_mut() {
+                        nih_debug_assert!(num_samples <= channel.capacity());
+                        channel.resize(num_samples, 0.0);
                         channel.fill(0.0);
                     }
                 }
             }
 
+            input_buffer.set_active(*is_active);
             input_buffer.set_slices(num_samples, |input_slices| {
                 // Since we initialized both `input_buffer` and `input_storage` this invariant
                 // should never fail unless we made an error ourselves
@@ -317,12 +433,38 @@ impl BufferManager {
         }
 
         // The auxiliary output buffers can point directly to the host's buffers. This logic is the
-        // same as the main outputs, minus the copying of input cdata
-        for (output_channel_pointers, output_buffer) in self
+        // same as the main outputs, minus the copying of input cdata. Inactive outputs are backed
+        // by our own scratch storage instead.
+        for ((output_channel_pointers, is_active), (output_storage, output_buffer)) in self
             .aux_output_channel_pointers
             .iter()
-            .zip(self.aux_output_buffers.iter_mut())
+            .zip(self.aux_output_active.iter())
+            .zip(
+                self.aux_output_storage
+                    .iter_mut()
+                    .zip(self.aux_output_buffers.iter_mut()),
+            )
         {
+            output_buffer.set_active(*is_active);
+            if !is_active {
+                output_buffer.set_slices(num_samples, |output_slices| {
+                    debug_assert_eq!(output_slices.len(), output_storage.len());
+
+                    for (output_slice, channel_storage) in
+                        output_slices.iter_mut().zip(output_storage.iter_mut())
+                    {
+                        nih_debug_assert!(num_samples <= channel_storage.capacity());
+                        channel_storage.resize(num_samples, 0.0);
+                        channel_storage.fill(0.0);
+
+                        // SAFETY: `channel_storage` is no longer used accessed directly after this
+                        *output_slice = &mut *(channel_storage.as_mut_slice() as *mut [f32]);
+                    }
+                });
+
+                continue;
+            }
+
             output_buffer.set_slices(num_samples, |output_slices| {
                 match output_channel_pointers {
                     Some(output_channel_pointers) => {
@@ -376,7 +518,7 @@ impl BufferManager {
 #[cfg(any(miri, test))]
 mod miri {
     use super::*;
-    use crate::prelude::{new_nonzero_u32, PortNames};
+    use crate::prelude::{new_nonzero_u32, ChannelLayouts, PortNames};
 
     const BUFFER_SIZE: usize = 512;
     const NUM_MAIN_INPUT_CHANNELS: usize = 1;
@@ -391,6 +533,7 @@ mod miri {
         aux_input_ports: &[new_nonzero_u32(NUM_AUX_CHANNELS as u32); NUM_AUX_PORTS],
         aux_output_ports: &[new_nonzero_u32(NUM_AUX_CHANNELS as u32); NUM_AUX_PORTS],
         names: PortNames::const_default(),
+        channel_layouts: ChannelLayouts::const_default(),
     };
 
     #[test]
@@ -501,4 +644,77 @@ mod miri {
             }
         }
     }
+
+    #[test]
+    fn inactive_aux_ports() {
+        let mut main_io_storage = vec![vec![0.0f32; BUFFER_SIZE]; NUM_MAIN_OUTPUT_CHANNELS];
+        let mut aux_input_storage = vec![vec![1.0f32; BUFFER_SIZE]; NUM_AUX_CHANNELS];
+        let mut aux_output_storage = vec![vec![0.0f32; BUFFER_SIZE]; NUM_AUX_CHANNELS];
+
+        let mut main_io_channel_pointers: Vec<*mut f32> = main_io_storage
+            .iter_mut()
+            .map(|channel_slice| channel_slice.as_mut_ptr())
+            .collect();
+        let mut aux_input_channel_pointers: Vec<*mut f32> = aux_input_storage
+            .iter_mut()
+            .map(|channel_slice| channel_slice.as_mut_ptr())
+            .collect();
+        let mut aux_output_channel_pointers: Vec<*mut f32> = aux_output_storage
+            .iter_mut()
+            .map(|channel_slice| channel_slice.as_mut_ptr())
+            .collect();
+
+        // The host has deactivated all aux ports. It still provided buffers for the first input and
+        // output ports, but not for the second ones.
+        let mut buffer_manager = BufferManager::for_audio_io_layout(BUFFER_SIZE, AUDIO_IO_LAYOUT);
+        let buffers = unsafe {
+            buffer_manager.create_buffers(0, BUFFER_SIZE, |buffer_sources| {
+                *buffer_sources.main_output_channel_pointers = Some(ChannelPointers {
+                    ptrs: NonNull::new(main_io_channel_pointers.as_mut_ptr()).unwrap(),
+                    num_channels: main_io_channel_pointers.len(),
+                });
+                buffer_sources.aux_input_channel_pointers[0] = Some(ChannelPointers {
+                    ptrs: NonNull::new(aux_input_channel_pointers.as_mut_ptr()).unwrap(),
+                    num_channels: aux_input_channel_pointers.len(),
+                });
+                buffer_sources.aux_output_channel_pointers[0] = Some(ChannelPointers {
+                    ptrs: NonNull::new(aux_output_channel_pointers.as_mut_ptr()).unwrap(),
+                    num_channels: aux_output_channel_pointers.len(),
+                });
+                buffer_sources.aux_input_active.fill(false);
+                buffer_sources.aux_output_active.fill(false);
+            })
+        };
+
+        assert!(buffers.main_buffer.is_active());
+        assert!(buffers.aux_inputs.iter().all(|buffer| !buffer.is_active()));
+        assert!(buffers.aux_outputs.iter().all(|buffer| !buffer.is_active()));
+
+        // The inactive inputs are silent even though the host provided data for one of them, and
+        // the inactive outputs can still be written to
+        for buffer in buffers.aux_inputs.iter_mut() {
+            assert_eq!(buffer.channels(), NUM_AUX_CHANNELS);
+            for channel_samples in buffer.iter_samples() {
+                for sample in channel_samples {
+                    assert!(*sample == 0.0);
+                }
+            }
+        }
+        for buffer in buffers.aux_outputs.iter_mut() {
+            assert_eq!(buffer.samples(), BUFFER_SIZE);
+            assert_eq!(buffer.channels(), NUM_AUX_CHANNELS);
+            for channel_samples in buffer.iter_samples() {
+                for sample in channel_samples {
+                    *sample += 1.0;
+                }
+            }
+        }
+
+        // Writes to the inactive output should not end up in the host's buffers
+        for channel in aux_output_storage.iter() {
+            for sample in channel {
+                assert!(*sample == 0.0);
+            }
+        }
+    }
 }
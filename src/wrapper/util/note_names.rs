@@ -0,0 +1,300 @@
+//! A cache for the names returned by [`Plugin::note_name()`], shared between the wrappers.
+
+use parking_lot::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::midi::MidiConfig;
+use crate::plugin::Plugin;
+
+/// The number of MIDI channels note names are collected for.
+const NUM_CHANNELS: u8 = 16;
+/// The number of keys note names are collected for.
+const NUM_NOTES: u8 = 128;
+
+/// A key's name on a single MIDI channel, or on all channels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteName {
+    /// The zero-indexed MIDI channel this name applies to, or `None` if the key has this name on
+    /// every channel.
+    pub channel: Option<u8>,
+    /// The MIDI note number.
+    pub note: u8,
+    pub name: String,
+}
+
+/// The plugin's note names. Collecting these requires calling [`Plugin::note_name()`] for every key
+/// on every channel, so this is only done when the names are first needed and after the plugin
+/// has called `note_names_changed()`. The names are stored outside of the plugin's lock so hosts
+/// can query them without blocking the audio thread.
+#[derive(Debug)]
+pub struct NoteNameCache {
+    /// Whether the names need to be collected again before they can be used. This is set by
+    /// [`invalidate()`][Self::invalidate()], which may be called from the audio thread.
+    changed: AtomicBool,
+    /// The note names from the last time they were collected. See [`collect_note_names()`].
+    names: Mutex<Vec<NoteName>>,
+}
+
+impl Default for NoteNameCache {
+    fn default() -> Self {
+        Self {
+            changed: AtomicBool::new(true),
+            names: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl NoteNameCache {
+    /// Mark the names as changed so they're collected again the next time they're needed. This
+    /// does not lock or allocate, so it's safe to call from the audio thread.
+    pub fn invalidate(&self) {
+        self.changed.store(true, Ordering::SeqCst);
+    }
+
+    /// Get the note names, collecting them first if they have changed. The plugin is only locked
+    /// while collecting the names.
+    pub fn names<P: Plugin>(&self, plugin: &Mutex<P>) -> MutexGuard<'_, Vec<NoteName>> {
+        let mut names = self.names.lock();
+        if P::HAS_NOTE_NAMES && self.changed.swap(false, Ordering::SeqCst) {
+            *names = collect_note_names(&*plugin.lock());
+        }
+
+        names
+    }
+
+    /// Get the note names from the last time they were collected without checking whether they
+    /// have changed. Used for APIs where the host first queries the number of names and then
+    /// queries the names by index.
+    pub fn cached_names(&self) -> MutexGuard<'_, Vec<NoteName>> {
+        self.names.lock()
+    }
+
+    /// Get the name of a key on the first MIDI channel, collecting the names first if they have
+    /// changed.
+    pub fn first_channel_name<P: Plugin>(&self, plugin: &Mutex<P>, note: u8) -> Option<String> {
+        self.names(plugin)
+            .iter()
+            .find(|name| name.note == note && matches!(name.channel, None | Some(0)))
+            .map(|name| name.name.clone())
+    }
+}
+
+/// Query the plugin's name for every key on every channel. Keys that have the same name on every
+/// channel are folded into a single [`NoteName`] with a `None` channel. The plugin is not queried at
+/// all if [`Plugin::HAS_NOTE_NAMES`] is not set or if the plugin does not accept MIDI input.
+pub fn collect_note_names<P: Plugin>(plugin: &P) -> Vec<NoteName> {
+    let mut names = Vec::new();
+    if !P::HAS_NOTE_NAMES || P::MIDI_INPUT < MidiConfig::Basic {
+        return names;
+    }
+
+    let mut channel_names = Vec::with_capacity(NUM_CHANNELS as usize);
+    for note in 0..NUM_NOTES {
+        channel_names.clear();
+        channel_names.extend((0..NUM_CHANNELS).map(|channel| plugin.note_name(channel, note)));
+
+        match &channel_names[0] {
+            Some(name) if channel_names.iter().all(|other| other == &channel_names[0]) => names
+                .push(NoteName {
+                    channel: None,
+                    note,
+                    name: name.clone(),
+                }),
+            _ => {
+                for (channel, name) in channel_names.drain(..).enumerate() {
+                    if let Some(name) = name {
+                        names.push(NoteName {
+                            channel: Some(channel as u8),
+                            note,
+                            name,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+
+    /// The number of times [`Plugin::note_name()`] has been called for a plugin instance.
+    struct Calls(AtomicUsize);
+
+    struct EmptyParams;
+
+    unsafe impl Params for EmptyParams {
+        fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+            Vec::new()
+        }
+    }
+
+    /// Names the first two keys on every channel, and key 2 only on the second channel. Key 1's
+    /// name can be changed.
+    struct DrumPlugin {
+        calls: Calls,
+        snare_name: &'static str,
+    }
+
+    impl Default for DrumPlugin {
+        fn default() -> Self {
+            Self {
+                calls: Calls(AtomicUsize::new(0)),
+                snare_name: "Snare",
+            }
+        }
+    }
+
+    impl Plugin for DrumPlugin {
+        const NAME: &'static str = "Drums";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[AudioIOLayout {
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        }];
+        const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+        const HAS_NOTE_NAMES: bool = true;
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            Arc::new(EmptyParams)
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+
+        fn note_name(&self, channel: u8, note: u8) -> Option<String> {
+            self.calls.0.fetch_add(1, Ordering::Relaxed);
+            match (channel, note) {
+                (_, 0) => Some(String::from("Kick")),
+                (_, 1) => Some(String::from(self.snare_name)),
+                (1, 2) => Some(String::from("Rimshot")),
+                _ => None,
+            }
+        }
+    }
+
+    /// The same as [`DrumPlugin`], but without setting [`Plugin::HAS_NOTE_NAMES`].
+    #[derive(Default)]
+    struct UnnamedPlugin(DrumPlugin);
+
+    impl Plugin for UnnamedPlugin {
+        const NAME: &'static str = "Unnamed";
+        const VENDOR: &'static str = "NIH-plug";
+        const URL: &'static str = "";
+        const EMAIL: &'static str = "";
+        const VERSION: &'static str = "0.0.0";
+
+        const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = DrumPlugin::AUDIO_IO_LAYOUTS;
+        const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+
+        type SysExMessage = ();
+        type BackgroundTask = ();
+
+        fn params(&self) -> Arc<dyn Params> {
+            Arc::new(EmptyParams)
+        }
+
+        fn process(
+            &mut self,
+            _buffer: &mut Buffer,
+            _aux: &mut AuxiliaryBuffers,
+            _context: &mut impl ProcessContext<Self>,
+        ) -> ProcessStatus {
+            ProcessStatus::Normal
+        }
+
+        fn note_name(&self, channel: u8, note: u8) -> Option<String> {
+            self.0.note_name(channel, note)
+        }
+    }
+
+    #[test]
+    fn collect_and_fold_wildcards() {
+        let names = collect_note_names(&DrumPlugin::default());
+        assert_eq!(
+            names,
+            [
+                NoteName {
+                    channel: None,
+                    note: 0,
+                    name: String::from("Kick"),
+                },
+                NoteName {
+                    channel: None,
+                    note: 1,
+                    name: String::from("Snare"),
+                },
+                NoteName {
+                    channel: Some(1),
+                    note: 2,
+                    name: String::from("Rimshot"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_plugins_without_note_names() {
+        let plugin = UnnamedPlugin::default();
+        assert!(collect_note_names(&plugin).is_empty());
+        assert_eq!(plugin.0.calls.0.load(Ordering::Relaxed), 0);
+
+        let plugin = Mutex::new(plugin);
+        let cache = NoteNameCache::default();
+        assert!(cache.names(&plugin).is_empty());
+        assert_eq!(cache.first_channel_name(&plugin, 0), None);
+        assert_eq!(plugin.lock().0.calls.0.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn cache_until_invalidated() {
+        let plugin = Mutex::new(DrumPlugin::default());
+        let cache = NoteNameCache::default();
+        assert!(cache.cached_names().is_empty());
+
+        assert_eq!(cache.names(&plugin).len(), 3);
+        let num_calls = plugin.lock().calls.0.load(Ordering::Relaxed);
+        assert_eq!(num_calls, NUM_CHANNELS as usize * NUM_NOTES as usize);
+
+        // The names should not be collected again until they have changed
+        assert_eq!(
+            cache.first_channel_name(&plugin, 1).as_deref(),
+            Some("Snare")
+        );
+        assert_eq!(cache.first_channel_name(&plugin, 2), None);
+        assert_eq!(plugin.lock().calls.0.load(Ordering::Relaxed), num_calls);
+
+        plugin.lock().snare_name = "Clap";
+        assert_eq!(
+            cache.first_channel_name(&plugin, 1).as_deref(),
+            Some("Snare")
+        );
+        cache.invalidate();
+        assert_eq!(
+            cache.first_channel_name(&plugin, 1).as_deref(),
+            Some("Clap")
+        );
+        assert_eq!(cache.cached_names()[1].name, "Clap");
+    }
+}
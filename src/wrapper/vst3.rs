@@ -2,10 +2,12 @@
 mod util;
 
 mod context;
+mod context_menu;
 mod factory;
 mod inner;
 mod note_expressions;
 mod param_units;
+mod speakers;
 pub mod subcategories;
 mod view;
 mod wrapper;
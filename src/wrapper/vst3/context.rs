@@ -3,13 +3,15 @@ use std::cell::Cell;
 use std::collections::VecDeque;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use vst3_sys::base::kResultOk;
 use vst3_sys::vst::IComponentHandler;
 
 use crate::prelude::{
-    GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent, PluginState, ProcessContext,
-    Transport, Vst3Plugin,
+    ContextMenuItem, GuiContext, InitContext, ParamPtr, PluginApi, PluginNoteEvent, PluginState,
+    ProcessContext, TrackInfo, Transport, Vst3Plugin,
 };
 
+use super::context_menu;
 use super::inner::{Task, WrapperInner};
 
 /// An [`InitContext`] implementation for the wrapper.
@@ -67,6 +69,10 @@ impl<P: Vst3Plugin> InitContext<P> for WrapperInitContext<'_, P> {
         PluginApi::Vst3
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.inner.track_info.lock().clone()
+    }
+
     fn execute(&self, task: P::BackgroundTask) {
         (self.inner.task_executor.lock())(task);
     }
@@ -116,6 +122,10 @@ impl<P: Vst3Plugin> ProcessContext<P> for WrapperProcessContext<'_, P> {
     fn set_current_voice_capacity(&self, _capacity: u32) {
         // This is only supported by CLAP
     }
+
+    fn note_names_changed(&self) {
+        self.inner.note_names_changed()
+    }
 }
 
 impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
@@ -123,6 +133,10 @@ impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
         PluginApi::Vst3
     }
 
+    fn track_info(&self) -> Option<TrackInfo> {
+        self.inner.track_info.lock().clone()
+    }
+
     fn request_resize(&self) -> bool {
         let task_posted = self.inner.schedule_gui(Task::RequestResize);
         nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
@@ -221,6 +235,49 @@ impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
         }
     }
 
+    unsafe fn raw_show_param_context_menu(&self, param: ParamPtr, x: i32, y: i32) -> bool {
+        match self.inner.param_ptr_to_hash.get(&param) {
+            Some(hash) => match context_menu::create_param_context_menu(&self.inner, *hash) {
+                Some(menu) => menu.popup(x, y) == kResultOk,
+                None => false,
+            },
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                false
+            }
+        }
+    }
+
+    unsafe fn raw_param_context_menu_items(&self, param: ParamPtr) -> Vec<ContextMenuItem> {
+        match self.inner.param_ptr_to_hash.get(&param) {
+            Some(hash) => context_menu::create_param_context_menu(&self.inner, *hash)
+                .map(|menu| context_menu::context_menu_items(&menu))
+                .unwrap_or_default(),
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                Vec::new()
+            }
+        }
+    }
+
+    unsafe fn raw_perform_param_context_menu_action(
+        &self,
+        param: ParamPtr,
+        action_id: u32,
+    ) -> bool {
+        // VST3 context menus are stateful, so the menu is recreated to find the item again
+        match self.inner.param_ptr_to_hash.get(&param) {
+            Some(hash) => match context_menu::create_param_context_menu(&self.inner, *hash) {
+                Some(menu) => context_menu::perform_context_menu_action(&menu, action_id),
+                None => false,
+            },
+            None => {
+                nih_debug_assert_failure!("Unknown parameter: {:?}", param);
+                false
+            }
+        }
+    }
+
     fn get_state(&self) -> PluginState {
         self.inner.get_state_object()
     }
@@ -228,4 +285,8 @@ impl<P: Vst3Plugin> GuiContext for WrapperGuiContext<P> {
     fn set_state(&self, state: PluginState) {
         self.inner.set_state_object_from_gui(state)
     }
+
+    fn note_names_changed(&self) {
+        self.inner.note_names_changed()
+    }
 }
@@ -0,0 +1,151 @@
+//! Access to the host's parameter context menus through `IComponentHandler3`.
+
+use std::ffi::c_void;
+use std::mem;
+use std::ptr;
+use vst3_sys::base::kResultOk;
+use vst3_sys::gui::IPlugView;
+use vst3_sys::interfaces::IUnknown;
+use vst3_sys::utils::SharedVstPtr;
+use vst3_sys::vst::{IComponentHandler3, IContextMenu, IContextMenuItem, IContextMenuTarget};
+use vst3_sys::{ComInterface, VstPtr};
+use widestring::U16CStr;
+
+use super::inner::WrapperInner;
+use crate::prelude::{ContextMenuItem, Vst3Plugin};
+
+// These are the `IContextMenuItem::Flags` values. The group flags include the disabled and
+// separator flags, so the group flags need to be checked first.
+const ITEM_IS_SEPARATOR: i32 = 1 << 0;
+const ITEM_IS_DISABLED: i32 = 1 << 1;
+const ITEM_IS_CHECKED: i32 = 1 << 2;
+const ITEM_IS_GROUP_START: i32 = (1 << 3) | ITEM_IS_DISABLED;
+const ITEM_IS_GROUP_END: i32 = (1 << 4) | ITEM_IS_SEPARATOR;
+
+/// Ask the host to create its context menu for the parameter with the specified hash. Returns
+/// `None` if the editor is closed or if the host does not support `IComponentHandler3`. Must be
+/// called from the GUI thread.
+pub unsafe fn create_param_context_menu<P: Vst3Plugin>(
+    inner: &WrapperInner<P>,
+    param_hash: u32,
+) -> Option<VstPtr<dyn IContextMenu>> {
+    let plug_view = inner.plug_view.read();
+    let plug_view = plug_view.as_ref()?;
+    let component_handler = inner.component_handler.borrow();
+    let component_handler = component_handler.as_ref()?;
+    let component_handler = match component_handler.cast::<dyn IComponentHandler3>() {
+        Some(component_handler) => component_handler,
+        None => {
+            nih_trace!("The host does not support IComponentHandler3");
+            return None;
+        }
+    };
+
+    // The host needs a pointer to the view's `IPlugView` interface. This adds a reference to the
+    // view that is released again after the menu has been created.
+    let mut plug_view_ptr: *mut c_void = ptr::null_mut();
+    let result =
+        plug_view.query_interface(&<dyn IPlugView as ComInterface>::IID, &mut plug_view_ptr);
+    if result != kResultOk || plug_view_ptr.is_null() {
+        nih_debug_assert_failure!("The editor does not implement IPlugView");
+        return None;
+    }
+
+    // The correct argument and return types are missing from the bindings
+    let menu: SharedVstPtr<dyn IContextMenu> = mem::transmute(
+        component_handler.create_context_menu(mem::transmute(plug_view_ptr), &param_hash),
+    );
+    plug_view.release();
+    let menu = menu.upgrade()?;
+
+    // `createContextMenu()` already returned an owned reference, and upgrading the pointer added
+    // another one
+    menu.release();
+
+    Some(menu)
+}
+
+/// Convert the items in the host's context menu to NIH-plug's [`ContextMenuItem`]s. VST3 doesn't
+/// have action IDs, so the items' tags are used instead.
+pub unsafe fn context_menu_items(menu: &VstPtr<dyn IContextMenu>) -> Vec<ContextMenuItem> {
+    // VST3's groups are a flat list of items between a group start and a group end item, just like
+    // CLAP's submenus
+    let mut items = Vec::new();
+    let mut open_submenus: Vec<(String, Vec<ContextMenuItem>)> = Vec::new();
+    for index in 0..menu.get_item_count() {
+        let mut item: IContextMenuItem = mem::zeroed();
+        let mut target: *mut c_void = std::ptr::null_mut();
+        if menu.get_item(index, &mut item, &mut target as *mut *mut c_void as *mut _) != kResultOk {
+            continue;
+        }
+
+        // Just in case the host doesn't null terminate the string
+        *item.name.last_mut().unwrap() = 0;
+        let label = U16CStr::from_ptr_str(item.name.as_ptr() as *const u16).to_string_lossy();
+
+        let item = if item.flags & ITEM_IS_GROUP_START == ITEM_IS_GROUP_START {
+            open_submenus.push((label, Vec::new()));
+            continue;
+        } else if item.flags & ITEM_IS_GROUP_END == ITEM_IS_GROUP_END {
+            match open_submenus.pop() {
+                Some((label, submenu_items)) => ContextMenuItem::Submenu {
+                    label,
+                    is_enabled: true,
+                    items: submenu_items,
+                },
+                None => continue,
+            }
+        } else if item.flags & ITEM_IS_SEPARATOR != 0 {
+            ContextMenuItem::Separator
+        } else {
+            ContextMenuItem::Entry {
+                label,
+                action_id: item.tag as u32,
+                is_enabled: item.flags & ITEM_IS_DISABLED == 0,
+                is_checked: item.flags & ITEM_IS_CHECKED != 0,
+            }
+        };
+
+        match open_submenus.last_mut() {
+            Some((_, submenu_items)) => submenu_items.push(item),
+            None => items.push(item),
+        }
+    }
+
+    // Close any groups the host forgot to end
+    while let Some((label, submenu_items)) = open_submenus.pop() {
+        let item = ContextMenuItem::Submenu {
+            label,
+            is_enabled: true,
+            items: submenu_items,
+        };
+        match open_submenus.last_mut() {
+            Some((_, parent_items)) => parent_items.push(item),
+            None => items.push(item),
+        }
+    }
+
+    items
+}
+
+/// Execute the item with the tag `action_id` in the host's context menu. Returns `false` if the
+/// menu does not contain an item with that tag.
+pub unsafe fn perform_context_menu_action(menu: &VstPtr<dyn IContextMenu>, action_id: u32) -> bool {
+    for index in 0..menu.get_item_count() {
+        let mut item: IContextMenuItem = mem::zeroed();
+        let mut target: *mut c_void = std::ptr::null_mut();
+        if menu.get_item(index, &mut item, &mut target as *mut *mut c_void as *mut _) != kResultOk
+            || item.tag as u32 != action_id
+        {
+            continue;
+        }
+
+        let target: SharedVstPtr<dyn IContextMenuTarget> = mem::transmute(target);
+        return match target.upgrade() {
+            Some(target) => target.execute_menu_item(item.tag) == kResultOk,
+            None => false,
+        };
+    }
+
+    false
+}
@@ -1,28 +1,36 @@
 use atomic_refcell::AtomicRefCell;
 use crossbeam::atomic::AtomicCell;
-use crossbeam::channel::{self, SendTimeoutError};
 use parking_lot::{Mutex, RwLock};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
 use vst3_sys::base::{kInvalidArgument, kResultOk, tresult};
-use vst3_sys::vst::{IComponentHandler, RestartFlags};
+use vst3_sys::vst::{IComponentHandler, IUnitHandler, RestartFlags};
 
 use super::context::{WrapperGuiContext, WrapperInitContext, WrapperProcessContext};
 use super::note_expressions::NoteExpressionController;
 use super::param_units::ParamUnits;
-use super::util::{ObjectPtr, VstPtr, VST3_MIDI_PARAMS_END, VST3_MIDI_PARAMS_START};
+use super::util::{
+    ObjectPtr, VstPtr, VST3_MIDI_PARAMS_END, VST3_MIDI_PARAMS_START,
+    VST3_NOTE_NAMES_PROGRAM_LIST_ID,
+};
 use super::view::WrapperView;
+use crate::audio_setup::AudioIOLayoutsValidator;
 use crate::event_loop::{EventLoop, MainThreadExecutor, OsEventLoop};
+use crate::midi::mpe::MpeTranslator;
 use crate::prelude::{
     AsyncExecutor, AudioIOLayout, BufferConfig, Editor, MidiConfig, ParamFlags, ParamPtr, Params,
-    Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor, Transport, Vst3Plugin,
+    Plugin, PluginNoteEvent, ProcessMode, ProcessStatus, TaskExecutor, TrackInfo, Transport,
+    Vst3Plugin,
 };
 use crate::util::permit_alloc;
+use crate::wrapper::state::prepared::{
+    handoff_timeout, HandoffResult, PreparedState, StateHandoff,
+};
 use crate::wrapper::state::{self, PluginState};
-use crate::wrapper::util::buffer_management::BufferManager;
-use crate::wrapper::util::{hash_param_id, process_wrapper};
+use crate::wrapper::util::buffer_management::{AuxPortActivation, BufferManager};
+use crate::wrapper::util::hash_param_id;
+use crate::wrapper::util::note_names::NoteNameCache;
 
 /// The actual wrapper bits. We need this as an `Arc<T>` so we can safely use our event loop API.
 /// Since we can't combine that with VST3's interior reference counting this just has to be moved to
@@ -68,6 +76,9 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// IO layout is chosen as the default. Because of the way VST3 works it's not possible to
     /// change the number of busses from that default, only the channel counts can change.
     pub current_audio_io_layout: AtomicCell<AudioIOLayout>,
+    /// Which of the current audio IO layout's auxiliary busses are active. Modified through
+    /// `IComponent::activateBus()`. All busses are active by default.
+    pub aux_port_activation: AtomicRefCell<AuxPortActivation>,
     /// The current buffer configuration, containing the sample rate and the maximum block size.
     /// Will be set in `IAudioProcessor::setupProcessing()`.
     pub current_buffer_config: AtomicCell<Option<BufferConfig>>,
@@ -78,6 +89,13 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// The current latency in samples, as set by the plugin through the [`InitContext`] and the
     /// [`ProcessContext`].
     pub current_latency: AtomicU32,
+    /// The plugin's note names for the first MIDI channel, exposed through a program list. These
+    /// are collected when the host first queries them and after the plugin reports that they have
+    /// changed.
+    pub note_names: NoteNameCache,
+    /// The track information last passed to `IInfoListener::setChannelContextInfos()`, if the host
+    /// supports that interface.
+    pub track_info: Mutex<Option<TrackInfo>>,
     /// A data structure that helps manage and create buffers for all of the plugin's inputs and
     /// outputs based on channel pointers provided by the host.
     pub buffer_manager: AtomicRefCell<BufferManager>,
@@ -97,6 +115,9 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// the most recent VST3 note IDs we've seen, and then map those back to MIDI note IDs and
     /// channels as needed.
     pub note_expression_controller: AtomicRefCell<NoteExpressionController>,
+    /// Translates the MIDI CCs, channel pressure, and pitch bend messages sent on MPE member
+    /// channels to polyphonic expression events if `P::MIDI_INPUT` is set to `MidiConfig::Mpe`.
+    pub mpe_translator: AtomicRefCell<MpeTranslator>,
     /// Unprocessed parameter changes and note events sent by the host during a process call.
     /// Parameter changes are sent as separate queues for each parameter, and note events are in
     /// another queue on top of that. And if `P::MIDI_INPUT >= MidiConfig::MidiCCs`, then we can
@@ -107,17 +128,12 @@ pub(crate) struct WrapperInner<P: Vst3Plugin> {
     /// then do the block splitting based on that. Note events need to have their timing adjusted to
     /// match the block start, since they're all read upfront.
     pub process_events: AtomicRefCell<Vec<ProcessEvent<P>>>,
-    /// The plugin is able to restore state through a method on the `GuiContext`. To avoid changing
-    /// parameters mid-processing and running into garbled data if the host also tries to load state
-    /// at the same time the restoring happens at the end of each processing call. If this zero
-    /// capacity channel contains state data at that point, then the audio thread will take the
-    /// state out of the channel, restore the state, and then send it back through the same channel.
-    /// In other words, the GUI thread acts as a sender and then as a receiver, while the audio
-    /// thread acts as a receiver and then as a sender. That way deallocation can happen on the GUI
-    /// thread. All of this happens without any blocking on the audio thread.
-    pub updated_state_sender: channel::Sender<PluginState>,
-    /// The receiver belonging to [`new_state_sender`][Self::new_state_sender].
-    pub updated_state_receiver: channel::Receiver<PluginState>,
+    /// State can be loaded by the host or through a method on the `GuiContext` while the plugin is
+    /// processing audio. To avoid changing parameters mid-processing and to avoid doing any
+    /// realtime-unsafe work on the audio thread, the state is prepared on the thread that's loading
+    /// it, and the prepared state is then applied at the end of the next processing call. See
+    /// [`set_state_inner()`][Self::set_state_inner()].
+    pub state_handoff: StateHandoff,
 
     /// The keys from `param_map` in a stable order.
     pub param_hashes: Vec<u32>,
@@ -157,6 +173,8 @@ pub enum Task<P: Plugin> {
     /// Request the editor to be resized according to its current size. Right now there is no way to
     /// handle "denied resize" requests yet.
     RequestResize,
+    /// Inform the host that the plugin's note names have changed.
+    NoteNamesChanged,
 }
 
 /// VST3 makes audio processing pretty complicated. In order to support both block splitting for
@@ -188,13 +206,11 @@ pub enum ProcessEvent<P: Plugin> {
 impl<P: Vst3Plugin> WrapperInner<P> {
     #[allow(unused_unsafe)]
     pub fn new() -> Arc<Self> {
+        let () = AudioIOLayoutsValidator::<P>::ASSERT_VALID;
+
         let mut plugin = P::default();
         let task_executor = Mutex::new(plugin.task_executor());
 
-        // This is used to allow the plugin to restore preset data from its editor, see the comment
-        // on `Self::updated_state_sender`
-        let (updated_state_sender, updated_state_receiver) = channel::bounded(0);
-
         // This is a mapping from the parameter IDs specified by the plugin to pointers to those
         // parameters. These pointers are assumed to be safe to dereference as long as
         // `wrapper.plugin` is alive. The plugin API identifiers these parameters by hashes, which
@@ -295,10 +311,15 @@ impl<P: Vst3Plugin> WrapperInner<P> {
             current_audio_io_layout: AtomicCell::new(
                 P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
             ),
+            aux_port_activation: AtomicRefCell::new(AuxPortActivation::for_audio_io_layout(
+                &P::AUDIO_IO_LAYOUTS.first().copied().unwrap_or_default(),
+            )),
             current_buffer_config: AtomicCell::new(None),
             current_process_mode: AtomicCell::new(ProcessMode::Realtime),
             last_process_status: AtomicCell::new(ProcessStatus::Normal),
             current_latency: AtomicU32::new(0),
+            note_names: NoteNameCache::default(),
+            track_info: Mutex::new(None),
             // This is initialized just before calling `Plugin::initialize()` so that during the
             // process call buffers can be initialized without any allocations
             buffer_manager: AtomicRefCell::new(BufferManager::for_audio_io_layout(
@@ -308,9 +329,9 @@ impl<P: Vst3Plugin> WrapperInner<P> {
             input_events: AtomicRefCell::new(VecDeque::with_capacity(1024)),
             output_events: AtomicRefCell::new(VecDeque::with_capacity(1024)),
             note_expression_controller: AtomicRefCell::new(NoteExpressionController::default()),
+            mpe_translator: AtomicRefCell::new(MpeTranslator::default()),
             process_events: AtomicRefCell::new(Vec::with_capacity(4096)),
-            updated_state_sender,
-            updated_state_receiver,
+            state_handoff: StateHandoff::default(),
 
             param_hashes,
             param_by_hash,
@@ -475,44 +496,10 @@ impl<P: Vst3Plugin> WrapperInner<P> {
 
     /// Update the plugin's internal state, called by the plugin itself from the GUI thread. To
     /// prevent corrupting data and changing parameters during processing the actual state is only
-    /// updated at the end of the audio processing cycle.
+    /// updated at the end of the audio processing cycle. See
+    /// [`set_state_inner()`][Self::set_state_inner()].
     pub fn set_state_object_from_gui(&self, mut state: PluginState) {
-        // Use a loop and timeouts to handle the super rare edge case when this function gets called
-        // between a process call and the host disabling the plugin
-        loop {
-            if self.is_processing.load(Ordering::SeqCst) {
-                // If the plugin is currently processing audio, then we'll perform the restore
-                // operation at the end of the audio call. This involves sending the state to the
-                // audio thread, having the audio thread handle the state restore at the very end of
-                // the process function, and then sending the state back to this thread so it can be
-                // deallocated without blocking the audio thread.
-                match self
-                    .updated_state_sender
-                    .send_timeout(state, Duration::from_secs(1))
-                {
-                    Ok(_) => {
-                        // As mentioned above, the state object will be passed back to this thread
-                        // so we can deallocate it without blocking.
-                        let state = self.updated_state_receiver.recv();
-                        drop(state);
-                        break;
-                    }
-                    Err(SendTimeoutError::Timeout(value)) => {
-                        state = value;
-                        continue;
-                    }
-                    Err(SendTimeoutError::Disconnected(_)) => {
-                        nih_debug_assert_failure!("State update channel got disconnected");
-                        return;
-                    }
-                }
-            } else {
-                // Otherwise we'll set the state right here and now, since this function should be
-                // called from a GUI thread
-                self.set_state_inner(&mut state);
-                break;
-            }
-        }
+        self.set_state_inner(&mut state);
 
         // After the state has been updated, notify the host about the new parameter values
         let task_posted =
@@ -526,6 +513,19 @@ impl<P: Vst3Plugin> WrapperInner<P> {
         nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
     }
 
+    /// Whether the plugin exposes note names. VST3 does not have per-channel note names, so only
+    /// the names for the first MIDI channel are used. This does not depend on the names
+    /// themselves so the plugin's units don't change when the names change.
+    pub fn has_note_names(&self) -> bool {
+        P::HAS_NOTE_NAMES && P::MIDI_INPUT >= MidiConfig::Basic
+    }
+
+    pub fn note_names_changed(&self) {
+        self.note_names.invalidate();
+        let task_posted = self.schedule_gui(Task::NoteNamesChanged);
+        nih_debug_assert!(task_posted, "The task queue is full, dropping task...");
+    }
+
     pub fn set_latency_samples(&self, samples: u32) {
         // Only trigger a restart if it's actually needed
         let old_latency = self.current_latency.swap(samples, Ordering::SeqCst);
@@ -536,54 +536,48 @@ impl<P: Vst3Plugin> WrapperInner<P> {
         }
     }
 
-    /// Immediately set the plugin state. Returns `false` if the deserialization failed. The plugin
-    /// state is set from a couple places, so this function aims to deduplicate that. Includes
-    /// `permit_alloc()`s around the deserialization and initialization for the use case where
-    /// `set_state_object_from_gui()` was called while the plugin is process audio.
+    /// Set the plugin state. Returns `false` if the plugin failed to load the state. The plugin
+    /// state is set from a couple places, so this function aims to deduplicate that. The state is
+    /// migrated, resolved, and prepared on the calling thread. If the plugin is currently processing
+    /// audio, then the prepared state is swapped in at the end of the next processing call and this
+    /// function blocks until that has happened. Otherwise the state is applied immediately.
     ///
     /// Implicitly emits `Task::ParameterValuesChanged`.
     ///
     /// # Notes
     ///
-    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
     pub fn set_state_inner(&self, state: &mut PluginState) -> bool {
         let audio_io_layout = self.current_audio_io_layout.load();
         let buffer_config = self.current_buffer_config.load();
 
-        // FIXME: This is obviously not realtime-safe, but loading presets without doing this could
-        //        lead to inconsistencies. It's the plugin's responsibility to not perform any
-        //        realtime-unsafe work when the initialize function is called a second time if it
-        //        supports runtime preset loading.  `state::deserialize_object()` normally never
-        //        allocates, but if the plugin has persistent non-parameter data then its
-        //        `deserialize_fields()` implementation may still allocate.
-        let mut success = permit_alloc(|| unsafe {
-            state::deserialize_object::<P>(
+        let mut prepared = Box::new(unsafe {
+            PreparedState::prepare::<P>(
                 state,
-                self.params.clone(),
                 state::make_params_getter(&self.param_by_hash, &self.param_id_to_hash),
-                buffer_config.as_ref(),
             )
         });
-        if !success {
-            nih_debug_assert_failure!("Deserializing plugin state from a state object failed");
-            return false;
+        if let Some(buffer_config) = &buffer_config {
+            prepared.set_plugin_data(P::prepare_state(state, &audio_io_layout, buffer_config));
         }
 
-        // If the plugin was already initialized then it needs to be reinitialized
-        if let Some(buffer_config) = buffer_config {
-            // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
-            let mut init_context = self.make_init_context();
-            let mut plugin = self.plugin.lock();
-
-            // See above
-            success = permit_alloc(|| {
-                plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
-            });
-            if success {
-                process_wrapper(|| plugin.reset());
+        let timeout = buffer_config
+            .as_ref()
+            .map(handoff_timeout)
+            .unwrap_or_default();
+        let success = match self
+            .state_handoff
+            .send_and_wait(prepared, &self.is_processing, timeout)
+        {
+            HandoffResult::Applied(prepared) => {
+                prepared.apply_fields(&*self.params);
+                true
             }
-        }
-
+            // The plugin is not processing audio, it didn't prepare anything, or it needs to be
+            // reinitialized, so the state is applied right here
+            HandoffResult::NotApplied(mut prepared) => self.apply_prepared_state(&mut prepared),
+        };
         nih_debug_assert!(
             success,
             "Plugin returned false when reinitializing after loading state"
@@ -603,6 +597,54 @@ impl<P: Vst3Plugin> WrapperInner<P> {
 
         success
     }
+
+    /// Apply state prepared in [`set_state_inner()`][Self::set_state_inner()] on the calling
+    /// thread. The plugin is locked while doing so, and it's reinitialized if it doesn't swap in the
+    /// prepared state.
+    ///
+    /// # Notes
+    ///
+    /// This must not be called from the audio thread, and `self.plugin` must _not_ be locked while
+    /// calling this function or it will deadlock.
+    pub fn apply_prepared_state(&self, prepared: &mut PreparedState) -> bool {
+        let audio_io_layout = self.current_audio_io_layout.load();
+        let buffer_config = self.current_buffer_config.load();
+
+        // NOTE: This needs to be dropped after the `plugin` lock to avoid deadlocks
+        let mut init_context = self.make_init_context();
+        let mut plugin = self.plugin.lock();
+        unsafe {
+            prepared.apply_with_plugin_lock(
+                &mut *plugin,
+                &*self.params,
+                buffer_config.map(|c| c.sample_rate),
+                |plugin| {
+                    buffer_config.map_or(true, |buffer_config| {
+                        plugin.initialize(&audio_io_layout, &buffer_config, &mut init_context)
+                    })
+                },
+            )
+        }
+    }
+
+    /// Swap in state prepared in [`set_state_inner()`][Self::set_state_inner()]. This is called at
+    /// the end of a processing call when state is loaded while the plugin is processing audio, and
+    /// it does not allocate. Returns `false` if the plugin needs to be reinitialized on the loading
+    /// thread instead.
+    ///
+    /// # Notes
+    ///
+    /// `self.plugin` must _not_ be locked while calling this function or it will deadlock.
+    pub fn apply_prepared_state_on_audio_thread(&self, prepared: &mut PreparedState) -> bool {
+        let buffer_config = match self.current_buffer_config.load() {
+            Some(buffer_config) => buffer_config,
+            None => return false,
+        };
+
+        // NOTE: `parking_lot`'s mutexes sometimes allocate because of their use of thread locals
+        let mut plugin = permit_alloc(|| self.plugin.lock());
+        unsafe { prepared.apply_on_audio_thread(&mut *plugin, buffer_config.sample_rate) }
+    }
 }
 
 impl<P: Vst3Plugin> MainThreadExecutor<Task<P>> for WrapperInner<P> {
@@ -648,6 +690,21 @@ impl<P: Vst3Plugin> MainThreadExecutor<Task<P>> for WrapperInner<P> {
                 },
                 None => nih_debug_assert_failure!("Can't resize a closed editor"),
             },
+            Task::NoteNamesChanged => match &*self.component_handler.borrow() {
+                Some(handler) => unsafe {
+                    nih_debug_assert!(is_gui_thread);
+                    match handler.cast::<dyn IUnitHandler>() {
+                        // A program index of -1 (`kAllProgramInvalid`) means that all of the
+                        // list's programs have changed
+                        Some(unit_handler) => {
+                            unit_handler
+                                .notify_program_list_change(VST3_NOTE_NAMES_PROGRAM_LIST_ID, -1);
+                        }
+                        None => nih_trace!("The host does not support IUnitHandler"),
+                    }
+                },
+                None => nih_debug_assert_failure!("Component handler not yet set"),
+            },
         }
     }
 }
@@ -110,6 +110,7 @@ impl NoteExpressionController {
             .note_ids
             .iter()
             .find(|(note_id, _, _)| *note_id == event.note_id)?;
+        let (channel, note) = (Some(channel), Some(note));
 
         match event.type_id {
             VOLUME_EXPRESSION_ID => Some(NoteEvent::PolyVolume {
@@ -145,19 +146,19 @@ impl NoteExpressionController {
                 note,
                 vibrato: event.value as f32,
             }),
-            EXPRESSION_EXPRESSION_ID => Some(NoteEvent::PolyBrightness {
+            EXPRESSION_EXPRESSION_ID => Some(NoteEvent::PolyExpression {
                 timing,
                 voice_id: Some(note_id),
                 channel,
                 note,
-                brightness: event.value as f32,
+                expression: event.value as f32,
             }),
-            BRIGHTNESS_EXPRESSION_ID => Some(NoteEvent::PolyExpression {
+            BRIGHTNESS_EXPRESSION_ID => Some(NoteEvent::PolyBrightness {
                 timing,
                 voice_id: Some(note_id),
                 channel,
                 note,
-                expression: event.value as f32,
+                brightness: event.value as f32,
             }),
             _ => None,
         }
@@ -170,11 +171,13 @@ impl NoteExpressionController {
         note_id: i32,
         event: &NoteEvent<impl SysExMessage>,
     ) -> Option<NoteExpressionValueEvent> {
+        // VST3 expression values are always normalized, so gains above +12 dB and tunings outside
+        // of the [-120, 120] semitone range will be clamped
         match &event {
             NoteEvent::PolyVolume { gain, .. } => Some(NoteExpressionValueEvent {
                 type_id: VOLUME_EXPRESSION_ID,
                 note_id,
-                value: *gain as f64 / 4.0,
+                value: (*gain as f64 / 4.0).clamp(0.0, 1.0),
             }),
             NoteEvent::PolyPan { pan, .. } => Some(NoteExpressionValueEvent {
                 type_id: PAN_EXPRESSION_ID,
@@ -184,7 +187,7 @@ impl NoteExpressionController {
             NoteEvent::PolyTuning { tuning, .. } => Some(NoteExpressionValueEvent {
                 type_id: TUNING_EXPRESSION_ID,
                 note_id,
-                value: (*tuning as f64 / 240.0) + 0.5,
+                value: ((*tuning as f64 / 240.0) + 0.5).clamp(0.0, 1.0),
             }),
             NoteEvent::PolyVibrato { vibrato, .. } => Some(NoteExpressionValueEvent {
                 type_id: VIBRATO_EXPRESSION_ID,
@@ -205,3 +208,164 @@ impl NoteExpressionController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrapper::clap::note_expressions as clap_note_expressions;
+
+    const TIMING: u32 = 5;
+
+    /// Register note ID 1 for key 60 on channel 2, matching the events from the CLAP tests.
+    fn controller() -> NoteExpressionController {
+        let mut controller = NoteExpressionController::default();
+        controller.register_note(&NoteOnEvent {
+            channel: 2,
+            pitch: 60,
+            tuning: 0.0,
+            velocity: 1.0,
+            length: 0,
+            note_id: 1,
+        });
+
+        controller
+    }
+
+    /// Polyphonic expression events paired with the VST3 note expression type ID and normalized
+    /// value they should be converted to. Polyphonic pressure uses a dedicated event type in VST3.
+    fn test_cases() -> [(NoteEvent<()>, u32, f64); 6] {
+        let voice_id = Some(1);
+        let channel = Some(2);
+        let note = Some(60);
+
+        [
+            (
+                NoteEvent::PolyVolume {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    gain: 2.0,
+                },
+                VOLUME_EXPRESSION_ID,
+                0.5,
+            ),
+            (
+                NoteEvent::PolyPan {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    pan: -0.5,
+                },
+                PAN_EXPRESSION_ID,
+                0.25,
+            ),
+            (
+                NoteEvent::PolyTuning {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    tuning: -30.0,
+                },
+                TUNING_EXPRESSION_ID,
+                0.375,
+            ),
+            (
+                NoteEvent::PolyVibrato {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    vibrato: 0.25,
+                },
+                VIBRATO_EXPRESSION_ID,
+                0.25,
+            ),
+            (
+                NoteEvent::PolyExpression {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    expression: 0.5,
+                },
+                EXPRESSION_EXPRESSION_ID,
+                0.5,
+            ),
+            (
+                NoteEvent::PolyBrightness {
+                    timing: TIMING,
+                    voice_id,
+                    channel,
+                    note,
+                    brightness: 0.125,
+                },
+                BRIGHTNESS_EXPRESSION_ID,
+                0.125,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_translate_event() {
+        let controller = controller();
+        for (expected, type_id, value) in test_cases() {
+            let event = NoteExpressionValueEvent {
+                type_id,
+                note_id: 1,
+                value,
+            };
+
+            assert_eq!(controller.translate_event(TIMING, &event), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_translate_event_reverse() {
+        for (event, type_id, value) in test_cases() {
+            let vst3_event = NoteExpressionController::translate_event_reverse(1, &event).unwrap();
+
+            assert_eq!(vst3_event.type_id, type_id);
+            assert_eq!(vst3_event.note_id, 1);
+            assert_eq!(vst3_event.value, value);
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_values() {
+        let event = NoteEvent::<()>::PolyTuning {
+            timing: TIMING,
+            voice_id: Some(1),
+            channel: Some(2),
+            note: Some(60),
+            tuning: 127.0,
+        };
+        let vst3_event = NoteExpressionController::translate_event_reverse(1, &event).unwrap();
+        assert_eq!(vst3_event.value, 1.0);
+    }
+
+    /// An expression sent by a plugin in one format should arrive at a plugin in the other format
+    /// with the same value.
+    #[test]
+    fn test_matches_clap() {
+        let controller = controller();
+        for (event, expression_id, value) in clap_note_expressions::tests::test_cases() {
+            // VST3 uses poly pressure events instead of a note expression
+            if matches!(event, NoteEvent::PolyPressure { .. }) {
+                continue;
+            }
+
+            let clap_event =
+                clap_note_expressions::translate_event_reverse(TIMING, &event).unwrap();
+            assert_eq!(clap_event.expression_id, expression_id);
+            assert_eq!(clap_event.value, value);
+            let from_clap = clap_note_expressions::translate_event::<()>(TIMING, &clap_event);
+            assert_eq!(from_clap, Some(event));
+
+            let vst3_event = NoteExpressionController::translate_event_reverse(1, &event).unwrap();
+            assert_eq!(controller.translate_event(TIMING, &vst3_event), Some(event));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Conversions between NIH-plug's [`ChannelLayout`]s and VST3 speaker arrangements.
+
+use vst3_sys::vst::SpeakerArrangement;
+
+use crate::prelude::{AmbisonicNormalization, AmbisonicOrdering, ChannelLayout};
+
+/// `kSpeakerACN0` through `kSpeakerACN3`. VST3 added the speakers for the other ambisonic channels
+/// later on, so they're not in a contiguous range.
+const FIRST_ACN_SPEAKERS_START: u32 = 20;
+/// `kSpeakerACN4` through `kSpeakerACN15`.
+const NEXT_ACN_SPEAKERS_START: u32 = 38;
+
+/// Get the VST3 speaker arrangement for a port with an optional channel layout. Ports without a
+/// channel layout get a made up arrangement based on their channel count. The
+/// [`Speaker`][crate::prelude::Speaker] discriminants match VST3's speaker bit positions, and VST3
+/// orders a bus' channels by those bits.
+pub fn speaker_arrangement(
+    channel_layout: Option<ChannelLayout>,
+    num_channels: u32,
+) -> SpeakerArrangement {
+    match channel_layout {
+        Some(ChannelLayout::Mono) => vst3_sys::vst::kMono,
+        Some(ChannelLayout::Stereo) => vst3_sys::vst::kStereo,
+        Some(ChannelLayout::Surround(speakers)) => {
+            speakers.iter().fold(0, |arrangement, speaker| {
+                arrangement | (1 << *speaker as u8)
+            })
+        }
+        Some(ChannelLayout::Ambisonic {
+            order,
+            ordering,
+            normalization,
+        }) => {
+            nih_debug_assert!(
+                ordering == AmbisonicOrdering::Acn && normalization == AmbisonicNormalization::Sn3d,
+                "VST3 only supports ambisonics with ACN ordering and SN3D normalization"
+            );
+
+            (0..(order + 1) * (order + 1)).fold(0, |arrangement, acn| {
+                let speaker_bit = if acn < 4 {
+                    FIRST_ACN_SPEAKERS_START + acn
+                } else {
+                    NEXT_ACN_SPEAKERS_START + (acn - 4)
+                };
+
+                arrangement | (1 << speaker_bit)
+            })
+        }
+        None => match num_channels {
+            0 => vst3_sys::vst::kEmpty,
+            1 => vst3_sys::vst::kMono,
+            2 => vst3_sys::vst::kStereo,
+            5 => vst3_sys::vst::k50,
+            6 => vst3_sys::vst::k51,
+            7 => vst3_sys::vst::k70Cine,
+            8 => vst3_sys::vst::k71Cine,
+            n => {
+                nih_debug_assert_failure!(
+                    "No defined layout for {} channels, making something up on the spot...",
+                    n
+                );
+                (1 << n) - 1
+            }
+        },
+    }
+}
+
+/// Whether a speaker arrangement proposed by the host in `IAudioProcessor::setBusArrangements()`
+/// can be used for a port. Surround and ambisonic ports need an exact match since the order of
+/// their channels matters. For other ports only the channel count needs to match.
+pub fn arrangement_matches(
+    arrangement: SpeakerArrangement,
+    channel_layout: Option<ChannelLayout>,
+    num_channels: u32,
+) -> bool {
+    match channel_layout {
+        Some(ChannelLayout::Surround(_) | ChannelLayout::Ambisonic { .. }) => {
+            arrangement == speaker_arrangement(channel_layout, num_channels)
+        }
+        _ => arrangement.count_ones() == num_channels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_surround_arrangements() {
+        // L, R, C, Lfe, Ls, Rs
+        assert_eq!(
+            speaker_arrangement(Some(ChannelLayout::SURROUND_5_1), 6),
+            vst3_sys::vst::k51
+        );
+        // VST3's `k71_4`: L, R, C, Lfe, Ls, Rs, Sl, Sr, Tfl, Tfr, Trl, Trr
+        assert_eq!(
+            speaker_arrangement(Some(ChannelLayout::SURROUND_7_1_4), 12),
+            0b10_1101_0110_0011_1111
+        );
+    }
+
+    #[test]
+    fn test_ambisonic_arrangements() {
+        assert_eq!(
+            speaker_arrangement(Some(ChannelLayout::AMBISONIC_FIRST_ORDER), 4),
+            0b1111 << 20
+        );
+
+        let third_order = speaker_arrangement(Some(ChannelLayout::AMBISONIC_THIRD_ORDER), 16);
+        assert_eq!(third_order.count_ones(), 16);
+        assert_eq!(third_order, (0b1111 << 20) | (0b1111_1111_1111 << 38));
+    }
+
+    #[test]
+    fn test_arrangement_matches() {
+        // Only the channel count matters for ports without a surround or ambisonic layout
+        assert!(arrangement_matches(vst3_sys::vst::kStereo, None, 2));
+        assert!(arrangement_matches(
+            vst3_sys::vst::kStereo,
+            Some(ChannelLayout::Stereo),
+            2
+        ));
+        assert!(arrangement_matches(vst3_sys::vst::k51, None, 6));
+
+        assert!(arrangement_matches(
+            vst3_sys::vst::k51,
+            Some(ChannelLayout::SURROUND_5_1),
+            6
+        ));
+        // L, R, C, Ls, Rs, Cs has the right number of channels but the wrong speakers
+        assert!(!arrangement_matches(
+            0b1_0011_0111,
+            Some(ChannelLayout::SURROUND_5_1),
+            6
+        ));
+    }
+}
@@ -18,6 +18,14 @@ pub const VST3_MIDI_NUM_PARAMS: u32 = VST3_MIDI_CCS * VST3_MIDI_CHANNELS;
 pub const VST3_MIDI_PARAMS_START: u32 = VST3_MIDI_PARAMS_END - VST3_MIDI_NUM_PARAMS;
 /// The (exclusive) end of the MIDI CC parameter range. Anything above this is reserved by the host.
 pub const VST3_MIDI_PARAMS_END: u32 = 1 << 31;
+/// VST3 only supports note names through program lists. If the plugin defines note names, then
+/// the root unit gets a program list with this ID containing a single program.
+pub const VST3_NOTE_NAMES_PROGRAM_LIST_ID: i32 = 0;
+/// The `IAttributeList` keys for the track's name and color passed to
+/// `IInfoListener::setChannelContextInfos()`. These are defined in `ivstchannelcontextinfo.h`,
+/// which vst3-sys doesn't expose.
+pub const VST3_CHANNEL_NAME_KEY: &[u8] = b"channel name\0";
+pub const VST3_CHANNEL_COLOR_KEY: &[u8] = b"channel color\0";
 
 /// Early exit out of a VST3 function when one of the passed pointers is null
 macro_rules! check_null_ptr {
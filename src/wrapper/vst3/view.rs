@@ -5,7 +5,9 @@ use std::ffi::{c_void, CStr};
 use std::mem;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use vst3_sys::base::{kInvalidArgument, kNotImplemented, kResultFalse, kResultOk, tresult, TBool};
+use vst3_sys::base::{
+    kInvalidArgument, kNotImplemented, kResultFalse, kResultOk, kResultTrue, tresult, TBool,
+};
 use vst3_sys::gui::{IPlugFrame, IPlugView, IPlugViewContentScaleSupport, ViewRect};
 use vst3_sys::utils::SharedVstPtr;
 use vst3_sys::VST3;
@@ -376,8 +378,8 @@ impl<P: Vst3Plugin> IPlugView for WrapperView<P> {
     unsafe fn on_size(&self, new_size: *mut ViewRect) -> tresult {
         check_null_ptr!(new_size);
 
-        // TODO: Implement Host->Plugin resizing
-        let (unscaled_width, unscaled_height) = self.editor.lock().size();
+        let editor = self.editor.lock();
+        let (unscaled_width, unscaled_height) = editor.size();
         let scaling_factor = self.scaling_factor.load(Ordering::Relaxed);
         let (editor_width, editor_height) = (
             (unscaled_width as f32 * scaling_factor).round() as i32,
@@ -387,6 +389,18 @@ impl<P: Vst3Plugin> IPlugView for WrapperView<P> {
         let width = (*new_size).right - (*new_size).left;
         let height = (*new_size).bottom - (*new_size).top;
         if width == editor_width && height == editor_height {
+            return kResultOk;
+        }
+
+        // The host has resized the window, and the editor needs to follow suit. The host should
+        // have already called `check_size_constraint()` with this size.
+        if width > 0
+            && height > 0
+            && editor.set_size(
+                (width as f32 / scaling_factor).round() as u32,
+                (height as f32 / scaling_factor).round() as u32,
+            )
+        {
             kResultOk
         } else {
             kResultFalse
@@ -425,19 +439,39 @@ impl<P: Vst3Plugin> IPlugView for WrapperView<P> {
     }
 
     unsafe fn can_resize(&self) -> tresult {
-        // TODO: Implement Host->Plugin resizing
-        kResultFalse
+        if self.editor.lock().resize_hints().is_some() {
+            kResultTrue
+        } else {
+            kResultFalse
+        }
     }
 
     unsafe fn check_size_constraint(&self, rect: *mut ViewRect) -> tresult {
         check_null_ptr!(rect);
 
-        // TODO: Implement Host->Plugin resizing
-        if (*rect).right - (*rect).left > 0 && (*rect).bottom - (*rect).top > 0 {
-            kResultOk
-        } else {
-            kResultFalse
+        let rect = &mut *rect;
+        let width = rect.right - rect.left;
+        let height = rect.bottom - rect.top;
+        if width <= 0 || height <= 0 {
+            return kResultFalse;
         }
+
+        // The rect is modified in place to the closest size the editor supports. Editors with a
+        // fixed size only support their current size.
+        let scaling_factor = self.scaling_factor.load(Ordering::Relaxed);
+        let (adjusted_width, adjusted_height) = {
+            let editor = self.editor.lock();
+            editor
+                .adjust_size(
+                    (width as f32 / scaling_factor).round() as u32,
+                    (height as f32 / scaling_factor).round() as u32,
+                )
+                .unwrap_or_else(|| editor.size())
+        };
+        rect.right = rect.left + (adjusted_width as f32 * scaling_factor).round() as i32;
+        rect.bottom = rect.top + (adjusted_height as f32 * scaling_factor).round() as i32;
+
+        kResultOk
     }
 }
 
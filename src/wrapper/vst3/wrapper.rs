@@ -1,5 +1,5 @@
 use std::borrow::Borrow;
-use std::ffi::c_void;
+use std::ffi::{c_char, c_void};
 use std::mem::{self, MaybeUninit};
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
@@ -10,29 +10,35 @@ use vst3_sys::base::{kInvalidArgument, kNoInterface, kResultFalse, kResultOk, tr
 use vst3_sys::base::{IBStream, IPluginBase};
 use vst3_sys::utils::SharedVstPtr;
 use vst3_sys::vst::{
-    kNoParamId, kNoParentUnitId, kNoProgramListId, kRootUnitId, Event, EventTypes, IAudioProcessor,
-    IComponent, IEditController, IEventList, IMidiMapping, INoteExpressionController,
-    IParamValueQueue, IParameterChanges, IProcessContextRequirements, IUnitInfo,
-    LegacyMidiCCOutEvent, NoteExpressionTypeInfo, NoteExpressionValueDescription, NoteOffEvent,
-    NoteOnEvent, ParameterFlags, PolyPressureEvent, ProgramListInfo, TChar, UnitInfo,
+    kNoParamId, kNoParentUnitId, kNoProgramListId, kRootUnitId, Event, EventTypes, IAttributeList,
+    IAudioProcessor, IComponent, IEditController, IEventList, IInfoListener, IMidiMapping,
+    INoteExpressionController, IParamValueQueue, IParameterChanges, IProcessContextRequirements,
+    IUnitInfo, LegacyMidiCCOutEvent, NoteExpressionTypeInfo, NoteExpressionValueDescription,
+    NoteOffEvent, NoteOnEvent, ParameterFlags, PolyPressureEvent, ProgramListInfo, TChar, UnitInfo,
 };
 use vst3_sys::VST3;
 use widestring::U16CStr;
 
 use super::inner::{ProcessEvent, WrapperInner};
 use super::note_expressions::{self, NoteExpressionController};
+use super::speakers;
 use super::util::{
     u16strlcpy, VstPtr, VST3_MIDI_CCS, VST3_MIDI_NUM_PARAMS, VST3_MIDI_PARAMS_START,
 };
-use super::util::{VST3_MIDI_CHANNELS, VST3_MIDI_PARAMS_END};
+use super::util::{
+    VST3_CHANNEL_COLOR_KEY, VST3_CHANNEL_NAME_KEY, VST3_MIDI_CHANNELS, VST3_MIDI_PARAMS_END,
+    VST3_NOTE_NAMES_PROGRAM_LIST_ID,
+};
 use super::view::WrapperView;
+use crate::midi::control_change;
 use crate::prelude::{
     AuxiliaryBuffers, BufferConfig, MidiConfig, NoteEvent, ParamFlags, ProcessMode, ProcessStatus,
-    SysExMessage, Transport, Vst3Plugin,
+    SysExMessage, TrackColor, TrackInfo, Transport, Vst3Plugin,
 };
 use crate::util::permit_alloc;
 use crate::wrapper::state;
-use crate::wrapper::util::buffer_management::{BufferManager, ChannelPointers};
+use crate::wrapper::util::audio_io_layouts::ProposedChannelCounts;
+use crate::wrapper::util::buffer_management::{AuxPortActivation, BufferManager, ChannelPointers};
 use crate::wrapper::util::{clamp_input_event_timing, clamp_output_event_timing, process_wrapper};
 
 // Alias needed for the VST3 attribute macro
@@ -45,7 +51,8 @@ use vst3_sys as vst3_com;
     IMidiMapping,
     INoteExpressionController,
     IProcessContextRequirements,
-    IUnitInfo
+    IUnitInfo,
+    IInfoListener
 ))]
 pub struct Wrapper<P: Vst3Plugin> {
     inner: Arc<WrapperInner<P>>,
@@ -308,12 +315,12 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
         type_: vst3_sys::vst::MediaType,
         dir: vst3_sys::vst::BusDirection,
         index: i32,
-        _state: vst3_sys::base::TBool,
+        state: vst3_sys::base::TBool,
     ) -> tresult {
         let current_audio_io_layout = self.inner.current_audio_io_layout.load();
 
-        // We don't support this, but the validator will get very angry with us if we let it know
-        // that
+        // The main busses are always active, but the activation state for the auxiliary busses is
+        // passed on to the plugin through `Buffer::is_active()`
         match (type_, dir, index) {
             (t, d, _)
                 if t == vst3_sys::vst::MediaTypes::kAudio as i32
@@ -326,7 +333,16 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
                 };
                 let aux_busses = current_audio_io_layout.aux_input_ports.len() as i32;
 
-                if (0..main_busses + aux_busses).contains(&index) {
+                if (main_busses..main_busses + aux_busses).contains(&index) {
+                    // The host may do this while the audio thread is processing audio, so this
+                    // only takes a shared borrow and stores the new state atomically
+                    self.inner
+                        .aux_port_activation
+                        .borrow()
+                        .set_input_active((index - main_busses) as usize, state != 0);
+
+                    kResultOk
+                } else if (0..main_busses).contains(&index) {
                     kResultOk
                 } else {
                     kInvalidArgument
@@ -343,7 +359,16 @@ impl<P: Vst3Plugin> IComponent for Wrapper<P> {
                 };
                 let aux_busses = current_audio_io_layout.aux_output_ports.len() as i32;
 
-                if (0..main_busses + aux_busses).contains(&index) {
+                if (main_busses..main_busses + aux_busses).contains(&index) {
+                    // The host may do this while the audio thread is processing audio, so this
+                    // only takes a shared borrow and stores the new state atomically
+                    self.inner
+                        .aux_port_activation
+                        .borrow()
+                        .set_output_active((index - main_busses) as usize, state != 0);
+
+                    kResultOk
+                } else if (0..main_busses).contains(&index) {
                     kResultOk
                 } else {
                     kInvalidArgument
@@ -716,8 +741,9 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
             return kInvalidArgument;
         }
 
-        // NIH-plug no longer supports flexible IO layouts. Instead we'll try to find an audio IO
-        // layout that matches the host's requested layout.
+        // We'll first try to find one of the plugin's static audio IO layouts that matches the
+        // host's requested layout. If there isn't one, the plugin gets to decide whether it can use
+        // the requested channel counts.
         let matching_layout = P::AUDIO_IO_LAYOUTS
             .iter()
             .find(|layout| {
@@ -738,44 +764,88 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                     return false;
                 }
 
-                // NOTE: We completely ignore the speaker arrangements and only look at the channel
-                //       counts here. This may cause issues at some point, but it works for now.
+                // Ports with a surround or ambisonic channel layout need to match the layout's
+                // speaker arrangement exactly. For all other ports only the channel count matters.
                 let has_main_input = layout.main_input_channels.is_some();
-                let aux_input_start_idx = if has_main_input { 0 } else { 1 };
+                let aux_input_start_idx = if has_main_input { 1 } else { 0 };
                 if has_main_input
-                    && (*inputs).count_ones() != layout.main_input_channels.unwrap().get()
+                    && !speakers::arrangement_matches(
+                        *inputs,
+                        layout.main_input_channel_layout(),
+                        layout.main_input_channels.unwrap().get(),
+                    )
                 {
                     return false;
                 }
                 for (aux_input_idx, channel_count) in layout.aux_input_ports.iter().enumerate() {
-                    if (*inputs.add(aux_input_idx + aux_input_start_idx)).count_ones()
-                        != channel_count.get()
-                    {
+                    if !speakers::arrangement_matches(
+                        *inputs.add(aux_input_idx + aux_input_start_idx),
+                        layout.aux_input_channel_layout(aux_input_idx),
+                        channel_count.get(),
+                    ) {
                         return false;
                     }
                 }
 
                 let has_main_output = layout.main_output_channels.is_some();
-                let aux_output_start_idx = if has_main_output { 0 } else { 1 };
-                if (*outputs).count_ones()
-                    != layout
-                        .main_output_channels
-                        .map(NonZeroU32::get)
-                        .unwrap_or_default()
+                let aux_output_start_idx = if has_main_output { 1 } else { 0 };
+                if has_main_output
+                    && !speakers::arrangement_matches(
+                        *outputs,
+                        layout.main_output_channel_layout(),
+                        layout.main_output_channels.unwrap().get(),
+                    )
                 {
                     return false;
                 }
                 for (aux_output_idx, channel_count) in layout.aux_output_ports.iter().enumerate() {
-                    if (*outputs.add(aux_output_idx + aux_output_start_idx)).count_ones()
-                        != channel_count.get()
-                    {
+                    if !speakers::arrangement_matches(
+                        *outputs.add(aux_output_idx + aux_output_start_idx),
+                        layout.aux_output_channel_layout(aux_output_idx),
+                        channel_count.get(),
+                    ) {
                         return false;
                     }
                 }
 
                 true
             })
-            .copied();
+            .copied()
+            .or_else(|| {
+                // If none of the static layouts match, then the plugin may still be able to use the
+                // host's channel counts. The number of busses needs to stay the same for this.
+                let current_audio_io_layout = self.inner.current_audio_io_layout.load();
+                let mut proposed =
+                    ProposedChannelCounts::for_audio_io_layout(&current_audio_io_layout);
+                let num_layout_ins =
+                    proposed.main_input.is_some() as usize + proposed.aux_inputs.len();
+                let num_layout_outs =
+                    proposed.main_output.is_some() as usize + proposed.aux_outputs.len();
+                if num_ins as usize != num_layout_ins || num_outs as usize != num_layout_outs {
+                    return None;
+                }
+
+                let mut input_arrangements =
+                    std::slice::from_raw_parts(inputs, num_ins as usize).iter();
+                for port_channels in proposed
+                    .main_input
+                    .iter_mut()
+                    .chain(proposed.aux_inputs.iter_mut())
+                {
+                    *port_channels = NonZeroU32::new(input_arrangements.next()?.count_ones())?;
+                }
+                let mut output_arrangements =
+                    std::slice::from_raw_parts(outputs, num_outs as usize).iter();
+                for port_channels in proposed
+                    .main_output
+                    .iter_mut()
+                    .chain(proposed.aux_outputs.iter_mut())
+                {
+                    *port_channels = NonZeroU32::new(output_arrangements.next()?.count_ones())?;
+                }
+
+                proposed.accept_runtime_layout(&*self.inner.plugin.lock(), &current_audio_io_layout)
+            });
 
         match matching_layout {
             Some(layout) => {
@@ -783,6 +853,13 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 // again
                 self.inner.current_audio_io_layout.store(layout);
 
+                // The number of busses normally can't change, but if it somehow does then the
+                // activation state no longer matches the layout's busses
+                let mut aux_port_activation = self.inner.aux_port_activation.borrow_mut();
+                if !aux_port_activation.matches_audio_io_layout(&layout) {
+                    *aux_port_activation = AuxPortActivation::for_audio_io_layout(&layout);
+                }
+
                 kResultOk
             }
             None => kResultFalse,
@@ -797,32 +874,21 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
     ) -> tresult {
         check_null_ptr!(arr);
 
-        let channel_count_to_map = |count| match count {
-            0 => vst3_sys::vst::kEmpty,
-            1 => vst3_sys::vst::kMono,
-            2 => vst3_sys::vst::kStereo,
-            5 => vst3_sys::vst::k50,
-            6 => vst3_sys::vst::k51,
-            7 => vst3_sys::vst::k70Cine,
-            8 => vst3_sys::vst::k71Cine,
-            n => {
-                nih_debug_assert_failure!(
-                    "No defined layout for {} channels, making something up on the spot...",
-                    n
-                );
-                (1 << n) - 1
-            }
-        };
-
         let current_audio_io_layout = self.inner.current_audio_io_layout.load();
-        let num_channels = if dir == vst3_sys::vst::BusDirections::kInput as i32 {
+        let (channel_layout, num_channels) = if dir == vst3_sys::vst::BusDirections::kInput as i32 {
             let has_main_input = current_audio_io_layout.main_input_channels.is_some();
             let aux_input_start_idx = if has_main_input { 1 } else { 0 };
             let aux_input_idx = (index - aux_input_start_idx).max(0) as usize;
             if index == 0 && has_main_input {
-                current_audio_io_layout.main_input_channels.unwrap().get()
+                (
+                    current_audio_io_layout.main_input_channel_layout(),
+                    current_audio_io_layout.main_input_channels.unwrap().get(),
+                )
             } else if aux_input_idx < current_audio_io_layout.aux_input_ports.len() {
-                current_audio_io_layout.aux_input_ports[aux_input_idx].get()
+                (
+                    current_audio_io_layout.aux_input_channel_layout(aux_input_idx),
+                    current_audio_io_layout.aux_input_ports[aux_input_idx].get(),
+                )
             } else {
                 return kInvalidArgument;
             }
@@ -831,16 +897,22 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
             let aux_output_start_idx = if has_main_output { 1 } else { 0 };
             let aux_output_idx = (index - aux_output_start_idx).max(0) as usize;
             if index == 0 && has_main_output {
-                current_audio_io_layout.main_output_channels.unwrap().get()
+                (
+                    current_audio_io_layout.main_output_channel_layout(),
+                    current_audio_io_layout.main_output_channels.unwrap().get(),
+                )
             } else if aux_output_idx < current_audio_io_layout.aux_output_ports.len() {
-                current_audio_io_layout.aux_output_ports[aux_output_idx].get()
+                (
+                    current_audio_io_layout.aux_output_channel_layout(aux_output_idx),
+                    current_audio_io_layout.aux_output_ports[aux_output_idx].get(),
+                )
             } else {
                 return kInvalidArgument;
             }
         } else {
             return kInvalidArgument;
         };
-        let channel_map = channel_count_to_map(num_channels);
+        let channel_map = speakers::speaker_arrangement(channel_layout, num_channels);
 
         nih_debug_assert_eq!(num_channels, channel_map.count_ones());
         *arr = channel_map;
@@ -889,6 +961,15 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
         };
         self.inner.current_process_mode.store(mode);
 
+        // The activation flags are sized here once so `activateBus()` and the audio thread never
+        // need to resize them. The host is not allowed to call this while processing audio.
+        let audio_io_layout = self.inner.current_audio_io_layout.load();
+        let mut aux_port_activation = self.inner.aux_port_activation.borrow_mut();
+        if !aux_port_activation.matches_audio_io_layout(&audio_io_layout) {
+            *aux_port_activation = AuxPortActivation::for_audio_io_layout(&audio_io_layout);
+        }
+        drop(aux_port_activation);
+
         // Initializing the plugin happens in `IAudioProcessor::set_active()` because the host may
         // still change the channel layouts at this point
 
@@ -921,6 +1002,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 }
             };
 
+            self.inner.mpe_translator.borrow_mut().reset();
             process_wrapper(|| plugin.reset());
         }
 
@@ -1109,8 +1191,8 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                 } else {
                                     None
                                 },
-                                channel: event.channel as u8,
-                                note: event.pitch as u8,
+                                channel: u8::try_from(event.channel).ok(),
+                                note: u8::try_from(event.pitch).ok(),
                                 velocity: event.velocity,
                             }));
                         } else if event.type_ == EventTypes::kPolyPressureEvent as u16 {
@@ -1122,8 +1204,8 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                 } else {
                                     None
                                 },
-                                channel: event.channel as u8,
-                                note: event.pitch as u8,
+                                channel: u8::try_from(event.channel).ok(),
+                                note: u8::try_from(event.pitch).ok(),
                                 pressure: event.pressure,
                             }));
                         } else if event.type_ == EventTypes::kNoteExpressionValueEvent as u16 {
@@ -1183,6 +1265,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 // The extra scope is here to make sure we release the borrow on input_events
                 {
                     let mut input_events = self.inner.input_events.borrow_mut();
+                    let mut mpe_translator = self.inner.mpe_translator.borrow_mut();
                     input_events.clear();
 
                     block_end = total_buffer_len;
@@ -1213,7 +1296,16 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                 // since we had to create the event object beforehand
                                 let mut event = event.clone();
                                 event.subtract_timing(block_start as u32);
-                                input_events.push_back(event);
+                                if P::MIDI_INPUT >= MidiConfig::Mpe {
+                                    // MPE controllers send their per-note expressions as MIDI CCs,
+                                    // which VST3 hosts send to us as parameter changes. Since the
+                                    // events are sorted at this point, they can be translated to
+                                    // polyphonic expressions here.
+                                    mpe_translator
+                                        .translate(event, |event| input_events.push_back(event));
+                                } else {
+                                    input_events.push_back(event);
+                                }
                             }
                         }
                     }
@@ -1231,6 +1323,8 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                     let mut buffer_manager = self.inner.buffer_manager.borrow_mut();
                     let buffers =
                         buffer_manager.create_buffers(block_start, block_len, |buffer_source| {
+                            self.inner.aux_port_activation.borrow().apply(buffer_source);
+
                             if data.num_outputs > 0
                                 && !data.outputs.is_null()
                                 && !(*data.outputs).buffers.is_null()
@@ -1418,7 +1512,12 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 // Send any events output by the plugin during the process cycle
                 if let Some(events) = data.output_events.upgrade() {
                     let mut output_events = self.inner.output_events.borrow_mut();
-                    while let Some(event) = output_events.pop_front() {
+                    // VST3 cannot target all channels or keys at once, so events using wildcards
+                    // are sent once for every matching note or channel instead
+                    for event in output_events
+                        .drain(..)
+                        .flat_map(NoteEvent::expand_wildcards)
+                    {
                         // We'll set the correct variant on this struct, or skip to the next loop
                         // iteration if we don't handle the event type
                         let mut vst3_event: Event = mem::zeroed();
@@ -1462,11 +1561,11 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                             } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
                                 vst3_event.type_ = EventTypes::kNoteOffEvent as u16;
                                 vst3_event.event.note_off = NoteOffEvent {
-                                    channel: channel as i16,
-                                    pitch: note as i16,
+                                    channel: channel.map_or(-1, |channel| channel as i16),
+                                    pitch: note.map_or(-1, |note| note as i16),
                                     velocity,
                                     note_id: voice_id
-                                        .unwrap_or_else(|| ((channel as i32) << 8) | note as i32),
+                                        .unwrap_or_else(|| fallback_note_id(channel, note)),
                                     tuning: 0.0,
                                 };
                             }
@@ -1487,10 +1586,10 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                             } if P::MIDI_OUTPUT >= MidiConfig::Basic => {
                                 vst3_event.type_ = EventTypes::kPolyPressureEvent as u16;
                                 vst3_event.event.poly_pressure = PolyPressureEvent {
-                                    channel: channel as i16,
-                                    pitch: note as i16,
+                                    channel: channel.map_or(-1, |channel| channel as i16),
+                                    pitch: note.map_or(-1, |note| note as i16),
                                     note_id: voice_id
-                                        .unwrap_or_else(|| ((channel as i32) << 8) | note as i32),
+                                        .unwrap_or_else(|| fallback_note_id(channel, note)),
                                     pressure,
                                 };
                             }
@@ -1531,8 +1630,7 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                 ..
                             }) if P::MIDI_OUTPUT >= MidiConfig::Basic => {
                                 match NoteExpressionController::translate_event_reverse(
-                                    voice_id
-                                        .unwrap_or_else(|| ((channel as i32) << 8) | note as i32),
+                                    voice_id.unwrap_or_else(|| fallback_note_id(channel, note)),
                                     event,
                                 ) {
                                     Some(translated_event) => {
@@ -1575,12 +1673,21 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                                     value2: ((scaled >> 7) & 0b01111111) as i8,
                                 };
                             }
+                            // Wildcard note offs and chokes are sent as all notes off and all
+                            // sound off messages, so those are also needed for basic MIDI output
                             NoteEvent::MidiCC {
                                 timing: _,
                                 channel,
                                 cc,
                                 value,
-                            } if P::MIDI_OUTPUT >= MidiConfig::MidiCCs => {
+                            } if P::MIDI_OUTPUT >= MidiConfig::MidiCCs
+                                || (P::MIDI_OUTPUT >= MidiConfig::Basic
+                                    && matches!(
+                                        cc,
+                                        control_change::ALL_NOTES_OFF
+                                            | control_change::ALL_SOUND_OFF
+                                    )) =>
+                            {
                                 vst3_event.type_ = EventTypes::kLegacyMIDICCOutEvent as u16;
                                 vst3_event.event.legacy_midi_cc_out = LegacyMidiCCOutEvent {
                                     control_number: cc,
@@ -1646,25 +1753,13 @@ impl<P: Vst3Plugin> IAudioProcessor for Wrapper<P> {
                 }
             };
 
-            // After processing audio, we'll check if the editor has sent us updated plugin state.
-            // We'll restore that here on the audio thread to prevent changing the values during the
-            // process call and also to prevent inconsistent state when the host also wants to load
-            // plugin state.
-            // FIXME: Zero capacity channels allocate on receiving, find a better alternative that
-            //        doesn't do that
-            let updated_state = permit_alloc(|| self.inner.updated_state_receiver.try_recv());
-            if let Ok(mut state) = updated_state {
-                self.inner.set_state_inner(&mut state);
-
-                // We'll pass the state object back to the GUI thread so deallocation can happen
-                // there without potentially blocking the audio thread
-                if let Err(err) = self.inner.updated_state_sender.send(state) {
-                    nih_debug_assert_failure!(
-                        "Failed to send state object back to GUI thread: {}",
-                        err
-                    );
-                };
-            }
+            // After processing audio, we'll check if the host or the editor has loaded new plugin
+            // state. The state has already been prepared on the thread that loaded it, so all
+            // that's left to do here is to swap it in. Doing this at the end of the process call
+            // prevents changing the values in the middle of processing.
+            self.inner.state_handoff.apply_pending(|prepared| {
+                self.inner.apply_prepared_state_on_audio_thread(prepared)
+            });
 
             result
         })
@@ -1794,12 +1889,29 @@ impl<P: Vst3Plugin> IProcessContextRequirements for Wrapper<P> {
 
 impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
     unsafe fn get_unit_count(&self) -> i32 {
-        self.inner.param_units.len() as i32
+        // The root unit is only listed when it needs a program list for the plugin's note names
+        if self.inner.has_note_names() {
+            self.inner.param_units.len() as i32 + 1
+        } else {
+            self.inner.param_units.len() as i32
+        }
     }
 
     unsafe fn get_unit_info(&self, unit_index: i32, info: *mut UnitInfo) -> tresult {
         check_null_ptr!(info);
 
+        if unit_index == self.inner.param_units.len() as i32 && self.inner.has_note_names() {
+            *info = mem::zeroed();
+
+            let info = &mut *info;
+            info.id = kRootUnitId;
+            info.parent_unit_id = kNoParentUnitId;
+            u16strlcpy(&mut info.name, P::NAME);
+            info.program_list_id = VST3_NOTE_NAMES_PROGRAM_LIST_ID;
+
+            return kResultOk;
+        }
+
         match self.inner.param_units.info(unit_index as usize) {
             Some((unit_id, unit_info)) => {
                 *info = mem::zeroed();
@@ -1817,25 +1929,44 @@ impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
     }
 
     unsafe fn get_program_list_count(&self) -> i32 {
-        // TODO: Do we want program lists? Probably not, CLAP doesn't even support them.
-        0
+        // We don't use program lists for presets since CLAP doesn't support them, but VST3 only
+        // supports note names through a program list
+        if self.inner.has_note_names() {
+            1
+        } else {
+            0
+        }
     }
 
-    unsafe fn get_program_list_info(
-        &self,
-        _list_index: i32,
-        _info: *mut ProgramListInfo,
-    ) -> tresult {
-        kInvalidArgument
+    unsafe fn get_program_list_info(&self, list_index: i32, info: *mut ProgramListInfo) -> tresult {
+        check_null_ptr!(info);
+
+        if list_index != 0 || !self.inner.has_note_names() {
+            return kInvalidArgument;
+        }
+
+        *info = mem::zeroed();
+
+        let info = &mut *info;
+        info.id = VST3_NOTE_NAMES_PROGRAM_LIST_ID;
+        u16strlcpy(&mut info.name, "Note names");
+        info.program_count = 1;
+
+        kResultOk
     }
 
-    unsafe fn get_program_name(
-        &self,
-        _list_id: i32,
-        _program_index: i32,
-        _name: *mut u16,
-    ) -> tresult {
-        kInvalidArgument
+    unsafe fn get_program_name(&self, list_id: i32, program_index: i32, name: *mut u16) -> tresult {
+        check_null_ptr!(name);
+
+        if list_id != VST3_NOTE_NAMES_PROGRAM_LIST_ID || program_index != 0 {
+            return kInvalidArgument;
+        }
+
+        // This is a `String128`
+        let name = std::slice::from_raw_parts_mut(name as *mut TChar, 128);
+        u16strlcpy(name, P::NAME);
+
+        kResultOk
     }
 
     unsafe fn get_program_info(
@@ -1848,19 +1979,41 @@ impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
         kInvalidArgument
     }
 
-    unsafe fn has_program_pitch_names(&self, _id: i32, _index: i32) -> tresult {
-        // TODO: Support note names once someone requests it
-        kInvalidArgument
+    unsafe fn has_program_pitch_names(&self, id: i32, index: i32) -> tresult {
+        if id == VST3_NOTE_NAMES_PROGRAM_LIST_ID && index == 0 && self.inner.has_note_names() {
+            kResultOk
+        } else {
+            kResultFalse
+        }
     }
 
     unsafe fn get_program_pitch_name(
         &self,
-        _id: i32,
-        _index: i32,
-        _pitch: i16,
-        _name: *mut u16,
+        id: i32,
+        index: i32,
+        pitch: i16,
+        name: *mut u16,
     ) -> tresult {
-        kInvalidArgument
+        check_null_ptr!(name);
+
+        if id != VST3_NOTE_NAMES_PROGRAM_LIST_ID || index != 0 || !(0..128).contains(&pitch) {
+            return kInvalidArgument;
+        }
+
+        let note_name = self
+            .inner
+            .note_names
+            .first_channel_name(&self.inner.plugin, pitch as u8);
+        match note_name {
+            Some(note_name) => {
+                // This is also a `String128`
+                let name = std::slice::from_raw_parts_mut(name as *mut TChar, 128);
+                u16strlcpy(name, &note_name);
+
+                kResultOk
+            }
+            None => kResultFalse,
+        }
     }
 
     unsafe fn get_selected_unit(&self) -> i32 {
@@ -1893,3 +2046,57 @@ impl<P: Vst3Plugin> IUnitInfo for Wrapper<P> {
         kInvalidArgument
     }
 }
+
+/// The note ID used for output events that don't have a voice ID. Wildcards will have already been
+/// expanded for events without voice IDs, so the channel and note are always set for those.
+fn fallback_note_id(channel: Option<u8>, note: Option<u8>) -> i32 {
+    ((channel.unwrap_or_default() as i32) << 8) | note.unwrap_or_default() as i32
+}
+
+impl<P: Vst3Plugin> IInfoListener for Wrapper<P> {
+    unsafe fn set_channel_context_infos(&self, list: SharedVstPtr<dyn IAttributeList>) -> tresult {
+        check_null_ptr!(list);
+
+        let list = list.upgrade().unwrap();
+
+        // VST3 only provides the track's name and color. The size is in bytes, not in characters.
+        let mut name_buffer: [TChar; 256] = [0; 256];
+        let name = if list.get_string(
+            VST3_CHANNEL_NAME_KEY.as_ptr() as *const c_char,
+            name_buffer.as_mut_ptr(),
+            mem::size_of_val(&name_buffer) as u32,
+        ) == kResultOk
+        {
+            // Just in case the host doesn't null terminate the string
+            *name_buffer.last_mut().unwrap() = 0;
+            Some(U16CStr::from_ptr_str(name_buffer.as_ptr() as *const u16).to_string_lossy())
+        } else {
+            None
+        };
+
+        // The color is stored as a 32-bit ARGB value
+        let mut color: i64 = 0;
+        let color = if list.get_int(VST3_CHANNEL_COLOR_KEY.as_ptr() as *const c_char, &mut color)
+            == kResultOk
+        {
+            Some(TrackColor {
+                red: (color >> 16) as u8,
+                green: (color >> 8) as u8,
+                blue: color as u8,
+                alpha: (color >> 24) as u8,
+            })
+        } else {
+            None
+        };
+
+        let track_info = TrackInfo {
+            name,
+            color,
+            ..TrackInfo::default()
+        };
+        *self.inner.track_info.lock() = Some(track_info.clone());
+        self.inner.plugin.lock().track_info_changed(&track_info);
+
+        kResultOk
+    }
+}